@@ -0,0 +1,101 @@
+// Copyright 2019 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use anyhow::anyhow;
+use exonum::runtime::SnapshotExt;
+use exonum_merkledb::{access::Access, Database, DbOptions, RocksDB};
+use structopt::StructOpt;
+
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::PathBuf,
+};
+
+use exonum_btc_anchoring::blockchain::Schema;
+
+/// BTC anchoring chain replay tool
+///
+/// Re-derives every historical anchoring transaction from the node database
+/// and prints JSON report with the transactions that do not match the recorded ones.
+#[derive(StructOpt)]
+struct Opts {
+    /// Path to the node database.
+    #[structopt(long, short = "d")]
+    db_path: PathBuf,
+    /// Name of the anchoring service instance.
+    #[structopt(long, short = "i", default_value = "anchoring")]
+    instance_name: String,
+    /// Number of threads used to replay the anchoring chain. All of the available cores
+    /// are used by default.
+    #[structopt(long, short = "j")]
+    threads: Option<usize>,
+    /// Path to the JSON file to export the anchored blocks to.
+    #[structopt(long)]
+    export: Option<PathBuf>,
+}
+
+/// Writes the anchored blocks as a JSON array one record at a time, so the memory
+/// consumed by the export does not depend on the length of the anchoring chain.
+fn export_anchors(schema: &Schema<impl Access>, mut writer: impl Write) -> anyhow::Result<()> {
+    writer.write_all(b"[")?;
+    for (i, record) in schema.anchor_records(0).enumerate() {
+        if i > 0 {
+            writer.write_all(b",")?;
+        }
+        writer.write_all(b"\n  ")?;
+        serde_json::to_writer(&mut writer, &record)?;
+    }
+    writer.write_all(b"\n]\n")?;
+    writer.flush()?;
+    Ok(())
+}
+
+fn main() -> anyhow::Result<()> {
+    let opts = Opts::from_args();
+    if let Some(threads) = opts.threads {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build_global()?;
+    }
+
+    let db = RocksDB::open(opts.db_path, &DbOptions::default())?;
+    let snapshot = db.snapshot();
+    let access = snapshot
+        .for_service(opts.instance_name.as_str())
+        .ok_or_else(|| {
+            anyhow!(
+                "Anchoring service instance `{}` is absent in the database",
+                opts.instance_name
+            )
+        })?;
+
+    let schema = Schema::new(access);
+    if let Some(path) = opts.export {
+        export_anchors(&schema, BufWriter::new(File::create(path)?))?;
+    }
+
+    let scan = schema.chain_scan(snapshot.for_core());
+
+    let report = scan.replay();
+    println!("{}", serde_json::to_string_pretty(&report)?);
+    if report.is_consistent() {
+        Ok(())
+    } else {
+        Err(anyhow!(
+            "Found {} mismatched anchoring transactions",
+            report.mismatches.len()
+        ))
+    }
+}
@@ -0,0 +1,150 @@
+// Copyright 2019 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+use anyhow::ensure;
+use hex::FromHex;
+use serde_derive::{Deserialize, Serialize};
+use structopt::StructOpt;
+
+use std::{fs, path::PathBuf};
+
+use exonum_btc_anchoring::{btc, config::Config as AnchoringConfig};
+
+/// BTC anchoring rescue tool
+///
+/// Spends the output of the latest anchoring transaction to the given address without
+/// the Exonum network. Each anchoring node signs the rescue transaction offline with
+/// its Bitcoin key, then anyone assembles the raw transaction from the quorum of the
+/// signatures. The nodes should use the same parameters, otherwise the signatures
+/// do not match.
+#[derive(StructOpt)]
+enum Commands {
+    /// Sign the rescue transaction with the Bitcoin key of the anchoring node and print
+    /// the signature in the JSON format.
+    Sign(SignCommand),
+    /// Assemble the rescue transaction from the signatures of the anchoring nodes and
+    /// print it in the hex format.
+    Assemble(AssembleCommand),
+}
+
+/// Parameters of the rescue transaction.
+#[derive(StructOpt)]
+struct RescueParams {
+    /// Path to the actual anchoring configuration in the JSON format.
+    #[structopt(long)]
+    anchoring_config: PathBuf,
+    /// Hex of the latest anchoring transaction.
+    #[structopt(long)]
+    anchoring_tx: String,
+    /// Address to which the funds are sent.
+    #[structopt(long)]
+    destination: btc::Address,
+    /// Fee per byte in satoshis.
+    #[structopt(long)]
+    fee: u64,
+}
+
+#[derive(StructOpt)]
+struct SignCommand {
+    #[structopt(flatten)]
+    params: RescueParams,
+    /// Bitcoin private key of the anchoring node in the WIF format.
+    #[structopt(long)]
+    private_key: btc::PrivateKey,
+}
+
+#[derive(StructOpt)]
+struct AssembleCommand {
+    #[structopt(flatten)]
+    params: RescueParams,
+    /// Paths to the files with the signatures printed by the `sign` command.
+    #[structopt(long = "signature", required = true)]
+    signatures: Vec<PathBuf>,
+}
+
+/// Signature of the rescue transaction made by the anchoring node.
+#[derive(Debug, Serialize, Deserialize)]
+struct RescueSignature {
+    bitcoin_key: btc::PublicKey,
+    signature: btc::InputSignature,
+}
+
+impl RescueParams {
+    fn rescue_transaction(&self) -> anyhow::Result<btc::RescueTransaction> {
+        let config: AnchoringConfig =
+            serde_json::from_str(&fs::read_to_string(&self.anchoring_config)?)?;
+        ensure!(
+            self.destination.0.network == config.network,
+            "Destination address belongs to the {} network, but the anchoring uses the {} one",
+            self.destination.0.network,
+            config.network
+        );
+        let anchoring_tx = btc::Transaction::from_hex(&self.anchoring_tx)?;
+        let rescue = btc::RescueTransaction::new(
+            config.redeem_script(),
+            anchoring_tx,
+            &self.destination,
+            self.fee,
+        )?;
+        log::info!(
+            "Rescue transaction {} spends {} satoshis",
+            rescue.unsigned_transaction().id(),
+            rescue.unsigned_transaction().0.output[0].value
+        );
+        Ok(rescue)
+    }
+}
+
+impl SignCommand {
+    fn run(self) -> anyhow::Result<()> {
+        let rescue = self.params.rescue_transaction()?;
+        let signature = RescueSignature {
+            bitcoin_key: btc::PublicKey(
+                self.private_key
+                    .0
+                    .public_key(&bitcoin::secp256k1::Secp256k1::new()),
+            ),
+            signature: rescue.sign(&self.private_key)?,
+        };
+        println!("{}", serde_json::to_string_pretty(&signature)?);
+        Ok(())
+    }
+}
+
+impl AssembleCommand {
+    fn run(self) -> anyhow::Result<()> {
+        let rescue = self.params.rescue_transaction()?;
+        let signatures = self
+            .signatures
+            .iter()
+            .map(|path| {
+                let signature: RescueSignature = serde_json::from_str(&fs::read_to_string(path)?)?;
+                Ok((signature.bitcoin_key, signature.signature))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let transaction = rescue.finalize(signatures)?;
+        println!(
+            "{}",
+            bitcoin::consensus::encode::serialize_hex(&transaction.0)
+        );
+        Ok(())
+    }
+}
+
+fn main() -> anyhow::Result<()> {
+    exonum::helpers::init_logger()?;
+    match Commands::from_args() {
+        Commands::Sign(cmd) => cmd.run(),
+        Commands::Assemble(cmd) => cmd.run(),
+    }
+}
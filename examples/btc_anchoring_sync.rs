@@ -12,24 +12,34 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use anyhow::{anyhow, bail};
-use async_trait::async_trait;
-use bitcoincore_rpc::{Auth as BitcoinRpcAuth, Client as BitcoinRpcClient};
-use exonum::crypto::Hash;
+use anyhow::{anyhow, bail, ensure};
+use bitcoincore_rpc::{Auth as BitcoinRpcAuth, RpcApi};
+use exonum::crypto::PublicKey;
+#[cfg(feature = "esplora")]
+use exonum_btc_anchoring::sync::{EsploraConfig, EsploraRelay, ProxyError};
+#[cfg(feature = "zmq")]
+use exonum_btc_anchoring::sync::{ZmqError, ZmqSubscriber, ZmqTopic};
 use exonum_btc_anchoring::{
-    api::{AnchoringChainLength, AnchoringProposalState, IndexQuery, PrivateApi},
-    blockchain::SignInput,
+    api::{AnchoringProposalState, PrivateApi},
+    blockchain::{SubmitAnchoringKey, UpdateParameters},
     btc,
-    config::Config as AnchoringConfig,
-    sync::{AnchoringChainUpdateTask, ChainUpdateError, SyncWithBitcoinError, SyncWithBitcoinTask},
+    client::{verify_transaction_proof, ApiClient, ConsensusHealthPolicy, ConsensusStatus},
+    config::{AnchoringKeys, Config as AnchoringConfig, KeyPossessionProof},
+    sync::{
+        AnchoringChainUpdateTask, BitcoinRelay, BitcoinRpcClient, BitcoinRpcRelay, CachedRelay,
+        ChainQuery, ChainUpdateError, FeePolicy, NodeRole, ProxyConfig, RehearsalMirror,
+        RejectionKind, RelayError, RetryPolicy, RpcTimeouts, ShutdownSignal, SignatureAuditTask,
+        SignatureVerification, SigningMarker, SyncWithBitcoinError, SyncWithBitcoinTask, TlsConfig,
+        TransactionStatus, TransitionSchedule, WalletOps, WatchOnlyToggle,
+    },
 };
-use serde::{de::DeserializeOwned, ser::Serialize};
+use futures::{channel::mpsc, future, StreamExt};
 use serde_derive::{Deserialize, Serialize};
 use structopt::StructOpt;
 use tokio::time::delay_for;
 
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     convert::TryFrom,
     fs::{self, File},
     io::prelude::*,
@@ -37,111 +47,16 @@ use std::{
     time::Duration,
 };
 
-/// Client implementation for the API of the anchoring service instance.
-#[derive(Debug, Clone)]
-pub struct ApiClient {
-    /// Complete prefix with the port and the anchoring instance name.
-    prefix: String,
-    /// Underlying HTTP client.
-    client: reqwest::Client,
-}
-
-impl ApiClient {
-    /// Create a new anchoring API relay with the specified host and name of instance.
-    /// Hostname should be in form `{http|https}://{address}:{port}`.
-    pub fn new(hostname: impl AsRef<str>, instance_name: impl AsRef<str>) -> Self {
-        Self {
-            prefix: format!(
-                "{}/api/services/{}",
-                hostname.as_ref(),
-                instance_name.as_ref()
-            ),
-            client: reqwest::Client::new(),
-        }
-    }
-
-    fn endpoint(&self, name: impl AsRef<str>) -> String {
-        format!("{}/{}", self.prefix, name.as_ref())
-    }
-
-    async fn get<R>(&self, endpoint: &str) -> Result<R, reqwest::Error>
-    where
-        R: DeserializeOwned + Send + 'static,
-    {
-        self.client
-            .get(&self.endpoint(endpoint))
-            .send()
-            .await?
-            .json()
-            .await
-    }
-
-    async fn get_query<Q, R>(&self, endpoint: &str, query: &Q) -> Result<R, reqwest::Error>
-    where
-        Q: Serialize,
-        R: DeserializeOwned + Send + 'static,
-    {
-        self.client
-            .get(&self.endpoint(endpoint))
-            .query(query)
-            .send()
-            .await?
-            .json()
-            .await
-    }
-
-    async fn post<Q, R>(&self, endpoint: &str, body: &Q) -> Result<R, reqwest::Error>
-    where
-        Q: Serialize,
-        R: DeserializeOwned + Send + 'static,
-    {
-        self.client
-            .post(&self.endpoint(endpoint))
-            .json(&body)
-            .send()
-            .await?
-            .json()
-            .await
-    }
-}
-
-#[async_trait]
-impl PrivateApi for ApiClient {
-    type Error = reqwest::Error;
-
-    async fn sign_input(&self, sign_input: SignInput) -> Result<Hash, Self::Error> {
-        self.post("sign-input", &sign_input).await
-    }
-
-    async fn add_funds(&self, transaction: btc::Transaction) -> Result<Hash, Self::Error> {
-        self.post("add-funds", &transaction).await
-    }
-
-    async fn anchoring_proposal(&self) -> Result<AnchoringProposalState, Self::Error> {
-        self.get("anchoring-proposal").await
-    }
-
-    async fn config(&self) -> Result<AnchoringConfig, Self::Error> {
-        self.get("config").await
-    }
-
-    async fn transaction_with_index(
-        &self,
-        index: u64,
-    ) -> Result<Option<btc::Transaction>, Self::Error> {
-        self.get_query("transaction", &IndexQuery { index }).await
-    }
-
-    async fn transactions_count(&self) -> Result<AnchoringChainLength, Self::Error> {
-        self.get("transactions-count").await
-    }
-}
+/// Number of the latest anchoring transactions reconciled with the Bitcoin blockchain
+/// on startup by default.
+const DEFAULT_RECONCILIATION_DEPTH: u64 = 10;
 
 /// Generate initial configuration for the btc anchoring sync utility.
 #[derive(Debug, StructOpt)]
 struct GenerateConfigCommand {
     /// Path to a sync utility configuration file which will be created after
-    /// running this command.
+    /// running this command. The file is written in the JSON format if it has
+    /// the `json` extension, otherwise in the TOML one.
     #[structopt(long, short = "o", default_value = "btc_anchoring_sync.toml")]
     output: PathBuf,
     /// Anchoring node private API url address.
@@ -162,6 +77,16 @@ struct GenerateConfigCommand {
     /// Bitcoin RPC password.
     #[structopt(long)]
     bitcoin_rpc_password: Option<String>,
+    /// Path to the RPC cookie file of the Bitcoin node, which is used instead of
+    /// the username and password.
+    #[structopt(long)]
+    bitcoin_rpc_cookie: Option<PathBuf>,
+    /// Name of the Bitcoin node wallet, which watches the anchoring addresses.
+    #[structopt(long)]
+    bitcoin_rpc_wallet: Option<String>,
+    /// Sign anchoring transactions, but never send them to the Bitcoin network.
+    #[structopt(long)]
+    dry_run: bool,
 }
 
 #[derive(Debug, StructOpt)]
@@ -169,6 +94,50 @@ struct RunCommand {
     /// Path to a sync utility configuration file.
     #[structopt(long, short = "c")]
     config: PathBuf,
+    #[structopt(flatten)]
+    overlay: ConfigOverlay,
+}
+
+/// Checks that the local setup of the sync utility is ready for anchoring.
+#[derive(Debug, StructOpt)]
+struct DoctorCommand {
+    /// Path to a sync utility configuration file.
+    #[structopt(long, short = "c")]
+    config: PathBuf,
+    #[structopt(flatten)]
+    overlay: ConfigOverlay,
+}
+
+/// Overrides of the sync utility configuration file values. The values given
+/// by the command line flags take precedence over the environment variables.
+#[derive(Debug, StructOpt)]
+struct ConfigOverlay {
+    /// Anchoring node private API url address.
+    #[structopt(long, env = "BTC_ANCHORING_EXONUM_PRIVATE_API")]
+    exonum_private_api: Option<String>,
+    /// Bitcoin RPC url.
+    #[structopt(long, env = "BTC_ANCHORING_BITCOIN_RPC_HOST")]
+    bitcoin_rpc_host: Option<String>,
+    /// Bitcoin RPC username.
+    #[structopt(long, env = "BTC_ANCHORING_BITCOIN_RPC_USER")]
+    bitcoin_rpc_user: Option<String>,
+    /// Bitcoin RPC password.
+    #[structopt(
+        long,
+        env = "BTC_ANCHORING_BITCOIN_RPC_PASSWORD",
+        hide_env_values = true
+    )]
+    bitcoin_rpc_password: Option<String>,
+    /// Path to the RPC cookie file of the Bitcoin node.
+    #[structopt(long, env = "BTC_ANCHORING_BITCOIN_RPC_COOKIE")]
+    bitcoin_rpc_cookie: Option<PathBuf>,
+    /// Name of the Bitcoin node wallet.
+    #[structopt(long, env = "BTC_ANCHORING_BITCOIN_RPC_WALLET")]
+    bitcoin_rpc_wallet: Option<String>,
+    /// Path to a file with the Bitcoin private keys in the WIF format (one per line),
+    /// which are added to the key pool.
+    #[structopt(long, env = "BTC_ANCHORING_BITCOIN_KEYS_FILE")]
+    bitcoin_keys_file: Option<PathBuf>,
 }
 
 /// Generates a new Bitcoin key pair and add them to the key pool of the specified
@@ -180,6 +149,201 @@ struct GenerateKeypairCommand {
     config: PathBuf,
 }
 
+/// Submits the Bitcoin key of the validator, which joins the anchoring.
+#[derive(Debug, StructOpt)]
+struct OnboardCommand {
+    /// Path to a sync utility configuration file.
+    #[structopt(long, short = "c")]
+    config: PathBuf,
+    /// Bitcoin public key from the key pool to submit. May be omitted if the key pool
+    /// contains a single key which is not used in the anchoring configuration.
+    #[structopt(long, short = "k")]
+    bitcoin_key: Option<btc::PublicKey>,
+    /// Service key of the validator, to which the possession of the Bitcoin key is proved.
+    #[structopt(long, short = "s")]
+    service_key: PublicKey,
+}
+
+/// Approves the Bitcoin key submitted by the validator, which joins the anchoring,
+/// on behalf of this anchoring node.
+#[derive(Debug, StructOpt)]
+struct ApproveKeyCommand {
+    /// Path to a sync utility configuration file.
+    #[structopt(long, short = "c")]
+    config: PathBuf,
+    /// Service key of the joining validator.
+    #[structopt(long, short = "s")]
+    service_key: PublicKey,
+}
+
+/// Proposes the new anchoring interval or transaction fee on behalf of this anchoring node.
+///
+/// The parameters are applied once 2/3+1 anchoring nodes have proposed the same values.
+#[derive(Debug, StructOpt)]
+struct UpdateParametersCommand {
+    /// Path to a sync utility configuration file.
+    #[structopt(long, short = "c")]
+    config: PathBuf,
+    /// New interval in blocks between anchored blocks.
+    #[structopt(long)]
+    anchoring_interval: Option<u64>,
+    /// New fee per byte in satoshis.
+    #[structopt(long)]
+    transaction_fee: Option<u64>,
+}
+
+/// Adds the proofs of possession of the Bitcoin keys from the key pool to the proposed
+/// anchoring configuration.
+///
+/// Each operator whose key is added by the configuration runs this command on the same
+/// configuration file before the configuration is proposed.
+#[derive(Debug, StructOpt)]
+struct ProveKeyPossessionCommand {
+    /// Path to a sync utility configuration file.
+    #[structopt(long, short = "c")]
+    config: PathBuf,
+    /// Path to the proposed anchoring configuration in the JSON format.
+    #[structopt(long, short = "a")]
+    anchoring_config: PathBuf,
+}
+
+/// Splits the Bitcoin private key from the key pool into the Shamir shares and writes
+/// each share to a separate file, so that the key is never stored as a single file
+/// outside the key pool. The shares are encrypted by the passphrase from the specified
+/// environment variable, and the share files are accessible only by their owner.
+#[derive(Debug, StructOpt)]
+struct ExportKeySharesCommand {
+    /// Path to a sync utility configuration file.
+    #[structopt(long, short = "c")]
+    config: PathBuf,
+    /// Bitcoin public key from the key pool to split. May be omitted if the key pool
+    /// contains a single key.
+    #[structopt(long, short = "k")]
+    bitcoin_key: Option<btc::PublicKey>,
+    /// Number of shares required to restore the key.
+    #[structopt(long, short = "t")]
+    threshold: u8,
+    /// Total number of shares.
+    #[structopt(long, short = "n")]
+    shares: u8,
+    /// Directory, to which the share files are written.
+    #[structopt(long, short = "o")]
+    output_dir: PathBuf,
+    /// Environment variable with the passphrase, which encrypts the shares.
+    #[structopt(long, default_value = "BTC_ANCHORING_SHARES_PASSPHRASE")]
+    passphrase_env: String,
+}
+
+/// Restores the Bitcoin private key from the Shamir shares and adds it to the key pool
+/// of the specified configuration file.
+#[derive(Debug, StructOpt)]
+struct RecoverKeyCommand {
+    /// Path to a sync utility configuration file.
+    #[structopt(long, short = "c")]
+    config: PathBuf,
+    /// Environment variable with the passphrase, which encrypts the shares.
+    #[structopt(long, default_value = "BTC_ANCHORING_SHARES_PASSPHRASE")]
+    passphrase_env: String,
+    /// Paths to the share files.
+    #[structopt(required = true)]
+    shares: Vec<PathBuf>,
+}
+
+/// Updates the Bitcoin RPC credentials in the specified configuration file. Only
+/// the given credentials are updated, and the cookie authentication is replaced by them.
+///
+/// The running sync utility with the same configuration file applies the new
+/// credentials without restart.
+#[derive(Debug, StructOpt)]
+struct RotateRpcCredentialsCommand {
+    /// Path to a sync utility configuration file.
+    #[structopt(long, short = "c")]
+    config: PathBuf,
+    /// New Bitcoin RPC username.
+    #[structopt(long)]
+    bitcoin_rpc_user: Option<String>,
+    /// New Bitcoin RPC password.
+    #[structopt(long)]
+    bitcoin_rpc_password: Option<String>,
+}
+
+/// Source of the Bitcoin blockchain data used to verify the anchors.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum BitcoinBackend {
+    /// Bitcoin Core RPC.
+    Rpc,
+}
+
+impl std::str::FromStr for BitcoinBackend {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "rpc" => Ok(BitcoinBackend::Rpc),
+            "esplora" => bail!("Esplora backend is not supported yet"),
+            _ => bail!("Unknown Bitcoin backend `{}`, expected `rpc`", s),
+        }
+    }
+}
+
+/// Verifies a single anchoring transaction against both the Exonum node and the Bitcoin
+/// network, prints the results of the checks in the JSON format and exits with a non-zero
+/// code if any of the checks has failed.
+#[derive(Debug, StructOpt)]
+struct VerifyAnchorCommand {
+    /// Identifier of the anchoring transaction.
+    #[structopt(long)]
+    txid: bitcoin::Txid,
+    /// Exonum node public API url address.
+    #[structopt(long)]
+    exonum_api: String,
+    /// Anchoring service instance name.
+    #[structopt(long, default_value = "btc_anchoring")]
+    instance_name: String,
+    /// Consensus keys of the Exonum validators used to verify the proofs.
+    #[structopt(long = "validator-key", required = true)]
+    validator_keys: Vec<PublicKey>,
+    /// Source of the Bitcoin blockchain data.
+    #[structopt(long, default_value = "rpc")]
+    btc: BitcoinBackend,
+    /// Bitcoin RPC url.
+    #[structopt(long)]
+    bitcoin_rpc_host: String,
+    /// Bitcoin RPC username.
+    #[structopt(long)]
+    bitcoin_rpc_user: Option<String>,
+    /// Bitcoin RPC password.
+    #[structopt(long)]
+    bitcoin_rpc_password: Option<String>,
+    /// Name of the Bitcoin node wallet, which watches the anchoring address.
+    #[structopt(long)]
+    bitcoin_rpc_wallet: Option<String>,
+    /// Minimal number of confirmations of the anchoring transaction in the Bitcoin network.
+    #[structopt(long, default_value = "1")]
+    min_confirmations: u32,
+}
+
+/// Creates the template of the transaction, which funds the anchoring wallet, and prints
+/// it as the PSBT and the payment URI in the JSON format.
+#[derive(Debug, StructOpt)]
+struct FundingTemplateCommand {
+    /// Exonum node public API url address.
+    #[structopt(long)]
+    exonum_api: String,
+    /// Anchoring service instance name.
+    #[structopt(long, default_value = "btc_anchoring")]
+    instance_name: String,
+    /// Amount to be paid to the anchoring address in satoshis.
+    #[structopt(long)]
+    amount: u64,
+    /// Label of the payment, which is shown by the wallet software.
+    #[structopt(long)]
+    label: Option<String>,
+    /// Fund the following anchoring address instead of the actual one.
+    #[structopt(long)]
+    following: bool,
+}
+
 #[derive(Debug, StructOpt)]
 enum Commands {
     /// Generate initial configuration for the btc anchoring sync utility.
@@ -189,15 +353,172 @@ enum Commands {
     /// Generate a new Bitcoin key pair and add them to the key pool of the specified
     /// configuration file.
     GenerateKeypair(GenerateKeypairCommand),
+    /// Check the local setup: Bitcoin RPC, anchoring wallet, keys and funds.
+    Doctor(DoctorCommand),
+    /// Submit the Bitcoin key of the validator, which joins the anchoring.
+    Onboard(OnboardCommand),
+    /// Approve the Bitcoin key submitted by the validator, which joins the anchoring.
+    ApproveKey(ApproveKeyCommand),
+    /// Propose the new anchoring interval or transaction fee.
+    UpdateParameters(UpdateParametersCommand),
+    /// Add the proofs of possession of the Bitcoin keys to the proposed anchoring
+    /// configuration.
+    ProveKeyPossession(ProveKeyPossessionCommand),
+    /// Update the Bitcoin RPC credentials in the specified configuration file.
+    RotateRpcCredentials(RotateRpcCredentialsCommand),
+    /// Split the Bitcoin private key from the key pool into the Shamir shares.
+    ExportKeyShares(ExportKeySharesCommand),
+    /// Restore the Bitcoin private key from the Shamir shares to the key pool.
+    RecoverKey(RecoverKeyCommand),
+    /// Verify the anchoring transaction against Bitcoin and Exonum.
+    VerifyAnchor(VerifyAnchorCommand),
+    /// Create the template of the transaction funding the anchoring wallet.
+    FundingTemplate(FundingTemplateCommand),
 }
 
+/// Configuration of the sync utility, which is stored either in the TOML or in the JSON
+/// (if the file has the `json` extension) format.
 #[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 struct SyncConfig {
     exonum_private_api: String,
     instance_name: String,
     #[serde(with = "flatten_keypairs")]
     bitcoin_key_pool: HashMap<btc::PublicKey, btc::PrivateKey>,
     bitcoin_rpc_config: Option<BitcoinRpcConfig>,
+    /// Sign anchoring transactions, but never send them to the Bitcoin network.
+    #[serde(default)]
+    dry_run: bool,
+    /// Path to the maintenance marker file. While this file exists, the sync utility
+    /// verifies the anchoring proposals, but does not sign them.
+    #[serde(default)]
+    watch_only_marker: Option<PathBuf>,
+    /// Maximal number of signatures submitted to the Exonum node at once. Should not
+    /// exceed the number of transactions in the Exonum block.
+    #[serde(default)]
+    max_signatures_per_round: Option<usize>,
+    /// Path to the file with the record of the signed proposal inputs. The signatures
+    /// recorded in this file are not resubmitted after the restart of the utility.
+    #[serde(default)]
+    signing_marker: Option<PathBuf>,
+    /// Mirror the anchoring transactions to the test Bitcoin network.
+    #[serde(default)]
+    rehearsal: Option<RehearsalConfig>,
+    /// Make the Bitcoin node wallet watch the following anchoring address as soon as
+    /// the following configuration is known.
+    #[serde(default)]
+    prewatch_following_address: bool,
+    /// Bitcoin block height from which the node wallet is rescanned if it has lost
+    /// the transactions of the anchoring address, and the block of the initial funding
+    /// transaction cannot be found.
+    #[serde(default)]
+    rescan_start_height: Option<u64>,
+    /// Number of the latest anchoring transactions, which are reconciled with the Bitcoin
+    /// blockchain on startup. Ten transactions are reconciled by default.
+    #[serde(default)]
+    reconciliation_depth: Option<u64>,
+    /// Path to the file, to which the startup reconciliation report is written.
+    #[serde(default)]
+    reconciliation_report: Option<PathBuf>,
+    /// Role of the node in the anchoring pipeline. The node signs the proposals and
+    /// broadcasts the anchoring transactions by default.
+    #[serde(default)]
+    role: NodeRole,
+    /// Defer broadcasting of the transition transactions to the following anchoring
+    /// address until the fee rate estimated by the Bitcoin node is low enough.
+    #[serde(default)]
+    transition_schedule: Option<TransitionSchedule>,
+    /// Esplora HTTP API, which is used as the Bitcoin relay instead of the Bitcoin node RPC.
+    #[cfg(feature = "esplora")]
+    #[serde(default)]
+    esplora_config: Option<EsploraConfig>,
+    /// Address of the ZMQ publisher of the Bitcoin node, for example, `127.0.0.1:28332`.
+    /// The node should publish the `rawblock` and `rawtx` notifications to this address.
+    #[cfg(feature = "zmq")]
+    #[serde(default)]
+    zmq_address: Option<String>,
+    /// Secondary anchoring target, to which the same Exonum blocks are anchored
+    /// independently of the primary one.
+    #[serde(default)]
+    secondary_target: Option<SecondaryTargetConfig>,
+    /// Number of Bitcoin confirmations, after which the node reports the latest anchoring
+    /// transaction as final to the anchoring service.
+    #[serde(default)]
+    finality_confirmations: Option<u32>,
+    /// Re-verification of the signatures of the recorded anchoring transactions, which is
+    /// intended for the observer and auditing nodes. Disabled by default.
+    #[serde(default)]
+    signature_verification: SignatureVerification,
+    /// Adjustment of the anchoring transaction fee to the fee rate estimated by
+    /// the Bitcoin relay. The fee is proposed via the `UpdateParameters` transaction.
+    #[serde(default)]
+    fee_policy: Option<FeePolicy>,
+    /// Report the status of the latest anchoring transaction in the Bitcoin network
+    /// to the anchoring service, so that the evicted transactions are visible via the API.
+    #[serde(default)]
+    report_anchor_status: bool,
+    /// Number of Bitcoin confirmations, after which the node proposes to record the hash
+    /// of the block containing the latest anchoring transaction. The recorded block hashes
+    /// allow verifying the anchoring transactions with pruned Bitcoin nodes.
+    #[serde(default)]
+    anchor_block_confirmations: Option<u32>,
+    /// Interval in seconds between the health checks of the Bitcoin relay. The relay status
+    /// is reported to the anchoring service, and nothing is sent to the Bitcoin network
+    /// while the relay is unhealthy. Health checks are disabled by default.
+    #[serde(default)]
+    relay_health_check_secs: Option<u64>,
+}
+
+/// Configuration of the rehearsal mirror, which sends the transactions with the same
+/// payloads as the anchoring ones to the test Bitcoin network.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RehearsalConfig {
+    /// RPC configuration of the Bitcoin node in the test network.
+    bitcoin_rpc_config: BitcoinRpcConfig,
+    /// Private key of the rehearsal wallet in the test network.
+    private_key: btc::PrivateKey,
+    /// Funding transaction of the rehearsal wallet.
+    funding_transaction: btc::Transaction,
+    /// Fee per byte in satoshis.
+    transaction_fee: u64,
+    /// Path to the file with the state of the mirror.
+    state_file: PathBuf,
+}
+
+impl RehearsalConfig {
+    fn into_mirror(
+        self,
+        client: ApiClient,
+    ) -> anyhow::Result<RehearsalMirror<ApiClient, BitcoinRpcRelay>> {
+        ensure!(
+            self.private_key.0.network != bitcoin::Network::Bitcoin,
+            "Rehearsal private key should belong to the test Bitcoin network"
+        );
+        let relay = BitcoinRpcRelay::try_from(self.bitcoin_rpc_config)?;
+        RehearsalMirror::new(
+            client,
+            relay,
+            self.private_key,
+            self.transaction_fee,
+            self.funding_transaction,
+        )
+        .with_state_file(self.state_file)
+    }
+}
+
+/// Configuration of the secondary anchoring target. The target is served by a separate
+/// anchoring service instance with its own configuration, keys and Bitcoin node.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SecondaryTargetConfig {
+    /// Name of the anchoring service instance of the secondary target.
+    instance_name: String,
+    /// Bitcoin keys of the anchoring node in the secondary target.
+    #[serde(with = "flatten_keypairs")]
+    bitcoin_key_pool: HashMap<btc::PublicKey, btc::PrivateKey>,
+    /// RPC configuration of the Bitcoin node in the secondary target.
+    bitcoin_rpc_config: Option<BitcoinRpcConfig>,
 }
 
 impl SyncConfig {
@@ -209,11 +530,184 @@ impl SyncConfig {
             .map(|key| key.0.network)
     }
 
+    /// Checks the configuration and returns the list of all found problems.
+    fn validate(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        if let Err(e) = reqwest::Url::parse(&self.exonum_private_api) {
+            problems.push(format!(
+                "`exonum_private_api` must be a valid URL, but `{}` given: {}.",
+                self.exonum_private_api, e
+            ));
+        }
+        if self.instance_name.is_empty() {
+            problems.push("`instance_name` must not be empty.".to_owned());
+        }
+
+        let networks = self
+            .bitcoin_key_pool
+            .values()
+            .map(|key| key.0.network)
+            .collect::<HashSet<_>>();
+        if networks.len() > 1 {
+            problems.push(format!(
+                "`bitcoin_key_pool` must contain keys for the same Bitcoin network, \
+                 but keys for {:?} given.",
+                networks
+            ));
+        }
+        let context = bitcoin::secp256k1::Secp256k1::new();
+        for (public_key, private_key) in &self.bitcoin_key_pool {
+            if private_key.0.public_key(&context) != public_key.0 {
+                problems.push(format!(
+                    "`bitcoin_key_pool` contains the private key which does not match \
+                     the public key {}.",
+                    public_key
+                ));
+            }
+        }
+
+        if let Some(rpc_config) = &self.bitcoin_rpc_config {
+            if let Err(e) = reqwest::Url::parse(&rpc_config.host) {
+                problems.push(format!(
+                    "`bitcoin_rpc_config.host` must be a valid URL, but `{}` given: {}.",
+                    rpc_config.host, e
+                ));
+            }
+            if rpc_config.cookie_file.is_some()
+                && (rpc_config.user.is_some() || rpc_config.password.is_some())
+            {
+                problems.push(
+                    "Only one of `bitcoin_rpc_config.cookie_file` and the username with \
+                     the password can be specified."
+                        .to_owned(),
+                );
+            }
+            for host in &rpc_config.fallback_hosts {
+                if let Err(e) = reqwest::Url::parse(host) {
+                    problems.push(format!(
+                        "`bitcoin_rpc_config.fallback_hosts` must contain valid URLs, \
+                         but `{}` given: {}.",
+                        host, e
+                    ));
+                }
+            }
+            if let Some(tls) = &rpc_config.tls {
+                let hosts = std::iter::once(&rpc_config.host).chain(&rpc_config.fallback_hosts);
+                for host in hosts.filter(|host| !host.starts_with("https://")) {
+                    problems.push(format!(
+                        "`bitcoin_rpc_config.tls` requires the `https` urls, but `{}` given.",
+                        host
+                    ));
+                }
+                if tls.client_certificate.is_some() != tls.client_key.is_some() {
+                    problems.push(
+                        "`bitcoin_rpc_config.tls.client_certificate` and `client_key` \
+                         should be specified together."
+                            .to_owned(),
+                    );
+                }
+            }
+            if let Some(Err(e)) = rpc_config.proxy.as_ref().map(ProxyConfig::url) {
+                problems.push(format!("`bitcoin_rpc_config.proxy` is invalid: {}.", e));
+            }
+            if rpc_config.wallet.as_ref().map_or(false, String::is_empty) {
+                problems.push("`bitcoin_rpc_config.wallet` must not be empty.".to_owned());
+            }
+            let timeouts = &rpc_config.timeouts;
+            if timeouts.connect_timeout_ms == 0
+                || timeouts.read_timeout_ms == 0
+                || timeouts.import_timeout_ms == 0
+            {
+                problems.push("`bitcoin_rpc_config.timeouts` must be positive.".to_owned());
+            }
+        }
+
+        #[cfg(feature = "esplora")]
+        {
+            if let Some(esplora_config) = &self.esplora_config {
+                if self.bitcoin_rpc_config.is_some() {
+                    problems.push(
+                        "Only one of `bitcoin_rpc_config` and `esplora_config` can be specified."
+                            .to_owned(),
+                    );
+                }
+                if let Err(e) = reqwest::Url::parse(&esplora_config.url) {
+                    problems.push(format!(
+                        "`esplora_config.url` must be a valid URL, but `{}` given: {}.",
+                        esplora_config.url, e
+                    ));
+                }
+                if let Some(Err(e)) = esplora_config.proxy.as_ref().map(ProxyConfig::url) {
+                    problems.push(format!("`esplora_config.proxy` is invalid: {}.", e));
+                }
+            }
+        }
+        if let Some(target) = &self.secondary_target {
+            if target.instance_name.is_empty() || target.instance_name == self.instance_name {
+                problems.push(
+                    "`secondary_target.instance_name` must differ from `instance_name`.".to_owned(),
+                );
+            }
+            if let Some(rpc_config) = &target.bitcoin_rpc_config {
+                if let Err(e) = reqwest::Url::parse(&rpc_config.host) {
+                    problems.push(format!(
+                        "`secondary_target.bitcoin_rpc_config.host` must be a valid URL, \
+                         but `{}` given: {}.",
+                        rpc_config.host, e
+                    ));
+                }
+            }
+        }
+        if self.transition_schedule.is_some() && self.bitcoin_rpc_config.is_none() {
+            problems.push(
+                "`transition_schedule` requires `bitcoin_rpc_config` to estimate the fee rate."
+                    .to_owned(),
+            );
+        }
+        if let Some(fee_policy) = &self.fee_policy {
+            problems.extend(
+                fee_policy
+                    .validate()
+                    .into_iter()
+                    .map(|problem| format!("`fee_policy`: {}", problem)),
+            );
+        }
+        if self.relay_health_check_secs == Some(0) {
+            problems.push("`relay_health_check_secs` must be positive.".to_owned());
+        }
+        problems
+    }
+
+    /// Returns `true` if any Bitcoin relay backend is configured.
+    fn has_bitcoin_relay(&self) -> bool {
+        #[cfg(feature = "esplora")]
+        {
+            if self.esplora_config.is_some() {
+                return true;
+            }
+        }
+        self.bitcoin_rpc_config.is_some()
+    }
+
     fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
-        let mut file = File::open(path)?;
-        let mut toml = String::new();
-        file.read_to_string(&mut toml)?;
-        toml::de::from_str(&toml).map_err(From::from)
+        let path = path.as_ref();
+        let contents = fs::read_to_string(path)?;
+        let config: Self = if is_json(path) {
+            serde_json::from_str(&contents)
+                .map_err(|e| anyhow!("Unable to parse {}: {}", path.display(), e))?
+        } else {
+            toml::de::from_str(&contents)
+                .map_err(|e| anyhow!("Unable to parse {}: {}", path.display(), e))?
+        };
+
+        let problems = config.validate();
+        ensure!(
+            problems.is_empty(),
+            "Sync utility configuration {} is invalid:\n  - {}",
+            path.display(),
+            problems.join("\n  - ")
+        );
+        Ok(config)
     }
 
     fn save(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
@@ -223,15 +717,107 @@ impl SyncConfig {
             fs::create_dir_all(dir)?;
         }
 
-        let mut file = File::create(path)?;
-        let value_toml = toml::Value::try_from(&self)?;
-        file.write_all(value_toml.to_string().as_bytes())?;
+        // Write the configuration to the temporary file first, so that the running sync
+        // utility never reads a partially written configuration. The configuration contains
+        // the Bitcoin key pool, so the file is accessible only by its owner.
+        let tmp_path = path.with_extension("tmp");
+        if tmp_path.exists() {
+            fs::remove_file(&tmp_path)?;
+        }
+        let mut file = create_private_file(&tmp_path)?;
+        let contents = if is_json(path) {
+            serde_json::to_string_pretty(&self)?
+        } else {
+            toml::Value::try_from(&self)?.to_string()
+        };
+        file.write_all(contents.as_bytes())?;
+        fs::rename(tmp_path, path)?;
         Ok(())
     }
 }
 
+impl ConfigOverlay {
+    /// Loads the sync utility configuration file and applies the overrides to it.
+    fn load(&self, path: impl AsRef<Path>) -> anyhow::Result<SyncConfig> {
+        let mut sync_config = SyncConfig::load(path)?;
+
+        if let Some(exonum_private_api) = &self.exonum_private_api {
+            sync_config.exonum_private_api = exonum_private_api.clone();
+        }
+        if self.bitcoin_rpc_host.is_some()
+            || self.bitcoin_rpc_user.is_some()
+            || self.bitcoin_rpc_password.is_some()
+            || self.bitcoin_rpc_cookie.is_some()
+            || self.bitcoin_rpc_wallet.is_some()
+        {
+            let rpc_config = sync_config
+                .bitcoin_rpc_config
+                .get_or_insert_with(BitcoinRpcConfig::default);
+            if let Some(host) = &self.bitcoin_rpc_host {
+                rpc_config.host = host.clone();
+            }
+            if let Some(user) = &self.bitcoin_rpc_user {
+                rpc_config.user = Some(user.clone());
+            }
+            if let Some(password) = &self.bitcoin_rpc_password {
+                rpc_config.password = Some(password.clone());
+            }
+            if let Some(cookie_file) = &self.bitcoin_rpc_cookie {
+                rpc_config.cookie_file = Some(cookie_file.clone());
+            }
+            if let Some(wallet) = &self.bitcoin_rpc_wallet {
+                rpc_config.wallet = Some(wallet.clone());
+            }
+        }
+        if let Some(path) = &self.bitcoin_keys_file {
+            let context = bitcoin::secp256k1::Secp256k1::new();
+            let keys = fs::read_to_string(path)?;
+            for line in keys.lines().map(str::trim).filter(|line| !line.is_empty()) {
+                let private_key = line.parse::<btc::PrivateKey>().map_err(|e| {
+                    anyhow!("Invalid Bitcoin private key in {}: {}", path.display(), e)
+                })?;
+                let public_key = btc::PublicKey(private_key.0.public_key(&context));
+                sync_config.bitcoin_key_pool.insert(public_key, private_key);
+            }
+        }
+
+        let problems = sync_config.validate();
+        ensure!(
+            problems.is_empty(),
+            "Sync utility configuration is invalid after applying the overrides:\n  - {}",
+            problems.join("\n  - ")
+        );
+        Ok(sync_config)
+    }
+}
+
+/// Checks that the configuration file should be stored in the JSON format.
+fn is_json(path: &Path) -> bool {
+    path.extension()
+        .map_or(false, |extension| extension == "json")
+}
+
+/// Creates a new file, which is readable and writable only by its owner, since it
+/// contains the private keys or their shares.
+fn create_private_file(path: &Path) -> std::io::Result<File> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options.open(path)
+}
+
+/// Reads the passphrase of the key shares from the given environment variable.
+fn read_shares_passphrase(var: &str) -> anyhow::Result<String> {
+    let passphrase = std::env::var(var)
+        .map_err(|e| anyhow!("Unable to read the passphrase from {}: {}", var, e))?;
+    ensure!(!passphrase.is_empty(), "Passphrase in {} is empty", var);
+    Ok(passphrase)
+}
+
 /// `Bitcoind` rpc configuration.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+#[serde(deny_unknown_fields)]
 struct BitcoinRpcConfig {
     /// Bitcoin RPC url.
     host: String,
@@ -239,17 +825,105 @@ struct BitcoinRpcConfig {
     user: Option<String>,
     /// Bitcoin RPC password.
     password: Option<String>,
+    /// Path to the RPC cookie file (`.cookie` in the data directory) of the Bitcoin node.
+    /// The cookie is used instead of the username and password, and is re-read after
+    /// the relay failures, since the node creates a new cookie on each restart.
+    #[serde(default)]
+    cookie_file: Option<PathBuf>,
+    /// Name of the node wallet, which watches the anchoring addresses, if the Bitcoin node
+    /// has several wallets loaded. The requests are sent to the `/wallet/<name>` endpoint
+    /// of the node, so the wallet calls are routed to this wallet.
+    #[serde(default)]
+    wallet: Option<String>,
+    /// RPC urls of the redundant Bitcoin nodes with the same credentials, to which the relay
+    /// fails over if the node at `host` is unreachable.
+    #[serde(default)]
+    fallback_hosts: Vec<String>,
+    /// Retries of the requests, which have failed due to the transient errors, for example,
+    /// while the Bitcoin node is restarting.
+    #[serde(default)]
+    retry_policy: RetryPolicy,
+    /// Deadlines of the requests. The imports into the node wallet, which may rescan
+    /// the blockchain, have a separate, longer deadline.
+    #[serde(default)]
+    timeouts: RpcTimeouts,
+    /// TLS settings for the Bitcoin nodes behind a TLS-terminating proxy. If specified,
+    /// `host` and `fallback_hosts` should use the `https` scheme.
+    #[serde(default)]
+    tls: Option<TlsConfig>,
+    /// SOCKS5 proxy, via which the Bitcoin nodes are reached, for example, Tor
+    /// or a bastion host.
+    #[serde(default)]
+    proxy: Option<ProxyConfig>,
 }
 
 impl TryFrom<BitcoinRpcConfig> for BitcoinRpcClient {
     type Error = bitcoincore_rpc::Error;
 
     fn try_from(value: BitcoinRpcConfig) -> Result<Self, Self::Error> {
-        let auth = BitcoinRpcAuth::UserPass(
-            value.user.unwrap_or_default(),
-            value.password.unwrap_or_default(),
-        );
-        Self::new(value.host, auth)
+        value.rpc_client(&value.host)
+    }
+}
+
+impl BitcoinRpcConfig {
+    /// Creates the Bitcoin RPC client for the node with the given url, which connects via
+    /// HTTPS if TLS or the proxy is configured.
+    fn rpc_client(&self, host: &str) -> Result<BitcoinRpcClient, bitcoincore_rpc::Error> {
+        let url = self.wallet_url(host)?;
+        if self.tls.is_none() && self.proxy.is_none() {
+            return BitcoinRpcClient::new(url, self.auth());
+        }
+        BitcoinRpcClient::with_transport(&url, self.auth(), self.tls.as_ref(), self.proxy.as_ref())
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e).into())
+    }
+
+    /// Returns the url of the endpoint of the configured wallet on the Bitcoin node
+    /// with the given url. The wallet name is percent-encoded.
+    fn wallet_url(&self, host: &str) -> Result<String, bitcoincore_rpc::Error> {
+        let wallet = match &self.wallet {
+            Some(wallet) => wallet,
+            None => return Ok(host.to_owned()),
+        };
+        let invalid_host = || {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("Invalid Bitcoin RPC url `{}`", host),
+            )
+        };
+        let mut url = reqwest::Url::parse(host).map_err(|_| invalid_host())?;
+        url.path_segments_mut()
+            .map_err(|_| invalid_host())?
+            .pop_if_empty()
+            .extend(&["wallet", wallet]);
+        Ok(url.to_string())
+    }
+
+    fn auth(&self) -> BitcoinRpcAuth {
+        if let Some(cookie_file) = &self.cookie_file {
+            return BitcoinRpcAuth::CookieFile(cookie_file.clone());
+        }
+        BitcoinRpcAuth::UserPass(
+            self.user.clone().unwrap_or_default(),
+            self.password.clone().unwrap_or_default(),
+        )
+    }
+}
+
+impl TryFrom<BitcoinRpcConfig> for BitcoinRpcRelay {
+    type Error = bitcoincore_rpc::Error;
+
+    fn try_from(value: BitcoinRpcConfig) -> Result<Self, Self::Error> {
+        let fallback_clients = value
+            .fallback_hosts
+            .iter()
+            .map(|host| value.rpc_client(host))
+            .collect::<Result<Vec<_>, _>>()?;
+        let (retry_policy, timeouts) = (value.retry_policy, value.timeouts);
+        let client = BitcoinRpcClient::try_from(value)?;
+        Ok(Self::new(client)?
+            .with_fallback_clients(fallback_clients)
+            .with_retry_policy(retry_policy)
+            .with_timeouts(timeouts))
     }
 }
 
@@ -263,6 +937,28 @@ impl GenerateConfigCommand {
             bitcoin_key_pool: std::iter::once(bitcoin_keypair.clone()).collect(),
             instance_name: self.instance_name,
             bitcoin_rpc_config,
+            dry_run: self.dry_run,
+            watch_only_marker: None,
+            max_signatures_per_round: None,
+            signing_marker: None,
+            rehearsal: None,
+            prewatch_following_address: false,
+            rescan_start_height: None,
+            reconciliation_depth: None,
+            reconciliation_report: None,
+            role: NodeRole::default(),
+            transition_schedule: None,
+            #[cfg(feature = "esplora")]
+            esplora_config: None,
+            #[cfg(feature = "zmq")]
+            zmq_address: None,
+            secondary_target: None,
+            finality_confirmations: None,
+            signature_verification: SignatureVerification::default(),
+            fee_policy: None,
+            report_anchor_status: false,
+            anchor_block_confirmations: None,
+            relay_health_check_secs: None,
         };
 
         sync_config.save(self.output)?;
@@ -281,37 +977,233 @@ impl GenerateConfigCommand {
             host,
             user: self.bitcoin_rpc_user.clone(),
             password: self.bitcoin_rpc_password.clone(),
+            cookie_file: self.bitcoin_rpc_cookie.clone(),
+            wallet: self.bitcoin_rpc_wallet.clone(),
+            fallback_hosts: Vec::new(),
+            retry_policy: RetryPolicy::default(),
+            timeouts: RpcTimeouts::default(),
+            tls: None,
+            proxy: None,
         })
     }
 }
 
 impl RunCommand {
     async fn run(self) -> anyhow::Result<()> {
-        let sync_config = SyncConfig::load(self.config)?;
+        let sync_config = self.overlay.load(&self.config)?;
+        let role = sync_config.role;
+        role.validate(
+            !sync_config.bitcoin_key_pool.is_empty(),
+            sync_config.has_bitcoin_relay(),
+        )
+        .map_err(|e| anyhow!(e))?;
+        log::info!("Sync utility works in the {:?} role.", role);
+        let mut bitcoin_notifications = Self::subscribe_bitcoin_notifications(&sync_config);
+        let dry_run = sync_config.dry_run;
+        let secondary_target = sync_config.secondary_target.clone();
+        let mut rpc_config = sync_config.bitcoin_rpc_config.clone();
+        let mut rpc_cookie = None;
         let client = ApiClient::new(sync_config.exonum_private_api, sync_config.instance_name);
-        let chain_updater =
-            AnchoringChainUpdateTask::new(sync_config.bitcoin_key_pool, client.clone());
+        let watch_only_marker = sync_config.watch_only_marker;
+        let prewatch_following_address = sync_config.prewatch_following_address;
+        let rescan_start_height = sync_config.rescan_start_height;
+        let reconciliation_depth = sync_config
+            .reconciliation_depth
+            .unwrap_or(DEFAULT_RECONCILIATION_DEPTH);
+        let reconciliation_report = sync_config.reconciliation_report;
+        let shutdown = ShutdownSignal::default();
+        tokio::spawn(Self::listen_shutdown(shutdown.clone()));
+        // Failures of the secondary target do not affect the primary one.
+        if let Some(target) = secondary_target {
+            let api = sync_config.exonum_private_api.clone();
+            tokio::spawn(Self::run_secondary_target(
+                api,
+                target,
+                role,
+                dry_run,
+                shutdown.clone(),
+            ));
+        }
+        // Do not sign anchoring proposals while the node may be on the minority fork.
+        let mut chain_updater =
+            AnchoringChainUpdateTask::new(sync_config.bitcoin_key_pool, client.clone())
+                .with_signing_policy(ConsensusHealthPolicy::new(client.clone()))
+                .with_shutdown(shutdown.clone());
+        if let Some(limit) = sync_config.max_signatures_per_round {
+            chain_updater = chain_updater.with_max_signatures_per_round(limit);
+        }
+        if let Some(path) = sync_config.signing_marker.as_ref() {
+            chain_updater = chain_updater.with_signing_marker(SigningMarker::open(path)?);
+        }
+        // Fee rate is estimated by the same Bitcoin node, which the relay uses.
+        let finality_confirmations = sync_config.finality_confirmations;
+        let fee_policy = sync_config.fee_policy;
+        let report_anchor_status = sync_config.report_anchor_status;
+        let anchor_block_confirmations = sync_config.anchor_block_confirmations;
+        let health_check_interval = sync_config.relay_health_check_secs.map(Duration::from_secs);
+        let transition_schedule = match (sync_config.transition_schedule, rpc_config.clone()) {
+            (Some(schedule), Some(rpc_config)) => {
+                Some((schedule, BitcoinRpcClient::try_from(rpc_config)?))
+            }
+            _ => None,
+        };
+        // Signers have no access to the Bitcoin network, even if it is configured.
         let bitcoin_relay = sync_config
             .bitcoin_rpc_config
-            .map(BitcoinRpcClient::try_from)
+            .filter(|_| role.uses_bitcoin_relay())
+            .map(BitcoinRpcRelay::try_from)
             .transpose()?
-            .map(|relay| SyncWithBitcoinTask::new(relay, client.clone()));
-
-        let mut latest_synced_tx_index: Option<u64> = None;
-        loop {
-            match chain_updater.process().await {
-                Ok(_) => {}
-                // Client problems most often occurs due to network problems.
-                Err(ChainUpdateError::Client(e)) => {
-                    log::error!("An error in the anchoring API client occurred. {}", e)
+            .map(|relay| {
+                log::info!("Bitcoin relay capabilities: {:?}", relay.capabilities());
+                // The statuses of the anchoring transactions are checked on every iteration.
+                // Observers only track the statuses of the anchoring transactions.
+                let mut task = SyncWithBitcoinTask::new(CachedRelay::new(relay), client.clone())
+                    .with_dry_run(dry_run || !role.broadcasts())
+                    .with_shutdown(shutdown.clone());
+                if let Some(confirmations) = finality_confirmations {
+                    task = task.with_finality_confirmations(confirmations);
                 }
-                // Sometimes Bitcoin end in the anchoring wallet.
-                Err(ChainUpdateError::InsufficientFunds { total_fee, balance }) => log::warn!(
-                    "Insufficient funds to construct a new anchoring transaction, \
-                     total fee is {}, total balance is {}",
-                    total_fee,
+                if let Some(policy) = fee_policy {
+                    task = task.with_fee_policy(policy);
+                }
+                if report_anchor_status {
+                    task = task.with_anchor_status_reports();
+                }
+                if let Some(confirmations) = anchor_block_confirmations {
+                    task = task.with_anchor_block_records(confirmations);
+                }
+                if let Some(interval) = health_check_interval {
+                    task = task.with_health_checks(interval);
+                }
+                match transition_schedule {
+                    Some((schedule, fee_oracle)) => {
+                        task.with_transition_schedule(schedule, fee_oracle)
+                    }
+                    None => task,
+                }
+            });
+        #[cfg(feature = "esplora")]
+        let esplora_relay = sync_config
+            .esplora_config
+            .filter(|_| role.uses_bitcoin_relay())
+            .map(|config| {
+                log::info!("Esplora API {} is used as the Bitcoin relay.", config.url);
+                let mut relay = EsploraRelay::new(&config.url);
+                if let Some(proxy) = &config.proxy {
+                    relay = relay.with_proxy(proxy)?;
+                }
+                Ok(
+                    SyncWithBitcoinTask::new(CachedRelay::new(relay), client.clone())
+                        .with_dry_run(dry_run || !role.broadcasts())
+                        .with_shutdown(shutdown.clone()),
+                )
+            })
+            .transpose()
+            .map_err(|e: ProxyError| anyhow!("Invalid proxy of the Esplora relay: {}", e))?
+            .map(|task| match finality_confirmations {
+                Some(confirmations) => task.with_finality_confirmations(confirmations),
+                None => task,
+            })
+            .map(|task| match anchor_block_confirmations {
+                Some(confirmations) => task.with_anchor_block_records(confirmations),
+                None => task,
+            })
+            .map(|task| match health_check_interval {
+                Some(interval) => task.with_health_checks(interval),
+                None => task,
+            });
+        let rehearsal_mirror = sync_config
+            .rehearsal
+            .map(|config| config.into_mirror(client.clone()))
+            .transpose()?;
+        let mut signature_audit = match sync_config.signature_verification {
+            SignatureVerification::Full => Some(SignatureAuditTask::new(client.clone())),
+            SignatureVerification::Disabled => None,
+        };
+        if dry_run {
+            log::warn!("Sync utility works in the dry-run mode, transactions will not be sent.");
+        }
+
+        let mut latest_synced_tx_index: Option<u64> = None;
+        let mut watched_address = None;
+        let mut watch_data_checked = false;
+        // Nothing is signed until the anchoring chain is reconciled with the Bitcoin blockchain.
+        let mut reconciled = bitcoin_relay.is_none();
+        #[cfg(feature = "esplora")]
+        {
+            reconciled = reconciled && esplora_relay.is_none();
+        }
+        loop {
+            // Operations of the previous iteration are completed at this point.
+            if shutdown.is_requested() {
+                log::info!("Sync utility has been stopped.");
+                return Ok(());
+            }
+
+            if let Some(marker) = watch_only_marker.as_ref() {
+                Self::update_watch_only_mode(marker, chain_updater.watch_only());
+            }
+
+            if let Some(relay) = bitcoin_relay.as_ref().filter(|_| !reconciled) {
+                reconciled = Self::reconcile(
+                    relay,
+                    reconciliation_depth,
+                    reconciliation_report.as_deref(),
+                    watch_only_marker.as_deref(),
+                    chain_updater.watch_only(),
+                )
+                .await;
+            }
+            #[cfg(feature = "esplora")]
+            {
+                if let Some(relay) = esplora_relay.as_ref().filter(|_| !reconciled) {
+                    reconciled = Self::reconcile(
+                        relay,
+                        reconciliation_depth,
+                        reconciliation_report.as_deref(),
+                        watch_only_marker.as_deref(),
+                        chain_updater.watch_only(),
+                    )
+                    .await;
+                }
+            }
+
+            let chain_update = if reconciled && role.signs() {
+                chain_updater.process().await
+            } else {
+                Ok(())
+            };
+            match chain_update {
+                Ok(_) => {}
+                // Client problems most often occurs due to network problems.
+                Err(ChainUpdateError::Client(e)) => {
+                    log::error!("An error in the anchoring API client occurred. {}", e)
+                }
+                // Sometimes Bitcoin end in the anchoring wallet.
+                Err(ChainUpdateError::InsufficientFunds { total_fee, balance }) => log::warn!(
+                    "Insufficient funds to construct a new anchoring transaction, \
+                     total fee is {}, total balance is {}",
+                    total_fee,
                     balance
                 ),
+                // Anchoring wallet balance should not go below the reserve.
+                Err(ChainUpdateError::ReserveReached { remaining, reserve }) => log::warn!(
+                    "Anchoring transaction spends the reserved funds, \
+                     remaining balance is {}, reserve is {}",
+                    remaining,
+                    reserve
+                ),
+                // Change output below the dust threshold would not be relayed.
+                Err(ChainUpdateError::DustChange {
+                    remaining,
+                    dust_threshold,
+                }) => log::warn!(
+                    "Change output of the anchoring transaction is below the dust threshold, \
+                     remaining balance is {}, dust threshold is {}. The anchoring is stopped \
+                     until the anchoring wallet is replenished.",
+                    remaining,
+                    dust_threshold
+                ),
                 // For the work of anchoring you need to replenish anchoring wallet.
                 Err(ChainUpdateError::NoInitialFunds) => {
                     let address = match chain_updater.anchoring_config().await {
@@ -332,11 +1224,33 @@ impl RunCommand {
                          `add-funds` API method."
                     )
                 }
+                // Conflicting proposals require the investigation of the operators.
+                Err(ChainUpdateError::AnchoringHalted { conflict }) => log::error!(
+                    "Anchoring is halted: node {} has signed the transaction {}, which conflicts \
+                     with the anchoring proposal {} for height {}. Investigate the evidence \
+                     via the `v1/proposal-conflicts` endpoint",
+                    conflict.author.to_hex(),
+                    conflict.conflicting_txid,
+                    conflict.proposal_txid,
+                    conflict.anchoring_height
+                ),
+                // The node refused to sign the proposal according to its signing policies.
+                Err(ChainUpdateError::SigningRefused { txid, reason }) => log::warn!(
+                    "Anchoring proposal {} has not been signed: {}",
+                    txid,
+                    reason
+                ),
                 // Stop execution if an internal error occurred.
                 Err(ChainUpdateError::Internal(e)) => return Err(e),
             }
 
             if let Some(relay) = bitcoin_relay.as_ref() {
+                if !watch_data_checked {
+                    watch_data_checked =
+                        Self::restore_watch_data(&client, relay.btc_relay(), rescan_start_height)
+                            .await;
+                }
+
                 match relay.process(latest_synced_tx_index).await {
                     Ok(index) => latest_synced_tx_index = index,
 
@@ -344,8 +1258,41 @@ impl RunCommand {
                         log::error!("An error in the anchoring API client occurred. {}", e)
                     }
 
+                    Err(SyncWithBitcoinError::Relay(RelayError::Rejected { txid, rejection })) => {
+                        log::warn!(
+                            "Anchoring transaction {} has been rejected by the Bitcoin node \
+                             mempool: {}",
+                            txid,
+                            rejection
+                        );
+                        if rejection.kind == RejectionKind::FeeTooLow {
+                            log::warn!(
+                                "Consider increasing the transaction fee in the anchoring \
+                                 configuration."
+                            )
+                        }
+                    }
+
                     Err(SyncWithBitcoinError::Relay(e)) => {
-                        log::error!("An error in the Bitcoin relay occurred. {}", e)
+                        log::error!("An error in the Bitcoin relay occurred. {}", e);
+                        Self::reload_rpc_cookie(
+                            rpc_config.as_ref(),
+                            relay.btc_relay().inner(),
+                            &mut rpc_cookie,
+                        );
+                    }
+
+                    // The Bitcoin node may have been restarted with a new RPC cookie.
+                    Err(SyncWithBitcoinError::RelayUnavailable(status)) => {
+                        log::error!(
+                            "Bitcoin relay is unavailable after {} failed health checks.",
+                            status.failed_checks
+                        );
+                        Self::reload_rpc_cookie(
+                            rpc_config.as_ref(),
+                            relay.btc_relay().inner(),
+                            &mut rpc_cookie,
+                        );
                     }
 
                     Err(SyncWithBitcoinError::UnconfirmedFundingTransaction(id)) => bail!(
@@ -357,11 +1304,687 @@ impl RunCommand {
                     // Stop execution if an internal error occurred.
                     Err(SyncWithBitcoinError::Internal(e)) => return Err(e),
                 }
+
+                if prewatch_following_address {
+                    Self::prewatch_following_address(
+                        &client,
+                        relay.btc_relay(),
+                        &mut watched_address,
+                    )
+                    .await;
+                }
+                self.rotate_rpc_credentials(&mut rpc_config, relay.btc_relay().inner());
+            }
+            #[cfg(feature = "esplora")]
+            {
+                if let Some(relay) = esplora_relay.as_ref() {
+                    Self::sync_with_esplora(relay, &mut latest_synced_tx_index).await?;
+                }
+            }
+
+            // Signature audit failures should not affect the anchoring.
+            if let Some(audit) = signature_audit.as_mut() {
+                if let Err(e) = audit.process().await {
+                    log::warn!(
+                        "Unable to audit the anchoring transaction signatures. {}",
+                        e
+                    );
+                }
+            }
+
+            // Rehearsal mirror failures should not affect the anchoring.
+            if let Some(mirror) = rehearsal_mirror.as_ref() {
+                if let Err(e) = mirror.process().await {
+                    log::warn!("Unable to mirror the anchoring transaction. {:?}", e);
+                }
             }
 
             // Don't perform this actions too frequent to avoid DOS attack.
-            delay_for(Duration::from_secs(5)).await
+            tokio::select! {
+                _ = delay_for(Duration::from_secs(5)) => {}
+                _ = shutdown.wait() => {}
+                // New Bitcoin blocks change the confirmations of the anchoring transactions.
+                _ = Self::next_notification(&mut bitcoin_notifications) => {
+                    if let Some(relay) = bitcoin_relay.as_ref() {
+                        relay.btc_relay().clear_statuses();
+                    }
+                    #[cfg(feature = "esplora")]
+                    {
+                        if let Some(relay) = esplora_relay.as_ref() {
+                            relay.btc_relay().clear_statuses();
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Anchors the Exonum blocks to the secondary target until the shutdown is requested.
+    async fn run_secondary_target(
+        exonum_private_api: String,
+        target: SecondaryTargetConfig,
+        role: NodeRole,
+        dry_run: bool,
+        shutdown: ShutdownSignal,
+    ) {
+        let instance_name = target.instance_name.clone();
+        if let Err(e) =
+            Self::sync_secondary_target(exonum_private_api, target, role, dry_run, &shutdown).await
+        {
+            log::error!(
+                "Anchoring to the secondary target `{}` has been stopped. {:?}",
+                instance_name,
+                e
+            );
+        }
+    }
+
+    async fn sync_secondary_target(
+        exonum_private_api: String,
+        target: SecondaryTargetConfig,
+        role: NodeRole,
+        dry_run: bool,
+        shutdown: &ShutdownSignal,
+    ) -> anyhow::Result<()> {
+        let client = ApiClient::new(exonum_private_api, target.instance_name.clone());
+        let chain_updater = AnchoringChainUpdateTask::new(target.bitcoin_key_pool, client.clone())
+            .with_signing_policy(ConsensusHealthPolicy::new(client.clone()))
+            .with_shutdown(shutdown.clone());
+        let bitcoin_relay = target
+            .bitcoin_rpc_config
+            .filter(|_| role.uses_bitcoin_relay())
+            .map(BitcoinRpcRelay::try_from)
+            .transpose()?
+            .map(|relay| {
+                SyncWithBitcoinTask::new(CachedRelay::new(relay), client.clone())
+                    .with_dry_run(dry_run || !role.broadcasts())
+                    .with_shutdown(shutdown.clone())
+            });
+        log::info!(
+            "Anchoring to the secondary target `{}` has been started.",
+            target.instance_name
+        );
+
+        let mut latest_synced_tx_index: Option<u64> = None;
+        while !shutdown.is_requested() {
+            if role.signs() {
+                match chain_updater.process().await {
+                    Ok(_) => {}
+                    Err(ChainUpdateError::Internal(e)) => return Err(e),
+                    Err(e) => log::warn!(
+                        "Unable to update the anchoring chain of the secondary target `{}`. {:?}",
+                        target.instance_name,
+                        e
+                    ),
+                }
+            }
+            if let Some(relay) = bitcoin_relay.as_ref() {
+                match relay.process(latest_synced_tx_index).await {
+                    Ok(index) => latest_synced_tx_index = index,
+                    Err(SyncWithBitcoinError::Internal(e)) => return Err(e),
+                    Err(e) => log::warn!(
+                        "Unable to sync the secondary target `{}` with Bitcoin. {:?}",
+                        target.instance_name,
+                        e
+                    ),
+                }
+            }
+
+            tokio::select! {
+                _ = delay_for(Duration::from_secs(5)) => {}
+                _ = shutdown.wait() => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Subscribes to the ZMQ notifications of the Bitcoin node, if the publisher address
+    /// is configured. Each relevant notification is sent to the returned channel.
+    #[cfg(feature = "zmq")]
+    fn subscribe_bitcoin_notifications(
+        sync_config: &SyncConfig,
+    ) -> Option<mpsc::UnboundedReceiver<()>> {
+        let address = sync_config.zmq_address.clone()?;
+        let (sender, receiver) = mpsc::unbounded();
+        tokio::spawn(Self::listen_zmq(address, sender));
+        Some(receiver)
+    }
+
+    #[cfg(not(feature = "zmq"))]
+    fn subscribe_bitcoin_notifications(
+        _sync_config: &SyncConfig,
+    ) -> Option<mpsc::UnboundedReceiver<()>> {
+        None
+    }
+
+    /// Listens to the ZMQ notifications of the Bitcoin node and reconnects to it
+    /// on failures until the receiver is dropped.
+    #[cfg(feature = "zmq")]
+    async fn listen_zmq(address: String, sender: mpsc::UnboundedSender<()>) {
+        let topics = [ZmqTopic::RawBlock, ZmqTopic::RawTx];
+        loop {
+            let result: Result<(), ZmqError> = async {
+                let mut subscriber = ZmqSubscriber::connect(&address, &topics).await?;
+                log::info!(
+                    "Subscribed to the Bitcoin node notifications at {}.",
+                    address
+                );
+                loop {
+                    match subscriber.next().await {
+                        Ok(notification) if notification.is_relevant() => {
+                            if sender.unbounded_send(()).is_err() {
+                                return Ok(());
+                            }
+                        }
+                        Ok(_) => {}
+                        // Malformed notification does not break the subscription.
+                        Err(e @ ZmqError::Decode { .. }) => log::warn!("{}", e),
+                        Err(e) => return Err(e),
+                    }
+                }
+            }
+            .await;
+
+            match result {
+                Ok(()) => return,
+                Err(e) => log::warn!(
+                    "ZMQ subscription to the Bitcoin node at {} failed, reconnecting. {}",
+                    address,
+                    e
+                ),
+            }
+            delay_for(Duration::from_secs(5)).await;
+        }
+    }
+
+    /// Waits for the next Bitcoin node notification, if the notifications are enabled.
+    /// The notifications received in the meantime are merged into this one.
+    async fn next_notification(notifications: &mut Option<mpsc::UnboundedReceiver<()>>) {
+        if let Some(receiver) = notifications {
+            if receiver.next().await.is_some() {
+                while let Ok(Some(())) = receiver.try_next() {}
+                return;
+            }
+        }
+        future::pending().await
+    }
+
+    /// Sends the anchoring transactions to the Bitcoin network via the Esplora API.
+    #[cfg(feature = "esplora")]
+    async fn sync_with_esplora(
+        relay: &SyncWithBitcoinTask<ApiClient, CachedRelay<EsploraRelay>>,
+        latest_synced_tx_index: &mut Option<u64>,
+    ) -> anyhow::Result<()> {
+        match relay.process(*latest_synced_tx_index).await {
+            Ok(index) => *latest_synced_tx_index = index,
+
+            Err(SyncWithBitcoinError::Client(e)) => {
+                log::error!("An error in the anchoring API client occurred. {}", e)
+            }
+
+            Err(SyncWithBitcoinError::Relay(e)) => {
+                log::error!("An error in the Esplora relay occurred. {}", e)
+            }
+
+            Err(SyncWithBitcoinError::RelayUnavailable(status)) => log::error!(
+                "Esplora relay is unavailable after {} failed health checks.",
+                status.failed_checks
+            ),
+
+            Err(SyncWithBitcoinError::UnconfirmedFundingTransaction(id)) => bail!(
+                "Funding transaction with id {} is unconfirmed by Bitcoin network. \
+                 This is a serious mistake that can break anchoring process.",
+                id
+            ),
+
+            // Stop execution if an internal error occurred.
+            Err(SyncWithBitcoinError::Internal(e)) => return Err(e),
+        }
+        Ok(())
+    }
+
+    /// Requests the shutdown of the sync utility on the interrupt or termination signal.
+    async fn listen_shutdown(shutdown: ShutdownSignal) {
+        #[cfg(unix)]
+        let terminate = async {
+            match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+                Ok(mut signal) => {
+                    signal.recv().await;
+                }
+                Err(e) => {
+                    log::warn!("Unable to listen for the termination signal. {}", e);
+                    future::pending::<()>().await
+                }
+            }
+        };
+        #[cfg(not(unix))]
+        let terminate = future::pending::<()>();
+
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => {}
+            _ = terminate => {}
+        }
+        log::info!("Shutdown requested, completing the operations in progress.");
+        shutdown.request();
+    }
+
+    /// Applies the Bitcoin RPC credentials from the configuration file if they have been
+    /// changed since the last check.
+    fn update_watch_only_mode(marker: &Path, toggle: &WatchOnlyToggle) {
+        let enabled = marker.exists();
+        if toggle.set(enabled) != enabled {
+            if enabled {
+                log::warn!(
+                    "Found the maintenance marker {}, sync utility works in the watch-only mode, \
+                     anchoring proposals will not be signed.",
+                    marker.display()
+                );
+            } else {
+                log::info!("Maintenance marker has been removed, watch-only mode is off.");
+            }
+        }
+    }
+
+    /// Reconciles the latest anchoring transactions with the Bitcoin blockchain and writes
+    /// the report to the given file, if any. If divergences are found, switches the watch-only
+    /// mode on, so the node does not sign anything new until the operators investigate them.
+    /// Returns `false` if the reconciliation should be retried.
+    async fn reconcile<R>(
+        relay: &SyncWithBitcoinTask<ApiClient, R>,
+        depth: u64,
+        report_path: Option<&Path>,
+        watch_only_marker: Option<&Path>,
+        toggle: &WatchOnlyToggle,
+    ) -> bool
+    where
+        R: BitcoinRelay + 'static,
+        R::Error: std::fmt::Display,
+    {
+        let report = match relay.reconcile(depth).await {
+            Ok(report) => report,
+            Err(e) => {
+                log::error!(
+                    "Unable to reconcile the anchoring chain with the Bitcoin blockchain. {}",
+                    e
+                );
+                return false;
+            }
+        };
+
+        if let Some(path) = report_path {
+            let result = serde_json::to_string_pretty(&report)
+                .map_err(anyhow::Error::from)
+                .and_then(|report| fs::write(path, report).map_err(anyhow::Error::from));
+            if let Err(e) = result {
+                log::warn!(
+                    "Unable to write the reconciliation report to {}. {}",
+                    path.display(),
+                    e
+                );
+            }
+        }
+
+        if report.is_consistent() {
+            log::info!(
+                "Latest {} anchoring transactions are consistent with the Bitcoin blockchain.",
+                report.anchors.len()
+            );
+            return true;
+        }
+        for anchor in report.divergent_anchors() {
+            for divergence in &anchor.divergences {
+                log::error!(
+                    "Anchoring transaction {} with index {} diverges from the Bitcoin \
+                     blockchain: {}",
+                    anchor.txid,
+                    anchor.index,
+                    divergence
+                );
+            }
+        }
+        // The marker keeps the watch-only mode on until the operators remove it.
+        if let Some(marker) = watch_only_marker {
+            if let Err(e) = File::create(marker) {
+                log::warn!(
+                    "Unable to create the maintenance marker {}. {}",
+                    marker.display(),
+                    e
+                );
+            }
+        }
+        toggle.set(true);
+        log::warn!(
+            "Sync utility works in the watch-only mode, anchoring proposals will not be signed."
+        );
+        true
+    }
+
+    /// Makes the Bitcoin node wallet watch the following anchoring address, so the
+    /// operators are able to pre-fund it before the transition completes.
+    async fn prewatch_following_address<W>(
+        client: &ApiClient,
+        relay: &W,
+        watched_address: &mut Option<btc::Address>,
+    ) where
+        W: WalletOps,
+        W::Error: std::fmt::Display,
+    {
+        let descriptor = match client.get_following_descriptor().await {
+            Ok(Some(descriptor)) => descriptor,
+            Ok(None) => return,
+            Err(e) => {
+                log::error!("An error in the anchoring API client occurred. {}", e);
+                return;
+            }
+        };
+        if watched_address.as_ref() == Some(&descriptor.address) {
+            return;
+        }
+
+        match relay
+            .watch_address(&descriptor, "exonum-anchoring-following")
+            .await
+        {
+            Ok(()) => {
+                log::info!(
+                    "Bitcoin node watches the following anchoring address {}, \
+                     descriptor is {}",
+                    descriptor.address,
+                    descriptor.descriptor
+                );
+                *watched_address = Some(descriptor.address);
+            }
+            Err(e) => log::warn!("Unable to watch the following anchoring address. {}", e),
+        }
+    }
+
+    /// Rescans the Bitcoin node wallet if it has lost the transactions of the anchoring
+    /// address, for example, after the anchoring node has been restored from a backup.
+    /// Returns `false` if the check should be retried.
+    async fn restore_watch_data<W>(
+        client: &ApiClient,
+        relay: &W,
+        fallback_height: Option<u64>,
+    ) -> bool
+    where
+        W: WalletOps,
+        W::Error: std::fmt::Display,
+    {
+        let anchors = async {
+            let count = client.transactions_count().await?.value;
+            if count == 0 {
+                return Ok::<_, reqwest::Error>(None);
+            }
+            let first_tx = client.transaction_with_index(0).await?;
+            let latest_tx = client.transaction_with_index(count - 1).await?;
+            let descriptor = client.get_actual_descriptor().await?;
+            Ok(match (first_tx, latest_tx) {
+                (Some(first_tx), Some(latest_tx)) => Some((descriptor, first_tx, latest_tx)),
+                _ => None,
+            })
+        };
+        let (descriptor, first_tx, latest_tx) = match anchors.await {
+            Ok(Some(anchors)) => anchors,
+            // There is nothing to watch before the first anchoring transaction.
+            Ok(None) => return true,
+            Err(e) => {
+                log::error!("An error in the anchoring API client occurred. {}", e);
+                return false;
+            }
+        };
+
+        // The first anchoring transaction spends the initial funding transaction.
+        let funding_tx = btc::Sha256d::from(first_tx.0.input[0].previous_output.txid);
+        match relay
+            .restore_watch_data(
+                &descriptor,
+                "exonum-anchoring",
+                latest_tx.id(),
+                funding_tx,
+                fallback_height,
+            )
+            .await
+        {
+            Ok(None) => {}
+            Ok(Some(height)) => log::warn!(
+                "Bitcoin node wallet has lost the anchoring transactions, the anchoring \
+                 address {} has been imported and the blocks have been rescanned from \
+                 the height {}",
+                descriptor.address,
+                height
+            ),
+            Err(e) => log::error!(
+                "Unable to restore the watch data of the Bitcoin node wallet. If the Bitcoin \
+                 node does not maintain the transaction index, set the `rescan_start_height` \
+                 in the sync utility configuration. {}",
+                e
+            ),
+        }
+        true
+    }
+
+    /// Re-reads the RPC cookie file after the Bitcoin relay failure, since the Bitcoin node
+    /// creates a new cookie on each restart. The relay client is replaced if the cookie
+    /// differs from the previously applied one.
+    fn reload_rpc_cookie(
+        rpc_config: Option<&BitcoinRpcConfig>,
+        relay: &BitcoinRpcRelay,
+        applied_cookie: &mut Option<String>,
+    ) {
+        let (rpc_config, cookie_file) = match rpc_config {
+            Some(rpc_config) => match &rpc_config.cookie_file {
+                Some(cookie_file) => (rpc_config, cookie_file),
+                None => return,
+            },
+            None => return,
+        };
+        let cookie = match fs::read_to_string(cookie_file) {
+            Ok(cookie) => cookie,
+            Err(e) => {
+                log::warn!(
+                    "Unable to read the Bitcoin RPC cookie file {}. {}",
+                    cookie_file.display(),
+                    e
+                );
+                return;
+            }
+        };
+        if applied_cookie.as_ref() == Some(&cookie) {
+            return;
+        }
+
+        let result = BitcoinRpcClient::try_from(rpc_config.clone())
+            .and_then(|client| relay.replace_client(client));
+        match result {
+            Ok(_) => {
+                log::info!("Bitcoin RPC cookie has been reloaded.");
+                *applied_cookie = Some(cookie);
+            }
+            Err(e) => log::warn!("Unable to apply the reloaded Bitcoin RPC cookie. {}", e),
+        }
+    }
+
+    fn rotate_rpc_credentials(
+        &self,
+        rpc_config: &mut Option<BitcoinRpcConfig>,
+        relay: &BitcoinRpcRelay,
+    ) {
+        let new_rpc_config = match self.overlay.load(&self.config) {
+            Ok(sync_config) => sync_config.bitcoin_rpc_config,
+            Err(e) => {
+                log::error!("Unable to reload the sync utility configuration. {}", e);
+                return;
+            }
+        };
+        let new_rpc_config = match new_rpc_config {
+            Some(new_rpc_config) if Some(&new_rpc_config) != rpc_config.as_ref() => new_rpc_config,
+            _ => return,
+        };
+
+        let result = BitcoinRpcClient::try_from(new_rpc_config.clone())
+            .and_then(|client| relay.replace_client(client));
+        match result {
+            Ok(capabilities) => log::info!(
+                "Bitcoin RPC credentials have been rotated, relay capabilities: {:?}",
+                capabilities
+            ),
+            Err(e) => log::error!(
+                "Unable to apply the new Bitcoin RPC credentials, \
+                 the previous ones are used. {}",
+                e
+            ),
+        }
+        // Remember the failed configuration as well to avoid retrying it until
+        // the configuration file is changed again.
+        *rpc_config = Some(new_rpc_config);
+    }
+}
+
+impl ProveKeyPossessionCommand {
+    fn run(self) -> anyhow::Result<()> {
+        let sync_config = SyncConfig::load(&self.config)?;
+        let mut anchoring_config: AnchoringConfig =
+            serde_json::from_str(&fs::read_to_string(&self.anchoring_config)?)?;
+
+        let proofs = anchoring_config
+            .anchoring_keys
+            .iter()
+            .filter_map(|keys| {
+                let private_key = sync_config.bitcoin_key_pool.get(&keys.bitcoin_key)?;
+                Some(anchoring_config.prove_key_possession(&keys.service_key, private_key))
+            })
+            .collect::<Vec<_>>();
+        ensure!(
+            !proofs.is_empty(),
+            "The key pool does not contain any of the anchoring keys of the configuration"
+        );
+
+        for proof in proofs {
+            log::info!(
+                "Proved the possession of the Bitcoin key {}",
+                proof.bitcoin_key
+            );
+            anchoring_config
+                .key_proofs
+                .retain(|known| known.bitcoin_key != proof.bitcoin_key);
+            anchoring_config.key_proofs.push(proof);
         }
+        fs::write(
+            &self.anchoring_config,
+            serde_json::to_string_pretty(&anchoring_config)?,
+        )?;
+        Ok(())
+    }
+}
+
+impl RotateRpcCredentialsCommand {
+    fn run(self) -> anyhow::Result<()> {
+        let mut sync_config = SyncConfig::load(&self.config)?;
+        let rpc_config = sync_config
+            .bitcoin_rpc_config
+            .as_mut()
+            .ok_or_else(|| anyhow!("Bitcoin RPC is not configured"))?;
+
+        ensure!(
+            self.bitcoin_rpc_user.is_some() || self.bitcoin_rpc_password.is_some(),
+            "At least one of the Bitcoin RPC username and password should be specified"
+        );
+        // Only the given credentials are updated, the others are kept.
+        if let Some(user) = self.bitcoin_rpc_user {
+            rpc_config.user = Some(user);
+        }
+        if let Some(password) = self.bitcoin_rpc_password {
+            rpc_config.password = Some(password);
+        }
+        // Explicit credentials replace the cookie authentication.
+        rpc_config.cookie_file = None;
+        ensure!(
+            rpc_config.user.is_some() && rpc_config.password.is_some(),
+            "Both the Bitcoin RPC username and password should be specified to replace \
+             the cookie authentication"
+        );
+        sync_config.save(self.config)?;
+        log::info!("Updated Bitcoin RPC credentials in the sync utility configuration.");
+        Ok(())
+    }
+}
+
+impl ExportKeySharesCommand {
+    fn run(self) -> anyhow::Result<()> {
+        let sync_config = SyncConfig::load(&self.config)?;
+        let (bitcoin_key, private_key) = if let Some(bitcoin_key) = self.bitcoin_key {
+            let private_key = sync_config
+                .bitcoin_key_pool
+                .get(&bitcoin_key)
+                .ok_or_else(|| {
+                    anyhow!(
+                        "Key pool does not contain the private key for {}",
+                        bitcoin_key
+                    )
+                })?;
+            (bitcoin_key, private_key)
+        } else {
+            let mut keys = sync_config.bitcoin_key_pool.iter();
+            match (keys.next(), keys.next()) {
+                (Some((bitcoin_key, private_key)), None) => (*bitcoin_key, private_key),
+                (None, _) => bail!("Key pool is empty"),
+                (Some(_), Some(_)) => {
+                    bail!("Key pool contains several Bitcoin keys, please specify the key to split")
+                }
+            }
+        };
+
+        let passphrase = read_shares_passphrase(&self.passphrase_env)?;
+        let shares = btc::split_private_key(private_key, self.threshold, self.shares)?;
+        fs::create_dir_all(&self.output_dir)?;
+        for share in &shares {
+            let path = self
+                .output_dir
+                .join(format!("{}.share-{}", bitcoin_key, share.index));
+            let encrypted = share.encrypt(passphrase.as_bytes())?;
+            // Never overwrite the shares of the previous backups.
+            let mut file = create_private_file(&path)
+                .map_err(|e| anyhow!("Unable to create {}: {}", path.display(), e))?;
+            writeln!(file, "{}", serde_json::to_string_pretty(&encrypted)?)?;
+        }
+        log::info!(
+            "Split the Bitcoin key {} into {} shares with the threshold {}",
+            bitcoin_key,
+            shares.len(),
+            self.threshold
+        );
+        Ok(())
+    }
+}
+
+impl RecoverKeyCommand {
+    fn run(self) -> anyhow::Result<()> {
+        let mut sync_config = SyncConfig::load(&self.config)?;
+        let passphrase = read_shares_passphrase(&self.passphrase_env)?;
+        let shares = self
+            .shares
+            .iter()
+            .map(|path| {
+                serde_json::from_str::<btc::EncryptedKeyShare>(&fs::read_to_string(path)?)
+                    .map_err(anyhow::Error::from)
+                    .and_then(|share| Ok(share.decrypt(passphrase.as_bytes())?))
+                    .map_err(|e| anyhow!("Invalid key share in {}: {}", path.display(), e))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        let private_key = btc::combine_key_shares(&shares)?;
+        let context = bitcoin::secp256k1::Secp256k1::new();
+        let bitcoin_key = btc::PublicKey(private_key.0.public_key(&context));
+        sync_config
+            .bitcoin_key_pool
+            .insert(bitcoin_key, private_key);
+        sync_config.save(self.config)?;
+        log::info!(
+            "Restored the Bitcoin key {} from {} shares to the key pool",
+            bitcoin_key,
+            shares.len()
+        );
+        Ok(())
     }
 }
 
@@ -388,12 +2011,546 @@ impl GenerateKeypairCommand {
     }
 }
 
+impl OnboardCommand {
+    async fn run(self) -> anyhow::Result<()> {
+        let sync_config = SyncConfig::load(self.config)?;
+        let client = ApiClient::new(&sync_config.exonum_private_api, &sync_config.instance_name);
+
+        let config = PrivateApi::config(&client).await?;
+        let mut candidates = sync_config.bitcoin_key_pool.keys().filter(|key| {
+            !config
+                .anchoring_keys
+                .iter()
+                .any(|keys| &keys.bitcoin_key == *key)
+        });
+        let bitcoin_key = if let Some(bitcoin_key) = self.bitcoin_key {
+            ensure!(
+                sync_config.bitcoin_key_pool.contains_key(&bitcoin_key),
+                "Key pool does not contain the private key for {}",
+                bitcoin_key
+            );
+            bitcoin_key
+        } else {
+            match (candidates.next(), candidates.next()) {
+                (Some(bitcoin_key), None) => *bitcoin_key,
+                (None, _) => bail!("Key pool does not contain any unused Bitcoin keys"),
+                (Some(_), Some(_)) => bail!(
+                    "Key pool contains several unused Bitcoin keys, \
+                     please specify the key to submit"
+                ),
+            }
+        };
+
+        // The possession of the key is proved for the configuration, which includes it.
+        let mut onboarding_config = config;
+        onboarding_config.anchoring_keys.push(AnchoringKeys {
+            service_key: self.service_key,
+            bitcoin_key,
+        });
+        let key_proof = onboarding_config.prove_key_possession(
+            &self.service_key,
+            &sync_config.bitcoin_key_pool[&bitcoin_key],
+        );
+        let tx_hash = client
+            .submit_anchoring_key(SubmitAnchoringKey {
+                bitcoin_key,
+                key_proof,
+            })
+            .await?;
+        log::info!(
+            "Submitted anchoring key {} in transaction {}, the key awaits the approval \
+             of the anchoring nodes",
+            bitcoin_key,
+            tx_hash.to_hex()
+        );
+        Ok(())
+    }
+}
+
+impl ApproveKeyCommand {
+    async fn run(self) -> anyhow::Result<()> {
+        let sync_config = SyncConfig::load(self.config)?;
+        let client = ApiClient::new(&sync_config.exonum_private_api, &sync_config.instance_name);
+
+        let pending_config = client.get_pending_config().await?;
+        let submitted = pending_config
+            .submitted_keys
+            .into_iter()
+            .find(|submitted| submitted.service_key == self.service_key)
+            .ok_or_else(|| {
+                anyhow!(
+                    "Validator {} has no anchoring key awaiting the approval",
+                    self.service_key
+                )
+            })?;
+        let bitcoin_key = submitted.key_proof.bitcoin_key;
+        let key_proof = KeyPossessionProof {
+            bitcoin_key,
+            signature: hex::decode(&submitted.key_proof.signature)?,
+        };
+        let tx_hash = client
+            .submit_anchoring_key(SubmitAnchoringKey {
+                bitcoin_key,
+                key_proof,
+            })
+            .await?;
+        log::info!(
+            "Approved anchoring key {} of the validator {} in transaction {}",
+            bitcoin_key,
+            self.service_key,
+            tx_hash.to_hex()
+        );
+        Ok(())
+    }
+}
+
+impl UpdateParametersCommand {
+    async fn run(self) -> anyhow::Result<()> {
+        ensure!(
+            self.anchoring_interval.is_some() || self.transaction_fee.is_some(),
+            "Either the anchoring interval or the transaction fee should be specified"
+        );
+        let sync_config = SyncConfig::load(self.config)?;
+        let client = ApiClient::new(&sync_config.exonum_private_api, &sync_config.instance_name);
+
+        let parameters = UpdateParameters {
+            anchoring_interval: self.anchoring_interval.unwrap_or_default(),
+            transaction_fee: self.transaction_fee.unwrap_or_default(),
+        };
+        let tx_hash = client.update_parameters(parameters).await?;
+        log::info!(
+            "Proposed the anchoring parameters in transaction {}",
+            tx_hash.to_hex()
+        );
+        Ok(())
+    }
+}
+
+/// Result of a single self-test check.
+#[derive(Debug)]
+struct CheckReport {
+    /// Short name of the check.
+    name: &'static str,
+    /// Check outcome with the human-readable details.
+    outcome: Result<String, String>,
+}
+
+impl CheckReport {
+    fn new(name: &'static str, outcome: anyhow::Result<String>) -> Self {
+        Self {
+            name,
+            outcome: outcome.map_err(|e| e.to_string()),
+        }
+    }
+}
+
+impl CheckReport {
+    fn to_json(&self) -> serde_json::Value {
+        let (passed, details) = match &self.outcome {
+            Ok(details) => (true, details),
+            Err(details) => (false, details),
+        };
+        serde_json::json!({
+            "check": self.name,
+            "passed": passed,
+            "details": details,
+        })
+    }
+}
+
+impl std::fmt::Display for CheckReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.outcome {
+            Ok(details) => write!(f, "[PASS] {}: {}", self.name, details),
+            Err(details) => write!(f, "[FAIL] {}: {}", self.name, details),
+        }
+    }
+}
+
+impl DoctorCommand {
+    async fn run(self) -> anyhow::Result<()> {
+        let sync_config = self.overlay.load(&self.config)?;
+        let client = ApiClient::new(&sync_config.exonum_private_api, &sync_config.instance_name);
+
+        let mut reports = Vec::new();
+        let config = PrivateApi::config(&client).await;
+        reports.push(CheckReport::new(
+            "Exonum private API",
+            config
+                .as_ref()
+                .map(|config| format!("anchoring address is {}", config.anchoring_address()))
+                .map_err(|e| anyhow!("{}", e)),
+        ));
+        reports.push(CheckReport::new(
+            "Exonum consensus",
+            Self::check_consensus(&client).await,
+        ));
+
+        if let Ok(config) = &config {
+            reports.push(CheckReport::new(
+                "Anchoring private key",
+                Self::check_private_key(&sync_config, config),
+            ));
+            reports.push(CheckReport::new(
+                "Funding UTXO",
+                Self::check_funds(&client).await,
+            ));
+        }
+
+        if let Some(rpc_config) = sync_config.bitcoin_rpc_config.clone() {
+            match BitcoinRpcRelay::try_from(rpc_config) {
+                Ok(relay) => Self::check_relay(&mut reports, &relay, &sync_config, &config).await,
+                Err(e) => reports.push(CheckReport::new("Bitcoin RPC", Err(e.into()))),
+            }
+        } else {
+            reports.push(CheckReport::new(
+                "Bitcoin RPC",
+                Err(anyhow!("Bitcoin RPC is not configured")),
+            ));
+        }
+
+        for report in &reports {
+            println!("{}", report);
+        }
+        let failed = reports.iter().filter(|r| r.outcome.is_err()).count();
+        if failed > 0 {
+            bail!("{} of {} checks have failed", failed, reports.len());
+        }
+        Ok(())
+    }
+
+    async fn check_relay(
+        reports: &mut Vec<CheckReport>,
+        relay: &BitcoinRpcRelay,
+        sync_config: &SyncConfig,
+        config: &Result<AnchoringConfig, reqwest::Error>,
+    ) {
+        let capabilities = relay.capabilities();
+        reports.push(CheckReport::new(
+            "Bitcoin RPC",
+            Self::check_chain(&relay.client(), sync_config.bitcoin_network()),
+        ));
+        reports.push(CheckReport::new(
+            "Relay capabilities",
+            Ok(format!("{:?}", capabilities)),
+        ));
+        if let Ok(config) = config {
+            reports.push(CheckReport::new(
+                "Anchoring wallet",
+                Self::check_wallet(relay, &config.anchoring_address()).await,
+            ));
+        }
+        let fee_estimator = if capabilities.fee_estimation {
+            Self::check_fee_estimator(&relay.client())
+        } else {
+            Ok("not supported by the Bitcoin node, the configured fee is used".to_owned())
+        };
+        reports.push(CheckReport::new("Fee estimator", fee_estimator));
+    }
+
+    fn check_private_key(
+        sync_config: &SyncConfig,
+        config: &AnchoringConfig,
+    ) -> anyhow::Result<String> {
+        config
+            .anchoring_keys
+            .iter()
+            .find(|keys| sync_config.bitcoin_key_pool.contains_key(&keys.bitcoin_key))
+            .map(|keys| format!("found private key for {}", keys.bitcoin_key))
+            .ok_or_else(|| anyhow!("Key pool does not contain any of the anchoring keys"))
+    }
+
+    async fn check_consensus(client: &ApiClient) -> anyhow::Result<String> {
+        match client.consensus_status().await? {
+            ConsensusStatus::Active => Ok("node is connected to the majority of validators".into()),
+            status => Err(anyhow!(
+                "node is not connected to the majority of validators, consensus status is {:?}",
+                status
+            )),
+        }
+    }
+
+    async fn check_funds(client: &ApiClient) -> anyhow::Result<String> {
+        match client.anchoring_proposal().await? {
+            AnchoringProposalState::NoInitialFunds => {
+                Err(anyhow!("Initial funding transaction is absent"))
+            }
+            AnchoringProposalState::InsufficientFunds { total_fee, balance } => Err(anyhow!(
+                "Insufficient funds: total fee is {}, total balance is {}",
+                total_fee,
+                balance
+            )),
+            AnchoringProposalState::ReserveReached { remaining, reserve } => Err(anyhow!(
+                "Reserve reached: remaining balance is {}, reserve is {}",
+                remaining,
+                reserve
+            )),
+            AnchoringProposalState::DustChange {
+                remaining,
+                dust_threshold,
+            } => Err(anyhow!(
+                "Dust change: remaining balance is {}, dust threshold is {}",
+                remaining,
+                dust_threshold
+            )),
+            AnchoringProposalState::Halted { conflict } => Err(anyhow!(
+                "Anchoring is halted due to the conflicting proposal {} for height {}",
+                conflict.conflicting_txid,
+                conflict.anchoring_height
+            )),
+            state => Ok(format!("{:?}", state)),
+        }
+    }
+
+    fn check_chain(
+        relay: &BitcoinRpcClient,
+        network: Option<bitcoin::Network>,
+    ) -> anyhow::Result<String> {
+        let info: serde_json::Value = relay.call("getblockchaininfo", &[])?;
+        let chain = info["chain"].as_str().unwrap_or_default().to_owned();
+        let expected_chain = match network {
+            Some(bitcoin::Network::Bitcoin) => "main",
+            Some(bitcoin::Network::Testnet) => "test",
+            Some(bitcoin::Network::Regtest) => "regtest",
+            None => return Ok(format!("connected to the `{}` chain", chain)),
+        };
+        ensure!(
+            chain == expected_chain,
+            "Bitcoin node is on the `{}` chain, but `{}` is expected",
+            chain,
+            expected_chain
+        );
+        Ok(format!("connected to the `{}` chain", chain))
+    }
+
+    async fn check_wallet(
+        relay: &BitcoinRpcRelay,
+        address: &btc::Address,
+    ) -> anyhow::Result<String> {
+        let info: serde_json::Value = relay
+            .client()
+            .call("getaddressinfo", &[address.to_string().into()])?;
+        let is_watched = info["iswatchonly"].as_bool().unwrap_or_default()
+            || info["ismine"].as_bool().unwrap_or_default();
+        ensure!(
+            is_watched,
+            "Anchoring address {} is not watched by the Bitcoin node wallet",
+            address
+        );
+        let utxos = relay.unspent_outputs(address).await?;
+        Ok(format!(
+            "address {} is watched, {} unspent outputs with the total value {} satoshis",
+            address,
+            utxos.len(),
+            utxos.iter().map(|utxo| utxo.value).sum::<u64>()
+        ))
+    }
+
+    fn check_fee_estimator(relay: &BitcoinRpcClient) -> anyhow::Result<String> {
+        let estimate: serde_json::Value = relay.call("estimatesmartfee", &[6.into()])?;
+        let fee_rate = estimate["feerate"]
+            .as_f64()
+            .ok_or_else(|| anyhow!("Fee estimation is not available: {}", estimate["errors"]))?;
+        Ok(format!("estimated fee rate is {} BTC/kB", fee_rate))
+    }
+}
+
+impl FundingTemplateCommand {
+    async fn run(self) -> anyhow::Result<()> {
+        // Private API has the method with the same name as the public one.
+        use exonum_btc_anchoring::api::PublicApi;
+
+        ensure!(self.amount > 0, "Funding amount should be positive");
+        let client = ApiClient::new(&self.exonum_api, &self.instance_name);
+        let address = if self.following {
+            client
+                .following_address()
+                .await?
+                .ok_or_else(|| anyhow!("Anchoring is not in the transition state"))?
+        } else {
+            client.actual_address().await?
+        };
+
+        let mut template = btc::FundingTemplate::new(address.clone(), self.amount);
+        if let Some(label) = self.label {
+            template = template.with_label(label);
+        }
+        let output = serde_json::json!({
+            "address": address,
+            "amount": self.amount,
+            "psbt": template.psbt_base64(),
+            "uri": template.uri(),
+        });
+        println!("{}", serde_json::to_string_pretty(&output)?);
+        Ok(())
+    }
+}
+
+impl VerifyAnchorCommand {
+    async fn run(self) -> anyhow::Result<()> {
+        let txid = btc::Sha256d::from(self.txid);
+        let client = ApiClient::new(&self.exonum_api, &self.instance_name);
+        let relay = match self.btc {
+            BitcoinBackend::Rpc => BitcoinRpcRelay::try_from(BitcoinRpcConfig {
+                host: self.bitcoin_rpc_host.clone(),
+                user: self.bitcoin_rpc_user.clone(),
+                password: self.bitcoin_rpc_password.clone(),
+                cookie_file: None,
+                wallet: self.bitcoin_rpc_wallet.clone(),
+                fallback_hosts: Vec::new(),
+                retry_policy: RetryPolicy::default(),
+                timeouts: RpcTimeouts::default(),
+                tls: None,
+                proxy: None,
+            })?,
+        };
+
+        let mut reports = Vec::new();
+        let anchor = self.check_exonum_proof(&client, txid).await;
+        reports.push(CheckReport::new(
+            "Exonum proof",
+            anchor
+                .as_ref()
+                .map(|(index, _)| format!("transaction has index {} in the anchoring chain", index))
+                .map_err(|e| anyhow!("{}", e)),
+        ));
+        if let Ok((index, transaction)) = &anchor {
+            reports.push(CheckReport::new(
+                "Payload",
+                Self::check_payload(transaction),
+            ));
+            reports.push(CheckReport::new(
+                "Chain linkage",
+                self.check_linkage(&client, *index, transaction).await,
+            ));
+        }
+        reports.push(CheckReport::new(
+            "Bitcoin confirmations",
+            self.check_confirmations(&client, &relay, txid).await,
+        ));
+
+        let passed = reports.iter().all(|report| report.outcome.is_ok());
+        let output = serde_json::json!({
+            "txid": txid.to_string(),
+            "passed": passed,
+            "checks": reports.iter().map(CheckReport::to_json).collect::<Vec<_>>(),
+        });
+        println!("{}", serde_json::to_string_pretty(&output)?);
+        if !passed {
+            std::process::exit(1);
+        }
+        Ok(())
+    }
+
+    async fn check_exonum_proof(
+        &self,
+        client: &ApiClient,
+        txid: btc::Sha256d,
+    ) -> anyhow::Result<(u64, btc::Transaction)> {
+        let proof = client.find_anchor(txid).await?;
+        let (index, transaction) = verify_transaction_proof(proof, &self.validator_keys)?
+            .ok_or_else(|| anyhow!("Transaction is absent in the anchoring chain"))?;
+        ensure!(
+            transaction.id() == txid,
+            "Proof contains the transaction {} instead of the requested one",
+            transaction.id()
+        );
+        Ok((index, transaction))
+    }
+
+    fn check_payload(transaction: &btc::Transaction) -> anyhow::Result<String> {
+        let payload = transaction
+            .anchoring_payload()
+            .ok_or_else(|| anyhow!("Transaction does not contain the anchoring payload"))?;
+        Ok(format!(
+            "anchors the block {} at height {}",
+            payload.block_hash.to_hex(),
+            payload.block_height
+        ))
+    }
+
+    async fn check_linkage(
+        &self,
+        client: &ApiClient,
+        index: u64,
+        transaction: &btc::Transaction,
+    ) -> anyhow::Result<String> {
+        let recovered = transaction
+            .anchoring_payload()
+            .and_then(|payload| payload.prev_tx_chain);
+        if let Some(prev_tx_chain) = recovered {
+            return Ok(format!(
+                "transaction recovers the anchoring chain ending with {}",
+                prev_tx_chain
+            ));
+        }
+        if index == 0 {
+            return Ok("transaction is the first one in the anchoring chain".to_owned());
+        }
+
+        let prev_txid = transaction.prev_tx_id();
+        let (prev_index, _) = self.check_exonum_proof(client, prev_txid).await?;
+        ensure!(
+            prev_index + 1 == index,
+            "Transaction spends the anchoring transaction {} with index {} instead of {}",
+            prev_txid,
+            prev_index,
+            index - 1
+        );
+        Ok(format!(
+            "transaction spends the previous anchor {}",
+            prev_txid
+        ))
+    }
+
+    async fn check_confirmations(
+        &self,
+        client: &ApiClient,
+        relay: &BitcoinRpcRelay,
+        txid: btc::Sha256d,
+    ) -> anyhow::Result<String> {
+        // The recorded block hash allows to look up the transaction without `txindex`.
+        let block_hash = client
+            .get_anchor_block(txid)
+            .await
+            .ok()
+            .and_then(|anchor_block| anchor_block.block_hash);
+        let status = match block_hash {
+            Some(block_hash) => relay.transaction_status_in_block(txid, block_hash).await?,
+            None => relay.transaction_status(txid).await?,
+        };
+        match status {
+            TransactionStatus::Committed(confirmations)
+                if confirmations >= self.min_confirmations =>
+            {
+                Ok(format!("transaction has {} confirmations", confirmations))
+            }
+            TransactionStatus::Committed(confirmations) => bail!(
+                "transaction has {} confirmations, at least {} required",
+                confirmations,
+                self.min_confirmations
+            ),
+            status => bail!("transaction is not committed, status is {:?}", status),
+        }
+    }
+}
+
 impl Commands {
     async fn run(self) -> anyhow::Result<()> {
         match self {
             Commands::GenerateConfig(cmd) => cmd.run(),
             Commands::GenerateKeypair(cmd) => cmd.run(),
             Commands::Run(cmd) => cmd.run().await,
+            Commands::Doctor(cmd) => cmd.run().await,
+            Commands::Onboard(cmd) => cmd.run().await,
+            Commands::ApproveKey(cmd) => cmd.run().await,
+            Commands::UpdateParameters(cmd) => cmd.run().await,
+            Commands::ProveKeyPossession(cmd) => cmd.run(),
+            Commands::RotateRpcCredentials(cmd) => cmd.run(),
+            Commands::ExportKeyShares(cmd) => cmd.run(),
+            Commands::RecoverKey(cmd) => cmd.run(),
+            Commands::VerifyAnchor(cmd) => cmd.run().await,
+            Commands::FundingTemplate(cmd) => cmd.run().await,
         }
     }
 }
@@ -18,12 +18,20 @@
 //! unauthorized use, and private part intended to be used by [sync][sync] module.
 //! Private part is implementation detail and should not be used directly.
 //!
+//! Public endpoints are also available in the versioned form with the stable data types,
+//! see the [v1][v1] module for details.
+//!
 //! [sync]: ../sync/index.html
+//! [v1]: v1/index.html
 
 use anyhow::{anyhow, ensure};
 use async_trait::async_trait;
 use btc_transaction_utils::{p2wsh, TxInRef};
-use exonum::{blockchain::IndexProof, crypto::Hash, helpers::Height};
+use exonum::{
+    blockchain::IndexProof,
+    crypto::Hash,
+    helpers::{Height, ValidateInput},
+};
 use exonum_merkledb::ListProof;
 use exonum_rust_runtime::{
     api::{self, ServiceApiBuilder, ServiceApiState},
@@ -31,17 +39,19 @@ use exonum_rust_runtime::{
 };
 use serde_derive::{Deserialize, Serialize};
 
-use std::cmp::{
-    self,
-    Ordering::{self, Equal, Greater, Less},
-};
-
 use crate::{
-    blockchain::{AddFunds, BtcAnchoringInterface, Schema, SignInput},
+    blockchain::{
+        data_layout::ProposalConflict, AddFunds, BtcAnchoringInterface, ConfirmAnchorFinality,
+        ImportAnchor, RecordAnchorBlock, ReportAnchorStatus, ReportRelayStatus, Schema, SignInput,
+        SubmitAnchoringKey, UpdateParameters,
+    },
     btc,
-    config::Config,
+    config::{AnchoringKeys, Config},
 };
 
+pub mod openapi;
+pub mod v1;
+
 /// A proof of existence for an anchoring transaction at the given height.
 #[derive(Debug, Serialize, Deserialize)]
 pub struct TransactionProof {
@@ -64,6 +74,9 @@ pub enum AnchoringProposalState {
         // `UnspentTxOutValue::Balance` variant. [ECR-3222]
         /// Input transactions.
         inputs: Vec<btc::Transaction>,
+        /// Anchoring epoch of the proposal.
+        #[serde(default)]
+        epoch: u64,
     },
     /// Insufficient funds to create an anchoring transaction proposal. Please fill up an anchoring wallet.
     InsufficientFunds {
@@ -72,23 +85,65 @@ pub enum AnchoringProposalState {
         /// Available balance.
         balance: u64,
     },
+    /// Anchoring transaction proposal spends the funds reserved in the anchoring wallet.
+    /// Please fill up an anchoring wallet.
+    ReserveReached {
+        /// Remaining balance after the anchoring transaction.
+        remaining: u64,
+        /// Reserved balance.
+        reserve: u64,
+    },
+    /// Change output of the anchoring transaction proposal is below the dust threshold.
+    /// The change output carries the anchoring chain, so it is never folded into the fee,
+    /// and the anchoring stops until the wallet is replenished by at least
+    /// `dust_threshold - remaining` satoshis. Please fill up an anchoring wallet.
+    DustChange {
+        /// Remaining balance after the anchoring transaction.
+        remaining: u64,
+        /// Dust threshold.
+        dust_threshold: u64,
+    },
     /// Initial funding transaction is absent.
     NoInitialFunds,
+    /// The anchoring has not been started yet.
+    NotStarted {
+        /// Exonum blockchain height from which the anchoring begins.
+        start_height: Height,
+    },
+    /// Finalization of the anchoring transaction proposal is halted, because several anchoring
+    /// nodes have signed conflicting transactions for the same anchoring height and epoch.
+    /// The configuration change approved by the validators resumes the finalization.
+    Halted {
+        /// The conflict which has halted the finalization of the proposal.
+        conflict: ProposalConflict,
+    },
 }
 
 impl AnchoringProposalState {
     fn try_from_proposal(
         proposal: Option<Result<(btc::Transaction, Vec<btc::Transaction>), btc::BuilderError>>,
+        epoch: u64,
     ) -> Result<Self, api::Error> {
         match proposal {
             None => Ok(AnchoringProposalState::None),
             Some(Ok((transaction, inputs))) => Ok(AnchoringProposalState::Available {
                 transaction,
                 inputs,
+                epoch,
             }),
             Some(Err(btc::BuilderError::InsufficientFunds { total_fee, balance })) => {
                 Ok(AnchoringProposalState::InsufficientFunds { total_fee, balance })
             }
+            Some(Err(btc::BuilderError::ReserveReached { remaining, reserve })) => {
+                Ok(AnchoringProposalState::ReserveReached { remaining, reserve })
+            }
+            Some(Err(btc::BuilderError::DustChange {
+                remaining,
+                dust_threshold,
+            })) => Ok(AnchoringProposalState::DustChange {
+                remaining,
+                dust_threshold,
+            }),
             Some(Err(btc::BuilderError::NoInputs)) => Ok(AnchoringProposalState::NoInitialFunds),
             Some(Err(e)) => Err(api::Error::internal(e)),
         }
@@ -198,6 +253,21 @@ pub trait PrivateApi {
     /// [`AddFunds`]: ../blockchain/struct.AddFunds.html
     /// [`Hash`]: https://docs.rs/exonum-crypto/latest/exonum_crypto/struct.Hash.html
     async fn add_funds(&self, transaction: btc::Transaction) -> Result<Hash, Self::Error>;
+    /// Imports the external transaction, which spends the output of the latest anchoring
+    /// transaction or conflicts with it.
+    ///
+    /// The transaction will be applied if 2/3+1 anchoring nodes sent it.
+    ///
+    /// | Property    | Value |
+    /// |-------------|-------|
+    /// | Path        | `/api/services/{btc_anchoring}/import-anchor` |
+    /// | Method      | POST   |
+    /// | Query type  | [`ImportAnchor`] |
+    /// | Return type | [`Hash`] |
+    ///
+    /// [`ImportAnchor`]: ../blockchain/struct.ImportAnchor.html
+    /// [`Hash`]: https://docs.rs/exonum-crypto/latest/exonum_crypto/struct.Hash.html
+    async fn import_anchor(&self, transaction: btc::Transaction) -> Result<Hash, Self::Error>;
     /// Returns a proposal for the next anchoring transaction, if it makes sense.
     /// If there is not enough satoshis to create a proposal an error is returned.
     ///
@@ -247,10 +317,109 @@ pub trait PrivateApi {
     ///
     /// [`AnchoringChainLength`]: struct.AnchoringChainLength.html
     async fn transactions_count(&self) -> Result<AnchoringChainLength, Self::Error>;
+    /// Creates and broadcasts the `SubmitAnchoringKey` transaction, which is signed
+    /// by the current node, and returns its hash.
+    ///
+    /// The node should be a validator, which does not participate in the anchoring yet.
+    /// The key should be accompanied by the proof of its possession for the configuration
+    /// resulting from the inclusion of the key, see `v1/pending-config`. The anchoring
+    /// node approves the key submitted by another validator by sending the same key
+    /// and proof.
+    ///
+    /// | Property    | Value |
+    /// |-------------|-------|
+    /// | Path        | `/api/services/{btc_anchoring}/submit-anchoring-key` |
+    /// | Method      | POST   |
+    /// | Query type  | [`SubmitAnchoringKey`] |
+    /// | Return type | [`Hash`] |
+    ///
+    /// [`SubmitAnchoringKey`]: ../blockchain/struct.SubmitAnchoringKey.html
+    /// [`Hash`]: https://docs.rs/exonum-crypto/latest/exonum_crypto/struct.Hash.html
+    async fn submit_anchoring_key(
+        &self,
+        submit_key: SubmitAnchoringKey,
+    ) -> Result<Hash, Self::Error>;
+    /// Creates and broadcasts the `UpdateParameters` transaction, which is signed
+    /// by the current node, and returns its hash.
+    ///
+    /// The parameters will be applied if 2/3+1 anchoring nodes sent the same message.
+    ///
+    /// | Property    | Value |
+    /// |-------------|-------|
+    /// | Path        | `/api/services/{btc_anchoring}/update-parameters` |
+    /// | Method      | POST   |
+    /// | Query type  | [`UpdateParameters`] |
+    /// | Return type | [`Hash`] |
+    ///
+    /// [`UpdateParameters`]: ../blockchain/struct.UpdateParameters.html
+    /// [`Hash`]: https://docs.rs/exonum-crypto/latest/exonum_crypto/struct.Hash.html
+    async fn update_parameters(&self, parameters: UpdateParameters) -> Result<Hash, Self::Error>;
+    /// Creates and broadcasts the `ConfirmAnchorFinality` transaction, which is signed
+    /// by the current node, and returns its hash.
+    ///
+    /// The anchoring transaction will be marked as final if 2/3+1 anchoring nodes
+    /// sent the same message.
+    ///
+    /// | Property    | Value |
+    /// |-------------|-------|
+    /// | Path        | `/api/services/{btc_anchoring}/confirm-anchor-finality` |
+    /// | Method      | POST   |
+    /// | Query type  | [`btc::Sha256d`] |
+    /// | Return type | [`Hash`] |
+    ///
+    /// [`btc::Sha256d`]: ../btc/struct.Sha256d.html
+    /// [`Hash`]: https://docs.rs/exonum-crypto/latest/exonum_crypto/struct.Hash.html
+    async fn confirm_anchor_finality(&self, txid: btc::Sha256d) -> Result<Hash, Self::Error>;
+    /// Creates and broadcasts the `ReportAnchorStatus` transaction, which is signed
+    /// by the current node, and returns its hash.
+    ///
+    /// | Property    | Value |
+    /// |-------------|-------|
+    /// | Path        | `/api/services/{btc_anchoring}/report-anchor-status` |
+    /// | Method      | POST   |
+    /// | Query type  | [`ReportAnchorStatus`] |
+    /// | Return type | [`Hash`] |
+    ///
+    /// [`ReportAnchorStatus`]: ../blockchain/struct.ReportAnchorStatus.html
+    /// [`Hash`]: https://docs.rs/exonum-crypto/latest/exonum_crypto/struct.Hash.html
+    async fn report_anchor_status(&self, report: ReportAnchorStatus) -> Result<Hash, Self::Error>;
+    /// Creates and broadcasts the `RecordAnchorBlock` transaction, which is signed
+    /// by the current node, and returns its hash.
+    ///
+    /// | Property    | Value |
+    /// |-------------|-------|
+    /// | Path        | `/api/services/{btc_anchoring}/record-anchor-block` |
+    /// | Method      | POST   |
+    /// | Query type  | [`RecordAnchorBlock`] |
+    /// | Return type | [`Hash`] |
+    ///
+    /// [`RecordAnchorBlock`]: ../blockchain/struct.RecordAnchorBlock.html
+    /// [`Hash`]: https://docs.rs/exonum-crypto/latest/exonum_crypto/struct.Hash.html
+    async fn record_anchor_block(&self, record: RecordAnchorBlock) -> Result<Hash, Self::Error>;
+    /// Creates and broadcasts the `ReportRelayStatus` transaction, which is signed
+    /// by the current node, and returns its hash.
+    ///
+    /// | Property    | Value |
+    /// |-------------|-------|
+    /// | Path        | `/api/services/{btc_anchoring}/report-relay-status` |
+    /// | Method      | POST   |
+    /// | Query type  | [`ReportRelayStatus`] |
+    /// | Return type | [`Hash`] |
+    ///
+    /// [`ReportRelayStatus`]: ../blockchain/struct.ReportRelayStatus.html
+    /// [`Hash`]: https://docs.rs/exonum-crypto/latest/exonum_crypto/struct.Hash.html
+    async fn report_relay_status(&self, report: ReportRelayStatus) -> Result<Hash, Self::Error>;
 }
 
 struct ApiImpl(ServiceApiState);
 
+/// Returns the error for the requests which require the anchoring configuration.
+fn not_configured() -> api::Error {
+    api::Error::not_found()
+        .title("Anchoring configuration not found")
+        .detail("Anchoring service has not been configured yet")
+}
+
 impl ApiImpl {
     fn broadcaster(&self) -> api::Result<Broadcaster> {
         self.0.broadcaster().ok_or_else(|| {
@@ -261,7 +430,9 @@ impl ApiImpl {
     }
 
     fn actual_config(self) -> api::Result<Config> {
-        Ok(Schema::new(self.0.service_data()).actual_config())
+        Schema::new(self.0.service_data())
+            .try_actual_config()
+            .ok_or_else(not_configured)
     }
 
     fn verify_sign_input(&self, sign_input: &SignInput) -> anyhow::Result<()> {
@@ -276,7 +447,9 @@ impl ApiImpl {
             .ok_or_else(|| anyhow!("Missing input with index: {}", sign_input.input))?;
 
         // Find corresponding Bitcoin key.
-        let config = schema.actual_config();
+        let config = schema
+            .try_actual_config()
+            .ok_or_else(|| anyhow!("Anchoring service has not been configured yet."))?;
         let bitcoin_key = config
             .find_bitcoin_key(&self.0.service_key())
             .ok_or_else(|| anyhow!("This node is not an anchoring node."))?
@@ -297,7 +470,9 @@ impl ApiImpl {
         let txid = tx.id();
 
         let schema = Schema::new(self.0.service_data());
-        let config = schema.actual_config();
+        let config = schema
+            .try_actual_config()
+            .ok_or_else(|| anyhow!("Anchoring service has not been configured yet."))?;
         ensure!(
             !schema.spent_funding_transactions.contains(&txid),
             "Funding transaction {} has been already used.",
@@ -331,9 +506,8 @@ impl ApiImpl {
 // Public API implementation
 impl ApiImpl {
     async fn actual_address(self) -> api::Result<btc::Address> {
-        Ok(Schema::new(self.0.service_data())
-            .actual_config()
-            .anchoring_address())
+        self.actual_config()
+            .map(|config| config.anchoring_address())
     }
 
     async fn following_address(self) -> api::Result<Option<btc::Address>> {
@@ -344,52 +518,25 @@ impl ApiImpl {
 
     async fn find_transaction(self, height: Option<Height>) -> api::Result<TransactionProof> {
         let anchoring_schema = Schema::new(self.0.service_data());
-        let tx_chain = anchoring_schema.transactions_chain;
+        let tx_chain = &anchoring_schema.transactions_chain;
 
         if tx_chain.is_empty() {
             return Ok(self.transaction_proof(0));
         }
 
-        let tx_index = if let Some(height) = height {
-            // Handmade binary search.
-            let f = |index| -> Ordering {
-                // index is always in [0, size), that means index is >= 0 and < size.
-                // index >= 0: by definition
-                // index < size: index = size / 2 + size / 4 + size / 8 ...
-                let other = tx_chain
-                    .get(index)
-                    .unwrap()
-                    .anchoring_payload()
-                    .unwrap()
-                    .block_height;
-                other.cmp(&height)
-            };
-
-            let mut base = 0;
-            let mut size = tx_chain.len();
-            while size > 1 {
-                let half = size / 2;
-                let mid = base + half;
-                let cmp = f(mid);
-                base = if cmp == Greater { base } else { mid };
-                size -= half;
-            }
-            // Don't forget to check base value.
-            let cmp = f(base);
-            if cmp == Equal {
-                base
-            } else {
-                cmp::min(base + (cmp == Less) as u64, tx_chain.len() - 1)
-            }
-        } else {
-            tx_chain.len() - 1
-        };
+        let tx_index = height
+            .and_then(|height| anchoring_schema.find_transaction_index(height))
+            .unwrap_or_else(|| tx_chain.len() - 1);
 
         Ok(self.transaction_proof(tx_index))
     }
 
     async fn config(self) -> api::Result<Config> {
-        self.actual_config().map_err(api::Error::internal)
+        self.actual_config()
+    }
+
+    async fn openapi(self) -> api::Result<serde_json::Value> {
+        Ok(openapi::generate(&self.0.instance().name))
     }
 }
 
@@ -422,13 +569,157 @@ impl ApiImpl {
             .map_err(|e| api::Error::internal(e).title("Add funds request failed"))
     }
 
+    async fn import_anchor(self, transaction: btc::Transaction) -> Result<Hash, api::Error> {
+        self.broadcaster()?
+            .import_anchor((), ImportAnchor { transaction })
+            .await
+            .map_err(|e| api::Error::internal(e).title("Import anchor request failed"))
+    }
+
+    async fn submit_anchoring_key(
+        self,
+        submit_key: SubmitAnchoringKey,
+    ) -> Result<Hash, api::Error> {
+        let schema = Schema::new(self.0.service_data());
+        schema.try_actual_config().ok_or_else(not_configured)?;
+        // The key submitted by another validator is approved by this node.
+        let owner = schema
+            .anchoring_key_proofs
+            .iter()
+            .find(|(owner, proof)| {
+                proof.bitcoin_key == submit_key.bitcoin_key
+                    && !schema.pending_anchoring_keys.contains(owner)
+            })
+            .map_or_else(|| self.0.service_key(), |(owner, _)| owner);
+        let key_exists = schema.pending_config().anchoring_keys.iter().any(|keys| {
+            keys.service_key == self.0.service_key() || keys.bitcoin_key == submit_key.bitcoin_key
+        });
+        if owner == self.0.service_key() && key_exists {
+            return Err(api::Error::bad_request()
+                .title("Anchoring key verification has failed")
+                .detail("Anchoring key of this node has been already submitted."));
+        }
+        let keys = AnchoringKeys {
+            service_key: owner,
+            bitcoin_key: submit_key.bitcoin_key,
+        };
+        let config = schema.onboarding_config(keys.clone());
+        if !config.verify_key_proof(&keys, &submit_key.key_proof) {
+            return Err(api::Error::bad_request()
+                .title("Anchoring key verification has failed")
+                .detail("Proof of possession of the anchoring key is invalid."));
+        }
+
+        self.broadcaster()?
+            .submit_anchoring_key((), submit_key)
+            .await
+            .map_err(|e| api::Error::internal(e).title("Submit anchoring key request failed"))
+    }
+
+    async fn update_parameters(self, parameters: UpdateParameters) -> Result<Hash, api::Error> {
+        let config = Schema::new(self.0.service_data())
+            .try_actual_config()
+            .ok_or_else(not_configured)?;
+        parameters.apply_to(config).validate().map_err(|e| {
+            api::Error::bad_request()
+                .title("Anchoring parameters verification has failed")
+                .detail(e.to_string())
+        })?;
+
+        self.broadcaster()?
+            .update_parameters((), parameters)
+            .await
+            .map_err(|e| api::Error::internal(e).title("Update parameters request failed"))
+    }
+
+    async fn confirm_anchor_finality(self, txid: btc::Sha256d) -> Result<Hash, api::Error> {
+        let schema = Schema::new(self.0.service_data());
+        if schema.transaction_index(&txid).is_none() {
+            return Err(api::Error::not_found()
+                .title("Unknown anchoring transaction")
+                .detail(format!(
+                    "Anchoring chain doesn't contain transaction {}.",
+                    txid
+                )));
+        }
+
+        self.broadcaster()?
+            .confirm_anchor_finality((), ConfirmAnchorFinality { txid })
+            .await
+            .map_err(|e| api::Error::internal(e).title("Confirm anchor finality request failed"))
+    }
+
+    async fn report_anchor_status(self, report: ReportAnchorStatus) -> Result<Hash, api::Error> {
+        let schema = Schema::new(self.0.service_data());
+        if schema.transaction_index(&report.txid).is_none() {
+            return Err(api::Error::not_found()
+                .title("Unknown anchoring transaction")
+                .detail(format!(
+                    "Anchoring chain doesn't contain transaction {}.",
+                    report.txid
+                )));
+        }
+
+        self.broadcaster()?
+            .report_anchor_status((), report)
+            .await
+            .map_err(|e| api::Error::internal(e).title("Report anchor status request failed"))
+    }
+
+    async fn record_anchor_block(self, record: RecordAnchorBlock) -> Result<Hash, api::Error> {
+        let schema = Schema::new(self.0.service_data());
+        if schema.transaction_index(&record.txid).is_none() {
+            return Err(api::Error::not_found()
+                .title("Unknown anchoring transaction")
+                .detail(format!(
+                    "Anchoring chain doesn't contain transaction {}.",
+                    record.txid
+                )));
+        }
+
+        self.broadcaster()?
+            .record_anchor_block((), record)
+            .await
+            .map_err(|e| api::Error::internal(e).title("Record anchor block request failed"))
+    }
+
+    async fn report_relay_status(self, report: ReportRelayStatus) -> Result<Hash, api::Error> {
+        self.broadcaster()?
+            .report_relay_status((), report)
+            .await
+            .map_err(|e| api::Error::internal(e).title("Report relay status request failed"))
+    }
+
     async fn anchoring_proposal(self) -> Result<AnchoringProposalState, api::Error> {
         let core_schema = self.0.data().for_core();
         let anchoring_schema = Schema::new(self.0.service_data());
 
-        AnchoringProposalState::try_from_proposal(
-            anchoring_schema.actual_proposed_anchoring_transaction(core_schema),
-        )
+        let config = if let Some(config) = anchoring_schema.try_actual_config() {
+            config
+        } else {
+            return Ok(AnchoringProposalState::None);
+        };
+        if anchoring_schema.transactions_chain.is_empty()
+            && !config.is_started(core_schema.height())
+        {
+            return Ok(AnchoringProposalState::NotStarted {
+                start_height: config.start_height,
+            });
+        }
+
+        let proposal = anchoring_schema.actual_proposed_anchoring_transaction(core_schema);
+        let epoch = anchoring_schema.anchoring_epoch();
+        if let Some(Ok((transaction, _))) = &proposal {
+            let conflict = anchoring_schema.anchoring_halt().filter(|conflict| {
+                transaction.anchoring_payload().map_or(false, |payload| {
+                    conflict.is_for_slot(payload.block_height, epoch)
+                })
+            });
+            if let Some(conflict) = conflict {
+                return Ok(AnchoringProposalState::Halted { conflict });
+            }
+        }
+        AnchoringProposalState::try_from_proposal(proposal, epoch)
     }
 
     async fn transaction_with_index(self, index: u64) -> api::Result<Option<btc::Transaction>> {
@@ -460,6 +751,7 @@ pub struct IndexQuery {
 }
 
 pub(crate) fn wire(builder: &mut ServiceApiBuilder) {
+    v1::wire(builder);
     builder
         .public_scope()
         .endpoint("address/actual", |state, _query: ()| {
@@ -471,7 +763,8 @@ pub(crate) fn wire(builder: &mut ServiceApiBuilder) {
         .endpoint("find-transaction", |state, query: FindTransactionQuery| {
             ApiImpl(state).find_transaction(query.height)
         })
-        .endpoint("config", |state, _query: ()| ApiImpl(state).config());
+        .endpoint("config", |state, _query: ()| ApiImpl(state).config())
+        .endpoint("openapi.json", |state, _query: ()| ApiImpl(state).openapi());
     builder
         .private_scope()
         .endpoint_mut("sign-input", |state, query: SignInput| {
@@ -480,6 +773,29 @@ pub(crate) fn wire(builder: &mut ServiceApiBuilder) {
         .endpoint_mut("add-funds", |state, query: btc::Transaction| {
             ApiImpl(state).add_funds(query)
         })
+        .endpoint_mut("import-anchor", |state, query: btc::Transaction| {
+            ApiImpl(state).import_anchor(query)
+        })
+        .endpoint_mut(
+            "submit-anchoring-key",
+            |state, query: SubmitAnchoringKey| ApiImpl(state).submit_anchoring_key(query),
+        )
+        .endpoint_mut("update-parameters", |state, query: UpdateParameters| {
+            ApiImpl(state).update_parameters(query)
+        })
+        .endpoint_mut("confirm-anchor-finality", |state, query: btc::Sha256d| {
+            ApiImpl(state).confirm_anchor_finality(query)
+        })
+        .endpoint_mut(
+            "report-anchor-status",
+            |state, query: ReportAnchorStatus| ApiImpl(state).report_anchor_status(query),
+        )
+        .endpoint_mut("record-anchor-block", |state, query: RecordAnchorBlock| {
+            ApiImpl(state).record_anchor_block(query)
+        })
+        .endpoint_mut("report-relay-status", |state, query: ReportRelayStatus| {
+            ApiImpl(state).report_relay_status(query)
+        })
         .endpoint("anchoring-proposal", |state, _query: ()| {
             ApiImpl(state).anchoring_proposal()
         })
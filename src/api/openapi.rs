@@ -0,0 +1,298 @@
+// Copyright 2019 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! OpenAPI specification of the public anchoring API.
+
+use serde_json::{json, Map, Value};
+
+/// Description of a single public API endpoint.
+#[derive(Debug, Clone, Copy)]
+pub struct EndpointSpec {
+    /// Endpoint path relative to the service API root.
+    pub path: &'static str,
+    /// Short summary of the endpoint.
+    pub summary: &'static str,
+    /// Optional query parameters in form of `(name, type, description)`.
+    pub query: &'static [(&'static str, &'static str, &'static str)],
+}
+
+/// Public endpoints of the anchoring service.
+pub const PUBLIC_ENDPOINTS: &[EndpointSpec] = &[
+    EndpointSpec {
+        path: "address/actual",
+        summary: "Returns an actual anchoring address.",
+        query: &[],
+    },
+    EndpointSpec {
+        path: "address/following",
+        summary: "Returns the following anchoring address if the node is in the transition state.",
+        query: &[],
+    },
+    EndpointSpec {
+        path: "find-transaction",
+        summary: "Returns a proof for the anchoring transaction with the height \
+                  greater or equal to the given one.",
+        query: &[("height", "integer", "Exonum block height.")],
+    },
+    EndpointSpec {
+        path: "config",
+        summary: "Returns an actual anchoring configuration.",
+        query: &[],
+    },
+    EndpointSpec {
+        path: "v1/address/actual",
+        summary: "Returns an actual anchoring address.",
+        query: &[],
+    },
+    EndpointSpec {
+        path: "v1/address/following",
+        summary: "Returns the following anchoring address if the node is in the transition state.",
+        query: &[],
+    },
+    EndpointSpec {
+        path: "v1/address/actual-descriptor",
+        summary: "Returns the actual anchoring address with its redeem script and output \
+                  descriptor.",
+        query: &[],
+    },
+    EndpointSpec {
+        path: "v1/address/following-descriptor",
+        summary: "Returns the following anchoring address with its redeem script and output \
+                  descriptor, as soon as the following configuration is known.",
+        query: &[],
+    },
+    EndpointSpec {
+        path: "v1/find-transaction",
+        summary: "Returns a proof for the anchoring transaction with the height \
+                  greater or equal to the given one.",
+        query: &[("height", "integer", "Exonum block height.")],
+    },
+    EndpointSpec {
+        path: "v1/config",
+        summary: "Returns an actual anchoring configuration.",
+        query: &[],
+    },
+    EndpointSpec {
+        path: "v1/status",
+        summary: "Returns a summary of the current anchoring state.",
+        query: &[],
+    },
+    EndpointSpec {
+        path: "v1/checkpoint",
+        summary: "Returns the checkpoint of the anchoring state signed by the validators.",
+        query: &[],
+    },
+    EndpointSpec {
+        path: "v1/find-anchor",
+        summary: "Returns a proof for the anchoring transaction with the given identifier.",
+        query: &[("txid", "string", "Bitcoin transaction identifier.")],
+    },
+    EndpointSpec {
+        path: "v1/finality-certificate",
+        summary: "Returns a finality certificate for the Exonum transaction with the given hash.",
+        query: &[("tx_hash", "string", "Exonum transaction hash.")],
+    },
+    EndpointSpec {
+        path: "v1/pending-config",
+        summary: "Returns the anchoring keys submitted by the validators joining the anchoring.",
+        query: &[],
+    },
+    EndpointSpec {
+        path: "v1/preview-proposal",
+        summary: "Returns the anchoring transaction which the service would propose right now \
+                  without creating any service state.",
+        query: &[],
+    },
+    EndpointSpec {
+        path: "v1/fee-accounting",
+        summary: "Returns the fees consumed from the anchoring wallet within the anchoring epoch.",
+        query: &[(
+            "epoch",
+            "integer",
+            "Anchoring epoch, the current epoch is used if omitted.",
+        )],
+    },
+    EndpointSpec {
+        path: "v1/balance-history",
+        summary: "Returns the latest anchoring wallet balances with the forecast of the wallet \
+                  depletion at the current fee rates.",
+        query: &[(
+            "count",
+            "integer",
+            "Number of the latest records, 100 if omitted.",
+        )],
+    },
+    EndpointSpec {
+        path: "v1/anchor-stats",
+        summary: "Returns the fee rates, sizes and the input and output values of the latest \
+                  anchoring transactions finalized by the service.",
+        query: &[(
+            "count",
+            "integer",
+            "Number of the latest anchoring transactions, 100 if omitted.",
+        )],
+    },
+    EndpointSpec {
+        path: "v1/signature-audit",
+        summary: "Verifies the signatures of the recorded anchoring transactions against \
+                  the outputs which they spend, at most 1000 transactions at once.",
+        query: &[(
+            "from",
+            "integer",
+            "Position of the first audited anchoring transaction, 0 if omitted.",
+        )],
+    },
+    EndpointSpec {
+        path: "v1/anchors",
+        summary: "Exports the anchored blocks of the anchoring chain page by page, at most 1000 \
+                  transactions at once. The `to` field of the page is the start of the next one.",
+        query: &[
+            (
+                "from",
+                "integer",
+                "Position of the first exported anchoring transaction, 0 if omitted.",
+            ),
+            (
+                "count",
+                "integer",
+                "Number of the exported anchoring transactions, 1000 if omitted.",
+            ),
+        ],
+    },
+    EndpointSpec {
+        path: "v1/anchor-proofs",
+        summary: "Returns the proof of existence for a range of the anchoring transactions, \
+                  at most 100 transactions at once.",
+        query: &[
+            (
+                "from",
+                "integer",
+                "Position of the first proved anchoring transaction, 0 if omitted.",
+            ),
+            (
+                "count",
+                "integer",
+                "Number of the proved anchoring transactions, 100 if omitted.",
+            ),
+        ],
+    },
+    EndpointSpec {
+        path: "v1/health",
+        summary: "Returns the rolling health score of the anchoring computed from the share \
+                  of the anchors finalized on time and the signature participation rate.",
+        query: &[],
+    },
+    EndpointSpec {
+        path: "v1/anchor-status",
+        summary: "Returns the status of the latest anchoring transaction in the Bitcoin network \
+                  reported by the anchoring nodes: missing, in the mempool or confirmed.",
+        query: &[],
+    },
+    EndpointSpec {
+        path: "v1/anchor-block",
+        summary: "Returns the hash of the Bitcoin block containing the anchoring transaction, \
+                  which allows to fetch the transaction from the pruned Bitcoin nodes.",
+        query: &[("txid", "string", "Identifier of the anchoring transaction.")],
+    },
+    EndpointSpec {
+        path: "v1/proposal-conflicts",
+        summary: "Returns the evidence of the anchoring nodes signing the transactions, which \
+                  conflict with the anchoring proposals, and the halted proposal, if any.",
+        query: &[],
+    },
+    EndpointSpec {
+        path: "v1/relay-status",
+        summary: "Returns the health of the Bitcoin relays reported by the anchoring nodes.",
+        query: &[],
+    },
+    EndpointSpec {
+        path: "v1/next-anchor",
+        summary: "Predicts the Exonum height and the approximate time of the next anchoring \
+                  slot, taking into account the pending transition of the anchoring address.",
+        query: &[],
+    },
+    EndpointSpec {
+        path: "v1/tenant-usage",
+        summary: "Returns the data quota usage of the tenant.",
+        query: &[("tenant", "string", "Tenant key.")],
+    },
+    EndpointSpec {
+        path: "v1/tenant-receipt",
+        summary: "Returns a receipt proof for the data hash submitted by the tenant.",
+        query: &[
+            ("tenant", "string", "Tenant key."),
+            ("height", "integer", "Exonum block height."),
+            ("data_hash", "string", "Hash of the submitted data."),
+        ],
+    },
+];
+
+/// Generates an OpenAPI 3.0 document for the public endpoints of the service instance
+/// with the given name.
+pub fn generate(instance_name: &str) -> Value {
+    let paths = PUBLIC_ENDPOINTS
+        .iter()
+        .map(|endpoint| {
+            let parameters = endpoint
+                .query
+                .iter()
+                .map(|(name, ty, description)| {
+                    json!({
+                        "name": name,
+                        "in": "query",
+                        "required": false,
+                        "description": description,
+                        "schema": { "type": ty },
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            let path = format!("/api/services/{}/{}", instance_name, endpoint.path);
+            let operation = json!({
+                "get": {
+                    "summary": endpoint.summary,
+                    "parameters": parameters,
+                    "responses": {
+                        "200": { "description": "Successful response." },
+                        "400": { "description": "Bad request." },
+                        "500": { "description": "Internal server error." },
+                    },
+                },
+            });
+            (path, operation)
+        })
+        .collect::<Map<_, _>>();
+
+    json!({
+        "openapi": "3.0.0",
+        "info": {
+            "title": "Exonum Bitcoin anchoring service",
+            "version": env!("CARGO_PKG_VERSION"),
+        },
+        "paths": paths,
+    })
+}
+
+#[test]
+fn test_openapi_spec_paths() {
+    let spec = generate("btc_anchoring");
+    let paths = spec["paths"].as_object().unwrap();
+    assert_eq!(paths.len(), PUBLIC_ENDPOINTS.len());
+    assert!(paths.contains_key("/api/services/btc_anchoring/v1/config"));
+    assert_eq!(
+        spec["paths"]["/api/services/btc_anchoring/find-transaction"]["get"]["parameters"][0]
+            ["name"],
+        "height"
+    );
+}
@@ -0,0 +1,1785 @@
+// Copyright 2019 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Stable data types of the first version of the public anchoring API.
+//!
+//! Endpoints of this version are available with the `v1/` prefix, for example
+//! `/api/services/{btc_anchoring}/v1/config`. Unlike the unversioned endpoints,
+//! they never expose internal schema types directly, so their layout does not depend
+//! on the service storage format.
+
+use exonum::{
+    blockchain::{BlockProof, IndexProof, TxLocation},
+    crypto::{Hash, PublicKey},
+    helpers::Height,
+};
+use exonum_merkledb::{BinaryKey, BinaryValue, ListProof, MapProof};
+use exonum_rust_runtime::api::{self, ServiceApiBuilder};
+use serde_derive::{Deserialize, Serialize};
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::{
+    blockchain::{
+        audit::SignatureAuditReport,
+        data_layout::{self, TenantBlockId, TxInputId},
+        export::{AnchorsExport, MAX_EXPORTED_ANCHORS},
+        schema::HEALTH_WINDOW,
+        Schema,
+    },
+    btc, config,
+};
+
+use super::{not_configured, AnchoringProposalState, ApiImpl, FindTransactionQuery};
+
+/// A proof of existence for an anchoring transaction with the given index.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TransactionProof {
+    /// Proof of authenticity for the anchoring transactions chain within the database.
+    pub index_proof: IndexProof,
+    /// Proof for the specific transaction in the anchoring transactions chain.
+    pub transaction_proof: ListProof<btc::Transaction>,
+}
+
+/// Public keys of an anchoring node.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AnchoringKeys {
+    /// Service key is used to authorize service transactions.
+    pub service_key: PublicKey,
+    /// The Bitcoin public key is used to calculate the corresponding redeem script.
+    pub bitcoin_key: btc::PublicKey,
+}
+
+/// Client application which is allowed to submit data for anchoring.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Tenant {
+    /// Key is used to authorize data submissions of the tenant.
+    pub key: PublicKey,
+    /// Maximum number of data hashes which can be submitted by the tenant.
+    pub quota: u64,
+}
+
+/// Proof that the anchoring node possesses the private part of its Bitcoin key.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct KeyPossessionProof {
+    /// The Bitcoin public key of the anchoring node.
+    pub bitcoin_key: btc::PublicKey,
+    /// Hex-encoded DER signature of the key possession challenge.
+    pub signature: String,
+}
+
+/// Anchoring configuration.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Config {
+    /// Type of the used BTC network.
+    pub network: bitcoin::Network,
+    /// Public keys of the anchoring nodes.
+    pub anchoring_keys: Vec<AnchoringKeys>,
+    /// Interval in blocks between anchored blocks.
+    pub anchoring_interval: u64,
+    /// Fee per byte in satoshis.
+    pub transaction_fee: u64,
+    /// Exonum blockchain height from which the anchoring begins.
+    pub start_height: Height,
+    /// Skip anchoring if the blockchain state has not been changed since the latest anchor.
+    pub anchor_only_on_state_change: bool,
+    /// Client applications which are allowed to submit data for anchoring.
+    pub tenants: Vec<Tenant>,
+    /// Minimal balance in satoshis which should remain in the anchoring wallet.
+    pub reserve_balance: u64,
+    /// Number of anchoring intervals without signatures of an anchoring node, after which
+    /// its standby key becomes active.
+    #[serde(default)]
+    pub standby_activation_intervals: u64,
+    /// Names of the service instances, which state hashes are included into the anchoring
+    /// transaction payload.
+    #[serde(default)]
+    pub auxiliary_services: Vec<String>,
+    /// Minimal value in satoshis of the anchoring transaction change output.
+    #[serde(default)]
+    pub dust_threshold: u64,
+    /// Maximal age in blocks of the anchoring proposal, which signatures are accepted.
+    #[serde(default)]
+    pub max_signature_age: u64,
+    /// Proofs of possession of the Bitcoin keys added by this configuration.
+    #[serde(default)]
+    pub key_proofs: Vec<KeyPossessionProof>,
+    /// Type of the output script of the anchoring transactions.
+    #[serde(default)]
+    pub output_type: config::OutputType,
+    /// Allow the anchoring address to match the address of a previous anchoring epoch
+    /// or one of the known external addresses.
+    #[serde(default)]
+    pub allow_address_reuse: bool,
+    /// Known external Bitcoin addresses, which must not be used as the anchoring address.
+    #[serde(default)]
+    pub external_addresses: Vec<String>,
+    /// Include the hash of the actual consensus configuration into the anchoring
+    /// transaction payload.
+    #[serde(default)]
+    pub commit_consensus_config: bool,
+    /// Number of blocks within which the high-risk maintenance operation should be
+    /// confirmed by the anchoring nodes.
+    #[serde(default)]
+    pub approval_window: u64,
+    /// Sort the funding inputs of the anchoring transactions according to BIP-69.
+    #[serde(default)]
+    pub bip69_input_ordering: bool,
+    /// Anchoring address corresponding to this configuration.
+    pub anchoring_address: btc::Address,
+}
+
+/// Anchoring addresses.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AnchoringAddress {
+    /// Anchoring address.
+    pub address: Option<btc::Address>,
+}
+
+/// Anchoring address with the corresponding redeem script and output descriptor.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AnchoringDescriptor {
+    /// Anchoring address.
+    pub address: btc::Address,
+    /// Hex-encoded redeem script of the address.
+    pub redeem_script: String,
+    /// Output descriptor of the address with the checksum, which can be imported
+    /// into the Bitcoin node wallet.
+    pub descriptor: String,
+}
+
+/// Summary of the current anchoring state.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AnchoringStatus {
+    /// Actual anchoring address.
+    pub actual_address: btc::Address,
+    /// Following anchoring address if the anchoring is in the transition state.
+    pub following_address: Option<btc::Address>,
+    /// Total number of anchoring transactions in the chain.
+    pub transactions_count: u64,
+    /// Height of the latest anchored block.
+    pub latest_anchored_height: Option<Height>,
+    /// Exonum blockchain height from which the anchoring begins.
+    pub start_height: Height,
+    /// Indicates whether the anchoring has been started.
+    pub is_started: bool,
+    /// Signing progress of the actual anchoring transaction proposal, if any.
+    #[serde(default)]
+    pub signing_progress: Option<SigningProgress>,
+    /// Version of the payload format of the anchoring transactions.
+    #[serde(default = "default_payload_version")]
+    pub payload_version: u8,
+    /// Height of the latest Exonum block in the snapshot, from which the status is read.
+    #[serde(default)]
+    pub snapshot_height: Height,
+}
+
+fn default_payload_version() -> u8 {
+    1
+}
+
+/// Signing progress of the anchoring transaction proposal.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SigningProgress {
+    /// Identifier of the anchoring transaction proposal.
+    pub txid: btc::Sha256d,
+    /// Number of the collected signatures for each input of the proposal.
+    pub input_signatures: Vec<u32>,
+    /// Number of the signatures required to finalize an input.
+    pub quorum: u32,
+}
+
+/// Query parameters for the find anchor request.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct FindAnchorQuery {
+    /// Identifier of the anchoring transaction.
+    pub txid: btc::Sha256d,
+}
+
+/// Query parameters for the finality certificate request.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct FinalityCertificateQuery {
+    /// Hash of the Exonum transaction of any service.
+    pub tx_hash: Hash,
+}
+
+/// Anchoring transaction which covers the block with the certified transaction.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CoveringAnchor {
+    /// Position of the anchoring transaction in the anchoring chain.
+    pub index: u64,
+    /// Proof of existence for the anchoring transaction.
+    pub proof: TransactionProof,
+}
+
+/// Confirmation metadata of the covering anchor in the Bitcoin blockchain.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct BitcoinConfirmation {
+    /// Identifier of the anchoring transaction in the Bitcoin network.
+    pub txid: btc::Sha256d,
+    /// Number of the transaction confirmations, zero if the transaction is in
+    /// the memory pool.
+    pub confirmations: u32,
+}
+
+/// Document which proves that the Exonum transaction has been committed and anchored
+/// to the Bitcoin blockchain.
+///
+/// The anchoring service is not connected to the Bitcoin network, so the certificate
+/// returned by the API contains no Bitcoin confirmation metadata. It can be added
+/// via the [`ApiClient::get_finality_certificate`] method.
+///
+/// [`ApiClient::get_finality_certificate`]: ../../client/struct.ApiClient.html#method.get_finality_certificate
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FinalityCertificate {
+    /// Hash of the certified transaction.
+    pub tx_hash: Hash,
+    /// Location of the transaction in the blockchain.
+    pub location: TxLocation,
+    /// Proof of the block with the transaction authenticated by the validators.
+    pub block_proof: BlockProof,
+    /// Proof of the transaction hash in the list of the block transactions.
+    pub location_proof: ListProof<Hash>,
+    /// The earliest anchoring transaction which covers the block with the transaction,
+    /// if the block has been anchored.
+    pub anchor: Option<CoveringAnchor>,
+    /// Confirmation metadata of the covering anchor in the Bitcoin blockchain, if known.
+    pub bitcoin: Option<BitcoinConfirmation>,
+}
+
+/// Query parameters for the tenant usage request.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct TenantQuery {
+    /// Tenant key.
+    pub tenant: PublicKey,
+}
+
+/// Data quota usage of the tenant.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct TenantUsage {
+    /// Total number of the submitted data hashes.
+    pub used: u64,
+    /// Data quota of the tenant, if the tenant is registered in the actual configuration.
+    pub quota: Option<u64>,
+}
+
+/// Query parameters for the tenant receipt request.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct TenantReceiptQuery {
+    /// Tenant key.
+    pub tenant: PublicKey,
+    /// Height of the Exonum block in which the data has been submitted.
+    pub height: Height,
+    /// Hash of the submitted data.
+    pub data_hash: Hash,
+}
+
+/// Proof that the data hash has been submitted by the tenant in the Exonum block.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TenantReceipt {
+    /// Proof of authenticity for the tenant data roots index within the database.
+    pub index_proof: IndexProof,
+    /// Proof for the root hash of the data submitted by the tenant in the block.
+    pub root_proof: MapProof<TenantBlockId, Hash>,
+    /// Proof for the data hash in the list of the data submitted by the tenant in the block.
+    pub data_proof: ListProof<Hash>,
+}
+
+/// Anchoring keys submitted by the validators joining the anchoring.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PendingConfig {
+    /// Anchoring keys approved by the anchoring nodes, which are not included into
+    /// the anchoring configuration yet.
+    pub pending_keys: Vec<AnchoringKeys>,
+    /// Anchoring keys which await the approval of the anchoring nodes.
+    #[serde(default)]
+    pub submitted_keys: Vec<SubmittedAnchoringKey>,
+    /// Configuration which will be scheduled for the transition, if there are pending
+    /// or submitted keys.
+    pub config: Option<Config>,
+}
+
+/// Anchoring key submitted by the validator joining the anchoring, which awaits
+/// the approval of the anchoring nodes.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SubmittedAnchoringKey {
+    /// Service key of the joining validator.
+    pub service_key: PublicKey,
+    /// Proof of possession of the submitted Bitcoin key, which is sent by the anchoring
+    /// nodes approving the key.
+    pub key_proof: KeyPossessionProof,
+    /// Number of the anchoring nodes, which have approved the key.
+    pub approvals: usize,
+}
+
+/// Query parameters for the fee accounting request.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct FeeAccountingQuery {
+    /// Anchoring epoch, the current epoch is used if it is not specified.
+    pub epoch: Option<u64>,
+}
+
+/// Fees of the anchoring transactions finalized by the anchoring node.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ValidatorFees {
+    /// Service key of the anchoring node.
+    pub service_key: PublicKey,
+    /// Number of the finalized anchoring transactions.
+    pub transactions_count: u64,
+    /// Total fee of the finalized anchoring transactions in satoshis.
+    pub total_fee: u64,
+}
+
+/// Fees consumed from the anchoring wallet within the anchoring epoch.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FeeAccounting {
+    /// Anchoring epoch.
+    pub epoch: u64,
+    /// Fees of the anchoring transactions grouped by the anchoring nodes which
+    /// have finalized them.
+    pub records: Vec<ValidatorFees>,
+    /// Total fee consumed within the epoch in satoshis.
+    pub total_fee: u64,
+}
+
+/// Query parameters for the balance history request.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct BalanceHistoryQuery {
+    /// Number of the latest records to return, `DEFAULT_BALANCE_RECORDS` if not specified.
+    pub count: Option<u64>,
+}
+
+/// Anchoring wallet balance after the anchoring transaction.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct BalanceRecord {
+    /// Position of the record in the balance history.
+    pub index: u64,
+    /// Height of the Exonum block in which the anchoring transaction has been finalized.
+    pub block_height: Height,
+    /// Total value of the anchoring transaction inputs in satoshis.
+    pub input_value: u64,
+    /// Fee of the anchoring transaction in satoshis.
+    pub fee: u64,
+    /// Value of the change output in satoshis.
+    pub change: u64,
+}
+
+/// Forecast of the anchoring wallet depletion at the current fee rates.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct BalanceForecast {
+    /// Average fee of the anchoring transactions in the returned records.
+    pub average_fee: u64,
+    /// Number of the anchoring transactions which can be paid by the latest balance.
+    pub remaining_anchors: u64,
+    /// Approximate number of the Exonum blocks, which can be anchored by the latest balance.
+    pub remaining_blocks: u64,
+}
+
+/// Anchoring wallet balance history.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BalanceHistory {
+    /// Latest balance records in the order of finalization.
+    pub records: Vec<BalanceRecord>,
+    /// Forecast of the wallet depletion, if there are records with the non-zero fees.
+    pub forecast: Option<BalanceForecast>,
+}
+
+/// Default number of the records returned by the balance history request.
+pub const DEFAULT_BALANCE_RECORDS: u64 = 100;
+
+/// Query parameters for the anchor statistics request.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct AnchorStatsQuery {
+    /// Number of the latest anchoring transactions to return, `DEFAULT_ANCHOR_STATS_RECORDS`
+    /// if not specified.
+    pub count: Option<u64>,
+}
+
+/// Query parameters for the signature audit request.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct SignatureAuditQuery {
+    /// Position of the first audited anchoring transaction, zero if not specified.
+    pub from: Option<u64>,
+}
+
+/// Query parameters for the paged requests of the anchoring chain.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct AnchorRangeQuery {
+    /// Position of the first anchoring transaction in the page, zero if not specified.
+    pub from: Option<u64>,
+    /// Maximal number of the anchoring transactions in the page, limited by the
+    /// maximal page size of the endpoint.
+    pub count: Option<u64>,
+}
+
+/// Query parameters for the anchor block request.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct AnchorBlockQuery {
+    /// Identifier of the anchoring transaction.
+    pub txid: btc::Sha256d,
+}
+
+/// Bitcoin block containing the anchoring transaction.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct AnchorBlock {
+    /// Identifier of the anchoring transaction.
+    pub txid: btc::Sha256d,
+    /// Position of the transaction in the anchoring chain.
+    pub index: u64,
+    /// Hash of the Bitcoin block containing the transaction, if a quorum of the anchoring
+    /// nodes has reported it.
+    pub block_hash: Option<btc::Sha256d>,
+}
+
+/// Maximal number of the latest proposal conflicts returned by a single request.
+pub const MAX_PROPOSAL_CONFLICTS: u64 = 100;
+
+/// Signature of the anchoring node for the transaction, which conflicts with the anchoring
+/// proposal computed by the service for the same anchoring height and epoch.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProposalConflict {
+    /// Height of the blockchain anchored by the proposal.
+    pub anchoring_height: Height,
+    /// Anchoring epoch of the proposal.
+    pub epoch: u64,
+    /// Identifier of the anchoring proposal computed by the service.
+    pub proposal_txid: btc::Sha256d,
+    /// Identifier of the conflicting transaction signed by the anchoring node.
+    pub conflicting_txid: btc::Sha256d,
+    /// Service key of the anchoring node.
+    pub author: PublicKey,
+    /// Signed input of the conflicting transaction.
+    pub input: u32,
+    /// Signature content.
+    pub input_signature: btc::InputSignature,
+    /// Height of the Exonum block in which the conflict has been detected.
+    pub detected_height: Height,
+    /// Content of the conflicting transaction.
+    pub conflicting_transaction: btc::Transaction,
+}
+
+/// Evidence of the conflicting anchoring proposals.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProposalConflicts {
+    /// The conflict which has halted the finalization of the anchoring proposal, if any.
+    pub halt: Option<ProposalConflict>,
+    /// Total number of the detected conflicts.
+    pub total: u64,
+    /// The latest detected conflicts, at most `MAX_PROPOSAL_CONFLICTS`, in the order
+    /// of detection.
+    pub conflicts: Vec<ProposalConflict>,
+}
+
+/// Maximal number of the anchoring transactions proved by a single request.
+pub const MAX_PROVED_ANCHORS: u64 = 100;
+
+/// Proof of existence for a range of the anchoring transactions.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AnchorProofs {
+    /// Position of the first proved anchoring transaction.
+    pub from: u64,
+    /// Position following the last proved anchoring transaction, i.e. the position
+    /// from which the next page should start.
+    pub to: u64,
+    /// Total number of the anchoring transactions.
+    pub transactions_count: u64,
+    /// Proof for the anchoring transactions in the range. If the range is empty,
+    /// the proof is the proof of absence for the `from` position.
+    pub proof: TransactionProof,
+}
+
+/// Fee and size accounting of the anchoring transaction.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AnchorStatsRecord {
+    /// Position of the transaction in the anchoring chain.
+    pub index: u64,
+    /// Identifier of the anchoring transaction.
+    pub txid: btc::Sha256d,
+    /// Fee of the anchoring transaction in satoshis.
+    pub fee: u64,
+    /// Fee rate in satoshis per 1000 virtual bytes.
+    pub fee_rate: u64,
+    /// Virtual size of the anchoring transaction in bytes.
+    pub vsize: u64,
+    /// Weight of the anchoring transaction in weight units.
+    pub weight: u64,
+    /// Values of the anchoring transaction inputs in satoshis.
+    pub input_values: Vec<u64>,
+    /// Values of the anchoring transaction outputs in satoshis.
+    pub output_values: Vec<u64>,
+}
+
+/// Aggregated fee efficiency of the returned anchoring transactions.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct AnchorStatsSummary {
+    /// Average fee rate in satoshis per 1000 virtual bytes.
+    pub average_fee_rate: u64,
+    /// Minimal fee rate in satoshis per 1000 virtual bytes.
+    pub min_fee_rate: u64,
+    /// Maximal fee rate in satoshis per 1000 virtual bytes.
+    pub max_fee_rate: u64,
+    /// Average virtual size of the anchoring transactions in bytes.
+    pub average_vsize: u64,
+}
+
+/// Fee and size statistics of the latest anchoring transactions finalized by the service.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AnchorStats {
+    /// Accounting records in the order of the anchoring chain.
+    pub records: Vec<AnchorStatsRecord>,
+    /// Summary of the records, if there are any.
+    pub summary: Option<AnchorStatsSummary>,
+}
+
+/// Default number of the anchoring transactions returned by the anchor statistics request.
+pub const DEFAULT_ANCHOR_STATS_RECORDS: u64 = 100;
+
+/// Rolling health score of the anchoring, which can be tracked against the agreed SLOs.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct AnchoringHealth {
+    /// Health score from 0 to 100, the average of the percentage of the anchoring
+    /// transactions finalized on time and the participation rate.
+    pub score: u64,
+    /// Number of the latest anchoring transactions over which the score is computed.
+    pub window: u64,
+    /// Number of the anchoring transactions taken into account.
+    pub anchors_count: u64,
+    /// Number of the anchoring transactions finalized within one anchoring interval
+    /// after the anchored block.
+    pub on_time_anchors: u64,
+    /// Percentage of the anchoring nodes which sign the anchoring proposals.
+    pub participation_rate: u64,
+    /// Finalization latency of the latest anchoring transaction in Exonum blocks.
+    pub latest_latency: Option<u64>,
+    /// Height of the latest Exonum block in the snapshot, from which the score is read.
+    #[serde(default)]
+    pub snapshot_height: Height,
+}
+
+/// Kind of the next anchoring slot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum NextAnchorKind {
+    /// The anchoring has not been started yet, the next slot is the start height.
+    NotStarted,
+    /// Regular anchoring transaction at the following anchoring height.
+    Regular,
+    /// Transition transaction, which moves the funds to the following anchoring address.
+    /// It anchors the latest anchored height again, so the slot is already reached.
+    Transition,
+    /// The transition transaction has been recorded, and the anchoring is paused until
+    /// the following configuration becomes actual.
+    Paused,
+}
+
+/// Prediction of the next anchoring slot, which allows monitoring systems to alert only
+/// when an expected anchor fails to appear.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct NextAnchor {
+    /// Kind of the next anchoring slot.
+    pub kind: NextAnchorKind,
+    /// Exonum height, which the next anchoring transaction anchors. Absent if
+    /// the anchoring is paused.
+    pub anchoring_height: Option<Height>,
+    /// Number of the Exonum blocks to be committed until the slot is reached.
+    pub blocks_remaining: u64,
+    /// Number of the Exonum blocks committed since the slot has been reached without
+    /// the anchoring transaction being recorded.
+    pub overdue_blocks: u64,
+    /// Approximate interval between the Exonum blocks in milliseconds, which is taken
+    /// from the `max_propose_timeout` of the consensus configuration.
+    pub block_interval_ms: u64,
+    /// Approximate Unix time in seconds, at which the slot is reached.
+    pub expected_time: Option<u64>,
+    /// Height of the latest Exonum block in the snapshot, from which the prediction is made.
+    pub snapshot_height: Height,
+}
+
+/// Status of the latest anchoring transaction in the Bitcoin network summarized over
+/// the reports of the anchoring nodes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BroadcastState {
+    /// None of the anchoring nodes has reported the status of the transaction yet.
+    NotReported,
+    /// The transaction is unknown to the Bitcoin nodes of all reporting anchoring nodes,
+    /// i.e. it has never been broadcast or has been evicted from the mempool.
+    Missing,
+    /// The transaction is in the mempool of at least one Bitcoin node.
+    Mempool,
+    /// The transaction is confirmed in the Bitcoin blockchain.
+    Confirmed,
+}
+
+/// Status of the latest anchoring transaction reported by the anchoring node.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NodeAnchorStatus {
+    /// Service key of the anchoring node.
+    pub service_key: PublicKey,
+    /// Whether the transaction is known to the Bitcoin node of the anchoring node.
+    pub known: bool,
+    /// Number of the transaction confirmations, zero if it is in the mempool.
+    pub confirmations: u32,
+    /// Height of the Exonum block in which the status has been reported.
+    pub reported_height: Height,
+}
+
+/// Health of the Bitcoin relay reported by the anchoring node.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NodeRelayStatus {
+    /// Service key of the anchoring node.
+    pub service_key: PublicKey,
+    /// Whether the latest health check of the relay has succeeded.
+    pub healthy: bool,
+    /// Height of the latest Bitcoin block known to the relay, if any.
+    pub block_height: Option<u64>,
+    /// Number of the consecutive failed health checks.
+    pub failed_checks: u32,
+    /// Height of the Exonum block in which the status has been reported.
+    pub reported_height: Height,
+}
+
+/// Health of the Bitcoin relays of the anchoring nodes.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RelayStatuses {
+    /// Number of the anchoring nodes, which have reported the healthy relays.
+    pub healthy_nodes: usize,
+    /// Latest reports of the anchoring nodes of the actual configuration. The nodes,
+    /// which have never reported the status, are omitted.
+    pub reports: Vec<NodeRelayStatus>,
+    /// Height of the latest Exonum block in the snapshot, from which the statuses are read.
+    pub snapshot_height: Height,
+}
+
+/// Status of the latest anchoring transaction in the Bitcoin network.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AnchorBroadcastStatus {
+    /// Identifier of the latest anchoring transaction.
+    pub txid: btc::Sha256d,
+    /// Status summarized over the reports of the anchoring nodes.
+    pub state: BroadcastState,
+    /// Maximal number of the transaction confirmations reported by the anchoring nodes.
+    pub confirmations: u32,
+    /// Reports of the anchoring nodes for the latest anchoring transaction.
+    pub reports: Vec<NodeAnchorStatus>,
+    /// Height of the latest Exonum block in the snapshot, from which the status is read.
+    pub snapshot_height: Height,
+}
+
+/// Checkpoint of the anchoring state signed by the validators, which allows a new observer
+/// to start from the latest anchor instead of replaying the whole blockchain.
+///
+/// Use [`verify_checkpoint`] to verify the checkpoint.
+///
+/// [`verify_checkpoint`]: ../../client/fn.verify_checkpoint.html
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Checkpoint {
+    /// Proof for the latest transaction in the anchoring chain.
+    pub latest_anchor: TransactionProof,
+    /// Actual anchoring configuration. The configuration is not covered by the proof,
+    /// so it should be checked against the trusted sources before use.
+    pub config: Config,
+    /// Height of the latest Exonum block in the snapshot, from which the checkpoint is read.
+    #[serde(default)]
+    pub snapshot_height: Height,
+}
+
+/// Storage usage of the anchoring schema index.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IndexUsage {
+    /// Index name.
+    pub name: String,
+    /// Number of the index entries.
+    pub entries: u64,
+    /// Approximate size of the index entries in bytes, i.e. the total size of the serialized
+    /// keys and values without the storage overhead.
+    pub size: u64,
+}
+
+/// Storage usage report of the anchoring schema.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StorageUsage {
+    /// Storage usage of the schema indexes.
+    pub indexes: Vec<IndexUsage>,
+    /// Approximate total size of the schema indexes in bytes.
+    pub total_size: u64,
+}
+
+/// Input of the previewed anchoring transaction.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PreviewInput {
+    /// Identifier of the spent transaction.
+    pub txid: btc::Sha256d,
+    /// Index of the spent output.
+    pub vout: u32,
+    /// Value of the spent output in satoshis.
+    pub value: u64,
+}
+
+/// Summary of the previewed anchoring transaction.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PreviewSummary {
+    /// Identifier of the unsigned transaction.
+    pub txid: btc::Sha256d,
+    /// Inputs of the transaction.
+    pub inputs: Vec<PreviewInput>,
+    /// Total value of the inputs in satoshis.
+    pub balance: u64,
+    /// Total transaction fee in satoshis.
+    pub fee: u64,
+    /// Fee per byte in satoshis.
+    pub fee_per_byte: u64,
+    /// Size of the unsigned transaction in bytes, which the fee is calculated from.
+    pub size: u64,
+    /// Value of the change output in satoshis.
+    pub change: u64,
+    /// Payload of the transaction.
+    pub payload: Option<btc::Payload>,
+}
+
+/// Anchoring transaction which the service would propose right now.
+///
+/// The preview does not create any service state, so operators can use it to check
+/// the fee settings and the payload content before the next anchoring slot.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ProposalPreview {
+    /// Height of the Exonum block anchored by the preview.
+    pub anchoring_height: Height,
+    /// Height of the block which will be anchored by the next real proposal.
+    pub next_anchoring_height: Height,
+    /// State of the previewed proposal.
+    pub proposal: AnchoringProposalState,
+    /// Summary of the previewed transaction, if it can be built.
+    pub summary: Option<PreviewSummary>,
+}
+
+impl IndexUsage {
+    fn new(name: &str, entry_sizes: impl IntoIterator<Item = usize>) -> Self {
+        entry_sizes.into_iter().fold(
+            Self {
+                name: name.to_owned(),
+                entries: 0,
+                size: 0,
+            },
+            |mut usage, entry_size| {
+                usage.entries += 1;
+                usage.size += entry_size as u64;
+                usage
+            },
+        )
+    }
+}
+
+/// Returns the size of the serialized index entry.
+fn entry_size<K: BinaryKey + ?Sized, V: BinaryValue>(key: &K, value: V) -> usize {
+    key.size() + value.into_bytes().len()
+}
+
+/// Returns the storage usage of the list index or entry with the given values.
+fn values_usage<V: BinaryValue>(name: &str, values: impl IntoIterator<Item = V>) -> IndexUsage {
+    IndexUsage::new(
+        name,
+        values.into_iter().map(|value| value.into_bytes().len()),
+    )
+}
+
+/// Returns the storage usage of the map index with the given entries.
+fn map_usage<K: BinaryKey, V: BinaryValue>(
+    name: &str,
+    entries: impl IntoIterator<Item = (K, V)>,
+) -> IndexUsage {
+    IndexUsage::new(
+        name,
+        entries
+            .into_iter()
+            .map(|(key, value)| entry_size(&key, value)),
+    )
+}
+
+impl From<super::TransactionProof> for TransactionProof {
+    fn from(proof: super::TransactionProof) -> Self {
+        Self {
+            index_proof: proof.index_proof,
+            transaction_proof: proof.transaction_proof,
+        }
+    }
+}
+
+impl From<&config::Config> for Config {
+    fn from(config: &config::Config) -> Self {
+        Self {
+            network: config.network,
+            anchoring_keys: config
+                .anchoring_keys
+                .iter()
+                .map(|keys| AnchoringKeys {
+                    service_key: keys.service_key,
+                    bitcoin_key: keys.bitcoin_key,
+                })
+                .collect(),
+            anchoring_interval: config.anchoring_interval,
+            transaction_fee: config.transaction_fee,
+            start_height: config.start_height,
+            anchor_only_on_state_change: config.anchor_only_on_state_change,
+            tenants: config
+                .tenants
+                .iter()
+                .map(|tenant| Tenant {
+                    key: tenant.key,
+                    quota: tenant.quota,
+                })
+                .collect(),
+            reserve_balance: config.reserve_balance,
+            standby_activation_intervals: config.standby_activation_intervals,
+            auxiliary_services: config.auxiliary_services.clone(),
+            dust_threshold: config.dust_threshold,
+            max_signature_age: config.max_signature_age,
+            key_proofs: config
+                .key_proofs
+                .iter()
+                .map(|proof| KeyPossessionProof {
+                    bitcoin_key: proof.bitcoin_key,
+                    signature: hex::encode(&proof.signature),
+                })
+                .collect(),
+            output_type: config.output_type,
+            allow_address_reuse: config.allow_address_reuse,
+            external_addresses: config.external_addresses.clone(),
+            commit_consensus_config: config.commit_consensus_config,
+            approval_window: config.approval_window,
+            bip69_input_ordering: config.bip69_input_ordering,
+            anchoring_address: config.anchoring_address(),
+        }
+    }
+}
+
+impl From<&Config> for config::Config {
+    fn from(config: &Config) -> Self {
+        Self {
+            network: config.network,
+            anchoring_keys: config
+                .anchoring_keys
+                .iter()
+                .map(|keys| config::AnchoringKeys {
+                    service_key: keys.service_key,
+                    bitcoin_key: keys.bitcoin_key,
+                })
+                .collect(),
+            anchoring_interval: config.anchoring_interval,
+            transaction_fee: config.transaction_fee,
+            start_height: config.start_height,
+            anchor_only_on_state_change: config.anchor_only_on_state_change,
+            tenants: config
+                .tenants
+                .iter()
+                .map(|tenant| config::Tenant {
+                    key: tenant.key,
+                    quota: tenant.quota,
+                })
+                .collect(),
+            reserve_balance: config.reserve_balance,
+            standby_activation_intervals: config.standby_activation_intervals,
+            auxiliary_services: config.auxiliary_services.clone(),
+            dust_threshold: config.dust_threshold,
+            max_signature_age: config.max_signature_age,
+            // Malformed signatures are kept empty and rejected by the proof verification.
+            key_proofs: config
+                .key_proofs
+                .iter()
+                .map(|proof| config::KeyPossessionProof {
+                    bitcoin_key: proof.bitcoin_key,
+                    signature: hex::decode(&proof.signature).unwrap_or_default(),
+                })
+                .collect(),
+            output_type: config.output_type,
+            allow_address_reuse: config.allow_address_reuse,
+            external_addresses: config.external_addresses.clone(),
+            commit_consensus_config: config.commit_consensus_config,
+            approval_window: config.approval_window,
+            bip69_input_ordering: config.bip69_input_ordering,
+        }
+    }
+}
+
+impl From<data_layout::ProposalConflict> for ProposalConflict {
+    fn from(conflict: data_layout::ProposalConflict) -> Self {
+        Self {
+            anchoring_height: conflict.anchoring_height,
+            epoch: conflict.epoch,
+            proposal_txid: conflict.proposal_txid,
+            conflicting_txid: conflict.conflicting_txid,
+            author: conflict.author,
+            input: conflict.input,
+            input_signature: conflict.input_signature,
+            detected_height: conflict.detected_height,
+            conflicting_transaction: conflict.conflicting_transaction,
+        }
+    }
+}
+
+impl From<&config::Config> for AnchoringDescriptor {
+    fn from(config: &config::Config) -> Self {
+        Self {
+            address: config.anchoring_address(),
+            redeem_script: hex::encode(&config.redeem_script().as_ref()[..]),
+            descriptor: config.anchoring_descriptor(),
+        }
+    }
+}
+
+impl From<Option<btc::Address>> for AnchoringAddress {
+    fn from(address: Option<btc::Address>) -> Self {
+        Self { address }
+    }
+}
+
+// Each request reads the data from the single blockchain snapshot of its API state,
+// so the responses never mix the data of different heights.
+impl ApiImpl {
+    /// Returns the height of the latest Exonum block in the snapshot of this request.
+    fn snapshot_height(&self) -> Height {
+        self.0.data().for_core().height()
+    }
+
+    async fn actual_address_v1(self) -> api::Result<AnchoringAddress> {
+        self.actual_address().await.map(Some).map(From::from)
+    }
+
+    async fn following_address_v1(self) -> api::Result<AnchoringAddress> {
+        self.following_address().await.map(From::from)
+    }
+
+    async fn actual_descriptor_v1(self) -> api::Result<AnchoringDescriptor> {
+        let schema = Schema::new(self.0.service_data());
+        let config = schema.try_actual_config().ok_or_else(not_configured)?;
+        Ok(AnchoringDescriptor::from(&config))
+    }
+
+    async fn following_descriptor_v1(self) -> api::Result<Option<AnchoringDescriptor>> {
+        let schema = Schema::new(self.0.service_data());
+        Ok(schema
+            .following_config()
+            .map(|config| AnchoringDescriptor::from(&config)))
+    }
+
+    async fn find_transaction_v1(self, height: Option<Height>) -> api::Result<TransactionProof> {
+        self.find_transaction(height)
+            .await
+            .map(TransactionProof::from)
+    }
+
+    async fn config_v1(self) -> api::Result<Config> {
+        self.config().await.map(|config| Config::from(&config))
+    }
+
+    async fn status_v1(self) -> api::Result<AnchoringStatus> {
+        let schema = Schema::new(self.0.service_data());
+        let config = schema.try_actual_config().ok_or_else(not_configured)?;
+        let current_height = self.snapshot_height();
+        let signing_progress = schema
+            .actual_proposed_anchoring_transaction(self.0.data().for_core())
+            .and_then(Result::ok)
+            .map(|(proposal, inputs)| {
+                let txid = proposal.id();
+                SigningProgress {
+                    txid,
+                    input_signatures: (0..inputs.len() as u32)
+                        .map(|input| {
+                            schema
+                                .input_signatures(&TxInputId::new(txid, input))
+                                .0
+                                .len() as u32
+                        })
+                        .collect(),
+                    quorum: config.byzantine_quorum() as u32,
+                }
+            });
+        Ok(AnchoringStatus {
+            actual_address: config.anchoring_address(),
+            following_address: schema
+                .following_config()
+                .map(|config| config.anchoring_address()),
+            transactions_count: schema.transactions_chain.len(),
+            latest_anchored_height: schema.latest_anchored_height(),
+            start_height: config.start_height,
+            is_started: !schema.transactions_chain.is_empty() || config.is_started(current_height),
+            signing_progress,
+            payload_version: schema.payload_version(),
+            snapshot_height: current_height,
+        })
+    }
+
+    async fn checkpoint_v1(self) -> api::Result<Checkpoint> {
+        let schema = Schema::new(self.0.service_data());
+        let transactions_count = schema.transactions_chain.len();
+        if transactions_count == 0 {
+            return Err(api::Error::not_found()
+                .title("Checkpoint not found")
+                .detail("Anchoring transactions chain is not established yet"));
+        }
+
+        let config = schema.actual_config();
+        Ok(Checkpoint {
+            latest_anchor: self.transaction_proof(transactions_count - 1).into(),
+            config: Config::from(&config),
+            snapshot_height: self.snapshot_height(),
+        })
+    }
+
+    async fn find_anchor_v1(self, txid: btc::Sha256d) -> api::Result<TransactionProof> {
+        let tx_index = Schema::new(self.0.service_data())
+            .transaction_index(&txid)
+            .ok_or_else(|| {
+                api::Error::not_found()
+                    .title("Anchoring transaction not found")
+                    .detail(format!("Unknown anchoring transaction {}", txid))
+            })?;
+        Ok(self.transaction_proof(tx_index).into())
+    }
+
+    async fn finality_certificate_v1(self, tx_hash: Hash) -> api::Result<FinalityCertificate> {
+        let core_schema = self.0.data().for_core();
+        let location = core_schema
+            .transactions_locations()
+            .get(&tx_hash)
+            .ok_or_else(|| {
+                api::Error::not_found()
+                    .title("Transaction not found")
+                    .detail(format!("Unknown committed transaction {}", tx_hash))
+            })?;
+        let block_proof = core_schema
+            .block_and_precommits(location.block_height())
+            .ok_or_else(|| api::Error::internal("Block with the transaction is absent"))?;
+        let location_proof = core_schema
+            .block_transactions(location.block_height())
+            .get_proof(u64::from(location.position_in_block()));
+
+        let anchor = Schema::new(self.0.service_data())
+            .find_transaction_index(location.block_height())
+            .map(|index| CoveringAnchor {
+                index,
+                proof: self.transaction_proof(index).into(),
+            });
+        Ok(FinalityCertificate {
+            tx_hash,
+            location,
+            block_proof,
+            location_proof,
+            anchor,
+            bitcoin: None,
+        })
+    }
+
+    async fn pending_config_v1(self) -> api::Result<PendingConfig> {
+        let schema = Schema::new(self.0.service_data());
+        let pending_keys = schema
+            .pending_anchoring_keys
+            .values()
+            .map(|keys| AnchoringKeys {
+                service_key: keys.service_key,
+                bitcoin_key: keys.bitcoin_key,
+            })
+            .collect::<Vec<_>>();
+        let submitted_keys = schema
+            .anchoring_key_proofs
+            .iter()
+            .filter(|(service_key, _)| !schema.pending_anchoring_keys.contains(service_key))
+            .map(|(service_key, proof)| SubmittedAnchoringKey {
+                service_key,
+                key_proof: KeyPossessionProof {
+                    bitcoin_key: proof.bitcoin_key,
+                    signature: hex::encode(&proof.signature),
+                },
+                approvals: schema
+                    .unconfirmed_anchoring_keys
+                    .get(&service_key)
+                    .map_or(0, |confirmations| confirmations.0.len()),
+            })
+            .collect::<Vec<_>>();
+
+        // The validators join the anchoring one at a time, so the configuration results
+        // from the inclusion of the submitted key, if any.
+        let config = if let Some(submitted) = submitted_keys.first() {
+            let keys = config::AnchoringKeys {
+                service_key: submitted.service_key,
+                bitcoin_key: submitted.key_proof.bitcoin_key,
+            };
+            Some(Config::from(&schema.onboarding_config(keys)))
+        } else if pending_keys.is_empty() {
+            None
+        } else {
+            Some(Config::from(&schema.pending_config()))
+        };
+        Ok(PendingConfig {
+            pending_keys,
+            submitted_keys,
+            config,
+        })
+    }
+
+    async fn preview_proposal_v1(self) -> api::Result<ProposalPreview> {
+        let core_schema = self.0.data().for_core();
+        let schema = Schema::new(self.0.service_data());
+        let config = schema.try_actual_config().ok_or_else(not_configured)?;
+        let next_anchoring_height = schema
+            .actual_state()
+            .following_anchoring_height(schema.latest_anchoring_slot());
+        let (anchoring_height, proposal) = schema.preview_anchoring_transaction(core_schema);
+
+        let summary = proposal
+            .as_ref()
+            .and_then(|proposal| proposal.as_ref().ok())
+            .map(|(transaction, inputs)| {
+                let inputs = transaction
+                    .0
+                    .input
+                    .iter()
+                    .zip(inputs)
+                    .map(|(txin, input)| PreviewInput {
+                        txid: txin.previous_output.txid.into(),
+                        vout: txin.previous_output.vout,
+                        value: input.0.output[txin.previous_output.vout as usize].value,
+                    })
+                    .collect::<Vec<_>>();
+                let balance = inputs.iter().map(|input| input.value).sum::<u64>();
+                let change = transaction.0.output[0].value;
+                PreviewSummary {
+                    txid: transaction.id(),
+                    inputs,
+                    balance,
+                    fee: balance - change,
+                    fee_per_byte: config.transaction_fee,
+                    size: bitcoin::consensus::serialize(&transaction.0).len() as u64,
+                    change,
+                    payload: transaction.anchoring_payload(),
+                }
+            });
+        Ok(ProposalPreview {
+            anchoring_height,
+            next_anchoring_height,
+            proposal: AnchoringProposalState::try_from_proposal(
+                proposal,
+                schema.anchoring_epoch(),
+            )?,
+            summary,
+        })
+    }
+
+    async fn fee_accounting_v1(self, epoch: Option<u64>) -> api::Result<FeeAccounting> {
+        let schema = Schema::new(self.0.service_data());
+        let current_epoch = schema.anchoring_epoch();
+        let epoch = epoch.unwrap_or(current_epoch);
+        if epoch > current_epoch {
+            return Err(api::Error::not_found()
+                .title("Anchoring epoch not found")
+                .detail(format!("The current anchoring epoch is {}", current_epoch)));
+        }
+
+        let records = schema
+            .fee_records(epoch)
+            .iter()
+            .map(|(service_key, record)| ValidatorFees {
+                service_key,
+                transactions_count: record.transactions_count,
+                total_fee: record.total_fee,
+            })
+            .collect::<Vec<_>>();
+        let total_fee = records.iter().map(|record| record.total_fee).sum();
+        Ok(FeeAccounting {
+            epoch,
+            records,
+            total_fee,
+        })
+    }
+
+    async fn balance_history_v1(self, count: Option<u64>) -> api::Result<BalanceHistory> {
+        let schema = Schema::new(self.0.service_data());
+        let history = schema.balance_history();
+        let count = count.unwrap_or(DEFAULT_BALANCE_RECORDS);
+        let start = history.len().saturating_sub(count);
+        let records = history
+            .iter_from(start)
+            .zip(start..)
+            .map(|(record, index)| BalanceRecord {
+                index,
+                block_height: Height(record.block_height),
+                input_value: record.input_value,
+                fee: record.fee,
+                change: record.change,
+            })
+            .collect::<Vec<_>>();
+
+        let total_fee = records.iter().map(|record| record.fee).sum::<u64>();
+        let forecast = records.last().and_then(|latest| {
+            let average_fee = total_fee / records.len() as u64;
+            if average_fee == 0 {
+                return None;
+            }
+            let remaining_anchors = latest.change / average_fee;
+            let anchoring_interval = schema.try_actual_config()?.anchoring_interval;
+            Some(BalanceForecast {
+                average_fee,
+                remaining_anchors,
+                remaining_blocks: remaining_anchors.saturating_mul(anchoring_interval),
+            })
+        });
+        Ok(BalanceHistory { records, forecast })
+    }
+
+    async fn anchor_stats_v1(self, count: Option<u64>) -> api::Result<AnchorStats> {
+        let schema = Schema::new(self.0.service_data());
+        let count = count.unwrap_or(DEFAULT_ANCHOR_STATS_RECORDS);
+        let start = schema.transactions_chain.len().saturating_sub(count);
+        let records = schema
+            .anchor_accounting()
+            .iter_from(&start)
+            .filter_map(|(index, accounting)| {
+                let txid = schema.transactions_chain.get(index)?.id();
+                Some(AnchorStatsRecord {
+                    index,
+                    txid,
+                    fee: accounting.fee,
+                    fee_rate: accounting.fee_rate,
+                    vsize: accounting.vsize,
+                    weight: accounting.weight,
+                    input_values: accounting.input_values,
+                    output_values: accounting.output_values,
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let summary = if records.is_empty() {
+            None
+        } else {
+            let len = records.len() as u64;
+            let fee_rates = records.iter().map(|record| record.fee_rate);
+            Some(AnchorStatsSummary {
+                average_fee_rate: fee_rates.clone().sum::<u64>() / len,
+                min_fee_rate: fee_rates.clone().min().unwrap_or_default(),
+                max_fee_rate: fee_rates.max().unwrap_or_default(),
+                average_vsize: records.iter().map(|record| record.vsize).sum::<u64>() / len,
+            })
+        };
+        Ok(AnchorStats { records, summary })
+    }
+
+    async fn signature_audit_v1(self, from: Option<u64>) -> api::Result<SignatureAuditReport> {
+        let schema = Schema::new(self.0.service_data());
+        Ok(schema.audit_signatures(from.unwrap_or_default()))
+    }
+
+    async fn export_anchors_v1(self, query: AnchorRangeQuery) -> api::Result<AnchorsExport> {
+        let schema = Schema::new(self.0.service_data());
+        Ok(schema.export_anchors(
+            query.from.unwrap_or_default(),
+            query.count.unwrap_or(MAX_EXPORTED_ANCHORS),
+        ))
+    }
+
+    async fn anchor_proofs_v1(self, query: AnchorRangeQuery) -> api::Result<AnchorProofs> {
+        let schema = Schema::new(self.0.service_data());
+        let tx_chain = &schema.transactions_chain;
+        let transactions_count = tx_chain.len();
+        let from = query.from.unwrap_or_default().min(transactions_count);
+        let count = query
+            .count
+            .unwrap_or(MAX_PROVED_ANCHORS)
+            .min(MAX_PROVED_ANCHORS);
+        let to = from.saturating_add(count).min(transactions_count);
+
+        let index_proof = self
+            .0
+            .data()
+            .proof_for_service_index("transactions_chain")
+            .unwrap();
+        let transaction_proof = if from < to {
+            tx_chain.get_range_proof(from..to)
+        } else {
+            tx_chain.get_proof(from)
+        };
+        Ok(AnchorProofs {
+            from,
+            to,
+            transactions_count,
+            proof: TransactionProof {
+                index_proof,
+                transaction_proof,
+            },
+        })
+    }
+
+    async fn health_v1(self) -> api::Result<AnchoringHealth> {
+        let schema = Schema::new(self.0.service_data());
+        let score = schema.health_score();
+        Ok(AnchoringHealth {
+            score: score.score,
+            window: HEALTH_WINDOW,
+            anchors_count: score.anchors_count,
+            on_time_anchors: score.on_time_anchors,
+            participation_rate: score.participation_rate,
+            latest_latency: schema
+                .health_records()
+                .last()
+                .map(|record| record.latency()),
+            snapshot_height: self.snapshot_height(),
+        })
+    }
+
+    async fn anchor_block_v1(self, txid: btc::Sha256d) -> api::Result<AnchorBlock> {
+        let schema = Schema::new(self.0.service_data());
+        let index = schema.transaction_index(&txid).ok_or_else(|| {
+            api::Error::not_found()
+                .title("Unknown anchoring transaction")
+                .detail(format!(
+                    "Anchoring chain doesn't contain transaction {}.",
+                    txid
+                ))
+        })?;
+        Ok(AnchorBlock {
+            txid,
+            index,
+            block_hash: schema.anchor_block_hash(&txid),
+        })
+    }
+
+    async fn proposal_conflicts_v1(self) -> api::Result<ProposalConflicts> {
+        let schema = Schema::new(self.0.service_data());
+        let total = schema.proposal_conflicts().len();
+        let conflicts = schema
+            .proposal_conflicts()
+            .iter_from(total.saturating_sub(MAX_PROPOSAL_CONFLICTS))
+            .map(ProposalConflict::from)
+            .collect();
+        Ok(ProposalConflicts {
+            halt: schema.anchoring_halt().map(ProposalConflict::from),
+            total,
+            conflicts,
+        })
+    }
+
+    async fn relay_status_v1(self) -> api::Result<RelayStatuses> {
+        let schema = Schema::new(self.0.service_data());
+        let config = schema.try_actual_config().ok_or_else(not_configured)?;
+        let reports = config
+            .anchoring_keys
+            .iter()
+            .filter_map(|keys| {
+                let report = schema.relay_status_reports().get(&keys.service_key)?;
+                Some(NodeRelayStatus {
+                    service_key: keys.service_key,
+                    healthy: report.healthy,
+                    block_height: Some(report.block_height).filter(|&height| height > 0),
+                    failed_checks: report.failed_checks,
+                    reported_height: Height(report.reported_height),
+                })
+            })
+            .collect::<Vec<_>>();
+        Ok(RelayStatuses {
+            healthy_nodes: reports.iter().filter(|report| report.healthy).count(),
+            reports,
+            snapshot_height: self.snapshot_height(),
+        })
+    }
+
+    async fn anchor_status_v1(self) -> api::Result<AnchorBroadcastStatus> {
+        let schema = Schema::new(self.0.service_data());
+        let txid = schema
+            .transactions_chain
+            .last()
+            .ok_or_else(|| {
+                api::Error::not_found()
+                    .title("Anchoring transaction not found")
+                    .detail("Anchoring transactions chain is not established yet")
+            })?
+            .id();
+
+        let reports = schema
+            .anchor_status_reports()
+            .iter()
+            .filter(|(_, report)| report.txid == txid)
+            .map(|(service_key, report)| NodeAnchorStatus {
+                service_key,
+                known: report.known,
+                confirmations: report.confirmations,
+                reported_height: Height(report.reported_height),
+            })
+            .collect::<Vec<_>>();
+        let confirmations = reports
+            .iter()
+            .map(|report| report.confirmations)
+            .max()
+            .unwrap_or_default();
+        let state = if reports.is_empty() {
+            BroadcastState::NotReported
+        } else if confirmations > 0 {
+            BroadcastState::Confirmed
+        } else if reports.iter().any(|report| report.known) {
+            BroadcastState::Mempool
+        } else {
+            BroadcastState::Missing
+        };
+        Ok(AnchorBroadcastStatus {
+            txid,
+            state,
+            confirmations,
+            reports,
+            snapshot_height: self.snapshot_height(),
+        })
+    }
+
+    async fn next_anchor_v1(self) -> api::Result<NextAnchor> {
+        let core_schema = self.0.data().for_core();
+        let schema = Schema::new(self.0.service_data());
+        schema.try_actual_config().ok_or_else(not_configured)?;
+
+        let current_height = core_schema.height();
+        let block_interval_ms = core_schema.consensus_config().max_propose_timeout;
+        let actual_state = schema.actual_state();
+        let anchoring_height =
+            actual_state.following_anchoring_height(schema.latest_anchoring_slot());
+        let kind = if let Some(following_config) = actual_state.following_config() {
+            // The latest anchoring transaction already sends the funds to the following
+            // address, see `Schema::build_anchoring_transaction`.
+            let transition_recorded = schema.transactions_chain.last().map_or(false, |tx| {
+                tx.0.output[0].script_pubkey == following_config.anchoring_out_script()
+            });
+            if transition_recorded {
+                NextAnchorKind::Paused
+            } else {
+                NextAnchorKind::Transition
+            }
+        } else if schema.transactions_chain.is_empty()
+            && !actual_state.actual_config().is_started(current_height)
+        {
+            NextAnchorKind::NotStarted
+        } else {
+            NextAnchorKind::Regular
+        };
+
+        if kind == NextAnchorKind::Paused {
+            return Ok(NextAnchor {
+                kind,
+                anchoring_height: None,
+                blocks_remaining: 0,
+                overdue_blocks: 0,
+                block_interval_ms,
+                expected_time: None,
+                snapshot_height: current_height,
+            });
+        }
+
+        let blocks_remaining = anchoring_height.0.saturating_sub(current_height.0);
+        // The prediction does not affect the consensus, so the local clock can be used.
+        let expected_time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .ok()
+            .map(|now| {
+                let delay =
+                    Duration::from_millis(blocks_remaining.saturating_mul(block_interval_ms));
+                (now + delay).as_secs()
+            });
+        Ok(NextAnchor {
+            kind,
+            anchoring_height: Some(anchoring_height),
+            blocks_remaining,
+            overdue_blocks: current_height.0.saturating_sub(anchoring_height.0),
+            block_interval_ms,
+            expected_time,
+            snapshot_height: current_height,
+        })
+    }
+
+    async fn storage_usage_v1(self) -> api::Result<StorageUsage> {
+        let schema = Schema::new(self.0.service_data());
+        // The exhaustive pattern makes the report cover every index of the schema.
+        let Schema {
+            transactions_chain,
+            spent_funding_transactions,
+            transaction_signatures,
+            actual_config,
+            following_config,
+            unconfirmed_funding_transactions,
+            unspent_funding_transaction,
+            anchored_heights,
+            transaction_positions,
+            indexed_transactions,
+            latest_anchor_block_height,
+            skipped_anchoring_heights,
+            tenant_data,
+            tenant_roots,
+            tenant_usage,
+            pending_anchoring_keys,
+            anchoring_epoch,
+            fee_records,
+            signed_proposals,
+            standby_keys,
+            latest_signed_heights,
+            auxiliary_roots,
+            consensus_config_hashes,
+            unconfirmed_external_anchors,
+            external_spends,
+            payload_version,
+            balance_history,
+            unconfirmed_parameters,
+            anchor_accounting,
+            health_records,
+            health_score,
+            used_addresses,
+            unconfirmed_final_anchors,
+            final_anchor_index,
+            anchor_status_reports,
+            anchor_blocks,
+            unconfirmed_anchor_blocks,
+            proposal_conflicts,
+            anchoring_halt,
+            superseded_proposals,
+            approval_windows,
+            unconfirmed_standby_keys,
+            relay_status_reports,
+            anchoring_key_proofs,
+            unconfirmed_anchoring_keys,
+        } = &schema;
+
+        let tenant_data = tenant_roots
+            .keys()
+            .flat_map(|id| {
+                tenant_data
+                    .get(&id)
+                    .iter()
+                    .map(|data_hash| data_hash.into_bytes().len())
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        let fee_records = (0..=schema.anchoring_epoch())
+            .flat_map(|epoch| {
+                fee_records
+                    .get(&epoch)
+                    .iter()
+                    .map(|(service_key, record)| epoch.size() + entry_size(&service_key, record))
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+
+        let indexes = vec![
+            values_usage("transactions_chain", transactions_chain.iter()),
+            map_usage(
+                "spent_funding_transactions",
+                spent_funding_transactions.iter(),
+            ),
+            map_usage("transaction_signatures", transaction_signatures.iter()),
+            values_usage("actual_config", actual_config.get()),
+            values_usage("following_config", following_config.get()),
+            map_usage(
+                "unconfirmed_funding_transactions",
+                unconfirmed_funding_transactions.iter(),
+            ),
+            values_usage(
+                "unspent_funding_transaction",
+                unspent_funding_transaction.get(),
+            ),
+            map_usage("anchored_heights", anchored_heights.iter()),
+            map_usage("transaction_positions", transaction_positions.iter()),
+            values_usage("indexed_transactions", indexed_transactions.get()),
+            values_usage(
+                "latest_anchor_block_height",
+                latest_anchor_block_height.get(),
+            ),
+            values_usage(
+                "skipped_anchoring_heights",
+                skipped_anchoring_heights.iter(),
+            ),
+            IndexUsage::new("tenant_data", tenant_data),
+            map_usage("tenant_roots", tenant_roots.iter()),
+            map_usage("tenant_usage", tenant_usage.iter()),
+            map_usage("pending_anchoring_keys", pending_anchoring_keys.iter()),
+            values_usage("anchoring_epoch", anchoring_epoch.get()),
+            IndexUsage::new("fee_records", fee_records),
+            map_usage("signed_proposals", signed_proposals.iter()),
+            map_usage("standby_keys", standby_keys.iter()),
+            map_usage("latest_signed_heights", latest_signed_heights.iter()),
+            map_usage("auxiliary_roots", auxiliary_roots.iter()),
+            map_usage("consensus_config_hashes", consensus_config_hashes.iter()),
+            map_usage(
+                "unconfirmed_external_anchors",
+                unconfirmed_external_anchors.iter(),
+            ),
+            map_usage("external_spends", external_spends.iter()),
+            values_usage("payload_version", payload_version.get()),
+            values_usage("balance_history", balance_history.iter()),
+            map_usage("unconfirmed_parameters", unconfirmed_parameters.iter()),
+            map_usage("anchor_accounting", anchor_accounting.iter()),
+            values_usage("health_records", health_records.iter()),
+            values_usage("health_score", health_score.get()),
+            map_usage("used_addresses", used_addresses.iter()),
+            map_usage(
+                "unconfirmed_final_anchors",
+                unconfirmed_final_anchors.iter(),
+            ),
+            values_usage("final_anchor_index", final_anchor_index.get()),
+            map_usage("anchor_status_reports", anchor_status_reports.iter()),
+            map_usage("anchor_blocks", anchor_blocks.iter()),
+            map_usage(
+                "unconfirmed_anchor_blocks",
+                unconfirmed_anchor_blocks.iter(),
+            ),
+            values_usage("proposal_conflicts", proposal_conflicts.iter()),
+            values_usage("anchoring_halt", anchoring_halt.get()),
+            map_usage("superseded_proposals", superseded_proposals.iter()),
+            map_usage("approval_windows", approval_windows.iter()),
+            map_usage("unconfirmed_standby_keys", unconfirmed_standby_keys.iter()),
+            map_usage("relay_status_reports", relay_status_reports.iter()),
+            map_usage("anchoring_key_proofs", anchoring_key_proofs.iter()),
+            map_usage(
+                "unconfirmed_anchoring_keys",
+                unconfirmed_anchoring_keys.iter(),
+            ),
+        ];
+        let total_size = indexes.iter().map(|index| index.size).sum();
+        Ok(StorageUsage {
+            indexes,
+            total_size,
+        })
+    }
+
+    async fn tenant_usage_v1(self, tenant: PublicKey) -> api::Result<TenantUsage> {
+        let schema = Schema::new(self.0.service_data());
+        Ok(TenantUsage {
+            used: schema.tenant_usage(&tenant),
+            quota: schema
+                .try_actual_config()
+                .and_then(|config| config.find_tenant_quota(&tenant)),
+        })
+    }
+
+    async fn tenant_receipt_v1(self, query: TenantReceiptQuery) -> api::Result<TenantReceipt> {
+        let schema = Schema::new(self.0.service_data());
+        let id = TenantBlockId::new(query.tenant, query.height);
+        let tenant_data = schema.tenant_data(&id);
+        let position = tenant_data
+            .iter()
+            .position(|data_hash| data_hash == query.data_hash)
+            .ok_or_else(|| {
+                api::Error::not_found()
+                    .title("Tenant data not found")
+                    .detail(format!(
+                        "Data {} has not been submitted by the tenant {} at height {}",
+                        query.data_hash, query.tenant, query.height
+                    ))
+            })?;
+
+        let index_proof = self
+            .0
+            .data()
+            .proof_for_service_index("tenant_roots")
+            .unwrap();
+        Ok(TenantReceipt {
+            index_proof,
+            root_proof: schema.tenant_roots.get_proof(id),
+            data_proof: tenant_data.get_proof(position as u64),
+        })
+    }
+}
+
+pub(crate) fn wire(builder: &mut ServiceApiBuilder) {
+    builder
+        .public_scope()
+        .endpoint("v1/address/actual", |state, _query: ()| {
+            ApiImpl(state).actual_address_v1()
+        })
+        .endpoint("v1/address/following", |state, _query: ()| {
+            ApiImpl(state).following_address_v1()
+        })
+        .endpoint("v1/address/actual-descriptor", |state, _query: ()| {
+            ApiImpl(state).actual_descriptor_v1()
+        })
+        .endpoint("v1/address/following-descriptor", |state, _query: ()| {
+            ApiImpl(state).following_descriptor_v1()
+        })
+        .endpoint(
+            "v1/find-transaction",
+            |state, query: FindTransactionQuery| ApiImpl(state).find_transaction_v1(query.height),
+        )
+        .endpoint("v1/config", |state, _query: ()| ApiImpl(state).config_v1())
+        .endpoint("v1/status", |state, _query: ()| ApiImpl(state).status_v1())
+        .endpoint("v1/checkpoint", |state, _query: ()| {
+            ApiImpl(state).checkpoint_v1()
+        })
+        .endpoint("v1/find-anchor", |state, query: FindAnchorQuery| {
+            ApiImpl(state).find_anchor_v1(query.txid)
+        })
+        .endpoint(
+            "v1/finality-certificate",
+            |state, query: FinalityCertificateQuery| {
+                ApiImpl(state).finality_certificate_v1(query.tx_hash)
+            },
+        )
+        .endpoint("v1/pending-config", |state, _query: ()| {
+            ApiImpl(state).pending_config_v1()
+        })
+        .endpoint("v1/preview-proposal", |state, _query: ()| {
+            ApiImpl(state).preview_proposal_v1()
+        })
+        .endpoint("v1/fee-accounting", |state, query: FeeAccountingQuery| {
+            ApiImpl(state).fee_accounting_v1(query.epoch)
+        })
+        .endpoint("v1/balance-history", |state, query: BalanceHistoryQuery| {
+            ApiImpl(state).balance_history_v1(query.count)
+        })
+        .endpoint("v1/anchor-stats", |state, query: AnchorStatsQuery| {
+            ApiImpl(state).anchor_stats_v1(query.count)
+        })
+        .endpoint("v1/signature-audit", |state, query: SignatureAuditQuery| {
+            ApiImpl(state).signature_audit_v1(query.from)
+        })
+        .endpoint("v1/anchors", |state, query: AnchorRangeQuery| {
+            ApiImpl(state).export_anchors_v1(query)
+        })
+        .endpoint("v1/anchor-proofs", |state, query: AnchorRangeQuery| {
+            ApiImpl(state).anchor_proofs_v1(query)
+        })
+        .endpoint("v1/health", |state, _query: ()| ApiImpl(state).health_v1())
+        .endpoint("v1/anchor-status", |state, _query: ()| {
+            ApiImpl(state).anchor_status_v1()
+        })
+        .endpoint("v1/anchor-block", |state, query: AnchorBlockQuery| {
+            ApiImpl(state).anchor_block_v1(query.txid)
+        })
+        .endpoint("v1/proposal-conflicts", |state, _query: ()| {
+            ApiImpl(state).proposal_conflicts_v1()
+        })
+        .endpoint("v1/relay-status", |state, _query: ()| {
+            ApiImpl(state).relay_status_v1()
+        })
+        .endpoint("v1/next-anchor", |state, _query: ()| {
+            ApiImpl(state).next_anchor_v1()
+        })
+        .endpoint("v1/tenant-usage", |state, query: TenantQuery| {
+            ApiImpl(state).tenant_usage_v1(query.tenant)
+        })
+        .endpoint("v1/tenant-receipt", |state, query: TenantReceiptQuery| {
+            ApiImpl(state).tenant_receipt_v1(query)
+        });
+    // The sync utility uses the private API to pre-watch the following address and
+    // to restore the watch data of the actual one. The storage usage report iterates
+    // over the whole schema, thus it is available to the node operators only.
+    builder
+        .private_scope()
+        .endpoint("v1/address/actual-descriptor", |state, _query: ()| {
+            ApiImpl(state).actual_descriptor_v1()
+        })
+        .endpoint("v1/address/following-descriptor", |state, _query: ()| {
+            ApiImpl(state).following_descriptor_v1()
+        })
+        .endpoint("v1/storage-usage", |state, _query: ()| {
+            ApiImpl(state).storage_usage_v1()
+        });
+}
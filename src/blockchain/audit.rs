@@ -0,0 +1,291 @@
+// Copyright 2020 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Bitcoin-level verification of the signatures of the recorded anchoring transactions.
+//!
+//! The signatures are verified by the service when they are submitted, however, the
+//! audit re-verifies the witnesses of the finalized anchoring transactions against
+//! the outputs which they spend, exactly as the Bitcoin nodes do. Thus, any signature
+//! which has been accepted erroneously, for example, due to a bug in the past versions
+//! of the service, is caught.
+
+use bitcoin::blockdata::script::Script;
+use btc_transaction_utils::{multisig::RedeemScript, p2wsh::InputSigner, TxInRef};
+use exonum_merkledb::access::Access;
+use serde_derive::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::btc::{Sha256d, Transaction};
+
+use super::{replay::ReplayMismatch, Schema};
+
+/// Maximal number of the anchoring transactions verified by a single audit request.
+pub const MAX_AUDITED_TRANSACTIONS: u64 = 1_000;
+
+/// Reasons why the witness of the anchoring transaction input is invalid.
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum SignatureError {
+    /// Input spends a transaction which is unknown to the anchoring service.
+    #[error("Input {input} spends an unknown transaction {txid}.")]
+    UnknownInputTransaction {
+        /// Index of the input.
+        input: usize,
+        /// Identifier of the spent transaction.
+        txid: Sha256d,
+    },
+    /// Input spends an output which is absent in the spent transaction.
+    #[error("Input {input} spends an absent output.")]
+    UnknownOutput {
+        /// Index of the input.
+        input: usize,
+    },
+    /// Input witness is not a multisignature witness.
+    #[error("Input {input} has a malformed witness: {description}")]
+    MalformedWitness {
+        /// Index of the input.
+        input: usize,
+        /// Description of the problem.
+        description: String,
+    },
+    /// Witness script does not correspond to the spent output.
+    #[error("Witness script of input {input} does not match the spent output.")]
+    ScriptMismatch {
+        /// Index of the input.
+        input: usize,
+    },
+    /// Not enough signatures to spend the output.
+    #[error("Input {input} has {given} signatures, but {required} are required.")]
+    NotEnoughSignatures {
+        /// Index of the input.
+        input: usize,
+        /// Number of the required signatures.
+        required: usize,
+        /// Number of the given signatures.
+        given: usize,
+    },
+    /// Signature does not match any of the remaining keys of the witness script.
+    #[error("Signature {signature} of input {input} is invalid.")]
+    InvalidSignature {
+        /// Index of the input.
+        input: usize,
+        /// Position of the signature in the witness.
+        signature: usize,
+    },
+}
+
+/// Result of the anchoring transactions signature audit.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SignatureAuditReport {
+    /// Position of the first audited anchoring transaction.
+    pub from: u64,
+    /// Position following the last audited anchoring transaction, i.e. the position
+    /// from which the next audit should start.
+    pub to: u64,
+    /// Total number of the verified signatures.
+    pub verified_signatures: u64,
+    /// Anchoring transactions with the invalid signatures.
+    pub mismatches: Vec<ReplayMismatch>,
+}
+
+impl SignatureAuditReport {
+    /// Checks that all of the signatures of the audited transactions are valid.
+    pub fn is_consistent(&self) -> bool {
+        self.mismatches.is_empty()
+    }
+}
+
+impl<T: Access> Schema<T> {
+    /// Verifies the signatures of the anchoring transactions starting from the given
+    /// position in the anchoring chain. At most `MAX_AUDITED_TRANSACTIONS` transactions
+    /// are verified at once.
+    pub fn audit_signatures(&self, from: u64) -> SignatureAuditReport {
+        let from = from.min(self.transactions_chain.len());
+        let mut report = SignatureAuditReport {
+            from,
+            to: from,
+            ..SignatureAuditReport::default()
+        };
+
+        for tx in self
+            .transactions_chain
+            .iter_from(from)
+            .take(MAX_AUDITED_TRANSACTIONS as usize)
+        {
+            match verify_transaction_signatures(|txid| self.spent_transaction(txid), &tx) {
+                Ok(signatures) => report.verified_signatures += signatures as u64,
+                Err(e) => report.mismatches.push(ReplayMismatch {
+                    index: report.to,
+                    txid: tx.id(),
+                    reason: e.to_string(),
+                }),
+            }
+            report.to += 1;
+        }
+        report
+    }
+
+    /// Finds the transaction spent by an anchoring transaction, that is, either
+    /// the previous anchoring transaction or a funding one.
+    fn spent_transaction(&self, txid: &Sha256d) -> Option<Transaction> {
+        self.transaction_index(txid)
+            .and_then(|index| self.transactions_chain.get(index))
+            .or_else(|| self.spent_funding_transactions.get(txid))
+    }
+}
+
+/// Verifies the witnesses of all inputs of the transaction against the outputs
+/// returned by the given lookup. Returns the number of the verified signatures.
+fn verify_transaction_signatures(
+    spent_tx: impl Fn(&Sha256d) -> Option<Transaction>,
+    tx: &Transaction,
+) -> Result<usize, SignatureError> {
+    let mut signatures = 0;
+    for (input, txin) in tx.0.input.iter().enumerate() {
+        let txid = Sha256d::from(txin.previous_output.txid);
+        let prev_tx =
+            spent_tx(&txid).ok_or(SignatureError::UnknownInputTransaction { input, txid })?;
+        signatures += verify_input_signatures(tx, input, &prev_tx)?;
+    }
+    Ok(signatures)
+}
+
+/// Verifies the multisignature witness of the given input. Returns the number of
+/// the verified signatures.
+fn verify_input_signatures(
+    tx: &Transaction,
+    input: usize,
+    prev_tx: &Transaction,
+) -> Result<usize, SignatureError> {
+    let malformed = |description: String| SignatureError::MalformedWitness { input, description };
+
+    let txin = &tx.0.input[input];
+    let prev_out = prev_tx
+        .0
+        .output
+        .get(txin.previous_output.vout as usize)
+        .ok_or(SignatureError::UnknownOutput { input })?;
+    // The witness consists of the dummy element consumed by `OP_CHECKMULTISIG`,
+    // the signatures and the witness script.
+    let (witness_script, signatures) = match txin.witness.split_last() {
+        Some((witness_script, elements)) if !elements.is_empty() => {
+            (witness_script, &elements[1..])
+        }
+        _ => return Err(malformed("too few witness elements".to_owned())),
+    };
+    let redeem_script = RedeemScript::from_script(Script::from(witness_script.clone()))
+        .map_err(|e| malformed(e.to_string()))?;
+    if redeem_script.as_ref().to_v0_p2wsh() != prev_out.script_pubkey {
+        return Err(SignatureError::ScriptMismatch { input });
+    }
+
+    let content = redeem_script.content();
+    if signatures.len() < content.quorum {
+        return Err(SignatureError::NotEnoughSignatures {
+            input,
+            required: content.quorum,
+            given: signatures.len(),
+        });
+    }
+
+    // `OP_CHECKMULTISIG` matches the signatures with the keys in the order of
+    // the witness script.
+    let signer = InputSigner::new(redeem_script.clone());
+    let mut public_keys = content.public_keys.iter();
+    for (position, bytes) in signatures.iter().enumerate() {
+        let signature = btc_transaction_utils::InputSignature::from_bytes(bytes.clone())
+            .map_err(|e| malformed(e.to_string()))?;
+        let is_valid = public_keys.by_ref().any(|public_key| {
+            signer
+                .verify_input(
+                    TxInRef::new(tx.as_ref(), input),
+                    prev_tx.as_ref(),
+                    public_key,
+                    &signature,
+                )
+                .is_ok()
+        });
+        if !is_valid {
+            return Err(SignatureError::InvalidSignature {
+                input,
+                signature: position,
+            });
+        }
+    }
+    Ok(signatures.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::network::constants::Network;
+    use btc_transaction_utils::{multisig::RedeemScriptBuilder, p2wsh};
+
+    use super::{verify_input_signatures, SignatureError};
+    use crate::{
+        btc::{self, RescueTransaction},
+        test_helpers::create_fake_funding_transaction,
+    };
+
+    #[test]
+    fn verify_multisig_witness() {
+        let keypairs = (0..4)
+            .map(|_| btc::gen_keypair(Network::Testnet))
+            .collect::<Vec<_>>();
+        let redeem_script = RedeemScriptBuilder::with_public_keys(keypairs.iter().map(|x| x.0 .0))
+            .quorum(3)
+            .to_script()
+            .unwrap();
+        let address = p2wsh::address(&redeem_script, Network::Testnet).into();
+        let prev_tx = create_fake_funding_transaction(&address, 100_000);
+
+        let rescue = || {
+            RescueTransaction::new(redeem_script.clone(), prev_tx.clone(), &address, 10).unwrap()
+        };
+        let signatures = keypairs
+            .iter()
+            .map(|(public_key, private_key)| (*public_key, rescue().sign(private_key).unwrap()))
+            .collect::<Vec<_>>();
+        let tx = rescue().finalize(signatures).unwrap();
+        assert_eq!(verify_input_signatures(&tx, 0, &prev_tx), Ok(3));
+
+        // Signatures in the wrong order are rejected by `OP_CHECKMULTISIG`.
+        let mut swapped_tx = tx.clone();
+        swapped_tx.0.input[0].witness.swap(1, 2);
+        assert_eq!(
+            verify_input_signatures(&swapped_tx, 0, &prev_tx),
+            Err(SignatureError::InvalidSignature {
+                input: 0,
+                signature: 1
+            })
+        );
+
+        let mut incomplete_tx = tx.clone();
+        incomplete_tx.0.input[0].witness.remove(1);
+        assert_eq!(
+            verify_input_signatures(&incomplete_tx, 0, &prev_tx),
+            Err(SignatureError::NotEnoughSignatures {
+                input: 0,
+                required: 3,
+                given: 2,
+            })
+        );
+
+        let other_key = btc::gen_keypair(Network::Testnet).0;
+        let other_address = btc::Address(bitcoin::Address::p2wpkh(&other_key.0, Network::Testnet));
+        let other_tx = create_fake_funding_transaction(&other_address, 100_000);
+        assert_eq!(
+            verify_input_signatures(&tx, 0, &other_tx),
+            Err(SignatureError::ScriptMismatch { input: 0 })
+        );
+    }
+}
@@ -14,11 +14,17 @@
 
 //! Additional data types for the BTC anchoring information schema.
 
-use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
-use exonum::crypto::{self, Hash};
+use byteorder::{BigEndian, LittleEndian, ReadBytesExt, WriteBytesExt};
+use exonum::{
+    crypto::{self, Hash, PublicKey, PUBLIC_KEY_LENGTH},
+    helpers::Height,
+};
+use exonum_derive::{BinaryValue, ObjectHash};
 use exonum_merkledb::{BinaryKey, ObjectHash};
+use exonum_proto::ProtobufConvert;
+use serde_derive::{Deserialize, Serialize};
 
-use crate::btc::Sha256d;
+use crate::btc::{InputSignature, Sha256d, Transaction};
 
 use std::io::{Cursor, Read, Write};
 
@@ -72,6 +78,368 @@ impl ObjectHash for TxInputId {
     }
 }
 
+/// Identifier of the data submitted by the tenant in the Exonum block with the given height.
+///
+/// Identifiers of the same tenant are ordered by the block height.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TenantBlockId {
+    /// Tenant key.
+    pub tenant: PublicKey,
+    /// Exonum block height.
+    pub height: Height,
+}
+
+impl TenantBlockId {
+    /// Creates a new identifier.
+    pub fn new(tenant: PublicKey, height: Height) -> Self {
+        Self { tenant, height }
+    }
+}
+
+impl BinaryKey for TenantBlockId {
+    fn size(&self) -> usize {
+        self.tenant.size() + self.height.0.size()
+    }
+
+    fn read(inp: &[u8]) -> Self {
+        let tenant = PublicKey::read(&inp[..PUBLIC_KEY_LENGTH]);
+        let height = Cursor::new(&inp[PUBLIC_KEY_LENGTH..])
+            .read_u64::<BigEndian>()
+            .unwrap();
+        Self {
+            tenant,
+            height: Height(height),
+        }
+    }
+
+    fn write(&self, out: &mut [u8]) -> usize {
+        self.tenant.write(&mut out[..PUBLIC_KEY_LENGTH]);
+        let mut writer = Cursor::new(&mut out[PUBLIC_KEY_LENGTH..]);
+        writer.write_u64::<BigEndian>(self.height.0).unwrap();
+        self.size()
+    }
+}
+
+impl ObjectHash for TenantBlockId {
+    fn object_hash(&self) -> Hash {
+        let mut bytes = [0_u8; PUBLIC_KEY_LENGTH + 8];
+        self.write(&mut bytes);
+        crypto::hash(bytes.as_ref())
+    }
+}
+
+/// Fees of the anchoring transactions finalized by the anchoring node within
+/// the anchoring epoch.
+///
+/// The anchoring transaction is attributed to the node which has submitted the last
+/// input signature required to finalize it.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Serialize,
+    Deserialize,
+    ProtobufConvert,
+    BinaryValue,
+    ObjectHash,
+)]
+#[protobuf_convert(source = "crate::proto::internal::FeeRecord")]
+pub struct FeeRecord {
+    /// Number of the finalized anchoring transactions.
+    pub transactions_count: u64,
+    /// Total fee of the finalized anchoring transactions in satoshis.
+    pub total_fee: u64,
+}
+
+impl FeeRecord {
+    /// Adds the fee of the anchoring transaction to the record.
+    pub fn add_fee(&mut self, fee: u64) {
+        self.transactions_count += 1;
+        self.total_fee += fee;
+    }
+}
+
+/// Anchoring wallet balance after the anchoring transaction finalized by the service.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Serialize,
+    Deserialize,
+    ProtobufConvert,
+    BinaryValue,
+    ObjectHash,
+)]
+#[protobuf_convert(source = "crate::proto::internal::BalanceRecord")]
+pub struct BalanceRecord {
+    /// Height of the Exonum block in which the anchoring transaction has been finalized.
+    pub block_height: u64,
+    /// Total value of the anchoring transaction inputs in satoshis.
+    pub input_value: u64,
+    /// Fee of the anchoring transaction in satoshis.
+    pub fee: u64,
+    /// Value of the change output in satoshis, i.e. the remaining balance of the
+    /// anchoring wallet.
+    pub change: u64,
+}
+
+/// Fee and size accounting of the anchoring transaction finalized by the service.
+#[derive(
+    Debug,
+    Clone,
+    Default,
+    PartialEq,
+    Serialize,
+    Deserialize,
+    ProtobufConvert,
+    BinaryValue,
+    ObjectHash,
+)]
+#[protobuf_convert(source = "crate::proto::internal::AnchorAccounting")]
+pub struct AnchorAccounting {
+    /// Fee of the anchoring transaction in satoshis.
+    pub fee: u64,
+    /// Fee rate in satoshis per 1000 virtual bytes.
+    pub fee_rate: u64,
+    /// Virtual size of the anchoring transaction in bytes.
+    pub vsize: u64,
+    /// Weight of the anchoring transaction in weight units.
+    pub weight: u64,
+    /// Values of the anchoring transaction inputs in satoshis.
+    pub input_values: Vec<u64>,
+    /// Values of the anchoring transaction outputs in satoshis.
+    pub output_values: Vec<u64>,
+}
+
+impl AnchorAccounting {
+    /// Creates the accounting record for the signed anchoring transaction spending
+    /// the outputs with the given values.
+    pub fn new(transaction: &Transaction, input_values: Vec<u64>) -> Self {
+        let output_values = transaction
+            .0
+            .output
+            .iter()
+            .map(|out| out.value)
+            .collect::<Vec<_>>();
+        let fee = input_values
+            .iter()
+            .sum::<u64>()
+            .saturating_sub(output_values.iter().sum());
+        let weight = transaction.0.get_weight() as u64;
+        // Virtual size is the weight divided by four and rounded up.
+        let vsize = (weight + 3) / 4;
+        Self {
+            fee,
+            fee_rate: fee * 1000 / vsize.max(1),
+            vsize,
+            weight,
+            input_values,
+            output_values,
+        }
+    }
+}
+
+/// Health indicators of the anchoring transaction finalized by the service.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Serialize,
+    Deserialize,
+    ProtobufConvert,
+    BinaryValue,
+    ObjectHash,
+)]
+#[protobuf_convert(source = "crate::proto::internal::HealthRecord")]
+pub struct HealthRecord {
+    /// Anchored Exonum block height.
+    pub anchored_height: u64,
+    /// Height of the Exonum block in which the anchoring transaction has been finalized.
+    pub finalized_height: u64,
+    /// Target latency of the finalization in Exonum blocks, i.e. the anchoring interval.
+    pub target_latency: u64,
+    /// Number of the anchoring nodes which have signed the anchoring proposals since
+    /// the previous anchor.
+    pub active_signers: u32,
+    /// Total number of the anchoring nodes.
+    pub anchoring_nodes: u32,
+}
+
+impl HealthRecord {
+    /// Returns the number of Exonum blocks between the anchored block and the block
+    /// in which the anchoring transaction has been finalized.
+    pub fn latency(&self) -> u64 {
+        self.finalized_height.saturating_sub(self.anchored_height)
+    }
+
+    /// Checks whether the anchoring transaction has been finalized within the target latency.
+    pub fn is_on_time(&self) -> bool {
+        self.latency() <= self.target_latency
+    }
+}
+
+/// Rolling health score of the anchoring computed over the latest health records.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Serialize,
+    Deserialize,
+    ProtobufConvert,
+    BinaryValue,
+    ObjectHash,
+)]
+#[protobuf_convert(source = "crate::proto::internal::HealthScore")]
+pub struct HealthScore {
+    /// Number of the anchoring transactions taken into account.
+    pub anchors_count: u64,
+    /// Number of the anchoring transactions finalized within the target latency.
+    pub on_time_anchors: u64,
+    /// Percentage of the anchoring nodes which sign the anchoring proposals.
+    pub participation_rate: u64,
+    /// Health score from 0 to 100, the average of the percentage of the anchoring
+    /// transactions finalized on time and the participation rate.
+    pub score: u64,
+}
+
+impl HealthScore {
+    /// Computes the health score over the given records.
+    pub fn from_records(records: impl IntoIterator<Item = HealthRecord>) -> Self {
+        let (mut anchors_count, mut on_time_anchors) = (0, 0);
+        let (mut active_signers, mut anchoring_nodes) = (0_u64, 0_u64);
+        for record in records {
+            anchors_count += 1;
+            on_time_anchors += record.is_on_time() as u64;
+            active_signers += u64::from(record.active_signers);
+            anchoring_nodes += u64::from(record.anchoring_nodes);
+        }
+        if anchors_count == 0 || anchoring_nodes == 0 {
+            return Self::default();
+        }
+
+        let on_time_rate = on_time_anchors * 100 / anchors_count;
+        let participation_rate = active_signers * 100 / anchoring_nodes;
+        Self {
+            anchors_count,
+            on_time_anchors,
+            participation_rate,
+            score: (on_time_rate + participation_rate) / 2,
+        }
+    }
+}
+
+/// Status of the latest anchoring transaction in the Bitcoin network reported by
+/// the anchoring node.
+#[derive(
+    Debug, Clone, PartialEq, Serialize, Deserialize, ProtobufConvert, BinaryValue, ObjectHash,
+)]
+#[protobuf_convert(source = "crate::proto::internal::AnchorStatusReport")]
+pub struct AnchorStatusReport {
+    /// Anchoring transaction identifier.
+    pub txid: Sha256d,
+    /// Whether the transaction is known to the Bitcoin node.
+    pub known: bool,
+    /// Number of the transaction confirmations, zero if it is in the mempool.
+    pub confirmations: u32,
+    /// Height of the Exonum block in which the status has been reported.
+    pub reported_height: u64,
+}
+
+impl AnchorStatusReport {
+    /// Checks that the transaction is in the mempool of the Bitcoin node.
+    pub fn is_in_mempool(&self) -> bool {
+        self.known && self.confirmations == 0
+    }
+
+    /// Checks that the transaction is unknown to the Bitcoin node, i.e. it has never been
+    /// broadcast or has been evicted from the mempool.
+    pub fn is_missing(&self) -> bool {
+        !self.known
+    }
+}
+
+/// Health of the Bitcoin relay reported by the anchoring node.
+#[derive(
+    Debug, Clone, PartialEq, Serialize, Deserialize, ProtobufConvert, BinaryValue, ObjectHash,
+)]
+#[protobuf_convert(source = "crate::proto::internal::RelayStatusReport")]
+pub struct RelayStatusReport {
+    /// Whether the latest health check of the relay has succeeded.
+    pub healthy: bool,
+    /// Height of the latest Bitcoin block known to the relay, zero if unknown.
+    pub block_height: u64,
+    /// Number of the consecutive failed health checks.
+    pub failed_checks: u32,
+    /// Height of the Exonum block in which the status has been reported.
+    pub reported_height: u64,
+}
+
+/// Evidence that the anchoring node has signed a transaction, which differs from
+/// the anchoring proposal computed by the service for the same anchoring slot.
+///
+/// The signature is verified against the conflicting transaction, which spends the same
+/// output as the anchoring proposal. The evidence is attributed to the node by the
+/// `SignInput` transaction, which is signed by its service key.
+#[derive(
+    Debug, Clone, PartialEq, Serialize, Deserialize, ProtobufConvert, BinaryValue, ObjectHash,
+)]
+#[protobuf_convert(source = "crate::proto::internal::ProposalConflict")]
+pub struct ProposalConflict {
+    /// Height of the blockchain anchored by the proposal.
+    pub anchoring_height: Height,
+    /// Anchoring epoch of the proposal.
+    pub epoch: u64,
+    /// Identifier of the anchoring proposal computed by the service.
+    pub proposal_txid: Sha256d,
+    /// Identifier of the conflicting transaction signed by the anchoring node.
+    pub conflicting_txid: Sha256d,
+    /// Service key of the anchoring node.
+    pub author: PublicKey,
+    /// Signed input of the conflicting transaction.
+    pub input: u32,
+    /// Signature content.
+    pub input_signature: InputSignature,
+    /// Height of the Exonum block in which the conflict has been detected.
+    pub detected_height: Height,
+    /// Content of the conflicting transaction.
+    pub conflicting_transaction: Transaction,
+}
+
+impl ProposalConflict {
+    /// Checks that the conflict has been detected for the anchoring proposal
+    /// of the given height and epoch.
+    pub fn is_for_slot(&self, anchoring_height: Height, epoch: u64) -> bool {
+        self.anchoring_height == anchoring_height && self.epoch == epoch
+    }
+}
+
+#[test]
+fn test_health_score() {
+    let record = |latency, active_signers| HealthRecord {
+        anchored_height: 100,
+        finalized_height: 100 + latency,
+        target_latency: 10,
+        active_signers,
+        anchoring_nodes: 4,
+    };
+
+    assert_eq!(HealthScore::from_records(vec![]), HealthScore::default());
+    let score = HealthScore::from_records(vec![record(2, 4), record(10, 4)]);
+    assert_eq!(score.score, 100);
+    let score = HealthScore::from_records(vec![record(2, 4), record(11, 2)]);
+    assert_eq!(score.on_time_anchors, 1);
+    assert_eq!(score.participation_rate, 75);
+    assert_eq!(score.score, 62);
+}
+
 #[test]
 fn test_tx_input_id_binary_key() {
     let txout = TxInputId {
@@ -88,3 +456,21 @@ fn test_tx_input_id_binary_key() {
     let buf_hash = crypto::hash(&buf);
     assert_eq!(txout2.object_hash(), buf_hash);
 }
+
+#[test]
+fn test_tenant_block_id_binary_key() {
+    let tenant = crypto::gen_keypair().0;
+    let id = TenantBlockId::new(tenant, Height(258));
+
+    let mut buf = vec![0_u8; id.size()];
+    id.write(&mut buf);
+
+    let id2 = TenantBlockId::read(&buf);
+    assert_eq!(id, id2);
+    assert_eq!(id2.object_hash(), crypto::hash(&buf));
+
+    // Identifiers of the same tenant should be ordered by the block height.
+    let mut buf2 = vec![0_u8; id.size()];
+    TenantBlockId::new(tenant, Height(1024)).write(&mut buf2);
+    assert!(buf < buf2);
+}
@@ -14,13 +14,19 @@
 
 //! Error types of the BTC anchoring service.
 
-use exonum::runtime::{ExecutionError, ExecutionFail};
+use exonum::runtime::{ErrorKind, ExecutionError, ExecutionFail};
 use exonum_derive::ExecutionFail;
+use serde_derive::{Deserialize, Serialize};
+
+use std::fmt::{self, Display};
 
 use crate::btc;
 
-/// Possible errors during execution of the `sign_input` method.
-#[derive(Debug, ExecutionFail)]
+/// Possible errors during execution of the anchoring service transactions.
+///
+/// Transactions of the anchoring service never panic, so the malformed transactions
+/// are rejected with one of these errors instead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ExecutionFail)]
 pub enum Error {
     /// Transaction author is not authorized to sign anchoring transactions.
     UnauthorizedAnchoringKey = 0,
@@ -36,11 +42,211 @@ pub enum Error {
     AlreadyUsedFundingTx = 5,
     /// Funding transaction is unsuitable.
     UnsuitableFundingTx = 6,
+    /// Transaction author is not a registered tenant.
+    UnknownTenant = 7,
+    /// Tenant has exhausted its data quota.
+    TenantQuotaExceeded = 8,
+    /// Transaction author is not a validator of the blockchain.
+    UnknownValidator = 9,
+    /// The anchoring key of the validator has been already submitted.
+    AnchoringKeyExists = 10,
+    /// The submitted anchoring key is unsuitable for the anchoring configuration.
+    InvalidAnchoringKey = 11,
+    /// The number of anchoring keys in the proposed configuration is unsuitable
+    /// for the redeem script.
+    InvalidAnchoringKeysCount = 12,
+    /// The proposed configuration contains duplicate anchoring keys.
+    DuplicateAnchoringKeys = 13,
+    /// The Bitcoin network of the proposed configuration differs from the actual one.
+    NetworkMismatch = 14,
+    /// The signature has been produced for another anchoring proposal.
+    ReplayedSignature = 15,
+    /// The anchoring key of the transaction author is absent in the redeem script.
+    KeyNotInRedeemScript = 16,
+    /// The finalized anchoring transaction has malformed metadata or output address.
+    MalformedAnchoringTransaction = 17,
+    /// The anchoring service has been started without the configuration, which
+    /// has not been applied yet.
+    NotConfigured = 18,
+    /// The external transaction neither spends the output of the latest anchoring
+    /// transaction nor conflicts with it.
+    UnsuitableExternalAnchor = 19,
+    /// The signature has been produced for the anchoring proposal, which is older
+    /// than the maximal signature age.
+    StaleSignature = 20,
+    /// The configuration does not contain a valid proof of possession of the added
+    /// anchoring key.
+    MissingKeyPossessionProof = 21,
+    /// The proposed anchoring parameters are unsuitable for the anchoring configuration.
+    InvalidParameters = 22,
+    /// The anchoring address of the proposed configuration has been used by a previous
+    /// anchoring epoch or is one of the known external addresses.
+    AddressReuse = 23,
+    /// The anchoring transaction is absent in the anchoring chain.
+    UnknownAnchoringTransaction = 24,
+    /// Finalization of the anchoring proposal has been halted, because an anchoring node
+    /// has signed a conflicting transaction for the same anchoring height and epoch.
+    AnchoringHalted = 25,
+    /// The high-risk maintenance operation has not been approved by the anchoring nodes
+    /// within the approval window.
+    ApprovalExpired = 26,
+    /// Another validator joining the anchoring awaits the inclusion of its anchoring key
+    /// into the anchoring configuration, or the transition of the anchoring chain is
+    /// in progress.
+    OnboardingInProgress = 27,
 }
 
 impl Error {
+    /// All errors of the anchoring service in the order of their codes.
+    const ALL: [Self; 28] = [
+        Error::UnauthorizedAnchoringKey,
+        Error::NoSuchInput,
+        Error::InputVerificationFailed,
+        Error::AnchoringBuilderError,
+        Error::UnexpectedProposalTxId,
+        Error::AlreadyUsedFundingTx,
+        Error::UnsuitableFundingTx,
+        Error::UnknownTenant,
+        Error::TenantQuotaExceeded,
+        Error::UnknownValidator,
+        Error::AnchoringKeyExists,
+        Error::InvalidAnchoringKey,
+        Error::InvalidAnchoringKeysCount,
+        Error::DuplicateAnchoringKeys,
+        Error::NetworkMismatch,
+        Error::ReplayedSignature,
+        Error::KeyNotInRedeemScript,
+        Error::MalformedAnchoringTransaction,
+        Error::NotConfigured,
+        Error::UnsuitableExternalAnchor,
+        Error::StaleSignature,
+        Error::MissingKeyPossessionProof,
+        Error::InvalidParameters,
+        Error::AddressReuse,
+        Error::UnknownAnchoringTransaction,
+        Error::AnchoringHalted,
+        Error::ApprovalExpired,
+        Error::OnboardingInProgress,
+    ];
+
+    /// Returns the error with the given code, if any.
+    pub fn from_code(code: u8) -> Option<Self> {
+        Self::ALL.get(code as usize).copied()
+    }
+
     /// Creates an error instance from the anchoring transaction builder error.
     pub fn anchoring_builder_error(error: btc::BuilderError) -> ExecutionError {
         Error::AnchoringBuilderError.with_description(error)
     }
+
+    /// Creates an error instance caused by the given field of the rejected transaction.
+    /// The field can be extracted from the execution error via
+    /// [`Rejection::from_execution_error`](struct.Rejection.html#method.from_execution_error).
+    pub fn with_field(self, field: &str, message: impl Display) -> ExecutionError {
+        self.with_description(format!("[{}] {}", field, message))
+    }
+}
+
+/// Structured details of the rejected anchoring service transaction, which allow
+/// the software submitting the transactions to react on the rejection programmatically.
+///
+/// The details are restored from the execution status of the transaction, which
+/// description starts with the `[field]` prefix if the rejection has been caused
+/// by the specific transaction field.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Rejection {
+    /// Code of the anchoring service error.
+    pub code: u8,
+    /// Human-readable message.
+    pub message: String,
+    /// Name of the transaction field, which has caused the rejection, if any.
+    pub field: Option<String>,
+}
+
+impl Rejection {
+    /// Extracts the rejection details from the execution error of the anchoring service
+    /// transaction. Returns `None` if the error has not been produced by the service.
+    pub fn from_execution_error(error: &ExecutionError) -> Option<Self> {
+        let code = match error.kind() {
+            ErrorKind::Service { code } => code,
+            _ => return None,
+        };
+        let description = error.description();
+
+        let field = Some(description)
+            .filter(|description| description.starts_with('['))
+            .and_then(|description| description.find("] "))
+            .map(|end| (&description[1..end], &description[end + 2..]))
+            .filter(|(field, _)| {
+                !field.is_empty()
+                    && field
+                        .chars()
+                        .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+            });
+        let (field, message) = match field {
+            Some((field, message)) => (Some(field.to_owned()), message.to_owned()),
+            None => (None, description.to_owned()),
+        };
+        // Errors without the description are explained by the error itself.
+        let message = match Error::from_code(code) {
+            Some(error) if message.is_empty() => error.description(),
+            _ => message,
+        };
+        Some(Self {
+            code,
+            message,
+            field,
+        })
+    }
+
+    /// Returns the anchoring service error of this rejection, if the code is known.
+    pub fn error(&self) -> Option<Error> {
+        Error::from_code(self.code)
+    }
+}
+
+impl Display for Rejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.field {
+            Some(field) => write!(
+                f,
+                "{} (code {}, field `{}`)",
+                self.message, self.code, field
+            ),
+            None => write!(f, "{} (code {})", self.message, self.code),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use exonum::runtime::{ErrorKind, ExecutionFail};
+
+    use super::{Error, Rejection};
+
+    #[test]
+    fn error_codes() {
+        for (code, error) in Error::ALL.iter().enumerate() {
+            assert_eq!(error.kind(), ErrorKind::Service { code: code as u8 });
+        }
+        assert!(Error::from_code(Error::ALL.len() as u8).is_none());
+    }
+
+    #[test]
+    fn rejection_from_execution_error() {
+        let error = Error::StaleSignature.with_field("anchoring_height", "Proposal is too old.");
+        let rejection = Rejection::from_execution_error(&error).unwrap();
+        assert_eq!(rejection.code, 20);
+        assert_eq!(rejection.field.as_deref(), Some("anchoring_height"));
+        assert_eq!(rejection.message, "Proposal is too old.");
+
+        let error = Error::InputVerificationFailed.with_description("secp: bad signature");
+        let rejection = Rejection::from_execution_error(&error).unwrap();
+        assert_eq!(rejection.field, None);
+        assert_eq!(rejection.message, "secp: bad signature");
+
+        let rejection = Rejection::from_execution_error(&Error::NotConfigured.into()).unwrap();
+        assert_eq!(rejection.error(), Some(Error::NotConfigured));
+        assert_eq!(rejection.message, Error::NotConfigured.description());
+    }
 }
@@ -0,0 +1,88 @@
+// Copyright 2019 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Bounded-memory export of the anchoring chain.
+//!
+//! Unlike the [chain scan](../scan/index.html), the export reads the anchoring chain
+//! lazily, one transaction at a time. The API exports the chain in pages, each of which
+//! contains the position to continue the export from, so the chains of any length can
+//! be exported without loading them into memory at once.
+
+use exonum_merkledb::access::Access;
+use serde_derive::{Deserialize, Serialize};
+
+use super::{scan::AnchorRecord, Schema};
+
+/// Maximal number of the anchoring transactions exported by a single request.
+pub const MAX_EXPORTED_ANCHORS: u64 = 1_000;
+
+/// Page of the exported anchoring chain.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AnchorsExport {
+    /// Position of the first exported anchoring transaction.
+    pub from: u64,
+    /// Position following the last exported anchoring transaction, i.e. the position
+    /// from which the next page should start.
+    pub to: u64,
+    /// Total number of the anchoring transactions.
+    pub transactions_count: u64,
+    /// Anchored blocks of the exported transactions. Transactions without a payload
+    /// are omitted.
+    pub records: Vec<AnchorRecord>,
+}
+
+impl AnchorsExport {
+    /// Returns the position from which the next page should start, or `None` if the
+    /// page is the last one.
+    pub fn next_from(&self) -> Option<u64> {
+        if self.to < self.transactions_count {
+            Some(self.to)
+        } else {
+            None
+        }
+    }
+}
+
+impl<T: Access> Schema<T> {
+    /// Returns an iterator over the anchored blocks of the anchoring transactions starting
+    /// from the given position in the anchoring chain. The transactions are read lazily.
+    pub fn anchor_records(&self, from: u64) -> impl Iterator<Item = AnchorRecord> + '_ {
+        self.transactions_chain
+            .iter_from(from)
+            .zip(from..)
+            .filter_map(|(tx, index)| AnchorRecord::new(index, &tx))
+    }
+
+    /// Exports at most `count` anchoring transactions starting from the given position
+    /// in the anchoring chain. The count is limited by `MAX_EXPORTED_ANCHORS`.
+    pub fn export_anchors(&self, from: u64, count: u64) -> AnchorsExport {
+        let transactions_count = self.transactions_chain.len();
+        let from = from.min(transactions_count);
+        let to = from
+            .saturating_add(count.min(MAX_EXPORTED_ANCHORS))
+            .min(transactions_count);
+        AnchorsExport {
+            from,
+            to,
+            transactions_count,
+            records: self
+                .transactions_chain
+                .iter_from(from)
+                .take((to - from) as usize)
+                .zip(from..)
+                .filter_map(|(tx, index)| AnchorRecord::new(index, &tx))
+                .collect(),
+        }
+    }
+}
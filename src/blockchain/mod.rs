@@ -15,21 +15,34 @@
 //! Blockchain implementation details for the BTC anchoring service.
 
 pub use self::{schema::Schema, transactions::BtcAnchoringInterface};
-pub use crate::proto::{AddFunds, SignInput};
+pub use crate::proto::{
+    AddFunds, ConfirmAnchorFinality, ImportAnchor, RecordAnchorBlock, RegisterStandbyKey,
+    ReportAnchorStatus, ReportRelayStatus, SignInput, SubmitAnchoringKey, SubmitData,
+    UpdateParameters,
+};
 
 use bitcoin::blockdata::script::Script;
-use btc_transaction_utils::{multisig::RedeemScript, p2wsh};
+use btc_transaction_utils::multisig::RedeemScript;
 use exonum::helpers::Height;
+use serde_derive::{Deserialize, Serialize};
 
 use crate::{btc::Address, config::Config};
 
+pub mod audit;
 pub mod data_layout;
 pub mod errors;
+pub mod export;
+pub mod replay;
+pub mod scan;
 pub mod schema;
 pub mod transactions;
 
 /// Current state of the BTC anchoring service.
-#[derive(Debug, Clone)]
+///
+/// The state is serialized as an object with the `type` field, which is equal
+/// either to `regular` or to `transition`, and the configuration fields.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
 pub enum BtcAnchoringState {
     /// The usual anchoring workflow.
     Regular {
@@ -49,27 +62,33 @@ impl BtcAnchoringState {
     /// Returns the redeem script corresponding to the address to which the anchoring
     /// transaction will be sent.
     pub fn redeem_script(&self) -> RedeemScript {
+        self.output_config().redeem_script()
+    }
+
+    /// Returns the `script_pubkey` for the corresponding redeem script and output type.
+    pub fn script_pubkey(&self) -> Script {
+        self.output_config().anchoring_out_script()
+    }
+
+    /// Returns the output address for the corresponding redeem script and output type.
+    pub fn output_address(&self) -> Address {
+        self.output_config().anchoring_address()
+    }
+
+    /// Returns the configuration of the address to which the anchoring transaction
+    /// will be sent.
+    fn output_config(&self) -> &Config {
         match self {
             BtcAnchoringState::Regular {
                 actual_configuration,
-            } => actual_configuration.redeem_script(),
+            } => actual_configuration,
             BtcAnchoringState::Transition {
                 following_configuration,
                 ..
-            } => following_configuration.redeem_script(),
+            } => following_configuration,
         }
     }
 
-    /// Returns the `script_pubkey` for the corresponding redeem script.
-    pub fn script_pubkey(&self) -> Script {
-        self.redeem_script().as_ref().to_v0_p2wsh()
-    }
-
-    /// Returns the output address for the corresponding redeem script.
-    pub fn output_address(&self) -> Address {
-        p2wsh::address(&self.redeem_script(), self.actual_config().network).into()
-    }
-
     /// Checks that anchoring state is regular.
     pub fn is_regular(&self) -> bool {
         if let BtcAnchoringState::Regular { .. } = self {
@@ -113,8 +132,12 @@ impl BtcAnchoringState {
     }
 
     /// Returns the nearest following anchoring height for the given height.
+    ///
+    /// If there are no anchored blocks yet, then the start height of the actual
+    /// configuration is returned.
     pub fn following_anchoring_height(&self, latest_anchored_height: Option<Height>) -> Height {
-        latest_anchored_height.map_or_else(Height::zero, |height| match self {
+        let start_height = self.actual_config().start_height;
+        latest_anchored_height.map_or(start_height, |height| match self {
             BtcAnchoringState::Regular {
                 ref actual_configuration,
             } => actual_configuration.following_anchoring_height(height),
@@ -0,0 +1,235 @@
+// Copyright 2019 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Deterministic replay of the historical anchoring transactions.
+//!
+//! Each recorded anchoring transaction is re-derived from the data stored in the
+//! service schema (previous anchoring transaction, spent funding transactions and
+//! anchored blocks) by the same transaction builder which is used to create
+//! anchoring proposals. Any difference between the re-derived and the recorded
+//! transactions indicates either a nondeterminism in the past proposals or
+//! tampering with the node database.
+
+use exonum::{blockchain::Schema as CoreSchema, crypto::Hash, helpers::Height};
+use exonum_merkledb::access::Access;
+use serde_derive::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::btc::{BtcAnchoringTransactionBuilder, BuilderError, Sha256d, Transaction};
+
+use super::Schema;
+
+/// Reasons why a recorded anchoring transaction differs from the re-derived one.
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum ReplayError {
+    /// Anchoring transaction does not contain a payload.
+    #[error("Anchoring transaction does not contain a payload.")]
+    MissingPayload,
+    /// Anchored block is absent in the blockchain.
+    #[error("Anchored block at height {0} is absent in the blockchain.")]
+    UnknownBlock(Height),
+    /// Anchored block hash differs from the actual one.
+    #[error("Anchored block hash at height {height} is {actual}, but expected {expected}.")]
+    BlockHashMismatch {
+        /// Anchored block height.
+        height: Height,
+        /// Actual block hash at the given height.
+        expected: Hash,
+        /// Block hash in the anchoring transaction payload.
+        actual: Hash,
+    },
+    /// Anchoring transaction spends an unknown funding transaction.
+    #[error("Anchoring transaction spends an unknown funding transaction {0}.")]
+    UnknownFundingTransaction(Sha256d),
+    /// Anchoring transaction output value exceeds the balance of its inputs.
+    #[error("Anchoring transaction output value {output} exceeds the inputs balance {balance}.")]
+    OutputExceedsBalance {
+        /// Output value.
+        output: u64,
+        /// Inputs balance.
+        balance: u64,
+    },
+    /// Anchoring transaction fee does not correspond to any fee per byte value.
+    #[error("Total fee {total_fee} is not a multiple of the transaction size {size}.")]
+    InconsistentFee {
+        /// Total transaction fee.
+        total_fee: u64,
+        /// Transaction size in bytes.
+        size: u64,
+    },
+    /// Unable to build the anchoring transaction.
+    #[error("Unable to build the anchoring transaction: {0}")]
+    Builder(#[from] BuilderError),
+    /// Re-derived anchoring transaction differs from the recorded one.
+    #[error("Re-derived anchoring transaction {0} differs from the recorded one.")]
+    TransactionMismatch(Sha256d),
+    /// Latest anchoring transaction does not pay to the actual anchoring address.
+    #[error("Latest anchoring transaction does not pay to the actual anchoring address.")]
+    UnexpectedOutputAddress,
+}
+
+/// Anchoring transaction which does not match its replayed counterpart.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReplayMismatch {
+    /// Position of the transaction in the anchoring chain.
+    pub index: u64,
+    /// Identifier of the recorded transaction.
+    pub txid: Sha256d,
+    /// Mismatch description.
+    pub reason: String,
+}
+
+/// Result of the anchoring chain replay.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ReplayReport {
+    /// Total number of the replayed anchoring transactions.
+    pub transactions_count: u64,
+    /// Anchoring transactions which do not match the re-derived ones.
+    pub mismatches: Vec<ReplayMismatch>,
+}
+
+impl ReplayReport {
+    /// Checks that all of the recorded anchoring transactions have been re-derived.
+    pub fn is_consistent(&self) -> bool {
+        self.mismatches.is_empty()
+    }
+}
+
+impl<T: Access> Schema<T> {
+    /// Re-derives every anchoring transaction in the chain and compares them with the
+    /// recorded ones.
+    pub fn replay_anchoring_chain(&self, core_schema: CoreSchema<impl Access>) -> ReplayReport {
+        let mut report = ReplayReport::default();
+
+        let mut prev_tx: Option<Transaction> = None;
+        for tx in self.transactions_chain.iter() {
+            let index = report.transactions_count;
+            if let Err(e) = self.replay_transaction(&core_schema, prev_tx.as_ref(), &tx) {
+                report.mismatches.push(ReplayMismatch {
+                    index,
+                    txid: tx.id(),
+                    reason: e.to_string(),
+                });
+            }
+            report.transactions_count += 1;
+            prev_tx = Some(tx);
+        }
+
+        // The latest anchoring transaction should pay to the actual anchoring address.
+        if let Some(tx) = prev_tx {
+            if tx.0.output[0].script_pubkey != self.actual_config().anchoring_out_script() {
+                report.mismatches.push(ReplayMismatch {
+                    index: report.transactions_count - 1,
+                    txid: tx.id(),
+                    reason: ReplayError::UnexpectedOutputAddress.to_string(),
+                });
+            }
+        }
+
+        report
+    }
+
+    fn replay_transaction(
+        &self,
+        core_schema: &CoreSchema<impl Access>,
+        prev_tx: Option<&Transaction>,
+        tx: &Transaction,
+    ) -> Result<(), ReplayError> {
+        replay_transaction(
+            |height| core_schema.block_hash_by_height(height),
+            |txid| self.spent_funding_transactions.get(txid),
+            prev_tx,
+            tx,
+        )
+    }
+}
+
+/// Re-derives the anchoring transaction using the given block hashes and spent funding
+/// transactions lookups and compares it with the recorded one.
+pub(super) fn replay_transaction(
+    block_hash: impl FnOnce(Height) -> Option<Hash>,
+    spent_funding_tx: impl Fn(&Sha256d) -> Option<Transaction>,
+    prev_tx: Option<&Transaction>,
+    tx: &Transaction,
+) -> Result<(), ReplayError> {
+    let payload = tx.anchoring_payload().ok_or(ReplayError::MissingPayload)?;
+    let expected_block_hash =
+        block_hash(payload.block_height).ok_or(ReplayError::UnknownBlock(payload.block_height))?;
+    if expected_block_hash != payload.block_hash {
+        return Err(ReplayError::BlockHashMismatch {
+            height: payload.block_height,
+            expected: expected_block_hash,
+            actual: payload.block_hash,
+        });
+    }
+
+    // Previous anchoring transaction determines the address from which the funds
+    // are spent, the first transaction always spends funds to the same address.
+    let output_script = &tx.0.output[0].script_pubkey;
+    let input_script = prev_tx.map_or(output_script, |prev_tx| &prev_tx.0.output[0].script_pubkey);
+
+    let build = |fee: u64| -> Result<Transaction, ReplayError> {
+        let mut builder = BtcAnchoringTransactionBuilder::with_script_pubkey(input_script.clone());
+        if output_script != input_script {
+            builder.transit_to(output_script.clone());
+        }
+
+        let mut funding_inputs = tx.0.input.iter();
+        match (prev_tx, payload.prev_tx_chain) {
+            (_, Some(last_tx)) => builder.recover(last_tx),
+            (Some(prev_tx), None) => {
+                builder.prev_tx(prev_tx.clone())?;
+                funding_inputs.next();
+            }
+            (None, None) => {}
+        }
+        for input in funding_inputs {
+            let txid = Sha256d::from(input.previous_output.txid);
+            let funding_tx =
+                spent_funding_tx(&txid).ok_or(ReplayError::UnknownFundingTransaction(txid))?;
+            builder.additional_funds(funding_tx)?;
+        }
+
+        builder.payload(payload.block_height, payload.block_hash);
+        // Optional payload commitments are taken as is, since they are not derived
+        // from the block hashes.
+        if let Some(root) = payload.auxiliary_root {
+            builder.auxiliary_root(root);
+        }
+        if let Some(config_hash) = payload.consensus_config_hash {
+            builder.consensus_config_hash(config_hash);
+        }
+        builder.fee(fee);
+        Ok(builder.create()?.0)
+    };
+
+    // Fee per byte value is restored from the difference between the inputs balance
+    // and the output value of the recorded transaction.
+    let unpaid_tx = build(0)?;
+    let balance = unpaid_tx.0.output[0].value;
+    let output = tx.0.output[0].value;
+    let total_fee = balance
+        .checked_sub(output)
+        .ok_or(ReplayError::OutputExceedsBalance { output, balance })?;
+    let size = bitcoin::consensus::serialize(&unpaid_tx.0).len() as u64;
+    if total_fee % size != 0 {
+        return Err(ReplayError::InconsistentFee { total_fee, size });
+    }
+
+    let replayed_tx = build(total_fee / size)?;
+    if replayed_tx.id() != tx.id() {
+        return Err(ReplayError::TransactionMismatch(replayed_tx.id()));
+    }
+    Ok(())
+}
@@ -0,0 +1,234 @@
+// Copyright 2019 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Parallel scans of the anchoring chain.
+//!
+//! Database views cannot be shared between threads, so the scan reads the anchoring
+//! chain together with the anchored block hashes and the spent funding transactions
+//! in a single sequential pass, after which the transactions are processed on all of
+//! the available cores. This keeps the maintenance commands (replay, export and reindexing)
+//! fast on the chains with tens of thousands of anchors.
+
+use bitcoin::blockdata::script::Script;
+use exonum::{blockchain::Schema as CoreSchema, crypto::Hash, helpers::Height};
+use exonum_merkledb::access::{Access, RawAccessMut};
+use rayon::prelude::*;
+use serde_derive::{Deserialize, Serialize};
+
+use std::collections::HashMap;
+
+use crate::{
+    btc::{Sha256d, Transaction},
+    config::OutputType,
+};
+
+use super::{
+    replay::{replay_transaction, ReplayError, ReplayMismatch, ReplayReport},
+    Schema,
+};
+
+/// Anchoring transaction along with its position in the anchoring chain.
+#[derive(Debug, Clone, Copy)]
+pub struct ChainItem<'a> {
+    /// Position of the transaction in the anchoring chain.
+    pub index: u64,
+    /// Previous anchoring transaction, if any.
+    pub prev_tx: Option<&'a Transaction>,
+    /// Anchoring transaction.
+    pub tx: &'a Transaction,
+}
+
+/// Exported anchoring transaction.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AnchorRecord {
+    /// Position of the transaction in the anchoring chain.
+    pub index: u64,
+    /// Anchoring transaction identifier.
+    pub txid: Sha256d,
+    /// Anchored Exonum block height.
+    pub block_height: Height,
+    /// Anchored Exonum block hash.
+    pub block_hash: Hash,
+    /// Identifier of the anchoring transaction from which the chain has been recovered, if any.
+    pub prev_tx_chain: Option<Sha256d>,
+    /// Type of the output script of the anchoring transaction, if it is one of the known types.
+    pub output_type: Option<OutputType>,
+    /// Hash of the consensus configuration committed by the transaction, if any.
+    #[serde(default)]
+    pub consensus_config_hash: Option<Hash>,
+}
+
+impl AnchorRecord {
+    /// Creates the record for the anchoring transaction at the given position, if
+    /// the transaction contains the anchoring payload.
+    pub fn new(index: u64, tx: &Transaction) -> Option<Self> {
+        tx.anchoring_payload().map(|payload| Self {
+            index,
+            txid: tx.id(),
+            block_height: payload.block_height,
+            block_hash: payload.block_hash,
+            prev_tx_chain: payload.prev_tx_chain,
+            output_type: OutputType::of_script(&tx.0.output[0].script_pubkey),
+            consensus_config_hash: payload.consensus_config_hash,
+        })
+    }
+}
+
+/// In-memory copy of the anchoring chain and the data required to process it.
+#[derive(Debug, Clone)]
+pub struct ChainScan {
+    transactions: Vec<Transaction>,
+    block_hashes: HashMap<Height, Hash>,
+    funding_transactions: HashMap<Sha256d, Transaction>,
+    anchoring_out_script: Option<Script>,
+}
+
+impl ChainScan {
+    /// Returns the number of the anchoring transactions in the scan.
+    pub fn len(&self) -> usize {
+        self.transactions.len()
+    }
+
+    /// Checks that the scan contains no anchoring transactions.
+    pub fn is_empty(&self) -> bool {
+        self.transactions.is_empty()
+    }
+
+    /// Applies the given function to every anchoring transaction in parallel and returns
+    /// the results in the anchoring chain order.
+    pub fn par_map<F, R>(&self, f: F) -> Vec<R>
+    where
+        F: Fn(ChainItem<'_>) -> R + Sync + Send,
+        R: Send,
+    {
+        (0..self.transactions.len())
+            .into_par_iter()
+            .map(|i| {
+                f(ChainItem {
+                    index: i as u64,
+                    prev_tx: i.checked_sub(1).map(|j| &self.transactions[j]),
+                    tx: &self.transactions[i],
+                })
+            })
+            .collect()
+    }
+
+    /// Re-derives every anchoring transaction in parallel and compares them with the
+    /// recorded ones. The report is the same as the one of the sequential
+    /// [`replay_anchoring_chain`](../struct.Schema.html#method.replay_anchoring_chain).
+    pub fn replay(&self) -> ReplayReport {
+        let mut mismatches = self
+            .par_map(|item| {
+                replay_transaction(
+                    |height| self.block_hashes.get(&height).copied(),
+                    |txid| self.funding_transactions.get(txid).cloned(),
+                    item.prev_tx,
+                    item.tx,
+                )
+                .err()
+                .map(|e| ReplayMismatch {
+                    index: item.index,
+                    txid: item.tx.id(),
+                    reason: e.to_string(),
+                })
+            })
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>();
+
+        // The latest anchoring transaction should pay to the actual anchoring address.
+        if let Some(tx) = self.transactions.last() {
+            if Some(&tx.0.output[0].script_pubkey) != self.anchoring_out_script.as_ref() {
+                mismatches.push(ReplayMismatch {
+                    index: self.transactions.len() as u64 - 1,
+                    txid: tx.id(),
+                    reason: ReplayError::UnexpectedOutputAddress.to_string(),
+                });
+            }
+        }
+
+        ReplayReport {
+            transactions_count: self.transactions.len() as u64,
+            mismatches,
+        }
+    }
+
+    /// Exports the anchored blocks of the anchoring transactions in the anchoring chain order.
+    /// Transactions without a payload are omitted.
+    pub fn anchors(&self) -> Vec<AnchorRecord> {
+        self.par_map(|item| AnchorRecord::new(item.index, item.tx))
+            .into_iter()
+            .flatten()
+            .collect()
+    }
+}
+
+impl<T: Access> Schema<T> {
+    /// Loads the anchoring chain into memory for the parallel processing.
+    pub fn chain_scan(&self, core_schema: CoreSchema<impl Access>) -> ChainScan {
+        let mut block_hashes = HashMap::new();
+        let mut funding_transactions = HashMap::new();
+        let mut transactions = Vec::with_capacity(self.transactions_chain.len() as usize);
+        for tx in self.transactions_chain.iter() {
+            if let Some(payload) = tx.anchoring_payload() {
+                if let Some(hash) = core_schema.block_hash_by_height(payload.block_height) {
+                    block_hashes.insert(payload.block_height, hash);
+                }
+            }
+            // The first input of the anchoring transaction usually spends the previous
+            // anchoring transaction, which is absent among the funding ones.
+            for input in &tx.0.input {
+                let txid = Sha256d::from(input.previous_output.txid);
+                if let Some(funding_tx) = self.spent_funding_transactions.get(&txid) {
+                    funding_transactions.insert(txid, funding_tx);
+                }
+            }
+            transactions.push(tx);
+        }
+
+        ChainScan {
+            transactions,
+            block_hashes,
+            funding_transactions,
+            anchoring_out_script: self
+                .try_actual_config()
+                .map(|config| config.anchoring_out_script()),
+        }
+    }
+}
+
+impl<T> Schema<T>
+where
+    T: Access,
+    T::Base: RawAccessMut,
+{
+    /// Rebuilds the indexes of the anchored heights and the anchoring transaction positions
+    /// from the given scan of the anchoring chain.
+    pub fn rebuild_chain_indexes(&mut self, scan: &ChainScan) {
+        let entries = scan.par_map(|item| {
+            let height = item.tx.anchoring_payload().map(|p| p.block_height.0);
+            (item.index, item.tx.id(), height)
+        });
+
+        self.anchored_heights.clear();
+        self.transaction_positions.clear();
+        for (index, txid, height) in entries {
+            if let Some(height) = height {
+                self.anchored_heights.put(&height, index);
+            }
+            self.transaction_positions.put(&txid, index);
+        }
+        self.indexed_transactions.set(scan.len() as u64);
+    }
+}
@@ -14,28 +14,49 @@
 
 //! Information schema for the btc anchoring service.
 
-use exonum::{blockchain::Schema as CoreSchema, helpers::Height};
+use exonum::{
+    blockchain::Schema as CoreSchema,
+    crypto::{Hash, PublicKey},
+    helpers::Height,
+    runtime::ExecutionError,
+};
 use exonum_derive::FromAccess;
 use exonum_merkledb::{
     access::{Access, FromAccess, RawAccessMut},
-    Entry, ProofListIndex, ProofMapIndex,
+    Entry, Group, HashTag, ListIndex, MapIndex, ObjectHash, ProofListIndex, ProofMapIndex,
 };
-use log::{error, trace};
+use log::{error, info, trace};
 
 use crate::{
     btc::{self, BtcAnchoringTransactionBuilder, BuilderError, Sha256d, Transaction},
-    config::Config,
+    config::{AnchoringKeys, Config, KeyPossessionProof},
     proto::BinaryMap,
 };
 
-use super::{data_layout::*, BtcAnchoringState};
+use super::{
+    data_layout::*, errors::Error, BtcAnchoringState, RecordAnchorBlock, UpdateParameters,
+};
 
 /// A set of signatures for a transaction input ordered by the anchoring node identifiers.
 pub type InputSignatures = BinaryMap<u16, btc::InputSignature>;
 /// A set of funding transaction confirmations.
 pub type TransactionConfirmations = BinaryMap<btc::PublicKey, ()>;
 
+/// Maximum number of expired proposals, which signatures are reclaimed within one block.
+const MAX_COMPACTED_PROPOSALS: usize = 16;
+/// Maximum number of the anchoring transactions added to the chain indexes within one block.
+const MAX_BACKFILLED_TRANSACTIONS: u64 = 1024;
+/// Number of the distinct anchoring nodes, which should sign the conflicting transactions
+/// to halt the finalization of the anchoring proposal.
+const HALT_CONFLICT_AUTHORS: usize = 2;
+/// Number of the latest anchoring transactions over which the health score is computed.
+pub const HEALTH_WINDOW: u64 = 100;
+
 /// Information schema for `exonum-btc-anchoring`.
+///
+/// The schema indexes are stored with the prefix equal to the name of the service instance
+/// (`btc_anchoring` by default), so several anchoring instances or forks of the service
+/// deployed on the same blockchain do not collide in the database.
 #[derive(Debug, FromAccess)]
 pub struct Schema<T: Access> {
     /// Complete chain of the anchoring transactions.
@@ -54,6 +75,108 @@ pub struct Schema<T: Access> {
     /// Entry that may contain an unspent funding transaction for the
     /// actual configuration.
     pub(crate) unspent_funding_transaction: Entry<T::Base, Transaction>,
+    /// Index of the anchored Exonum heights to the positions of the corresponding
+    /// transactions in the anchoring chain.
+    pub(crate) anchored_heights: MapIndex<T::Base, u64, u64>,
+    /// Index of the anchoring transaction identifiers to their positions in the anchoring chain.
+    pub(crate) transaction_positions: MapIndex<T::Base, Sha256d, u64>,
+    /// Number of the anchoring transactions at the beginning of the chain, which are covered
+    /// by the `anchored_heights` and `transaction_positions` indexes.
+    pub(crate) indexed_transactions: Entry<T::Base, u64>,
+    /// Height of the Exonum block in which the latest anchoring transaction has been committed.
+    pub(crate) latest_anchor_block_height: Entry<T::Base, u64>,
+    /// Anchoring heights which have been skipped, because the blockchain state has not been
+    /// changed since the latest anchor.
+    pub skipped_anchoring_heights: ListIndex<T::Base, u64>,
+    /// Data hashes submitted by the tenants in the corresponding Exonum blocks.
+    pub(crate) tenant_data: Group<T, TenantBlockId, ProofListIndex<T::Base, Hash>>,
+    /// Root hashes of the tenant data submitted in the corresponding Exonum blocks.
+    pub(crate) tenant_roots: ProofMapIndex<T::Base, TenantBlockId, Hash>,
+    /// Total number of data hashes submitted by the tenants.
+    pub(crate) tenant_usage: ProofMapIndex<T::Base, PublicKey, u64>,
+    /// Anchoring keys submitted by the validators joining the anchoring and approved by
+    /// the anchoring nodes, which are not included into the anchoring configuration yet.
+    pub(crate) pending_anchoring_keys: ProofMapIndex<T::Base, PublicKey, AnchoringKeys>,
+    /// Number of the anchoring configurations which have become actual after the transitions
+    /// of the anchoring chain, i.e. the number of the current anchoring epoch.
+    pub(crate) anchoring_epoch: Entry<T::Base, u64>,
+    /// Fees of the anchoring transactions finalized by the anchoring nodes within
+    /// the corresponding anchoring epochs.
+    pub(crate) fee_records: Group<T, u64, ProofMapIndex<T::Base, PublicKey, FeeRecord>>,
+    /// Anchoring proposals with the collected input signatures and the number of their inputs.
+    pub(crate) signed_proposals: MapIndex<T::Base, Sha256d, u32>,
+    /// Backup Bitcoin keys registered by the anchoring nodes, which replace their actual keys
+    /// if the nodes stop signing the anchoring proposals.
+    pub(crate) standby_keys: ProofMapIndex<T::Base, PublicKey, btc::PublicKey>,
+    /// Latest anchoring heights signed by the anchoring nodes.
+    pub(crate) latest_signed_heights: MapIndex<T::Base, PublicKey, u64>,
+    /// Merkle roots of the auxiliary services state hashes at the corresponding
+    /// anchoring heights.
+    pub(crate) auxiliary_roots: ProofMapIndex<T::Base, u64, Hash>,
+    /// Hashes of the consensus configuration at the corresponding anchoring heights.
+    pub(crate) consensus_config_hashes: ProofMapIndex<T::Base, u64, Hash>,
+    /// Confirmations for the corresponding external anchoring transaction.
+    pub(crate) unconfirmed_external_anchors:
+        ProofMapIndex<T::Base, Sha256d, TransactionConfirmations>,
+    /// External transactions, which have broken the anchoring chain, indexed by the
+    /// identifiers of the anchoring transactions spent by them or conflicting with them.
+    pub(crate) external_spends: ProofMapIndex<T::Base, Sha256d, Transaction>,
+    /// Version of the payload format of the anchoring transactions produced by the service.
+    pub(crate) payload_version: Entry<T::Base, u8>,
+    /// Anchoring wallet balances after the anchoring transactions finalized by the service.
+    pub(crate) balance_history: ListIndex<T::Base, BalanceRecord>,
+    /// Confirmations for the anchoring parameters proposed by the anchoring nodes,
+    /// indexed by the hashes of the proposals.
+    pub(crate) unconfirmed_parameters: ProofMapIndex<T::Base, Hash, TransactionConfirmations>,
+    /// Fee and size accounting of the anchoring transactions finalized by the service,
+    /// indexed by their positions in the anchoring chain.
+    pub(crate) anchor_accounting: MapIndex<T::Base, u64, AnchorAccounting>,
+    /// Health indicators of the anchoring transactions finalized by the service.
+    pub(crate) health_records: ListIndex<T::Base, HealthRecord>,
+    /// Rolling health score of the anchoring over the latest health records.
+    pub(crate) health_score: Entry<T::Base, HealthScore>,
+    /// Anchoring addresses which have become actual, indexed to the anchoring epochs
+    /// in which they have become actual for the first time.
+    pub(crate) used_addresses: MapIndex<T::Base, String, u64>,
+    /// Confirmations of the anchoring nodes that the corresponding anchoring transaction
+    /// is buried deep enough in the Bitcoin blockchain.
+    pub(crate) unconfirmed_final_anchors: ProofMapIndex<T::Base, Sha256d, TransactionConfirmations>,
+    /// Position of the latest anchoring transaction confirmed as final by the anchoring nodes.
+    pub(crate) final_anchor_index: Entry<T::Base, u64>,
+    /// Statuses of the latest anchoring transaction in the Bitcoin network reported by
+    /// the anchoring nodes, indexed by their service keys.
+    pub(crate) anchor_status_reports: ProofMapIndex<T::Base, PublicKey, AnchorStatusReport>,
+    /// Hashes of the Bitcoin blocks containing the anchoring transactions, indexed by
+    /// the transaction identifiers.
+    pub(crate) anchor_blocks: ProofMapIndex<T::Base, Sha256d, Sha256d>,
+    /// Confirmations of the anchoring nodes for the Bitcoin blocks containing the anchoring
+    /// transactions, indexed by the hashes of the corresponding messages.
+    pub(crate) unconfirmed_anchor_blocks: ProofMapIndex<T::Base, Hash, TransactionConfirmations>,
+    /// Evidence of the anchoring nodes signing the transactions, which conflict with
+    /// the anchoring proposals computed by the service, in the order of detection.
+    pub(crate) proposal_conflicts: ProofListIndex<T::Base, ProposalConflict>,
+    /// The conflict which has halted the finalization of the anchoring proposal.
+    pub(crate) anchoring_halt: Entry<T::Base, ProposalConflict>,
+    /// Anchoring proposals of the actual anchoring slot, which have been replaced after
+    /// the changes in the funding or anchoring parameters, indexed to the anchoring heights.
+    pub(crate) superseded_proposals: MapIndex<T::Base, Sha256d, u64>,
+    /// Heights of the blocks in which the approval windows of the high-risk maintenance
+    /// operations have been opened, indexed by the hashes of the operations.
+    pub(crate) approval_windows: MapIndex<T::Base, Hash, u64>,
+    /// Standby keys registered by the anchoring nodes, which await the approval of another
+    /// anchoring node, indexed by the service keys of the registering nodes.
+    pub(crate) unconfirmed_standby_keys: ProofMapIndex<T::Base, PublicKey, btc::PublicKey>,
+    /// Health of the Bitcoin relays reported by the anchoring nodes, indexed by their
+    /// service keys.
+    pub(crate) relay_status_reports: ProofMapIndex<T::Base, PublicKey, RelayStatusReport>,
+    /// Proofs of possession of the anchoring keys submitted by the validators joining
+    /// the anchoring, indexed by their service keys. The keys, which are absent in
+    /// `pending_anchoring_keys`, await the approval of the anchoring nodes.
+    pub(crate) anchoring_key_proofs: ProofMapIndex<T::Base, PublicKey, KeyPossessionProof>,
+    /// Approvals of the anchoring nodes for the submitted anchoring keys, indexed by
+    /// the service keys of the joining validators.
+    pub(crate) unconfirmed_anchoring_keys:
+        ProofMapIndex<T::Base, PublicKey, TransactionConfirmations>,
 }
 
 impl<T: Access> Schema<T> {
@@ -63,18 +186,65 @@ impl<T: Access> Schema<T> {
     }
 
     /// Returns an actual anchoring configuration.
+    ///
+    /// # Panics
+    ///
+    /// If the anchoring service has not been configured yet.
     pub fn actual_config(&self) -> Config {
-        self.actual_config.get().expect(
+        self.try_actual_config().expect(
             "Actual configuration of anchoring is absent. \
              If this error occurs, inform the service authors about it.",
         )
     }
 
+    /// Returns an actual anchoring configuration, or `None` if the service has been
+    /// started without the configuration, and the configuration has not been applied yet.
+    pub fn try_actual_config(&self) -> Option<Config> {
+        self.actual_config.get()
+    }
+
+    /// Returns the version of the payload format of the anchoring transactions produced
+    /// by the service. The services started before the versioning produce the first version.
+    pub fn payload_version(&self) -> u8 {
+        self.payload_version.get().unwrap_or(1)
+    }
+
     /// Returns the nearest following configuration if it exists.
     pub fn following_config(&self) -> Option<Config> {
         self.following_config.get()
     }
 
+    /// Returns the configuration which includes the anchoring keys submitted by the
+    /// joining validators and approved by the anchoring nodes in addition to the following
+    /// (or actual) configuration.
+    pub fn pending_config(&self) -> Config {
+        let mut config = self
+            .following_config()
+            .unwrap_or_else(|| self.actual_config());
+        for keys in self.pending_anchoring_keys.values() {
+            config
+                .key_proofs
+                .extend(self.anchoring_key_proofs.get(&keys.service_key));
+            config.anchoring_keys.push(keys);
+        }
+        config
+    }
+
+    /// Returns the configuration resulting from the inclusion of the given anchoring keys
+    /// of the joining validator into the pending configuration. The joining validator
+    /// proves the possession of its Bitcoin key for this configuration.
+    pub fn onboarding_config(&self, keys: AnchoringKeys) -> Config {
+        let mut config = self.pending_config();
+        config.anchoring_keys.push(keys);
+        config
+    }
+
+    /// Returns the proof of possession of the anchoring key submitted by the joining validator
+    /// with the given service key, if any.
+    pub fn submitted_anchoring_key(&self, service_key: &PublicKey) -> Option<KeyPossessionProof> {
+        self.anchoring_key_proofs.get(service_key)
+    }
+
     /// Returns the list of signatures for the given transaction input.
     pub fn input_signatures(&self, input: &TxInputId) -> InputSignatures {
         self.transaction_signatures.get(input).unwrap_or_default()
@@ -89,7 +259,10 @@ impl<T: Access> Schema<T> {
     pub fn actual_state(&self) -> BtcAnchoringState {
         let actual_configuration = self.actual_config();
         if let Some(following_configuration) = self.following_config() {
-            if actual_configuration.redeem_script() != following_configuration.redeem_script() {
+            // Either the anchoring keys or the output type have been changed.
+            if actual_configuration.anchoring_out_script()
+                != following_configuration.anchoring_out_script()
+            {
                 return BtcAnchoringState::Transition {
                     actual_configuration,
                     following_configuration,
@@ -107,6 +280,51 @@ impl<T: Access> Schema<T> {
         &self,
         core_schema: CoreSchema<impl Access>,
         actual_state: &BtcAnchoringState,
+    ) -> Option<Result<(Transaction, Vec<Transaction>), BuilderError>> {
+        let anchoring_height =
+            actual_state.following_anchoring_height(self.latest_anchoring_slot());
+        self.build_anchoring_transaction(core_schema, actual_state, anchoring_height)
+    }
+
+    /// Returns the anchoring transaction which the service would propose right now for
+    /// the actual anchoring state along with the anchored height.
+    ///
+    /// Unlike the [`actual_proposed_anchoring_transaction`], the preview anchors the latest
+    /// committed block if the following anchoring height has not been reached yet. If the
+    /// auxiliary services are configured, the block preceding the latest one is anchored
+    /// instead, since the auxiliary root of the latest block is not recorded yet.
+    ///
+    /// [`actual_proposed_anchoring_transaction`]: #method.actual_proposed_anchoring_transaction
+    pub fn preview_anchoring_transaction(
+        &self,
+        core_schema: CoreSchema<impl Access>,
+    ) -> (
+        Height,
+        Option<Result<(Transaction, Vec<Transaction>), BuilderError>>,
+    ) {
+        let actual_state = self.actual_state();
+        let latest_height = core_schema.height();
+        let mut anchoring_height = actual_state
+            .following_anchoring_height(self.latest_anchoring_slot())
+            .min(latest_height);
+        let config = actual_state.actual_config();
+        if (!config.auxiliary_services.is_empty() || config.commit_consensus_config)
+            && anchoring_height == latest_height
+            && latest_height > Height(0)
+        {
+            anchoring_height = latest_height.previous();
+        }
+
+        let proposal =
+            self.build_anchoring_transaction(core_schema, &actual_state, anchoring_height);
+        (anchoring_height, proposal)
+    }
+
+    fn build_anchoring_transaction(
+        &self,
+        core_schema: CoreSchema<impl Access>,
+        actual_state: &BtcAnchoringState,
+        anchoring_height: Height,
     ) -> Option<Result<(Transaction, Vec<Transaction>), BuilderError>> {
         let config = actual_state.actual_config();
         let unspent_anchoring_transaction = self.transactions_chain.last();
@@ -138,7 +356,13 @@ impl<T: Access> Schema<T> {
             }
 
             // TODO Re-implement recovery business logic [ECR-3581]
-            if let Err(e) = builder.prev_tx(tx) {
+            if self.external_spends.contains(&tx_id) {
+                if unspent_funding_transaction.is_none() {
+                    return Some(Err(BuilderError::NoInputs));
+                }
+                error!("Anchoring is broken by the external transaction. Will try to recover");
+                builder.recover(tx_id);
+            } else if let Err(e) = builder.prev_tx(tx) {
                 if unspent_funding_transaction.is_none() {
                     return Some(Err(e));
                 }
@@ -154,12 +378,30 @@ impl<T: Access> Schema<T> {
         }
 
         // Add corresponding payload.
-        let latest_anchored_height = self.latest_anchored_height();
-        let anchoring_height = actual_state.following_anchoring_height(latest_anchored_height);
         let anchoring_block_hash = core_schema.block_hash_by_height(anchoring_height)?;
 
         builder.payload(anchoring_height, anchoring_block_hash);
+        if !config.auxiliary_services.is_empty() {
+            match self.auxiliary_root(anchoring_height) {
+                Some(root) => builder.auxiliary_root(root),
+                // The root of the latest block is recorded in the following block.
+                None if anchoring_height == core_schema.height() => return None,
+                // The auxiliary services have been configured after the anchoring height.
+                None => {}
+            }
+        } else if config.commit_consensus_config {
+            match self.consensus_config_hash(anchoring_height) {
+                Some(config_hash) => builder.consensus_config_hash(config_hash),
+                // The hash for the latest block is recorded in the following block.
+                None if anchoring_height == core_schema.height() => return None,
+                // The commitment has been enabled after the anchoring height.
+                None => {}
+            }
+        }
         builder.fee(config.transaction_fee);
+        builder.reserve(config.reserve_balance);
+        builder.dust_threshold(config.dust_threshold);
+        builder.bip69_input_ordering(config.bip69_input_ordering);
 
         // Create anchoring proposal.
         Some(builder.create())
@@ -174,6 +416,305 @@ impl<T: Access> Schema<T> {
         self.proposed_anchoring_transaction(core_schema, &actual_state)
     }
 
+    /// Returns the position in the anchoring chain of the first transaction which anchors
+    /// a block with the height greater or equal to the given one.
+    ///
+    /// If the same height has been anchored several times (for example, during the transition
+    /// to a new anchoring address), the latest of such transactions is returned.
+    pub fn find_transaction_index(&self, height: Height) -> Option<u64> {
+        if !self.chain_indexes_complete() {
+            return self.scan_transaction_index(height);
+        }
+
+        self.anchored_heights
+            .iter_from(&height.0)
+            .next()
+            .map(|(_, index)| index)
+    }
+
+    /// Returns the position of the anchoring transaction with the given identifier
+    /// in the anchoring chain.
+    pub fn transaction_index(&self, txid: &Sha256d) -> Option<u64> {
+        self.transaction_positions.get(txid).or_else(|| {
+            if self.chain_indexes_complete() {
+                return None;
+            }
+            self.transactions_chain
+                .iter()
+                .position(|tx| tx.id() == *txid)
+                .map(|index| index as u64)
+        })
+    }
+
+    /// Returns the number of the anchoring transactions at the beginning of the chain,
+    /// which are covered by the chain indexes.
+    pub fn indexed_transactions(&self) -> u64 {
+        self.indexed_transactions.get().unwrap_or_default()
+    }
+
+    /// Returns `true` if the chain indexes cover the whole anchoring chain. The indexes
+    /// of the chains created before them are backfilled gradually after each block.
+    pub fn chain_indexes_complete(&self) -> bool {
+        self.indexed_transactions() >= self.transactions_chain.len()
+    }
+
+    /// Finds the transaction for the `find_transaction_index` method by the sequential scan
+    /// of the anchoring chain, while the chain indexes are not complete.
+    fn scan_transaction_index(&self, height: Height) -> Option<u64> {
+        let mut found: Option<(Height, u64)> = None;
+        for (index, tx) in self.transactions_chain.iter().enumerate() {
+            let block_height = match tx.anchoring_payload() {
+                Some(payload) => payload.block_height,
+                None => continue,
+            };
+            match found {
+                // The transactions with the same anchored height follow each other.
+                Some((found_height, _)) if block_height > found_height => break,
+                _ if block_height >= height => found = Some((block_height, index as u64)),
+                _ => {}
+            }
+        }
+        found.map(|(_, index)| index)
+    }
+
+    /// Returns the total number of data hashes submitted by the given tenant.
+    pub fn tenant_usage(&self, tenant: &PublicKey) -> u64 {
+        self.tenant_usage.get(tenant).unwrap_or_default()
+    }
+
+    /// Returns the list of data hashes submitted by the tenant in the Exonum block.
+    pub fn tenant_data(&self, id: &TenantBlockId) -> ProofListIndex<T::Base, Hash> {
+        self.tenant_data.get(id)
+    }
+
+    /// Returns the number of the current anchoring epoch. The epoch is changed each time
+    /// the following configuration becomes an actual one.
+    pub fn anchoring_epoch(&self) -> u64 {
+        self.anchoring_epoch.get().unwrap_or_default()
+    }
+
+    /// Returns the fees of the anchoring transactions finalized by the anchoring nodes
+    /// within the given anchoring epoch.
+    pub fn fee_records(&self, epoch: u64) -> ProofMapIndex<T::Base, PublicKey, FeeRecord> {
+        self.fee_records.get(&epoch)
+    }
+
+    /// Returns the anchoring wallet balances after the anchoring transactions finalized
+    /// by the service in the order of finalization. The imported external transactions
+    /// are not recorded, since the values of their inputs may be unknown.
+    pub fn balance_history(&self) -> &ListIndex<T::Base, BalanceRecord> {
+        &self.balance_history
+    }
+
+    /// Returns the fee and size accounting of the anchoring transactions finalized by
+    /// the service, indexed by their positions in the anchoring chain. The imported
+    /// external transactions are not recorded, since the values of their inputs may
+    /// be unknown.
+    pub fn anchor_accounting(&self) -> &MapIndex<T::Base, u64, AnchorAccounting> {
+        &self.anchor_accounting
+    }
+
+    /// Returns the health indicators of the anchoring transactions finalized by the service
+    /// in the order of finalization.
+    pub fn health_records(&self) -> &ListIndex<T::Base, HealthRecord> {
+        &self.health_records
+    }
+
+    /// Returns the statuses of the anchoring transactions in the Bitcoin network reported
+    /// by the anchoring nodes. Only the latest report of each node is kept, so the reports
+    /// for the previous anchoring transactions are outdated.
+    pub fn anchor_status_reports(&self) -> &ProofMapIndex<T::Base, PublicKey, AnchorStatusReport> {
+        &self.anchor_status_reports
+    }
+
+    /// Returns the health of the Bitcoin relays reported by the anchoring nodes. Only
+    /// the latest report of each node is kept.
+    pub fn relay_status_reports(&self) -> &ProofMapIndex<T::Base, PublicKey, RelayStatusReport> {
+        &self.relay_status_reports
+    }
+
+    /// Returns the evidence of the anchoring nodes signing the transactions, which conflict
+    /// with the anchoring proposals computed by the service, in the order of detection.
+    pub fn proposal_conflicts(&self) -> &ProofListIndex<T::Base, ProposalConflict> {
+        &self.proposal_conflicts
+    }
+
+    /// Returns the conflict which has halted the finalization of the anchoring proposal,
+    /// if any.
+    pub fn anchoring_halt(&self) -> Option<ProposalConflict> {
+        self.anchoring_halt.get()
+    }
+
+    /// Checks that the anchoring proposal with the given identifier has been replaced after
+    /// the changes in the funding or anchoring parameters. Signatures for such proposals
+    /// are not considered as evidence of the conflicting proposals.
+    pub fn is_proposal_superseded(&self, txid: &Sha256d) -> bool {
+        self.superseded_proposals.contains(txid)
+    }
+
+    /// Returns the standby key registered by the anchoring node with the given service key,
+    /// which awaits the approval of another anchoring node, if any.
+    pub fn unconfirmed_standby_key(&self, service_key: &PublicKey) -> Option<btc::PublicKey> {
+        self.unconfirmed_standby_keys.get(service_key)
+    }
+
+    /// Checks that the approval window of the high-risk maintenance operation with the given
+    /// hash is open at the given height, i.e. the operation has been confirmed by an anchoring
+    /// node no more than `approval_window` blocks ago.
+    pub fn is_approval_window_open(
+        &self,
+        operation: &Hash,
+        height: Height,
+    ) -> Result<bool, ExecutionError> {
+        let window = self
+            .try_actual_config()
+            .ok_or(Error::NotConfigured)?
+            .approval_window;
+        Ok(self.approval_windows.get(operation).map_or(false, |start| {
+            window == 0 || height.0 <= start.saturating_add(window)
+        }))
+    }
+
+    /// Checks that the finalization of the anchoring proposal for the given anchoring height
+    /// and epoch is halted.
+    pub fn is_anchoring_halted(&self, anchoring_height: Height, epoch: u64) -> bool {
+        self.anchoring_halt().map_or(false, |conflict| {
+            conflict.is_for_slot(anchoring_height, epoch)
+        })
+    }
+
+    /// Returns the rolling health score of the anchoring over the latest
+    /// `HEALTH_WINDOW` anchoring transactions finalized by the service.
+    pub fn health_score(&self) -> HealthScore {
+        self.health_score.get().unwrap_or_default()
+    }
+
+    /// Returns the first anchoring epoch which has used the given anchoring address, if any.
+    pub fn address_epoch(&self, address: &btc::Address) -> Option<u64> {
+        self.used_addresses.get(&address.to_string())
+    }
+
+    /// Checks whether the anchoring address of the given configuration is reused, that is,
+    /// it differs from the actual address, but has been used by a previous anchoring epoch,
+    /// or it is one of the known external addresses. Returns the description of the reuse.
+    pub fn address_reuse(&self, config: &Config) -> Option<String> {
+        let address = config.anchoring_address();
+        let is_external = config
+            .external_addresses
+            .iter()
+            .any(|external| external.parse::<btc::Address>().ok().as_ref() == Some(&address));
+        if is_external {
+            return Some(format!(
+                "Anchoring address {} is one of the known external addresses.",
+                address
+            ));
+        }
+
+        let actual_address = self.try_actual_config().map(|x| x.anchoring_address());
+        if actual_address.as_ref() == Some(&address) {
+            return None;
+        }
+        self.address_epoch(&address).map(|epoch| {
+            format!(
+                "Anchoring address {} has been used by the anchoring epoch {}.",
+                address, epoch
+            )
+        })
+    }
+
+    /// Returns the backup Bitcoin key registered by the anchoring node, if any.
+    pub fn standby_key(&self, service_key: &PublicKey) -> Option<btc::PublicKey> {
+        self.standby_keys.get(service_key)
+    }
+
+    /// Returns the Merkle root of the auxiliary services state hashes at the given
+    /// anchoring height, if it has been recorded.
+    pub fn auxiliary_root(&self, height: Height) -> Option<Hash> {
+        self.auxiliary_roots.get(&height.0)
+    }
+
+    /// Returns the hash of the consensus configuration at the given anchoring height,
+    /// if it has been recorded.
+    pub fn consensus_config_hash(&self, height: Height) -> Option<Hash> {
+        self.consensus_config_hashes.get(&height.0)
+    }
+
+    /// Returns the external transaction, which has broken the anchoring chain by spending
+    /// the output of the given anchoring transaction or conflicting with it, if any.
+    pub fn external_spend(&self, txid: &Sha256d) -> Option<Transaction> {
+        self.external_spends.get(txid)
+    }
+
+    /// Checks that the external transaction can be adopted into the anchoring chain,
+    /// i.e. it has the anchoring payload for the committed Exonum block not older than
+    /// the latest anchored one, and its change output goes to the expected anchoring address.
+    pub(crate) fn verify_external_anchor(
+        &self,
+        tx: &Transaction,
+        core_schema: CoreSchema<impl Access>,
+    ) -> Result<(), String> {
+        let (script_pubkey, payload) = tx
+            .anchoring_metadata()
+            .ok_or_else(|| "anchoring payload is absent".to_owned())?;
+        let expected_config = self
+            .following_config()
+            .unwrap_or_else(|| self.actual_config());
+        if *script_pubkey != expected_config.anchoring_out_script() {
+            return Err("change output does not go to the anchoring address".to_owned());
+        }
+
+        let height = payload.block_height;
+        if Some(height) < self.latest_anchored_height() {
+            return Err(format!("anchored height {} is outdated", height));
+        }
+        if core_schema.block_hash_by_height(height) != Some(payload.block_hash) {
+            return Err(format!(
+                "block hash {} does not match the block at height {}",
+                payload.block_hash.to_hex(),
+                height
+            ));
+        }
+        Ok(())
+    }
+
+    /// Returns the latest anchoring height which has been either anchored or skipped.
+    pub fn latest_anchoring_slot(&self) -> Option<Height> {
+        let latest_anchored_height = self.latest_anchored_height();
+        let latest_skipped_height = self.skipped_anchoring_heights.last().map(Height);
+        latest_anchored_height.max(latest_skipped_height)
+    }
+
+    /// Returns the position of the latest anchoring transaction, which a quorum of
+    /// the anchoring nodes has reported as buried deep enough in the Bitcoin blockchain.
+    /// The preceding anchoring transactions are final as well, since each of them is spent
+    /// by the following one.
+    pub fn final_anchor_index(&self) -> Option<u64> {
+        self.final_anchor_index.get()
+    }
+
+    /// Returns the hash of the Bitcoin block containing the given anchoring transaction,
+    /// if a quorum of the anchoring nodes has reported it.
+    pub fn anchor_block_hash(&self, txid: &Sha256d) -> Option<Sha256d> {
+        self.anchor_blocks.get(txid)
+    }
+
+    /// Checks whether the Exonum block with the given height is anchored by a final
+    /// anchoring transaction, so the block cannot be reverted by a reorganization
+    /// of the Bitcoin blockchain.
+    ///
+    /// Other services may use this check in their transactions to wait for the Bitcoin
+    /// finality, for example, by reading the anchoring schema via
+    /// `context.data().for_service("btc_anchoring")`.
+    pub fn is_anchored_and_final(&self, height: Height) -> bool {
+        match (
+            self.find_transaction_index(height),
+            self.final_anchor_index(),
+        ) {
+            (Some(index), Some(final_index)) => index <= final_index,
+            _ => false,
+        }
+    }
+
     /// Returns the height of the latest anchored block.
     pub fn latest_anchored_height(&self) -> Option<Height> {
         let tx = self.transactions_chain.last()?;
@@ -194,38 +735,627 @@ where
     T: Access,
     T::Base: RawAccessMut,
 {
-    /// Adds a finalized transaction to the tail of the anchoring transactions.
-    pub(crate) fn push_anchoring_transaction(&mut self, tx: Transaction) {
-        // An unspent funding transaction is always unconditionally added to the anchoring
-        // transaction proposal, so we can simply move it to the list of spent.
-        if let Some(funding_transaction) = self.unspent_funding_transaction.take() {
-            self.spent_funding_transactions
-                .put(&funding_transaction.id(), funding_transaction);
+    /// Applies the new anchoring configuration. The configuration becomes actual immediately
+    /// if the service has not been configured yet or the anchoring address is not changed,
+    /// otherwise it becomes actual after the transition of the anchoring chain.
+    pub(crate) fn apply_config(&mut self, config: Config) {
+        let actual_config = self.try_actual_config();
+        if actual_config.map_or(true, |actual| {
+            actual.anchoring_address() == config.anchoring_address()
+        }) {
+            self.record_used_address(&config);
+            self.actual_config.set(config);
+        } else {
+            self.following_config.set(config);
         }
-        // Special case if we have an active following configuration.
-        if let Some(config) = self.following_config() {
-            // Check that the anchoring transaction is correct.
-            let tx_out_script = tx
-                .anchoring_metadata()
-                .expect(
-                    "Unable to find metadata in the anchoring transaction. \
-                     If this error occurs, inform the service authors about it.",
-                )
-                .0;
+    }
+
+    /// Marks the anchoring transaction with the given index as final, that is, confirmed
+    /// by a quorum of the anchoring nodes, and discards its pending confirmations.
+    pub(crate) fn set_final_anchor(&mut self, index: u64, txid: &Sha256d) {
+        self.final_anchor_index.set(index);
+        self.unconfirmed_final_anchors.remove(txid);
+    }
+
+    /// Records the Bitcoin block containing the anchoring transaction, which a quorum
+    /// of the anchoring nodes has reported.
+    pub(crate) fn set_anchor_block(&mut self, record: &RecordAnchorBlock, record_hash: &Hash) {
+        self.anchor_blocks.put(&record.txid, record.block_hash);
+        self.unconfirmed_anchor_blocks.remove(record_hash);
+    }
+
+    /// Records the anchoring address of the configuration, which becomes actual in the
+    /// current anchoring epoch.
+    pub(crate) fn record_used_address(&mut self, config: &Config) {
+        let address = config.anchoring_address().to_string();
+        if !self.used_addresses.contains(&address) {
+            self.used_addresses.put(&address, self.anchoring_epoch());
+        }
+    }
+
+    /// Adds a finalized transaction to the tail of the anchoring transactions.
+    ///
+    /// `block_height` is the height of the Exonum block in which the transaction
+    /// has been finalized.
+    ///
+    /// Returns an error if the transaction has no anchoring metadata or, in case
+    /// of the active following configuration, does not spend funds to its address.
+    /// The schema is not modified in this case.
+    pub(crate) fn push_anchoring_transaction(
+        &mut self,
+        tx: Transaction,
+        block_height: Height,
+    ) -> Result<(), ExecutionError> {
+        // Check that the anchoring transaction is correct.
+        let (tx_out_script, payload) = tx.anchoring_metadata().ok_or_else(|| {
+            Error::MalformedAnchoringTransaction
+                .with_description("Unable to find metadata in the anchoring transaction.")
+        })?;
+        let anchored_height = payload.block_height;
+        let following_config = self.following_config();
+        if let Some(config) = following_config.as_ref() {
             // If there is a following config, then the anchoring transaction's output should have
             // same script as in the following config.
             // Otherwise, this is a critical error in the logic of the anchoring.
-            assert_eq!(
-                config.anchoring_out_script(),
-                *tx_out_script,
-                "Malformed output address in the anchoring transaction. \
-                 If this error occurs, inform the service authors about it."
-            );
+            if config.anchoring_out_script() != *tx_out_script {
+                return Err(Error::MalformedAnchoringTransaction
+                    .with_description("Malformed output address in the anchoring transaction."));
+            }
+        }
+
+        // An unspent funding transaction is always unconditionally added to the anchoring
+        // transaction proposal, but the imported external transactions may not spend it.
+        if let Some(funding_transaction) = self.unspent_funding_transaction.get() {
+            let funding_txid = funding_transaction.0.txid();
+            if tx
+                .0
+                .input
+                .iter()
+                .any(|txin| txin.previous_output.txid == funding_txid)
+            {
+                self.unspent_funding_transaction.remove();
+                self.spent_funding_transactions
+                    .put(&funding_transaction.id(), funding_transaction);
+            }
+        }
+        // Special case if we have an active following configuration.
+        if let Some(config) = following_config {
             // If preconditions are correct, just reassign the config as an actual.
             self.following_config.remove();
+            self.anchoring_epoch.set(self.anchoring_epoch() + 1);
+            self.record_used_address(&config);
             self.actual_config.set(config);
         }
+        let index = self.transactions_chain.len();
+        self.anchored_heights.put(&anchored_height.0, index);
+        self.latest_anchor_block_height.set(block_height.0);
+        self.transaction_positions.put(&tx.id(), index);
+        // The chains created before the indexes are covered by the backfill.
+        if self.indexed_transactions() == index {
+            self.indexed_transactions.set(index + 1);
+        }
+        // The proposals superseded within the finalized slot cannot be signed anymore.
+        self.superseded_proposals.clear();
         self.transactions_chain.push(tx);
+        Ok(())
+    }
+
+    /// Skips the following anchoring height if the anchoring is configured to anchor
+    /// only on state changes, and the blockchain state at this height is the same as
+    /// right after the latest anchor. Returns the skipped height, if any.
+    pub(crate) fn skip_unchanged_anchoring_height(
+        &mut self,
+        core_schema: CoreSchema<impl Access>,
+    ) -> Option<Height> {
+        let actual_state = self.actual_state();
+        if !actual_state.actual_config().anchor_only_on_state_change || actual_state.is_transition()
+        {
+            return None;
+        }
+
+        let anchor_block_height = Height(self.latest_anchor_block_height.get()?);
+        let anchoring_height =
+            actual_state.following_anchoring_height(self.latest_anchoring_slot());
+        // The latest anchor should be committed before the following anchoring height.
+        if anchoring_height <= anchor_block_height {
+            return None;
+        }
+
+        let state_hash = |height| {
+            let block_hash = core_schema.block_hash_by_height(height)?;
+            core_schema
+                .blocks()
+                .get(&block_hash)
+                .map(|block| block.state_hash)
+        };
+        let anchor_state_hash = state_hash(anchor_block_height)?;
+        if state_hash(anchoring_height)? == anchor_state_hash {
+            trace!(
+                "Skip anchoring of the block at height {}, state has not been changed.",
+                anchoring_height
+            );
+            self.skipped_anchoring_heights.push(anchoring_height.0);
+            Some(anchoring_height)
+        } else {
+            None
+        }
+    }
+
+    /// Skips the following anchoring height if it is older than the maximal signature
+    /// age, since the signatures of the corresponding proposal are rejected. Thus the
+    /// anchoring catches up with the blockchain one interval per block. Returns the skipped
+    /// height, if any.
+    pub(crate) fn skip_stale_anchoring_height(
+        &mut self,
+        core_schema: CoreSchema<impl Access>,
+    ) -> Option<Height> {
+        let actual_state = self.actual_state();
+        let max_age = actual_state.actual_config().max_signature_age;
+        // The transition transaction re-anchors the latest anchoring height.
+        if max_age == 0 || !actual_state.is_regular() {
+            return None;
+        }
+
+        let anchoring_height =
+            actual_state.following_anchoring_height(self.latest_anchoring_slot());
+        let height = core_schema.next_height().0.checked_sub(1)?;
+        if height <= anchoring_height.0.saturating_add(max_age) {
+            return None;
+        }
+        trace!(
+            "Skip anchoring of the block at height {}, it is older than {} blocks.",
+            anchoring_height,
+            max_age
+        );
+        self.skipped_anchoring_heights.push(anchoring_height.0);
+        Some(anchoring_height)
+    }
+
+    /// Records the Merkle root of the state hashes of the auxiliary services if the latest
+    /// committed block has the anchoring height. Returns the recorded root, if any.
+    ///
+    /// The root is computed over the state hash components of the auxiliary services in
+    /// the order of the services in the configuration, and the components of each service
+    /// are ordered by the index names, just like in the state hash aggregator of the block.
+    pub(crate) fn record_auxiliary_root(
+        &mut self,
+        core_schema: CoreSchema<impl Access>,
+    ) -> Option<Hash> {
+        let config = self.actual_config();
+        // There are no committed blocks while the genesis block is being created.
+        let height = Height(core_schema.next_height().0.checked_sub(1)?);
+        if config.auxiliary_services.is_empty()
+            || config.previous_anchoring_height(height) != height
+        {
+            return None;
+        }
+
+        let aggregator = core_schema.state_hash_aggregator();
+        let mut state_hashes = Vec::new();
+        for service in &config.auxiliary_services {
+            let prefix = format!("{}.", service);
+            state_hashes.extend(
+                aggregator
+                    .iter_from(&prefix)
+                    .take_while(|(index_name, _)| index_name.starts_with(&prefix))
+                    .map(|(_, state_hash)| state_hash),
+            );
+        }
+
+        let root = HashTag::hash_list(&state_hashes);
+        trace!("Auxiliary state root for height {} is {}", height, root);
+        self.auxiliary_roots.put(&height.0, root);
+        Some(root)
+    }
+
+    /// Records the hash of the actual consensus configuration if the latest committed
+    /// block has the anchoring height and the configuration commitment is enabled.
+    /// Returns the recorded hash, if any.
+    pub(crate) fn record_consensus_config_hash(
+        &mut self,
+        core_schema: CoreSchema<impl Access>,
+    ) -> Option<Hash> {
+        let config = self.actual_config();
+        // There are no committed blocks while the genesis block is being created.
+        let height = Height(core_schema.next_height().0.checked_sub(1)?);
+        if !config.commit_consensus_config || config.previous_anchoring_height(height) != height {
+            return None;
+        }
+
+        let config_hash = core_schema.consensus_config().object_hash();
+        trace!(
+            "Consensus configuration hash for height {} is {}",
+            height,
+            config_hash
+        );
+        self.consensus_config_hashes.put(&height.0, config_hash);
+        Some(config_hash)
+    }
+
+    /// Adds the fee of the anchoring transaction to the fee record of the anchoring node,
+    /// which has finalized this transaction within the current anchoring epoch.
+    pub(crate) fn record_anchoring_fee(&mut self, service_key: PublicKey, fee: u64) {
+        let mut fee_records = self.fee_records(self.anchoring_epoch());
+        let mut record = fee_records.get(&service_key).unwrap_or_default();
+        record.add_fee(fee);
+        fee_records.put(&service_key, record);
+    }
+
+    /// Returns the pending configuration if there are approved anchoring keys of
+    /// the joining validators, and there is no other transition in progress.
+    pub(crate) fn pending_transition(&self) -> Option<Config> {
+        if self.following_config().is_some() || self.pending_anchoring_keys.keys().next().is_none()
+        {
+            return None;
+        }
+        Some(self.pending_config())
+    }
+
+    /// Schedules the transition to the given pending configuration, which has been verified
+    /// in the same way as the configuration proposed via the supervisor.
+    pub(crate) fn schedule_pending_config(&mut self, config: Config) {
+        info!(
+            "Scheduled transition to the anchoring address {} with {} anchoring keys.",
+            config.anchoring_address(),
+            config.anchoring_keys.len()
+        );
+        self.discard_pending_keys();
+        self.following_config.set(config);
+    }
+
+    /// Discards the approved anchoring keys of the joining validators, which should be
+    /// submitted again.
+    pub(crate) fn discard_pending_keys(&mut self) {
+        for service_key in self.pending_anchoring_keys.keys() {
+            self.anchoring_key_proofs.remove(&service_key);
+        }
+        self.pending_anchoring_keys.clear();
+    }
+
+    /// Discards all anchoring keys submitted by the joining validators, both approved
+    /// and awaiting the approval.
+    pub(crate) fn discard_submitted_keys(&mut self) {
+        for service_key in self.anchoring_key_proofs.keys().collect::<Vec<_>>() {
+            self.discard_submitted_key(&service_key);
+        }
+        self.pending_anchoring_keys.clear();
+    }
+
+    /// Discards the anchoring key submitted by the joining validator with the given service
+    /// key, which has not been approved by the anchoring nodes.
+    pub(crate) fn discard_submitted_key(&mut self, service_key: &PublicKey) {
+        if let Some(proof) = self.anchoring_key_proofs.get(service_key) {
+            let keys = AnchoringKeys {
+                service_key: *service_key,
+                bitcoin_key: proof.bitcoin_key,
+            };
+            self.close_approval_window(&keys.object_hash());
+        }
+        self.anchoring_key_proofs.remove(service_key);
+        self.unconfirmed_anchoring_keys.remove(service_key);
+    }
+
+    /// Registers the backup Bitcoin key of the anchoring node. The signing activity of the node
+    /// is counted from the latest anchored height, if the node has not signed anything yet.
+    pub(crate) fn register_standby_key(
+        &mut self,
+        service_key: PublicKey,
+        bitcoin_key: btc::PublicKey,
+    ) {
+        if !self.latest_signed_heights.contains(&service_key) {
+            let latest_anchored_height = self.latest_anchored_height().unwrap_or_default();
+            self.latest_signed_heights
+                .put(&service_key, latest_anchored_height.0);
+        }
+        self.standby_keys.put(&service_key, bitcoin_key);
+    }
+
+    /// Records the health indicators of the anchoring transaction finalized at the given
+    /// height and updates the rolling health score. Should be called before the transaction
+    /// is pushed to the anchoring chain, so the previous anchor is the latest one.
+    pub(crate) fn record_anchor_health(
+        &mut self,
+        anchored_height: Height,
+        finalized_height: Height,
+    ) {
+        let config = self.actual_config();
+        // Nodes which have signed any proposal since the previous anchor are active.
+        let previous_height = self.latest_anchored_height().unwrap_or(anchored_height).0;
+        let active_signers = config
+            .anchoring_keys
+            .iter()
+            .filter(|keys| {
+                self.latest_signed_heights
+                    .get(&keys.service_key)
+                    .map_or(false, |height| height >= previous_height)
+            })
+            .count();
+        self.health_records.push(HealthRecord {
+            anchored_height: anchored_height.0,
+            finalized_height: finalized_height.0,
+            target_latency: config.anchoring_interval,
+            active_signers: active_signers as u32,
+            anchoring_nodes: config.anchoring_keys.len() as u32,
+        });
+
+        let start = self.health_records.len().saturating_sub(HEALTH_WINDOW);
+        let score = HealthScore::from_records(self.health_records.iter_from(start));
+        self.health_score.set(score);
+    }
+
+    /// Records that the anchoring node has signed the proposal for the given anchoring height.
+    pub(crate) fn record_signing_activity(&mut self, service_key: PublicKey, height: Height) {
+        let latest_signed_height = self.latest_signed_heights.get(&service_key);
+        if latest_signed_height.map_or(true, |latest| latest < height.0) {
+            self.latest_signed_heights.put(&service_key, height.0);
+        }
+    }
+
+    /// Records the status of the latest anchoring transaction reported by the anchoring node.
+    pub(crate) fn record_anchor_status(
+        &mut self,
+        service_key: PublicKey,
+        report: AnchorStatusReport,
+    ) {
+        self.anchor_status_reports.put(&service_key, report);
+    }
+
+    /// Records the health of the Bitcoin relay reported by the anchoring node.
+    pub(crate) fn record_relay_status(
+        &mut self,
+        service_key: PublicKey,
+        report: RelayStatusReport,
+    ) {
+        self.relay_status_reports.put(&service_key, report);
+    }
+
+    /// Records the evidence of the conflicting proposal. The finalization of the anchoring
+    /// proposal for the same slot is halted as soon as `HALT_CONFLICT_AUTHORS` distinct
+    /// anchoring nodes have signed the conflicting transactions, so a single diverging node
+    /// cannot stop the anchoring. Repeated evidence of the same node signing the same
+    /// conflicting transaction for the slot is ignored. Returns `true` if the evidence
+    /// has been recorded.
+    pub(crate) fn record_proposal_conflict(&mut self, conflict: ProposalConflict) -> bool {
+        // Conflicts of the same slot are at the tail of the list, since the slots
+        // are only moving forward.
+        let slot_conflicts = (0..self.proposal_conflicts.len())
+            .rev()
+            .filter_map(|index| self.proposal_conflicts.get(index))
+            .take_while(|known| known.is_for_slot(conflict.anchoring_height, conflict.epoch))
+            .collect::<Vec<_>>();
+        let is_known = slot_conflicts.iter().any(|known| {
+            known.author == conflict.author && known.conflicting_txid == conflict.conflicting_txid
+        });
+        if is_known {
+            return false;
+        }
+
+        let mut authors = slot_conflicts
+            .iter()
+            .map(|known| known.author)
+            .chain(Some(conflict.author))
+            .collect::<Vec<_>>();
+        authors.sort();
+        authors.dedup();
+        if authors.len() >= HALT_CONFLICT_AUTHORS
+            && !self.is_anchoring_halted(conflict.anchoring_height, conflict.epoch)
+        {
+            self.anchoring_halt.set(conflict.clone());
+        }
+        self.proposal_conflicts.push(conflict);
+        true
+    }
+
+    /// Marks the actual anchoring proposal, if any, as superseded before the changes
+    /// in the funding or anchoring parameters, which may replace it with another
+    /// transaction for the same anchoring slot.
+    pub(crate) fn supersede_anchoring_proposal(&mut self, core_schema: CoreSchema<impl Access>) {
+        if let Some(Ok((proposal, _))) = self.actual_proposed_anchoring_transaction(core_schema) {
+            let anchoring_height = proposal
+                .anchoring_payload()
+                .map_or(0, |payload| payload.block_height.0);
+            self.superseded_proposals
+                .put(&proposal.id(), anchoring_height);
+        }
+    }
+
+    /// Opens the approval window of the high-risk maintenance operation with the given hash
+    /// at the given height. The previous window of the operation, if any, is replaced.
+    pub(crate) fn open_approval_window(&mut self, operation: &Hash, height: Height) {
+        self.approval_windows.put(operation, height.0);
+    }
+
+    /// Closes the approval window of the approved high-risk maintenance operation.
+    pub(crate) fn close_approval_window(&mut self, operation: &Hash) {
+        self.approval_windows.remove(operation);
+    }
+
+    /// Lifts the halt of the anchoring proposal finalization, if any.
+    pub(crate) fn lift_anchoring_halt(&mut self) {
+        self.anchoring_halt.remove();
+    }
+
+    /// Schedules the transition to the configuration in which the key of the anchoring node
+    /// is replaced by its standby key, if the node has not signed the anchoring proposals
+    /// during `standby_activation_intervals` anchoring intervals, and there is no other
+    /// transition in progress. Returns the scheduled configuration, if any.
+    pub(crate) fn schedule_standby_key(&mut self) -> Option<Config> {
+        let mut config = self.actual_config();
+        if config.standby_activation_intervals == 0 || self.following_config().is_some() {
+            return None;
+        }
+
+        let latest_anchored_height = self.latest_anchored_height()?.0;
+        let max_silence = config
+            .standby_activation_intervals
+            .saturating_mul(config.anchoring_interval);
+        let (service_key, bitcoin_key) = self.standby_keys.iter().find(|(service_key, _)| {
+            let latest_signed_height = self
+                .latest_signed_heights
+                .get(service_key)
+                .unwrap_or_default();
+            latest_anchored_height.saturating_sub(latest_signed_height) >= max_silence
+        })?;
+
+        self.standby_keys.remove(&service_key);
+        let keys = config
+            .anchoring_keys
+            .iter_mut()
+            .find(|keys| keys.service_key == service_key)?;
+        info!(
+            "Anchoring node {} has not signed the anchoring proposals since the height {}, \
+             its standby key {} replaces the key {}.",
+            service_key,
+            self.latest_signed_heights
+                .get(&service_key)
+                .unwrap_or_default(),
+            bitcoin_key,
+            keys.bitcoin_key
+        );
+        keys.bitcoin_key = bitcoin_key;
+        self.latest_signed_heights
+            .put(&service_key, latest_anchored_height);
+        self.following_config.set(config.clone());
+        Some(config)
+    }
+
+    /// Puts the signatures for the given input of the anchoring proposal with the given
+    /// number of inputs.
+    pub(crate) fn put_input_signatures(
+        &mut self,
+        input: &TxInputId,
+        inputs_count: u32,
+        signatures: InputSignatures,
+    ) {
+        self.signed_proposals.put(&input.txid, inputs_count);
+        self.transaction_signatures.put(input, signatures);
+    }
+
+    /// Removes the input signatures of the expired anchoring proposals, i.e. the finalized
+    /// or superseded ones (due to a new anchoring height, configuration change and so on).
+    /// The signatures of at most `MAX_COMPACTED_PROPOSALS` proposals are removed at a time.
+    /// Returns the number of the removed proposals.
+    pub(crate) fn compact_expired_proposals(
+        &mut self,
+        core_schema: CoreSchema<impl Access>,
+    ) -> usize {
+        if self.signed_proposals.keys().next().is_none() {
+            return 0;
+        }
+
+        let actual_proposal = self
+            .actual_proposed_anchoring_transaction(core_schema)
+            .and_then(Result::ok)
+            .map(|(proposal, _)| proposal.id());
+        let expired_proposals = self
+            .signed_proposals
+            .iter()
+            .filter(|(txid, _)| Some(*txid) != actual_proposal)
+            .take(MAX_COMPACTED_PROPOSALS)
+            .collect::<Vec<_>>();
+
+        for (txid, inputs_count) in &expired_proposals {
+            trace!(
+                "Removing signatures of the expired anchoring proposal {}",
+                txid
+            );
+            for input in 0..*inputs_count {
+                self.transaction_signatures
+                    .remove(&TxInputId::new(*txid, input));
+            }
+            self.signed_proposals.remove(txid);
+        }
+        expired_proposals.len()
+    }
+
+    /// Removes the approval windows of the maintenance operations, which have expired
+    /// at the given height, along with the confirmations of the corresponding parameters.
+    /// At most `MAX_COMPACTED_PROPOSALS` windows are removed at a time. Returns the number
+    /// of the removed windows.
+    pub(crate) fn compact_expired_approvals(&mut self, height: Height) -> usize {
+        let window = match self.try_actual_config() {
+            Some(config) if config.approval_window != 0 => config.approval_window,
+            _ => return 0,
+        };
+
+        let expired_operations = self
+            .approval_windows
+            .iter()
+            .filter(|(_, start)| height.0 > start.saturating_add(window))
+            .map(|(operation, _)| operation)
+            .take(MAX_COMPACTED_PROPOSALS)
+            .collect::<Vec<_>>();
+
+        for operation in &expired_operations {
+            trace!(
+                "Removing the expired approval window of the operation {}",
+                operation
+            );
+            self.approval_windows.remove(operation);
+            self.unconfirmed_parameters.remove(operation);
+        }
+        expired_operations.len()
+    }
+
+    /// Adds the anchoring transactions, which are not covered by the chain indexes yet,
+    /// to the indexes. At most `MAX_BACKFILLED_TRANSACTIONS` transactions are added at a time.
+    /// Returns the number of the added transactions.
+    pub(crate) fn backfill_chain_indexes(&mut self) -> u64 {
+        let from = self.indexed_transactions();
+        let to = self
+            .transactions_chain
+            .len()
+            .min(from + MAX_BACKFILLED_TRANSACTIONS);
+        if from >= to {
+            return 0;
+        }
+
+        for index in from..to {
+            let tx = self.transactions_chain.get(index).unwrap();
+            if let Some(payload) = tx.anchoring_payload() {
+                let height = payload.block_height.0;
+                // The later transactions anchoring the same height take precedence.
+                if self
+                    .anchored_heights
+                    .get(&height)
+                    .map_or(true, |other| other < index)
+                {
+                    self.anchored_heights.put(&height, index);
+                }
+            }
+            self.transaction_positions.put(&tx.id(), index);
+        }
+        self.indexed_transactions.set(to);
+        trace!(
+            "Added anchoring transactions {}..{} to the chain indexes",
+            from,
+            to
+        );
+        to - from
+    }
+
+    /// Adds the data hash submitted by the tenant to the tenant data of the Exonum block
+    /// with the given height.
+    pub(crate) fn push_tenant_data(&mut self, tenant: PublicKey, height: Height, data_hash: Hash) {
+        let id = TenantBlockId::new(tenant, height);
+        let mut tenant_data = self.tenant_data.get(&id);
+        tenant_data.push(data_hash);
+        self.tenant_roots.put(&id, tenant_data.object_hash());
+
+        let usage = self.tenant_usage(&tenant);
+        self.tenant_usage.put(&tenant, usage + 1);
+    }
+
+    /// Applies the anchoring parameters confirmed by the anchoring nodes to the actual
+    /// and following configurations and discards the other proposed parameters.
+    pub(crate) fn update_parameters(&mut self, parameters: &UpdateParameters) {
+        let actual_config = parameters.apply_to(self.actual_config());
+        self.actual_config.set(actual_config);
+        if let Some(following_config) = self.following_config() {
+            self.following_config
+                .set(parameters.apply_to(following_config));
+        }
+        self.unconfirmed_parameters.clear();
     }
 
     /// Sets the given transaction as the current unspent funding transaction.
@@ -241,3 +1371,81 @@ where
         self.unspent_funding_transaction.set(transaction);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use exonum::helpers::Height;
+    use exonum_merkledb::{access::Access, Database, TemporaryDB};
+
+    use super::Schema;
+    use crate::{
+        btc::Transaction,
+        test_helpers::{get_anchoring_schema, AnchoringTestKit},
+    };
+
+    fn check_chain_lookups(
+        schema: &Schema<impl Access>,
+        anchoring_interval: u64,
+        transactions: &[Transaction],
+    ) {
+        assert_eq!(schema.find_transaction_index(Height(0)), Some(0));
+        assert_eq!(schema.find_transaction_index(Height(1)), Some(1));
+        assert_eq!(
+            schema.find_transaction_index(Height(anchoring_interval * 2)),
+            Some(2)
+        );
+        assert_eq!(
+            schema.find_transaction_index(Height(anchoring_interval * 2 + 1)),
+            None
+        );
+        for (index, tx) in transactions.iter().enumerate() {
+            assert_eq!(schema.transaction_index(&tx.id()), Some(index as u64));
+        }
+    }
+
+    #[test]
+    fn backfill_chain_indexes() {
+        let mut anchoring_testkit = AnchoringTestKit::default();
+        let anchoring_interval = anchoring_testkit
+            .actual_anchoring_config()
+            .anchoring_interval;
+        for i in 1..=3 {
+            anchoring_testkit.inner.create_block_with_transactions(
+                anchoring_testkit
+                    .create_signature_txs()
+                    .into_iter()
+                    .flatten(),
+            );
+            anchoring_testkit
+                .inner
+                .create_blocks_until(Height(anchoring_interval * i));
+        }
+        let snapshot = anchoring_testkit.inner.snapshot();
+        let transactions = get_anchoring_schema(&snapshot)
+            .transactions_chain
+            .iter()
+            .collect::<Vec<_>>();
+        assert_eq!(transactions.len(), 3);
+
+        // Emulate the anchoring chain created before the chain indexes.
+        let db = TemporaryDB::new();
+        let fork = db.fork();
+        let mut schema = Schema::new(&fork);
+        schema
+            .transactions_chain
+            .extend(transactions.iter().cloned());
+        assert!(!schema.chain_indexes_complete());
+        // Lookups fall back to the scan of the chain.
+        check_chain_lookups(&schema, anchoring_interval, &transactions);
+
+        assert_eq!(schema.backfill_chain_indexes(), 3);
+        assert!(schema.chain_indexes_complete());
+        assert_eq!(schema.indexed_transactions(), 3);
+        assert_eq!(schema.anchored_heights.iter().count(), 3);
+        assert_eq!(schema.transaction_positions.iter().count(), 3);
+        check_chain_lookups(&schema, anchoring_interval, &transactions);
+
+        // There is nothing to backfill anymore.
+        assert_eq!(schema.backfill_chain_indexes(), 0);
+    }
+}
@@ -14,18 +14,34 @@
 
 //! BTC anchoring transactions.
 
-pub use crate::proto::{AddFunds, SignInput};
+pub use crate::proto::{
+    AddFunds, ConfirmAnchorFinality, ImportAnchor, RecordAnchorBlock, RegisterStandbyKey,
+    ReportAnchorStatus, ReportRelayStatus, SignInput, SubmitAnchoringKey, SubmitData,
+    UpdateParameters,
+};
 
+use bitcoin::OutPoint;
 use btc_transaction_utils::{p2wsh::InputSigner, TxInRef};
-use exonum::runtime::{CommonError, ExecutionError, ExecutionFail};
+use exonum::{
+    helpers::ValidateInput,
+    runtime::{CommonError, ExecutionError, ExecutionFail},
+};
 use exonum_derive::{exonum_interface, interface_method};
+use exonum_merkledb::{BinaryValue, ObjectHash};
 use exonum_rust_runtime::ExecutionContext;
-use log::{info, trace};
+use log::{error, info, trace, warn};
 
-use crate::{btc, config::Config, BtcAnchoringService};
+use crate::{
+    btc,
+    config::{AnchoringKeys, Config},
+    BtcAnchoringService,
+};
 
 use super::{
-    data_layout::TxInputId,
+    data_layout::{
+        AnchorAccounting, AnchorStatusReport, BalanceRecord, ProposalConflict, RelayStatusReport,
+        TxInputId,
+    },
     errors::Error,
     schema::{InputSignatures, Schema, TransactionConfirmations},
 };
@@ -40,7 +56,16 @@ impl SignInput {
         inputs: &[btc::Transaction],
     ) -> Result<(), ExecutionError> {
         // Check that input with the specified index exist.
-        let input_transaction = inputs.get(self.input as usize).ok_or(Error::NoSuchInput)?;
+        let input_transaction = inputs.get(self.input as usize).ok_or_else(|| {
+            Error::NoSuchInput.with_field(
+                "input",
+                format!(
+                    "Anchoring proposal has {} inputs, but input {} is signed.",
+                    inputs.len(),
+                    self.input
+                ),
+            )
+        })?;
         input_signer
             .verify_input(
                 TxInRef::new(proposal.as_ref(), self.input as usize),
@@ -48,7 +73,95 @@ impl SignInput {
                 &public_key.0,
                 self.input_signature.as_ref(),
             )
-            .map_err(|e| Error::InputVerificationFailed.with_description(e))
+            .map_err(|e| Error::InputVerificationFailed.with_field("input_signature", e))
+    }
+
+    // Check that input signature is correct for the signed transaction, which spends
+    // the same output as the anchoring proposal, and return this transaction.
+    fn verify_conflicting_signature(
+        &self,
+        input_signer: &InputSigner,
+        public_key: &btc::PublicKey,
+        proposal: &btc::Transaction,
+        inputs: &[btc::Transaction],
+    ) -> Result<btc::Transaction, ExecutionError> {
+        if self.signed_transaction.is_empty() {
+            return Err(Error::UnexpectedProposalTxId.with_field(
+                "txid",
+                format!(
+                    "Actual anchoring proposal is {}, and the signed transaction is not provided.",
+                    proposal.id()
+                ),
+            ));
+        }
+        let transaction =
+            btc::Transaction::from_bytes(self.signed_transaction.as_slice().into())
+                .map_err(|e| Error::UnexpectedProposalTxId.with_field("signed_transaction", e))?;
+        if transaction.id() != self.txid {
+            return Err(Error::UnexpectedProposalTxId.with_field(
+                "signed_transaction",
+                format!(
+                    "Signed transaction is {}, but the signature is for {}.",
+                    transaction.id(),
+                    self.txid
+                ),
+            ));
+        }
+        // Only the transaction spending the same output as the proposal conflicts with it.
+        let previous_output = transaction
+            .0
+            .input
+            .get(self.input as usize)
+            .ok_or_else(|| {
+                Error::NoSuchInput.with_field(
+                    "input",
+                    format!(
+                        "Signed transaction has {} inputs, but input {} is signed.",
+                        transaction.0.input.len(),
+                        self.input
+                    ),
+                )
+            })?
+            .previous_output;
+        let input_transaction = proposal
+            .0
+            .input
+            .iter()
+            .zip(inputs)
+            .find(|(txin, _)| txin.previous_output == previous_output)
+            .map(|(_, input_transaction)| input_transaction)
+            .ok_or_else(|| {
+                Error::UnexpectedProposalTxId.with_field(
+                    "signed_transaction",
+                    format!(
+                        "Signed transaction does not spend the outputs of the anchoring \
+                         proposal {}.",
+                        proposal.id()
+                    ),
+                )
+            })?;
+        input_signer
+            .verify_input(
+                TxInRef::new(transaction.as_ref(), self.input as usize),
+                input_transaction.as_ref(),
+                &public_key.0,
+                self.input_signature.as_ref(),
+            )
+            .map_err(|e| Error::InputVerificationFailed.with_field("input_signature", e))?;
+        Ok(transaction)
+    }
+}
+
+impl UpdateParameters {
+    /// Returns the given configuration with the parameters from this message.
+    pub(crate) fn apply_to(&self, mut config: Config) -> Config {
+        if self.anchoring_interval != 0 {
+            config.anchoring_interval = self.anchoring_interval;
+        }
+        if self.transaction_fee != 0 {
+            config.transaction_fee = self.transaction_fee;
+        }
+        config
     }
 }
 
@@ -82,6 +195,12 @@ impl TransactionConfirmations {
         let confirmations = self.0.len();
         Ok(confirmations == config.byzantine_quorum())
     }
+
+    /// Checks if there are enough confirmations to approve the high-risk maintenance operation.
+    pub(crate) fn has_enough_approvals(&self, config: &Config) -> Result<bool, ExecutionError> {
+        let confirmations = self.0.len();
+        Ok(confirmations == config.approval_quorum())
+    }
 }
 
 /// Exonum BTC anchoring transactions.
@@ -99,6 +218,77 @@ pub trait BtcAnchoringInterface<Ctx> {
     /// The transaction will be applied if 2/3+1 anchoring nodes sent it.
     #[interface_method(id = 1)]
     fn add_funds(&self, context: Ctx, arg: AddFunds) -> Self::Output;
+    /// Submits the hash of the tenant data for anchoring.
+    ///
+    /// The transaction author should be one of the tenants listed in the actual
+    /// configuration, which has not exhausted its data quota.
+    #[interface_method(id = 2)]
+    fn submit_data(&self, context: Ctx, arg: SubmitData) -> Self::Output;
+    /// Submits the Bitcoin key of the validator, which joins the anchoring, or approves
+    /// the key submitted by another validator.
+    ///
+    /// The submitting validator should not participate in the anchoring yet, and should
+    /// prove the possession of the key for the resulting configuration. The same message
+    /// sent by the anchoring nodes approves the submitted key. Once the key is approved
+    /// by the quorum of the anchoring nodes within the approval window, it is included
+    /// into the pending configuration, which is scheduled for the transition automatically.
+    #[interface_method(id = 3)]
+    fn submit_anchoring_key(&self, context: Ctx, arg: SubmitAnchoringKey) -> Self::Output;
+    /// Registers the backup Bitcoin key of the anchoring node.
+    ///
+    /// The transaction author should be an anchoring node. The key is registered after
+    /// another anchoring node sends the same message within the approval window. If the node
+    /// does not sign the anchoring proposals during the number of anchoring intervals
+    /// specified in the configuration, the transition to the configuration with the backup
+    /// key instead of the actual one is scheduled automatically.
+    #[interface_method(id = 4)]
+    fn register_standby_key(&self, context: Ctx, arg: RegisterStandbyKey) -> Self::Output;
+    /// Imports the external transaction, which spends the output of the latest anchoring
+    /// transaction or conflicts with it, for example, the rescue transaction crafted
+    /// by the operators manually.
+    ///
+    /// The transaction will be applied if 2/3+1 anchoring nodes, but at least two of them,
+    /// sent it within the approval window. The transaction with the valid anchoring payload
+    /// is adopted into the anchoring chain, otherwise the anchoring chain is marked as broken
+    /// and is recovered with the funding transaction.
+    #[interface_method(id = 5)]
+    fn import_anchor(&self, context: Ctx, arg: ImportAnchor) -> Self::Output;
+    /// Updates the anchoring interval and the transaction fee without the configuration
+    /// change via the supervisor.
+    ///
+    /// The parameters will be applied if 2/3+1 anchoring nodes, but at least two of them,
+    /// sent the same message within the approval window. The following configuration,
+    /// if any, is updated as well.
+    #[interface_method(id = 6)]
+    fn update_parameters(&self, context: Ctx, arg: UpdateParameters) -> Self::Output;
+    /// Reports that the anchoring transaction is buried deep enough in the Bitcoin
+    /// blockchain.
+    ///
+    /// The transaction is marked as final if 2/3+1 anchoring nodes sent the same message,
+    /// see [`Schema::is_anchored_and_final`].
+    ///
+    /// [`Schema::is_anchored_and_final`]: struct.Schema.html#method.is_anchored_and_final
+    #[interface_method(id = 7)]
+    fn confirm_anchor_finality(&self, context: Ctx, arg: ConfirmAnchorFinality) -> Self::Output;
+    /// Reports the status of the latest anchoring transaction in the Bitcoin network
+    /// observed by the anchoring node, so that the transactions which have been evicted
+    /// from the mempool or have never been broadcast can be detected.
+    ///
+    /// The reports for the previous anchoring transactions are ignored.
+    #[interface_method(id = 8)]
+    fn report_anchor_status(&self, context: Ctx, arg: ReportAnchorStatus) -> Self::Output;
+    /// Reports the Bitcoin block which contains the anchoring transaction, so that
+    /// the transaction can be fetched from the pruned Bitcoin nodes.
+    ///
+    /// The block hash is recorded if 2/3+1 anchoring nodes sent the same message.
+    #[interface_method(id = 9)]
+    fn record_anchor_block(&self, context: Ctx, arg: RecordAnchorBlock) -> Self::Output;
+    /// Reports the health of the Bitcoin relay used by the anchoring node, so that
+    /// the nodes unable to broadcast the anchoring transactions can be detected.
+    ///
+    /// Only the latest report of each anchoring node is kept.
+    #[interface_method(id = 10)]
+    fn report_relay_status(&self, context: Ctx, arg: ReportRelayStatus) -> Self::Output;
 }
 
 impl BtcAnchoringInterface<ExecutionContext<'_>> for BtcAnchoringService {
@@ -113,10 +303,13 @@ impl BtcAnchoringInterface<ExecutionContext<'_>> for BtcAnchoringService {
         let mut schema = Schema::new(context.service_data());
 
         // Check that author is authorized to sign inputs of the anchoring proposal.
-        let actual_config = schema.actual_config();
-        let (anchoring_node_id, public_key) = actual_config
-            .find_bitcoin_key(&author)
-            .ok_or(Error::UnauthorizedAnchoringKey)?;
+        // The Bitcoin key of the author is determined by its service key, rather than
+        // by the position of the author in the list of validators or anchoring nodes.
+        let actual_config = schema.try_actual_config().ok_or(Error::NotConfigured)?;
+        let public_key = actual_config
+            .find_anchoring_keys(&author)
+            .ok_or(Error::UnauthorizedAnchoringKey)?
+            .bitcoin_key;
 
         // Check that there is an anchoring proposal for the actual blockchain state.
         let (proposal, expected_inputs) = if let Some(proposal) = schema
@@ -128,29 +321,140 @@ impl BtcAnchoringInterface<ExecutionContext<'_>> for BtcAnchoringService {
         } else {
             // There is no anchoring request at the current blockchain state.
             // Make sure txid is equal to the identifier of the last anchoring transaction.
+            // If the anchoring chain is not established, then the anchoring has not been
+            // started yet, and there is nothing to sign.
             let latest_anchoring_txid = schema
                 .transactions_chain
                 .last()
-                // If the anchoring chain is not established, then the proposal must exist.
-                .unwrap()
+                .ok_or_else(|| {
+                    Error::UnexpectedProposalTxId
+                        .with_field("txid", "There is no anchoring proposal to sign.")
+                })?
                 .id();
             if latest_anchoring_txid == arg.txid {
                 return Ok(());
             } else {
-                return Err(Error::UnexpectedProposalTxId.into());
+                return Err(Error::UnexpectedProposalTxId.with_field(
+                    "txid",
+                    format!(
+                        "There is no anchoring proposal, the latest anchoring transaction is {}.",
+                        latest_anchoring_txid
+                    ),
+                ));
             }
         };
 
         // Make sure txid is equal to the identifier of the anchoring transaction proposal.
+        let anchoring_height = proposal.anchoring_payload().map(|x| x.block_height);
         if proposal.id() != arg.txid {
-            return Err(Error::UnexpectedProposalTxId.into());
+            // The signature for another transaction of the same anchoring slot means that
+            // the node has computed a different proposal or equivocates, unless the signed
+            // proposal has been superseded by the changes in the funding or parameters.
+            // The verified evidence is kept, and the finalization of the slot is halted
+            // once several nodes have signed the conflicting transactions.
+            if anchoring_height == Some(arg.anchoring_height)
+                && arg.epoch == schema.anchoring_epoch()
+                && !schema.is_proposal_superseded(&arg.txid)
+            {
+                let input_signer = InputSigner::new(actual_config.redeem_script());
+                let conflicting_transaction = arg.verify_conflicting_signature(
+                    &input_signer,
+                    &public_key,
+                    &proposal,
+                    &expected_inputs,
+                )?;
+                let conflict = ProposalConflict {
+                    anchoring_height: arg.anchoring_height,
+                    epoch: arg.epoch,
+                    proposal_txid: proposal.id(),
+                    conflicting_txid: arg.txid,
+                    author,
+                    input: arg.input,
+                    input_signature: arg.input_signature,
+                    detected_height: context.data().for_core().height(),
+                    conflicting_transaction,
+                };
+                if schema.record_proposal_conflict(conflict) {
+                    error!("====== PROPOSAL_CONFLICT ======");
+                    error!("author: {}", author.to_hex());
+                    error!("height: {}", arg.anchoring_height);
+                    error!("proposal: {}", proposal.id());
+                    error!("conflicting: {}", arg.txid);
+                }
+                return Ok(());
+            }
+            return Err(Error::UnexpectedProposalTxId.with_field(
+                "txid",
+                format!("Actual anchoring proposal is {}.", proposal.id()),
+            ));
+        }
+        // Make sure the signature has been produced for the proposal of the actual
+        // anchoring height and epoch.
+        if anchoring_height != Some(arg.anchoring_height) {
+            return Err(Error::ReplayedSignature.with_field(
+                "anchoring_height",
+                format!(
+                    "Signature is produced for the proposal of height {}.",
+                    arg.anchoring_height
+                ),
+            ));
+        }
+        if arg.epoch != schema.anchoring_epoch() {
+            return Err(Error::ReplayedSignature.with_field(
+                "epoch",
+                format!(
+                    "Signature is produced for the anchoring epoch {}, the actual one is {}.",
+                    arg.epoch,
+                    schema.anchoring_epoch()
+                ),
+            ));
+        }
+        // Make sure the proposal is not too old. The transition proposal re-anchors
+        // the latest anchored height, so its age is not limited, just like the stale
+        // anchoring heights are not skipped during the transition.
+        let max_age = actual_config.max_signature_age;
+        let height = context.data().for_core().height();
+        if max_age != 0
+            && schema.actual_state().is_regular()
+            && height.0 > arg.anchoring_height.0.saturating_add(max_age)
+        {
+            return Err(Error::StaleSignature.with_field(
+                "anchoring_height",
+                format!(
+                    "Anchoring proposal for height {} is older than {} blocks.",
+                    arg.anchoring_height, max_age
+                ),
+            ));
+        }
+        // Make sure the anchoring nodes have not signed conflicting proposals for this slot.
+        if schema.is_anchoring_halted(arg.anchoring_height, arg.epoch) {
+            return Err(Error::AnchoringHalted.with_field(
+                "txid",
+                format!(
+                    "Finalization of the anchoring proposal {} is halted due to the conflicting \
+                     proposals.",
+                    proposal.id()
+                ),
+            ));
         }
 
         // Check that input signature is correct.
         let redeem_script = actual_config.redeem_script();
         let quorum = redeem_script.content().quorum;
+        // Make sure the author key is a part of the redeem script, otherwise the signature
+        // can not be used to finalize the proposal. Signatures are ordered by the positions
+        // of the corresponding keys in the redeem script.
+        let anchoring_node_id = actual_config
+            .redeem_script_position(&public_key)
+            .ok_or_else(|| {
+                Error::KeyNotInRedeemScript.with_description(format!(
+                    "Anchoring key {} is absent in the redeem script.",
+                    public_key
+                ))
+            })?;
         let input_signer = InputSigner::new(redeem_script);
         arg.verify_signature(&input_signer, &public_key, &proposal, &expected_inputs)?;
+        schema.record_signing_activity(author, arg.anchoring_height);
 
         // All preconditions are correct and we can use this signature.
         let input_id = TxInputId::new(proposal.id(), arg.input);
@@ -160,9 +464,7 @@ impl BtcAnchoringInterface<ExecutionContext<'_>> for BtcAnchoringService {
         if input_signature_len < quorum {
             // Add signature to schema.
             input_signatures.insert(anchoring_node_id, arg.input_signature);
-            schema
-                .transaction_signatures
-                .put(&input_id, input_signatures);
+            schema.put_input_signatures(&input_id, expected_inputs.len() as u32, input_signatures);
             input_signature_len += 1;
         } else {
             return Ok(());
@@ -188,7 +490,10 @@ impl BtcAnchoringInterface<ExecutionContext<'_>> for BtcAnchoringService {
                 );
             }
 
-            let payload = finalized_tx.anchoring_metadata().unwrap().1;
+            let payload = finalized_tx.anchoring_payload().ok_or_else(|| {
+                Error::MalformedAnchoringTransaction
+                    .with_description("Unable to find payload in the anchoring proposal.")
+            })?;
 
             info!("====== ANCHORING ======");
             info!("txid: {}", finalized_tx.id().to_string());
@@ -197,8 +502,39 @@ impl BtcAnchoringInterface<ExecutionContext<'_>> for BtcAnchoringService {
             info!("balance: {}", finalized_tx.0.output[0].value);
             trace!("Anchoring txhex: {}", finalized_tx.to_string());
 
+            let input_values = proposal
+                .0
+                .input
+                .iter()
+                .zip(&expected_inputs)
+                .filter_map(|(txin, input)| input.0.output.get(txin.previous_output.vout as usize))
+                .map(|out| out.value)
+                .collect::<Vec<_>>();
+            let balance = input_values.iter().sum::<u64>();
+            let accounting = AnchorAccounting::new(&finalized_tx, input_values);
+            info!(
+                "fee: {} (rate {} sat/kvB, vsize {})",
+                accounting.fee, accounting.fee_rate, accounting.vsize
+            );
+            // Attribute the fee consumed from the anchoring wallet to the node which has
+            // finalized the transaction.
+            let fee = accounting.fee;
+            schema.record_anchoring_fee(author, fee);
+
+            let block_height = context.data().for_core().next_height();
+            schema.balance_history.push(BalanceRecord {
+                block_height: block_height.0,
+                input_value: balance,
+                fee,
+                change: finalized_tx.0.output[0].value,
+            });
+
+            let index = schema.transactions_chain.len();
+            schema.anchor_accounting.put(&index, accounting);
+            schema.record_anchor_health(payload.block_height, block_height);
+
             // Add finalized transaction to the tail of anchoring transactions.
-            schema.push_anchoring_transaction(finalized_tx);
+            schema.push_anchoring_transaction(finalized_tx, block_height)?;
         }
         Ok(())
     }
@@ -211,7 +547,7 @@ impl BtcAnchoringInterface<ExecutionContext<'_>> for BtcAnchoringService {
         let mut schema = Schema::new(context.service_data());
 
         // Check that author is authorized to sign inputs of the anchoring proposal.
-        let actual_config = schema.actual_config();
+        let actual_config = schema.try_actual_config().ok_or(Error::NotConfigured)?;
         let (_, public_key) = actual_config
             .find_bitcoin_key(&author)
             .ok_or(Error::UnauthorizedAnchoringKey)?;
@@ -220,12 +556,23 @@ impl BtcAnchoringInterface<ExecutionContext<'_>> for BtcAnchoringService {
         let (_, txout) = arg
             .transaction
             .find_out(&actual_config.anchoring_out_script())
-            .ok_or(Error::UnsuitableFundingTx)?;
+            .ok_or_else(|| {
+                Error::UnsuitableFundingTx.with_field(
+                    "transaction",
+                    format!(
+                        "Transaction has no outputs to the anchoring address {}.",
+                        actual_config.anchoring_address()
+                    ),
+                )
+            })?;
 
         // Check that the transaction has not been used before
         let funding_txid = arg.transaction.id();
         if schema.spent_funding_transactions.contains(&funding_txid) {
-            return Err(Error::AlreadyUsedFundingTx.into());
+            return Err(Error::AlreadyUsedFundingTx.with_field(
+                "transaction",
+                format!("Funding transaction {} is already spent.", funding_txid),
+            ));
         }
 
         // Add confirmation from this node for this funding transaction.
@@ -242,6 +589,8 @@ impl BtcAnchoringInterface<ExecutionContext<'_>> for BtcAnchoringService {
             info!("txid: {}", arg.transaction.id().to_string());
             info!("balance: {}", txout.value);
 
+            // The funding transaction is added to the anchoring proposal.
+            schema.supersede_anchoring_proposal(context.data().for_core());
             schema.set_funding_transaction(arg.transaction);
         } else {
             schema
@@ -250,4 +599,633 @@ impl BtcAnchoringInterface<ExecutionContext<'_>> for BtcAnchoringService {
         }
         Ok(())
     }
+
+    fn submit_data(&self, context: ExecutionContext<'_>, arg: SubmitData) -> Self::Output {
+        let author = context
+            .caller()
+            .author()
+            .ok_or(CommonError::UnauthorizedCaller)?;
+        let mut schema = Schema::new(context.service_data());
+
+        // Check that author is a tenant with the available quota.
+        let quota = schema
+            .try_actual_config()
+            .ok_or(Error::NotConfigured)?
+            .find_tenant_quota(&author)
+            .ok_or(Error::UnknownTenant)?;
+        if schema.tenant_usage(&author) >= quota {
+            return Err(Error::TenantQuotaExceeded.into());
+        }
+
+        let block_height = context.data().for_core().next_height();
+        trace!(
+            "Tenant {} submitted data {} at height {}",
+            author,
+            arg.data_hash,
+            block_height
+        );
+        schema.push_tenant_data(author, block_height, arg.data_hash);
+        Ok(())
+    }
+
+    fn submit_anchoring_key(
+        &self,
+        context: ExecutionContext<'_>,
+        arg: SubmitAnchoringKey,
+    ) -> Self::Output {
+        let author = context
+            .caller()
+            .author()
+            .ok_or(CommonError::UnauthorizedCaller)?;
+        let mut schema = Schema::new(context.service_data());
+        let actual_config = schema.try_actual_config().ok_or(Error::NotConfigured)?;
+
+        // The message is either the submission of the key of the joining validator, or
+        // the approval of the key submitted by another validator by an anchoring node.
+        let owner = schema
+            .anchoring_key_proofs
+            .iter()
+            .find(|(owner, proof)| {
+                proof.bitcoin_key == arg.bitcoin_key
+                    && !schema.pending_anchoring_keys.contains(owner)
+            })
+            .map_or(author, |(owner, _)| owner);
+        let height = context.data().for_core().height();
+
+        let approver_key = if owner == author {
+            // Check that author is a validator of the blockchain.
+            let is_validator = context
+                .data()
+                .for_core()
+                .consensus_config()
+                .validator_keys
+                .iter()
+                .any(|keys| keys.service_key == author);
+            if !is_validator {
+                return Err(Error::UnknownValidator.into());
+            }
+
+            // Check that neither the validator nor its Bitcoin key participates in the anchoring.
+            let key_exists = schema
+                .pending_config()
+                .anchoring_keys
+                .iter()
+                .any(|keys| keys.service_key == author || keys.bitcoin_key == arg.bitcoin_key);
+            if key_exists {
+                return Err(Error::AnchoringKeyExists.with_field(
+                    "bitcoin_key",
+                    "Validator or its Bitcoin key already participates in the anchoring.",
+                ));
+            }
+
+            // The proof of the key possession is bound to the resulting configuration,
+            // so the validators join the anchoring one at a time and not during
+            // the transition of the anchoring chain. The submission, which has not been
+            // approved within the approval window, is discarded.
+            if schema.following_config().is_some() {
+                return Err(Error::OnboardingInProgress.with_field(
+                    "bitcoin_key",
+                    "Transition of the anchoring chain is in progress.",
+                ));
+            }
+            let other_submission = schema
+                .anchoring_key_proofs
+                .iter()
+                .find(|(service_key, _)| *service_key != author);
+            if let Some((service_key, proof)) = other_submission {
+                let operation = AnchoringKeys {
+                    service_key,
+                    bitcoin_key: proof.bitcoin_key,
+                }
+                .object_hash();
+                if schema.pending_anchoring_keys.contains(&service_key)
+                    || schema.is_approval_window_open(&operation, height)?
+                {
+                    return Err(Error::OnboardingInProgress.with_field(
+                        "bitcoin_key",
+                        format!(
+                            "Anchoring key of the validator {} awaits the inclusion into \
+                             the anchoring configuration.",
+                            service_key
+                        ),
+                    ));
+                }
+                schema.discard_submitted_key(&service_key);
+            }
+            None
+        } else {
+            // Check that author is an anchoring node.
+            let (_, public_key) = actual_config
+                .find_bitcoin_key(&author)
+                .ok_or(Error::UnauthorizedAnchoringKey)?;
+            Some(public_key)
+        };
+
+        // Check that the configuration with the submitted key is suitable.
+        let anchoring_keys = AnchoringKeys {
+            service_key: owner,
+            bitcoin_key: arg.bitcoin_key,
+        };
+        let mut onboarding_config = schema.onboarding_config(anchoring_keys.clone());
+        onboarding_config
+            .validate()
+            .map_err(|e| Error::InvalidAnchoringKey.with_field("bitcoin_key", e))?;
+        // The validator should prove the possession of the key for the resulting configuration
+        // in the same way as for the configuration proposed via the supervisor.
+        if !onboarding_config.verify_key_proof(&anchoring_keys, &arg.key_proof) {
+            return Err(Error::MissingKeyPossessionProof.with_field(
+                "key_proof",
+                format!(
+                    "Bitcoin key {} is not accompanied by the valid proof of possession.",
+                    arg.bitcoin_key
+                ),
+            ));
+        }
+
+        // The submitted key should be approved by the anchoring nodes within the approval
+        // window, so a single validator cannot change the anchoring address.
+        let operation = anchoring_keys.object_hash();
+        let approver_key = match approver_key {
+            Some(public_key) => public_key,
+            None => {
+                trace!(
+                    "Validator {} submitted anchoring key {}, which awaits the approval",
+                    author,
+                    arg.bitcoin_key
+                );
+                schema.discard_submitted_key(&author);
+                schema.anchoring_key_proofs.put(&author, arg.key_proof);
+                schema.open_approval_window(&operation, height);
+                return Ok(());
+            }
+        };
+        if !schema.is_approval_window_open(&operation, height)? {
+            return Err(Error::ApprovalExpired.with_field(
+                "bitcoin_key",
+                format!(
+                    "Anchoring key {} has not been approved within {} blocks after \
+                     the submission.",
+                    arg.bitcoin_key, actual_config.approval_window
+                ),
+            ));
+        }
+
+        let mut confirmations = schema
+            .unconfirmed_anchoring_keys
+            .get(&owner)
+            .unwrap_or_default();
+        confirmations.confirm_by_node(approver_key);
+        if !confirmations.has_enough_approvals(&actual_config)? {
+            trace!(
+                "Anchoring node {} approved anchoring key {} of the validator {}",
+                author,
+                arg.bitcoin_key,
+                owner
+            );
+            schema.unconfirmed_anchoring_keys.put(&owner, confirmations);
+            return Ok(());
+        }
+
+        // The resulting configuration passes the same checks as the configuration
+        // proposed via the supervisor.
+        onboarding_config.key_proofs.push(arg.key_proof);
+        BtcAnchoringService::verify_params(Some(&actual_config), &onboarding_config)?;
+        BtcAnchoringService::verify_address_reuse(&schema, &onboarding_config)?;
+
+        info!("====== APPROVE_ANCHORING_KEY ======");
+        info!("service_key: {}", owner);
+        info!("bitcoin_key: {}", arg.bitcoin_key);
+        schema.unconfirmed_anchoring_keys.remove(&owner);
+        schema.close_approval_window(&operation);
+        schema.pending_anchoring_keys.put(&owner, anchoring_keys);
+        Ok(())
+    }
+
+    fn register_standby_key(
+        &self,
+        context: ExecutionContext<'_>,
+        arg: RegisterStandbyKey,
+    ) -> Self::Output {
+        let author = context
+            .caller()
+            .author()
+            .ok_or(CommonError::UnauthorizedCaller)?;
+        let mut schema = Schema::new(context.service_data());
+
+        // Check that author is an anchoring node.
+        let actual_config = schema.try_actual_config().ok_or(Error::NotConfigured)?;
+        actual_config
+            .find_bitcoin_key(&author)
+            .ok_or(Error::UnauthorizedAnchoringKey)?;
+
+        // The message is either the registration of the standby key of the author, or
+        // the approval of the standby key registered by another anchoring node.
+        let owner = schema
+            .unconfirmed_standby_keys
+            .iter()
+            .find(|(_, bitcoin_key)| *bitcoin_key == arg.bitcoin_key)
+            .map_or(author, |(owner, _)| owner);
+
+        // Check that the standby key is not used by the anchoring nodes.
+        let key_exists = schema
+            .pending_config()
+            .anchoring_keys
+            .iter()
+            .any(|keys| keys.bitcoin_key == arg.bitcoin_key)
+            || schema
+                .standby_keys
+                .values()
+                .any(|bitcoin_key| bitcoin_key == arg.bitcoin_key);
+        if key_exists {
+            return Err(Error::AnchoringKeyExists.with_field(
+                "bitcoin_key",
+                format!("Bitcoin key {} is already in use.", arg.bitcoin_key),
+            ));
+        }
+        // The standby key is activated without the approval of the validators, so
+        // the anchoring node should prove the possession of the key.
+        let standby_keys = AnchoringKeys {
+            service_key: owner,
+            bitcoin_key: arg.bitcoin_key,
+        };
+        if !actual_config.verify_key_proof(&standby_keys, &arg.key_proof) {
+            return Err(Error::MissingKeyPossessionProof.with_field(
+                "key_proof",
+                format!(
+                    "Bitcoin key {} is not accompanied by the valid proof of possession.",
+                    arg.bitcoin_key
+                ),
+            ));
+        }
+
+        // The standby key should be approved by another anchoring node within the approval
+        // window, so a single compromised service key cannot replace the anchoring key.
+        let operation = standby_keys.object_hash();
+        let height = context.data().for_core().height();
+        if owner == author {
+            trace!(
+                "Anchoring node {} registered standby key {}, which awaits the approval",
+                author,
+                arg.bitcoin_key
+            );
+            schema
+                .unconfirmed_standby_keys
+                .put(&author, arg.bitcoin_key);
+            schema.open_approval_window(&operation, height);
+            return Ok(());
+        }
+        if !schema.is_approval_window_open(&operation, height)? {
+            return Err(Error::ApprovalExpired.with_field(
+                "bitcoin_key",
+                format!(
+                    "Standby key {} has not been approved within {} blocks after \
+                     the registration.",
+                    arg.bitcoin_key, actual_config.approval_window
+                ),
+            ));
+        }
+
+        trace!(
+            "Anchoring node {} approved standby key {} of the anchoring node {}",
+            author,
+            arg.bitcoin_key,
+            owner
+        );
+        schema.unconfirmed_standby_keys.remove(&owner);
+        schema.close_approval_window(&operation);
+        schema.register_standby_key(owner, arg.bitcoin_key);
+        Ok(())
+    }
+
+    fn import_anchor(&self, context: ExecutionContext<'_>, arg: ImportAnchor) -> Self::Output {
+        let author = context
+            .caller()
+            .author()
+            .ok_or(CommonError::UnauthorizedCaller)?;
+        let mut schema = Schema::new(context.service_data());
+
+        // Check that author is an anchoring node.
+        let actual_config = schema.try_actual_config().ok_or(Error::NotConfigured)?;
+        let (_, public_key) = actual_config
+            .find_bitcoin_key(&author)
+            .ok_or(Error::UnauthorizedAnchoringKey)?;
+
+        // Check that the transaction has not been imported before.
+        let txid = arg.transaction.id();
+        let latest_tx = schema.transactions_chain.last().ok_or_else(|| {
+            Error::UnsuitableExternalAnchor
+                .with_field("transaction", "Anchoring chain is not established.")
+        })?;
+        if schema.transaction_index(&txid).is_some()
+            || schema.external_spend(&latest_tx.id()).is_some()
+        {
+            return Ok(());
+        }
+
+        // Check that the transaction spends the output of the latest anchoring transaction
+        // or conflicts with it.
+        let spent_output = OutPoint {
+            txid: latest_tx.0.txid(),
+            vout: 0,
+        };
+        let spends_output = arg
+            .transaction
+            .0
+            .input
+            .iter()
+            .any(|txin| txin.previous_output == spent_output);
+        let conflicts = arg.transaction.0.input.iter().any(|txin| {
+            latest_tx
+                .0
+                .input
+                .iter()
+                .any(|latest_txin| latest_txin.previous_output == txin.previous_output)
+        });
+        if !spends_output && !conflicts {
+            return Err(Error::UnsuitableExternalAnchor.with_field(
+                "transaction",
+                format!(
+                    "Transaction {} neither spends nor conflicts with the latest anchoring \
+                     transaction {}.",
+                    txid,
+                    latest_tx.id()
+                ),
+            ));
+        }
+
+        // Add confirmation from this node for this transaction. The confirmations given
+        // within the expired approval window are discarded.
+        let operation = arg.object_hash();
+        let height = context.data().for_core().height();
+        let mut confirmations = if schema.is_approval_window_open(&operation, height)? {
+            schema
+                .unconfirmed_external_anchors
+                .get(&txid)
+                .unwrap_or_default()
+        } else {
+            schema.open_approval_window(&operation, height);
+            TransactionConfirmations::default()
+        };
+        confirmations.confirm_by_node(public_key);
+        if !confirmations.has_enough_approvals(&actual_config)? {
+            schema
+                .unconfirmed_external_anchors
+                .put(&txid, confirmations);
+            return Ok(());
+        }
+        schema.unconfirmed_external_anchors.remove(&txid);
+        schema.close_approval_window(&operation);
+
+        let core_schema = context.data().for_core();
+        let block_height = core_schema.next_height();
+        match schema.verify_external_anchor(&arg.transaction, core_schema) {
+            Ok(()) if spends_output => {
+                info!("====== IMPORT_ANCHOR ======");
+                info!("txid: {}", txid.to_string());
+                info!("balance: {}", arg.transaction.0.output[0].value);
+
+                schema.push_anchoring_transaction(arg.transaction, block_height)?;
+            }
+            result => {
+                warn!(
+                    "Anchoring chain is broken by the external transaction {}: {}",
+                    txid,
+                    result.err().unwrap_or_else(|| {
+                        "transaction conflicts with the latest anchoring transaction".to_owned()
+                    })
+                );
+                schema.external_spends.put(&latest_tx.id(), arg.transaction);
+            }
+        }
+        Ok(())
+    }
+
+    fn update_parameters(
+        &self,
+        context: ExecutionContext<'_>,
+        arg: UpdateParameters,
+    ) -> Self::Output {
+        let author = context
+            .caller()
+            .author()
+            .ok_or(CommonError::UnauthorizedCaller)?;
+        let mut schema = Schema::new(context.service_data());
+
+        // Check that author is an anchoring node.
+        let actual_config = schema.try_actual_config().ok_or(Error::NotConfigured)?;
+        let (_, public_key) = actual_config
+            .find_bitcoin_key(&author)
+            .ok_or(Error::UnauthorizedAnchoringKey)?;
+
+        // Check that the configuration with the proposed parameters is suitable.
+        arg.apply_to(actual_config.clone())
+            .validate()
+            .map_err(|e| Error::InvalidParameters.with_description(e))?;
+
+        // Add confirmation from this node for these parameters. The confirmations given
+        // within the expired approval window are discarded.
+        let parameters_hash = arg.object_hash();
+        let height = context.data().for_core().height();
+        let mut confirmations = if schema.is_approval_window_open(&parameters_hash, height)? {
+            schema
+                .unconfirmed_parameters
+                .get(&parameters_hash)
+                .unwrap_or_default()
+        } else {
+            schema.open_approval_window(&parameters_hash, height);
+            TransactionConfirmations::default()
+        };
+        confirmations.confirm_by_node(public_key);
+        if !confirmations.has_enough_approvals(&actual_config)? {
+            schema
+                .unconfirmed_parameters
+                .put(&parameters_hash, confirmations);
+            return Ok(());
+        }
+        schema.close_approval_window(&parameters_hash);
+
+        info!("====== UPDATE_PARAMETERS ======");
+        info!("anchoring_interval: {}", arg.anchoring_interval);
+        info!("transaction_fee: {}", arg.transaction_fee);
+        schema.supersede_anchoring_proposal(context.data().for_core());
+        schema.update_parameters(&arg);
+        Ok(())
+    }
+
+    fn confirm_anchor_finality(
+        &self,
+        context: ExecutionContext<'_>,
+        arg: ConfirmAnchorFinality,
+    ) -> Self::Output {
+        let author = context
+            .caller()
+            .author()
+            .ok_or(CommonError::UnauthorizedCaller)?;
+        let mut schema = Schema::new(context.service_data());
+
+        // Check that author is an anchoring node.
+        let actual_config = schema.try_actual_config().ok_or(Error::NotConfigured)?;
+        let (_, public_key) = actual_config
+            .find_bitcoin_key(&author)
+            .ok_or(Error::UnauthorizedAnchoringKey)?;
+
+        let index = schema.transaction_index(&arg.txid).ok_or_else(|| {
+            Error::UnknownAnchoringTransaction.with_field(
+                "txid",
+                format!("Transaction {} is absent in the anchoring chain.", arg.txid),
+            )
+        })?;
+        // The transaction is final already if the following one is final.
+        if schema
+            .final_anchor_index()
+            .map_or(false, |final_index| index <= final_index)
+        {
+            return Ok(());
+        }
+
+        // Add confirmation from this node for this transaction.
+        let mut confirmations = schema
+            .unconfirmed_final_anchors
+            .get(&arg.txid)
+            .unwrap_or_default();
+        confirmations.confirm_by_node(public_key);
+        if !confirmations.has_enough_confirmations(&actual_config)? {
+            schema
+                .unconfirmed_final_anchors
+                .put(&arg.txid, confirmations);
+            return Ok(());
+        }
+
+        info!("====== ANCHOR_FINALITY ======");
+        info!("txid: {}", arg.txid);
+        info!("index: {}", index);
+        schema.set_final_anchor(index, &arg.txid);
+        Ok(())
+    }
+
+    fn report_anchor_status(
+        &self,
+        context: ExecutionContext<'_>,
+        arg: ReportAnchorStatus,
+    ) -> Self::Output {
+        let author = context
+            .caller()
+            .author()
+            .ok_or(CommonError::UnauthorizedCaller)?;
+        let reported_height = context.data().for_core().height();
+        let mut schema = Schema::new(context.service_data());
+
+        // Check that author is an anchoring node.
+        let actual_config = schema.try_actual_config().ok_or(Error::NotConfigured)?;
+        actual_config
+            .find_bitcoin_key(&author)
+            .ok_or(Error::UnauthorizedAnchoringKey)?;
+
+        let index = schema.transaction_index(&arg.txid).ok_or_else(|| {
+            Error::UnknownAnchoringTransaction.with_field(
+                "txid",
+                format!("Transaction {} is absent in the anchoring chain.", arg.txid),
+            )
+        })?;
+        // The report may have been sent before the following anchoring transaction has been
+        // recorded, it is not relevant anymore.
+        if index + 1 != schema.transactions_chain.len() {
+            return Ok(());
+        }
+
+        schema.record_anchor_status(
+            author,
+            AnchorStatusReport {
+                txid: arg.txid,
+                known: arg.known,
+                confirmations: arg.confirmations,
+                reported_height: reported_height.0,
+            },
+        );
+        Ok(())
+    }
+
+    fn record_anchor_block(
+        &self,
+        context: ExecutionContext<'_>,
+        arg: RecordAnchorBlock,
+    ) -> Self::Output {
+        let author = context
+            .caller()
+            .author()
+            .ok_or(CommonError::UnauthorizedCaller)?;
+        let mut schema = Schema::new(context.service_data());
+
+        // Check that author is an anchoring node.
+        let actual_config = schema.try_actual_config().ok_or(Error::NotConfigured)?;
+        let (_, public_key) = actual_config
+            .find_bitcoin_key(&author)
+            .ok_or(Error::UnauthorizedAnchoringKey)?;
+
+        if schema.transaction_index(&arg.txid).is_none() {
+            return Err(Error::UnknownAnchoringTransaction.with_field(
+                "txid",
+                format!("Transaction {} is absent in the anchoring chain.", arg.txid),
+            ));
+        }
+        if schema.anchor_block_hash(&arg.txid).is_some() {
+            return Ok(());
+        }
+
+        // Add confirmation from this node for this block.
+        let record_hash = arg.object_hash();
+        let mut confirmations = schema
+            .unconfirmed_anchor_blocks
+            .get(&record_hash)
+            .unwrap_or_default();
+        confirmations.confirm_by_node(public_key);
+        if !confirmations.has_enough_confirmations(&actual_config)? {
+            schema
+                .unconfirmed_anchor_blocks
+                .put(&record_hash, confirmations);
+            return Ok(());
+        }
+
+        info!("====== ANCHOR_BLOCK ======");
+        info!("txid: {}", arg.txid);
+        info!("block_hash: {}", arg.block_hash);
+        schema.set_anchor_block(&arg, &record_hash);
+        Ok(())
+    }
+
+    fn report_relay_status(
+        &self,
+        context: ExecutionContext<'_>,
+        arg: ReportRelayStatus,
+    ) -> Self::Output {
+        let author = context
+            .caller()
+            .author()
+            .ok_or(CommonError::UnauthorizedCaller)?;
+        let reported_height = context.data().for_core().height();
+        let mut schema = Schema::new(context.service_data());
+
+        // Check that author is an anchoring node.
+        let actual_config = schema.try_actual_config().ok_or(Error::NotConfigured)?;
+        actual_config
+            .find_bitcoin_key(&author)
+            .ok_or(Error::UnauthorizedAnchoringKey)?;
+
+        if !arg.healthy {
+            warn!(
+                "Bitcoin relay of the anchoring node {} is unavailable, {} failed checks.",
+                author, arg.failed_checks
+            );
+        }
+        schema.record_relay_status(
+            author,
+            RelayStatusReport {
+                healthy: arg.healthy,
+                block_height: arg.block_height,
+                failed_checks: arg.failed_checks,
+                reported_height: reported_height.0,
+            },
+        );
+        Ok(())
+    }
 }
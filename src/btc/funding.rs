@@ -0,0 +1,212 @@
+// Copyright 2019 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Templates of the transactions, which fund the anchoring wallet.
+
+use bitcoin::{
+    blockdata::transaction::TxOut,
+    consensus::encode,
+    util::psbt::{raw, PartiallySignedTransaction},
+};
+
+use super::{Address, Transaction};
+
+/// Identifier of the proprietary PSBT fields of the anchoring service.
+const PSBT_PROPRIETARY_ID: &[u8] = b"exonum";
+/// Type of the proprietary PSBT fields (`PSBT_OUT_PROPRIETARY`).
+const PSBT_PROPRIETARY_TYPE: u8 = 0xFC;
+/// Subtype of the proprietary PSBT output field with the output label.
+const PSBT_LABEL_SUBTYPE: u8 = 0x00;
+
+/// Template of the transaction, which pays the given amount to the anchoring address.
+///
+/// The template has no inputs, so it can be imported into the wallet software of
+/// the treasury, which selects the inputs and signs the transaction, for example,
+/// by the `walletcreatefundedpsbt` call of the Bitcoin Core wallet.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FundingTemplate {
+    address: Address,
+    amount: u64,
+    label: Option<String>,
+}
+
+impl FundingTemplate {
+    /// Creates a template paying the given amount in satoshis to the anchoring address.
+    pub fn new(address: Address, amount: u64) -> Self {
+        Self {
+            address,
+            amount,
+            label: None,
+        }
+    }
+
+    /// Sets the label of the funding output, which is shown by the wallet software.
+    pub fn with_label(mut self, label: impl Into<String>) -> Self {
+        self.label = Some(label.into());
+        self
+    }
+
+    /// Returns the unsigned transaction without inputs.
+    pub fn unsigned_transaction(&self) -> Transaction {
+        Transaction::from(bitcoin::Transaction {
+            version: 2,
+            lock_time: 0,
+            input: vec![],
+            output: vec![TxOut {
+                value: self.amount,
+                script_pubkey: self.address.0.script_pubkey(),
+            }],
+        })
+    }
+
+    /// Returns the partially signed transaction, the label is stored in the proprietary
+    /// field of the funding output.
+    pub fn psbt(&self) -> PartiallySignedTransaction {
+        let mut psbt = PartiallySignedTransaction::from_unsigned_tx(self.unsigned_transaction().0)
+            .expect("Transaction without inputs has no signatures");
+        if let Some(label) = &self.label {
+            let mut key = vec![PSBT_PROPRIETARY_ID.len() as u8];
+            key.extend_from_slice(PSBT_PROPRIETARY_ID);
+            key.push(PSBT_LABEL_SUBTYPE);
+            psbt.outputs[0].unknown.insert(
+                raw::Key {
+                    type_value: PSBT_PROPRIETARY_TYPE,
+                    key,
+                },
+                label.as_bytes().to_vec(),
+            );
+        }
+        psbt
+    }
+
+    /// Returns the partially signed transaction in the Base64 encoding, which is accepted
+    /// by the most of the wallets.
+    pub fn psbt_base64(&self) -> String {
+        to_base64(&encode::serialize(&self.psbt()))
+    }
+
+    /// Returns the BIP-21 payment URI for the wallets, which do not support PSBT.
+    pub fn uri(&self) -> String {
+        let mut uri = format!(
+            "bitcoin:{}?amount={}",
+            self.address,
+            format_btc_amount(self.amount)
+        );
+        if let Some(label) = &self.label {
+            uri.push_str("&label=");
+            uri.push_str(&percent_encode(label));
+        }
+        uri
+    }
+}
+
+/// Formats the amount in satoshis as the decimal amount in bitcoins without trailing zeros.
+fn format_btc_amount(amount: u64) -> String {
+    const SATOSHIS_PER_BTC: u64 = 100_000_000;
+
+    let fraction = format!("{:08}", amount % SATOSHIS_PER_BTC);
+    let fraction = fraction.trim_end_matches('0');
+    if fraction.is_empty() {
+        (amount / SATOSHIS_PER_BTC).to_string()
+    } else {
+        format!("{}.{}", amount / SATOSHIS_PER_BTC, fraction)
+    }
+}
+
+fn percent_encode(s: &str) -> String {
+    s.bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                char::from(byte).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+fn to_base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut encoded = String::with_capacity((bytes.len() + 2) / 3 * 4);
+    for chunk in bytes.chunks(3) {
+        let triple = chunk.iter().enumerate().fold(0_u32, |acc, (i, &byte)| {
+            acc | (u32::from(byte) << (16 - 8 * i))
+        });
+        for i in 0..4 {
+            if i <= chunk.len() {
+                let index = (triple >> (18 - 6 * i)) & 0x3F;
+                encoded.push(char::from(ALPHABET[index as usize]));
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::{consensus::encode, network::constants::Network};
+
+    use super::{format_btc_amount, to_base64, FundingTemplate};
+    use crate::btc;
+
+    #[test]
+    fn base64_encoding() {
+        let test_cases = [
+            ("", ""),
+            ("f", "Zg=="),
+            ("fo", "Zm8="),
+            ("foo", "Zm9v"),
+            ("foob", "Zm9vYg=="),
+            ("fooba", "Zm9vYmE="),
+            ("foobar", "Zm9vYmFy"),
+        ];
+        for &(input, output) in &test_cases {
+            assert_eq!(to_base64(input.as_bytes()), output);
+        }
+    }
+
+    #[test]
+    fn btc_amount_formatting() {
+        assert_eq!(format_btc_amount(0), "0");
+        assert_eq!(format_btc_amount(100_000_000), "1");
+        assert_eq!(format_btc_amount(150_000), "0.0015");
+        assert_eq!(format_btc_amount(2_000_000_001), "20.00000001");
+    }
+
+    #[test]
+    fn funding_template() {
+        let public_key = btc::gen_keypair(Network::Testnet).0;
+        let address = btc::Address(bitcoin::Address::p2wpkh(&public_key.0, Network::Testnet));
+        let template = FundingTemplate::new(address.clone(), 150_000).with_label("Q3 top-up");
+
+        let tx = template.unsigned_transaction();
+        assert!(tx.0.input.is_empty());
+        assert_eq!(tx.0.output[0].value, 150_000);
+        assert_eq!(tx.0.output[0].script_pubkey, address.0.script_pubkey());
+
+        let psbt = template.psbt();
+        assert_eq!(psbt.global.unsigned_tx, tx.0);
+        assert_eq!(psbt.outputs[0].unknown.len(), 1);
+        // PSBT magic bytes followed by the unsigned transaction key.
+        assert!(template.psbt_base64().starts_with("cHNidP8B"));
+        assert_eq!(encode::serialize(&psbt)[..5], b"psbt\xff"[..]);
+
+        assert_eq!(
+            template.uri(),
+            format!("bitcoin:{}?amount=0.0015&label=Q3%20top-up", address)
+        );
+    }
+}
@@ -0,0 +1,437 @@
+// Copyright 2019 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Backup of the Bitcoin private keys split into the Shamir shares.
+//!
+//! The key is split over GF(256) with the same field polynomial as in SLIP-39, so that
+//! any `threshold` shares restore the key, while fewer shares reveal nothing about it.
+//!
+//! The scheme is not compatible with SLIP-39. The secret is shared directly without
+//! the digest share and the group sharing, and the shares are encoded as hex strings
+//! with the truncated SHA256d checksum instead of the mnemonics. The shares are
+//! protected by the passphrase with [`EncryptedKeyShare`] in the same way as the keys
+//! of the Exonum nodes, rather than by the SLIP-39 master secret encryption.
+//!
+//! [`EncryptedKeyShare`]: struct.EncryptedKeyShare.html
+
+use bitcoin::network::constants::Network;
+use bitcoin_hashes::{sha256d, Hash};
+use pwbox::{sodium::Sodium, ErasedPwBox, Eraser, Suite};
+use rand::{thread_rng, Rng};
+use serde_derive::{Deserialize, Serialize};
+use thiserror::Error;
+
+use std::collections::BTreeSet;
+
+use super::PrivateKey;
+
+/// Length of the secret key in bytes.
+const SECRET_LEN: usize = 32;
+/// Length of the share checksum in bytes.
+const CHECKSUM_LEN: usize = 4;
+/// Length of the encoded share without the checksum.
+const SHARE_LEN: usize = 2 + 1 + 1 + 1 + 1 + SECRET_LEN;
+/// Minimal number of shares required to restore the key.
+const MIN_THRESHOLD: u8 = 2;
+
+/// Key share errors.
+#[derive(Debug, Error, PartialEq)]
+pub enum KeyShareError {
+    /// Threshold or number of shares is out of the valid range.
+    #[error("Invalid sharing scheme, {threshold} of {shares} shares.")]
+    InvalidScheme {
+        /// Number of shares required to restore the key.
+        threshold: u8,
+        /// Total number of shares.
+        shares: u8,
+    },
+    /// Share has an invalid encoding.
+    #[error("Invalid key share encoding: {0}")]
+    InvalidEncoding(String),
+    /// Share checksum does not match its contents.
+    #[error("Key share checksum mismatch.")]
+    ChecksumMismatch,
+    /// Shares belong to different keys or sharing schemes.
+    #[error("Key shares belong to different backups.")]
+    MixedShares,
+    /// Same share is given several times.
+    #[error("Key share {0} is given several times.")]
+    DuplicateShare(u8),
+    /// Not enough shares to restore the key.
+    #[error("Not enough key shares, {required} are required, but {given} are given.")]
+    NotEnoughShares {
+        /// Number of the required shares.
+        required: u8,
+        /// Number of the given shares.
+        given: usize,
+    },
+    /// Restored bytes are not a valid secret key.
+    #[error("Restored secret key is invalid.")]
+    InvalidKey,
+    /// Share cannot be encrypted or decrypted, for example, the passphrase is wrong.
+    #[error("Unable to encrypt or decrypt the key share: {0}")]
+    Encryption(String),
+}
+
+/// Single Shamir share of the Bitcoin private key.
+///
+/// The share is encoded as a hex string with a checksum, which allows custodians to
+/// detect transcription errors.
+#[derive(Clone, PartialEq, Eq)]
+pub struct KeyShare {
+    /// Random identifier common to all shares of the same backup.
+    pub identifier: u16,
+    /// Number of shares required to restore the key.
+    pub threshold: u8,
+    /// Index of the share, starting from one.
+    pub index: u8,
+    /// Bitcoin network of the key.
+    pub network: Network,
+    /// Whether the public key of the key is compressed.
+    pub compressed: bool,
+    value: [u8; SECRET_LEN],
+}
+
+impl std::fmt::Debug for KeyShare {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("KeyShare")
+            .field("identifier", &self.identifier)
+            .field("threshold", &self.threshold)
+            .field("index", &self.index)
+            .finish()
+    }
+}
+
+impl KeyShare {
+    fn body(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(SHARE_LEN + CHECKSUM_LEN);
+        bytes.extend_from_slice(&self.identifier.to_be_bytes());
+        bytes.push(self.threshold);
+        bytes.push(self.index);
+        bytes.push(match self.network {
+            Network::Bitcoin => 0,
+            Network::Testnet => 1,
+            Network::Regtest => 2,
+        });
+        bytes.push(self.compressed as u8);
+        bytes.extend_from_slice(&self.value);
+        bytes
+    }
+}
+
+impl std::fmt::Display for KeyShare {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let mut bytes = self.body();
+        let checksum = sha256d::Hash::hash(&bytes);
+        bytes.extend_from_slice(&checksum[..CHECKSUM_LEN]);
+        f.write_str(&hex::encode(bytes))
+    }
+}
+
+impl std::str::FromStr for KeyShare {
+    type Err = KeyShareError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bytes =
+            hex::decode(s.trim()).map_err(|e| KeyShareError::InvalidEncoding(e.to_string()))?;
+        if bytes.len() != SHARE_LEN + CHECKSUM_LEN {
+            return Err(KeyShareError::InvalidEncoding(format!(
+                "expected {} bytes, got {}",
+                SHARE_LEN + CHECKSUM_LEN,
+                bytes.len()
+            )));
+        }
+        let (body, checksum) = bytes.split_at(SHARE_LEN);
+        if sha256d::Hash::hash(body)[..CHECKSUM_LEN] != *checksum {
+            return Err(KeyShareError::ChecksumMismatch);
+        }
+
+        let network = match body[4] {
+            0 => Network::Bitcoin,
+            1 => Network::Testnet,
+            2 => Network::Regtest,
+            other => {
+                return Err(KeyShareError::InvalidEncoding(format!(
+                    "unknown network {}",
+                    other
+                )))
+            }
+        };
+        let mut value = [0; SECRET_LEN];
+        value.copy_from_slice(&body[6..]);
+        Ok(Self {
+            identifier: u16::from_be_bytes([body[0], body[1]]),
+            threshold: body[2],
+            index: body[3],
+            network,
+            compressed: body[5] != 0,
+            value,
+        })
+    }
+}
+
+impl_serde_str! { KeyShare }
+
+/// Key share encrypted by the passphrase.
+///
+/// The share is sealed with the scrypt key derivation and the `xsalsa20-poly1305` cipher,
+/// just like the keys of the Exonum nodes, so the share files are safe to keep by
+/// the custodians as is.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct EncryptedKeyShare(ErasedPwBox);
+
+impl KeyShare {
+    /// Encrypts the share by the given passphrase.
+    pub fn encrypt(&self, passphrase: &[u8]) -> Result<EncryptedKeyShare, KeyShareError> {
+        let pwbox = Sodium::build_box(&mut thread_rng())
+            .seal(passphrase, self.to_string().as_bytes())
+            .map_err(|e| KeyShareError::Encryption(e.to_string()))?;
+        key_share_eraser()
+            .erase(&pwbox)
+            .map(EncryptedKeyShare)
+            .map_err(|e| KeyShareError::Encryption(e.to_string()))
+    }
+}
+
+impl EncryptedKeyShare {
+    /// Decrypts the share by the given passphrase.
+    pub fn decrypt(&self, passphrase: &[u8]) -> Result<KeyShare, KeyShareError> {
+        let share = key_share_eraser()
+            .restore(&self.0)
+            .map_err(|e| KeyShareError::Encryption(e.to_string()))?
+            .open(passphrase)
+            .map_err(|e| KeyShareError::Encryption(e.to_string()))?;
+        std::str::from_utf8(&*share)
+            .map_err(|e| KeyShareError::InvalidEncoding(e.to_string()))?
+            .parse()
+    }
+}
+
+fn key_share_eraser() -> Eraser {
+    let mut eraser = Eraser::new();
+    eraser.add_suite::<Sodium>();
+    eraser
+}
+
+/// Splits the private key into `shares` Shamir shares, any `threshold` of which
+/// restore the key. The threshold should be at least two, since each share of
+/// the backup with the single required share is the key itself.
+pub fn split_private_key(
+    private_key: &PrivateKey,
+    threshold: u8,
+    shares: u8,
+) -> Result<Vec<KeyShare>, KeyShareError> {
+    if threshold < MIN_THRESHOLD || threshold > shares {
+        return Err(KeyShareError::InvalidScheme { threshold, shares });
+    }
+
+    let mut rng = thread_rng();
+    let identifier = rng.gen();
+    let secret = &private_key.0.key[..];
+    // Random polynomial of the degree `threshold - 1` for each byte of the secret.
+    let coefficients = (1..threshold)
+        .map(|_| rng.gen::<[u8; SECRET_LEN]>())
+        .collect::<Vec<_>>();
+
+    let shares = (1..=shares)
+        .map(|index| {
+            let mut value = [0; SECRET_LEN];
+            for (i, byte) in value.iter_mut().enumerate() {
+                *byte = coefficients
+                    .iter()
+                    .rev()
+                    .fold(0, |acc, coefficient| gf_mul(acc, index) ^ coefficient[i]);
+                *byte = gf_mul(*byte, index) ^ secret[i];
+            }
+            KeyShare {
+                identifier,
+                threshold,
+                index,
+                network: private_key.0.network,
+                compressed: private_key.0.compressed,
+                value,
+            }
+        })
+        .collect();
+    Ok(shares)
+}
+
+/// Restores the private key from the given shares of the same backup.
+pub fn combine_key_shares(shares: &[KeyShare]) -> Result<PrivateKey, KeyShareError> {
+    let first = shares.first().ok_or(KeyShareError::NotEnoughShares {
+        required: 1,
+        given: 0,
+    })?;
+    let mut indices = BTreeSet::new();
+    for share in shares {
+        if share.identifier != first.identifier
+            || share.threshold != first.threshold
+            || share.network != first.network
+            || share.compressed != first.compressed
+        {
+            return Err(KeyShareError::MixedShares);
+        }
+        if share.index == 0 || !indices.insert(share.index) {
+            return Err(KeyShareError::DuplicateShare(share.index));
+        }
+    }
+    if first.threshold < MIN_THRESHOLD {
+        return Err(KeyShareError::InvalidScheme {
+            threshold: first.threshold,
+            shares: shares.len() as u8,
+        });
+    }
+    if shares.len() < first.threshold as usize {
+        return Err(KeyShareError::NotEnoughShares {
+            required: first.threshold,
+            given: shares.len(),
+        });
+    }
+
+    // Lagrange interpolation of the polynomials at zero.
+    let shares = &shares[..first.threshold as usize];
+    let mut secret = [0; SECRET_LEN];
+    for share in shares {
+        let basis = shares
+            .iter()
+            .filter(|other| other.index != share.index)
+            .fold(1, |acc, other| {
+                gf_mul(acc, gf_div(other.index, other.index ^ share.index))
+            });
+        for (byte, value) in secret.iter_mut().zip(&share.value) {
+            *byte ^= gf_mul(basis, *value);
+        }
+    }
+
+    let key = bitcoin::secp256k1::SecretKey::from_slice(&secret)
+        .map_err(|_| KeyShareError::InvalidKey)?;
+    Ok(PrivateKey(bitcoin::PrivateKey {
+        compressed: first.compressed,
+        network: first.network,
+        key,
+    }))
+}
+
+/// Multiplication in GF(256) modulo the Rijndael polynomial `x^8 + x^4 + x^3 + x + 1`.
+fn gf_mul(mut a: u8, mut b: u8) -> u8 {
+    let mut product = 0;
+    while b != 0 {
+        if b & 1 != 0 {
+            product ^= a;
+        }
+        let carry = a & 0x80 != 0;
+        a <<= 1;
+        if carry {
+            a ^= 0x1b;
+        }
+        b >>= 1;
+    }
+    product
+}
+
+/// Division in GF(256), the divisor should not be zero.
+fn gf_div(a: u8, b: u8) -> u8 {
+    // The multiplicative group has the order 255, so `b^254` is the inverse of `b`.
+    let mut inverse = 1;
+    for _ in 0..254 {
+        inverse = gf_mul(inverse, b);
+    }
+    gf_mul(a, inverse)
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::network::constants::Network;
+
+    use super::{
+        combine_key_shares, split_private_key, EncryptedKeyShare, KeyShare, KeyShareError,
+    };
+    use crate::btc::gen_keypair;
+
+    #[test]
+    fn split_and_combine_key() {
+        let (_, private_key) = gen_keypair(Network::Testnet);
+        let shares = split_private_key(&private_key, 3, 5).unwrap();
+        assert_eq!(shares.len(), 5);
+
+        let restored =
+            combine_key_shares(&[shares[4].clone(), shares[1].clone(), shares[2].clone()]).unwrap();
+        assert_eq!(restored, private_key);
+        assert_eq!(combine_key_shares(&shares).unwrap(), private_key);
+
+        assert_eq!(
+            combine_key_shares(&shares[..2]).unwrap_err(),
+            KeyShareError::NotEnoughShares {
+                required: 3,
+                given: 2
+            }
+        );
+        assert_eq!(
+            combine_key_shares(&[shares[0].clone(), shares[0].clone(), shares[1].clone()])
+                .unwrap_err(),
+            KeyShareError::DuplicateShare(1)
+        );
+
+        // Each share of the 1-of-n backup would be the key itself.
+        assert_eq!(
+            split_private_key(&private_key, 1, 3).unwrap_err(),
+            KeyShareError::InvalidScheme {
+                threshold: 1,
+                shares: 3
+            }
+        );
+        assert_eq!(
+            split_private_key(&private_key, 4, 3).unwrap_err(),
+            KeyShareError::InvalidScheme {
+                threshold: 4,
+                shares: 3
+            }
+        );
+    }
+
+    #[test]
+    fn key_share_encoding() {
+        let (_, private_key) = gen_keypair(Network::Bitcoin);
+        let share = split_private_key(&private_key, 2, 3).unwrap().remove(0);
+
+        let encoded = share.to_string();
+        assert_eq!(encoded.parse::<KeyShare>().unwrap(), share);
+
+        let mut corrupted = encoded.into_bytes();
+        corrupted[20] = if corrupted[20] == b'0' { b'1' } else { b'0' };
+        let corrupted = String::from_utf8(corrupted).unwrap();
+        assert_eq!(
+            corrupted.parse::<KeyShare>().unwrap_err(),
+            KeyShareError::ChecksumMismatch
+        );
+    }
+
+    #[test]
+    fn key_share_encryption() {
+        let (_, private_key) = gen_keypair(Network::Testnet);
+        let share = split_private_key(&private_key, 2, 3).unwrap().remove(0);
+
+        let encrypted = share.encrypt(b"correct horse").unwrap();
+        let json = serde_json::to_string(&encrypted).unwrap();
+        assert!(!json.contains(&share.to_string()));
+
+        let encrypted: EncryptedKeyShare = serde_json::from_str(&json).unwrap();
+        assert_eq!(encrypted.decrypt(b"correct horse").unwrap(), share);
+        match encrypted.decrypt(b"battery staple").unwrap_err() {
+            KeyShareError::Encryption(_) => {}
+            other => panic!("Unexpected error: {}", other),
+        }
+    }
+}
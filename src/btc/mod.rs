@@ -17,7 +17,12 @@
 pub use btc_transaction_utils::test_data::{secp_gen_keypair, secp_gen_keypair_with_rng};
 
 pub use self::{
-    payload::Payload,
+    funding::FundingTemplate,
+    key_shares::{
+        combine_key_shares, split_private_key, EncryptedKeyShare, KeyShare, KeyShareError,
+    },
+    payload::{Payload, PAYLOAD_VERSION, SUPPORTED_PAYLOAD_VERSIONS},
+    rescue::{RescueError, RescueTransaction},
     transaction::{BtcAnchoringTransactionBuilder, BuilderError, Transaction},
 };
 
@@ -32,7 +37,10 @@ use serde_derive::{Deserialize, Serialize};
 #[macro_use]
 mod macros;
 
+pub(crate) mod funding;
+pub(crate) mod key_shares;
 pub(crate) mod payload;
+pub(crate) mod rescue;
 pub(crate) mod transaction;
 
 /// Bitcoin ECDSA private key wrapper.
@@ -21,6 +21,8 @@ use bitcoin::blockdata::{
 use byteorder::{ByteOrder, LittleEndian};
 use serde_derive::{Deserialize, Serialize};
 
+use std::ops::RangeInclusive;
+
 use super::Sha256d;
 
 const PAYLOAD_PREFIX: &[u8] = b"EXONUM";
@@ -28,6 +30,15 @@ const PAYLOAD_HEADER_LEN: usize = 8;
 const PAYLOAD_V1: u8 = 1;
 const PAYLOAD_V1_KIND_REGULAR: u8 = 0;
 const PAYLOAD_V1_KIND_RECOVER: u8 = 1;
+const PAYLOAD_V1_KIND_AUXILIARY: u8 = 2;
+const PAYLOAD_V1_KIND_CONSENSUS: u8 = 3;
+
+/// Version of the payload format produced by the service.
+pub const PAYLOAD_VERSION: u8 = PAYLOAD_V1;
+/// Versions of the payload format which can be parsed by this version of the service.
+/// The verifiers should reject the anchoring transactions with the payload versions
+/// out of this range instead of treating them as malformed ones.
+pub const SUPPORTED_PAYLOAD_VERSIONS: RangeInclusive<u8> = PAYLOAD_V1..=PAYLOAD_V1;
 
 /// Anchoring transaction payload.
 ///
@@ -37,12 +48,17 @@ const PAYLOAD_V1_KIND_RECOVER: u8 = 1;
 /// |-----------------------|---------------------------------------------------|
 /// | 0..6                  | ASCII-encoded prefix `EXONUM`                     |
 /// | 6                     | Version byte, currently is 1                      |
-/// | 7                     | Payload kind: (0 is regular, 1 is recover)        |
+/// | 7                     | Payload kind: (0 is regular, 1 is recover,        |
+/// |                       | 2 is auxiliary, 3 is consensus)                   |
 /// | 8..16                 | Block height                                      |
 /// | 16..48                | Block hash                                        |
 /// | 48..80 (Optionally)   | Txid of previous tx chain (only for recover kind) |
+/// | 48..80 (Optionally)   | Auxiliary state root (only for auxiliary kind)    |
+/// | 48..80 (Optionally)   | Consensus config hash (only for consensus kind)   |
 ///
-/// In this way the length of `regular` payload is 48, and for `recover` is 80.
+/// In this way the length of `regular` payload is 48, and for the other kinds is 80.
+/// The payload contains at most one of the optional fields, since the `OP_RETURN` data
+/// is limited by 80 bytes.
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Payload {
     /// Anchored block height.
@@ -51,12 +67,19 @@ pub struct Payload {
     pub block_hash: Hash,
     /// `Txid` of previous transactions chain if it has been lost.
     pub prev_tx_chain: Option<Sha256d>,
+    /// Merkle root of the state hashes of the auxiliary services at the anchored height.
+    pub auxiliary_root: Option<Hash>,
+    /// Hash of the Exonum consensus configuration at the anchored height.
+    #[serde(default)]
+    pub consensus_config_hash: Option<Hash>,
 }
 
 #[derive(Debug)]
 enum PayloadV1 {
     Regular(Height, Hash),
     Recover(Height, Hash, Sha256d),
+    Auxiliary(Height, Hash, Hash),
+    Consensus(Height, Hash, Hash),
 }
 
 #[derive(Debug, Default)]
@@ -64,36 +87,43 @@ pub struct PayloadV1Builder {
     block_hash: Option<Hash>,
     block_height: Option<Height>,
     prev_tx_chain: Option<Sha256d>,
+    auxiliary_root: Option<Hash>,
+    consensus_config_hash: Option<Hash>,
 }
 
 pub type PayloadBuilder = PayloadV1Builder;
 
 #[cfg_attr(feature = "cargo-clippy", allow(clippy::len_without_is_empty))]
 impl PayloadV1 {
+    /// Reads the payload from the given bytes. Returns `None` if the bytes have
+    /// the unknown kind or the length does not match the kind.
     fn read(bytes: &[u8]) -> Option<Self> {
-        let kind = bytes[0];
-        let data = &bytes[1..];
+        let (&kind, data) = bytes.split_first()?;
+        let expected_len = match kind {
+            PAYLOAD_V1_KIND_REGULAR => 40,
+            PAYLOAD_V1_KIND_RECOVER | PAYLOAD_V1_KIND_AUXILIARY | PAYLOAD_V1_KIND_CONSENSUS => 72,
+            _ => return None,
+        };
+        if data.len() != expected_len {
+            return None;
+        }
+
+        let block_height = Height(LittleEndian::read_u64(&data[0..8]));
+        let block_hash = Hash::from_slice(&data[8..40])?;
         match kind {
-            PAYLOAD_V1_KIND_REGULAR => {
-                if data.len() != 40 {
-                    return None;
-                }
-
-                let block_height = LittleEndian::read_u64(&data[0..8]);
-                let block_hash = Hash::from_slice(&data[8..40]).unwrap();
-                Some(PayloadV1::Regular(Height(block_height), block_hash))
-            }
+            PAYLOAD_V1_KIND_REGULAR => Some(PayloadV1::Regular(block_height, block_hash)),
             PAYLOAD_V1_KIND_RECOVER => {
-                if data.len() != 72 {
-                    return None;
-                }
-
-                let block_height = LittleEndian::read_u64(&data[0..8]);
-                let block_hash = Hash::from_slice(&data[8..40]).unwrap();
-                let txid = Sha256d::from_slice(&data[40..72]).unwrap();
-                Some(PayloadV1::Recover(Height(block_height), block_hash, txid))
+                let txid = Sha256d::from_slice(&data[40..72])?;
+                Some(PayloadV1::Recover(block_height, block_hash, txid))
+            }
+            PAYLOAD_V1_KIND_AUXILIARY => {
+                let root = Hash::from_slice(&data[40..72])?;
+                Some(PayloadV1::Auxiliary(block_height, block_hash, root))
+            }
+            _ => {
+                let config_hash = Hash::from_slice(&data[40..72])?;
+                Some(PayloadV1::Consensus(block_height, block_hash, config_hash))
             }
-            _ => None,
         }
     }
 
@@ -114,13 +144,23 @@ impl PayloadV1 {
                 buf[8..40].copy_from_slice(hash.as_ref());
                 buf[40..72].copy_from_slice(&txid.0[..]);
             }
+            PayloadV1::Auxiliary(height, hash, root) => {
+                LittleEndian::write_u64(&mut buf[0..8], height.0);
+                buf[8..40].copy_from_slice(hash.as_ref());
+                buf[40..72].copy_from_slice(root.as_ref());
+            }
+            PayloadV1::Consensus(height, hash, config_hash) => {
+                LittleEndian::write_u64(&mut buf[0..8], height.0);
+                buf[8..40].copy_from_slice(hash.as_ref());
+                buf[40..72].copy_from_slice(config_hash.as_ref());
+            }
         };
     }
 
     fn len(&self) -> usize {
         match *self {
             PayloadV1::Regular(..) => 40,
-            PayloadV1::Recover(..) => 72,
+            PayloadV1::Recover(..) | PayloadV1::Auxiliary(..) | PayloadV1::Consensus(..) => 72,
         }
     }
 
@@ -128,6 +168,8 @@ impl PayloadV1 {
         match *self {
             PayloadV1::Regular(..) => PAYLOAD_V1_KIND_REGULAR,
             PayloadV1::Recover(..) => PAYLOAD_V1_KIND_RECOVER,
+            PayloadV1::Auxiliary(..) => PAYLOAD_V1_KIND_AUXILIARY,
+            PayloadV1::Consensus(..) => PAYLOAD_V1_KIND_CONSENSUS,
         }
     }
 
@@ -152,6 +194,8 @@ impl PayloadV1Builder {
             block_hash: None,
             block_height: None,
             prev_tx_chain: None,
+            auxiliary_root: None,
+            consensus_config_hash: None,
         }
     }
 
@@ -170,13 +214,34 @@ impl PayloadV1Builder {
         self
     }
 
+    /// Sets the auxiliary state root, which is ignored for the recover payload.
+    pub fn auxiliary_root(mut self, root: Option<Hash>) -> Self {
+        self.auxiliary_root = root;
+        self
+    }
+
+    /// Sets the hash of the consensus configuration, which is ignored for the recover
+    /// and auxiliary payloads.
+    pub fn consensus_config_hash(mut self, config_hash: Option<Hash>) -> Self {
+        self.consensus_config_hash = config_hash;
+        self
+    }
+
     pub fn into_script(self) -> Script {
         let block_height = self.block_height.expect("Block height is not set");
         let block_hash = self.block_hash.expect("Block hash is not set");
 
-        let payload = match self.prev_tx_chain {
-            Some(txid) => PayloadV1::Recover(block_height, block_hash, txid),
-            None => PayloadV1::Regular(block_height, block_hash),
+        let payload = match (
+            self.prev_tx_chain,
+            self.auxiliary_root,
+            self.consensus_config_hash,
+        ) {
+            (Some(txid), ..) => PayloadV1::Recover(block_height, block_hash, txid),
+            (None, Some(root), _) => PayloadV1::Auxiliary(block_height, block_hash, root),
+            (None, None, Some(config_hash)) => {
+                PayloadV1::Consensus(block_height, block_hash, config_hash)
+            }
+            (None, None, None) => PayloadV1::Regular(block_height, block_hash),
         };
         payload.into_script()
     }
@@ -184,35 +249,46 @@ impl PayloadV1Builder {
 
 impl Payload {
     /// Tries to extract payload from given `Script`.
+    ///
+    /// The parser is total: it returns `None` for any script, which is not the `OP_RETURN`
+    /// script with the single push of the well-formed payload, including the truncated
+    /// and oversized payloads and payloads of the unknown versions.
     pub fn from_script(script: &Script) -> Option<Self> {
+        let bytes = Self::payload_bytes(script)?;
+        // Parse metadata
+        let version = bytes[PAYLOAD_PREFIX.len()];
+        match version {
+            PAYLOAD_V1 => PayloadV1::read(&bytes[PAYLOAD_PREFIX.len() + 1..]).map(Self::from),
+            _ => None,
+        }
+    }
+
+    /// Reads the payload format version from the given `Script` without parsing the payload
+    /// itself, so the payloads of the unknown versions can be distinguished from the
+    /// malformed ones.
+    pub fn read_version(script: &Script) -> Option<u8> {
+        Self::payload_bytes(script).map(|bytes| bytes[PAYLOAD_PREFIX.len()])
+    }
+
+    /// Returns the payload bytes with the valid header pushed by the `OP_RETURN` script.
+    fn payload_bytes(script: &Script) -> Option<&[u8]> {
         let mut instructions = script.iter(true);
-        instructions
-            .next()
-            .and_then(|instr| {
-                if instr == Instruction::Op(OP_RETURN) {
-                    instructions.next()
-                } else {
-                    None
-                }
-            })
-            .and_then(|instr| {
-                if let Instruction::PushBytes(bytes) = instr {
-                    if bytes.len() < PAYLOAD_HEADER_LEN {
-                        return None;
-                    }
-                    if &bytes[0..6] != PAYLOAD_PREFIX {
-                        return None;
-                    }
-                    // Parse metadata
-                    let version = bytes[6];
-                    match version {
-                        PAYLOAD_V1 => PayloadV1::read(&bytes[7..]).map(Self::from),
-                        _ => None,
-                    }
-                } else {
-                    None
-                }
-            })
+        if instructions.next()? != Instruction::Op(OP_RETURN) {
+            return None;
+        }
+        let bytes = match instructions.next()? {
+            Instruction::PushBytes(bytes) => bytes,
+            _ => return None,
+        };
+        // The payload must be the only data of the script.
+        if instructions.next().is_some() {
+            return None;
+        }
+
+        if bytes.len() < PAYLOAD_HEADER_LEN || !bytes.starts_with(PAYLOAD_PREFIX) {
+            return None;
+        }
+        Some(bytes)
     }
 }
 
@@ -223,11 +299,29 @@ impl From<PayloadV1> for Payload {
                 block_height: height,
                 block_hash: hash,
                 prev_tx_chain: None,
+                auxiliary_root: None,
+                consensus_config_hash: None,
             },
             PayloadV1::Recover(height, hash, txid) => Self {
                 block_height: height,
                 block_hash: hash,
                 prev_tx_chain: Some(txid),
+                auxiliary_root: None,
+                consensus_config_hash: None,
+            },
+            PayloadV1::Auxiliary(height, hash, root) => Self {
+                block_height: height,
+                block_hash: hash,
+                prev_tx_chain: None,
+                auxiliary_root: Some(root),
+                consensus_config_hash: None,
+            },
+            PayloadV1::Consensus(height, hash, config_hash) => Self {
+                block_height: height,
+                block_hash: hash,
+                prev_tx_chain: None,
+                auxiliary_root: None,
+                consensus_config_hash: Some(config_hash),
             },
         }
     }
@@ -243,7 +337,7 @@ mod tests {
 
     use crate::btc::Sha256d;
 
-    use super::{Payload, PayloadBuilder};
+    use super::{Payload, PayloadBuilder, PAYLOAD_VERSION};
 
     trait HexValue {
         fn from_hex(hex: impl AsRef<[u8]>) -> Self;
@@ -326,6 +420,55 @@ mod tests {
         assert_eq!(payload.prev_tx_chain, Some(prev_txid));
     }
 
+    #[test]
+    fn test_payload_auxiliary_roundtrip() {
+        let block_hash = hash(&[]);
+        let root = hash(&[1, 2, 3]);
+        let payload_script = PayloadBuilder::new()
+            .block_hash(block_hash)
+            .block_height(Height(1234))
+            .auxiliary_root(Some(root))
+            .into_script();
+        // The payload should fit into the standard `OP_RETURN` size limit.
+        assert_eq!(payload_script.len(), 83);
+
+        let payload = Payload::from_script(&payload_script).unwrap();
+        assert_eq!(payload.block_hash, block_hash);
+        assert_eq!(payload.block_height, Height(1234));
+        assert_eq!(payload.prev_tx_chain, None);
+        assert_eq!(payload.auxiliary_root, Some(root));
+    }
+
+    #[test]
+    fn test_payload_consensus_roundtrip() {
+        let block_hash = hash(&[]);
+        let config_hash = hash(&[4, 5, 6]);
+        let payload_script = PayloadBuilder::new()
+            .block_hash(block_hash)
+            .block_height(Height(1234))
+            .consensus_config_hash(Some(config_hash))
+            .into_script();
+        assert_eq!(payload_script.len(), 83);
+
+        let payload = Payload::from_script(&payload_script).unwrap();
+        assert_eq!(payload.block_hash, block_hash);
+        assert_eq!(payload.block_height, Height(1234));
+        assert_eq!(payload.auxiliary_root, None);
+        assert_eq!(payload.consensus_config_hash, Some(config_hash));
+
+        // The auxiliary root takes precedence over the consensus configuration hash.
+        let root = hash(&[1, 2, 3]);
+        let payload_script = PayloadBuilder::new()
+            .block_hash(block_hash)
+            .block_height(Height(1234))
+            .auxiliary_root(Some(root))
+            .consensus_config_hash(Some(config_hash))
+            .into_script();
+        let payload = Payload::from_script(&payload_script).unwrap();
+        assert_eq!(payload.auxiliary_root, Some(root));
+        assert_eq!(payload.consensus_config_hash, None);
+    }
+
     #[test]
     fn test_payload_incorrect_deserialize() {
         // Payload from old anchoring transaction
@@ -336,6 +479,37 @@ mod tests {
         assert_eq!(Payload::from_script(&payload_script), None);
     }
 
+    #[test]
+    fn test_payload_version() {
+        let payload_script = PayloadBuilder::new()
+            .block_hash(hash(&[]))
+            .block_height(Height(1234))
+            .into_script();
+        assert_eq!(
+            Payload::read_version(&payload_script),
+            Some(PAYLOAD_VERSION)
+        );
+
+        // Payload of the future version with the same header.
+        let mut bytes = payload_script[..].to_vec();
+        bytes[8] = 2;
+        let payload_script = Script::from(bytes);
+        assert_eq!(Payload::read_version(&payload_script), Some(2));
+        assert_eq!(Payload::from_script(&payload_script), None);
+    }
+
+    #[test]
+    fn test_payload_trailing_data() {
+        let mut bytes = PayloadBuilder::new()
+            .block_hash(hash(&[]))
+            .block_height(Height(1234))
+            .into_script()[..]
+            .to_vec();
+        // OP_TRUE after the payload push.
+        bytes.push(0x51);
+        assert_eq!(Payload::from_script(&Script::from(bytes)), None);
+    }
+
     #[test]
     fn test_payload_non_op_return() {
         // Payload from old anchoring transaction
@@ -0,0 +1,266 @@
+// Copyright 2019 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Emergency spend of the anchoring output without the Exonum network.
+
+use bitcoin::blockdata::{
+    script::Script,
+    transaction::{self, OutPoint, TxIn, TxOut},
+};
+use btc_transaction_utils::{multisig::RedeemScript, p2wsh::InputSigner, TxInRef};
+use thiserror::Error;
+
+use std::collections::BTreeMap;
+
+use super::{Address, BuilderError, InputSignature, PrivateKey, PublicKey, Transaction};
+
+/// Rescue transaction errors.
+#[derive(Debug, Error)]
+pub enum RescueError {
+    /// Unable to construct the rescue transaction.
+    #[error("{0}")]
+    Builder(#[from] BuilderError),
+    /// Bitcoin key is absent in the redeem script.
+    #[error("Bitcoin key {0} is absent in the redeem script.")]
+    UnknownKey(PublicKey),
+    /// Signature does not match the rescue transaction.
+    #[error("Signature of the Bitcoin key {key} is invalid: {description}")]
+    InvalidSignature {
+        /// Bitcoin key of the signature.
+        key: PublicKey,
+        /// Description of the verification failure.
+        description: String,
+    },
+    /// Not enough signatures to spend the anchoring output.
+    #[error("Not enough signatures, {required} are required, but {given} are given.")]
+    NotEnoughSignatures {
+        /// Number of the required signatures.
+        required: usize,
+        /// Number of the given valid signatures.
+        given: usize,
+    },
+}
+
+/// Transaction, which spends the anchoring output to the given address without the
+/// participation of the anchoring service.
+///
+/// The transaction is created by each anchoring node independently from the same
+/// anchoring transaction, destination address and fee, so the signatures made by the
+/// nodes offline can be combined into the single raw transaction. Such a transaction
+/// does not contain the anchoring payload, thus it should be used only if the Exonum
+/// network itself is unable to continue the anchoring.
+#[derive(Debug)]
+pub struct RescueTransaction {
+    redeem_script: RedeemScript,
+    anchoring_tx: Transaction,
+    transaction: Transaction,
+}
+
+impl RescueTransaction {
+    /// Creates the unsigned rescue transaction, which spends the output of the given
+    /// anchoring transaction to the destination address and pays the given fee per byte.
+    pub fn new(
+        redeem_script: RedeemScript,
+        anchoring_tx: Transaction,
+        destination: &Address,
+        fee: u64,
+    ) -> Result<Self, RescueError> {
+        let (out_index, out) = anchoring_tx
+            .find_out(&redeem_script.as_ref().to_v0_p2wsh())
+            .ok_or(BuilderError::UnsuitableOutput)?;
+        let balance = out.value;
+
+        let mut transaction = Transaction::from(transaction::Transaction {
+            version: 2,
+            lock_time: 0,
+            input: vec![TxIn {
+                previous_output: OutPoint {
+                    txid: anchoring_tx.0.txid(),
+                    vout: out_index as u32,
+                },
+                script_sig: Script::default(),
+                sequence: 0xFFFF_FFFF,
+                witness: Vec::default(),
+            }],
+            output: vec![TxOut {
+                value: balance,
+                script_pubkey: destination.0.script_pubkey(),
+            }],
+        });
+        // The fee is computed in the same way as for the anchoring transactions.
+        let size_in_bytes = bitcoin::consensus::serialize(&transaction.0).len() as u64;
+        let total_fee = fee * size_in_bytes;
+        if total_fee >= balance {
+            return Err(BuilderError::InsufficientFunds { total_fee, balance }.into());
+        }
+        transaction.0.output[0].value = balance - total_fee;
+
+        Ok(Self {
+            redeem_script,
+            anchoring_tx,
+            transaction,
+        })
+    }
+
+    /// Returns the unsigned rescue transaction.
+    pub fn unsigned_transaction(&self) -> &Transaction {
+        &self.transaction
+    }
+
+    /// Signs the rescue transaction input with the given private key.
+    pub fn sign(&self, private_key: &PrivateKey) -> Result<InputSignature, RescueError> {
+        let context = bitcoin::secp256k1::Secp256k1::new();
+        let public_key = PublicKey(private_key.0.public_key(&context));
+        let mut signer = InputSigner::new(self.redeem_script.clone());
+        self.position(&public_key)?;
+        signer
+            .sign_input(
+                TxInRef::new(self.transaction.as_ref(), 0),
+                self.anchoring_tx.as_ref(),
+                &private_key.0.key,
+            )
+            .map(InputSignature)
+            .map_err(|e| RescueError::InvalidSignature {
+                key: public_key,
+                description: e.to_string(),
+            })
+    }
+
+    /// Verifies the given signatures and assembles the signed rescue transaction. Extra
+    /// signatures beyond the redeem script quorum are ignored.
+    pub fn finalize(
+        self,
+        signatures: impl IntoIterator<Item = (PublicKey, InputSignature)>,
+    ) -> Result<Transaction, RescueError> {
+        let signer = InputSigner::new(self.redeem_script.clone());
+        // Signatures are ordered by the positions of the corresponding keys in the
+        // redeem script.
+        let mut ordered = BTreeMap::new();
+        for (public_key, signature) in signatures {
+            let position = self.position(&public_key)?;
+            signer
+                .verify_input(
+                    TxInRef::new(self.transaction.as_ref(), 0),
+                    self.anchoring_tx.as_ref(),
+                    &public_key.0,
+                    signature.as_ref(),
+                )
+                .map_err(|e| RescueError::InvalidSignature {
+                    key: public_key,
+                    description: e.to_string(),
+                })?;
+            ordered.insert(position, signature);
+        }
+
+        let required = self.redeem_script.content().quorum;
+        if ordered.len() < required {
+            return Err(RescueError::NotEnoughSignatures {
+                required,
+                given: ordered.len(),
+            });
+        }
+
+        let mut transaction = self.transaction;
+        signer.spend_input(
+            &mut transaction.0.input[0],
+            ordered
+                .values()
+                .take(required)
+                .map(|signature| signature.0.clone()),
+        );
+        Ok(transaction)
+    }
+
+    fn position(&self, public_key: &PublicKey) -> Result<usize, RescueError> {
+        self.redeem_script
+            .content()
+            .public_keys
+            .iter()
+            .position(|key| key == &public_key.0)
+            .ok_or(RescueError::UnknownKey(*public_key))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::network::constants::Network;
+    use btc_transaction_utils::{multisig::RedeemScriptBuilder, p2wsh};
+
+    use super::{RescueError, RescueTransaction};
+    use crate::{btc, test_helpers::create_fake_funding_transaction};
+
+    #[test]
+    fn rescue_transaction_finalize() {
+        let keypairs = (0..4)
+            .map(|_| btc::gen_keypair(Network::Testnet))
+            .collect::<Vec<_>>();
+        let redeem_script = RedeemScriptBuilder::with_public_keys(keypairs.iter().map(|x| x.0 .0))
+            .quorum(3)
+            .to_script()
+            .unwrap();
+        let address = p2wsh::address(&redeem_script, Network::Testnet).into();
+        let anchoring_tx = create_fake_funding_transaction(&address, 100_000);
+        let destination = btc::gen_keypair(Network::Testnet).0;
+        let destination = btc::Address(bitcoin::Address::p2wpkh(&destination.0, Network::Testnet));
+
+        let rescue = || {
+            RescueTransaction::new(
+                redeem_script.clone(),
+                anchoring_tx.clone(),
+                &destination,
+                10,
+            )
+            .unwrap()
+        };
+        // Each node creates the same transaction offline.
+        let signatures = keypairs
+            .iter()
+            .map(|(public_key, private_key)| (*public_key, rescue().sign(private_key).unwrap()))
+            .collect::<Vec<_>>();
+
+        let err = rescue()
+            .finalize(signatures.iter().take(2).cloned())
+            .unwrap_err();
+        match err {
+            RescueError::NotEnoughSignatures { required, given } => {
+                assert_eq!((required, given), (3, 2))
+            }
+            e => panic!("Unexpected error: {}", e),
+        }
+
+        let unknown_key = btc::gen_keypair(Network::Testnet).0;
+        let err = rescue()
+            .finalize(vec![(unknown_key, signatures[0].1.clone())])
+            .unwrap_err();
+        match err {
+            RescueError::UnknownKey(key) => assert_eq!(key, unknown_key),
+            e => panic!("Unexpected error: {}", e),
+        }
+
+        let err = rescue()
+            .finalize(vec![(signatures[1].0, signatures[0].1.clone())])
+            .unwrap_err();
+        match err {
+            RescueError::InvalidSignature { key, .. } => assert_eq!(key, signatures[1].0),
+            e => panic!("Unexpected error: {}", e),
+        }
+
+        let tx = rescue().finalize(signatures.into_iter().rev()).unwrap();
+        assert_eq!(tx.0.input[0].previous_output.txid, anchoring_tx.0.txid());
+        // Witness contains the empty item, three signatures and the redeem script.
+        assert_eq!(tx.0.input[0].witness.len(), 5);
+        assert_eq!(tx.0.output[0].script_pubkey, destination.0.script_pubkey());
+        assert!(tx.0.output[0].value < 100_000);
+    }
+}
@@ -13,6 +13,7 @@ use bitcoin::blockdata::{
     script::Script,
     transaction::{self, OutPoint, TxIn, TxOut},
 };
+use bitcoin_hashes::Hash as BitcoinHash;
 use btc_transaction_utils::multisig::RedeemScript;
 use derive_more::{From, Into};
 use thiserror::Error;
@@ -57,6 +58,13 @@ impl Transaction {
         Payload::from_script(&out.script_pubkey)
     }
 
+    /// Return the payload format version for the transaction if it is the anchoring
+    /// transaction, even if the version is unknown.
+    pub fn payload_version(&self) -> Option<u8> {
+        let out = self.0.output.get(1)?;
+        Payload::read_version(&out.script_pubkey)
+    }
+
     /// Return the complete meta information for the transaction
     /// if it is the anchoring transaction.
     pub fn anchoring_metadata(&self) -> Option<(&Script, Payload)> {
@@ -73,6 +81,16 @@ impl Transaction {
 }
 
 /// Builder for the anchoring transactions.
+///
+/// The previous anchoring transaction is always spent by the first input, followed by
+/// the funding inputs in the order in which they were added. If the BIP-69 input ordering
+/// is enabled, the funding inputs are sorted according to [BIP-69] instead, so that
+/// the proposal does not depend on the order of the funding transactions. Only the inputs
+/// are covered: the outputs keep the fixed layout, i.e. the change output to the anchoring
+/// address goes first, then the payload output. This layout is used to recognize
+/// the anchoring transactions, thus the full BIP-69 ordering is not applied.
+///
+/// [BIP-69]: https://github.com/bitcoin/bips/blob/master/bip-0069.mediawiki
 #[derive(Debug)]
 pub struct BtcAnchoringTransactionBuilder {
     script_pubkey: Script,
@@ -81,7 +99,12 @@ pub struct BtcAnchoringTransactionBuilder {
     recovery_tx: Option<Sha256d>,
     additional_funds: Vec<(usize, Transaction)>,
     fee: Option<u64>,
+    reserve: u64,
+    dust_threshold: u64,
+    bip69_input_ordering: bool,
     payload: Option<(Height, Hash)>,
+    auxiliary_root: Option<Hash>,
+    consensus_config_hash: Option<Hash>,
 }
 
 /// Anchoring transaction builder errors.
@@ -98,6 +121,29 @@ pub enum BuilderError {
         /// Available balance.
         balance: u64,
     },
+    /// Anchoring transaction spends the funds reserved in the anchoring wallet.
+    #[error(
+        "Anchoring transaction spends the reserved funds, \
+        remaining balance is {remaining}, reserve is {reserve}"
+    )]
+    ReserveReached {
+        /// Remaining balance after the anchoring transaction.
+        remaining: u64,
+        /// Reserved balance.
+        reserve: u64,
+    },
+    /// Change output of the anchoring transaction is below the dust threshold. The change
+    /// output carries the anchoring chain, so it is never folded into the fee.
+    #[error(
+        "Change output of the anchoring transaction is below the dust threshold, \
+        remaining balance is {remaining}, dust threshold is {dust_threshold}"
+    )]
+    DustChange {
+        /// Remaining balance after the anchoring transaction.
+        remaining: u64,
+        /// Dust threshold.
+        dust_threshold: u64,
+    },
     /// At least one input should be provided.
     #[error("At least one input should be provided.")]
     NoInputs,
@@ -112,14 +158,27 @@ pub enum BuilderError {
 impl BtcAnchoringTransactionBuilder {
     /// Creates a new btc anchoring transaction builder for the given redeem script.
     pub fn new(redeem_script: &RedeemScript) -> BtcAnchoringTransactionBuilder {
+        Self::with_script_pubkey(redeem_script.as_ref().to_v0_p2wsh())
+    }
+
+    /// Creates a new btc anchoring transaction builder for the given output script.
+    ///
+    /// Useful if the redeem script of the anchoring address is unknown, for example
+    /// when replaying the historical anchoring transactions.
+    pub fn with_script_pubkey(script_pubkey: Script) -> BtcAnchoringTransactionBuilder {
         Self {
-            script_pubkey: redeem_script.as_ref().to_v0_p2wsh(),
+            script_pubkey,
             transit_to: None,
             prev_tx: None,
             recovery_tx: None,
             additional_funds: Vec::default(),
             fee: None,
+            reserve: 0,
+            dust_threshold: 0,
+            bip69_input_ordering: false,
             payload: None,
+            auxiliary_root: None,
+            consensus_config_hash: None,
         }
     }
 
@@ -161,14 +220,50 @@ impl BtcAnchoringTransactionBuilder {
         self.fee = Some(fee);
     }
 
+    /// Sets the minimal balance in satoshis which should remain in the anchoring wallet.
+    pub fn reserve(&mut self, reserve: u64) {
+        self.reserve = reserve;
+    }
+
+    /// Sets the minimal value in satoshis of the change output, below which the output
+    /// is considered as dust and cannot be relayed by the Bitcoin nodes.
+    pub fn dust_threshold(&mut self, dust_threshold: u64) {
+        self.dust_threshold = dust_threshold;
+    }
+
+    /// Enables the sorting of the funding inputs according to BIP-69. The input spending
+    /// the previous anchoring transaction stays first, and the outputs are not reordered.
+    pub fn bip69_input_ordering(&mut self, enabled: bool) {
+        self.bip69_input_ordering = enabled;
+    }
+
     /// Sets the anchoring transaction payload.
     pub fn payload(&mut self, block_height: Height, block_hash: Hash) {
         self.payload = Some((block_height, block_hash));
     }
 
+    /// Sets the Merkle root of the auxiliary services state hashes, which is included
+    /// into the payload unless the transaction recovers the anchoring chain.
+    pub fn auxiliary_root(&mut self, root: Hash) {
+        self.auxiliary_root = Some(root);
+    }
+
+    /// Sets the hash of the consensus configuration, which is included into the payload
+    /// unless the payload already contains the recovery txid or the auxiliary root.
+    pub fn consensus_config_hash(&mut self, config_hash: Hash) {
+        self.consensus_config_hash = Some(config_hash);
+    }
+
     /// Finalizes the anchoring transaction and returns
     /// it with the list of input transactions.
     pub fn create(mut self) -> Result<(Transaction, Vec<Transaction>), BuilderError> {
+        // Sort funding inputs according to BIP-69 to make the result independent
+        // of the order in which they were added.
+        if self.bip69_input_ordering {
+            self.additional_funds
+                .sort_by_key(|(out_index, tx)| bip69_input_key(tx, *out_index));
+        }
+
         // Creates transaction inputs.
         let (input, input_transactions, balance) = {
             let mut input = Vec::new();
@@ -208,6 +303,8 @@ impl BtcAnchoringTransactionBuilder {
             .block_hash(block_hash)
             .block_height(block_height)
             .prev_tx_chain(self.recovery_tx)
+            .auxiliary_root(self.auxiliary_root)
+            .consensus_config_hash(self.consensus_config_hash)
             .into_script();
         let output = match self.transit_to {
             Some(script) => script,
@@ -240,12 +337,36 @@ impl BtcAnchoringTransactionBuilder {
         if total_fee > balance {
             return Err(BuilderError::InsufficientFunds { total_fee, balance });
         }
+        // Check that the reserved funds remain in the anchoring wallet.
+        let remaining = balance - total_fee;
+        if remaining < self.reserve {
+            return Err(BuilderError::ReserveReached {
+                remaining,
+                reserve: self.reserve,
+            });
+        }
+        // The change output carries the anchoring chain, so it cannot be folded into
+        // the fee; the dust output would make the next anchoring transaction unrelayable.
+        if remaining < self.dust_threshold {
+            return Err(BuilderError::DustChange {
+                remaining,
+                dust_threshold: self.dust_threshold,
+            });
+        }
         // Set the corresponding fee.
-        transaction.0.output[0].value -= total_fee;
+        transaction.0.output[0].value = remaining;
         Ok((transaction, input_transactions))
     }
 }
 
+/// Returns the BIP-69 sort key of the input spending the given transaction output,
+/// i.e. the previous transaction hash in the reversed byte order and the output index.
+fn bip69_input_key(tx: &Transaction, out_index: usize) -> ([u8; 32], usize) {
+    let mut txid = tx.0.txid().into_inner();
+    txid.reverse();
+    (txid, out_index)
+}
+
 #[cfg(test)]
 mod tests {
     use exonum::crypto::Hash;
@@ -532,6 +653,78 @@ mod tests {
         assert_eq!(out_1.value, 0);
     }
 
+    #[test]
+    fn test_anchoring_transaction_builder_inputs_order() {
+        let funding_tx0: Transaction = Transaction::from_hex(
+            "02000000000101b651818fe3855d0d5d74de1cf72b56503c16f808519440e842b6\
+             dc2dd570c4930100000000feffffff02deaa7b0000000000160014923904449829\
+             cd865cdfb72abdba0806ce9e48911027000000000000220020e9bb049fdff8f8d3\
+             b33b7335978b1dbb268833a32a69906f9e500e4103151bef02483045022100ddc7\
+             eb1193529a8d0e48cf24f536d5fbb5de3b67d2f56c98190ea8585d58a156022075\
+             e33981f1a7d78ce2915402d4b9b38b8d5311e0aef2e3ccf9284d2ce602968d0121\
+             021d0478acd223fb9b2ad7485f06f12914a1b7effc78390a08c50bfe53b3b24815\
+             062c1400",
+        )
+        .unwrap();
+        let funding_tx1: Transaction = Transaction::from_hex(
+            "020000000001018aa4065d472efc80d2a9f26bf0f77aabd5b8fcb45661de8a0161\
+             cbcc6b5fef9e0000000000feffffff0235837b00000000001600143e9fd2829e66\
+             868739ddbb8c397a3e35ae02a5151027000000000000220020e9bb049fdff8f8d3\
+             b33b7335978b1dbb268833a32a69906f9e500e4103151bef0247304402201d2f3c\
+             a3ec4c82071b825a44c5b8a7455e4e50caef07e988bbe46554846e445702205f1b\
+             066bf6d747c06b3721ac878104e434e977e0e321191a0c860f05fb3bb319012103\
+             b475c0164be599df74ea5d4b669fe1c439953e40eea2d4958d66698f26eeaa5f2a\
+             2c1400",
+        )
+        .unwrap();
+
+        let keys = vec![
+            "038b782f94d19f34536a96e12e0bad99e6f82c838fa16a4234572f5f132d95ba29",
+            "020ae2216f42575c4196864eda0252c75c61273065f691b32be9a99cb2a3c9b4d1",
+            "02536d5e1464b961562da57207e4a46edb7dade9b92aa29712ca8309c8aba5be5b",
+        ]
+        .iter()
+        .map(|h| PublicKey::from_hex(h).unwrap().0)
+        .collect::<Vec<_>>();
+
+        let redeem_script = RedeemScriptBuilder::with_public_keys(keys)
+            .to_script()
+            .unwrap();
+
+        let create_tx = |funds: &[&Transaction], bip69_input_ordering: bool| {
+            let mut builder = BtcAnchoringTransactionBuilder::new(&redeem_script);
+            for tx in funds {
+                builder.additional_funds((*tx).clone()).unwrap();
+            }
+            builder.fee(1);
+            builder.bip69_input_ordering(bip69_input_ordering);
+            builder.payload(Height::zero(), funding_tx0.object_hash());
+            builder.create().unwrap()
+        };
+
+        // Without the BIP-69 ordering, the inputs keep the order of the funding transactions.
+        let (tx, _) = create_tx(&[&funding_tx0, &funding_tx1], false);
+        let (tx2, _) = create_tx(&[&funding_tx1, &funding_tx0], false);
+        assert_ne!(tx, tx2);
+        assert_eq!(tx.0.input[0].previous_output.txid, funding_tx0.0.txid());
+
+        let (tx, inputs) = create_tx(&[&funding_tx0, &funding_tx1], true);
+        let (tx2, inputs2) = create_tx(&[&funding_tx1, &funding_tx0], true);
+        assert_eq!(tx, tx2);
+        assert_eq!(inputs, inputs2);
+
+        // Inputs are sorted by the hex representation of the previous transaction hash.
+        let txids =
+            tx.0.input
+                .iter()
+                .map(|input| input.previous_output.txid.to_string())
+                .collect::<Vec<_>>();
+        let mut sorted_txids = txids.clone();
+        sorted_txids.sort();
+        assert_eq!(txids, sorted_txids);
+        assert_eq!(inputs[0].0.txid().to_string(), txids[0]);
+    }
+
     #[test]
     fn test_anchoring_transaction_builder_incorrect_prev_tx() {
         let funding_tx: Transaction = Transaction::from_hex(
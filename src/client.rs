@@ -0,0 +1,667 @@
+// Copyright 2019 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! HTTP client for the anchoring service API.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use exonum_btc_anchoring::client::{verify_transaction_proof, ApiClient};
+//! # use exonum::crypto::PublicKey;
+//!
+//! # async fn example(validator_keys: Vec<PublicKey>) -> anyhow::Result<()> {
+//! let client = ApiClient::new("http://localhost:8080", "btc_anchoring");
+//! let status = client.get_status().await?;
+//! if let Some(height) = status.latest_anchored_height {
+//!     let proof = client.get_proof(Some(height)).await?;
+//!     let (_, transaction) = verify_transaction_proof(proof, &validator_keys)?
+//!         .expect("Anchoring transaction is absent in proof");
+//!     println!("Anchoring transaction: {}", transaction.id());
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use anyhow::{anyhow, ensure};
+use async_trait::async_trait;
+use exonum::{
+    crypto::{Hash, PublicKey},
+    helpers::Height,
+};
+use serde::{de::DeserializeOwned, ser::Serialize};
+use serde_derive::Deserialize;
+
+use std::fmt::Display;
+
+use crate::{
+    api::{
+        v1::{
+            self, AnchorBlock, AnchorBlockQuery, AnchorBroadcastStatus, AnchorProofs,
+            AnchorRangeQuery, AnchorStats, AnchorStatsQuery, AnchoringDescriptor, AnchoringHealth,
+            AnchoringStatus, BalanceHistory, BalanceHistoryQuery, BitcoinConfirmation, Checkpoint,
+            FeeAccounting, FeeAccountingQuery, FinalityCertificate, FinalityCertificateQuery,
+            FindAnchorQuery, NextAnchor, PendingConfig, ProposalPreview, RelayStatuses,
+            SignatureAuditQuery, StorageUsage, TenantQuery, TenantReceipt, TenantReceiptQuery,
+            TenantUsage,
+        },
+        AnchoringChainLength, AnchoringProposalState, FindTransactionQuery, IndexQuery, PrivateApi,
+        PublicApi, TransactionProof,
+    },
+    blockchain::{
+        audit::SignatureAuditReport, data_layout::TenantBlockId, export::AnchorsExport,
+        RecordAnchorBlock, ReportAnchorStatus, ReportRelayStatus, SignInput, SubmitAnchoringKey,
+        UpdateParameters,
+    },
+    btc,
+    config::Config,
+    sync::{ChainQuery, SigningPolicy, SigningRequest, TransactionStatus},
+};
+
+/// Consensus status of the Exonum node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConsensusStatus {
+    /// Consensus is disabled on the node.
+    Disabled,
+    /// Consensus is enabled, but the node is not connected to the majority of validators,
+    /// thus it may reside in the minority partition of the network.
+    Enabled,
+    /// Consensus is enabled and the node is connected to the majority of validators.
+    Active,
+}
+
+/// Subset of the node information returned by the system API.
+#[derive(Debug, Deserialize)]
+struct NodeInfo {
+    consensus_status: ConsensusStatus,
+}
+
+/// Client implementation for the API of the anchoring service instance.
+#[derive(Debug, Clone)]
+pub struct ApiClient {
+    /// Address of the node API with the port.
+    hostname: String,
+    /// Complete prefix with the port and the anchoring instance name.
+    prefix: String,
+    /// Underlying HTTP client.
+    client: reqwest::Client,
+}
+
+impl ApiClient {
+    /// Create a new anchoring API relay with the specified host and name of instance.
+    /// Hostname should be in form `{http|https}://{address}:{port}`.
+    pub fn new(hostname: impl AsRef<str>, instance_name: impl AsRef<str>) -> Self {
+        Self {
+            hostname: hostname.as_ref().to_owned(),
+            prefix: format!(
+                "{}/api/services/{}",
+                hostname.as_ref(),
+                instance_name.as_ref()
+            ),
+            client: reqwest::Client::new(),
+        }
+    }
+
+    /// Returns a summary of the current anchoring state.
+    pub async fn get_status(&self) -> Result<AnchoringStatus, reqwest::Error> {
+        self.get("v1/status").await
+    }
+
+    /// Returns the actual anchoring address with its redeem script and output descriptor.
+    pub async fn get_actual_descriptor(&self) -> Result<AnchoringDescriptor, reqwest::Error> {
+        self.get("v1/address/actual-descriptor").await
+    }
+
+    /// Returns the following anchoring address with its redeem script and output descriptor,
+    /// if the following configuration is known.
+    pub async fn get_following_descriptor(
+        &self,
+    ) -> Result<Option<AnchoringDescriptor>, reqwest::Error> {
+        self.get("v1/address/following-descriptor").await
+    }
+
+    /// Returns a proof for the anchoring transaction with the height greater or equal to
+    /// the given one, or for the latest anchoring transaction if the height is not specified.
+    pub async fn get_proof(
+        &self,
+        height: Option<Height>,
+    ) -> Result<v1::TransactionProof, reqwest::Error> {
+        self.get_query("v1/find-transaction", &FindTransactionQuery { height })
+            .await
+    }
+
+    /// Returns a checkpoint of the anchoring state, which should be verified
+    /// by the [`verify_checkpoint`] function.
+    ///
+    /// [`verify_checkpoint`]: fn.verify_checkpoint.html
+    pub async fn get_checkpoint(&self) -> Result<Checkpoint, reqwest::Error> {
+        self.get("v1/checkpoint").await
+    }
+
+    /// Returns a proof for the anchoring transaction with the given identifier.
+    pub async fn find_anchor(
+        &self,
+        txid: btc::Sha256d,
+    ) -> Result<v1::TransactionProof, reqwest::Error> {
+        self.get_query("v1/find-anchor", &FindAnchorQuery { txid })
+            .await
+    }
+
+    /// Returns a finality certificate for the Exonum transaction with the given hash,
+    /// supplemented by the Bitcoin confirmation metadata of the covering anchor, which is
+    /// obtained from the given Bitcoin relay.
+    pub async fn get_finality_certificate<R>(
+        &self,
+        tx_hash: Hash,
+        btc_relay: &R,
+    ) -> anyhow::Result<FinalityCertificate>
+    where
+        R: ChainQuery + Sync,
+        R::Error: Display,
+    {
+        let mut certificate: FinalityCertificate = self
+            .get_query(
+                "v1/finality-certificate",
+                &FinalityCertificateQuery { tx_hash },
+            )
+            .await?;
+
+        let anchor = certificate
+            .anchor
+            .as_ref()
+            .and_then(|anchor| anchor.proof.transaction_proof.entries_unchecked().first())
+            .map(|(_, transaction)| transaction.id());
+        if let Some(txid) = anchor {
+            // The recorded block allows to get the status from the pruned Bitcoin nodes.
+            let status = match self.get_anchor_block(txid).await?.block_hash {
+                Some(block_hash) => {
+                    btc_relay
+                        .transaction_status_in_block(txid, block_hash)
+                        .await
+                }
+                None => btc_relay.transaction_status(txid).await,
+            }
+            .map_err(|e| anyhow!("Unable to get anchoring transaction status: {}", e))?;
+            certificate.bitcoin = match status {
+                TransactionStatus::Mempool => Some(0),
+                TransactionStatus::Committed(confirmations) => Some(confirmations),
+                TransactionStatus::Unknown | TransactionStatus::Simulated => None,
+            }
+            .map(|confirmations| BitcoinConfirmation {
+                txid,
+                confirmations,
+            });
+        }
+        Ok(certificate)
+    }
+
+    /// Returns the anchoring transaction which the service would propose right now.
+    pub async fn get_proposal_preview(&self) -> Result<ProposalPreview, reqwest::Error> {
+        self.get("v1/preview-proposal").await
+    }
+
+    /// Returns the fees consumed from the anchoring wallet within the given anchoring epoch,
+    /// or within the current epoch if the epoch is not specified.
+    pub async fn get_fee_accounting(
+        &self,
+        epoch: Option<u64>,
+    ) -> Result<FeeAccounting, reqwest::Error> {
+        self.get_query("v1/fee-accounting", &FeeAccountingQuery { epoch })
+            .await
+    }
+
+    /// Returns the given number of the latest anchoring wallet balances with the forecast
+    /// of the wallet depletion.
+    pub async fn get_balance_history(
+        &self,
+        count: Option<u64>,
+    ) -> Result<BalanceHistory, reqwest::Error> {
+        self.get_query("v1/balance-history", &BalanceHistoryQuery { count })
+            .await
+    }
+
+    /// Returns the fee and size statistics of the given number of the latest anchoring
+    /// transactions finalized by the service.
+    pub async fn get_anchor_stats(
+        &self,
+        count: Option<u64>,
+    ) -> Result<AnchorStats, reqwest::Error> {
+        self.get_query("v1/anchor-stats", &AnchorStatsQuery { count })
+            .await
+    }
+
+    /// Verifies the signatures of the anchoring transactions starting from the given
+    /// position in the anchoring chain.
+    pub async fn get_signature_audit(
+        &self,
+        from: Option<u64>,
+    ) -> Result<SignatureAuditReport, reqwest::Error> {
+        self.get_query("v1/signature-audit", &SignatureAuditQuery { from })
+            .await
+    }
+
+    /// Exports the anchored blocks of at most `count` anchoring transactions starting from
+    /// the given position in the anchoring chain. The next page starts from
+    /// the [`next_from`] position of the returned page.
+    ///
+    /// [`next_from`]: ../blockchain/export/struct.AnchorsExport.html#method.next_from
+    pub async fn get_anchors(
+        &self,
+        from: Option<u64>,
+        count: Option<u64>,
+    ) -> Result<AnchorsExport, reqwest::Error> {
+        self.get_query("v1/anchors", &AnchorRangeQuery { from, count })
+            .await
+    }
+
+    /// Returns the proof of existence for at most `count` anchoring transactions starting
+    /// from the given position in the anchoring chain.
+    pub async fn get_anchor_proofs(
+        &self,
+        from: Option<u64>,
+        count: Option<u64>,
+    ) -> Result<AnchorProofs, reqwest::Error> {
+        self.get_query("v1/anchor-proofs", &AnchorRangeQuery { from, count })
+            .await
+    }
+
+    /// Returns the rolling health score of the anchoring.
+    pub async fn get_health(&self) -> Result<AnchoringHealth, reqwest::Error> {
+        self.get("v1/health").await
+    }
+
+    /// Returns the status of the latest anchoring transaction in the Bitcoin network
+    /// reported by the anchoring nodes.
+    pub async fn get_anchor_status(&self) -> Result<AnchorBroadcastStatus, reqwest::Error> {
+        self.get("v1/anchor-status").await
+    }
+
+    /// Returns the anchoring keys submitted by the validators joining the anchoring
+    /// together with the resulting configuration.
+    pub async fn get_pending_config(&self) -> Result<PendingConfig, reqwest::Error> {
+        self.get("v1/pending-config").await
+    }
+
+    /// Returns the health of the Bitcoin relays reported by the anchoring nodes.
+    pub async fn get_relay_status(&self) -> Result<RelayStatuses, reqwest::Error> {
+        self.get("v1/relay-status").await
+    }
+
+    /// Returns the hash of the Bitcoin block containing the given anchoring transaction,
+    /// if it has been recorded by the anchoring nodes.
+    pub async fn get_anchor_block(
+        &self,
+        txid: btc::Sha256d,
+    ) -> Result<AnchorBlock, reqwest::Error> {
+        self.get_query("v1/anchor-block", &AnchorBlockQuery { txid })
+            .await
+    }
+
+    /// Returns the prediction of the next anchoring slot.
+    pub async fn get_next_anchor(&self) -> Result<NextAnchor, reqwest::Error> {
+        self.get("v1/next-anchor").await
+    }
+
+    /// Returns the entry counts and approximate sizes of the anchoring schema indexes.
+    /// This method uses the private API of the node.
+    pub async fn get_storage_usage(&self) -> Result<StorageUsage, reqwest::Error> {
+        self.get("v1/storage-usage").await
+    }
+
+    /// Returns the data quota usage of the tenant with the given key.
+    pub async fn get_tenant_usage(&self, tenant: PublicKey) -> Result<TenantUsage, reqwest::Error> {
+        self.get_query("v1/tenant-usage", &TenantQuery { tenant })
+            .await
+    }
+
+    /// Returns a receipt proof for the data hash submitted by the tenant in the Exonum block
+    /// with the given height.
+    pub async fn get_tenant_receipt(
+        &self,
+        tenant: PublicKey,
+        height: Height,
+        data_hash: Hash,
+    ) -> Result<TenantReceipt, reqwest::Error> {
+        let query = TenantReceiptQuery {
+            tenant,
+            height,
+            data_hash,
+        };
+        self.get_query("v1/tenant-receipt", &query).await
+    }
+
+    /// Returns the consensus status of the node. This method uses the private
+    /// system API of the node.
+    pub async fn consensus_status(&self) -> Result<ConsensusStatus, reqwest::Error> {
+        let info: NodeInfo = self
+            .client
+            .get(&format!("{}/api/system/v1/info", self.hostname))
+            .send()
+            .await?
+            .json()
+            .await?;
+        Ok(info.consensus_status)
+    }
+
+    fn endpoint(&self, name: impl AsRef<str>) -> String {
+        format!("{}/{}", self.prefix, name.as_ref())
+    }
+
+    async fn get<R>(&self, endpoint: &str) -> Result<R, reqwest::Error>
+    where
+        R: DeserializeOwned + Send + 'static,
+    {
+        self.client
+            .get(&self.endpoint(endpoint))
+            .send()
+            .await?
+            .json()
+            .await
+    }
+
+    async fn get_query<Q, R>(&self, endpoint: &str, query: &Q) -> Result<R, reqwest::Error>
+    where
+        Q: Serialize,
+        R: DeserializeOwned + Send + 'static,
+    {
+        self.client
+            .get(&self.endpoint(endpoint))
+            .query(query)
+            .send()
+            .await?
+            .json()
+            .await
+    }
+
+    async fn post<Q, R>(&self, endpoint: &str, body: &Q) -> Result<R, reqwest::Error>
+    where
+        Q: Serialize,
+        R: DeserializeOwned + Send + 'static,
+    {
+        self.client
+            .post(&self.endpoint(endpoint))
+            .json(&body)
+            .send()
+            .await?
+            .json()
+            .await
+    }
+}
+
+/// Refuses to sign the anchoring proposals while the node is not connected to the
+/// majority of validators.
+///
+/// A node in the minority partition of the network cannot know whether the proposal
+/// it sees will be anchored, so signing it may waste the anchoring wallet funds. The
+/// policy uses the private system API of the node, thus the client must be created
+/// for the private API address.
+#[derive(Debug, Clone)]
+pub struct ConsensusHealthPolicy {
+    client: ApiClient,
+}
+
+impl ConsensusHealthPolicy {
+    /// Creates a new policy which uses the given client to request the consensus status.
+    pub fn new(client: ApiClient) -> Self {
+        Self { client }
+    }
+}
+
+#[async_trait]
+impl SigningPolicy for ConsensusHealthPolicy {
+    async fn check(&self, _request: &SigningRequest<'_>) -> anyhow::Result<()> {
+        let status = self.client.consensus_status().await?;
+        ensure!(
+            status == ConsensusStatus::Active,
+            "Node is not connected to the majority of validators, consensus status is {:?}",
+            status
+        );
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl PublicApi for ApiClient {
+    type Error = reqwest::Error;
+
+    async fn actual_address(&self) -> Result<btc::Address, Self::Error> {
+        self.get("address/actual").await
+    }
+
+    async fn following_address(&self) -> Result<Option<btc::Address>, Self::Error> {
+        self.get("address/following").await
+    }
+
+    async fn find_transaction(
+        &self,
+        height: Option<Height>,
+    ) -> Result<TransactionProof, Self::Error> {
+        self.get_query("find-transaction", &FindTransactionQuery { height })
+            .await
+    }
+
+    async fn config(&self) -> Result<Config, Self::Error> {
+        self.get("config").await
+    }
+}
+
+#[async_trait]
+impl PrivateApi for ApiClient {
+    type Error = reqwest::Error;
+
+    async fn sign_input(&self, sign_input: SignInput) -> Result<Hash, Self::Error> {
+        self.post("sign-input", &sign_input).await
+    }
+
+    async fn add_funds(&self, transaction: btc::Transaction) -> Result<Hash, Self::Error> {
+        self.post("add-funds", &transaction).await
+    }
+
+    async fn import_anchor(&self, transaction: btc::Transaction) -> Result<Hash, Self::Error> {
+        self.post("import-anchor", &transaction).await
+    }
+
+    async fn anchoring_proposal(&self) -> Result<AnchoringProposalState, Self::Error> {
+        self.get("anchoring-proposal").await
+    }
+
+    async fn config(&self) -> Result<Config, Self::Error> {
+        self.get("config").await
+    }
+
+    async fn transaction_with_index(
+        &self,
+        index: u64,
+    ) -> Result<Option<btc::Transaction>, Self::Error> {
+        self.get_query("transaction", &IndexQuery { index }).await
+    }
+
+    async fn transactions_count(&self) -> Result<AnchoringChainLength, Self::Error> {
+        self.get("transactions-count").await
+    }
+
+    async fn submit_anchoring_key(
+        &self,
+        submit_key: SubmitAnchoringKey,
+    ) -> Result<Hash, Self::Error> {
+        self.post("submit-anchoring-key", &submit_key).await
+    }
+
+    async fn update_parameters(&self, parameters: UpdateParameters) -> Result<Hash, Self::Error> {
+        self.post("update-parameters", &parameters).await
+    }
+
+    async fn confirm_anchor_finality(&self, txid: btc::Sha256d) -> Result<Hash, Self::Error> {
+        self.post("confirm-anchor-finality", &txid).await
+    }
+
+    async fn report_anchor_status(&self, report: ReportAnchorStatus) -> Result<Hash, Self::Error> {
+        self.post("report-anchor-status", &report).await
+    }
+
+    async fn record_anchor_block(&self, record: RecordAnchorBlock) -> Result<Hash, Self::Error> {
+        self.post("record-anchor-block", &record).await
+    }
+
+    async fn report_relay_status(&self, report: ReportRelayStatus) -> Result<Hash, Self::Error> {
+        self.post("report-relay-status", &report).await
+    }
+}
+
+/// Checks that the payload format version of the anchoring transaction is supported
+/// by this verifier, so the transactions of the future versions are not treated as
+/// malformed ones silently.
+fn ensure_supported_payload(transaction: &btc::Transaction) -> anyhow::Result<()> {
+    let version = transaction.payload_version().ok_or_else(|| {
+        anyhow!(
+            "Anchoring transaction {} has no anchoring payload",
+            transaction.id()
+        )
+    })?;
+    ensure!(
+        btc::SUPPORTED_PAYLOAD_VERSIONS.contains(&version),
+        "Anchoring transaction {} has the payload version {}, but the supported versions \
+         are {:?}. Update the verifier to check this transaction",
+        transaction.id(),
+        version,
+        btc::SUPPORTED_PAYLOAD_VERSIONS
+    );
+    Ok(())
+}
+
+/// Verifies the given anchoring transaction proof against the consensus keys of
+/// the Exonum validators and returns the proven transaction with its index in the
+/// anchoring chain, if any.
+///
+/// Returns an error if the payload format version of the transaction is not supported.
+pub fn verify_transaction_proof(
+    proof: v1::TransactionProof,
+    validator_keys: &[PublicKey],
+) -> anyhow::Result<Option<(u64, btc::Transaction)>> {
+    proof.index_proof.verify(validator_keys)?;
+
+    let entry = proof
+        .transaction_proof
+        .check()?
+        .entries()
+        .iter()
+        .cloned()
+        .next();
+    if let Some((_, transaction)) = entry.as_ref() {
+        ensure_supported_payload(transaction)?;
+    }
+    Ok(entry)
+}
+
+/// Verifies the given proof for a range of the anchoring transactions against the consensus
+/// keys of the Exonum validators and returns the proved transactions with their indexes
+/// in the anchoring chain.
+///
+/// The proof is considered as valid if it contains exactly the transactions of
+/// the declared range.
+pub fn verify_anchor_proofs(
+    proofs: AnchorProofs,
+    validator_keys: &[PublicKey],
+) -> anyhow::Result<Vec<(u64, btc::Transaction)>> {
+    let (_, chain_hash) = proofs.proof.index_proof.verify(validator_keys)?;
+    let checked_proof = proofs
+        .proof
+        .transaction_proof
+        .check_against_hash(chain_hash)?;
+    ensure!(
+        checked_proof.length() == proofs.transactions_count,
+        "Anchoring chain length {} does not match the declared one {}",
+        checked_proof.length(),
+        proofs.transactions_count
+    );
+
+    let entries = checked_proof.entries().to_vec();
+    ensure!(
+        entries
+            .iter()
+            .map(|(index, _)| *index)
+            .eq(proofs.from..proofs.to),
+        "Proof does not contain the anchoring transactions from {} to {}",
+        proofs.from,
+        proofs.to
+    );
+    for (_, transaction) in &entries {
+        ensure_supported_payload(transaction)?;
+    }
+    Ok(entries)
+}
+
+/// Verifies the given anchoring state checkpoint against the consensus keys of the Exonum
+/// validators and returns the actual anchoring address in the given Bitcoin network
+/// with the latest anchoring transaction and its index in the anchoring chain.
+///
+/// The checkpoint is considered as valid if the latest anchoring transaction is the last one
+/// in the anchoring chain. The anchoring address is derived from the output of this
+/// transaction, since the configuration in the checkpoint is not covered by the proof.
+pub fn verify_checkpoint(
+    checkpoint: Checkpoint,
+    network: bitcoin::Network,
+    validator_keys: &[PublicKey],
+) -> anyhow::Result<(btc::Address, u64, btc::Transaction)> {
+    let (_, chain_hash) = checkpoint
+        .latest_anchor
+        .index_proof
+        .verify(validator_keys)?;
+    let checked_proof = checkpoint
+        .latest_anchor
+        .transaction_proof
+        .check_against_hash(chain_hash)?;
+    let (index, transaction) = checked_proof
+        .entries()
+        .first()
+        .cloned()
+        .ok_or_else(|| anyhow!("Latest anchoring transaction is absent in proof"))?;
+    ensure!(
+        index + 1 == checked_proof.length(),
+        "Anchoring transaction with index {} is not the latest one",
+        index
+    );
+    ensure_supported_payload(&transaction)?;
+
+    let (out_script, _) = transaction
+        .anchoring_metadata()
+        .ok_or_else(|| anyhow!("Latest anchoring transaction has no anchoring metadata"))?;
+    let address = bitcoin::Address::from_script(out_script, network).ok_or_else(|| {
+        anyhow!("Latest anchoring transaction does not spend funds to an anchoring address")
+    })?;
+    Ok((address.into(), index, transaction))
+}
+
+/// Verifies the given tenant receipt against the consensus keys of the Exonum validators
+/// and returns the proven data hash with its position in the data submitted by the tenant
+/// in the Exonum block, if any.
+pub fn verify_tenant_receipt(
+    receipt: TenantReceipt,
+    validator_keys: &[PublicKey],
+) -> anyhow::Result<Option<(TenantBlockId, u64, Hash)>> {
+    let (_, roots_hash) = receipt.index_proof.verify(validator_keys)?;
+    let checked_root_proof = receipt.root_proof.check_against_hash(roots_hash)?;
+    let (id, root_hash) = match checked_root_proof.entries().next() {
+        Some((id, root_hash)) => (*id, *root_hash),
+        None => return Ok(None),
+    };
+
+    let entry = receipt
+        .data_proof
+        .check_against_hash(root_hash)?
+        .entries()
+        .first()
+        .map(|(position, data_hash)| (id, *position, *data_hash));
+    Ok(entry)
+}
@@ -14,21 +14,75 @@
 
 //! BTC anchoring configuration data types.
 
-pub use crate::proto::{AnchoringKeys, Config};
+pub use crate::proto::{AnchoringKeys, Config, KeyPossessionProof, Tenant};
 
 use anyhow::ensure;
-use bitcoin::network::constants::Network;
+use bitcoin::{
+    network::constants::Network,
+    secp256k1::{Message, Secp256k1, Signature},
+};
 use btc_transaction_utils::{
     multisig::{RedeemScript, RedeemScriptBuilder, RedeemScriptError},
     p2wsh,
 };
 use exonum::{
-    crypto::PublicKey,
+    crypto::{self, Hash, PublicKey},
     helpers::{Height, ValidateInput},
 };
+use exonum_merkledb::ObjectHash;
+use serde_derive::{Deserialize, Serialize};
+
+use std::{cmp, collections::HashSet};
 
 use crate::btc::{self, Address};
 
+/// Type of the output script of the anchoring transactions.
+///
+/// The output type is a part of the anchoring configuration, so the anchoring epoch
+/// uses a single output type. Only the P2WSH outputs are supported: the Taproot outputs
+/// require the Schnorr signatures, which are not available in the Bitcoin libraries used
+/// by the service. Thus the configuration parameter has the single possible value for
+/// now. It is kept in the configuration and the anchor proofs, so that the future
+/// output types do not change their format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputType {
+    /// Pay to witness script hash output with the multisignature redeem script.
+    P2wsh,
+}
+
+impl OutputType {
+    /// Returns the code of the output type used in the Protobuf representation.
+    pub fn code(self) -> u32 {
+        match self {
+            OutputType::P2wsh => 0,
+        }
+    }
+
+    /// Returns the output type with the given Protobuf code, if any.
+    pub fn from_code(code: u32) -> Option<Self> {
+        match code {
+            0 => Some(OutputType::P2wsh),
+            _ => None,
+        }
+    }
+
+    /// Detects the output type of the given output script, if it is one of the known types.
+    pub fn of_script(script: &bitcoin::Script) -> Option<Self> {
+        if script.is_v0_p2wsh() {
+            Some(OutputType::P2wsh)
+        } else {
+            None
+        }
+    }
+}
+
+impl Default for OutputType {
+    fn default() -> Self {
+        OutputType::P2wsh
+    }
+}
+
 impl Default for Config {
     fn default() -> Self {
         Self {
@@ -36,20 +90,40 @@ impl Default for Config {
             anchoring_keys: vec![],
             anchoring_interval: 5_000,
             transaction_fee: 10,
+            start_height: Height::zero(),
+            anchor_only_on_state_change: false,
+            tenants: vec![],
+            reserve_balance: 0,
+            standby_activation_intervals: 0,
+            auxiliary_services: vec![],
+            dust_threshold: 0,
+            max_signature_age: 0,
+            key_proofs: vec![],
+            output_type: OutputType::P2wsh,
+            allow_address_reuse: false,
+            external_addresses: vec![],
+            commit_consensus_config: false,
+            approval_window: 0,
+            bip69_input_ordering: false,
         }
     }
 }
 
 impl Config {
     /// Current limit on the number of keys in a redeem script on the Bitcoin network.
-    const MAX_NODES_COUNT: usize = 20;
+    pub(crate) const MAX_NODES_COUNT: usize = 20;
+    /// Minimal number of the distinct anchoring nodes confirming the high-risk
+    /// maintenance operation.
+    pub(crate) const MIN_APPROVALS: usize = 2;
+    /// Maximal age in blocks of the anchoring proposal, which can be set by the configuration.
+    pub(crate) const MAX_SIGNATURE_AGE: u64 = 10_000_000;
     /// Minimal fee in satoshis for Bitcoin transaction.
     const MIN_TOTAL_TX_FEE: u64 = 1000;
     /// Minimal total transaction size according to
     /// https://bitcoin.stackexchange.com/questions/1195/how-to-calculate-transaction-size-before-sending-legacy-non-segwit-p2pkh-p2sh     
     const MIN_TX_LEN: u64 = 10 + 146 + 33 + 81;
     /// Minimal enough transaction fee per byte.
-    const MIN_TX_FEE: u64 = Self::MIN_TOTAL_TX_FEE / Self::MIN_TX_LEN + 1; // Round up.
+    pub(crate) const MIN_TX_FEE: u64 = Self::MIN_TOTAL_TX_FEE / Self::MIN_TX_LEN + 1; // Round up.
 
     /// Creates Bitcoin anchoring config instance with default parameters for the
     /// given Bitcoin network and public keys of participants.
@@ -69,7 +143,26 @@ impl Config {
         })
     }
 
+    /// Tries to find the anchoring keys of the node with the given service key.
+    pub fn find_anchoring_keys(&self, service_key: &PublicKey) -> Option<&AnchoringKeys> {
+        self.anchoring_keys
+            .iter()
+            .find(|keys| &keys.service_key == service_key)
+    }
+
+    /// Returns the position of the given Bitcoin key in the redeem script.
+    pub fn redeem_script_position(&self, bitcoin_key: &btc::PublicKey) -> Option<u16> {
+        self.redeem_script()
+            .content()
+            .public_keys
+            .iter()
+            .position(|key| key == &bitcoin_key.0)
+            .map(|position| position as u16)
+    }
+
     /// Tries to find bitcoin public key corresponding with the given service key.
+    ///
+    /// Returned index is the position of the key in the list of the anchoring keys.
     pub fn find_bitcoin_key(&self, service_key: &PublicKey) -> Option<(u16, btc::PublicKey)> {
         self.anchoring_keys.iter().enumerate().find_map(|(n, x)| {
             if &x.service_key == service_key {
@@ -80,9 +173,29 @@ impl Config {
         })
     }
 
+    /// Tries to find the data quota of the tenant with the given key.
+    pub fn find_tenant_quota(&self, tenant_key: &PublicKey) -> Option<u64> {
+        self.tenants
+            .iter()
+            .find(|tenant| &tenant.key == tenant_key)
+            .map(|tenant| tenant.quota)
+    }
+
+    /// Checks that the configuration contains the same Bitcoin or service key
+    /// more than once.
+    pub fn has_duplicate_keys(&self) -> bool {
+        let mut bitcoin_keys = HashSet::new();
+        let mut service_keys = HashSet::new();
+        self.anchoring_keys
+            .iter()
+            .any(|x| !bitcoin_keys.insert(x.bitcoin_key) || !service_keys.insert(x.service_key))
+    }
+
     /// Returns the corresponding Bitcoin address.
     pub fn anchoring_address(&self) -> Address {
-        p2wsh::address(&self.redeem_script(), self.network).into()
+        match self.output_type {
+            OutputType::P2wsh => p2wsh::address(&self.redeem_script(), self.network).into(),
+        }
     }
 
     /// Returns the corresponding redeem script.
@@ -93,9 +206,29 @@ impl Config {
             .unwrap()
     }
 
-    /// Computes the P2WSH output corresponding to the actual redeem script.
+    /// Returns the output descriptor of the anchoring address with the checksum, which
+    /// can be imported into the Bitcoin node wallet to watch the address.
+    pub fn anchoring_descriptor(&self) -> String {
+        let redeem_script = self.redeem_script();
+        let content = redeem_script.content();
+        let keys = content
+            .public_keys
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        let descriptor = match self.output_type {
+            OutputType::P2wsh => format!("wsh(multi({},{}))", content.quorum, keys.join(",")),
+        };
+        let checksum = descriptor_checksum(&descriptor).expect("Invalid descriptor characters");
+        format!("{}#{}", descriptor, checksum)
+    }
+
+    /// Computes the output script of the configured type corresponding to the actual
+    /// redeem script.
     pub fn anchoring_out_script(&self) -> bitcoin::Script {
-        self.redeem_script().as_ref().to_v0_p2wsh()
+        match self.output_type {
+            OutputType::P2wsh => self.redeem_script().as_ref().to_v0_p2wsh(),
+        }
     }
 
     /// Returns the latest height below the given height which must be anchored.
@@ -108,39 +241,229 @@ impl Config {
         Height(self.previous_anchoring_height(current_height).0 + self.anchoring_interval)
     }
 
+    /// Checks that the anchoring is started at the given height.
+    pub fn is_started(&self, current_height: Height) -> bool {
+        current_height >= self.start_height
+    }
+
     /// Returns sufficient number of votes for the given anchoring nodes number.
     pub fn byzantine_quorum(&self) -> usize {
         exonum::helpers::byzantine_quorum(self.anchoring_keys.len())
     }
+
+    /// Returns the number of the anchoring nodes, which should confirm the high-risk
+    /// maintenance operation. The operation always requires the confirmations of at least
+    /// two distinct anchoring nodes, even if the byzantine quorum is smaller. Thus
+    /// the single anchoring node cannot approve the maintenance operations at all, and
+    /// the corresponding changes should be made via the supervisor service instead.
+    pub fn approval_quorum(&self) -> usize {
+        cmp::max(self.byzantine_quorum(), Self::MIN_APPROVALS)
+    }
+
+    /// Returns the challenge, which should be signed by the Bitcoin key of the anchoring
+    /// node with the given service key to prove the key possession. The challenge is the
+    /// hash of the configuration without the key proofs and the service key, so the proof
+    /// cannot be reused in another configuration or for another anchoring node.
+    pub fn key_possession_challenge(&self, service_key: &PublicKey) -> Hash {
+        let config = Self {
+            key_proofs: vec![],
+            ..self.clone()
+        };
+        let mut bytes = config.object_hash().as_ref().to_vec();
+        bytes.extend_from_slice(service_key.as_ref());
+        crypto::hash(&bytes)
+    }
+
+    /// Signs the key possession challenge of the anchoring node with the given service key
+    /// by the given Bitcoin private key.
+    pub fn prove_key_possession(
+        &self,
+        service_key: &PublicKey,
+        private_key: &btc::PrivateKey,
+    ) -> KeyPossessionProof {
+        let context = Secp256k1::new();
+        let challenge = self.key_possession_challenge(service_key);
+        let message = Message::from_slice(challenge.as_ref()).unwrap();
+        KeyPossessionProof {
+            bitcoin_key: private_key.0.public_key(&context).into(),
+            signature: context
+                .sign(&message, &private_key.0.key)
+                .serialize_der()
+                .to_vec(),
+        }
+    }
+
+    /// Checks that the configuration contains a valid proof of possession of the Bitcoin
+    /// key of the given anchoring node.
+    pub fn verify_key_possession(&self, keys: &AnchoringKeys) -> bool {
+        self.key_proofs
+            .iter()
+            .any(|proof| self.verify_key_proof(keys, proof))
+    }
+
+    /// Checks that the given proof of possession of the Bitcoin key of the given anchoring
+    /// node is valid for this configuration.
+    pub fn verify_key_proof(&self, keys: &AnchoringKeys, proof: &KeyPossessionProof) -> bool {
+        if proof.bitcoin_key != keys.bitcoin_key {
+            return false;
+        }
+
+        let context = Secp256k1::verification_only();
+        let challenge = self.key_possession_challenge(&keys.service_key);
+        let message = Message::from_slice(challenge.as_ref()).unwrap();
+        Signature::from_der(&proof.signature)
+            .and_then(|signature| context.verify(&message, &signature, &keys.bitcoin_key.0.key))
+            .is_ok()
+    }
 }
 
-impl ValidateInput for Config {
-    type Error = anyhow::Error;
+impl Config {
+    /// Checks the configuration and returns the list of all found problems, which is
+    /// empty for the correct configuration.
+    pub fn validation_problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        let keys_count = self.anchoring_keys.len();
+        if keys_count == 0 {
+            problems.push("The list of anchoring keys must not be empty.".to_owned());
+        }
+        if keys_count > Self::MAX_NODES_COUNT {
+            problems.push(format!(
+                "Too many anchoring nodes: amount of anchoring nodes should be less or equal than the {}.",
+                Self::MAX_NODES_COUNT
+            ));
+        }
+        if self.has_duplicate_keys() {
+            problems.push("The list of anchoring keys must not contain duplicate keys.".to_owned());
+        }
+        if self.anchoring_interval == 0 {
+            problems.push("Anchoring interval should be greater than zero.".to_owned());
+        }
+        if self.transaction_fee < Self::MIN_TX_FEE {
+            problems.push(format!(
+                "Transaction fee should be greater than {}.",
+                Self::MIN_TX_FEE
+            ));
+        }
 
-    fn validate(&self) -> Result<(), Self::Error> {
-        ensure!(
-            !self.anchoring_keys.is_empty(),
-            "The list of anchoring keys must not be empty."
-        );
-        ensure!(
-            self.anchoring_keys.len() <= Self::MAX_NODES_COUNT,
-            "Too many anchoring nodes: amount of anchoring nodes should be less or equal than the {}.",
-            Self::MAX_NODES_COUNT
-        );
-        ensure!(
-            self.anchoring_interval > 0,
-            "Anchoring interval should be greater than zero."
-        );
-        ensure!(
-            self.transaction_fee >= Self::MIN_TX_FEE,
-            "Transaction fee should be greater than {}",
-            Self::MIN_TX_FEE
-        );
+        if self.max_signature_age != 0 && self.max_signature_age < self.anchoring_interval {
+            problems.push(
+                "Maximal signature age should not be less than the anchoring interval.".to_owned(),
+            );
+        }
+        if self.max_signature_age > Self::MAX_SIGNATURE_AGE {
+            problems.push(format!(
+                "Maximal signature age should be less or equal than the {}.",
+                Self::MAX_SIGNATURE_AGE
+            ));
+        }
+
+        let mut tenant_keys = self.tenants.iter().map(|x| x.key).collect::<Vec<_>>();
+        tenant_keys.sort();
+        tenant_keys.dedup();
+        if tenant_keys.len() != self.tenants.len() {
+            problems.push("The list of tenants must not contain duplicate keys.".to_owned());
+        }
+
+        let mut auxiliary_services = HashSet::new();
+        if self
+            .auxiliary_services
+            .iter()
+            .any(|name| name.is_empty() || !auxiliary_services.insert(name))
+        {
+            problems.push(
+                "The list of auxiliary services must not contain empty or duplicate names."
+                    .to_owned(),
+            );
+        }
+        if self.commit_consensus_config && !self.auxiliary_services.is_empty() {
+            problems.push(
+                "The consensus configuration hash cannot be committed along with the auxiliary \
+                 state root."
+                    .to_owned(),
+            );
+        }
+
+        for address in &self.external_addresses {
+            if address.parse::<Address>().is_err() {
+                problems.push(format!("Malformed external address: {}.", address));
+            }
+        }
 
         // Verify that the redeem script is suitable.
-        RedeemScriptBuilder::with_public_keys(self.anchoring_keys.iter().map(|x| x.bitcoin_key.0))
+        if keys_count > 0 && keys_count <= Self::MAX_NODES_COUNT {
+            let redeem_script = RedeemScriptBuilder::with_public_keys(
+                self.anchoring_keys.iter().map(|x| x.bitcoin_key.0),
+            )
             .quorum(self.byzantine_quorum())
-            .to_script()?;
+            .to_script();
+            if let Err(e) = redeem_script {
+                problems.push(format!("Unsuitable redeem script: {}.", e));
+            }
+        }
+        problems
+    }
+}
+
+/// Computes the checksum of the output descriptor according to BIP-380. Returns `None`
+/// if the descriptor contains invalid characters.
+fn descriptor_checksum(descriptor: &str) -> Option<String> {
+    const INPUT_CHARSET: &str = "0123456789()[],'/*abcdefgh@:$%{}\
+                                 IJKLMNOPQRSTUVWXYZ&+-.;<=>?!^_|~\
+                                 ijklmnopqrstuvwxyzABCDEFGH`#\"\\ ";
+    const CHECKSUM_CHARSET: &[u8] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+    const GENERATORS: [u64; 5] = [
+        0xf5_dee5_1989,
+        0xa9_fdca_3312,
+        0x1b_ab10_e32d,
+        0x37_06b1_677a,
+        0x64_4d62_6ffd,
+    ];
+
+    let poly_mod = |c: u64, value: u64| {
+        let c0 = c >> 35;
+        let c = ((c & 0x7_ffff_ffff) << 5) ^ value;
+        GENERATORS
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| (c0 >> i) & 1 == 1)
+            .fold(c, |c, (_, generator)| c ^ generator)
+    };
+
+    let mut c = 1;
+    let mut class = 0;
+    let mut class_count = 0;
+    for ch in descriptor.chars() {
+        let position = INPUT_CHARSET.find(ch)? as u64;
+        c = poly_mod(c, position & 31);
+        class = class * 3 + (position >> 5);
+        class_count += 1;
+        if class_count == 3 {
+            c = poly_mod(c, class);
+            class = 0;
+            class_count = 0;
+        }
+    }
+    if class_count > 0 {
+        c = poly_mod(c, class);
+    }
+    for _ in 0..8 {
+        c = poly_mod(c, 0);
+    }
+    c ^= 1;
+
+    Some(
+        (0..8)
+            .map(|j| CHECKSUM_CHARSET[((c >> (5 * (7 - j))) & 31) as usize] as char)
+            .collect(),
+    )
+}
+
+impl ValidateInput for Config {
+    type Error = anyhow::Error;
+
+    fn validate(&self) -> Result<(), Self::Error> {
+        let problems = self.validation_problems();
+        ensure!(problems.is_empty(), "{}", problems.join(" "));
         Ok(())
     }
 }
@@ -155,9 +478,9 @@ mod tests {
     use bitcoin::network::constants::Network;
     use btc_transaction_utils::test_data::secp_gen_keypair;
 
-    use crate::proto::AnchoringKeys;
+    use crate::proto::{AnchoringKeys, Tenant};
 
-    use super::Config;
+    use super::{descriptor_checksum, Config, OutputType};
 
     fn gen_anchoring_keys(network: bitcoin::Network, count: usize) -> Vec<AnchoringKeys> {
         (0..count)
@@ -180,6 +503,105 @@ mod tests {
         assert_eq!(config2, config);
     }
 
+    #[test]
+    fn config_anchoring_descriptor() {
+        assert_eq!(
+            descriptor_checksum("raw(deadbeef)"),
+            Some("89f8spxm".to_owned())
+        );
+        assert_eq!(descriptor_checksum("raw(\u{e9})"), None);
+
+        let public_keys = gen_anchoring_keys(Network::Testnet, 4);
+        let config = Config::with_public_keys(Network::Testnet, public_keys.clone()).unwrap();
+        let descriptor = config.anchoring_descriptor();
+        let expected = format!(
+            "wsh(multi(3,{}))",
+            public_keys
+                .iter()
+                .map(|keys| keys.bitcoin_key.to_string())
+                .collect::<Vec<_>>()
+                .join(",")
+        );
+        assert_eq!(
+            descriptor,
+            format!("{}#{}", expected, descriptor_checksum(&expected).unwrap())
+        );
+    }
+
+    #[test]
+    fn config_output_type() {
+        let public_keys = gen_anchoring_keys(Network::Testnet, 4);
+        let config = Config::with_public_keys(Network::Testnet, public_keys).unwrap();
+        assert_eq!(config.output_type, OutputType::P2wsh);
+        assert_eq!(
+            OutputType::of_script(&config.anchoring_out_script()),
+            Some(OutputType::P2wsh)
+        );
+        assert_eq!(
+            OutputType::of_script(&config.anchoring_address().0.script_pubkey()),
+            Some(OutputType::P2wsh)
+        );
+        assert_eq!(OutputType::of_script(&bitcoin::Script::new()), None);
+
+        assert_eq!(
+            OutputType::from_code(OutputType::P2wsh.code()),
+            Some(OutputType::P2wsh)
+        );
+        assert_eq!(OutputType::from_code(1), None);
+
+        let json = serde_json::to_value(&config).unwrap();
+        assert_eq!(json["output_type"], "p2wsh");
+        // Configurations without the output type use P2WSH outputs.
+        let mut json = json;
+        json.as_object_mut().unwrap().remove("output_type");
+        let config2: Config = serde_json::from_value(json).unwrap();
+        assert_eq!(config2, config);
+    }
+
+    #[test]
+    fn config_key_possession() {
+        let (bitcoin_key, private_key) = secp_gen_keypair(Network::Testnet);
+        let keys = AnchoringKeys {
+            bitcoin_key: bitcoin_key.into(),
+            service_key: crypto::gen_keypair().0,
+        };
+        let mut config = Config::with_public_keys(Network::Testnet, vec![keys.clone()]).unwrap();
+        assert!(!config.verify_key_possession(&keys));
+
+        let proof = config.prove_key_possession(&keys.service_key, &private_key.into());
+        assert_eq!(proof.bitcoin_key, keys.bitcoin_key);
+        config.key_proofs.push(proof);
+        assert!(config.verify_key_possession(&keys));
+
+        // The proof is bound to the service key and to the configuration.
+        let other_keys = AnchoringKeys {
+            service_key: crypto::gen_keypair().0,
+            ..keys.clone()
+        };
+        assert!(!config.verify_key_possession(&other_keys));
+        config.transaction_fee += 1;
+        assert!(!config.verify_key_possession(&keys));
+    }
+
+    #[test]
+    fn config_find_anchoring_keys() {
+        let public_keys = gen_anchoring_keys(Network::Testnet, 4);
+        let mut config = Config::with_public_keys(Network::Testnet, public_keys.clone()).unwrap();
+        config.anchoring_keys.swap(0, 2);
+
+        for keys in &public_keys {
+            assert_eq!(config.find_anchoring_keys(&keys.service_key), Some(keys));
+            let position = config.redeem_script_position(&keys.bitcoin_key).unwrap();
+            assert_eq!(
+                config.redeem_script().content().public_keys[position as usize],
+                keys.bitcoin_key.0
+            );
+        }
+        assert_eq!(config.find_anchoring_keys(&crypto::gen_keypair().0), None);
+        let unknown_key = secp_gen_keypair(Network::Testnet).0.into();
+        assert_eq!(config.redeem_script_position(&unknown_key), None);
+    }
+
     #[test]
     fn config_anchoring_height() {
         let public_keys = gen_anchoring_keys(Network::Bitcoin, 4);
@@ -208,6 +630,10 @@ mod tests {
 
     #[test]
     fn config_validate_errors() {
+        let tenant = Tenant {
+            key: crypto::gen_keypair().0,
+            quota: 10,
+        };
         let test_cases = [
             (
                 Config::default(),
@@ -220,6 +646,17 @@ mod tests {
                 },
                 "Too many anchoring nodes: amount of anchoring nodes should be less or equal",
             ),
+            (
+                Config {
+                    anchoring_keys: {
+                        let mut keys = gen_anchoring_keys(bitcoin::Network::Regtest, 4);
+                        keys.push(keys[0].clone());
+                        keys
+                    },
+                    ..Config::default()
+                },
+                "The list of anchoring keys must not contain duplicate keys",
+            ),
             (
                 Config {
                     anchoring_keys: gen_anchoring_keys(bitcoin::Network::Regtest, 4),
@@ -244,6 +681,31 @@ mod tests {
                 },
                 "Transaction fee should be greater than",
             ),
+            (
+                Config {
+                    anchoring_keys: gen_anchoring_keys(bitcoin::Network::Regtest, 4),
+                    tenants: vec![tenant.clone(), tenant],
+                    ..Config::default()
+                },
+                "The list of tenants must not contain duplicate keys",
+            ),
+            (
+                Config {
+                    anchoring_keys: gen_anchoring_keys(bitcoin::Network::Regtest, 4),
+                    auxiliary_services: vec!["timestamping".to_owned(); 2],
+                    ..Config::default()
+                },
+                "The list of auxiliary services must not contain empty or duplicate names",
+            ),
+            (
+                Config {
+                    anchoring_keys: gen_anchoring_keys(bitcoin::Network::Regtest, 4),
+                    auxiliary_services: vec!["timestamping".to_owned()],
+                    commit_consensus_config: true,
+                    ..Config::default()
+                },
+                "The consensus configuration hash cannot be committed",
+            ),
         ];
 
         for (config, expected_err) in &test_cases {
@@ -251,4 +713,33 @@ mod tests {
             assert!(actual_err.contains(expected_err), actual_err);
         }
     }
+
+    #[test]
+    fn config_validation_problems() {
+        let config = Config {
+            anchoring_keys: gen_anchoring_keys(bitcoin::Network::Regtest, 4),
+            ..Config::default()
+        };
+        assert!(config.validation_problems().is_empty());
+
+        let config = Config {
+            anchoring_interval: 0,
+            transaction_fee: 0,
+            ..Config::default()
+        };
+        let problems = config.validation_problems();
+        assert_eq!(problems.len(), 3);
+        assert!(problems[0].contains("The list of anchoring keys must not be empty"));
+        assert!(problems[1].contains("Anchoring interval should be greater than zero"));
+        assert!(problems[2].contains("Transaction fee should be greater than"));
+
+        let config = Config {
+            anchoring_keys: gen_anchoring_keys(bitcoin::Network::Regtest, 4),
+            max_signature_age: u64::max_value(),
+            ..Config::default()
+        };
+        let problems = config.validation_problems();
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].contains("Maximal signature age should be less or equal than"));
+    }
 }
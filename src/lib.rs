@@ -58,11 +58,12 @@
     bare_trait_objects
 )]
 
-pub use crate::service::BtcAnchoringService;
+pub use crate::{blockchain::BtcAnchoringState, service::BtcAnchoringService};
 
 pub mod api;
 pub mod blockchain;
 pub mod btc;
+pub mod client;
 pub mod config;
 pub mod sync;
 pub mod test_helpers;
@@ -19,6 +19,7 @@ pub use binary_map::BinaryMap;
 use anyhow::anyhow;
 use exonum::{
     crypto::{proto::*, Hash, PublicKey},
+    helpers::Height,
     merkledb::{
         impl_object_hash_for_binary_value, impl_serde_hex_for_binary_value, BinaryKey, BinaryValue,
         ObjectHash,
@@ -31,7 +32,7 @@ use serde_derive::{Deserialize, Serialize};
 
 use std::borrow::Cow;
 
-use crate::btc;
+use crate::{btc, config::OutputType};
 
 mod binary_map;
 
@@ -124,6 +125,14 @@ pub struct SignInput {
     pub input: u32,
     /// Signature content.
     pub input_signature: btc::InputSignature,
+    /// Height of the blockchain anchored by the proposal.
+    pub anchoring_height: Height,
+    /// Anchoring epoch of the proposal.
+    pub epoch: u64,
+    /// Consensus encoding of the signed transaction, or empty bytes. Required to verify
+    /// the signature if the transaction differs from the anchoring proposal computed
+    /// by the service.
+    pub signed_transaction: Vec<u8>,
 }
 
 /// Exonum message with the unspent funding transaction.
@@ -134,6 +143,139 @@ pub struct AddFunds {
     pub transaction: btc::Transaction,
 }
 
+/// Exonum message with the external transaction, which spends the output of the latest
+/// anchoring transaction.
+#[derive(Debug, Clone, PartialEq, ProtobufConvert, BinaryValue, ObjectHash)]
+#[protobuf_convert(source = "self::service::ImportAnchor")]
+pub struct ImportAnchor {
+    /// Transaction content.
+    pub transaction: btc::Transaction,
+}
+
+/// Exonum message reporting that the anchoring transaction is buried deep enough
+/// in the Bitcoin blockchain.
+#[derive(Debug, Clone, PartialEq, ProtobufConvert, BinaryValue, ObjectHash)]
+#[protobuf_convert(source = "self::service::ConfirmAnchorFinality")]
+pub struct ConfirmAnchorFinality {
+    /// Anchoring transaction id.
+    pub txid: Sha256d,
+}
+
+/// Exonum message reporting the status of the latest anchoring transaction in the Bitcoin
+/// network observed by the anchoring node.
+#[derive(
+    Serialize, Deserialize, Debug, Clone, PartialEq, ProtobufConvert, BinaryValue, ObjectHash,
+)]
+#[protobuf_convert(source = "self::service::ReportAnchorStatus")]
+pub struct ReportAnchorStatus {
+    /// Anchoring transaction id.
+    pub txid: Sha256d,
+    /// Whether the transaction is known to the Bitcoin node, i.e. it is either
+    /// in the mempool or in the blockchain.
+    pub known: bool,
+    /// Number of the transaction confirmations, zero if it is in the mempool.
+    pub confirmations: u32,
+}
+
+/// Exonum message reporting the Bitcoin block which contains the anchoring transaction.
+#[derive(
+    Serialize, Deserialize, Debug, Clone, PartialEq, ProtobufConvert, BinaryValue, ObjectHash,
+)]
+#[protobuf_convert(source = "self::service::RecordAnchorBlock")]
+pub struct RecordAnchorBlock {
+    /// Anchoring transaction id.
+    pub txid: Sha256d,
+    /// Hash of the Bitcoin block containing the transaction.
+    pub block_hash: Sha256d,
+}
+
+/// Exonum message reporting the health of the Bitcoin relay used by the anchoring node.
+#[derive(
+    Serialize, Deserialize, Debug, Clone, PartialEq, ProtobufConvert, BinaryValue, ObjectHash,
+)]
+#[protobuf_convert(source = "self::service::ReportRelayStatus")]
+pub struct ReportRelayStatus {
+    /// Whether the latest health check of the relay has succeeded.
+    pub healthy: bool,
+    /// Height of the latest Bitcoin block known to the relay, zero if unknown.
+    pub block_height: u64,
+    /// Number of the consecutive failed health checks.
+    pub failed_checks: u32,
+}
+
+/// Exonum message with the hash of the tenant data to be anchored.
+#[derive(Debug, Clone, PartialEq, ProtobufConvert, BinaryValue, ObjectHash)]
+#[protobuf_convert(source = "self::service::SubmitData")]
+pub struct SubmitData {
+    /// Hash of the tenant data.
+    pub data_hash: Hash,
+}
+
+/// Exonum message with the Bitcoin key of a validator joining the anchoring.
+#[derive(Debug, Clone, PartialEq, ProtobufConvert, BinaryValue, ObjectHash)]
+#[protobuf_convert(source = "self::service::SubmitAnchoringKey")]
+pub struct SubmitAnchoringKey {
+    /// The Bitcoin public key of the validator.
+    pub bitcoin_key: btc::PublicKey,
+    /// Proof of possession of the Bitcoin key for the configuration resulting from
+    /// the inclusion of the key into the pending configuration.
+    pub key_proof: KeyPossessionProof,
+}
+
+/// Exonum message with the backup Bitcoin key of an anchoring node.
+#[derive(Debug, Clone, PartialEq, ProtobufConvert, BinaryValue, ObjectHash)]
+#[protobuf_convert(source = "self::service::RegisterStandbyKey")]
+pub struct RegisterStandbyKey {
+    /// The backup Bitcoin public key of the anchoring node.
+    pub bitcoin_key: btc::PublicKey,
+    /// Proof of possession of the backup key for the actual anchoring configuration.
+    pub key_proof: KeyPossessionProof,
+}
+
+/// Exonum message with the anchoring parameters proposed by an anchoring node.
+///
+/// Zero values leave the corresponding parameters unchanged.
+#[derive(
+    Serialize, Deserialize, Debug, Clone, PartialEq, ProtobufConvert, BinaryValue, ObjectHash,
+)]
+#[protobuf_convert(source = "self::service::UpdateParameters")]
+pub struct UpdateParameters {
+    /// Interval in blocks between anchored blocks.
+    #[serde(default)]
+    pub anchoring_interval: u64,
+    /// Fee per byte in satoshis.
+    #[serde(default)]
+    pub transaction_fee: u64,
+}
+
+/// Proof that the anchoring node possesses the private part of its Bitcoin key.
+///
+/// The proof is a signature of the challenge, which binds the Bitcoin key to the
+/// configuration and the service key of the anchoring node, see
+/// [`Config::key_possession_challenge`](../config/struct.Config.html#method.key_possession_challenge).
+#[derive(
+    Serialize, Deserialize, Debug, Clone, PartialEq, ProtobufConvert, BinaryValue, ObjectHash,
+)]
+#[protobuf_convert(source = "self::service::KeyPossessionProof")]
+pub struct KeyPossessionProof {
+    /// The Bitcoin public key of the anchoring node.
+    pub bitcoin_key: btc::PublicKey,
+    /// DER-encoded ECDSA signature of the key possession challenge.
+    pub signature: Vec<u8>,
+}
+
+/// Client application which is allowed to submit data for anchoring.
+#[derive(
+    Serialize, Deserialize, Debug, Clone, PartialEq, ProtobufConvert, BinaryValue, ObjectHash,
+)]
+#[protobuf_convert(source = "self::service::Tenant")]
+pub struct Tenant {
+    /// Key is used to authorize data submissions of the tenant.
+    pub key: PublicKey,
+    /// Maximum number of data hashes which can be submitted by the tenant.
+    pub quota: u64,
+}
+
 /// Consensus parameters in the BTC anchoring.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, BinaryValue, ObjectHash)]
 pub struct Config {
@@ -145,6 +287,72 @@ pub struct Config {
     pub anchoring_interval: u64,
     /// Fee per byte in satoshis.
     pub transaction_fee: u64,
+    /// Exonum blockchain height from which the anchoring begins.
+    #[serde(default)]
+    pub start_height: Height,
+    /// Skip anchoring if the blockchain state has not been changed since the latest anchor.
+    #[serde(default)]
+    pub anchor_only_on_state_change: bool,
+    /// Client applications which are allowed to submit data for anchoring.
+    #[serde(default)]
+    pub tenants: Vec<Tenant>,
+    /// Minimal balance in satoshis which should remain in the anchoring wallet.
+    #[serde(default)]
+    pub reserve_balance: u64,
+    /// Number of anchoring intervals without signatures of an anchoring node, after which
+    /// its standby key becomes active. Zero value disables the activation of standby keys.
+    #[serde(default)]
+    pub standby_activation_intervals: u64,
+    /// Names of the service instances, which state hashes are included into the anchoring
+    /// transaction payload.
+    #[serde(default)]
+    pub auxiliary_services: Vec<String>,
+    /// Minimal value in satoshis of the anchoring transaction change output. The anchoring
+    /// stops instead of creating the smaller change output. Zero value disables the check.
+    #[serde(default)]
+    pub dust_threshold: u64,
+    /// Maximal age in blocks of the anchoring proposal, which signatures are accepted.
+    /// The age of the proposal is counted from its anchoring height. Zero value disables
+    /// the limit, otherwise it should not exceed 10 000 000 blocks.
+    #[serde(default)]
+    pub max_signature_age: u64,
+    /// Proofs of possession of the Bitcoin keys added by this configuration. Proofs
+    /// are required for every anchoring key, which is absent in the actual configuration.
+    #[serde(default)]
+    pub key_proofs: Vec<KeyPossessionProof>,
+    /// Type of the output script of the anchoring transactions. Only P2WSH outputs
+    /// are supported at the moment.
+    #[serde(default)]
+    pub output_type: OutputType,
+    /// Allow the anchoring address to match the address of a previous anchoring epoch
+    /// or one of the known external addresses. Such a reuse weakens the audit trail,
+    /// so it should be allowed explicitly.
+    #[serde(default)]
+    pub allow_address_reuse: bool,
+    /// Known external Bitcoin addresses, for example, the exchange or custody ones,
+    /// which must not be used as the anchoring address.
+    #[serde(default)]
+    pub external_addresses: Vec<String>,
+    /// Include the hash of the actual consensus configuration into the anchoring
+    /// transaction payload, so that the changes of the validator set can be tracked
+    /// using the Bitcoin blockchain only. The auxiliary state root takes precedence
+    /// over the consensus configuration hash.
+    #[serde(default)]
+    pub commit_consensus_config: bool,
+    /// Number of blocks within which the high-risk maintenance operation, i.e. the import
+    /// of an external anchor, the update of the anchoring parameters or the registration
+    /// of a standby key, should be confirmed by the anchoring nodes. The window starts
+    /// with the first confirmation, and the confirmations are discarded once it has
+    /// expired. Zero value disables the limit.
+    #[serde(default)]
+    pub approval_window: u64,
+    /// Sort the funding inputs of the anchoring transactions according to BIP-69, so that
+    /// the proposals do not depend on the order of the funding transactions. The input
+    /// spending the previous anchoring transaction stays first, and the outputs are not
+    /// reordered. The ordering changes the proposals, so it should be enabled only after
+    /// all anchoring nodes have been upgraded.
+    #[serde(default)]
+    pub bip69_input_ordering: bool,
 }
 
 impl ProtobufConvert for Config {
@@ -157,23 +365,56 @@ impl ProtobufConvert for Config {
         proto_struct.set_anchoring_keys(self.anchoring_keys.to_pb().into());
         proto_struct.set_anchoring_interval(self.anchoring_interval.to_pb());
         proto_struct.set_transaction_fee(self.transaction_fee.to_pb());
+        proto_struct.set_start_height(self.start_height.0);
+        proto_struct.set_anchor_only_on_state_change(self.anchor_only_on_state_change);
+        proto_struct.set_tenants(self.tenants.to_pb().into());
+        proto_struct.set_reserve_balance(self.reserve_balance);
+        proto_struct.set_standby_activation_intervals(self.standby_activation_intervals);
+        proto_struct.set_auxiliary_services(self.auxiliary_services.clone().into());
+        proto_struct.set_dust_threshold(self.dust_threshold);
+        proto_struct.set_max_signature_age(self.max_signature_age);
+        proto_struct.set_key_proofs(self.key_proofs.to_pb().into());
+        proto_struct.set_output_type(self.output_type.code());
+        proto_struct.set_allow_address_reuse(self.allow_address_reuse);
+        proto_struct.set_external_addresses(self.external_addresses.clone().into());
+        proto_struct.set_commit_consensus_config(self.commit_consensus_config);
+        proto_struct.set_approval_window(self.approval_window);
+        proto_struct.set_bip69_input_ordering(self.bip69_input_ordering);
         proto_struct
     }
 
     fn from_pb(mut pb: Self::ProtoStruct) -> anyhow::Result<Self> {
         let network = bitcoin::Network::from_magic(pb.get_network())
             .ok_or_else(|| anyhow!("Unknown Bitcoin network"))?;
+        let output_type = OutputType::from_code(pb.get_output_type())
+            .ok_or_else(|| anyhow!("Unsupported anchoring output type"))?;
 
         Ok(Self {
             network,
             anchoring_keys: ProtobufConvert::from_pb(pb.take_anchoring_keys().into_vec())?,
             anchoring_interval: ProtobufConvert::from_pb(pb.get_anchoring_interval())?,
             transaction_fee: ProtobufConvert::from_pb(pb.get_transaction_fee())?,
+            start_height: Height(pb.get_start_height()),
+            anchor_only_on_state_change: pb.get_anchor_only_on_state_change(),
+            tenants: ProtobufConvert::from_pb(pb.take_tenants().into_vec())?,
+            reserve_balance: pb.get_reserve_balance(),
+            standby_activation_intervals: pb.get_standby_activation_intervals(),
+            auxiliary_services: pb.take_auxiliary_services().into_vec(),
+            dust_threshold: pb.get_dust_threshold(),
+            max_signature_age: pb.get_max_signature_age(),
+            key_proofs: ProtobufConvert::from_pb(pb.take_key_proofs().into_vec())?,
+            output_type,
+            allow_address_reuse: pb.get_allow_address_reuse(),
+            external_addresses: pb.take_external_addresses().into_vec(),
+            commit_consensus_config: pb.get_commit_consensus_config(),
+            approval_window: pb.get_approval_window(),
+            bip69_input_ordering: pb.get_bip69_input_ordering(),
         })
     }
 }
 
 impl_serde_hex_for_binary_value! { SignInput }
+impl_serde_hex_for_binary_value! { SubmitAnchoringKey }
 
 impl BinaryValue for btc::Sha256d {
     fn to_bytes(&self) -> Vec<u8> {
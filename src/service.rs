@@ -14,7 +14,7 @@
 
 use exonum::{
     helpers::ValidateInput,
-    merkledb::BinaryValue,
+    merkledb::{access::Access, BinaryValue},
     runtime::{CommonError, ExecutionContext, ExecutionError},
 };
 use exonum_derive::{ServiceDispatcher, ServiceFactory};
@@ -23,7 +23,8 @@ use exonum_supervisor::Configure;
 
 use crate::{
     api,
-    blockchain::{BtcAnchoringInterface, Schema},
+    blockchain::{errors::Error, BtcAnchoringInterface, Schema},
+    btc,
     config::Config,
     proto,
 };
@@ -34,20 +35,139 @@ use crate::{
 #[service_factory(proto_sources = "proto")]
 pub struct BtcAnchoringService;
 
+impl BtcAnchoringService {
+    /// Verifies that the given configuration can replace the actual one, if any.
+    pub(crate) fn verify_params(
+        actual_config: Option<&Config>,
+        params: &Config,
+    ) -> Result<(), ExecutionError> {
+        if let Some(actual_config) = actual_config {
+            if params.network != actual_config.network {
+                return Err(Error::NetworkMismatch.with_description(format!(
+                    "Expected {} network, but {} given.",
+                    actual_config.network, params.network
+                )));
+            }
+        }
+        let keys_count = params.anchoring_keys.len();
+        if keys_count == 0 || keys_count > Config::MAX_NODES_COUNT {
+            return Err(Error::InvalidAnchoringKeysCount.with_description(format!(
+                "Amount of anchoring keys should be in range from 1 to {}, but {} given.",
+                Config::MAX_NODES_COUNT,
+                keys_count
+            )));
+        }
+        if params.has_duplicate_keys() {
+            return Err(Error::DuplicateAnchoringKeys.into());
+        }
+        // Keys added by the configuration should be accompanied by the proofs of their
+        // possession to prevent rogue or mistyped keys.
+        let added_keys = params.anchoring_keys.iter().filter(|keys| {
+            actual_config.map_or(true, |actual_config| {
+                !actual_config.anchoring_keys.contains(keys)
+            })
+        });
+        for keys in added_keys {
+            if !params.verify_key_possession(keys) {
+                return Err(Error::MissingKeyPossessionProof.with_description(format!(
+                    "Bitcoin key {} of the anchoring node with the service key {} \
+                     is not accompanied by the valid proof of possession.",
+                    keys.bitcoin_key, keys.service_key
+                )));
+            }
+        }
+
+        params.validate().map_err(CommonError::malformed_arguments)
+    }
+
+    /// Verifies that the anchoring address of the given configuration is not reused,
+    /// unless the reuse is allowed by the configuration explicitly.
+    pub(crate) fn verify_address_reuse(
+        schema: &Schema<impl Access>,
+        params: &Config,
+    ) -> Result<(), ExecutionError> {
+        match schema.address_reuse(params) {
+            Some(reuse) if params.allow_address_reuse => {
+                log::warn!("{} The reuse is allowed by the configuration.", reuse);
+                Ok(())
+            }
+            Some(reuse) => Err(Error::AddressReuse.with_description(format!(
+                "{} Set `allow_address_reuse` to use it anyway.",
+                reuse
+            ))),
+            None => Ok(()),
+        }
+    }
+}
+
 impl Service for BtcAnchoringService {
     fn initialize(
         &self,
         context: ExecutionContext<'_>,
         params: Vec<u8>,
     ) -> Result<(), ExecutionError> {
+        let mut schema = Schema::new(context.service_data());
+        schema.payload_version.set(btc::PAYLOAD_VERSION);
+        // The service may be started without the configuration, in this case
+        // the first configuration is applied later via the supervisor.
+        if params.is_empty() {
+            return Ok(());
+        }
+
         // TODO Use a special type for constructor. [ECR-3222]
         let config = Config::from_bytes(params.into())
             .and_then(ValidateInput::into_validated)
             .map_err(CommonError::malformed_arguments)?;
 
-        Schema::new(context.service_data())
-            .actual_config
-            .set(config);
+        schema.record_used_address(&config);
+        schema.actual_config.set(config);
+        Ok(())
+    }
+
+    fn resume(&self, context: ExecutionContext<'_>, params: Vec<u8>) -> Result<(), ExecutionError> {
+        // The service may be resumed with the new configuration, otherwise
+        // the configuration remains the same.
+        if params.is_empty() {
+            return Ok(());
+        }
+
+        let config = Config::from_bytes(params.into()).map_err(CommonError::malformed_arguments)?;
+        let mut schema = Schema::new(context.service_data());
+        Self::verify_params(schema.try_actual_config().as_ref(), &config)?;
+        Self::verify_address_reuse(&schema, &config)?;
+        schema.apply_config(config);
+        Ok(())
+    }
+
+    fn after_transactions(&self, context: ExecutionContext<'_>) -> Result<(), ExecutionError> {
+        let core_schema = context.data().for_core();
+        let mut schema = Schema::new(context.service_data());
+        // There is nothing to do until the service is configured.
+        if schema.try_actual_config().is_none() {
+            return Ok(());
+        }
+
+        schema.record_auxiliary_root(context.data().for_core());
+        schema.record_consensus_config_hash(context.data().for_core());
+        schema.skip_unchanged_anchoring_height(core_schema);
+        schema.skip_stale_anchoring_height(context.data().for_core());
+        schema.compact_expired_proposals(context.data().for_core());
+        schema.compact_expired_approvals(context.data().for_core().height());
+        schema.backfill_chain_indexes();
+        schema.schedule_standby_key();
+        // The pending configuration is verified once again, since the actual configuration
+        // may have been changed after the approval of the anchoring keys.
+        if let Some(config) = schema.pending_transition() {
+            let verified = Self::verify_params(schema.try_actual_config().as_ref(), &config)
+                .and_then(|()| Self::verify_address_reuse(&schema, &config));
+            match verified {
+                Ok(()) => schema.schedule_pending_config(config),
+                Err(e) => {
+                    log::warn!("Approved anchoring keys have been discarded: {}", e);
+                    schema.discard_pending_keys();
+                }
+            }
+        }
         Ok(())
     }
 
@@ -69,7 +189,9 @@ impl Configure for BtcAnchoringService {
             .as_supervisor()
             .ok_or(CommonError::UnauthorizedCaller)?;
 
-        params.validate().map_err(CommonError::malformed_arguments)
+        let schema = Schema::new(context.service_data());
+        Self::verify_params(schema.try_actual_config().as_ref(), &params)?;
+        Self::verify_address_reuse(&schema, &params)
     }
 
     fn apply_config(
@@ -82,16 +204,17 @@ impl Configure for BtcAnchoringService {
             .as_supervisor()
             .ok_or(CommonError::UnauthorizedCaller)?;
 
+        // If there are changes in the anchoring address, the config becomes an actual
+        // after the transition of the anchoring chain to the following address.
         let mut schema = Schema::new(context.service_data());
-        if schema.actual_config().anchoring_address() == params.anchoring_address() {
-            // There are no changes in the anchoring address, so we just apply the config
-            // immediately.
-            schema.actual_config.set(params);
-        } else {
-            // Set the config as the next one, which will become an actual after the transition
-            // of the anchoring chain to the following address.
-            schema.following_config.set(params);
-        }
+        schema.supersede_anchoring_proposal(context.data().for_core());
+        schema.apply_config(params);
+        // The proofs of possession of the submitted anchoring keys are bound to
+        // the replaced configuration, so the keys should be submitted again.
+        schema.discard_submitted_keys();
+        // The configuration change approved by the validators resolves the conflicting
+        // proposals, so the finalization of the anchoring proposal is resumed.
+        schema.lift_anchoring_halt();
         Ok(())
     }
 }
@@ -15,13 +15,31 @@
 //! Collections of helpers for synchronization with the Bitcoin network.
 
 use async_trait::async_trait;
+use bitcoin::OutPoint;
+use bitcoin_hashes::sha256d;
 use bitcoincore_rpc::RpcApi;
+use hex::FromHex;
 use jsonrpc::Error as JsonRpcError;
+use rand::{thread_rng, Rng};
+use serde_derive::{Deserialize, Serialize};
+use thiserror::Error;
 
-use crate::btc;
+use std::{
+    fmt::{self, Display},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, RwLock,
+    },
+    time::Duration,
+};
+
+use crate::{api::v1::AnchoringDescriptor, btc};
+
+use super::BitcoinRpcClient;
 
 /// Status of the transaction in the Bitcoin network.
-#[derive(Debug, Copy, Clone, PartialEq)]
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum TransactionStatus {
     /// Transaction is unknown in the Bitcoin network.
     Unknown,
@@ -30,6 +48,9 @@ pub enum TransactionStatus {
     /// The transaction was completed to the Bitcoin blockchain with the specified number
     /// of confirmations.
     Committed(u32),
+    /// The transaction was not sent to the Bitcoin network, since the sync task
+    /// works in the dry-run mode.
+    Simulated,
 }
 
 impl TransactionStatus {
@@ -48,46 +69,1734 @@ impl TransactionStatus {
     }
 }
 
-/// Describes communication with the Bitcoin network node.
-#[async_trait]
-pub trait BitcoinRelay {
+/// Entry of the transaction in the Bitcoin node mempool.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MempoolEntry {
+    /// Virtual size of the transaction in bytes.
+    pub vsize: u64,
+    /// Fee of the transaction in satoshis.
+    pub fee: u64,
+}
+
+impl MempoolEntry {
+    /// Returns the fee rate of the transaction in satoshis per virtual byte.
+    pub fn fee_rate(&self) -> u64 {
+        self.fee / self.vsize.max(1)
+    }
+}
+
+/// JSON-RPC error code returned by the Bitcoin node for unsupported methods.
+const RPC_METHOD_NOT_FOUND: i32 = -32601;
+/// JSON-RPC error code returned by the Bitcoin node while it is starting, for example,
+/// loading the block index or the wallet.
+const RPC_IN_WARMUP: i32 = -28;
+
+/// Unspent output of the anchoring address.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WalletUtxo {
+    /// Identifier of the transaction with the output.
+    pub txid: btc::Sha256d,
+    /// Index of the output in the transaction.
+    pub vout: u32,
+    /// Output value in satoshis.
+    pub value: u64,
+    /// Number of the transaction confirmations, zero for the mempool transactions.
+    pub confirmations: u32,
+}
+
+/// Optional features of the Bitcoin node, which are detected at the relay startup.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct RelayCapabilities {
+    /// The node is able to estimate the transaction fee (`estimatesmartfee`).
+    pub fee_estimation: bool,
+    /// The node wallet is a descriptor wallet.
+    pub descriptor_wallet: bool,
+    /// The node maintains the full transaction index (`-txindex`).
+    pub txindex: bool,
+    /// The node publishes ZMQ notifications.
+    pub zmq: bool,
+}
+
+impl RelayCapabilities {
+    /// Probes the Bitcoin node for the supported features.
+    ///
+    /// Methods which are unknown to the node are treated as unsupported features,
+    /// other errors are returned to the caller.
+    pub fn probe(client: &BitcoinRpcClient) -> Result<Self, bitcoincore_rpc::Error> {
+        let call = |method: &str, args: &[serde_json::Value]| match client
+            .call::<serde_json::Value>(method, args)
+        {
+            Ok(value) => Ok(Some(value)),
+            Err(bitcoincore_rpc::Error::JsonRpc(JsonRpcError::Rpc(ref e)))
+                if e.code == RPC_METHOD_NOT_FOUND =>
+            {
+                Ok(None)
+            }
+            Err(e) => Err(e),
+        };
+
+        // Fee estimation may lack data on a fresh node, but the method itself is supported.
+        let fee_estimation = match call("estimatesmartfee", &[6.into()]) {
+            Ok(value) => value.is_some(),
+            Err(bitcoincore_rpc::Error::JsonRpc(JsonRpcError::Rpc(_))) => true,
+            Err(e) => return Err(e),
+        };
+        let descriptor_wallet = call("getwalletinfo", &[])
+            .unwrap_or_default()
+            .and_then(|info| info["descriptors"].as_bool())
+            .unwrap_or_default();
+        let txindex = call("getindexinfo", &[])?
+            .map(|info| info.get("txindex").is_some())
+            .unwrap_or_default();
+        let zmq = call("getzmqnotifications", &[])?
+            .and_then(|notifications| notifications.as_array().map(|x| !x.is_empty()))
+            .unwrap_or_default();
+
+        Ok(Self {
+            fee_estimation,
+            descriptor_wallet,
+            txindex,
+            zmq,
+        })
+    }
+}
+
+/// Kinds of the mempool policy rejections.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RejectionKind {
+    /// Transaction output value is below the dust threshold.
+    Dust,
+    /// Transaction fee is below the minimal fee accepted by the mempool.
+    FeeTooLow,
+    /// Transaction is non-standard.
+    NonStandard,
+    /// Transaction conflicts with another transaction in the mempool.
+    Conflict,
+    /// Transaction spends missing or already spent outputs.
+    MissingInputs,
+    /// Transaction is rejected due to another reason.
+    Other,
+}
+
+/// Mempool policy rejection reported by the Bitcoin node.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MempoolRejection {
+    /// Kind of the rejection.
+    pub kind: RejectionKind,
+    /// Rejection reason reported by the Bitcoin node.
+    pub reason: String,
+}
+
+impl MempoolRejection {
+    /// Reasons which mean that the transaction is already known by the node.
+    const ALREADY_KNOWN: &'static [&'static str] = &[
+        "txn-already-in-mempool",
+        "txn-already-known",
+        "txn-same-nonwitness-data-in-mempool",
+    ];
+    /// Reasons of the transactions which are rejected by the standardness policy.
+    const NON_STANDARD: &'static [&'static str] = &[
+        "version",
+        "tx-size",
+        "scriptsig-size",
+        "scriptsig-not-pushonly",
+        "scriptpubkey",
+        "bare-multisig",
+        "multi-op-return",
+        "non-standard",
+    ];
+
+    /// Creates a rejection from the reason reported by the Bitcoin node.
+    pub fn from_reason(reason: impl Into<String>) -> Self {
+        let reason = reason.into();
+        let kind = if reason.contains("dust") {
+            RejectionKind::Dust
+        } else if reason.contains("fee") {
+            RejectionKind::FeeTooLow
+        } else if reason.contains("conflict") {
+            RejectionKind::Conflict
+        } else if reason.contains("missing") {
+            RejectionKind::MissingInputs
+        } else if Self::NON_STANDARD.iter().any(|x| reason.starts_with(x)) {
+            RejectionKind::NonStandard
+        } else {
+            RejectionKind::Other
+        };
+        Self { kind, reason }
+    }
+
+    pub(super) fn is_already_known(reason: &str) -> bool {
+        Self::ALREADY_KNOWN.contains(&reason)
+    }
+}
+
+impl Display for MempoolRejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?} ({})", self.kind, self.reason)
+    }
+}
+
+/// Errors of the Bitcoin RPC relay.
+#[derive(Debug, Error)]
+pub enum RelayError {
+    /// Error occurred in the Bitcoin RPC client.
+    #[error("{0}")]
+    Rpc(#[from] bitcoincore_rpc::Error),
+    /// Transaction has been rejected by the mempool policy of the Bitcoin node.
+    #[error("Transaction {txid} has been rejected by the Bitcoin node mempool: {rejection}")]
+    Rejected {
+        /// Identifier of the rejected transaction.
+        txid: btc::Sha256d,
+        /// Rejection details.
+        rejection: MempoolRejection,
+    },
+    /// Bitcoin node wallet is unable to watch the anchoring address.
+    #[error("Unable to watch the address {address}: {reason}")]
+    WatchFailed {
+        /// Anchoring address.
+        address: btc::Address,
+        /// Error reported by the Bitcoin node.
+        reason: String,
+    },
+    /// Blocking RPC request has been interrupted, for example, due to the runtime shutdown.
+    #[error("Bitcoin RPC request has been interrupted: {0}")]
+    Interrupted(#[from] tokio::task::JoinError),
+    /// Bitcoin RPC request has not been completed before its deadline, for example,
+    /// because the Bitcoin node hangs.
+    #[error("Bitcoin RPC request has not been completed in {0:?}")]
+    Timeout(Duration),
+}
+
+impl RelayError {
+    /// Checks whether the error is transient, that is, the failed request may succeed
+    /// if it is retried later. For example, the Bitcoin node may be unreachable during
+    /// the restart, or may be still loading the wallet.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            RelayError::Rpc(bitcoincore_rpc::Error::JsonRpc(JsonRpcError::Rpc(e))) => {
+                e.code == RPC_IN_WARMUP
+            }
+            RelayError::Rpc(bitcoincore_rpc::Error::JsonRpc(JsonRpcError::Json(_))) => false,
+            // Transport errors.
+            RelayError::Rpc(bitcoincore_rpc::Error::JsonRpc(_))
+            | RelayError::Rpc(bitcoincore_rpc::Error::Io(_))
+            | RelayError::Timeout(_) => true,
+            _ => false,
+        }
+    }
+}
+
+/// Policy of the retries of the Bitcoin RPC requests, which have failed due to
+/// the transient errors.
+///
+/// The delay before each next retry is doubled up to the upper bound. The delay is
+/// randomized between the half and the whole of its value, so the anchoring nodes
+/// sharing the Bitcoin node do not retry the requests simultaneously.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RetryPolicy {
+    /// Maximal number of the retries of a single request.
+    pub max_retries: u32,
+    /// Delay before the first retry in milliseconds.
+    pub initial_delay_ms: u64,
+    /// Upper bound of the delay between the retries in milliseconds.
+    pub max_delay_ms: u64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 5,
+            initial_delay_ms: 500,
+            max_delay_ms: 10_000,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Returns the policy which does not retry the requests.
+    pub fn never() -> Self {
+        Self {
+            max_retries: 0,
+            ..Self::default()
+        }
+    }
+
+    /// Returns the randomized delay before the retry with the given number, starting
+    /// from zero.
+    pub fn delay(&self, retry: u32) -> Duration {
+        let backoff = self
+            .initial_delay_ms
+            .saturating_mul(1_u64.checked_shl(retry).unwrap_or(u64::max_value()))
+            .min(self.max_delay_ms);
+        let half = backoff / 2;
+        Duration::from_millis(backoff - half + thread_rng().gen_range(0, half + 1))
+    }
+}
+
+/// Deadlines of the Bitcoin RPC requests depending on the request type.
+///
+/// The request, which has not been completed before its deadline, fails with
+/// the transient error, so it is retried according to the [retry policy], possibly
+/// with a fallback node. The RPC client performs the requests on the blocking threads,
+/// which cannot be interrupted, so the thread of the expired request is released
+/// only when the connection is closed by the operating system.
+///
+/// [retry policy]: struct.RetryPolicy.html
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RpcTimeouts {
+    /// Deadline of the connection to the Bitcoin node in milliseconds, that is,
+    /// of probing the node capabilities on the failover and reconnection.
+    pub connect_timeout_ms: u64,
+    /// Deadline of the regular requests in milliseconds, such as the transaction
+    /// broadcasts and the blockchain queries.
+    pub read_timeout_ms: u64,
+    /// Deadline of the imports of the anchoring addresses into the node wallet
+    /// in milliseconds. Imports may rescan the blockchain, which takes much longer
+    /// than the regular requests.
+    pub import_timeout_ms: u64,
+}
+
+impl Default for RpcTimeouts {
+    fn default() -> Self {
+        Self {
+            connect_timeout_ms: 10_000,
+            read_timeout_ms: 60_000,
+            import_timeout_ms: 3_600_000,
+        }
+    }
+}
+
+impl RpcTimeouts {
+    /// Returns the deadline of the connection to the Bitcoin node.
+    pub fn connect_timeout(&self) -> Duration {
+        Duration::from_millis(self.connect_timeout_ms)
+    }
+
+    /// Returns the deadline of the regular requests.
+    pub fn read_timeout(&self) -> Duration {
+        Duration::from_millis(self.read_timeout_ms)
+    }
+
+    /// Returns the deadline of the imports into the node wallet.
+    pub fn import_timeout(&self) -> Duration {
+        Duration::from_millis(self.import_timeout_ms)
+    }
+}
+
+/// Backend of the Bitcoin relay, which provides some of the relay capabilities.
+///
+/// The capabilities are split into the separate traits, so the backends which lack
+/// some of them (for example, block explorers without the wallet, or broadcast-only relays)
+/// can be combined with each other, see [`ComposedRelay`].
+///
+/// [`ComposedRelay`]: struct.ComposedRelay.html
+pub trait RelayBackend {
     /// Error type for the current Bitcoin relay implementation.
     type Error;
+}
+
+/// Relay capability to broadcast transactions to the Bitcoin network.
+#[async_trait]
+pub trait TxBroadcaster: RelayBackend {
     /// Sends a raw transaction to the Bitcoin network node.
     async fn send_transaction(
         &self,
         transaction: &btc::Transaction,
     ) -> Result<btc::Sha256d, Self::Error>;
+}
+
+/// Relay capability to query the Bitcoin blockchain.
+#[async_trait]
+pub trait ChainQuery: RelayBackend {
     /// Gets status for the transaction with the specified identifier.
     async fn transaction_status(&self, id: btc::Sha256d) -> Result<TransactionStatus, Self::Error>;
+    /// Gets statuses for the transactions with the specified identifiers in the same order.
+    ///
+    /// The default implementation queries the statuses one by one, relays which support
+    /// the request batching get all of them in a single round trip.
+    async fn transaction_statuses(
+        &self,
+        ids: &[btc::Sha256d],
+    ) -> Result<Vec<TransactionStatus>, Self::Error> {
+        let mut statuses = Vec::with_capacity(ids.len());
+        for &id in ids {
+            statuses.push(self.transaction_status(id).await?);
+        }
+        Ok(statuses)
+    }
+    /// Finds the transaction which spends the given output. Returns `None` if the output
+    /// is unspent, or the relay is unable to find the spending transaction.
+    async fn spending_transaction(
+        &self,
+        _outpoint: OutPoint,
+    ) -> Result<Option<btc::Transaction>, Self::Error> {
+        Ok(None)
+    }
+    /// Estimates the fee rate in satoshis per virtual byte, at which a transaction is
+    /// confirmed within the given number of blocks. Returns `None` if the relay is
+    /// unable to estimate the fee rate, for example, due to insufficient data.
+    async fn fee_rate(&self, _target_blocks: u16) -> Result<Option<u64>, Self::Error> {
+        Ok(None)
+    }
+    /// Gets the mempool entry of the transaction with the specified identifier. Returns
+    /// `None` if the transaction is not in the mempool, or the relay is unable to inspect
+    /// the mempool.
+    async fn mempool_entry(&self, _id: btc::Sha256d) -> Result<Option<MempoolEntry>, Self::Error> {
+        Ok(None)
+    }
+    /// Gets the hash of the block containing the transaction with the specified identifier.
+    /// Returns `None` if the transaction is unconfirmed, or the relay is unable to find
+    /// its block.
+    async fn transaction_block_hash(
+        &self,
+        _id: btc::Sha256d,
+    ) -> Result<Option<btc::Sha256d>, Self::Error> {
+        Ok(None)
+    }
+    /// Gets status for the transaction with the specified identifier, which is contained
+    /// in the block with the given hash. Unlike [`transaction_status`], the lookup in the known
+    /// block does not require the full transaction index, so the pruned Bitcoin nodes are able
+    /// to find the transaction while the block is within their prune window.
+    ///
+    /// The default implementation ignores the block hash.
+    ///
+    /// [`transaction_status`]: #tymethod.transaction_status
+    async fn transaction_status_in_block(
+        &self,
+        id: btc::Sha256d,
+        _block_hash: btc::Sha256d,
+    ) -> Result<TransactionStatus, Self::Error> {
+        self.transaction_status(id).await
+    }
+    /// Checks whether the relay is able to query the Bitcoin blockchain, and gets the height
+    /// of the latest block known to the relay. Returns `None` if the relay is unable to get
+    /// the block height.
+    ///
+    /// This method is used as the periodic health check of the relay, so the relays
+    /// which are able to restore the lost connection should do it here. The default
+    /// implementation always succeeds.
+    async fn ping(&self) -> Result<Option<u64>, Self::Error> {
+        Ok(None)
+    }
+}
+
+/// Relay capability to manage the Bitcoin node wallet, which watches the anchoring addresses.
+#[async_trait]
+pub trait WalletOps: RelayBackend {
+    /// Makes the node wallet watch the given anchoring address without the rescan, so
+    /// the wallet tracks the transactions sent to the address from now on.
+    async fn watch_address(
+        &self,
+        descriptor: &AnchoringDescriptor,
+        label: &str,
+    ) -> Result<(), Self::Error>;
+    /// Makes the node wallet watch all of the given anchoring addresses, see
+    /// [`watch_address`](#tymethod.watch_address).
+    ///
+    /// The default implementation imports the addresses one by one, relays which support
+    /// the request batching import all of them in a single round trip.
+    async fn watch_addresses(
+        &self,
+        descriptors: &[AnchoringDescriptor],
+        label: &str,
+    ) -> Result<(), Self::Error> {
+        for descriptor in descriptors {
+            self.watch_address(descriptor, label).await?;
+        }
+        Ok(())
+    }
+    /// Returns the unspent outputs of the given anchoring address including the unconfirmed
+    /// ones. The address should be watched by the node wallet.
+    async fn unspent_outputs(&self, address: &btc::Address)
+        -> Result<Vec<WalletUtxo>, Self::Error>;
+    /// Restores the watch data of the node wallet, which has been lost, for example, if
+    /// the anchoring node has been restored from a backup, but the Bitcoin node wallet
+    /// is a fresh one.
+    ///
+    /// If the wallet does not know the latest anchoring transaction, the anchoring address
+    /// is imported into the wallet and the blocks are rescanned from the block of the initial
+    /// funding transaction, or from the given fallback height, if the block of the funding
+    /// transaction cannot be found.
+    ///
+    /// Returns the height of the block from which the rescan has started, or `None` if
+    /// the watch data is intact.
+    async fn restore_watch_data(
+        &self,
+        descriptor: &AnchoringDescriptor,
+        label: &str,
+        latest_anchor: btc::Sha256d,
+        funding_tx: btc::Sha256d,
+        fallback_height: Option<u64>,
+    ) -> Result<Option<u64>, Self::Error>;
+}
+
+/// Describes communication with the Bitcoin network node, which is able both to broadcast
+/// the transactions and to query the Bitcoin blockchain. This trait is implemented
+/// automatically for such relays.
+pub trait BitcoinRelay: TxBroadcaster + ChainQuery {}
+
+impl<T: TxBroadcaster + ChainQuery> BitcoinRelay for T {}
+
+/// Bitcoin relay composed of the separate broadcasting and query backends, for example,
+/// of a broadcast-only relay and a block explorer.
+#[derive(Debug, Clone)]
+pub struct ComposedRelay<B, Q> {
+    broadcaster: B,
+    query: Q,
+}
+
+impl<B, Q> ComposedRelay<B, Q> {
+    /// Creates a relay, which broadcasts the transactions via the first backend and
+    /// queries the Bitcoin blockchain via the second one.
+    pub fn new(broadcaster: B, query: Q) -> Self {
+        Self { broadcaster, query }
+    }
+
+    /// Returns the broadcasting backend.
+    pub fn broadcaster(&self) -> &B {
+        &self.broadcaster
+    }
+
+    /// Returns the query backend.
+    pub fn query(&self) -> &Q {
+        &self.query
+    }
+}
+
+/// Errors of the composed relay.
+#[derive(Debug)]
+pub enum ComposedRelayError<B, Q> {
+    /// Error occurred in the broadcasting backend.
+    Broadcast(B),
+    /// Error occurred in the query backend.
+    Query(Q),
+}
+
+impl<B: Display, Q: Display> Display for ComposedRelayError<B, Q> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ComposedRelayError::Broadcast(e) => write!(f, "Broadcasting backend error: {}", e),
+            ComposedRelayError::Query(e) => write!(f, "Query backend error: {}", e),
+        }
+    }
+}
+
+impl<B: RelayBackend, Q: RelayBackend> RelayBackend for ComposedRelay<B, Q> {
+    type Error = ComposedRelayError<B::Error, Q::Error>;
 }
 
 #[async_trait]
-impl BitcoinRelay for bitcoincore_rpc::Client {
+impl<B, Q> TxBroadcaster for ComposedRelay<B, Q>
+where
+    B: TxBroadcaster + Send + Sync,
+    Q: RelayBackend + Send + Sync,
+    B::Error: Send,
+    Q::Error: Send,
+{
+    async fn send_transaction(
+        &self,
+        transaction: &btc::Transaction,
+    ) -> Result<btc::Sha256d, Self::Error> {
+        self.broadcaster
+            .send_transaction(transaction)
+            .await
+            .map_err(ComposedRelayError::Broadcast)
+    }
+}
+
+#[async_trait]
+impl<B, Q> ChainQuery for ComposedRelay<B, Q>
+where
+    B: RelayBackend + Send + Sync,
+    Q: ChainQuery + Send + Sync,
+    B::Error: Send,
+    Q::Error: Send,
+{
+    async fn transaction_status(&self, id: btc::Sha256d) -> Result<TransactionStatus, Self::Error> {
+        self.query
+            .transaction_status(id)
+            .await
+            .map_err(ComposedRelayError::Query)
+    }
+
+    async fn transaction_statuses(
+        &self,
+        ids: &[btc::Sha256d],
+    ) -> Result<Vec<TransactionStatus>, Self::Error> {
+        self.query
+            .transaction_statuses(ids)
+            .await
+            .map_err(ComposedRelayError::Query)
+    }
+
+    async fn spending_transaction(
+        &self,
+        outpoint: OutPoint,
+    ) -> Result<Option<btc::Transaction>, Self::Error> {
+        self.query
+            .spending_transaction(outpoint)
+            .await
+            .map_err(ComposedRelayError::Query)
+    }
+
+    async fn fee_rate(&self, target_blocks: u16) -> Result<Option<u64>, Self::Error> {
+        self.query
+            .fee_rate(target_blocks)
+            .await
+            .map_err(ComposedRelayError::Query)
+    }
+
+    async fn mempool_entry(&self, id: btc::Sha256d) -> Result<Option<MempoolEntry>, Self::Error> {
+        self.query
+            .mempool_entry(id)
+            .await
+            .map_err(ComposedRelayError::Query)
+    }
+
+    async fn transaction_block_hash(
+        &self,
+        id: btc::Sha256d,
+    ) -> Result<Option<btc::Sha256d>, Self::Error> {
+        self.query
+            .transaction_block_hash(id)
+            .await
+            .map_err(ComposedRelayError::Query)
+    }
+
+    async fn transaction_status_in_block(
+        &self,
+        id: btc::Sha256d,
+        block_hash: btc::Sha256d,
+    ) -> Result<TransactionStatus, Self::Error> {
+        self.query
+            .transaction_status_in_block(id, block_hash)
+            .await
+            .map_err(ComposedRelayError::Query)
+    }
+
+    async fn ping(&self) -> Result<Option<u64>, Self::Error> {
+        self.query.ping().await.map_err(ComposedRelayError::Query)
+    }
+}
+
+impl RelayBackend for BitcoinRpcClient {
     type Error = bitcoincore_rpc::Error;
+}
 
+// The plain RPC client performs the requests on the calling thread. Use `BitcoinRpcRelay`
+// to avoid blocking the asynchronous runtime.
+#[async_trait]
+impl TxBroadcaster for BitcoinRpcClient {
     async fn send_transaction(
         &self,
         transaction: &btc::Transaction,
     ) -> Result<btc::Sha256d, Self::Error> {
-        self.send_raw_transaction(transaction.to_string())
-            .map(|txid| btc::Sha256d(txid.into()))
+        send_raw_transaction(self, transaction)
     }
+}
 
+#[async_trait]
+impl ChainQuery for BitcoinRpcClient {
     async fn transaction_status(&self, id: btc::Sha256d) -> Result<TransactionStatus, Self::Error> {
-        match self.get_raw_transaction_verbose(&id.into(), None) {
-            Ok(info) => {
-                let status = match info.confirmations {
-                    None => TransactionStatus::Mempool,
-                    Some(num) => TransactionStatus::Committed(num),
+        raw_transaction_status(self, id)
+    }
+
+    async fn fee_rate(&self, target_blocks: u16) -> Result<Option<u64>, Self::Error> {
+        estimate_smart_fee(self, target_blocks)
+    }
+
+    async fn mempool_entry(&self, id: btc::Sha256d) -> Result<Option<MempoolEntry>, Self::Error> {
+        get_mempool_entry(self, id)
+    }
+
+    async fn transaction_block_hash(
+        &self,
+        id: btc::Sha256d,
+    ) -> Result<Option<btc::Sha256d>, Self::Error> {
+        raw_transaction_block_hash(self, id)
+    }
+
+    async fn transaction_status_in_block(
+        &self,
+        id: btc::Sha256d,
+        block_hash: btc::Sha256d,
+    ) -> Result<TransactionStatus, Self::Error> {
+        match raw_transaction_status_in_block(self, id, block_hash)? {
+            Some(status) => Ok(status),
+            None => raw_transaction_status(self, id),
+        }
+    }
+
+    async fn ping(&self) -> Result<Option<u64>, Self::Error> {
+        Ok(Some(self.get_blockchain_info()?.blocks))
+    }
+}
+
+/// Estimates the fee rate in satoshis per virtual byte via `estimatesmartfee`.
+pub(super) fn estimate_smart_fee(
+    client: &BitcoinRpcClient,
+    target_blocks: u16,
+) -> Result<Option<u64>, bitcoincore_rpc::Error> {
+    let estimate = client.call::<serde_json::Value>("estimatesmartfee", &[target_blocks.into()])?;
+    // Fee rate is returned in BTC per kilo virtual byte.
+    Ok(estimate["feerate"]
+        .as_f64()
+        .map(|fee_rate| (fee_rate * 100_000_f64).ceil() as u64))
+}
+
+/// Gets the mempool entry of the transaction via `getmempoolentry`.
+fn get_mempool_entry(
+    client: &BitcoinRpcClient,
+    id: btc::Sha256d,
+) -> Result<Option<MempoolEntry>, bitcoincore_rpc::Error> {
+    let entry = match client.call::<serde_json::Value>("getmempoolentry", &[id.to_string().into()])
+    {
+        Ok(entry) => entry,
+        // The node returns an error if the transaction is not in the mempool.
+        Err(bitcoincore_rpc::Error::JsonRpc(JsonRpcError::Rpc(_))) => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    // The fee is returned in BTC, the newer nodes return it in the `fees` object only.
+    let fee = entry["fees"]["base"]
+        .as_f64()
+        .or_else(|| entry["fee"].as_f64())
+        .unwrap_or_default();
+    Ok(Some(MempoolEntry {
+        vsize: entry["vsize"].as_u64().unwrap_or_default(),
+        fee: (fee * 100_000_000_f64).round() as u64,
+    }))
+}
+
+/// Parses the unspent output returned by `listunspent`.
+fn wallet_utxo(entry: &serde_json::Value) -> Option<WalletUtxo> {
+    Some(WalletUtxo {
+        txid: entry["txid"]
+            .as_str()?
+            .parse::<sha256d::Hash>()
+            .map(btc::Sha256d)
+            .ok()?,
+        vout: entry["vout"].as_u64()? as u32,
+        // The amount is returned in BTC.
+        value: (entry["amount"].as_f64()? * 100_000_000_f64).round() as u64,
+        confirmations: entry["confirmations"].as_u64().unwrap_or_default() as u32,
+    })
+}
+
+fn send_raw_transaction(
+    client: &BitcoinRpcClient,
+    transaction: &btc::Transaction,
+) -> Result<btc::Sha256d, bitcoincore_rpc::Error> {
+    client
+        .send_raw_transaction(transaction.to_string())
+        .map(|txid| btc::Sha256d(txid.into()))
+}
+
+fn raw_transaction_status(
+    client: &BitcoinRpcClient,
+    id: btc::Sha256d,
+) -> Result<TransactionStatus, bitcoincore_rpc::Error> {
+    match client.get_raw_transaction_verbose(&id.into(), None) {
+        Ok(info) => {
+            let status = match info.confirmations {
+                None => TransactionStatus::Mempool,
+                Some(num) => TransactionStatus::Committed(num),
+            };
+            Ok(status)
+        }
+        // TODO Write more graceful error handling. [ECR-3222]
+        Err(bitcoincore_rpc::Error::JsonRpc(JsonRpcError::Rpc(_))) => {
+            Ok(TransactionStatus::Unknown)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Gets the hash of the block containing the transaction via `getrawtransaction`, which
+/// requires the full transaction index for the transactions outside of the node wallet.
+fn raw_transaction_block_hash(
+    client: &BitcoinRpcClient,
+    id: btc::Sha256d,
+) -> Result<Option<btc::Sha256d>, bitcoincore_rpc::Error> {
+    match client
+        .call::<serde_json::Value>("getrawtransaction", &[id.to_string().into(), true.into()])
+    {
+        Ok(info) => Ok(block_hash_from(&info)),
+        Err(bitcoincore_rpc::Error::JsonRpc(JsonRpcError::Rpc(_))) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Gets status of the transaction via `getrawtransaction` with the hash of the block
+/// containing it, which works without the full transaction index. Returns `None` if
+/// the block is unknown, pruned or disconnected from the main chain, or it does not
+/// contain the transaction.
+fn raw_transaction_status_in_block(
+    client: &BitcoinRpcClient,
+    id: btc::Sha256d,
+    block_hash: btc::Sha256d,
+) -> Result<Option<TransactionStatus>, bitcoincore_rpc::Error> {
+    let info = match client.call::<serde_json::Value>(
+        "getrawtransaction",
+        &[
+            id.to_string().into(),
+            true.into(),
+            block_hash.to_string().into(),
+        ],
+    ) {
+        Ok(info) => info,
+        Err(bitcoincore_rpc::Error::JsonRpc(JsonRpcError::Rpc(_))) => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    if info["in_active_chain"].as_bool() == Some(false) {
+        return Ok(None);
+    }
+    Ok(info["confirmations"]
+        .as_u64()
+        .map(|confirmations| TransactionStatus::Committed(confirmations as u32)))
+}
+
+/// Parses the hash of the block containing the transaction from the result of
+/// the `getrawtransaction` or `gettransaction` call.
+fn block_hash_from(info: &serde_json::Value) -> Option<btc::Sha256d> {
+    info["blockhash"]
+        .as_str()?
+        .parse::<sha256d::Hash>()
+        .map(btc::Sha256d)
+        .ok()
+}
+
+/// Converts the result of the `getrawtransaction` call into the transaction status.
+fn raw_transaction_status_from(
+    result: Result<serde_json::Value, JsonRpcError>,
+) -> Result<TransactionStatus, bitcoincore_rpc::Error> {
+    match result {
+        Ok(info) => Ok(match info["confirmations"].as_u64() {
+            None => TransactionStatus::Mempool,
+            Some(num) => TransactionStatus::Committed(num as u32),
+        }),
+        Err(JsonRpcError::Rpc(_)) => Ok(TransactionStatus::Unknown),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Bitcoin RPC relay, which selects the code paths compatible with the capabilities
+/// of the Bitcoin node.
+///
+/// The RPC requests are performed on the thread pool for the blocking operations,
+/// so they do not block the asynchronous runtime. Thus, the relay methods should be called
+/// within the Tokio runtime, use [`BlockingRelay`] in the synchronous code.
+///
+/// [`BlockingRelay`]: struct.BlockingRelay.html
+///
+/// The underlying RPC client can be replaced at runtime, for example, after rotation
+/// of the RPC credentials on the Bitcoin node. Requests failed due to the transient
+/// errors are retried according to the [retry policy](struct.RetryPolicy.html).
+///
+/// The relay may have the fallback clients of the redundant Bitcoin nodes. If the active
+/// node is unreachable, the relay switches to the first responding fallback node, and
+/// the previous one becomes the last fallback.
+///
+/// If the Bitcoin node has several wallets loaded, the clients should be connected to
+/// the `/wallet/<name>` endpoint of the wallet, which watches the anchoring addresses,
+/// so the wallet calls, such as `importaddress` and `listunspent`, are routed to it.
+///
+/// Each request should be completed before its [deadline](struct.RpcTimeouts.html), which
+/// depends on the request type; otherwise the request fails as if the node is unreachable.
+///
+/// The health check of the relay (`getblockchaininfo`) reconnects to the Bitcoin node
+/// after the outage, that is, the node capabilities are probed once again, since they may
+/// have been changed by the node restart.
+#[derive(Debug)]
+pub struct BitcoinRpcRelay {
+    connection: RwLock<RelayConnection>,
+    fallback_clients: RwLock<Vec<Arc<BitcoinRpcClient>>>,
+    retry_policy: RetryPolicy,
+    timeouts: RpcTimeouts,
+    disconnected: AtomicBool,
+}
+
+/// Bitcoin RPC client with the probed capabilities of the Bitcoin node.
+#[derive(Debug, Clone)]
+struct RelayConnection {
+    client: Arc<BitcoinRpcClient>,
+    capabilities: RelayCapabilities,
+}
+
+impl RelayConnection {
+    fn new(client: BitcoinRpcClient) -> Result<Self, bitcoincore_rpc::Error> {
+        Self::probe(Arc::new(client))
+    }
+
+    fn probe(client: Arc<BitcoinRpcClient>) -> Result<Self, bitcoincore_rpc::Error> {
+        let capabilities = RelayCapabilities::probe(&client)?;
+        Ok(Self {
+            client,
+            capabilities,
+        })
+    }
+}
+
+impl BitcoinRpcRelay {
+    /// Creates a new relay for the given Bitcoin RPC client and probes the node capabilities.
+    pub fn new(client: BitcoinRpcClient) -> Result<Self, bitcoincore_rpc::Error> {
+        Ok(Self {
+            connection: RwLock::new(RelayConnection::new(client)?),
+            fallback_clients: RwLock::default(),
+            retry_policy: RetryPolicy::default(),
+            timeouts: RpcTimeouts::default(),
+            disconnected: AtomicBool::default(),
+        })
+    }
+
+    /// Adds the clients of the fallback Bitcoin nodes, which are used in the given order
+    /// if the active node is unreachable. The fallback nodes are probed only when
+    /// the relay switches to them.
+    pub fn with_fallback_clients(
+        self,
+        clients: impl IntoIterator<Item = BitcoinRpcClient>,
+    ) -> Self {
+        self.fallback_clients
+            .write()
+            .unwrap()
+            .extend(clients.into_iter().map(Arc::new));
+        self
+    }
+
+    /// Sets the policy of the retries of the failed requests.
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    /// Sets the deadlines of the requests.
+    pub fn with_timeouts(mut self, timeouts: RpcTimeouts) -> Self {
+        self.timeouts = timeouts;
+        self
+    }
+
+    /// Returns the capabilities of the Bitcoin node.
+    pub fn capabilities(&self) -> RelayCapabilities {
+        self.connection().capabilities
+    }
+
+    /// Returns the underlying Bitcoin RPC client.
+    pub fn client(&self) -> Arc<BitcoinRpcClient> {
+        self.connection().client
+    }
+
+    /// Replaces the underlying Bitcoin RPC client with the given one.
+    ///
+    /// The new client is probed before the replacement, so the relay keeps using
+    /// the previous client if the new one is unable to connect to the Bitcoin node,
+    /// for example, due to invalid credentials. Requests which are in progress are
+    /// completed with the previous client.
+    pub fn replace_client(
+        &self,
+        client: BitcoinRpcClient,
+    ) -> Result<RelayCapabilities, bitcoincore_rpc::Error> {
+        let connection = RelayConnection::new(client)?;
+        let capabilities = connection.capabilities;
+        *self.connection.write().unwrap() = connection;
+        Ok(capabilities)
+    }
+
+    fn connection(&self) -> RelayConnection {
+        self.connection.read().unwrap().clone()
+    }
+
+    /// Probes the capabilities of the active Bitcoin node once again after the lost
+    /// connection has been restored.
+    async fn reconnect(&self) -> Result<(), RelayError> {
+        let client = self.client();
+        let connection = self.probe(Arc::clone(&client)).await?;
+        let capabilities = connection.capabilities;
+        {
+            let mut active = self.connection.write().unwrap();
+            // The client may have been replaced by a concurrent request.
+            if Arc::ptr_eq(&active.client, &client) {
+                *active = connection;
+            }
+        }
+        log::info!(
+            "Reconnected to the Bitcoin node, capabilities: {:?}",
+            capabilities
+        );
+        Ok(())
+    }
+
+    /// Probes the capabilities of the Bitcoin node with the given client within
+    /// the connection deadline.
+    async fn probe(&self, client: Arc<BitcoinRpcClient>) -> Result<RelayConnection, RelayError> {
+        let deadline = self.timeouts.connect_timeout();
+        let probe = tokio::task::spawn_blocking(move || RelayConnection::probe(client));
+        match tokio::time::timeout(deadline, probe).await {
+            Ok(connection) => Ok(connection??),
+            Err(_) => Err(RelayError::Timeout(deadline)),
+        }
+    }
+
+    /// Performs the blocking RPC requests on the thread pool for the blocking operations
+    /// within the deadline of the regular requests, see [`spawn_blocking_within`].
+    ///
+    /// [`spawn_blocking_within`]: #method.spawn_blocking_within
+    async fn spawn_blocking<T, F>(&self, f: F) -> Result<T, RelayError>
+    where
+        T: Send + 'static,
+        F: Fn(RelayConnection) -> Result<T, RelayError> + Send + Sync + 'static,
+    {
+        self.spawn_blocking_within(self.timeouts.read_timeout(), f)
+            .await
+    }
+
+    /// Performs the blocking RPC requests on the thread pool for the blocking operations.
+    /// Each attempt should be completed within the given deadline. The requests are retried
+    /// on the transient errors, with the fallback clients first, and then with the delays
+    /// according to the retry policy.
+    async fn spawn_blocking_within<T, F>(&self, deadline: Duration, f: F) -> Result<T, RelayError>
+    where
+        T: Send + 'static,
+        F: Fn(RelayConnection) -> Result<T, RelayError> + Send + Sync + 'static,
+    {
+        let f = Arc::new(f);
+        let max_failovers = self.fallback_clients.read().unwrap().len();
+        let (mut retry, mut failovers) = (0, 0);
+        loop {
+            let (connection, f) = (self.connection(), Arc::clone(&f));
+            let client = Arc::clone(&connection.client);
+            let request = tokio::task::spawn_blocking(move || f(connection));
+            let result = match tokio::time::timeout(deadline, request).await {
+                Ok(result) => result?,
+                Err(_) => Err(RelayError::Timeout(deadline)),
+            };
+            let e = match result {
+                Err(e) if e.is_transient() => e,
+                result => return result,
+            };
+
+            if failovers < max_failovers && self.fail_over(&client).await {
+                log::warn!(
+                    "Bitcoin RPC request failed, switched to the fallback Bitcoin node. {}",
+                    e
+                );
+                failovers += 1;
+            } else if retry < self.retry_policy.max_retries {
+                let delay = self.retry_policy.delay(retry);
+                log::warn!("Bitcoin RPC request failed, retrying in {:?}. {}", delay, e);
+                tokio::time::delay_for(delay).await;
+                retry += 1;
+            } else {
+                return Err(e);
+            }
+        }
+    }
+
+    /// Switches the relay from the failed client to the first responding fallback client.
+    /// Returns `true` if the relay uses another client now, possibly switched by
+    /// a concurrent request.
+    async fn fail_over(&self, failed: &Arc<BitcoinRpcClient>) -> bool {
+        let candidates = self.fallback_clients.read().unwrap().clone();
+        for candidate in candidates {
+            if !Arc::ptr_eq(&self.connection().client, failed) {
+                return true;
+            }
+
+            let connection = match self.probe(Arc::clone(&candidate)).await {
+                Ok(connection) => connection,
+                Err(_) => continue,
+            };
+            let previous = {
+                let mut active = self.connection.write().unwrap();
+                if !Arc::ptr_eq(&active.client, failed) {
+                    return true;
+                }
+                std::mem::replace(&mut *active, connection)
+            };
+            let mut fallback_clients = self.fallback_clients.write().unwrap();
+            fallback_clients.retain(|client| !Arc::ptr_eq(client, &candidate));
+            fallback_clients.push(previous.client);
+            return true;
+        }
+        false
+    }
+
+    /// Gets the height of the block containing the given transaction. Returns `None` if
+    /// the transaction is unconfirmed or unknown, for example, if the node does not maintain
+    /// the full transaction index.
+    fn transaction_block_height(
+        client: &BitcoinRpcClient,
+        id: btc::Sha256d,
+    ) -> Result<Option<u64>, bitcoincore_rpc::Error> {
+        let info = match client
+            .call::<serde_json::Value>("getrawtransaction", &[id.to_string().into(), true.into()])
+        {
+            Ok(info) => info,
+            Err(bitcoincore_rpc::Error::JsonRpc(JsonRpcError::Rpc(_))) => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let block_hash = match info["blockhash"].as_str() {
+            Some(block_hash) => block_hash.to_owned(),
+            None => return Ok(None),
+        };
+        let header = client.call::<serde_json::Value>("getblockheader", &[block_hash.into()])?;
+        Ok(header["height"].as_u64())
+    }
+
+    /// Checks whether the given transactions would be accepted by the mempool of the Bitcoin
+    /// node (`testmempoolaccept`) without broadcasting them. Transactions may depend on each
+    /// other, for example, a transaction and its CPFP child, in this case the node
+    /// should support package validation.
+    ///
+    /// Returns the transaction identifiers with the rejection details for the rejected ones,
+    /// or `None` if the node does not support this check.
+    ///
+    /// Unlike the relay methods, this method performs the request on the calling thread.
+    pub fn test_mempool_accept(
+        &self,
+        transactions: &[btc::Transaction],
+    ) -> Result<Option<Vec<(btc::Sha256d, Option<MempoolRejection>)>>, bitcoincore_rpc::Error> {
+        Self::mempool_accept(&self.client(), transactions)
+    }
+
+    fn mempool_accept(
+        client: &BitcoinRpcClient,
+        transactions: &[btc::Transaction],
+    ) -> Result<Option<Vec<(btc::Sha256d, Option<MempoolRejection>)>>, bitcoincore_rpc::Error> {
+        let raw_transactions = transactions
+            .iter()
+            .map(|tx| serde_json::Value::from(tx.to_string()))
+            .collect::<Vec<_>>();
+        let results = match client
+            .call::<Vec<serde_json::Value>>("testmempoolaccept", &[raw_transactions.into()])
+        {
+            Ok(results) => results,
+            Err(bitcoincore_rpc::Error::JsonRpc(JsonRpcError::Rpc(ref e)))
+                if e.code == RPC_METHOD_NOT_FOUND =>
+            {
+                return Ok(None)
+            }
+            Err(e) => return Err(e),
+        };
+
+        let results = transactions
+            .iter()
+            .zip(results)
+            .map(|(tx, result)| {
+                let rejection = if result["allowed"].as_bool().unwrap_or_default() {
+                    None
+                } else {
+                    let reason = result["reject-reason"].as_str().unwrap_or("unknown");
+                    if MempoolRejection::is_already_known(reason) {
+                        None
+                    } else {
+                        Some(MempoolRejection::from_reason(reason))
+                    }
                 };
-                Ok(status)
+                (tx.id(), rejection)
+            })
+            .collect();
+        Ok(Some(results))
+    }
+
+    /// Number of the latest wallet transactions, which are checked for spending
+    /// the anchoring outputs.
+    const SPENDING_LOOKUP_DEPTH: usize = 100;
+
+    /// Gets the transaction by its identifier from the node wallet.
+    fn wallet_transaction(
+        client: &BitcoinRpcClient,
+        id: &str,
+    ) -> Result<Option<btc::Transaction>, bitcoincore_rpc::Error> {
+        let info =
+            match client.call::<serde_json::Value>("gettransaction", &[id.into(), true.into()]) {
+                Ok(info) => info,
+                Err(bitcoincore_rpc::Error::JsonRpc(JsonRpcError::Rpc(_))) => return Ok(None),
+                Err(e) => return Err(e),
+            };
+        Ok(info["hex"]
+            .as_str()
+            .and_then(|hex| btc::Transaction::from_hex(hex).ok()))
+    }
+
+    /// Finds the transaction spending the given output in the mempool
+    /// (`gettxspendingprevout`) or among the latest transactions of the node wallet,
+    /// which watches the anchoring address.
+    fn find_spending_transaction(
+        client: &BitcoinRpcClient,
+        outpoint: OutPoint,
+    ) -> Result<Option<btc::Transaction>, bitcoincore_rpc::Error> {
+        // Unspent output has no spending transaction.
+        let txout = client.call::<serde_json::Value>(
+            "gettxout",
+            &[outpoint.txid.to_string().into(), outpoint.vout.into()],
+        )?;
+        if !txout.is_null() {
+            return Ok(None);
+        }
+
+        let prevout =
+            serde_json::json!([{ "txid": outpoint.txid.to_string(), "vout": outpoint.vout }]);
+        match client.call::<Vec<serde_json::Value>>("gettxspendingprevout", &[prevout]) {
+            Ok(results) => {
+                let spending_txid = results
+                    .first()
+                    .and_then(|result| result["spendingtxid"].as_str().map(ToOwned::to_owned));
+                if let Some(txid) = spending_txid {
+                    let tx = client.call::<String>("getrawtransaction", &[txid.into()])?;
+                    return Ok(btc::Transaction::from_hex(tx).ok());
+                }
             }
-            // TODO Write more graceful error handling. [ECR-3222]
-            Err(bitcoincore_rpc::Error::JsonRpc(JsonRpcError::Rpc(_))) => {
-                Ok(TransactionStatus::Unknown)
+            Err(bitcoincore_rpc::Error::JsonRpc(JsonRpcError::Rpc(ref e)))
+                if e.code == RPC_METHOD_NOT_FOUND => {}
+            Err(e) => return Err(e),
+        }
+
+        let entries = match client.call::<Vec<serde_json::Value>>(
+            "listtransactions",
+            &[
+                "*".into(),
+                Self::SPENDING_LOOKUP_DEPTH.into(),
+                0.into(),
+                true.into(),
+            ],
+        ) {
+            Ok(entries) => entries,
+            Err(bitcoincore_rpc::Error::JsonRpc(JsonRpcError::Rpc(_))) => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let mut txids = entries
+            .iter()
+            .filter_map(|entry| entry["txid"].as_str())
+            .collect::<Vec<_>>();
+        txids.dedup();
+        for txid in txids {
+            if let Some(tx) = Self::wallet_transaction(client, txid)? {
+                if tx
+                    .0
+                    .input
+                    .iter()
+                    .any(|txin| txin.previous_output == outpoint)
+                {
+                    return Ok(Some(tx));
+                }
             }
+        }
+        Ok(None)
+    }
+
+    /// Gets status of the transaction from the node wallet, which watches the anchoring
+    /// address. Used if the node does not maintain the full transaction index.
+    fn wallet_transaction_status(
+        client: &BitcoinRpcClient,
+        id: btc::Sha256d,
+    ) -> Result<Option<TransactionStatus>, bitcoincore_rpc::Error> {
+        let info = match client
+            .call::<serde_json::Value>("gettransaction", &[id.to_string().into(), true.into()])
+        {
+            Ok(info) => info,
+            Err(bitcoincore_rpc::Error::JsonRpc(JsonRpcError::Rpc(_))) => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        Ok(Some(Self::wallet_status_from(&info)))
+    }
+
+    /// Gets the hash of the block containing the transaction from the node wallet.
+    fn wallet_block_hash(
+        client: &BitcoinRpcClient,
+        id: btc::Sha256d,
+    ) -> Result<Option<btc::Sha256d>, bitcoincore_rpc::Error> {
+        match client
+            .call::<serde_json::Value>("gettransaction", &[id.to_string().into(), true.into()])
+        {
+            Ok(info) => Ok(block_hash_from(&info)),
+            Err(bitcoincore_rpc::Error::JsonRpc(JsonRpcError::Rpc(_))) => Ok(None),
             Err(e) => Err(e),
         }
     }
+
+    fn wallet_status_from(info: &serde_json::Value) -> TransactionStatus {
+        // Negative number of confirmations means that the transaction conflicts
+        // with the committed one.
+        match info["confirmations"].as_i64().unwrap_or_default() {
+            0 => TransactionStatus::Mempool,
+            confirmations if confirmations > 0 => {
+                TransactionStatus::Committed(confirmations as u32)
+            }
+            _ => TransactionStatus::Unknown,
+        }
+    }
+
+    /// Gets statuses of the transactions in a single batch request, first from the node
+    /// wallet if the node does not maintain the full transaction index, and then via
+    /// `getrawtransaction` for the transactions unknown to the wallet.
+    fn batch_transaction_statuses(
+        connection: &RelayConnection,
+        ids: &[btc::Sha256d],
+    ) -> Result<Vec<TransactionStatus>, bitcoincore_rpc::Error> {
+        let client = &connection.client;
+        let params = |ids: &[btc::Sha256d]| {
+            ids.iter()
+                .map(|id| vec![id.to_string().into(), true.into()])
+                .collect::<Vec<_>>()
+        };
+
+        let wallet_statuses = if connection.capabilities.txindex {
+            vec![None; ids.len()]
+        } else {
+            client
+                .batch_call("gettransaction", &params(ids))?
+                .into_iter()
+                .map(|result| match result {
+                    Ok(info) => Ok(Some(Self::wallet_status_from(&info))),
+                    Err(JsonRpcError::Rpc(_)) => Ok(None),
+                    Err(e) => Err(e.into()),
+                })
+                .collect::<Result<Vec<_>, bitcoincore_rpc::Error>>()?
+        };
+
+        let missing = ids
+            .iter()
+            .zip(&wallet_statuses)
+            .filter(|(_, status)| status.is_none())
+            .map(|(id, _)| *id)
+            .collect::<Vec<_>>();
+        let mut raw_statuses = client
+            .batch_call("getrawtransaction", &params(&missing))?
+            .into_iter()
+            .map(raw_transaction_status_from)
+            .collect::<Result<Vec<_>, _>>()?
+            .into_iter();
+
+        Ok(wallet_statuses
+            .into_iter()
+            .map(|status| {
+                status.unwrap_or_else(|| raw_statuses.next().unwrap_or(TransactionStatus::Unknown))
+            })
+            .collect())
+    }
+
+    // The descriptor wallets import the output descriptors, and the legacy ones import
+    // the addresses. Several descriptors are imported in a single request, and several
+    // addresses in a single batch request.
+    //
+    // If the rescan height is given, the descriptor wallets rescan the blocks from
+    // the time of the block at this height along with the import, while the legacy
+    // wallets rescan them by the separate `rescanblockchain` request.
+    fn import_descriptors(
+        connection: &RelayConnection,
+        descriptors: &[AnchoringDescriptor],
+        label: &str,
+        rescan_height: Option<u64>,
+    ) -> Result<(), RelayError> {
+        if !connection.capabilities.descriptor_wallet {
+            let params = descriptors
+                .iter()
+                .map(|descriptor| {
+                    vec![
+                        descriptor.address.to_string().into(),
+                        label.into(),
+                        false.into(),
+                    ]
+                })
+                .collect::<Vec<_>>();
+            let results = connection.client.batch_call("importaddress", &params)?;
+            for (descriptor, result) in descriptors.iter().zip(results) {
+                if let Err(e) = result {
+                    return Err(RelayError::WatchFailed {
+                        address: descriptor.address.clone(),
+                        reason: e.to_string(),
+                    });
+                }
+            }
+            if let Some(height) = rescan_height {
+                connection
+                    .client
+                    .call::<serde_json::Value>("rescanblockchain", &[height.into()])?;
+            }
+            return Ok(());
+        }
+
+        let timestamp = match rescan_height {
+            Some(height) => {
+                let hash = connection
+                    .client
+                    .call::<String>("getblockhash", &[height.into()])?;
+                let header = connection
+                    .client
+                    .call::<serde_json::Value>("getblockheader", &[hash.into()])?;
+                header["time"].clone()
+            }
+            None => "now".into(),
+        };
+        let request = descriptors
+            .iter()
+            .map(|descriptor| {
+                serde_json::json!({
+                    "desc": descriptor.descriptor,
+                    "timestamp": timestamp,
+                    "label": label,
+                })
+            })
+            .collect::<Vec<_>>();
+        let results = connection
+            .client
+            .call::<Vec<serde_json::Value>>("importdescriptors", &[request.into()])?;
+        for (i, descriptor) in descriptors.iter().enumerate() {
+            match results.get(i) {
+                Some(result) if result["success"].as_bool() == Some(true) => {}
+                result => {
+                    return Err(RelayError::WatchFailed {
+                        address: descriptor.address.clone(),
+                        reason: result
+                            .and_then(|result| result["error"]["message"].as_str())
+                            .unwrap_or("unknown error")
+                            .to_owned(),
+                    })
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl RelayBackend for BitcoinRpcRelay {
+    type Error = RelayError;
+}
+
+#[async_trait]
+impl TxBroadcaster for BitcoinRpcRelay {
+    async fn send_transaction(
+        &self,
+        transaction: &btc::Transaction,
+    ) -> Result<btc::Sha256d, Self::Error> {
+        let transaction = transaction.clone();
+        self.spawn_blocking(move |connection| {
+            // Check the transaction against the mempool policy before broadcasting to report
+            // the exact rejection reason.
+            let results =
+                Self::mempool_accept(&connection.client, std::slice::from_ref(&transaction))?;
+            if let Some((txid, Some(rejection))) = results.into_iter().flatten().next() {
+                return Err(RelayError::Rejected { txid, rejection });
+            }
+            Ok(send_raw_transaction(&connection.client, &transaction)?)
+        })
+        .await
+    }
+}
+
+#[async_trait]
+impl ChainQuery for BitcoinRpcRelay {
+    async fn transaction_status(&self, id: btc::Sha256d) -> Result<TransactionStatus, Self::Error> {
+        self.spawn_blocking(move |connection| {
+            if !connection.capabilities.txindex {
+                // Without the transaction index, only mempool and wallet transactions are
+                // available, so try the wallet first.
+                if let Some(status) = Self::wallet_transaction_status(&connection.client, id)? {
+                    return Ok(status);
+                }
+            }
+            Ok(raw_transaction_status(&connection.client, id)?)
+        })
+        .await
+    }
+
+    async fn transaction_statuses(
+        &self,
+        ids: &[btc::Sha256d],
+    ) -> Result<Vec<TransactionStatus>, Self::Error> {
+        let ids = ids.to_vec();
+        self.spawn_blocking(move |connection| {
+            Ok(Self::batch_transaction_statuses(&connection, &ids)?)
+        })
+        .await
+    }
+
+    async fn spending_transaction(
+        &self,
+        outpoint: OutPoint,
+    ) -> Result<Option<btc::Transaction>, Self::Error> {
+        self.spawn_blocking(move |connection| {
+            Ok(Self::find_spending_transaction(
+                &connection.client,
+                outpoint,
+            )?)
+        })
+        .await
+    }
+
+    async fn fee_rate(&self, target_blocks: u16) -> Result<Option<u64>, Self::Error> {
+        if !self.capabilities().fee_estimation {
+            return Ok(None);
+        }
+        self.spawn_blocking(move |connection| {
+            Ok(estimate_smart_fee(&connection.client, target_blocks)?)
+        })
+        .await
+    }
+
+    async fn mempool_entry(&self, id: btc::Sha256d) -> Result<Option<MempoolEntry>, Self::Error> {
+        self.spawn_blocking(move |connection| Ok(get_mempool_entry(&connection.client, id)?))
+            .await
+    }
+
+    async fn transaction_block_hash(
+        &self,
+        id: btc::Sha256d,
+    ) -> Result<Option<btc::Sha256d>, Self::Error> {
+        self.spawn_blocking(move |connection| {
+            if !connection.capabilities.txindex {
+                // Without the transaction index, the blocks of the historical transactions
+                // are known to the wallet only.
+                if let Some(block_hash) = Self::wallet_block_hash(&connection.client, id)? {
+                    return Ok(Some(block_hash));
+                }
+            }
+            Ok(raw_transaction_block_hash(&connection.client, id)?)
+        })
+        .await
+    }
+
+    async fn transaction_status_in_block(
+        &self,
+        id: btc::Sha256d,
+        block_hash: btc::Sha256d,
+    ) -> Result<TransactionStatus, Self::Error> {
+        let status = self
+            .spawn_blocking(move |connection| {
+                Ok(raw_transaction_status_in_block(
+                    &connection.client,
+                    id,
+                    block_hash,
+                )?)
+            })
+            .await?;
+        match status {
+            Some(status) => Ok(status),
+            None => self.transaction_status(id).await,
+        }
+    }
+
+    async fn ping(&self) -> Result<Option<u64>, Self::Error> {
+        let result = self
+            .spawn_blocking(|connection| Ok(connection.client.get_blockchain_info()?.blocks))
+            .await;
+        match result {
+            Ok(_) if self.disconnected.swap(false, Ordering::SeqCst) => {
+                if let Err(e) = self.reconnect().await {
+                    self.disconnected.store(true, Ordering::SeqCst);
+                    return Err(e);
+                }
+            }
+            Ok(_) => {}
+            Err(_) => self.disconnected.store(true, Ordering::SeqCst),
+        }
+        result.map(Some)
+    }
+}
+
+#[async_trait]
+impl WalletOps for BitcoinRpcRelay {
+    async fn watch_address(
+        &self,
+        descriptor: &AnchoringDescriptor,
+        label: &str,
+    ) -> Result<(), Self::Error> {
+        let (descriptor, label) = (descriptor.clone(), label.to_owned());
+        self.spawn_blocking_within(self.timeouts.import_timeout(), move |connection| {
+            Self::import_descriptors(&connection, std::slice::from_ref(&descriptor), &label, None)
+        })
+        .await
+    }
+
+    async fn watch_addresses(
+        &self,
+        descriptors: &[AnchoringDescriptor],
+        label: &str,
+    ) -> Result<(), Self::Error> {
+        let (descriptors, label) = (descriptors.to_vec(), label.to_owned());
+        self.spawn_blocking_within(self.timeouts.import_timeout(), move |connection| {
+            Self::import_descriptors(&connection, &descriptors, &label, None)
+        })
+        .await
+    }
+
+    // Without the full transaction index the block of the funding transaction cannot
+    // be found, in this case the fallback height is used.
+    async fn restore_watch_data(
+        &self,
+        descriptor: &AnchoringDescriptor,
+        label: &str,
+        latest_anchor: btc::Sha256d,
+        funding_tx: btc::Sha256d,
+        fallback_height: Option<u64>,
+    ) -> Result<Option<u64>, Self::Error> {
+        let (descriptor, label) = (descriptor.clone(), label.to_owned());
+        self.spawn_blocking_within(self.timeouts.import_timeout(), move |connection| {
+            let client = &connection.client;
+            if Self::wallet_transaction(client, &latest_anchor.to_string())?.is_some() {
+                return Ok(None);
+            }
+
+            let start_height = Self::transaction_block_height(client, funding_tx)?
+                .or(fallback_height)
+                .ok_or_else(|| RelayError::WatchFailed {
+                    address: descriptor.address.clone(),
+                    reason: format!(
+                        "the block of the funding transaction {} is unknown to the Bitcoin node",
+                        funding_tx
+                    ),
+                })?;
+            // The rescan is bounded by the block of the funding transaction, since
+            // the anchoring address has no transactions before it.
+            Self::import_descriptors(
+                &connection,
+                std::slice::from_ref(&descriptor),
+                &label,
+                Some(start_height),
+            )?;
+            Ok(Some(start_height))
+        })
+        .await
+    }
+
+    async fn unspent_outputs(
+        &self,
+        address: &btc::Address,
+    ) -> Result<Vec<WalletUtxo>, Self::Error> {
+        let address = address.to_string();
+        self.spawn_blocking(move |connection| {
+            let entries = connection.client.call::<Vec<serde_json::Value>>(
+                "listunspent",
+                &[0.into(), 9_999_999.into(), vec![address].into()],
+            )?;
+            Ok(entries.iter().filter_map(wallet_utxo).collect())
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use jsonrpc::Error as JsonRpcError;
+
+    use std::time::Duration;
+
+    use super::{
+        block_hash_from, wallet_utxo, MempoolRejection, RejectionKind, RelayError, RetryPolicy,
+        RpcTimeouts,
+    };
+
+    #[test]
+    fn mempool_rejection_kinds() {
+        let test_cases = [
+            ("dust", RejectionKind::Dust),
+            ("min relay fee not met", RejectionKind::FeeTooLow),
+            ("mempool min fee not met", RejectionKind::FeeTooLow),
+            ("insufficient fee", RejectionKind::FeeTooLow),
+            ("txn-mempool-conflict", RejectionKind::Conflict),
+            ("missing-inputs", RejectionKind::MissingInputs),
+            (
+                "bad-txns-inputs-missingorspent",
+                RejectionKind::MissingInputs,
+            ),
+            ("scriptpubkey", RejectionKind::NonStandard),
+            ("tx-size", RejectionKind::NonStandard),
+            ("non-mandatory-script-verify-flag", RejectionKind::Other),
+        ];
+
+        for &(reason, kind) in &test_cases {
+            let rejection = MempoolRejection::from_reason(reason);
+            assert_eq!(rejection.kind, kind, "{}", reason);
+            assert_eq!(rejection.reason, reason);
+        }
+    }
+
+    #[test]
+    fn retry_policy_delays() {
+        let policy = RetryPolicy {
+            max_retries: 10,
+            initial_delay_ms: 100,
+            max_delay_ms: 1_000,
+        };
+        let bounds = [(50, 100), (100, 200), (200, 400), (400, 800), (500, 1_000)];
+        for (retry, &(min, max)) in bounds.iter().enumerate() {
+            let delay = policy.delay(retry as u32);
+            assert!(delay >= Duration::from_millis(min), "{:?}", delay);
+            assert!(delay <= Duration::from_millis(max), "{:?}", delay);
+        }
+        assert!(policy.delay(100) <= Duration::from_millis(1_000));
+    }
+
+    #[test]
+    fn transient_relay_errors() {
+        let rpc_error = |code: i32| {
+            let e = serde_json::from_value(serde_json::json!({
+                "code": code,
+                "message": "Loading wallet...",
+            }))
+            .unwrap();
+            RelayError::Rpc(bitcoincore_rpc::Error::JsonRpc(JsonRpcError::Rpc(e)))
+        };
+        assert!(rpc_error(-28).is_transient());
+        assert!(!rpc_error(-26).is_transient());
+
+        let io_error = std::io::Error::from(std::io::ErrorKind::ConnectionRefused);
+        assert!(RelayError::Rpc(bitcoincore_rpc::Error::Io(io_error)).is_transient());
+        assert!(RelayError::Timeout(Duration::from_secs(60)).is_transient());
+    }
+
+    #[test]
+    fn rpc_timeouts_config() {
+        let timeouts: RpcTimeouts =
+            serde_json::from_value(serde_json::json!({ "read_timeout_ms": 5_000 })).unwrap();
+        assert_eq!(timeouts.read_timeout(), Duration::from_secs(5));
+        // Omitted deadlines have the default values.
+        assert_eq!(
+            timeouts.import_timeout(),
+            RpcTimeouts::default().import_timeout()
+        );
+        assert!(timeouts.import_timeout() > timeouts.read_timeout());
+        serde_json::from_value::<RpcTimeouts>(serde_json::json!({ "timeout_ms": 5_000 }))
+            .unwrap_err();
+    }
+
+    #[test]
+    fn listunspent_entry() {
+        let txid = "d8e5ea6ad0da9f3e4bba5c1d6f94b43f2b5fe33e3ee4ed5d1a6b1c8c2e1f0a3b";
+        let entry = serde_json::json!({
+            "txid": txid,
+            "vout": 1,
+            "address": "tb1qw508d6qejxtdg4y5r3zarvary0c5xw7kxpjzsx",
+            "amount": 0.0010005,
+            "confirmations": 3,
+            "spendable": false,
+        });
+        let utxo = wallet_utxo(&entry).unwrap();
+        assert_eq!(utxo.txid.to_string(), txid);
+        assert_eq!(utxo.vout, 1);
+        assert_eq!(utxo.value, 100_050);
+        assert_eq!(utxo.confirmations, 3);
+
+        assert_eq!(
+            wallet_utxo(&serde_json::json!({ "txid": "zz", "vout": 0 })),
+            None
+        );
+    }
+    #[test]
+    fn transaction_block_hash() {
+        let block_hash = "0000000000000003b4e5d7bd43a3bd4e8c1ab8a2c4f4f0bd2c77a5c0f1b0e8a1";
+        let info = serde_json::json!({
+            "txid": "d8e5ea6ad0da9f3e4bba5c1d6f94b43f2b5fe33e3ee4ed5d1a6b1c8c2e1f0a3b",
+            "blockhash": block_hash,
+            "confirmations": 12,
+        });
+        assert_eq!(block_hash_from(&info).unwrap().to_string(), block_hash);
+        // Unconfirmed transactions have no block.
+        assert_eq!(
+            block_hash_from(&serde_json::json!({ "confirmations": 0 })),
+            None
+        );
+    }
 }
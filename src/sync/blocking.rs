@@ -0,0 +1,90 @@
+// Copyright 2019 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Blocking interface of the Bitcoin relays for the synchronous code.
+
+use bitcoin::OutPoint;
+use tokio::runtime::Runtime;
+
+use std::sync::Mutex;
+
+use crate::btc;
+
+use super::{ChainQuery, TransactionStatus, TxBroadcaster};
+
+/// Bitcoin relay wrapper with the blocking interface, which is intended for the synchronous
+/// code, for example, the tests built on top of the Exonum testkit.
+///
+/// The wrapper drives the relay futures to completion on its own Tokio runtime, so the relays
+/// which rely on the runtime facilities, such as [`BitcoinRpcRelay`], can be used outside
+/// of the runtime. The wrapper methods must not be called within the asynchronous context.
+///
+/// [`BitcoinRpcRelay`]: struct.BitcoinRpcRelay.html
+#[derive(Debug)]
+pub struct BlockingRelay<R> {
+    inner: R,
+    runtime: Mutex<Runtime>,
+}
+
+impl<R> BlockingRelay<R> {
+    /// Creates a new blocking wrapper for the given relay.
+    pub fn new(inner: R) -> std::io::Result<Self> {
+        Ok(Self {
+            inner,
+            runtime: Mutex::new(Runtime::new()?),
+        })
+    }
+
+    /// Returns the underlying relay.
+    pub fn inner(&self) -> &R {
+        &self.inner
+    }
+}
+
+impl<R: TxBroadcaster> BlockingRelay<R> {
+    /// Sends the transaction to the Bitcoin network and returns its identifier.
+    pub fn send_transaction(
+        &self,
+        transaction: &btc::Transaction,
+    ) -> Result<btc::Sha256d, R::Error> {
+        let mut runtime = self.runtime.lock().unwrap();
+        runtime.block_on(self.inner.send_transaction(transaction))
+    }
+}
+
+impl<R: ChainQuery> BlockingRelay<R> {
+    /// Gets the status of the transaction with the given identifier.
+    pub fn transaction_status(&self, id: btc::Sha256d) -> Result<TransactionStatus, R::Error> {
+        let mut runtime = self.runtime.lock().unwrap();
+        runtime.block_on(self.inner.transaction_status(id))
+    }
+
+    /// Gets the statuses of the transactions with the given identifiers.
+    pub fn transaction_statuses(
+        &self,
+        ids: &[btc::Sha256d],
+    ) -> Result<Vec<TransactionStatus>, R::Error> {
+        let mut runtime = self.runtime.lock().unwrap();
+        runtime.block_on(self.inner.transaction_statuses(ids))
+    }
+
+    /// Finds the transaction spending the given output, if any.
+    pub fn spending_transaction(
+        &self,
+        outpoint: OutPoint,
+    ) -> Result<Option<btc::Transaction>, R::Error> {
+        let mut runtime = self.runtime.lock().unwrap();
+        runtime.block_on(self.inner.spending_transaction(outpoint))
+    }
+}
@@ -0,0 +1,1388 @@
+// Copyright 2019 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Light Bitcoin relay based on the compact block filters (BIP 157 and BIP 158).
+
+use async_trait::async_trait;
+use bitcoin::{
+    consensus::{
+        encode::{self, deserialize, serialize},
+        params::Params,
+    },
+    hash_types::{BlockHash, FilterHash, Txid},
+    network::{
+        address::Address as PeerAddress,
+        constants::{Network, ServiceFlags},
+        message::{NetworkMessage, RawNetworkMessage},
+        message_blockdata::{GetHeadersMessage, InvType, Inventory},
+        message_filter::{GetCFHeaders, GetCFilters},
+        message_network::VersionMessage,
+    },
+    util::{bip158::BlockFilter, uint::Uint256},
+    Block, BlockHeader, OutPoint, Script, TxOut,
+};
+use bitcoin_hashes::{Hash as BitcoinHash, HashEngine};
+use futures::lock::{Mutex, MutexGuard};
+use rand::{thread_rng, Rng};
+use serde_derive::{Deserialize, Serialize};
+use thiserror::Error;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+};
+
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    io, iter,
+    net::{Ipv4Addr, SocketAddr},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use crate::{api::v1::AnchoringDescriptor, btc};
+
+use super::{
+    ChainQuery, ProxyConfig, ProxyError, RelayBackend, TransactionStatus, TxBroadcaster, WalletOps,
+    WalletUtxo,
+};
+
+/// Type of the basic block filter defined in BIP 158.
+const BASIC_FILTER: u8 = 0x00;
+/// Maximum number of headers in the `headers` message.
+const MAX_HEADERS: usize = 2_000;
+/// Maximum number of filters requested by a single `getcfilters` message.
+const MAX_FILTERS: u64 = 1_000;
+/// Length of the P2P message header: magic, command, payload length and checksum.
+const MESSAGE_HEADER_LEN: usize = 24;
+/// Maximum size of the P2P message payload accepted by the Bitcoin nodes.
+const MAX_PAYLOAD_LEN: usize = 32 * 1024 * 1024;
+/// Messages of the peer, which are decoded by the relay, the other ones are skipped.
+const HANDLED_COMMANDS: &[&str] = &[
+    "version",
+    "verack",
+    "ping",
+    "headers",
+    "cfheaders",
+    "cfilter",
+    "block",
+    "notfound",
+];
+/// Deadline of a single response of the peer.
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(60);
+/// Minimal interval between the synchronizations with the peer.
+const SYNC_INTERVAL: Duration = Duration::from_secs(10);
+/// Number of the latest block hashes included in the locator one by one.
+const LOCATOR_DENSE_HASHES: usize = 10;
+/// Number of the previous blocks, the median time of which the block timestamp should exceed.
+const MEDIAN_TIME_SPAN: u64 = 11;
+/// User agent of the relay announced to the peer.
+const USER_AGENT: &str = "/exonum-btc-anchoring:1.0.0/";
+
+/// Configuration of the compact filter relay.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CompactFilterConfig {
+    /// Address of the Bitcoin peer, which serves the compact block filters,
+    /// for example, `127.0.0.1:8333`.
+    pub peer: String,
+    /// Bitcoin network of the peer.
+    pub network: Network,
+    /// Height of the block, from which the relay follows the Bitcoin blockchain.
+    /// Should not exceed the height of the block with the initial funding transaction.
+    pub start_height: u64,
+    /// Hash of the block at the start height, which is trusted by the relay.
+    pub start_block: btc::Sha256d,
+    /// SOCKS5 proxy, via which the peers are reached, for example, Tor.
+    #[serde(default)]
+    pub proxy: Option<ProxyConfig>,
+    /// Addresses of the additional Bitcoin peers, which cross-check the main peer.
+    /// The block headers are downloaded from all the peers, and the filter headers
+    /// served by the main peer should match the ones served by each of these peers.
+    #[serde(default)]
+    pub witness_peers: Vec<String>,
+}
+
+/// Errors of the compact filter relay.
+#[derive(Debug, Error)]
+pub enum CompactFilterError {
+    /// Unable to communicate with the Bitcoin peer.
+    #[error("{0}")]
+    Io(#[from] io::Error),
+    /// Unable to connect to the Bitcoin peer via the SOCKS5 proxy.
+    #[error("{0}")]
+    Proxy(#[from] ProxyError),
+    /// Unable to decode the message of the Bitcoin peer.
+    #[error("{0}")]
+    Encode(#[from] encode::Error),
+    /// Bitcoin peer has not responded in time.
+    #[error("Bitcoin peer has not responded in time")]
+    Timeout,
+    /// Bitcoin peer does not serve the compact block filters.
+    #[error("Bitcoin peer {0} does not serve the compact block filters")]
+    UnsupportedPeer(String),
+    /// Bitcoin peer has sent an unexpected message.
+    #[error("Unexpected message of the Bitcoin peer: {0}")]
+    Protocol(String),
+    /// Block headers sent by the Bitcoin peer do not form a valid chain.
+    #[error("Invalid block headers: {0}")]
+    InvalidChain(String),
+    /// Block filter sent by the Bitcoin peer is malformed.
+    #[error("Invalid block filter of the block {block_hash}: {message}")]
+    InvalidFilter {
+        /// Hash of the block.
+        block_hash: btc::Sha256d,
+        /// Error message.
+        message: String,
+    },
+    /// Bitcoin peer is unable to provide the block.
+    #[error("Block {0} is not available from the Bitcoin peer")]
+    BlockUnavailable(btc::Sha256d),
+    /// Bitcoin peers have served the conflicting filter headers, so at least one of them
+    /// is dishonest or follows another branch.
+    #[error(
+        "Bitcoin peers {peer} and {witness} have served conflicting filter headers \
+         of the blocks since the height {height}"
+    )]
+    ConflictingFilters {
+        /// Address of the main peer.
+        peer: String,
+        /// Address of the witness peer.
+        witness: String,
+        /// Height of the first block in the conflicting range.
+        height: u64,
+    },
+}
+
+/// Light Bitcoin relay, which follows the Bitcoin blockchain via the compact block filters
+/// (BIP 157 and BIP 158) served by a Bitcoin peer, so the anchoring transactions can be
+/// tracked without any full Bitcoin node, for example, by the auditors.
+///
+/// The relay downloads the block headers starting from the trusted start block, matches
+/// the block filters against the scripts of the watched anchoring addresses and downloads
+/// the matching blocks only. The headers are checked against the difficulty rules of
+/// the network, and the branch with the most work is followed. The blocks preceding
+/// the start block are unknown, so the difficulty of the first retarget is only checked
+/// to be within the adjustment bounds. The filters are checked against the filter headers
+/// (`cfheaders`), which are cross-checked with the witness peers, if any. The transactions of the watched addresses are indexed in memory,
+/// so the relay should be started from the block preceding the initial funding transaction,
+/// and the blocks are rescanned after the restart.
+///
+/// The transactions are broadcast to the same peer. The peer does not report whether
+/// the transaction has been accepted, so the relay may be combined with a fallback
+/// broadcaster, for example, the Esplora relay, via [`ComposedRelay`].
+///
+/// [`ComposedRelay`]: struct.ComposedRelay.html
+#[derive(Debug)]
+pub struct CompactFilterRelay {
+    config: CompactFilterConfig,
+    state: Mutex<FilterState>,
+}
+
+#[derive(Debug)]
+struct FilterState {
+    peer: Option<Peer>,
+    witnesses: Vec<Peer>,
+    chain: Option<HeaderChain>,
+    index: TransactionIndex,
+    last_sync: Option<Instant>,
+}
+
+impl FilterState {
+    /// Returns the header chain, which is available after the first synchronization.
+    fn chain(&self) -> &HeaderChain {
+        self.chain
+            .as_ref()
+            .expect("header chain is initialized by the synchronization")
+    }
+}
+
+impl CompactFilterRelay {
+    /// Creates a new relay with the given configuration. The connection to the peer
+    /// is established lazily and re-established after the communication errors.
+    pub fn new(config: CompactFilterConfig) -> Self {
+        let state = FilterState {
+            peer: None,
+            witnesses: Vec::new(),
+            chain: None,
+            index: TransactionIndex::new(config.start_height),
+            last_sync: None,
+        };
+        Self {
+            config,
+            state: Mutex::new(state),
+        }
+    }
+
+    /// Returns the address of the Bitcoin peer.
+    pub fn peer(&self) -> &str {
+        &self.config.peer
+    }
+
+    /// Returns the height of the latest block known to the relay.
+    pub async fn tip_height(&self) -> u64 {
+        self.state
+            .lock()
+            .await
+            .chain
+            .as_ref()
+            .map_or(self.config.start_height, HeaderChain::tip_height)
+    }
+
+    /// Locks the relay state synchronized with the peer. The synchronization is skipped
+    /// if the previous one has been performed recently, unless it is forced.
+    async fn synced_state(
+        &self,
+        force: bool,
+    ) -> Result<MutexGuard<'_, FilterState>, CompactFilterError> {
+        let mut state = self.state.lock().await;
+        let is_due = state
+            .last_sync
+            .map_or(true, |last_sync| last_sync.elapsed() >= SYNC_INTERVAL);
+        if force || is_due {
+            let result = self.sync(&mut state).await;
+            // The connection state is unknown after the failure, so the connection is dropped.
+            if result.is_err() {
+                state.peer = None;
+                state.witnesses.clear();
+            }
+            result?;
+            state.last_sync = Some(Instant::now());
+        }
+        Ok(state)
+    }
+
+    async fn sync(&self, state: &mut FilterState) -> Result<(), CompactFilterError> {
+        if state.peer.is_none() {
+            state.peer = Some(Peer::connect(&self.config, &self.config.peer).await?);
+        }
+        if state.witnesses.len() < self.config.witness_peers.len() {
+            state.witnesses.clear();
+            for address in &self.config.witness_peers {
+                state
+                    .witnesses
+                    .push(Peer::connect(&self.config, address).await?);
+            }
+        }
+        let FilterState {
+            peer,
+            witnesses,
+            chain,
+            index,
+            ..
+        } = state;
+        let peer = peer.as_mut().unwrap();
+
+        if chain.is_none() {
+            // Only the hash of the start block is trusted, so its header is taken from the block.
+            let start_block = BlockHash::from_hash(self.config.start_block.0);
+            let header = peer.block(start_block).await?.header;
+            *chain = Some(HeaderChain::new(
+                self.config.network,
+                self.config.start_height,
+                header,
+            ));
+        }
+        let chain = chain.as_mut().unwrap();
+
+        // The headers of all the peers are connected, so the branch with the most work wins.
+        let mut fork_height = None;
+        for peer in iter::once(&mut *peer).chain(witnesses.iter_mut()) {
+            if let Some(height) = peer.sync_headers(chain).await? {
+                fork_height = Some(fork_height.map_or(height, |fork: u64| fork.min(height)));
+            }
+        }
+        if let Some(fork_height) = fork_height {
+            log::warn!(
+                "Bitcoin blockchain has been reorganized since the block {}.",
+                fork_height
+            );
+            index.roll_back(fork_height);
+        }
+        // Nothing is matched against the empty filter query.
+        if !index.scripts.is_empty() {
+            peer.scan_filters(chain, index, witnesses).await?;
+        }
+        Ok(())
+    }
+}
+
+impl RelayBackend for CompactFilterRelay {
+    type Error = CompactFilterError;
+}
+
+#[async_trait]
+impl TxBroadcaster for CompactFilterRelay {
+    async fn send_transaction(
+        &self,
+        transaction: &btc::Transaction,
+    ) -> Result<btc::Sha256d, Self::Error> {
+        let mut state = self.state.lock().await;
+        if state.peer.is_none() {
+            state.peer = Some(Peer::connect(&self.config, &self.config.peer).await?);
+        }
+        let peer = state.peer.as_mut().unwrap();
+        if let Err(e) = peer.send(NetworkMessage::Tx(transaction.0.clone())).await {
+            state.peer = None;
+            return Err(e);
+        }
+        state.index.broadcast.insert(transaction.0.txid());
+        Ok(transaction.id())
+    }
+}
+
+#[async_trait]
+impl ChainQuery for CompactFilterRelay {
+    // The relay does not see the mempool of the peer, so the transactions broadcast by
+    // this relay are considered to be in the mempool until they are confirmed.
+    async fn transaction_status(&self, id: btc::Sha256d) -> Result<TransactionStatus, Self::Error> {
+        let state = self.synced_state(false).await?;
+        let txid = Txid::from(id);
+        Ok(match state.index.transactions.get(&txid) {
+            Some(entry) => TransactionStatus::Committed(state.chain().confirmations(entry.height)),
+            None if state.index.broadcast.contains(&txid) => TransactionStatus::Mempool,
+            None => TransactionStatus::Unknown,
+        })
+    }
+
+    async fn spending_transaction(
+        &self,
+        outpoint: OutPoint,
+    ) -> Result<Option<btc::Transaction>, Self::Error> {
+        let state = self.synced_state(false).await?;
+        Ok(state
+            .index
+            .spends
+            .get(&outpoint)
+            .and_then(|txid| state.index.transactions.get(txid))
+            .map(|entry| entry.transaction.clone()))
+    }
+
+    async fn transaction_block_hash(
+        &self,
+        id: btc::Sha256d,
+    ) -> Result<Option<btc::Sha256d>, Self::Error> {
+        let state = self.synced_state(false).await?;
+        Ok(state
+            .index
+            .transactions
+            .get(&Txid::from(id))
+            .map(|entry| btc::Sha256d(entry.block_hash.as_hash())))
+    }
+
+    // The lost connection to the peer is restored during the synchronization.
+    async fn ping(&self) -> Result<Option<u64>, Self::Error> {
+        let state = self.synced_state(false).await?;
+        Ok(Some(state.chain().tip_height()))
+    }
+}
+
+#[async_trait]
+impl WalletOps for CompactFilterRelay {
+    async fn watch_address(
+        &self,
+        descriptor: &AnchoringDescriptor,
+        _label: &str,
+    ) -> Result<(), Self::Error> {
+        let mut state = self.state.lock().await;
+        state
+            .index
+            .scripts
+            .insert(descriptor.address.0.script_pubkey());
+        Ok(())
+    }
+
+    async fn restore_watch_data(
+        &self,
+        descriptor: &AnchoringDescriptor,
+        _label: &str,
+        latest_anchor: btc::Sha256d,
+        _funding_tx: btc::Sha256d,
+        fallback_height: Option<u64>,
+    ) -> Result<Option<u64>, Self::Error> {
+        let mut state = self.synced_state(false).await?;
+        if state
+            .index
+            .transactions
+            .contains_key(&Txid::from(latest_anchor))
+        {
+            return Ok(None);
+        }
+
+        // The blocks preceding the start block are unknown to the relay.
+        let rescan_height = fallback_height
+            .unwrap_or(self.config.start_height)
+            .max(self.config.start_height);
+        state
+            .index
+            .scripts
+            .insert(descriptor.address.0.script_pubkey());
+        state.index.next_height = state.index.next_height.min(rescan_height);
+        drop(state);
+        self.synced_state(true).await?;
+        Ok(Some(rescan_height))
+    }
+
+    async fn unspent_outputs(
+        &self,
+        address: &btc::Address,
+    ) -> Result<Vec<WalletUtxo>, Self::Error> {
+        let state = self.synced_state(false).await?;
+        let script = address.0.script_pubkey();
+        let mut utxos = state
+            .index
+            .outputs
+            .iter()
+            .filter(|(outpoint, out)| {
+                out.script_pubkey == script && !state.index.spends.contains_key(*outpoint)
+            })
+            .map(|(outpoint, out)| {
+                let height = state.index.transactions[&outpoint.txid].height;
+                WalletUtxo {
+                    txid: outpoint.txid.into(),
+                    vout: outpoint.vout,
+                    value: out.value,
+                    confirmations: state.chain().confirmations(height),
+                }
+            })
+            .collect::<Vec<_>>();
+        utxos.sort_by_key(|utxo| std::cmp::Reverse(utxo.confirmations));
+        Ok(utxos)
+    }
+}
+
+/// Connection to the Bitcoin peer.
+#[derive(Debug)]
+struct Peer {
+    address: String,
+    stream: TcpStream,
+    network: Network,
+}
+
+impl Peer {
+    /// Connects to the peer with the given address and performs the version handshake.
+    async fn connect(
+        config: &CompactFilterConfig,
+        address: &str,
+    ) -> Result<Self, CompactFilterError> {
+        let stream = match &config.proxy {
+            Some(proxy) => proxy.connect(address).await?,
+            None => TcpStream::connect(address).await?,
+        };
+        let mut peer = Self {
+            address: address.to_owned(),
+            stream,
+            network: config.network,
+        };
+
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_secs() as i64)
+            .unwrap_or_default();
+        let unknown_address = PeerAddress::new(
+            &SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0)),
+            ServiceFlags::NONE,
+        );
+        // The relay flag is unset, so the peer does not announce its mempool transactions.
+        let version = VersionMessage::new(
+            ServiceFlags::NONE,
+            timestamp,
+            unknown_address.clone(),
+            unknown_address,
+            thread_rng().gen(),
+            USER_AGENT.to_owned(),
+            0,
+        );
+        peer.send(NetworkMessage::Version(version)).await?;
+
+        let (mut has_version, mut has_verack) = (false, false);
+        while !(has_version && has_verack) {
+            match peer.receive().await? {
+                NetworkMessage::Version(version) => {
+                    if !version.services.has(ServiceFlags::COMPACT_FILTERS) {
+                        return Err(CompactFilterError::UnsupportedPeer(address.to_owned()));
+                    }
+                    peer.send(NetworkMessage::Verack).await?;
+                    has_version = true;
+                }
+                NetworkMessage::Verack => has_verack = true,
+                _ => {}
+            }
+        }
+        Ok(peer)
+    }
+
+    async fn send(&mut self, payload: NetworkMessage) -> Result<(), CompactFilterError> {
+        let message = RawNetworkMessage {
+            magic: self.network.magic(),
+            payload,
+        };
+        self.stream.write_all(&serialize(&message)).await?;
+        Ok(())
+    }
+
+    /// Receives the next message handled by the relay, answering the pings of the peer.
+    async fn receive(&mut self) -> Result<NetworkMessage, CompactFilterError> {
+        loop {
+            let message = tokio::time::timeout(RESPONSE_TIMEOUT, self.read_message())
+                .await
+                .map_err(|_| CompactFilterError::Timeout)??;
+            match message {
+                Some(NetworkMessage::Ping(nonce)) => self.send(NetworkMessage::Pong(nonce)).await?,
+                Some(message) => return Ok(message),
+                None => {}
+            }
+        }
+    }
+
+    /// Reads the next message of the peer, or `None` if the message is not handled
+    /// by the relay.
+    async fn read_message(&mut self) -> Result<Option<NetworkMessage>, CompactFilterError> {
+        let mut message = vec![0_u8; MESSAGE_HEADER_LEN];
+        self.stream.read_exact(&mut message).await?;
+        if message[..4] != self.network.magic().to_le_bytes() {
+            return Err(CompactFilterError::Protocol(
+                "message of another Bitcoin network".to_owned(),
+            ));
+        }
+        let payload_len =
+            u32::from_le_bytes([message[16], message[17], message[18], message[19]]) as usize;
+        if payload_len > MAX_PAYLOAD_LEN {
+            return Err(CompactFilterError::Protocol(format!(
+                "payload of {} bytes exceeds the limit",
+                payload_len
+            )));
+        }
+        message.resize(MESSAGE_HEADER_LEN + payload_len, 0);
+        self.stream
+            .read_exact(&mut message[MESSAGE_HEADER_LEN..])
+            .await?;
+
+        if !HANDLED_COMMANDS.contains(&command_name(&message[4..16])) {
+            return Ok(None);
+        }
+        let message: RawNetworkMessage = deserialize(&message)?;
+        Ok(Some(message.payload))
+    }
+
+    /// Downloads the block headers of the peer following the given chain and connects them
+    /// if they have more work than the chain. Returns the height of the latest common block
+    /// if the chain has switched to another branch.
+    async fn sync_headers(
+        &mut self,
+        chain: &mut HeaderChain,
+    ) -> Result<Option<u64>, CompactFilterError> {
+        // The whole branch of the peer is downloaded, since the work of its part may be less
+        // than the work of the chain.
+        let mut branch: Vec<BlockHeader> = Vec::new();
+        loop {
+            let mut locator = chain.locator();
+            if let Some(header) = branch.last() {
+                locator.insert(0, header.block_hash());
+            }
+            let request = GetHeadersMessage::new(locator, BlockHash::from_inner([0; 32]));
+            self.send(NetworkMessage::GetHeaders(request)).await?;
+            let headers = loop {
+                if let NetworkMessage::Headers(headers) = self.receive().await? {
+                    break headers;
+                }
+            };
+
+            let is_complete = headers.len() < MAX_HEADERS;
+            branch.extend(headers);
+            if is_complete {
+                return chain.connect(&branch);
+            }
+        }
+    }
+
+    /// Downloads the hashes of the basic filters of the blocks from the start height up to
+    /// the stop block along with the filter header of the block preceding them.
+    async fn filter_hashes(
+        &mut self,
+        start_height: u64,
+        stop_height: u64,
+        stop_hash: BlockHash,
+    ) -> Result<(FilterHash, Vec<FilterHash>), CompactFilterError> {
+        let request = GetCFHeaders {
+            filter_type: BASIC_FILTER,
+            start_height: start_height as u32,
+            stop_hash,
+        };
+        self.send(NetworkMessage::GetCFHeaders(request)).await?;
+        let headers = loop {
+            if let NetworkMessage::CFHeaders(headers) = self.receive().await? {
+                break headers;
+            }
+        };
+        if headers.filter_type != BASIC_FILTER
+            || headers.stop_hash != stop_hash
+            || headers.filter_hashes.len() as u64 != stop_height - start_height + 1
+        {
+            return Err(CompactFilterError::Protocol(format!(
+                "filter headers up to the block {} instead of the block {}",
+                headers.stop_hash, stop_hash
+            )));
+        }
+        Ok((headers.previous_filter, headers.filter_hashes))
+    }
+
+    /// Matches the filters of the blocks following the latest scanned one against
+    /// the watched scripts and indexes the transactions of the matching blocks.
+    /// The filters are checked against the filter headers, which should be the same
+    /// for all the witness peers.
+    async fn scan_filters(
+        &mut self,
+        chain: &HeaderChain,
+        index: &mut TransactionIndex,
+        witnesses: &mut [Peer],
+    ) -> Result<(), CompactFilterError> {
+        while index.next_height <= chain.tip_height() {
+            let start_height = index.next_height;
+            let stop_height = chain.tip_height().min(start_height + MAX_FILTERS - 1);
+            let stop_hash = chain.hash_at(stop_height).unwrap();
+
+            let (previous, filter_hashes) = self
+                .filter_hashes(start_height, stop_height, stop_hash)
+                .await?;
+            for witness in witnesses.iter_mut() {
+                let (witness_previous, witness_hashes) = witness
+                    .filter_hashes(start_height, stop_height, stop_hash)
+                    .await?;
+                if witness_previous != previous || witness_hashes != filter_hashes {
+                    return Err(CompactFilterError::ConflictingFilters {
+                        peer: self.address.clone(),
+                        witness: witness.address.clone(),
+                        height: start_height,
+                    });
+                }
+            }
+            let filter_headers = index
+                .check_filter_headers(start_height, previous, &filter_hashes)
+                .map_err(CompactFilterError::Protocol)?;
+
+            let request = GetCFilters {
+                filter_type: BASIC_FILTER,
+                start_height: start_height as u32,
+                stop_hash,
+            };
+            self.send(NetworkMessage::GetCFilters(request)).await?;
+
+            let mut matched_blocks = Vec::new();
+            for height in start_height..=stop_height {
+                let filter = loop {
+                    if let NetworkMessage::CFilter(filter) = self.receive().await? {
+                        break filter;
+                    }
+                };
+                if filter.filter_type != BASIC_FILTER
+                    || Some(filter.block_hash) != chain.hash_at(height)
+                {
+                    return Err(CompactFilterError::Protocol(format!(
+                        "filter of the block {} instead of the block at height {}",
+                        filter.block_hash, height
+                    )));
+                }
+                let filter_hash = &filter_hashes[(height - start_height) as usize];
+                if FilterHash::hash(&filter.filter) != *filter_hash {
+                    return Err(CompactFilterError::InvalidFilter {
+                        block_hash: btc::Sha256d(filter.block_hash.as_hash()),
+                        message: "filter does not match the filter header".to_owned(),
+                    });
+                }
+
+                let mut query = index.scripts.iter().map(Script::as_bytes);
+                let is_match = BlockFilter::new(&filter.filter)
+                    .match_any(&filter.block_hash, &mut query)
+                    .map_err(|e| CompactFilterError::InvalidFilter {
+                        block_hash: btc::Sha256d(filter.block_hash.as_hash()),
+                        message: e.to_string(),
+                    })?;
+                if is_match {
+                    matched_blocks.push((height, filter.block_hash));
+                }
+            }
+
+            for (height, block_hash) in matched_blocks {
+                let block = self.block(block_hash).await?;
+                index.add_block(&block, height);
+            }
+            index.filter_headers.extend(filter_headers);
+            index.next_height = stop_height + 1;
+        }
+        Ok(())
+    }
+
+    /// Downloads the block with the given hash including the witness data.
+    async fn block(&mut self, block_hash: BlockHash) -> Result<Block, CompactFilterError> {
+        let inventory = Inventory {
+            inv_type: InvType::WitnessBlock,
+            hash: block_hash.as_hash(),
+        };
+        self.send(NetworkMessage::GetData(vec![inventory])).await?;
+        let block = loop {
+            match self.receive().await? {
+                NetworkMessage::Block(block) if block.header.block_hash() == block_hash => {
+                    break block
+                }
+                NetworkMessage::NotFound(_) => {
+                    return Err(CompactFilterError::BlockUnavailable(btc::Sha256d(
+                        block_hash.as_hash(),
+                    )))
+                }
+                _ => {}
+            }
+        };
+        if !block.check_merkle_root() {
+            return Err(CompactFilterError::Protocol(format!(
+                "transactions of the block {} do not match its header",
+                block_hash
+            )));
+        }
+        Ok(block)
+    }
+}
+
+/// Returns the command name of the P2P message padded with zeros.
+fn command_name(command: &[u8]) -> &str {
+    let len = command
+        .iter()
+        .position(|&c| c == 0)
+        .unwrap_or(command.len());
+    std::str::from_utf8(&command[..len]).unwrap_or_default()
+}
+
+/// Returns the filter header following the given one as defined in BIP 157.
+fn filter_header(filter_hash: &FilterHash, prev_header: &FilterHash) -> FilterHash {
+    let mut engine = FilterHash::engine();
+    engine.input(&filter_hash[..]);
+    engine.input(&prev_header[..]);
+    FilterHash::from_engine(engine)
+}
+
+/// Returns the compact representation of the target, the same as `GetCompact` of Bitcoin Core.
+fn compact_target(target: &Uint256) -> u32 {
+    let mut size = (target.bits() + 7) / 8;
+    let mut compact = if size <= 3 {
+        (target.low_u64() << (8 * (3 - size))) as u32
+    } else {
+        (*target >> (8 * (size - 3))).low_u64() as u32
+    };
+    // The sign bit of the mantissa should be unset.
+    if compact & 0x0080_0000 != 0 {
+        compact >>= 8;
+        size += 1;
+    }
+    compact | (size as u32) << 24
+}
+
+/// Returns the difficulty bits required after the retarget period of the given duration
+/// finished by the block with the given bits.
+fn next_bits(params: &Params, prev_bits: u32, timespan: i64) -> u32 {
+    let target_timespan = params.pow_target_timespan as i64;
+    let timespan = timespan.max(target_timespan / 4).min(target_timespan * 4);
+    let target = BlockHeader::u256_from_compact_target(prev_bits).mul_u32(timespan as u32)
+        / Uint256::from_u64(target_timespan as u64).unwrap();
+    compact_target(&target.min(params.pow_limit))
+}
+
+/// Block header of the chain with the total work of the chain up to this block.
+#[derive(Debug, Clone, Copy)]
+struct ChainEntry {
+    header: BlockHeader,
+    hash: BlockHash,
+    work: Uint256,
+}
+
+impl ChainEntry {
+    fn new(header: BlockHeader, prev_work: Option<Uint256>) -> Self {
+        let work = match prev_work {
+            Some(prev_work) => prev_work + header.work(),
+            None => header.work(),
+        };
+        Self {
+            header,
+            hash: header.block_hash(),
+            work,
+        }
+    }
+}
+
+/// Chain of the block headers with the most work starting from the trusted start block.
+#[derive(Debug)]
+struct HeaderChain {
+    network: Network,
+    start_height: u64,
+    entries: Vec<ChainEntry>,
+    heights: HashMap<BlockHash, u64>,
+}
+
+impl HeaderChain {
+    fn new(network: Network, start_height: u64, start_header: BlockHeader) -> Self {
+        let start = ChainEntry::new(start_header, None);
+        let mut heights = HashMap::new();
+        heights.insert(start.hash, start_height);
+        Self {
+            network,
+            start_height,
+            entries: vec![start],
+            heights,
+        }
+    }
+
+    fn tip_height(&self) -> u64 {
+        self.start_height + self.entries.len() as u64 - 1
+    }
+
+    fn hash_at(&self, height: u64) -> Option<BlockHash> {
+        let offset = height.checked_sub(self.start_height)?;
+        self.entries.get(offset as usize).map(|entry| entry.hash)
+    }
+
+    /// Returns the number of confirmations of the block at the given height.
+    fn confirmations(&self, height: u64) -> u32 {
+        (self.tip_height() + 1).saturating_sub(height) as u32
+    }
+
+    /// Returns the block locator: the latest hashes one by one, then with the exponentially
+    /// increasing step, and the start block.
+    fn locator(&self) -> Vec<BlockHash> {
+        let mut locator = Vec::new();
+        let mut step = 1;
+        let mut offset = self.entries.len() - 1;
+        while offset > 0 {
+            locator.push(self.entries[offset].hash);
+            if locator.len() >= LOCATOR_DENSE_HASHES {
+                step *= 2;
+            }
+            offset = offset.saturating_sub(step);
+        }
+        locator.push(self.entries[0].hash);
+        locator
+    }
+
+    /// Returns the header at the given height of the branch following the block
+    /// at the fork height, or `None` if the block precedes the start block.
+    fn branch_header<'a>(
+        &'a self,
+        fork_height: u64,
+        branch: &'a [ChainEntry],
+        height: u64,
+    ) -> Option<&'a BlockHeader> {
+        let entry = if height > fork_height {
+            branch.get((height - fork_height - 1) as usize)
+        } else {
+            self.entries
+                .get(height.checked_sub(self.start_height)? as usize)
+        };
+        entry.map(|entry| &entry.header)
+    }
+
+    /// Checks the timestamp and the proof of work of the header at the given height
+    /// of the branch following the block at the fork height.
+    fn check_header(
+        &self,
+        params: &Params,
+        fork_height: u64,
+        branch: &[ChainEntry],
+        height: u64,
+        header: &BlockHeader,
+    ) -> Result<(), String> {
+        let ancestor = |height: u64| self.branch_header(fork_height, branch, height);
+        let prev = ancestor(height - 1).expect("previous block is connected");
+
+        let mut times = (height.saturating_sub(MEDIAN_TIME_SPAN)..height)
+            .filter_map(ancestor)
+            .map(|header| header.time)
+            .collect::<Vec<_>>();
+        times.sort_unstable();
+        if header.time <= times[times.len() / 2] {
+            return Err("timestamp does not exceed the median time of the previous blocks".into());
+        }
+
+        // Difficulty rules are the same as `GetNextWorkRequired` of Bitcoin Core.
+        let interval = params.pow_target_timespan / params.pow_target_spacing;
+        let pow_limit_bits = compact_target(&params.pow_limit);
+        let required_bits = if params.no_pow_retargeting {
+            Some(prev.bits)
+        } else if height % interval != 0 {
+            if !params.allow_min_difficulty_blocks {
+                Some(prev.bits)
+            } else if u64::from(header.time) > u64::from(prev.time) + 2 * params.pow_target_spacing
+            {
+                Some(pow_limit_bits)
+            } else {
+                // Testnet blocks have the difficulty of the latest block mined without
+                // the minimal difficulty exception.
+                let mut ancestor_height = height - 1;
+                loop {
+                    match ancestor(ancestor_height) {
+                        Some(block)
+                            if ancestor_height % interval != 0 && block.bits == pow_limit_bits =>
+                        {
+                            ancestor_height -= 1
+                        }
+                        Some(block) => break Some(block.bits),
+                        None => break None,
+                    }
+                }
+            }
+        } else if let Some(first) = ancestor(height - interval) {
+            let timespan = i64::from(prev.time) - i64::from(first.time);
+            Some(next_bits(params, prev.bits, timespan))
+        } else {
+            // The start of the retarget period precedes the start block, so the difficulty
+            // is only checked to be within the adjustment bounds.
+            let max_target = BlockHeader::u256_from_compact_target(next_bits(
+                params,
+                prev.bits,
+                i64::max_value(),
+            ));
+            let min_target = BlockHeader::u256_from_compact_target(next_bits(params, prev.bits, 0));
+            if header.target() > max_target || header.target() < min_target {
+                return Err(format!(
+                    "difficulty bits {:#x} exceed the adjustment bounds",
+                    header.bits
+                ));
+            }
+            None
+        };
+
+        if let Some(required_bits) = required_bits {
+            if header.bits != required_bits {
+                return Err(format!(
+                    "difficulty bits {:#x} instead of {:#x}",
+                    header.bits, required_bits
+                ));
+            }
+        }
+        if header.target() > params.pow_limit || header.validate_pow(&header.target()).is_err() {
+            return Err("insufficient proof of work".into());
+        }
+        Ok(())
+    }
+
+    /// Connects the headers to the chain if they have more work than the blocks replaced
+    /// by them, if any. Returns the height of the latest common block if the blocks
+    /// have been replaced.
+    fn connect(&mut self, headers: &[BlockHeader]) -> Result<Option<u64>, CompactFilterError> {
+        let first = match headers.first() {
+            Some(header) => header,
+            None => return Ok(None),
+        };
+        let fork_height = *self.heights.get(&first.prev_blockhash).ok_or_else(|| {
+            CompactFilterError::InvalidChain(format!(
+                "block {} does not follow the known blocks",
+                first.block_hash()
+            ))
+        })?;
+        let fork_offset = (fork_height - self.start_height) as usize;
+
+        let params = Params::new(self.network);
+        let mut branch: Vec<ChainEntry> = Vec::with_capacity(headers.len());
+        for header in headers {
+            let prev = *branch.last().unwrap_or(&self.entries[fork_offset]);
+            if header.prev_blockhash != prev.hash {
+                return Err(CompactFilterError::InvalidChain(format!(
+                    "block {} does not follow the block {}",
+                    header.block_hash(),
+                    prev.hash
+                )));
+            }
+            let height = fork_height + branch.len() as u64 + 1;
+            self.check_header(&params, fork_height, &branch, height, header)
+                .map_err(|message| {
+                    CompactFilterError::InvalidChain(format!(
+                        "block {}: {}",
+                        header.block_hash(),
+                        message
+                    ))
+                })?;
+            branch.push(ChainEntry::new(*header, Some(prev.work)));
+        }
+
+        // Of the branches with the same work, the first seen one is followed,
+        // like in Bitcoin Core.
+        let tip = self.entries[self.entries.len() - 1];
+        if branch[branch.len() - 1].work <= tip.work {
+            log::warn!(
+                "Ignored the branch of {} blocks following the block {}, which does not have \
+                 more work than the current chain.",
+                branch.len(),
+                fork_height
+            );
+            return Ok(None);
+        }
+
+        let is_reorganized = fork_height < self.tip_height();
+        for stale in self.entries.drain(fork_offset + 1..) {
+            self.heights.remove(&stale.hash);
+        }
+        for entry in branch {
+            self.heights.insert(entry.hash, self.tip_height() + 1);
+            self.entries.push(entry);
+        }
+        Ok(if is_reorganized {
+            Some(fork_height)
+        } else {
+            None
+        })
+    }
+}
+
+/// Transaction of the watched addresses with its location in the blockchain.
+#[derive(Debug)]
+struct IndexedTransaction {
+    transaction: btc::Transaction,
+    height: u64,
+    block_hash: BlockHash,
+}
+
+/// Index of the transactions of the watched addresses.
+#[derive(Debug)]
+struct TransactionIndex {
+    /// Output scripts of the watched addresses.
+    scripts: HashSet<Script>,
+    /// Height of the next block to be matched against the filter.
+    next_height: u64,
+    /// Transactions of the watched addresses.
+    transactions: HashMap<Txid, IndexedTransaction>,
+    /// Outputs to the watched addresses.
+    outputs: HashMap<OutPoint, TxOut>,
+    /// Spent outputs to the watched addresses with the spending transactions.
+    spends: HashMap<OutPoint, Txid>,
+    /// Transactions broadcast by the relay.
+    broadcast: HashSet<Txid>,
+    /// Filter headers of the scanned blocks.
+    filter_headers: BTreeMap<u64, FilterHash>,
+}
+
+impl TransactionIndex {
+    fn new(start_height: u64) -> Self {
+        Self {
+            scripts: HashSet::new(),
+            next_height: start_height,
+            transactions: HashMap::new(),
+            outputs: HashMap::new(),
+            spends: HashMap::new(),
+            broadcast: HashSet::new(),
+            filter_headers: BTreeMap::new(),
+        }
+    }
+
+    /// Computes the filter headers of the blocks starting from the given height from
+    /// the filter hashes and the preceding filter header served by the peer. The headers
+    /// should match the ones of the previously scanned blocks.
+    fn check_filter_headers(
+        &self,
+        start_height: u64,
+        previous: FilterHash,
+        filter_hashes: &[FilterHash],
+    ) -> Result<Vec<(u64, FilterHash)>, String> {
+        let known_previous = start_height
+            .checked_sub(1)
+            .and_then(|height| self.filter_headers.get(&height));
+        if known_previous.map_or(false, |known| *known != previous) {
+            return Err(format!(
+                "filter header of the block at height {} differs from the scanned one",
+                start_height - 1
+            ));
+        }
+
+        let mut prev_header = previous;
+        let mut headers = Vec::with_capacity(filter_hashes.len());
+        for (height, filter_hash) in (start_height..).zip(filter_hashes) {
+            let header = filter_header(filter_hash, &prev_header);
+            if self
+                .filter_headers
+                .get(&height)
+                .map_or(false, |known| *known != header)
+            {
+                return Err(format!(
+                    "filter header of the block at height {} differs from the scanned one",
+                    height
+                ));
+            }
+            headers.push((height, header));
+            prev_header = header;
+        }
+        Ok(headers)
+    }
+
+    /// Adds the transactions of the watched addresses from the given block.
+    fn add_block(&mut self, block: &Block, height: u64) {
+        let block_hash = block.header.block_hash();
+        for tx in &block.txdata {
+            let txid = tx.txid();
+            let mut is_relevant = false;
+            for input in &tx.input {
+                if self.outputs.contains_key(&input.previous_output) {
+                    self.spends.insert(input.previous_output, txid);
+                    is_relevant = true;
+                }
+            }
+            for (vout, out) in tx.output.iter().enumerate() {
+                if self.scripts.contains(&out.script_pubkey) {
+                    let outpoint = OutPoint::new(txid, vout as u32);
+                    self.outputs.insert(outpoint, out.clone());
+                    is_relevant = true;
+                }
+            }
+
+            if is_relevant {
+                let entry = IndexedTransaction {
+                    transaction: btc::Transaction(tx.clone()),
+                    height,
+                    block_hash,
+                };
+                self.transactions.insert(txid, entry);
+            }
+        }
+    }
+
+    /// Removes the transactions of the blocks above the given height.
+    fn roll_back(&mut self, height: u64) {
+        self.transactions.retain(|_, entry| entry.height <= height);
+        let transactions = &self.transactions;
+        self.outputs
+            .retain(|outpoint, _| transactions.contains_key(&outpoint.txid));
+        self.spends
+            .retain(|_, txid| transactions.contains_key(txid));
+        self.filter_headers.split_off(&(height + 1));
+        self.next_height = self.next_height.min(height + 1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::{
+        blockdata::constants::genesis_block,
+        consensus::params::Params,
+        hash_types::{FilterHash, TxMerkleNode},
+        network::constants::Network,
+        Block, BlockHeader, OutPoint, Script, Transaction, TxIn, TxOut,
+    };
+    use bitcoin_hashes::Hash as BitcoinHash;
+
+    use super::{
+        command_name, compact_target, filter_header, next_bits, HeaderChain, TransactionIndex,
+    };
+
+    /// Mines the header with the given difficulty bits following the given one.
+    fn mine_header(prev: &BlockHeader, time: u32, bits: u32) -> BlockHeader {
+        let mut header = BlockHeader {
+            version: 1,
+            prev_blockhash: prev.block_hash(),
+            merkle_root: TxMerkleNode::from_inner([0; 32]),
+            time,
+            bits,
+            nonce: 0,
+        };
+        while header.validate_pow(&header.target()).is_err() {
+            header.nonce += 1;
+        }
+        header
+    }
+
+    /// Mines the headers with the minimal difficulty of the regtest following the given one.
+    /// The branches following the same block differ in the time shift.
+    fn branch(prev: &BlockHeader, len: usize, time_shift: u32) -> Vec<BlockHeader> {
+        let mut headers: Vec<BlockHeader> = Vec::with_capacity(len);
+        for _ in 0..len {
+            let prev = headers.last().unwrap_or(prev);
+            let header = mine_header(prev, prev.time + 600 + time_shift, prev.bits);
+            headers.push(header);
+        }
+        headers
+    }
+
+    #[test]
+    fn header_chain_reorganization() {
+        let genesis = genesis_block(Network::Regtest).header;
+        let mut chain = HeaderChain::new(Network::Regtest, 0, genesis);
+        let headers = branch(&genesis, 5, 1);
+        assert_eq!(chain.connect(&headers).unwrap(), None);
+        assert_eq!(chain.tip_height(), 5);
+        assert_eq!(chain.hash_at(3), Some(headers[2].block_hash()));
+        assert_eq!(chain.confirmations(3), 3);
+
+        // The last two blocks are replaced by the longer branch.
+        let fork = branch(&headers[2], 3, 2);
+        assert_eq!(chain.connect(&fork).unwrap(), Some(3));
+        assert_eq!(chain.tip_height(), 6);
+        assert_eq!(chain.hash_at(4), Some(fork[0].block_hash()));
+        assert!(!chain.heights.contains_key(&headers[4].block_hash()));
+
+        // The branch without more work is ignored.
+        let same_work = branch(&headers[2], 3, 3);
+        assert_eq!(chain.connect(&same_work).unwrap(), None);
+        assert_eq!(chain.hash_at(6), Some(fork[2].block_hash()));
+        assert!(!chain.heights.contains_key(&same_work[0].block_hash()));
+
+        let unknown = branch(&fork[0], 2, 4);
+        chain.connect(&unknown[1..]).unwrap_err();
+        let mut invalid = branch(&fork[2], 2, 5);
+        invalid[1].prev_blockhash = genesis.block_hash();
+        chain.connect(&invalid).unwrap_err();
+        assert_eq!(chain.tip_height(), 6);
+    }
+
+    #[test]
+    fn header_chain_consensus_rules() {
+        let genesis = genesis_block(Network::Regtest).header;
+        let mut chain = HeaderChain::new(Network::Regtest, 0, genesis);
+        let headers = branch(&genesis, 11, 0);
+        chain.connect(&headers).unwrap();
+
+        // The difficulty of the regtest is not adjusted.
+        let tip = headers[10];
+        let harder = mine_header(&tip, tip.time + 600, 0x2000_ffff);
+        chain.connect(&[harder]).unwrap_err();
+        let mut unmined = mine_header(&tip, tip.time + 600, tip.bits);
+        unmined.nonce += 1;
+        while unmined.validate_pow(&unmined.target()).is_ok() {
+            unmined.nonce += 1;
+        }
+        chain.connect(&[unmined]).unwrap_err();
+        // The timestamp should exceed the median time of the 11 previous blocks.
+        let outdated = mine_header(&tip, headers[5].time, tip.bits);
+        chain.connect(&[outdated]).unwrap_err();
+        let delayed = mine_header(&tip, headers[5].time + 1, tip.bits);
+        chain.connect(&[delayed]).unwrap();
+        assert_eq!(chain.tip_height(), 12);
+    }
+
+    #[test]
+    fn difficulty_retarget() {
+        let params = Params::new(Network::Bitcoin);
+        assert_eq!(compact_target(&params.pow_limit), 0x1d00_ffff);
+        let regtest = Params::new(Network::Regtest);
+        assert_eq!(compact_target(&regtest.pow_limit), 0x207f_ffff);
+
+        // Vectors of the retargets of the Bitcoin mainnet from the Bitcoin Core tests.
+        let retargets = [
+            (0x1d00_ffff, 1_231_006_505, 1_233_061_996, 0x1d00_ffff),
+            (0x1d00_ffff, 1_261_130_161, 1_262_152_739, 0x1d00_d86a),
+            (0x1c05_a3f4, 1_279_008_237, 1_279_297_671, 0x1c01_68fd),
+            (0x1c38_7f6f, 1_263_163_443, 1_269_211_443, 0x1d00_e1fd),
+        ];
+        for &(prev_bits, first_time, last_time, expected_bits) in &retargets {
+            let timespan = last_time - first_time;
+            assert_eq!(next_bits(&params, prev_bits, timespan), expected_bits);
+        }
+    }
+
+    #[test]
+    fn filter_headers() {
+        let mut index = TransactionIndex::new(10);
+        let previous = FilterHash::hash(b"previous");
+        let hashes = [FilterHash::hash(b"first"), FilterHash::hash(b"second")];
+        let headers = index.check_filter_headers(10, previous, &hashes).unwrap();
+        assert_eq!(headers[0], (10, filter_header(&hashes[0], &previous)));
+        assert_eq!(headers[1], (11, filter_header(&hashes[1], &headers[0].1)));
+        index.filter_headers.extend(headers.clone());
+
+        // The headers of the scanned blocks should not change.
+        assert_eq!(
+            index.check_filter_headers(10, previous, &hashes).unwrap(),
+            headers
+        );
+        index
+            .check_filter_headers(11, previous, &hashes[1..])
+            .unwrap_err();
+        index
+            .check_filter_headers(10, previous, &[hashes[1]])
+            .unwrap_err();
+        let third = [FilterHash::hash(b"third")];
+        index
+            .check_filter_headers(12, headers[1].1, &third)
+            .unwrap();
+
+        index.roll_back(10);
+        assert_eq!(index.filter_headers.len(), 1);
+        index
+            .check_filter_headers(11, headers[0].1, &third)
+            .unwrap();
+    }
+
+    #[test]
+    fn header_chain_locator() {
+        let genesis = genesis_block(Network::Regtest).header;
+        let mut chain = HeaderChain::new(Network::Regtest, 100, genesis);
+        assert_eq!(chain.locator(), vec![genesis.block_hash()]);
+
+        let headers = branch(&genesis, 30, 1);
+        chain.connect(&headers).unwrap();
+        let locator = chain.locator();
+        let expected_heights = [
+            130, 129, 128, 127, 126, 125, 124, 123, 122, 121, 119, 115, 107,
+        ];
+        let mut expected = expected_heights
+            .iter()
+            .map(|&height| chain.hash_at(height).unwrap())
+            .collect::<Vec<_>>();
+        expected.push(genesis.block_hash());
+        assert_eq!(locator, expected);
+    }
+
+    #[test]
+    fn transaction_index() {
+        let watched = Script::from(vec![0x00, 0x20, 0x01]);
+        let other = Script::from(vec![0x00, 0x20, 0x02]);
+        let mut index = TransactionIndex::new(10);
+        index.scripts.insert(watched.clone());
+
+        let transaction = |previous_output: OutPoint, script_pubkey: &Script| Transaction {
+            version: 2,
+            lock_time: 0,
+            input: vec![TxIn {
+                previous_output,
+                script_sig: Script::new(),
+                sequence: 0xFFFF_FFFF,
+                witness: Vec::new(),
+            }],
+            output: vec![TxOut {
+                value: 1_000,
+                script_pubkey: script_pubkey.clone(),
+            }],
+        };
+        let block = |txdata: Vec<Transaction>| Block {
+            header: genesis_block(Network::Regtest).header,
+            txdata,
+        };
+
+        let funding = transaction(OutPoint::default(), &watched);
+        let unrelated = transaction(OutPoint::default(), &other);
+        index.add_block(&block(vec![funding.clone(), unrelated.clone()]), 12);
+        assert!(index.transactions.contains_key(&funding.txid()));
+        assert!(!index.transactions.contains_key(&unrelated.txid()));
+
+        let funding_out = OutPoint::new(funding.txid(), 0);
+        let anchor = transaction(funding_out, &watched);
+        index.add_block(&block(vec![anchor.clone()]), 14);
+        assert_eq!(index.spends[&funding_out], anchor.txid());
+        assert_eq!(index.outputs.len(), 2);
+
+        index.roll_back(13);
+        assert!(!index.transactions.contains_key(&anchor.txid()));
+        assert!(index.spends.is_empty());
+        assert_eq!(index.outputs.len(), 1);
+        assert_eq!(index.next_height, 10);
+    }
+
+    #[test]
+    fn p2p_command_name() {
+        assert_eq!(command_name(b"cfilter\0\0\0\0\0"), "cfilter");
+        assert_eq!(command_name(b"sendaddrv2\0\0"), "sendaddrv2");
+        assert_eq!(command_name(b"\xff\xff\0\0\0\0\0\0\0\0\0\0"), "");
+    }
+}
@@ -0,0 +1,432 @@
+// Copyright 2019 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Bitcoin relay backed by the Electrum server.
+
+use async_trait::async_trait;
+use bitcoin::{OutPoint, Script};
+use bitcoin_hashes::{sha256, sha256d, Hash as BitcoinHash};
+use futures::lock::Mutex;
+use hex::FromHex;
+use serde_json::{json, Value};
+use thiserror::Error;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::TcpStream,
+};
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::{api::v1::AnchoringDescriptor, btc};
+
+use super::{
+    ChainQuery, MempoolRejection, ProxyConfig, ProxyError, RelayBackend, TransactionStatus,
+    TxBroadcaster, WalletOps, WalletUtxo,
+};
+
+/// Errors of the Electrum relay.
+#[derive(Debug, Error)]
+pub enum ElectrumError {
+    /// Unable to communicate with the Electrum server.
+    #[error("{0}")]
+    Io(#[from] std::io::Error),
+    /// Unable to connect to the Electrum server via the SOCKS5 proxy.
+    #[error("{0}")]
+    Proxy(#[from] ProxyError),
+    /// Unable to decode the response of the Electrum server.
+    #[error("{0}")]
+    Json(#[from] serde_json::Error),
+    /// Electrum server has returned an error.
+    #[error("Electrum server error: {0}")]
+    Server(String),
+    /// Electrum server has returned an unexpected response.
+    #[error("Unexpected response of the Electrum server: {0}")]
+    UnexpectedResponse(String),
+    /// Transaction has been rejected by the Bitcoin node behind the Electrum server.
+    #[error("Transaction {txid} has been rejected by the Electrum server: {rejection}")]
+    Rejected {
+        /// Identifier of the rejected transaction.
+        txid: btc::Sha256d,
+        /// Rejection details.
+        rejection: MempoolRejection,
+    },
+}
+
+/// Bitcoin relay, which talks to the Electrum server via the Electrum protocol over
+/// the plaintext TCP connection, so the anchoring nodes do not need the local full
+/// Bitcoin node with the transaction index.
+///
+/// The Electrum server indexes the transactions of all addresses, so the wallet
+/// operations are no-ops for this relay. The connection is established lazily and
+/// re-established after the communication errors.
+#[derive(Debug)]
+pub struct ElectrumRelay {
+    address: String,
+    proxy: Option<ProxyConfig>,
+    connection: Mutex<Option<BufReader<TcpStream>>>,
+    next_id: AtomicU64,
+}
+
+impl ElectrumRelay {
+    /// Creates a new relay for the Electrum server with the given address,
+    /// for example, `electrum.example.com:50001`.
+    pub fn new(address: impl Into<String>) -> Self {
+        Self {
+            address: address.into(),
+            proxy: None,
+            connection: Mutex::new(None),
+            next_id: AtomicU64::new(0),
+        }
+    }
+
+    /// Connects to the Electrum server via the given SOCKS5 proxy.
+    pub fn with_proxy(mut self, proxy: ProxyConfig) -> Self {
+        self.proxy = Some(proxy);
+        self
+    }
+
+    /// Returns the address of the Electrum server.
+    pub fn address(&self) -> &str {
+        &self.address
+    }
+
+    /// Calls the method of the Electrum server.
+    async fn call(&self, method: &str, params: Value) -> Result<Value, ElectrumError> {
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        let mut request = serde_json::to_vec(&json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": method,
+            "params": params,
+        }))?;
+        request.push(b'\n');
+
+        let mut connection = self.connection.lock().await;
+        if connection.is_none() {
+            let stream = match &self.proxy {
+                Some(proxy) => proxy.connect(&self.address).await?,
+                None => TcpStream::connect(&self.address).await?,
+            };
+            *connection = Some(BufReader::new(stream));
+        }
+        let stream = connection.as_mut().unwrap();
+        let response = Self::exchange(stream, &request, id).await;
+        // The stream state is unknown after the failure, so the connection is dropped.
+        if let Err(ElectrumError::Io(_)) | Err(ElectrumError::Json(_)) = response {
+            *connection = None;
+        }
+        let mut response = response?;
+
+        match response.get("error") {
+            None | Some(Value::Null) => Ok(response["result"].take()),
+            Some(error) => Err(ElectrumError::Server(
+                error["message"]
+                    .as_str()
+                    .map(ToOwned::to_owned)
+                    .unwrap_or_else(|| error.to_string()),
+            )),
+        }
+    }
+
+    /// Sends the request and reads the response with the given identifier, skipping
+    /// the subscription notifications.
+    async fn exchange(
+        stream: &mut BufReader<TcpStream>,
+        request: &[u8],
+        id: u64,
+    ) -> Result<Value, ElectrumError> {
+        stream.get_mut().write_all(request).await?;
+        loop {
+            let mut line = String::new();
+            if stream.read_line(&mut line).await? == 0 {
+                return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into());
+            }
+            let response: Value = serde_json::from_str(&line)?;
+            if response["id"].as_u64() == Some(id) {
+                return Ok(response);
+            }
+        }
+    }
+
+    /// Gets the transaction with the given identifier, or `None` if the transaction
+    /// is unknown to the Electrum server.
+    async fn transaction(
+        &self,
+        id: btc::Sha256d,
+    ) -> Result<Option<btc::Transaction>, ElectrumError> {
+        let raw = match self
+            .call("blockchain.transaction.get", json!([id.to_string()]))
+            .await
+        {
+            Ok(raw) => raw,
+            Err(ElectrumError::Server(_)) => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let raw = raw
+            .as_str()
+            .ok_or_else(|| ElectrumError::UnexpectedResponse(raw.to_string()))?;
+        btc::Transaction::from_hex(raw)
+            .map(Some)
+            .map_err(|e| ElectrumError::UnexpectedResponse(e.to_string()))
+    }
+
+    /// Gets the history of the given output script as the list of the transaction
+    /// identifiers with the heights of their blocks. Zero and negative heights stand
+    /// for the mempool transactions.
+    async fn script_history(&self, script: &Script) -> Result<Vec<(String, i64)>, ElectrumError> {
+        let history = self
+            .call(
+                "blockchain.scripthash.get_history",
+                json!([script_hash(script)]),
+            )
+            .await?;
+        let entries = history
+            .as_array()
+            .ok_or_else(|| ElectrumError::UnexpectedResponse(history.to_string()))?;
+        Ok(entries
+            .iter()
+            .filter_map(|entry| {
+                let txid = entry["tx_hash"].as_str()?.to_owned();
+                Some((txid, entry["height"].as_i64().unwrap_or_default()))
+            })
+            .collect())
+    }
+
+    /// Gets the height of the latest block known to the Electrum server.
+    async fn tip_height(&self) -> Result<i64, ElectrumError> {
+        let header = self.call("blockchain.headers.subscribe", json!([])).await?;
+        header["height"]
+            .as_i64()
+            .ok_or_else(|| ElectrumError::UnexpectedResponse(header.to_string()))
+    }
+}
+
+/// Returns the Electrum script hash, that is, the reversed SHA-256 hash of the script.
+fn script_hash(script: &Script) -> String {
+    let mut hash = sha256::Hash::hash(script.as_bytes()).into_inner();
+    hash.reverse();
+    hex::encode(hash)
+}
+
+/// Extracts the rejection reason reported by the Bitcoin node from the error message
+/// of the Electrum server, which may wrap it into its own explanation.
+fn rejection_reason(message: &str) -> &str {
+    message
+        .split("\n\n")
+        .nth(1)
+        .and_then(|details| details.lines().next())
+        .unwrap_or(message)
+        .trim()
+}
+
+impl RelayBackend for ElectrumRelay {
+    type Error = ElectrumError;
+}
+
+#[async_trait]
+impl TxBroadcaster for ElectrumRelay {
+    async fn send_transaction(
+        &self,
+        transaction: &btc::Transaction,
+    ) -> Result<btc::Sha256d, Self::Error> {
+        let txid = transaction.id();
+        match self
+            .call(
+                "blockchain.transaction.broadcast",
+                json!([transaction.to_string()]),
+            )
+            .await
+        {
+            Ok(_) => Ok(txid),
+            Err(ElectrumError::Server(message)) => {
+                let reason = rejection_reason(&message);
+                if MempoolRejection::is_already_known(reason) {
+                    return Ok(txid);
+                }
+                Err(ElectrumError::Rejected {
+                    txid,
+                    rejection: MempoolRejection::from_reason(reason),
+                })
+            }
+            Err(e) => Err(e),
+        }
+    }
+}
+
+#[async_trait]
+impl ChainQuery for ElectrumRelay {
+    // The Electrum protocol has no method to get the transaction height by its identifier,
+    // so the height is found in the history of the first transaction output, which
+    // is the anchoring output for the anchoring transactions.
+    async fn transaction_status(&self, id: btc::Sha256d) -> Result<TransactionStatus, Self::Error> {
+        let transaction = match self.transaction(id).await? {
+            Some(transaction) => transaction,
+            None => return Ok(TransactionStatus::Unknown),
+        };
+        let script = match transaction.0.output.first() {
+            Some(out) => &out.script_pubkey,
+            None => return Ok(TransactionStatus::Mempool),
+        };
+
+        let txid = id.to_string();
+        let height = self
+            .script_history(script)
+            .await?
+            .into_iter()
+            .find(|(entry_txid, _)| entry_txid == &txid)
+            .map(|(_, height)| height)
+            .unwrap_or_default();
+        if height <= 0 {
+            return Ok(TransactionStatus::Mempool);
+        }
+        let confirmations = self.tip_height().await? - height + 1;
+        Ok(TransactionStatus::Committed(confirmations.max(1) as u32))
+    }
+
+    async fn spending_transaction(
+        &self,
+        outpoint: OutPoint,
+    ) -> Result<Option<btc::Transaction>, Self::Error> {
+        let prev_txid = btc::Sha256d::from(outpoint.txid);
+        let script = match self.transaction(prev_txid).await? {
+            Some(prev_tx) => match prev_tx.0.output.get(outpoint.vout as usize) {
+                Some(out) => out.script_pubkey.clone(),
+                None => return Ok(None),
+            },
+            None => return Ok(None),
+        };
+
+        let prev_txid = prev_txid.to_string();
+        for (txid, _) in self.script_history(&script).await? {
+            if txid == prev_txid {
+                continue;
+            }
+            let txid = txid
+                .parse::<sha256d::Hash>()
+                .map(btc::Sha256d)
+                .map_err(|e| ElectrumError::UnexpectedResponse(e.to_string()))?;
+            if let Some(tx) = self.transaction(txid).await? {
+                if tx
+                    .0
+                    .input
+                    .iter()
+                    .any(|txin| txin.previous_output == outpoint)
+                {
+                    return Ok(Some(tx));
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    async fn ping(&self) -> Result<Option<u64>, Self::Error> {
+        Ok(Some(self.tip_height().await?.max(0) as u64))
+    }
+}
+
+#[async_trait]
+impl WalletOps for ElectrumRelay {
+    async fn watch_address(
+        &self,
+        _descriptor: &AnchoringDescriptor,
+        _label: &str,
+    ) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    async fn restore_watch_data(
+        &self,
+        _descriptor: &AnchoringDescriptor,
+        _label: &str,
+        _latest_anchor: btc::Sha256d,
+        _funding_tx: btc::Sha256d,
+        _fallback_height: Option<u64>,
+    ) -> Result<Option<u64>, Self::Error> {
+        Ok(None)
+    }
+
+    async fn unspent_outputs(
+        &self,
+        address: &btc::Address,
+    ) -> Result<Vec<WalletUtxo>, Self::Error> {
+        let script = address.0.script_pubkey();
+        let utxos = self
+            .call(
+                "blockchain.scripthash.listunspent",
+                json!([script_hash(&script)]),
+            )
+            .await?;
+        let entries = utxos
+            .as_array()
+            .ok_or_else(|| ElectrumError::UnexpectedResponse(utxos.to_string()))?;
+        let tip_height = self.tip_height().await?;
+        entries
+            .iter()
+            .map(|entry| {
+                let txid = entry["tx_hash"]
+                    .as_str()
+                    .and_then(|txid| txid.parse::<sha256d::Hash>().ok())
+                    .ok_or_else(|| ElectrumError::UnexpectedResponse(entry.to_string()))?;
+                // Zero and negative heights stand for the mempool transactions.
+                let height = entry["height"].as_i64().unwrap_or_default();
+                let confirmations = if height > 0 {
+                    (tip_height - height + 1).max(1) as u32
+                } else {
+                    0
+                };
+                Ok(WalletUtxo {
+                    txid: btc::Sha256d(txid),
+                    vout: entry["tx_pos"].as_u64().unwrap_or_default() as u32,
+                    value: entry["value"].as_u64().unwrap_or_default(),
+                    confirmations,
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::Script;
+    use hex::FromHex;
+
+    use super::{rejection_reason, script_hash};
+
+    #[test]
+    fn electrum_script_hash() {
+        // Example from the Electrum protocol documentation.
+        let script = Script::from(
+            Vec::from_hex("76a91462e907b15cbf27d5425399ebf6f0fb50ebb88f1888ac").unwrap(),
+        );
+        assert_eq!(
+            script_hash(&script),
+            "8b01df4e368ea28f8dc0423bcf7a4923e3a12d307c875e47a0cfbf90b5c39161"
+        );
+    }
+
+    #[test]
+    fn electrum_rejection_reason() {
+        assert_eq!(
+            rejection_reason(
+                "the transaction was rejected by network rules.\n\n\
+                 txn-already-in-mempool\n[02000000...]"
+            ),
+            "txn-already-in-mempool"
+        );
+        assert_eq!(
+            rejection_reason("min relay fee not met"),
+            "min relay fee not met"
+        );
+    }
+}
@@ -0,0 +1,416 @@
+// Copyright 2019 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Bitcoin relay backed by the Esplora HTTP API.
+
+use async_trait::async_trait;
+use bitcoin::OutPoint;
+use hex::FromHex;
+use reqwest::StatusCode;
+use serde_derive::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{api::v1::AnchoringDescriptor, btc};
+
+use super::{
+    ChainQuery, MempoolRejection, ProxyConfig, ProxyError, RelayBackend, TransactionStatus,
+    TxBroadcaster, WalletOps, WalletUtxo,
+};
+
+/// Configuration of the Esplora relay.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EsploraConfig {
+    /// Base URL of the Esplora API, for example, `https://blockstream.info/testnet/api`.
+    pub url: String,
+    /// SOCKS5 proxy, via which the Esplora API is reached, for example, Tor.
+    #[serde(default)]
+    pub proxy: Option<ProxyConfig>,
+}
+
+/// Errors of the Esplora relay.
+#[derive(Debug, Error)]
+pub enum EsploraError {
+    /// Unable to communicate with the Esplora server.
+    #[error("{0}")]
+    Http(#[from] reqwest::Error),
+    /// Esplora server has returned an error.
+    #[error("Esplora server error: {status}: {message}")]
+    Server {
+        /// HTTP status of the response.
+        status: StatusCode,
+        /// Error message.
+        message: String,
+    },
+    /// Esplora server has returned an unexpected response.
+    #[error("Unexpected response of the Esplora server: {0}")]
+    UnexpectedResponse(String),
+    /// Transaction has been rejected by the Bitcoin node behind the Esplora server.
+    #[error("Transaction {txid} has been rejected by the Esplora server: {rejection}")]
+    Rejected {
+        /// Identifier of the rejected transaction.
+        txid: btc::Sha256d,
+        /// Rejection details.
+        rejection: MempoolRejection,
+    },
+}
+
+/// Unspent output of the address returned by the Esplora server.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EsploraUtxo {
+    /// Identifier of the transaction with the output.
+    pub txid: btc::Sha256d,
+    /// Index of the output in the transaction.
+    pub vout: u32,
+    /// Output value in satoshis.
+    pub value: u64,
+    /// Confirmation status of the transaction.
+    pub status: EsploraTxStatus,
+}
+
+/// Confirmation status of the transaction returned by the Esplora server.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct EsploraTxStatus {
+    /// Whether the transaction is included in the block.
+    pub confirmed: bool,
+    /// Height of the block with the transaction, if it is confirmed.
+    #[serde(default)]
+    pub block_height: Option<u64>,
+    /// Hash of the block with the transaction, if it is confirmed.
+    #[serde(default)]
+    pub block_hash: Option<btc::Sha256d>,
+}
+
+/// Spending status of the transaction output returned by the Esplora server.
+#[derive(Debug, Deserialize)]
+struct EsploraOutspend {
+    spent: bool,
+    #[serde(default)]
+    txid: Option<btc::Sha256d>,
+}
+
+/// Bitcoin relay, which talks to the Esplora HTTP API, for example, the public
+/// `blockstream.info` one or the self-hosted instance, so the anchoring nodes do not
+/// need the local full Bitcoin node.
+///
+/// The Esplora server indexes the transactions of all addresses, so the wallet
+/// operations are no-ops for this relay.
+#[derive(Debug, Clone)]
+pub struct EsploraRelay {
+    url: String,
+    client: reqwest::Client,
+}
+
+impl EsploraRelay {
+    /// Creates a new relay for the Esplora API with the given base URL.
+    pub fn new(url: impl AsRef<str>) -> Self {
+        Self {
+            url: url.as_ref().trim_end_matches('/').to_owned(),
+            client: reqwest::Client::new(),
+        }
+    }
+
+    /// Sends the requests to the Esplora API via the given SOCKS5 proxy.
+    pub fn with_proxy(mut self, proxy: &ProxyConfig) -> Result<Self, ProxyError> {
+        self.client = reqwest::Client::builder()
+            .proxy(proxy.http_proxy()?)
+            .build()?;
+        Ok(self)
+    }
+
+    /// Returns the base URL of the Esplora API.
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Returns the unspent outputs of the given address.
+    pub async fn address_utxos(
+        &self,
+        address: &btc::Address,
+    ) -> Result<Vec<EsploraUtxo>, EsploraError> {
+        let body = self
+            .get(&format!("address/{}/utxo", address))
+            .await?
+            .ok_or_else(|| EsploraError::UnexpectedResponse("address not found".to_owned()))?;
+        serde_json::from_str(&body).map_err(|e| EsploraError::UnexpectedResponse(e.to_string()))
+    }
+
+    /// Performs the `GET` request to the given endpoint and returns the response body,
+    /// or `None` if the requested object is unknown to the Esplora server.
+    async fn get(&self, endpoint: &str) -> Result<Option<String>, EsploraError> {
+        let response = self
+            .client
+            .get(&format!("{}/{}", self.url, endpoint))
+            .send()
+            .await?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        Self::body(response).await.map(Some)
+    }
+
+    /// Returns the body of the successful response, or the server error otherwise.
+    async fn body(response: reqwest::Response) -> Result<String, EsploraError> {
+        let status = response.status();
+        let body = response.text().await?;
+        if status.is_success() {
+            Ok(body)
+        } else {
+            Err(EsploraError::Server {
+                status,
+                message: body,
+            })
+        }
+    }
+
+    /// Gets the transaction with the given identifier, or `None` if the transaction
+    /// is unknown to the Esplora server.
+    async fn transaction(
+        &self,
+        id: btc::Sha256d,
+    ) -> Result<Option<btc::Transaction>, EsploraError> {
+        match self.get(&format!("tx/{}/hex", id)).await? {
+            Some(raw) => btc::Transaction::from_hex(raw.trim())
+                .map(Some)
+                .map_err(|e| EsploraError::UnexpectedResponse(e.to_string())),
+            None => Ok(None),
+        }
+    }
+
+    /// Gets the confirmation status of the transaction with the given identifier, or `None`
+    /// if the transaction is unknown to the Esplora server.
+    async fn tx_status(&self, id: btc::Sha256d) -> Result<Option<EsploraTxStatus>, EsploraError> {
+        match self.get(&format!("tx/{}/status", id)).await? {
+            Some(status) => serde_json::from_str(&status)
+                .map(Some)
+                .map_err(|e| EsploraError::UnexpectedResponse(e.to_string())),
+            None => Ok(None),
+        }
+    }
+
+    /// Gets the height of the latest block known to the Esplora server.
+    async fn tip_height(&self) -> Result<u64, EsploraError> {
+        let height = self
+            .get("blocks/tip/height")
+            .await?
+            .ok_or_else(|| EsploraError::UnexpectedResponse("tip not found".to_owned()))?;
+        height
+            .trim()
+            .parse()
+            .map_err(|_| EsploraError::UnexpectedResponse(height))
+    }
+}
+
+/// Extracts the rejection reason reported by the Bitcoin node from the error message
+/// of the Esplora server, which wraps the JSON-RPC error of the node.
+fn rejection_reason(message: &str) -> String {
+    message
+        .find('{')
+        .and_then(|start| serde_json::from_str::<serde_json::Value>(&message[start..]).ok())
+        .and_then(|error| error["message"].as_str().map(ToOwned::to_owned))
+        .unwrap_or_else(|| message.trim().to_owned())
+}
+
+impl RelayBackend for EsploraRelay {
+    type Error = EsploraError;
+}
+
+#[async_trait]
+impl TxBroadcaster for EsploraRelay {
+    async fn send_transaction(
+        &self,
+        transaction: &btc::Transaction,
+    ) -> Result<btc::Sha256d, Self::Error> {
+        let txid = transaction.id();
+        let response = self
+            .client
+            .post(&format!("{}/tx", self.url))
+            .body(transaction.to_string())
+            .send()
+            .await?;
+        match Self::body(response).await {
+            Ok(_) => Ok(txid),
+            Err(EsploraError::Server { status, message }) if status.is_client_error() => {
+                let reason = rejection_reason(&message);
+                if MempoolRejection::is_already_known(&reason) {
+                    return Ok(txid);
+                }
+                Err(EsploraError::Rejected {
+                    txid,
+                    rejection: MempoolRejection::from_reason(reason),
+                })
+            }
+            Err(e) => Err(e),
+        }
+    }
+}
+
+#[async_trait]
+impl ChainQuery for EsploraRelay {
+    async fn transaction_status(&self, id: btc::Sha256d) -> Result<TransactionStatus, Self::Error> {
+        let status = match self.tx_status(id).await? {
+            Some(status) => status,
+            None => return Ok(TransactionStatus::Unknown),
+        };
+
+        match status.block_height.filter(|_| status.confirmed) {
+            Some(height) => {
+                let confirmations = self.tip_height().await?.saturating_sub(height) + 1;
+                Ok(TransactionStatus::Committed(confirmations as u32))
+            }
+            None => Ok(TransactionStatus::Mempool),
+        }
+    }
+
+    async fn transaction_block_hash(
+        &self,
+        id: btc::Sha256d,
+    ) -> Result<Option<btc::Sha256d>, Self::Error> {
+        Ok(self
+            .tx_status(id)
+            .await?
+            .filter(|status| status.confirmed)
+            .and_then(|status| status.block_hash))
+    }
+
+    async fn spending_transaction(
+        &self,
+        outpoint: OutPoint,
+    ) -> Result<Option<btc::Transaction>, Self::Error> {
+        let outspend = match self
+            .get(&format!("tx/{}/outspend/{}", outpoint.txid, outpoint.vout))
+            .await?
+        {
+            Some(outspend) => outspend,
+            None => return Ok(None),
+        };
+        let outspend: EsploraOutspend = serde_json::from_str(&outspend)
+            .map_err(|e| EsploraError::UnexpectedResponse(e.to_string()))?;
+
+        match outspend.txid.filter(|_| outspend.spent) {
+            Some(txid) => self.transaction(txid).await,
+            None => Ok(None),
+        }
+    }
+
+    async fn ping(&self) -> Result<Option<u64>, Self::Error> {
+        self.tip_height().await.map(Some)
+    }
+}
+
+#[async_trait]
+impl WalletOps for EsploraRelay {
+    async fn watch_address(
+        &self,
+        _descriptor: &AnchoringDescriptor,
+        _label: &str,
+    ) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    async fn restore_watch_data(
+        &self,
+        _descriptor: &AnchoringDescriptor,
+        _label: &str,
+        _latest_anchor: btc::Sha256d,
+        _funding_tx: btc::Sha256d,
+        _fallback_height: Option<u64>,
+    ) -> Result<Option<u64>, Self::Error> {
+        Ok(None)
+    }
+
+    async fn unspent_outputs(
+        &self,
+        address: &btc::Address,
+    ) -> Result<Vec<WalletUtxo>, Self::Error> {
+        let utxos = self.address_utxos(address).await?;
+        let tip_height = self.tip_height().await?;
+        Ok(utxos
+            .into_iter()
+            .map(|utxo| WalletUtxo {
+                txid: utxo.txid,
+                vout: utxo.vout,
+                value: utxo.value,
+                confirmations: utxo
+                    .status
+                    .block_height
+                    .filter(|_| utxo.status.confirmed)
+                    .map_or(0, |height| (tip_height.saturating_sub(height) + 1) as u32),
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{rejection_reason, EsploraRelay, EsploraTxStatus, EsploraUtxo};
+
+    #[test]
+    fn esplora_rejection_reason() {
+        assert_eq!(
+            rejection_reason(
+                r#"sendrawtransaction RPC error: {"code":-26,"message":"min relay fee not met"}"#
+            ),
+            "min relay fee not met"
+        );
+        assert_eq!(
+            rejection_reason("Transaction already in block chain\n"),
+            "Transaction already in block chain"
+        );
+    }
+
+    #[test]
+    fn esplora_utxo_deserialize() {
+        let utxos: Vec<EsploraUtxo> = serde_json::from_str(
+            r#"[{
+                "txid": "6f14de8a2cc3a0ebcdc6ed5b0de2d7f5a1e3b0e5c11e2f1dd5a2b3c4d5e6f708",
+                "vout": 1,
+                "status": { "confirmed": true, "block_height": 1700000 },
+                "value": 100000
+            }]"#,
+        )
+        .unwrap();
+        assert_eq!(utxos.len(), 1);
+        assert_eq!(utxos[0].vout, 1);
+        assert_eq!(utxos[0].value, 100_000);
+        assert_eq!(
+            utxos[0].status,
+            EsploraTxStatus {
+                confirmed: true,
+                block_height: Some(1_700_000),
+                block_hash: None,
+            }
+        );
+    }
+
+    #[test]
+    fn esplora_tx_status_deserialize() {
+        let block_hash = "0000000000000003b4e5d7bd43a3bd4e8c1ab8a2c4f4f0bd2c77a5c0f1b0e8a1";
+        let status: EsploraTxStatus = serde_json::from_str(&format!(
+            r#"{{ "confirmed": true, "block_height": 1700000, "block_hash": "{}" }}"#,
+            block_hash
+        ))
+        .unwrap();
+        assert_eq!(status.block_hash.unwrap().to_string(), block_hash);
+
+        let status: EsploraTxStatus = serde_json::from_str(r#"{ "confirmed": false }"#).unwrap();
+        assert_eq!(status.block_hash, None);
+    }
+
+    #[test]
+    fn esplora_url_normalized() {
+        let relay = EsploraRelay::new("https://blockstream.info/testnet/api/");
+        assert_eq!(relay.url(), "https://blockstream.info/testnet/api");
+    }
+}
@@ -0,0 +1,100 @@
+// Copyright 2019 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Adjustment of the anchoring transaction fee to the fee rate estimated by the relay.
+
+use serde_derive::{Deserialize, Serialize};
+
+/// Policy of the adjustment of the anchoring transaction fee.
+///
+/// The anchoring transactions are built by the service deterministically, so the fee
+/// cannot be estimated while building them. Instead, each anchoring node estimates
+/// the fee rate via its Bitcoin relay and proposes the new transaction fee via
+/// the `UpdateParameters` transaction, which is applied once the quorum of the anchoring
+/// nodes proposes the same fee. The estimate is rounded up to the multiple of `fee_step`,
+/// so the nodes with the slightly different estimates propose the same fee.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FeePolicy {
+    /// Confirmation target in blocks used to estimate the fee rate.
+    pub target_blocks: u16,
+    /// Minimal transaction fee in satoshis per byte.
+    pub min_fee_rate: u64,
+    /// Maximal transaction fee in satoshis per byte.
+    pub max_fee_rate: u64,
+    /// Granularity of the proposed transaction fee in satoshis per byte.
+    pub fee_step: u64,
+}
+
+impl Default for FeePolicy {
+    fn default() -> Self {
+        Self {
+            target_blocks: 6,
+            min_fee_rate: 1,
+            max_fee_rate: 500,
+            fee_step: 5,
+        }
+    }
+}
+
+impl FeePolicy {
+    /// Returns the transaction fee, which should be proposed for the given estimated
+    /// fee rate.
+    pub fn transaction_fee(&self, estimated_fee_rate: u64) -> u64 {
+        let step = self.fee_step.max(1);
+        let rounded = (estimated_fee_rate.saturating_add(step - 1) / step).saturating_mul(step);
+        rounded.max(self.min_fee_rate).min(self.max_fee_rate)
+    }
+
+    /// Checks the policy bounds, returns the descriptions of the found problems.
+    pub fn validate(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        if self.target_blocks == 0 {
+            problems.push("Fee estimation target should be greater than zero.".to_owned());
+        }
+        if self.min_fee_rate > self.max_fee_rate {
+            problems.push(format!(
+                "Minimal fee rate {} exceeds the maximal one {}.",
+                self.min_fee_rate, self.max_fee_rate
+            ));
+        }
+        problems
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FeePolicy;
+
+    #[test]
+    fn transaction_fee_bounds() {
+        let policy = FeePolicy {
+            target_blocks: 6,
+            min_fee_rate: 10,
+            max_fee_rate: 100,
+            fee_step: 5,
+        };
+        assert_eq!(policy.transaction_fee(1), 10);
+        assert_eq!(policy.transaction_fee(41), 45);
+        assert_eq!(policy.transaction_fee(45), 45);
+        assert_eq!(policy.transaction_fee(1_000), 100);
+        assert!(policy.validate().is_empty());
+
+        let policy = FeePolicy {
+            fee_step: 0,
+            ..policy
+        };
+        assert_eq!(policy.transaction_fee(41), 41);
+    }
+}
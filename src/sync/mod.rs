@@ -14,27 +14,96 @@
 
 //! Building blocks of the anchoring sync utility.
 
-pub use self::bitcoin_relay::{BitcoinRelay, TransactionStatus};
+pub use self::bitcoin_relay::{
+    BitcoinRelay, BitcoinRpcRelay, ChainQuery, ComposedRelay, ComposedRelayError, MempoolEntry,
+    MempoolRejection, RejectionKind, RelayBackend, RelayCapabilities, RelayError, RetryPolicy,
+    RpcTimeouts, TransactionStatus, TxBroadcaster, WalletOps, WalletUtxo,
+};
+pub use self::blocking::BlockingRelay;
+#[cfg(feature = "compact-filters")]
+pub use self::compact_filter_relay::{CompactFilterConfig, CompactFilterError, CompactFilterRelay};
+#[cfg(feature = "electrum")]
+pub use self::electrum_relay::{ElectrumError, ElectrumRelay};
+#[cfg(feature = "esplora")]
+pub use self::esplora_relay::{
+    EsploraConfig, EsploraError, EsploraRelay, EsploraTxStatus, EsploraUtxo,
+};
+pub use self::fee_policy::FeePolicy;
+pub use self::proxy::{ProxyConfig, ProxyError};
+pub use self::reconciliation::{AnchorReconciliation, Divergence, ReconciliationReport};
+pub use self::rehearsal::{MirrorState, RehearsalMirror};
+pub use self::relay_cache::CachedRelay;
+pub use self::relay_health::RelayStatus;
+pub use self::role::NodeRole;
+pub use self::rpc_client::{BitcoinRpcClient, TlsConfig, TlsError};
+pub use self::shutdown::ShutdownSignal;
+pub use self::signature_audit::{SignatureAuditTask, SignatureVerification};
+pub use self::signing_marker::{SignedInputs, SigningMarker};
+pub use self::signing_policy::{MaxFeePolicy, SigningPolicy, SigningRequest};
+pub use self::transition::{FeeOracle, TransitionSchedule};
+#[cfg(feature = "zmq")]
+pub use self::zmq_subscriber::{BitcoinNotification, ZmqError, ZmqSubscriber, ZmqTopic};
 
 use anyhow::anyhow;
+use bitcoin::OutPoint;
 use btc_transaction_utils::{p2wsh, TxInRef};
 
-use std::{collections::HashMap, fmt::Display, sync::Arc};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Display,
+    ops::Range,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
 
 use crate::{
     api::{AnchoringProposalState, PrivateApi},
-    blockchain::SignInput,
+    blockchain::{
+        data_layout::ProposalConflict, RecordAnchorBlock, ReportAnchorStatus, SignInput,
+        UpdateParameters,
+    },
     btc,
     config::Config,
 };
 
+use self::{relay_health::RelayHealth, transition::TransitionScheduler};
+
 mod bitcoin_relay;
+mod blocking;
+#[cfg(feature = "compact-filters")]
+mod compact_filter_relay;
+#[cfg(feature = "electrum")]
+mod electrum_relay;
+#[cfg(feature = "esplora")]
+mod esplora_relay;
+mod fee_policy;
+mod proxy;
+mod reconciliation;
+mod rehearsal;
+mod relay_cache;
+mod relay_health;
+mod role;
+mod rpc_client;
+mod shutdown;
+mod signature_audit;
+mod signing_marker;
+mod signing_policy;
+mod transition;
+#[cfg(feature = "zmq")]
+mod zmq_subscriber;
 
 /// Anchoring transaction with its index in the anchoring chain.
 pub type TransactionWithIndex = (btc::Transaction, u64);
 
 type KeyPool = Arc<HashMap<btc::PublicKey, btc::PrivateKey>>;
 
+/// Number of the anchoring transactions which can be created before reaching the reserve,
+/// below which the anchoring chain update task alerts about the approaching reserve.
+const RESERVE_ALERT_TRANSACTIONS: u64 = 10;
+
 /// Errors that occur when updating the anchoring chain.
 #[derive(Debug)]
 pub enum ChainUpdateError<C: Display> {
@@ -47,12 +116,66 @@ pub enum ChainUpdateError<C: Display> {
         /// Available balance.
         balance: u64,
     },
+    /// Anchoring transaction proposal spends the funds reserved in the anchoring wallet.
+    ReserveReached {
+        /// Remaining balance after the anchoring transaction.
+        remaining: u64,
+        /// Reserved balance.
+        reserve: u64,
+    },
+    /// Change output of the anchoring transaction proposal is below the dust threshold,
+    /// so the anchoring stops until the anchoring wallet is replenished.
+    DustChange {
+        /// Remaining balance after the anchoring transaction.
+        remaining: u64,
+        /// Dust threshold.
+        dust_threshold: u64,
+    },
     /// Initial funding transaction is absent.
     NoInitialFunds,
+    /// Finalization of the anchoring transaction proposal is halted, because an anchoring
+    /// node has signed a conflicting transaction for the same anchoring slot.
+    AnchoringHalted {
+        /// The first conflict detected for the proposal.
+        conflict: ProposalConflict,
+    },
+    /// Signing policy of the node refused to sign the anchoring transaction proposal.
+    SigningRefused {
+        /// Identifier of the anchoring transaction proposal.
+        txid: btc::Sha256d,
+        /// Reason of the refusal.
+        reason: anyhow::Error,
+    },
     /// Internal error.
     Internal(anyhow::Error),
 }
 
+/// Maintenance switch of the watch-only mode of the anchoring chain update task.
+///
+/// In the watch-only mode the node verifies the anchoring proposals and reports
+/// which of them it would have signed, but does not sign anything. This mode is
+/// intended for the disaster recovery drills. The toggle can be cloned and switched
+/// while the task is running.
+#[derive(Debug, Clone, Default)]
+pub struct WatchOnlyToggle(Arc<AtomicBool>);
+
+impl WatchOnlyToggle {
+    /// Creates a new toggle with the given initial state.
+    pub fn new(enabled: bool) -> Self {
+        Self(Arc::new(AtomicBool::new(enabled)))
+    }
+
+    /// Switches the watch-only mode on or off and returns the previous state.
+    pub fn set(&self, enabled: bool) -> bool {
+        self.0.swap(enabled, Ordering::SeqCst)
+    }
+
+    /// Checks whether the watch-only mode is on.
+    pub fn is_enabled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
 /// Signs the inputs of the anchoring transaction proposal by the corresponding
 /// Bitcoin private keys.
 #[derive(Debug)]
@@ -62,6 +185,14 @@ where
 {
     key_pool: KeyPool,
     api_client: T,
+    policies: Vec<Box<dyn SigningPolicy>>,
+    watch_only: WatchOnlyToggle,
+    max_signatures_per_round: Option<usize>,
+    /// Proposal being signed and the index of its first input, which signature
+    /// has not been submitted in the current submission cycle.
+    next_input: Mutex<Option<(btc::Sha256d, usize)>>,
+    signing_marker: Option<SigningMarker>,
+    shutdown: ShutdownSignal,
 }
 
 impl<T> AnchoringChainUpdateTask<T>
@@ -77,9 +208,59 @@ where
         Self {
             key_pool: Arc::new(keys.into_iter().collect()),
             api_client,
+            policies: Vec::new(),
+            watch_only: WatchOnlyToggle::default(),
+            max_signatures_per_round: None,
+            next_input: Mutex::new(None),
+            signing_marker: None,
+            shutdown: ShutdownSignal::default(),
         }
     }
 
+    /// Uses the given marker to skip the signatures, which have been already submitted
+    /// by the node, including the ones submitted before the restart.
+    pub fn with_signing_marker(mut self, marker: SigningMarker) -> Self {
+        self.signing_marker = Some(marker);
+        self
+    }
+
+    /// Limits the number of signatures submitted to the Exonum node within one
+    /// [`process`] call. The remaining signatures of the proposal are submitted within
+    /// the following calls, so a node which has many unsigned inputs does not flood
+    /// the Exonum transaction pool.
+    ///
+    /// [`process`]: #method.process
+    pub fn with_max_signatures_per_round(mut self, limit: usize) -> Self {
+        self.max_signatures_per_round = Some(limit.max(1));
+        self
+    }
+
+    /// Adds a signing policy which must approve the anchoring proposals before
+    /// the node signs them. Policies are checked in the order of their addition.
+    pub fn with_signing_policy(mut self, policy: impl SigningPolicy + 'static) -> Self {
+        self.policies.push(Box::new(policy));
+        self
+    }
+
+    /// Uses the given toggle to switch the watch-only mode of the task.
+    pub fn with_watch_only(mut self, toggle: WatchOnlyToggle) -> Self {
+        self.watch_only = toggle;
+        self
+    }
+
+    /// Stops submitting the signatures once the given signal is requested. The signature
+    /// being submitted is recorded in the signing marker, the remaining ones are submitted
+    /// after the restart.
+    pub fn with_shutdown(mut self, shutdown: ShutdownSignal) -> Self {
+        self.shutdown = shutdown;
+        self
+    }
+
+    /// Returns the toggle of the watch-only mode of the task.
+    pub fn watch_only(&self) -> &WatchOnlyToggle {
+        &self.watch_only
+    }
+
     /// Returns an actual anchoring configuration.
     pub async fn anchoring_config(&self) -> Result<Config, T::Error> {
         self.api_client.config().await
@@ -96,20 +277,39 @@ where
             .map_err(ChainUpdateError::Client)?
         {
             AnchoringProposalState::None => Ok(()),
+            AnchoringProposalState::NotStarted { start_height } => {
+                log::trace!("Anchoring will be started at height {}", start_height);
+                Ok(())
+            }
             AnchoringProposalState::Available {
                 transaction,
                 inputs,
+                epoch,
             } => {
                 let config = self
                     .anchoring_config()
                     .await
                     .map_err(ChainUpdateError::Client)?;
-                self.handle_proposal(config, transaction, inputs).await
+                self.handle_proposal(config, transaction, inputs, epoch)
+                    .await
             }
             AnchoringProposalState::InsufficientFunds { balance, total_fee } => {
                 Err(ChainUpdateError::InsufficientFunds { balance, total_fee })
             }
+            AnchoringProposalState::ReserveReached { remaining, reserve } => {
+                Err(ChainUpdateError::ReserveReached { remaining, reserve })
+            }
+            AnchoringProposalState::DustChange {
+                remaining,
+                dust_threshold,
+            } => Err(ChainUpdateError::DustChange {
+                remaining,
+                dust_threshold,
+            }),
             AnchoringProposalState::NoInitialFunds => Err(ChainUpdateError::NoInitialFunds),
+            AnchoringProposalState::Halted { conflict } => {
+                Err(ChainUpdateError::AnchoringHalted { conflict })
+            }
         }
     }
 
@@ -118,6 +318,7 @@ where
         config: Config,
         proposal: btc::Transaction,
         inputs: Vec<btc::Transaction>,
+        epoch: u64,
     ) -> Result<(), ChainUpdateError<T::Error>> {
         log::trace!("Got an anchoring proposal: {:?}", proposal);
         // Find among the keys one from which we have a private part.
@@ -145,11 +346,69 @@ where
             "Found a new unfinished anchoring transaction proposal for height: {}",
             block_height
         );
+        let request = SigningRequest {
+            config: &config,
+            proposal: &proposal,
+            inputs: &inputs,
+            anchoring_height: block_height,
+            epoch,
+        };
+        Self::check_reserve(&request);
+        for policy in &self.policies {
+            if let Err(reason) = policy.check(&request).await {
+                log::warn!(
+                    "Signing policy {:?} refused to sign the anchoring proposal {}: {}",
+                    policy,
+                    proposal.id(),
+                    reason
+                );
+                return Err(ChainUpdateError::SigningRefused {
+                    txid: proposal.id(),
+                    reason,
+                });
+            }
+        }
 
+        if self.watch_only.is_enabled() {
+            Self::verify_proposal_inputs(&proposal, &inputs)?;
+            log::info!(
+                "Watch-only mode: would have signed {} inputs of the anchoring proposal {} \
+                 for height {} (epoch {}) with the key {}, total fee is {}",
+                inputs.len(),
+                proposal.id(),
+                block_height,
+                epoch,
+                keypair.0,
+                request.total_fee()
+            );
+            return Ok(());
+        }
+
+        let txid = proposal.id();
+        let inputs_range = self.next_inputs_batch(txid, inputs.len());
+        log::trace!(
+            "Submitting signatures for inputs {:?} of {} of the anchoring proposal {}",
+            inputs_range,
+            inputs.len(),
+            txid
+        );
+
+        let signed_inputs = self
+            .signing_marker
+            .as_ref()
+            .map(|marker| marker.signed_inputs(txid))
+            .unwrap_or_default();
+
+        // The signed transaction is attached, so that the service can verify the signature
+        // if the proposal has changed before the signature is committed.
+        let signed_transaction = bitcoin::consensus::serialize(proposal.as_ref());
         let mut signer = p2wsh::InputSigner::new(redeem_script);
         let sign_input_messages = inputs
             .iter()
             .enumerate()
+            .skip(inputs_range.start)
+            .take(inputs_range.len())
+            .filter(|(index, _)| !signed_inputs.contains(&(*index as u32)))
             .map(|(index, proposal_input)| {
                 let signature = signer.sign_input(
                     TxInRef::new(proposal.as_ref(), index),
@@ -160,21 +419,101 @@ where
                 Ok(SignInput {
                     input: index as u32,
                     input_signature: signature.into(),
-                    txid: proposal.id(),
+                    txid,
+                    anchoring_height: block_height,
+                    epoch,
+                    signed_transaction: signed_transaction.clone(),
                 })
             })
             .collect::<anyhow::Result<Vec<_>>>()
             .map_err(ChainUpdateError::Internal)?;
         // Send sign input transactions to the Exonum node.
         for sign_input in sign_input_messages {
+            if self.shutdown.is_requested() {
+                log::info!(
+                    "Shutdown requested, the remaining signatures of the anchoring proposal {} \
+                     will be submitted after the restart",
+                    txid
+                );
+                break;
+            }
+
+            let input = sign_input.input;
             self.api_client
                 .sign_input(sign_input)
                 .await
                 .map_err(ChainUpdateError::Client)?;
+            if let Some(marker) = &self.signing_marker {
+                marker
+                    .record(txid, input)
+                    .map_err(ChainUpdateError::Internal)?;
+            }
         }
         Ok(())
     }
 
+    /// Returns the range of the proposal inputs which signatures should be submitted
+    /// within this round. When all the signatures of the proposal have been submitted,
+    /// the submission starts over, so the lost signatures are eventually resubmitted.
+    fn next_inputs_batch(&self, txid: btc::Sha256d, inputs_count: usize) -> Range<usize> {
+        let limit = match self.max_signatures_per_round {
+            Some(limit) => limit,
+            None => return 0..inputs_count,
+        };
+
+        let mut next_input = self.next_input.lock().unwrap();
+        let start = match *next_input {
+            Some((proposal, start)) if proposal == txid && start < inputs_count => start,
+            _ => 0,
+        };
+        let end = inputs_count.min(start + limit);
+        *next_input = Some((txid, end));
+        start..end
+    }
+
+    fn verify_proposal_inputs(
+        proposal: &btc::Transaction,
+        inputs: &[btc::Transaction],
+    ) -> Result<(), ChainUpdateError<T::Error>> {
+        let spent_outputs = proposal
+            .0
+            .input
+            .iter()
+            .map(|input| input.previous_output)
+            .collect::<Vec<_>>();
+        let is_consistent = spent_outputs.len() == inputs.len()
+            && spent_outputs.iter().zip(inputs).all(|(outpoint, tx)| {
+                outpoint.txid == tx.0.txid() && (outpoint.vout as usize) < tx.0.output.len()
+            });
+        if is_consistent {
+            Ok(())
+        } else {
+            Err(ChainUpdateError::Internal(anyhow!(
+                "Anchoring proposal {} does not spend the given inputs",
+                proposal.id()
+            )))
+        }
+    }
+
+    fn check_reserve(request: &SigningRequest<'_>) {
+        let config = request.config;
+        if config.reserve_balance == 0 {
+            return;
+        }
+
+        let remaining = request.remaining();
+        let total_fee = request.total_fee();
+        if remaining < config.reserve_balance + total_fee * RESERVE_ALERT_TRANSACTIONS {
+            log::warn!(
+                "Anchoring wallet balance {} is approaching the reserve {}, \
+                 please fill up an anchoring wallet {}",
+                remaining,
+                config.reserve_balance,
+                config.anchoring_address()
+            );
+        }
+    }
+
     fn find_private_key(
         &self,
         anchoring_keys: impl IntoIterator<Item = btc::PublicKey>,
@@ -199,6 +538,9 @@ pub enum SyncWithBitcoinError<C: Display, R: Display> {
     Internal(anyhow::Error),
     /// Initial funding transaction is unconfirmed.
     UnconfirmedFundingTransaction(btc::Sha256d),
+    /// Bitcoin relay has failed the health check, so nothing is sent to the Bitcoin network
+    /// until the relay recovers.
+    RelayUnavailable(RelayStatus),
 }
 
 /// Pushes anchoring transactions to the Bitcoin blockchain.
@@ -210,6 +552,21 @@ where
 {
     btc_relay: R,
     api_client: T,
+    dry_run: bool,
+    shutdown: ShutdownSignal,
+    simulated_transactions: Mutex<HashSet<btc::Sha256d>>,
+    imported_transactions: Mutex<HashSet<btc::Sha256d>>,
+    transition_scheduler: Option<TransitionScheduler>,
+    finality_confirmations: Option<u32>,
+    confirmed_final_anchor: Mutex<Option<u64>>,
+    fee_policy: Option<FeePolicy>,
+    proposed_fee: Mutex<Option<u64>>,
+    report_anchor_status: bool,
+    reported_anchor_status: Mutex<Option<(btc::Sha256d, bool, bool)>>,
+    anchor_block_confirmations: Option<u32>,
+    recorded_anchor_block: Mutex<Option<u64>>,
+    health_check_interval: Option<Duration>,
+    relay_health: Mutex<RelayHealth>,
 }
 
 impl<T, R> SyncWithBitcoinTask<T, R>
@@ -224,9 +581,107 @@ where
         Self {
             api_client,
             btc_relay,
+            dry_run: false,
+            shutdown: ShutdownSignal::default(),
+            simulated_transactions: Mutex::default(),
+            imported_transactions: Mutex::default(),
+            transition_scheduler: None,
+            finality_confirmations: None,
+            confirmed_final_anchor: Mutex::default(),
+            fee_policy: None,
+            proposed_fee: Mutex::default(),
+            report_anchor_status: false,
+            reported_anchor_status: Mutex::default(),
+            anchor_block_confirmations: None,
+            recorded_anchor_block: Mutex::default(),
+            health_check_interval: None,
+            relay_health: Mutex::default(),
         }
     }
 
+    /// Enables or disables the dry-run mode.
+    ///
+    /// In this mode anchoring transactions are never sent to the Bitcoin network,
+    /// instead they are marked as simulated.
+    pub fn with_dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// Stops broadcasting the anchoring transactions once the given signal is requested.
+    /// The transactions which have not been sent are found in the anchoring chain and
+    /// sent after the restart.
+    pub fn with_shutdown(mut self, shutdown: ShutdownSignal) -> Self {
+        self.shutdown = shutdown;
+        self
+    }
+
+    /// Defers broadcasting of the transition transactions, which transfer the anchoring
+    /// funds to the address of the following configuration, until the fee rate reported
+    /// by the given oracle falls under the threshold of the schedule, or the deadline
+    /// of the schedule is reached.
+    pub fn with_transition_schedule(
+        mut self,
+        schedule: TransitionSchedule,
+        fee_oracle: impl FeeOracle + 'static,
+    ) -> Self {
+        self.transition_scheduler = Some(TransitionScheduler::new(schedule, Box::new(fee_oracle)));
+        self
+    }
+
+    /// Reports the latest committed anchoring transaction as final to the anchoring
+    /// service once it has the given number of confirmations in the Bitcoin blockchain.
+    pub fn with_finality_confirmations(mut self, confirmations: u32) -> Self {
+        self.finality_confirmations = Some(confirmations);
+        self
+    }
+
+    /// Proposes the anchoring transaction fee adjusted to the fee rate estimated by
+    /// the Bitcoin relay within the bounds of the given policy. The fee is not adjusted
+    /// in the dry-run mode.
+    pub fn with_fee_policy(mut self, policy: FeePolicy) -> Self {
+        self.fee_policy = Some(policy);
+        self
+    }
+
+    /// Reports the status of the latest committed anchoring transaction in the Bitcoin
+    /// network to the anchoring service whenever the transaction is accepted to the mempool,
+    /// confirmed, or disappears from the Bitcoin node. The status is not reported in
+    /// the dry-run mode.
+    pub fn with_anchor_status_reports(mut self) -> Self {
+        self.report_anchor_status = true;
+        self
+    }
+
+    /// Records the Bitcoin block containing the latest committed anchoring transaction
+    /// in the anchoring service once the transaction has the given number of confirmations,
+    /// so that the pruned Bitcoin nodes are able to find the transaction by its block.
+    pub fn with_anchor_block_records(mut self, confirmations: u32) -> Self {
+        self.anchor_block_confirmations = Some(confirmations);
+        self
+    }
+
+    /// Checks the health of the Bitcoin relay with the given interval before syncing with
+    /// the Bitcoin network. The unhealthy relay is checked on every attempt until it recovers,
+    /// and the attempts fail with the `RelayUnavailable` error meanwhile.
+    ///
+    /// The changes of the relay health are reported to the anchoring service, so they are
+    /// visible via the API. The status is not reported in the dry-run mode.
+    pub fn with_health_checks(mut self, interval: Duration) -> Self {
+        self.health_check_interval = Some(interval);
+        self
+    }
+
+    /// Returns the health of the Bitcoin relay determined by the latest health check.
+    pub fn relay_status(&self) -> RelayStatus {
+        self.relay_health.lock().unwrap().status().clone()
+    }
+
+    /// Returns the underlying Bitcoin relay.
+    pub fn btc_relay(&self) -> &R {
+        &self.btc_relay
+    }
+
     /// Performs one attempt to send the first uncommitted anchoring transaction into the Bitcoin network, if any.
     /// sign an anchoring proposal, if any. Returns an index of the last committed transaction.
     pub async fn process(
@@ -234,12 +689,20 @@ where
         latest_committed_tx_index: Option<u64>,
     ) -> Result<Option<u64>, SyncWithBitcoinError<T::Error, R::Error>> {
         log::trace!("Perform syncing with the Bitcoin network");
+        self.check_relay_health().await?;
+        self.import_external_anchor().await?;
+        self.adjust_transaction_fee().await?;
         // Try to find a suitable transaction for sending to the Bitcoin network.
         let (index, transaction) = if let Some(index) = latest_committed_tx_index {
             // Check that the latest committed transaction was really sent into
             // the Bitcoin network.
             let transaction = self.get_transaction(index).await?;
             let status = self.transaction_status(transaction.id()).await?;
+            self.confirm_anchor_finality(index, transaction.id(), status)
+                .await?;
+            self.record_anchor_block(index, transaction.id(), status)
+                .await?;
+            self.report_anchor_status(transaction.id(), status).await?;
             if status.is_known() {
                 let chain_len = self
                     .api_client
@@ -265,6 +728,34 @@ where
             return Ok(None);
         };
 
+        if self.dry_run {
+            log::info!(
+                "Simulated sending transaction to the Bitcoin network: {}",
+                transaction.id()
+            );
+            self.simulated_transactions
+                .lock()
+                .unwrap()
+                .insert(transaction.id());
+            return Ok(Some(index));
+        }
+
+        if self.shutdown.is_requested() {
+            log::info!(
+                "Shutdown requested, transaction {} will be sent after the restart",
+                transaction.id()
+            );
+            return Ok(latest_committed_tx_index);
+        }
+
+        if let Some(scheduler) = self.transition_scheduler.as_ref() {
+            if self.is_transition(index, &transaction).await?
+                && !scheduler.should_broadcast(transaction.id()).await
+            {
+                return Ok(latest_committed_tx_index);
+            }
+        }
+
         // Send an actual uncommitted transaction into the Bitcoin network.
         self.btc_relay
             .send_transaction(&transaction)
@@ -279,6 +770,290 @@ where
         Ok(Some(index))
     }
 
+    /// Checks the health of the Bitcoin relay if the check is due and reports the changes
+    /// of the relay health to the anchoring service.
+    async fn check_relay_health(&self) -> Result<(), SyncWithBitcoinError<T::Error, R::Error>> {
+        let interval = match self.health_check_interval {
+            Some(interval) => interval,
+            None => return Ok(()),
+        };
+        if !self.relay_health.lock().unwrap().is_check_due(interval) {
+            return Ok(());
+        }
+
+        let result = self.btc_relay.ping().await;
+        let (status, report) = {
+            let mut health = self.relay_health.lock().unwrap();
+            health.record_check(result);
+            (health.status().clone(), health.pending_report())
+        };
+        if let Some(report) = report.filter(|_| !self.dry_run) {
+            let healthy = report.healthy;
+            match self.api_client.report_relay_status(report).await {
+                Ok(_) => self.relay_health.lock().unwrap().mark_reported(healthy),
+                // The status is reported once again after the following check.
+                Err(e) => log::warn!("Unable to report the Bitcoin relay status. {}", e),
+            }
+        }
+
+        if status.healthy {
+            Ok(())
+        } else {
+            Err(SyncWithBitcoinError::RelayUnavailable(status))
+        }
+    }
+
+    /// Sends the finality confirmation for the anchoring transaction with the given index
+    /// if it is buried deep enough in the Bitcoin blockchain and has not been reported yet.
+    async fn confirm_anchor_finality(
+        &self,
+        index: u64,
+        txid: btc::Sha256d,
+        status: TransactionStatus,
+    ) -> Result<(), SyncWithBitcoinError<T::Error, R::Error>> {
+        let required = match self.finality_confirmations {
+            Some(required) => required,
+            None => return Ok(()),
+        };
+        if status
+            .confirmations()
+            .map_or(true, |actual| actual < required)
+        {
+            return Ok(());
+        }
+        if self
+            .confirmed_final_anchor
+            .lock()
+            .unwrap()
+            .map_or(false, |confirmed| index <= confirmed)
+        {
+            return Ok(());
+        }
+
+        self.api_client
+            .confirm_anchor_finality(txid)
+            .await
+            .map_err(SyncWithBitcoinError::Client)?;
+        log::info!(
+            "Confirmed finality of the anchoring transaction {} with index {}",
+            txid,
+            index
+        );
+        *self.confirmed_final_anchor.lock().unwrap() = Some(index);
+        Ok(())
+    }
+
+    /// Records the Bitcoin block containing the anchoring transaction with the given index
+    /// if the transaction has enough confirmations and its block has not been recorded yet.
+    /// Failures of the block lookup do not prevent the anchoring.
+    async fn record_anchor_block(
+        &self,
+        index: u64,
+        txid: btc::Sha256d,
+        status: TransactionStatus,
+    ) -> Result<(), SyncWithBitcoinError<T::Error, R::Error>> {
+        let required = match self.anchor_block_confirmations {
+            Some(required) => required,
+            None => return Ok(()),
+        };
+        if status
+            .confirmations()
+            .map_or(true, |actual| actual < required)
+        {
+            return Ok(());
+        }
+        if self
+            .recorded_anchor_block
+            .lock()
+            .unwrap()
+            .map_or(false, |recorded| index <= recorded)
+        {
+            return Ok(());
+        }
+
+        let block_hash = match self.btc_relay.transaction_block_hash(txid).await {
+            Ok(Some(block_hash)) => block_hash,
+            Ok(None) => {
+                log::trace!("Bitcoin relay is unable to find the block of {}", txid);
+                return Ok(());
+            }
+            Err(e) => {
+                log::warn!("Unable to get the Bitcoin block of {}: {}", txid, e);
+                return Ok(());
+            }
+        };
+        self.api_client
+            .record_anchor_block(RecordAnchorBlock { txid, block_hash })
+            .await
+            .map_err(SyncWithBitcoinError::Client)?;
+        log::info!(
+            "Recorded Bitcoin block {} of the anchoring transaction {} with index {}",
+            block_hash,
+            txid,
+            index
+        );
+        *self.recorded_anchor_block.lock().unwrap() = Some(index);
+        Ok(())
+    }
+
+    /// Reports the status of the anchoring transaction if it has changed since the previous
+    /// report. The number of confirmations is reported along with the first confirmation
+    /// only, the deeper confirmations are tracked via the finality confirmations.
+    async fn report_anchor_status(
+        &self,
+        txid: btc::Sha256d,
+        status: TransactionStatus,
+    ) -> Result<(), SyncWithBitcoinError<T::Error, R::Error>> {
+        if !self.report_anchor_status || self.dry_run || status == TransactionStatus::Simulated {
+            return Ok(());
+        }
+        let report = ReportAnchorStatus {
+            txid,
+            known: status.is_known(),
+            confirmations: status.confirmations().unwrap_or_default(),
+        };
+        let reported = (txid, report.known, report.confirmations > 0);
+        if *self.reported_anchor_status.lock().unwrap() == Some(reported) {
+            return Ok(());
+        }
+
+        match status {
+            TransactionStatus::Unknown => log::warn!(
+                "Anchoring transaction {} is unknown to the Bitcoin node, it has either been \
+                 evicted from the mempool or has never been broadcast",
+                txid
+            ),
+            TransactionStatus::Mempool => match self.btc_relay.mempool_entry(txid).await {
+                Ok(Some(entry)) => log::info!(
+                    "Anchoring transaction {} is in the mempool with the fee rate {} sat/vB",
+                    txid,
+                    entry.fee_rate()
+                ),
+                Ok(None) => {}
+                Err(e) => log::warn!("Unable to get the mempool entry of {}: {}", txid, e),
+            },
+            _ => {}
+        }
+        self.api_client
+            .report_anchor_status(report)
+            .await
+            .map_err(SyncWithBitcoinError::Client)?;
+        *self.reported_anchor_status.lock().unwrap() = Some(reported);
+        Ok(())
+    }
+
+    /// Proposes the transaction fee for the fee rate estimated by the relay, if it differs
+    /// from the actual one and has not been proposed by this node yet. Failures of
+    /// the estimation do not prevent the anchoring.
+    async fn adjust_transaction_fee(&self) -> Result<(), SyncWithBitcoinError<T::Error, R::Error>> {
+        let policy = match self.fee_policy {
+            Some(policy) if !self.dry_run => policy,
+            _ => return Ok(()),
+        };
+        let fee_rate = match self.btc_relay.fee_rate(policy.target_blocks).await {
+            Ok(Some(fee_rate)) => fee_rate,
+            Ok(None) => {
+                log::trace!("Fee rate estimate is not available");
+                return Ok(());
+            }
+            Err(e) => {
+                log::warn!("Unable to estimate the fee rate. {}", e);
+                return Ok(());
+            }
+        };
+
+        let transaction_fee = policy.transaction_fee(fee_rate);
+        let actual_fee = self
+            .api_client
+            .config()
+            .await
+            .map_err(SyncWithBitcoinError::Client)?
+            .transaction_fee;
+        if transaction_fee == actual_fee {
+            *self.proposed_fee.lock().unwrap() = None;
+            return Ok(());
+        }
+        if *self.proposed_fee.lock().unwrap() == Some(transaction_fee) {
+            return Ok(());
+        }
+
+        self.api_client
+            .update_parameters(UpdateParameters {
+                anchoring_interval: 0,
+                transaction_fee,
+            })
+            .await
+            .map_err(SyncWithBitcoinError::Client)?;
+        log::info!(
+            "Proposed transaction fee {} sat/vB instead of {} sat/vB for the estimated \
+             fee rate {} sat/vB",
+            transaction_fee,
+            actual_fee,
+            fee_rate
+        );
+        *self.proposed_fee.lock().unwrap() = Some(transaction_fee);
+        Ok(())
+    }
+
+    /// Checks whether the anchoring transaction with the given index transfers the funds
+    /// to another anchoring address, that is, its anchoring output differs from the one
+    /// of the previous anchoring transaction.
+    async fn is_transition(
+        &self,
+        index: u64,
+        transaction: &btc::Transaction,
+    ) -> Result<bool, SyncWithBitcoinError<T::Error, R::Error>> {
+        if index == 0 {
+            return Ok(false);
+        }
+        let previous_tx = self.get_transaction(index - 1).await?;
+        Ok(previous_tx.0.output[0].script_pubkey != transaction.0.output[0].script_pubkey)
+    }
+
+    /// Compares the given number of the latest anchoring transactions recorded in the service
+    /// schema with the Bitcoin blockchain: checks that the transactions are known to the Bitcoin
+    /// network, that their confirmations are consistent with the order of the anchoring chain,
+    /// and that their outputs are spent by the following anchoring transactions.
+    ///
+    /// The report is intended to be checked on startup, before the node signs anything new.
+    pub async fn reconcile(
+        &self,
+        depth: u64,
+    ) -> Result<ReconciliationReport, SyncWithBitcoinError<T::Error, R::Error>> {
+        let count = self
+            .api_client
+            .transactions_count()
+            .await
+            .map_err(SyncWithBitcoinError::Client)?
+            .value;
+
+        let mut transactions = Vec::new();
+        for index in count.saturating_sub(depth)..count {
+            transactions.push((index, self.get_transaction(index).await?));
+        }
+        let txids = transactions
+            .iter()
+            .map(|(_, transaction)| transaction.id())
+            .collect::<Vec<_>>();
+        let statuses = self.transaction_statuses(&txids).await?;
+
+        let mut records = Vec::new();
+        for ((index, transaction), status) in transactions.into_iter().zip(statuses) {
+            let outpoint = OutPoint {
+                txid: transaction.0.txid(),
+                vout: 0,
+            };
+            let spending_tx = self
+                .btc_relay
+                .spending_transaction(outpoint)
+                .await
+                .map_err(SyncWithBitcoinError::Relay)?
+                .map(|tx| tx.id());
+            records.push((index, transaction.id(), status, spending_tx));
+        }
+        Ok(ReconciliationReport::new(count, records))
+    }
+
     /// Finds the first anchoring transaction and its index, which was not committed into
     /// the Bitcoin blockchain.
     pub async fn find_first_uncommitted_transaction(
@@ -347,6 +1122,65 @@ where
         }
     }
 
+    /// Checks whether the output of the latest anchoring transaction has been spent by
+    /// the external transaction (or the latest anchoring transaction is conflicting with it),
+    /// for example, by the rescue transaction crafted manually by the operators. Such
+    /// a transaction is submitted to the anchoring service to be imported into the anchoring
+    /// chain. Returns the identifier of the submitted transaction, if any.
+    pub async fn import_external_anchor(
+        &self,
+    ) -> Result<Option<btc::Sha256d>, SyncWithBitcoinError<T::Error, R::Error>> {
+        let count = self
+            .api_client
+            .transactions_count()
+            .await
+            .map_err(SyncWithBitcoinError::Client)?
+            .value;
+        if count == 0 {
+            return Ok(None);
+        }
+
+        let latest_tx = self.get_transaction(count - 1).await?;
+        let latest_txid = latest_tx.id();
+        let outpoint = OutPoint {
+            txid: latest_tx.0.txid(),
+            vout: 0,
+        };
+        let mut external_tx = self
+            .btc_relay
+            .spending_transaction(outpoint)
+            .await
+            .map_err(SyncWithBitcoinError::Relay)?;
+        if external_tx.is_none() {
+            external_tx = self
+                .btc_relay
+                .spending_transaction(latest_tx.0.input[0].previous_output)
+                .await
+                .map_err(SyncWithBitcoinError::Relay)?
+                .filter(|tx| tx.id() != latest_txid);
+        }
+
+        let external_tx = match external_tx {
+            Some(tx) => tx,
+            None => return Ok(None),
+        };
+        let txid = external_tx.id();
+        if self.imported_transactions.lock().unwrap().contains(&txid) {
+            return Ok(None);
+        }
+        log::warn!(
+            "Anchoring transaction {} is spent by the external transaction {}, importing it",
+            latest_txid,
+            txid
+        );
+        self.api_client
+            .import_anchor(external_tx)
+            .await
+            .map_err(SyncWithBitcoinError::Client)?;
+        self.imported_transactions.lock().unwrap().insert(txid);
+        Ok(Some(txid))
+    }
+
     async fn get_transaction(
         &self,
         index: u64,
@@ -367,9 +1201,33 @@ where
         &self,
         txid: btc::Sha256d,
     ) -> Result<TransactionStatus, SyncWithBitcoinError<T::Error, R::Error>> {
+        if self.simulated_transactions.lock().unwrap().contains(&txid) {
+            return Ok(TransactionStatus::Simulated);
+        }
+
         self.btc_relay
             .transaction_status(txid)
             .await
             .map_err(SyncWithBitcoinError::Relay)
     }
+
+    /// Gets statuses of the given transactions in a single relay request, if the relay
+    /// supports the request batching.
+    async fn transaction_statuses(
+        &self,
+        txids: &[btc::Sha256d],
+    ) -> Result<Vec<TransactionStatus>, SyncWithBitcoinError<T::Error, R::Error>> {
+        let mut statuses = self
+            .btc_relay
+            .transaction_statuses(txids)
+            .await
+            .map_err(SyncWithBitcoinError::Relay)?;
+        let simulated = self.simulated_transactions.lock().unwrap();
+        for (txid, status) in txids.iter().zip(&mut statuses) {
+            if simulated.contains(txid) {
+                *status = TransactionStatus::Simulated;
+            }
+        }
+        Ok(statuses)
+    }
 }
@@ -0,0 +1,370 @@
+// Copyright 2020 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Connections of the Bitcoin relays via the SOCKS5 proxy, for example, Tor.
+
+use reqwest::Url;
+use serde_derive::{Deserialize, Serialize};
+use thiserror::Error;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+};
+
+use std::{io, net::IpAddr};
+
+const SOCKS_VERSION: u8 = 0x05;
+const AUTH_VERSION: u8 = 0x01;
+const METHOD_NO_AUTH: u8 = 0x00;
+const METHOD_USER_PASS: u8 = 0x02;
+const METHOD_NOT_ACCEPTABLE: u8 = 0xff;
+const COMMAND_CONNECT: u8 = 0x01;
+const ADDRESS_IPV4: u8 = 0x01;
+const ADDRESS_DOMAIN: u8 = 0x03;
+const ADDRESS_IPV6: u8 = 0x04;
+
+/// SOCKS5 proxy settings of the connections to the Bitcoin network.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProxyConfig {
+    /// Host of the proxy, for example, `127.0.0.1` for the local Tor daemon.
+    pub host: String,
+    /// Port of the proxy, for example, `9050` for the Tor daemon.
+    pub port: u16,
+    /// Username for the proxy authentication. Tor uses different circuits for
+    /// the connections with different credentials.
+    #[serde(default)]
+    pub username: Option<String>,
+    /// Password for the proxy authentication.
+    #[serde(default)]
+    pub password: Option<String>,
+}
+
+/// Errors of the connections via the SOCKS5 proxy.
+#[derive(Debug, Error)]
+pub enum ProxyError {
+    /// The proxy settings are invalid.
+    #[error("Invalid SOCKS5 proxy settings: {0}")]
+    InvalidConfig(String),
+    /// The target address is not in the `host:port` format.
+    #[error("Invalid address `{0}`, `host:port` is expected")]
+    InvalidTarget(String),
+    /// The proxy does not follow the SOCKS5 protocol.
+    #[error("Unexpected response of the SOCKS5 proxy: {0}")]
+    Protocol(&'static str),
+    /// The proxy has rejected the credentials or requires the unsupported authentication.
+    #[error("SOCKS5 proxy authentication failed: {0}")]
+    Authentication(&'static str),
+    /// The proxy is unable to connect to the target.
+    #[error("SOCKS5 proxy is unable to connect to {target}: {message}")]
+    Connect {
+        /// The target address.
+        target: String,
+        /// Error message corresponding to the reply code of the proxy.
+        message: &'static str,
+    },
+    /// Unable to create the HTTP client.
+    #[error("{0}")]
+    Http(#[from] reqwest::Error),
+    /// Network error.
+    #[error("{0}")]
+    Io(#[from] io::Error),
+}
+
+impl ProxyConfig {
+    /// Returns the proxy URL. The `socks5h` scheme is used, so the host names,
+    /// including the onion ones, are resolved by the proxy.
+    pub fn url(&self) -> Result<Url, ProxyError> {
+        let invalid_config = |message: &str| ProxyError::InvalidConfig(message.to_owned());
+
+        let mut url = Url::parse(&format!("socks5h://{}:{}", self.host, self.port))
+            .map_err(|e| invalid_config(&e.to_string()))?;
+        match (&self.username, &self.password) {
+            (Some(username), password) => {
+                url.set_username(username)
+                    .map_err(|_| invalid_config("invalid username"))?;
+                url.set_password(password.as_deref())
+                    .map_err(|_| invalid_config("invalid password"))?;
+            }
+            (None, Some(_)) => return Err(invalid_config("password requires the username")),
+            (None, None) => {}
+        }
+        Ok(url)
+    }
+
+    /// Returns the proxy for the HTTP clients, which routes all requests via this proxy.
+    pub fn http_proxy(&self) -> Result<reqwest::Proxy, ProxyError> {
+        Ok(reqwest::Proxy::all(self.url()?)?)
+    }
+
+    /// Establishes the TCP connection to the target address in the `host:port` format
+    /// via the proxy.
+    pub async fn connect(&self, target: &str) -> Result<TcpStream, ProxyError> {
+        let (host, port) = parse_target(target)?;
+        let mut stream = TcpStream::connect((self.host.as_str(), self.port)).await?;
+        self.authenticate(&mut stream).await?;
+
+        let mut request = vec![SOCKS_VERSION, COMMAND_CONNECT, 0x00];
+        match host.parse::<IpAddr>() {
+            Ok(IpAddr::V4(ip)) => {
+                request.push(ADDRESS_IPV4);
+                request.extend_from_slice(&ip.octets());
+            }
+            Ok(IpAddr::V6(ip)) => {
+                request.push(ADDRESS_IPV6);
+                request.extend_from_slice(&ip.octets());
+            }
+            Err(_) => {
+                if host.len() > 255 {
+                    return Err(ProxyError::InvalidTarget(target.to_owned()));
+                }
+                request.push(ADDRESS_DOMAIN);
+                request.push(host.len() as u8);
+                request.extend_from_slice(host.as_bytes());
+            }
+        }
+        request.extend_from_slice(&port.to_be_bytes());
+        stream.write_all(&request).await?;
+
+        let mut reply = [0_u8; 4];
+        stream.read_exact(&mut reply).await?;
+        if reply[0] != SOCKS_VERSION {
+            return Err(ProxyError::Protocol("invalid version in the reply"));
+        }
+        if reply[1] != 0x00 {
+            return Err(ProxyError::Connect {
+                target: target.to_owned(),
+                message: reply_message(reply[1]),
+            });
+        }
+        // The bound address is not used, but should be read out of the stream.
+        let address_len = match reply[3] {
+            ADDRESS_IPV4 => 4,
+            ADDRESS_IPV6 => 16,
+            ADDRESS_DOMAIN => usize::from(stream.read_u8().await?),
+            _ => return Err(ProxyError::Protocol("invalid address type in the reply")),
+        };
+        let mut bound_address = vec![0_u8; address_len + 2];
+        stream.read_exact(&mut bound_address).await?;
+        Ok(stream)
+    }
+
+    /// Negotiates the authentication method and authenticates with the credentials,
+    /// if they are specified.
+    async fn authenticate(&self, stream: &mut TcpStream) -> Result<(), ProxyError> {
+        let credentials = self.username.as_ref().map(|username| {
+            (
+                username.as_bytes(),
+                self.password.as_deref().unwrap_or_default().as_bytes(),
+            )
+        });
+        let method = if credentials.is_some() {
+            METHOD_USER_PASS
+        } else {
+            METHOD_NO_AUTH
+        };
+        stream.write_all(&[SOCKS_VERSION, 1, method]).await?;
+
+        let mut response = [0_u8; 2];
+        stream.read_exact(&mut response).await?;
+        if response[0] != SOCKS_VERSION {
+            return Err(ProxyError::Protocol(
+                "invalid version in the method selection",
+            ));
+        }
+        if response[1] == METHOD_NOT_ACCEPTABLE {
+            return Err(ProxyError::Authentication(
+                "no acceptable authentication method",
+            ));
+        }
+        if response[1] != method {
+            return Err(ProxyError::Protocol("unexpected authentication method"));
+        }
+
+        if let Some((username, password)) = credentials {
+            if username.len() > 255 || password.len() > 255 {
+                return Err(ProxyError::InvalidConfig(
+                    "username and password should not be longer than 255 bytes".to_owned(),
+                ));
+            }
+            let mut request = vec![AUTH_VERSION, username.len() as u8];
+            request.extend_from_slice(username);
+            request.push(password.len() as u8);
+            request.extend_from_slice(password);
+            stream.write_all(&request).await?;
+
+            let mut response = [0_u8; 2];
+            stream.read_exact(&mut response).await?;
+            if response[1] != 0x00 {
+                return Err(ProxyError::Authentication("credentials are rejected"));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Splits the `host:port` address, IPv6 hosts should be enclosed in the brackets.
+fn parse_target(target: &str) -> Result<(&str, u16), ProxyError> {
+    let invalid_target = || ProxyError::InvalidTarget(target.to_owned());
+    let position = target.rfind(':').ok_or_else(invalid_target)?;
+    let port = target[position + 1..]
+        .parse()
+        .map_err(|_| invalid_target())?;
+    let host = &target[..position];
+    let host = if host.starts_with('[') && host.ends_with(']') {
+        &host[1..host.len() - 1]
+    } else if host.contains(':') {
+        return Err(invalid_target());
+    } else {
+        host
+    };
+    if host.is_empty() {
+        return Err(invalid_target());
+    }
+    Ok((host, port))
+}
+
+fn reply_message(code: u8) -> &'static str {
+    match code {
+        0x01 => "general failure",
+        0x02 => "connection not allowed by ruleset",
+        0x03 => "network unreachable",
+        0x04 => "host unreachable",
+        0x05 => "connection refused",
+        0x06 => "TTL expired",
+        0x07 => "command not supported",
+        0x08 => "address type not supported",
+        _ => "unknown error",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::net::TcpListener;
+
+    use super::*;
+
+    #[test]
+    fn proxy_url() {
+        let mut config = ProxyConfig {
+            host: "127.0.0.1".to_owned(),
+            port: 9050,
+            ..ProxyConfig::default()
+        };
+        assert_eq!(config.url().unwrap().as_str(), "socks5h://127.0.0.1:9050");
+
+        config.username = Some("anchoring".to_owned());
+        config.password = Some("p@ss:word".to_owned());
+        let url = config.url().unwrap();
+        assert_eq!(url.username(), "anchoring");
+        assert_eq!(url.password(), Some("p%40ss%3Aword"));
+
+        config.username = None;
+        assert!(config.url().is_err());
+    }
+
+    #[test]
+    fn target_parsing() {
+        assert_eq!(
+            parse_target("electrum.example.com:50001").unwrap(),
+            ("electrum.example.com", 50001)
+        );
+        assert_eq!(parse_target("10.0.0.1:8332").unwrap(), ("10.0.0.1", 8332));
+        assert_eq!(parse_target("[::1]:8332").unwrap(), ("::1", 8332));
+        assert!(parse_target("electrum.example.com").is_err());
+        assert!(parse_target(":50001").is_err());
+        assert!(parse_target("::1:8332").is_err());
+    }
+
+    #[tokio::test]
+    async fn connect_with_credentials() {
+        let mut listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let config = ProxyConfig {
+            host: "127.0.0.1".to_owned(),
+            port: listener.local_addr().unwrap().port(),
+            username: Some("user".to_owned()),
+            password: Some("pass".to_owned()),
+        };
+
+        let proxy = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut greeting = [0_u8; 3];
+            stream.read_exact(&mut greeting).await.unwrap();
+            assert_eq!(greeting, [SOCKS_VERSION, 1, METHOD_USER_PASS]);
+            stream
+                .write_all(&[SOCKS_VERSION, METHOD_USER_PASS])
+                .await
+                .unwrap();
+
+            let mut auth = [0_u8; 11];
+            stream.read_exact(&mut auth).await.unwrap();
+            assert_eq!(&auth, b"\x01\x04user\x04pass");
+            stream.write_all(&[AUTH_VERSION, 0x00]).await.unwrap();
+
+            let mut request = [0_u8; 5];
+            stream.read_exact(&mut request).await.unwrap();
+            assert_eq!(
+                request,
+                [SOCKS_VERSION, COMMAND_CONNECT, 0x00, ADDRESS_DOMAIN, 11]
+            );
+            let mut host_and_port = [0_u8; 13];
+            stream.read_exact(&mut host_and_port).await.unwrap();
+            assert_eq!(&host_and_port[..11], b"node.onion1");
+            assert_eq!(&host_and_port[11..], &8332_u16.to_be_bytes());
+            stream
+                .write_all(&[SOCKS_VERSION, 0x00, 0x00, ADDRESS_IPV4, 0, 0, 0, 0, 0, 0])
+                .await
+                .unwrap();
+            stream.write_all(b"pong").await.unwrap();
+        });
+
+        let mut stream = config.connect("node.onion1:8332").await.unwrap();
+        let mut payload = [0_u8; 4];
+        stream.read_exact(&mut payload).await.unwrap();
+        assert_eq!(&payload, b"pong");
+        proxy.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn connect_refused() {
+        let mut listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let config = ProxyConfig {
+            host: "127.0.0.1".to_owned(),
+            port: listener.local_addr().unwrap().port(),
+            ..ProxyConfig::default()
+        };
+
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut greeting = [0_u8; 3];
+            stream.read_exact(&mut greeting).await.unwrap();
+            stream
+                .write_all(&[SOCKS_VERSION, METHOD_NO_AUTH])
+                .await
+                .unwrap();
+            let mut request = [0_u8; 10];
+            stream.read_exact(&mut request).await.unwrap();
+            assert_eq!(request[3], ADDRESS_IPV4);
+            stream
+                .write_all(&[SOCKS_VERSION, 0x05, 0x00, ADDRESS_IPV4, 0, 0, 0, 0, 0, 0])
+                .await
+                .unwrap();
+        });
+
+        let err = config.connect("10.0.0.1:8332").await.unwrap_err();
+        assert!(
+            matches!(err, ProxyError::Connect { message, .. } if message == "connection refused")
+        );
+    }
+}
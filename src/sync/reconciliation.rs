@@ -0,0 +1,227 @@
+// Copyright 2019 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Reconciliation of the anchoring chain with the Bitcoin blockchain.
+
+use serde_derive::{Deserialize, Serialize};
+
+use std::fmt::{self, Display};
+
+use crate::btc;
+
+use super::TransactionStatus;
+
+/// Divergence between the anchoring chain recorded in the service schema and the state
+/// of the Bitcoin blockchain reported by the relay.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Divergence {
+    /// Anchoring transaction is unknown to the Bitcoin network, while the following
+    /// anchoring transaction is known.
+    Missing,
+    /// Anchoring transaction has fewer confirmations than the following anchoring
+    /// transaction, which spends its output.
+    ConfirmationsOutOfOrder {
+        /// Number of confirmations of the following anchoring transaction.
+        following_confirmations: u32,
+    },
+    /// Anchoring output is spent by the transaction, which is absent in the anchoring chain.
+    ConflictingSpend {
+        /// Identifier of the spending transaction.
+        txid: btc::Sha256d,
+    },
+}
+
+impl Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Divergence::Missing => f.write_str(
+                "transaction is unknown to the Bitcoin network, \
+                 but the following anchoring transaction is known",
+            ),
+            Divergence::ConfirmationsOutOfOrder {
+                following_confirmations,
+            } => write!(
+                f,
+                "following anchoring transaction has more confirmations ({})",
+                following_confirmations
+            ),
+            Divergence::ConflictingSpend { txid } => write!(
+                f,
+                "anchoring output is spent by the external transaction {}",
+                txid
+            ),
+        }
+    }
+}
+
+/// Reconciliation outcome of the single anchoring transaction.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AnchorReconciliation {
+    /// Index of the transaction in the anchoring chain.
+    pub index: u64,
+    /// Identifier of the anchoring transaction.
+    pub txid: btc::Sha256d,
+    /// Status of the transaction reported by the Bitcoin relay.
+    pub status: TransactionStatus,
+    /// Found divergences, if any.
+    pub divergences: Vec<Divergence>,
+}
+
+/// Report of the reconciliation between the latest anchoring transactions recorded
+/// in the service schema and the Bitcoin blockchain.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ReconciliationReport {
+    /// Length of the anchoring chain at the moment of the reconciliation.
+    pub transactions_count: u64,
+    /// Reconciled anchoring transactions in the order of the anchoring chain.
+    pub anchors: Vec<AnchorReconciliation>,
+}
+
+impl ReconciliationReport {
+    /// Checks that no divergences have been found.
+    pub fn is_consistent(&self) -> bool {
+        self.anchors
+            .iter()
+            .all(|anchor| anchor.divergences.is_empty())
+    }
+
+    /// Returns the anchoring transactions with the found divergences.
+    pub fn divergent_anchors(&self) -> impl Iterator<Item = &AnchorReconciliation> {
+        self.anchors
+            .iter()
+            .filter(|anchor| !anchor.divergences.is_empty())
+    }
+
+    /// Finds the divergences of the anchoring transactions, which statuses and the
+    /// transactions spending their outputs have been reported by the relay. The given
+    /// records should form the continuous tail of the anchoring chain.
+    pub(super) fn new(
+        transactions_count: u64,
+        records: Vec<(u64, btc::Sha256d, TransactionStatus, Option<btc::Sha256d>)>,
+    ) -> Self {
+        let anchors = records
+            .iter()
+            .enumerate()
+            .map(|(i, (index, txid, status, spending_tx))| {
+                let following = records.get(i + 1);
+                let mut divergences = Vec::new();
+                if let Some((_, following_txid, following_status, _)) = following {
+                    if !status.is_known() && following_status.is_known() {
+                        divergences.push(Divergence::Missing);
+                    }
+                    let confirmations = status.confirmations().unwrap_or_default();
+                    match following_status.confirmations() {
+                        Some(following_confirmations)
+                            if status.is_known() && following_confirmations > confirmations =>
+                        {
+                            divergences.push(Divergence::ConfirmationsOutOfOrder {
+                                following_confirmations,
+                            })
+                        }
+                        _ => {}
+                    }
+                    if let Some(spending_tx) =
+                        spending_tx.filter(|spending_tx| spending_tx != following_txid)
+                    {
+                        divergences.push(Divergence::ConflictingSpend { txid: spending_tx });
+                    }
+                } else if let Some(spending_tx) = spending_tx {
+                    divergences.push(Divergence::ConflictingSpend { txid: *spending_tx });
+                }
+
+                AnchorReconciliation {
+                    index: *index,
+                    txid: *txid,
+                    status: *status,
+                    divergences,
+                }
+            })
+            .collect();
+
+        Self {
+            transactions_count,
+            anchors,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Divergence, ReconciliationReport, TransactionStatus};
+    use crate::btc;
+
+    fn txid(n: u8) -> btc::Sha256d {
+        btc::Sha256d::from_slice(&[n; 32]).unwrap()
+    }
+
+    #[test]
+    fn reconciliation_divergences() {
+        // Consistent chain with the latest transaction in the mempool.
+        let report = ReconciliationReport::new(
+            3,
+            vec![
+                (1, txid(1), TransactionStatus::Committed(2), Some(txid(2))),
+                (2, txid(2), TransactionStatus::Mempool, None),
+            ],
+        );
+        assert!(report.is_consistent());
+
+        // Unsent transactions at the tail of the chain are not divergences.
+        let report = ReconciliationReport::new(
+            3,
+            vec![
+                (1, txid(1), TransactionStatus::Unknown, None),
+                (2, txid(2), TransactionStatus::Unknown, None),
+            ],
+        );
+        assert!(report.is_consistent());
+
+        let report = ReconciliationReport::new(
+            3,
+            vec![
+                (0, txid(0), TransactionStatus::Committed(1), Some(txid(3))),
+                (1, txid(1), TransactionStatus::Unknown, None),
+                (2, txid(2), TransactionStatus::Committed(4), Some(txid(4))),
+            ],
+        );
+        assert!(!report.is_consistent());
+        let divergences = report
+            .divergent_anchors()
+            .map(|anchor| (anchor.index, anchor.divergences.clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            divergences,
+            vec![
+                (0, vec![Divergence::ConflictingSpend { txid: txid(3) }]),
+                (1, vec![Divergence::Missing]),
+                (2, vec![Divergence::ConflictingSpend { txid: txid(4) }]),
+            ]
+        );
+
+        let report = ReconciliationReport::new(
+            2,
+            vec![
+                (0, txid(0), TransactionStatus::Committed(1), Some(txid(1))),
+                (1, txid(1), TransactionStatus::Committed(3), None),
+            ],
+        );
+        assert_eq!(
+            report.anchors[0].divergences,
+            vec![Divergence::ConfirmationsOutOfOrder {
+                following_confirmations: 3
+            }]
+        );
+    }
+}
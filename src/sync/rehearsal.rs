@@ -0,0 +1,238 @@
+// Copyright 2019 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Mirroring of the anchoring transactions to the test Bitcoin network.
+
+use anyhow::anyhow;
+use bitcoin::blockdata::{
+    script::Script,
+    transaction::{self, OutPoint, TxIn, TxOut},
+};
+use btc_transaction_utils::{p2wpk, TxInRef};
+use serde_derive::{Deserialize, Serialize};
+
+use std::{
+    fmt::Display,
+    fs,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use super::{SyncWithBitcoinError, TxBroadcaster};
+use crate::{api::PrivateApi, btc};
+
+/// Virtual size in bytes of the P2WPKH input witness.
+const P2WPKH_WITNESS_VSIZE: u64 = 27;
+
+/// State of the rehearsal mirror.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MirrorState {
+    /// Index of the latest mirrored anchoring transaction.
+    pub latest_index: Option<u64>,
+    /// Transaction, which first output funds the following mirror transaction.
+    pub unspent_transaction: btc::Transaction,
+}
+
+/// Mirrors the anchoring transactions to the test Bitcoin network (testnet or signet).
+///
+/// Each mirror transaction carries the same payload as the mirrored anchoring transaction,
+/// and spends the change output of the previous mirror transaction, which is controlled
+/// by the single rehearsal key. Thus the tooling and monitoring can be validated against
+/// the harmless chain.
+#[derive(Debug)]
+pub struct RehearsalMirror<T, R>
+where
+    T: PrivateApi + 'static,
+    R: TxBroadcaster + 'static,
+{
+    api_client: T,
+    btc_relay: R,
+    keypair: (btc::PublicKey, btc::PrivateKey),
+    fee: u64,
+    state: Mutex<MirrorState>,
+    state_file: Option<PathBuf>,
+}
+
+impl<T, R> RehearsalMirror<T, R>
+where
+    T: PrivateApi + 'static,
+    R: TxBroadcaster + 'static,
+    T::Error: Display,
+    R::Error: Display,
+{
+    /// Creates a new mirror, which mirror transactions are signed by the given private key
+    /// and pay the given fee per byte. The first mirror transaction spends the output of the
+    /// funding transaction to the address of the private key.
+    pub fn new(
+        api_client: T,
+        btc_relay: R,
+        private_key: btc::PrivateKey,
+        fee: u64,
+        funding_transaction: btc::Transaction,
+    ) -> Self {
+        let context = bitcoin::secp256k1::Secp256k1::new();
+        let public_key = btc::PublicKey(private_key.0.public_key(&context));
+        Self {
+            api_client,
+            btc_relay,
+            keypair: (public_key, private_key),
+            fee,
+            state: Mutex::new(MirrorState {
+                latest_index: None,
+                unspent_transaction: funding_transaction,
+            }),
+            state_file: None,
+        }
+    }
+
+    /// Persists the mirror state in the given file, so the mirror continues from the latest
+    /// mirrored transaction after the restart. The state is loaded from the file if it exists.
+    pub fn with_state_file(mut self, path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref().to_owned();
+        if path.exists() {
+            *self.state.get_mut().unwrap() = serde_json::from_str(&fs::read_to_string(&path)?)?;
+        }
+        self.state_file = Some(path);
+        Ok(self)
+    }
+
+    /// Returns the current state of the mirror.
+    pub fn state(&self) -> MirrorState {
+        self.state.lock().unwrap().clone()
+    }
+
+    /// Mirrors the following anchoring transaction, if any. The mirroring starts from
+    /// the latest anchoring transaction. Returns the identifier of the sent mirror transaction.
+    pub async fn process(
+        &self,
+    ) -> Result<Option<btc::Sha256d>, SyncWithBitcoinError<T::Error, R::Error>> {
+        let count = self
+            .api_client
+            .transactions_count()
+            .await
+            .map_err(SyncWithBitcoinError::Client)?
+            .value;
+        let state = self.state();
+        let index = match state.latest_index {
+            Some(index) if index + 1 < count => index + 1,
+            Some(_) => return Ok(None),
+            None if count > 0 => count - 1,
+            None => return Ok(None),
+        };
+
+        let anchoring_tx = self
+            .api_client
+            .transaction_with_index(index)
+            .await
+            .map_err(SyncWithBitcoinError::Client)?
+            .ok_or_else(|| {
+                SyncWithBitcoinError::Internal(anyhow!(
+                    "Transaction with index {} is absent in the anchoring chain",
+                    index
+                ))
+            })?;
+        let mirror_tx = self
+            .create_mirror_transaction(&anchoring_tx, &state.unspent_transaction)
+            .map_err(SyncWithBitcoinError::Internal)?;
+        let txid = self
+            .btc_relay
+            .send_transaction(&mirror_tx)
+            .await
+            .map_err(SyncWithBitcoinError::Relay)?;
+        log::info!(
+            "Mirrored anchoring transaction {} by the transaction {}",
+            anchoring_tx.id(),
+            txid
+        );
+
+        self.update_state(MirrorState {
+            latest_index: Some(index),
+            unspent_transaction: mirror_tx,
+        })
+        .map_err(SyncWithBitcoinError::Internal)?;
+        Ok(Some(txid))
+    }
+
+    fn create_mirror_transaction(
+        &self,
+        anchoring_tx: &btc::Transaction,
+        unspent_tx: &btc::Transaction,
+    ) -> anyhow::Result<btc::Transaction> {
+        let payload_script = anchoring_tx
+            .0
+            .output
+            .get(1)
+            .map(|out| out.script_pubkey.clone())
+            .ok_or_else(|| anyhow!("Anchoring transaction {} has no payload", anchoring_tx.id()))?;
+        let script_pubkey = p2wpk::script_pubkey(&(self.keypair.0).0);
+        let (vout, prev_out) = unspent_tx
+            .find_out(&script_pubkey)
+            .ok_or_else(|| anyhow!("Transaction {} has no rehearsal output", unspent_tx.id()))?;
+        let balance = prev_out.value;
+
+        let mut transaction = transaction::Transaction {
+            version: 2,
+            lock_time: 0,
+            input: vec![TxIn {
+                previous_output: OutPoint {
+                    txid: unspent_tx.0.txid(),
+                    vout: vout as u32,
+                },
+                script_sig: Script::default(),
+                sequence: 0xFFFF_FFFF,
+                witness: Vec::default(),
+            }],
+            output: vec![
+                TxOut {
+                    value: balance,
+                    script_pubkey,
+                },
+                TxOut {
+                    value: 0,
+                    script_pubkey: payload_script,
+                },
+            ],
+        };
+
+        let size = bitcoin::consensus::serialize(&transaction).len() as u64 + P2WPKH_WITNESS_VSIZE;
+        let total_fee = self.fee * size;
+        transaction.output[0].value = balance.checked_sub(total_fee).ok_or_else(|| {
+            anyhow!(
+                "Insufficient rehearsal funds, total fee is {}, balance is {}",
+                total_fee,
+                balance
+            )
+        })?;
+
+        let mut signer = p2wpk::InputSigner::new((self.keypair.0).0, (self.keypair.1).0.network);
+        let signature = signer.sign_input(
+            TxInRef::new(&transaction, 0),
+            unspent_tx.as_ref(),
+            &(self.keypair.1).0.key,
+        )?;
+        signer.spend_input(&mut transaction.input[0], signature);
+        Ok(btc::Transaction(transaction))
+    }
+
+    fn update_state(&self, state: MirrorState) -> anyhow::Result<()> {
+        if let Some(path) = &self.state_file {
+            // Replace the file atomically to not corrupt the state on crash.
+            let tmp_path = path.with_extension("tmp");
+            fs::write(&tmp_path, serde_json::to_string(&state)?)?;
+            fs::rename(tmp_path, path)?;
+        }
+        *self.state.lock().unwrap() = state;
+        Ok(())
+    }
+}
@@ -0,0 +1,366 @@
+// Copyright 2019 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Read-through cache of the Bitcoin data fetched via the relay.
+
+use async_trait::async_trait;
+use bitcoin::OutPoint;
+
+use std::{
+    collections::HashMap,
+    hash::Hash,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use crate::{api::v1::AnchoringDescriptor, btc};
+
+use super::{
+    ChainQuery, MempoolEntry, RelayBackend, TransactionStatus, TxBroadcaster, WalletOps, WalletUtxo,
+};
+
+/// Bounded cache, which evicts the least recently used entry on overflow.
+///
+/// The eviction takes linear time, which is negligible for the cache sizes used
+/// by the relay compared to the RPC round trips.
+#[derive(Debug)]
+struct LruCache<K, V> {
+    capacity: usize,
+    entries: HashMap<K, (V, u64)>,
+    tick: u64,
+}
+
+impl<K: Hash + Eq + Clone, V: Clone> LruCache<K, V> {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: HashMap::new(),
+            tick: 0,
+        }
+    }
+
+    fn get(&mut self, key: &K) -> Option<V> {
+        self.tick += 1;
+        let tick = self.tick;
+        self.entries.get_mut(key).map(|(value, used_at)| {
+            *used_at = tick;
+            value.clone()
+        })
+    }
+
+    fn put(&mut self, key: K, value: V) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.entries.len() >= self.capacity && !self.entries.contains_key(&key) {
+            let oldest = self
+                .entries
+                .iter()
+                .min_by_key(|(_, (_, used_at))| *used_at)
+                .map(|(key, _)| key.clone());
+            if let Some(oldest) = oldest {
+                self.entries.remove(&oldest);
+            }
+        }
+        self.tick += 1;
+        self.entries.insert(key, (value, self.tick));
+    }
+
+    fn remove(&mut self, key: &K) {
+        self.entries.remove(key);
+    }
+
+    fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+/// Bitcoin relay with a read-through cache of the fetched transaction data.
+///
+/// The sync task repeatedly checks the statuses of the same anchoring transactions, and
+/// the verification of the anchors requests the same historical data again and again.
+/// This relay keeps the recently fetched data in the bounded caches:
+///
+/// - Statuses of the committed transactions are cached for the limited time, since their
+///   numbers of confirmations grow with the new Bitcoin blocks. Statuses of the unknown and
+///   mempool transactions are never cached.
+/// - Transactions spending the anchoring outputs are cached until eviction.
+#[derive(Debug)]
+pub struct CachedRelay<R> {
+    inner: R,
+    status_ttl: Duration,
+    statuses: Mutex<LruCache<btc::Sha256d, (u32, Instant)>>,
+    spending_transactions: Mutex<LruCache<OutPoint, btc::Transaction>>,
+}
+
+impl<R> CachedRelay<R> {
+    /// Default maximal number of the entries in each of the caches.
+    pub const DEFAULT_CAPACITY: usize = 1_000;
+    /// Default lifetime of the cached transaction statuses.
+    pub const DEFAULT_STATUS_TTL: Duration = Duration::from_secs(60);
+
+    /// Creates a new cached relay on top of the given one with the default parameters.
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            status_ttl: Self::DEFAULT_STATUS_TTL,
+            statuses: Mutex::new(LruCache::new(Self::DEFAULT_CAPACITY)),
+            spending_transactions: Mutex::new(LruCache::new(Self::DEFAULT_CAPACITY)),
+        }
+    }
+
+    /// Sets the maximal number of the entries in each of the caches.
+    pub fn with_capacity(mut self, capacity: usize) -> Self {
+        self.statuses = Mutex::new(LruCache::new(capacity));
+        self.spending_transactions = Mutex::new(LruCache::new(capacity));
+        self
+    }
+
+    /// Sets the lifetime of the cached transaction statuses.
+    pub fn with_status_ttl(mut self, status_ttl: Duration) -> Self {
+        self.status_ttl = status_ttl;
+        self
+    }
+
+    /// Returns the underlying relay.
+    pub fn inner(&self) -> &R {
+        &self.inner
+    }
+
+    /// Drops the cached transaction statuses, for example, once a new Bitcoin block
+    /// is connected.
+    pub fn clear_statuses(&self) {
+        self.statuses.lock().unwrap().clear();
+    }
+
+    /// Drops all of the cached data, for example, after a reorganization of the Bitcoin
+    /// blockchain.
+    pub fn clear(&self) {
+        self.statuses.lock().unwrap().clear();
+        self.spending_transactions.lock().unwrap().clear();
+    }
+}
+
+impl<R: RelayBackend> RelayBackend for CachedRelay<R> {
+    type Error = R::Error;
+}
+
+#[async_trait]
+impl<R> TxBroadcaster for CachedRelay<R>
+where
+    R: TxBroadcaster + Send + Sync,
+    R::Error: Send,
+{
+    async fn send_transaction(
+        &self,
+        transaction: &btc::Transaction,
+    ) -> Result<btc::Sha256d, Self::Error> {
+        let txid = self.inner.send_transaction(transaction).await?;
+        self.statuses.lock().unwrap().remove(&txid);
+        Ok(txid)
+    }
+}
+
+#[async_trait]
+impl<R> ChainQuery for CachedRelay<R>
+where
+    R: ChainQuery + Send + Sync,
+    R::Error: Send,
+{
+    async fn transaction_status(&self, id: btc::Sha256d) -> Result<TransactionStatus, Self::Error> {
+        let cached = self.statuses.lock().unwrap().get(&id);
+        if let Some((confirmations, cached_at)) = cached {
+            if cached_at.elapsed() < self.status_ttl {
+                return Ok(TransactionStatus::Committed(confirmations));
+            }
+        }
+
+        let status = self.inner.transaction_status(id).await?;
+        if let TransactionStatus::Committed(confirmations) = status {
+            self.statuses
+                .lock()
+                .unwrap()
+                .put(id, (confirmations, Instant::now()));
+        } else {
+            self.statuses.lock().unwrap().remove(&id);
+        }
+        Ok(status)
+    }
+
+    async fn transaction_statuses(
+        &self,
+        ids: &[btc::Sha256d],
+    ) -> Result<Vec<TransactionStatus>, Self::Error> {
+        let mut statuses = {
+            let mut cache = self.statuses.lock().unwrap();
+            ids.iter()
+                .map(|id| {
+                    cache
+                        .get(id)
+                        .filter(|(_, cached_at)| cached_at.elapsed() < self.status_ttl)
+                        .map(|(confirmations, _)| TransactionStatus::Committed(confirmations))
+                })
+                .collect::<Vec<_>>()
+        };
+
+        // Only the statuses missing in the cache are requested from the underlying relay.
+        let missing = ids
+            .iter()
+            .zip(&statuses)
+            .filter(|(_, status)| status.is_none())
+            .map(|(id, _)| *id)
+            .collect::<Vec<_>>();
+        if !missing.is_empty() {
+            let fetched = self.inner.transaction_statuses(&missing).await?;
+            let mut cache = self.statuses.lock().unwrap();
+            let mut fetched = missing.into_iter().zip(fetched);
+            for status in statuses.iter_mut().filter(|status| status.is_none()) {
+                if let Some((id, fetched_status)) = fetched.next() {
+                    if let TransactionStatus::Committed(confirmations) = fetched_status {
+                        cache.put(id, (confirmations, Instant::now()));
+                    } else {
+                        cache.remove(&id);
+                    }
+                    *status = Some(fetched_status);
+                }
+            }
+        }
+        Ok(statuses
+            .into_iter()
+            .map(|status| status.unwrap_or(TransactionStatus::Unknown))
+            .collect())
+    }
+
+    async fn spending_transaction(
+        &self,
+        outpoint: OutPoint,
+    ) -> Result<Option<btc::Transaction>, Self::Error> {
+        let cached = self.spending_transactions.lock().unwrap().get(&outpoint);
+        if cached.is_some() {
+            return Ok(cached);
+        }
+
+        let transaction = self.inner.spending_transaction(outpoint).await?;
+        if let Some(transaction) = transaction.as_ref() {
+            self.spending_transactions
+                .lock()
+                .unwrap()
+                .put(outpoint, transaction.clone());
+        }
+        Ok(transaction)
+    }
+
+    // Fee rate estimates change with every new transaction, so they are not cached.
+    async fn fee_rate(&self, target_blocks: u16) -> Result<Option<u64>, Self::Error> {
+        self.inner.fee_rate(target_blocks).await
+    }
+
+    // Mempool entries are used to detect the evicted transactions, so they are not cached.
+    async fn mempool_entry(&self, id: btc::Sha256d) -> Result<Option<MempoolEntry>, Self::Error> {
+        self.inner.mempool_entry(id).await
+    }
+
+    // Blocks of the transactions change on the reorganizations, so they are not cached.
+    async fn transaction_block_hash(
+        &self,
+        id: btc::Sha256d,
+    ) -> Result<Option<btc::Sha256d>, Self::Error> {
+        self.inner.transaction_block_hash(id).await
+    }
+
+    async fn transaction_status_in_block(
+        &self,
+        id: btc::Sha256d,
+        block_hash: btc::Sha256d,
+    ) -> Result<TransactionStatus, Self::Error> {
+        self.inner.transaction_status_in_block(id, block_hash).await
+    }
+
+    // Health checks should reach the relay, so they are not cached.
+    async fn ping(&self) -> Result<Option<u64>, Self::Error> {
+        self.inner.ping().await
+    }
+}
+
+#[async_trait]
+impl<R> WalletOps for CachedRelay<R>
+where
+    R: WalletOps + Send + Sync,
+    R::Error: Send,
+{
+    async fn watch_address(
+        &self,
+        descriptor: &AnchoringDescriptor,
+        label: &str,
+    ) -> Result<(), Self::Error> {
+        self.inner.watch_address(descriptor, label).await
+    }
+
+    async fn watch_addresses(
+        &self,
+        descriptors: &[AnchoringDescriptor],
+        label: &str,
+    ) -> Result<(), Self::Error> {
+        self.inner.watch_addresses(descriptors, label).await
+    }
+
+    async fn restore_watch_data(
+        &self,
+        descriptor: &AnchoringDescriptor,
+        label: &str,
+        latest_anchor: btc::Sha256d,
+        funding_tx: btc::Sha256d,
+        fallback_height: Option<u64>,
+    ) -> Result<Option<u64>, Self::Error> {
+        self.clear();
+        self.inner
+            .restore_watch_data(
+                descriptor,
+                label,
+                latest_anchor,
+                funding_tx,
+                fallback_height,
+            )
+            .await
+    }
+
+    async fn unspent_outputs(
+        &self,
+        address: &btc::Address,
+    ) -> Result<Vec<WalletUtxo>, Self::Error> {
+        self.inner.unspent_outputs(address).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LruCache;
+
+    #[test]
+    fn lru_cache_eviction() {
+        let mut cache = LruCache::new(2);
+        cache.put(1, "a");
+        cache.put(2, "b");
+        // Make the first entry the most recently used one.
+        assert_eq!(cache.get(&1), Some("a"));
+        cache.put(3, "c");
+        assert_eq!(cache.get(&2), None);
+        assert_eq!(cache.get(&1), Some("a"));
+        assert_eq!(cache.get(&3), Some("c"));
+
+        let mut cache = LruCache::new(0);
+        cache.put(1, "a");
+        assert_eq!(cache.get(&1), None);
+    }
+}
@@ -0,0 +1,184 @@
+// Copyright 2019 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Periodic health checks of the Bitcoin relay.
+
+use serde_derive::{Deserialize, Serialize};
+
+use std::{
+    fmt::Display,
+    time::{Duration, Instant},
+};
+
+use crate::blockchain::ReportRelayStatus;
+
+/// Health of the Bitcoin relay determined by the periodic health checks.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RelayStatus {
+    /// Whether the latest health check of the relay has succeeded.
+    pub healthy: bool,
+    /// Height of the latest Bitcoin block known to the relay, if any.
+    pub block_height: Option<u64>,
+    /// Number of the consecutive failed health checks.
+    pub failed_checks: u32,
+    /// Error of the latest failed health check.
+    pub last_error: Option<String>,
+}
+
+impl Default for RelayStatus {
+    fn default() -> Self {
+        Self {
+            healthy: true,
+            block_height: None,
+            failed_checks: 0,
+            last_error: None,
+        }
+    }
+}
+
+impl RelayStatus {
+    /// Updates the status with the result of the health check, that is, the height
+    /// of the latest Bitcoin block known to the relay or the relay error.
+    pub fn update<E: Display>(&mut self, result: Result<Option<u64>, E>) {
+        match result {
+            Ok(block_height) => {
+                self.healthy = true;
+                self.block_height = block_height.or(self.block_height);
+                self.failed_checks = 0;
+                self.last_error = None;
+            }
+            Err(e) => {
+                self.healthy = false;
+                self.failed_checks += 1;
+                self.last_error = Some(e.to_string());
+            }
+        }
+    }
+
+    /// Returns the report of this status for the anchoring service.
+    pub fn to_report(&self) -> ReportRelayStatus {
+        ReportRelayStatus {
+            healthy: self.healthy,
+            block_height: self.block_height.unwrap_or_default(),
+            failed_checks: self.failed_checks,
+        }
+    }
+}
+
+/// Health check state of the relay used by the sync with Bitcoin task.
+#[derive(Debug, Default)]
+pub(super) struct RelayHealth {
+    status: RelayStatus,
+    checked_at: Option<Instant>,
+    reported: Option<bool>,
+}
+
+impl RelayHealth {
+    pub(super) fn status(&self) -> &RelayStatus {
+        &self.status
+    }
+
+    /// Checks whether the health check is due. The unhealthy relay is checked on every
+    /// attempt, so its recovery is detected as soon as possible.
+    pub(super) fn is_check_due(&self, interval: Duration) -> bool {
+        !self.status.healthy
+            || self
+                .checked_at
+                .map_or(true, |checked_at| checked_at.elapsed() >= interval)
+    }
+
+    /// Records the result of the health check and logs the changes of the relay health.
+    pub(super) fn record_check<E: Display>(&mut self, result: Result<Option<u64>, E>) {
+        let was_healthy = self.status.healthy;
+        self.status.update(result);
+        self.checked_at = Some(Instant::now());
+
+        match (&self.status.last_error, was_healthy) {
+            (Some(e), true) => log::error!("Bitcoin relay is unavailable. {}", e),
+            (None, false) => log::info!("Bitcoin relay is available again."),
+            _ => {}
+        }
+    }
+
+    /// Returns the report of the relay status if the relay health has changed since
+    /// the latest report.
+    pub(super) fn pending_report(&self) -> Option<ReportRelayStatus> {
+        if self.reported == Some(self.status.healthy) {
+            None
+        } else {
+            Some(self.status.to_report())
+        }
+    }
+
+    pub(super) fn mark_reported(&mut self, healthy: bool) {
+        self.reported = Some(healthy);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{RelayHealth, RelayStatus};
+
+    #[test]
+    fn relay_status_update() {
+        let mut status = RelayStatus::default();
+        status.update::<String>(Ok(Some(100)));
+        assert_eq!(status.to_report().block_height, 100);
+
+        status.update(Err("Connection refused"));
+        status.update(Err("Connection refused"));
+        assert!(!status.healthy);
+        assert_eq!(status.failed_checks, 2);
+        // The latest known block height is kept.
+        assert_eq!(status.block_height, Some(100));
+
+        status.update::<String>(Ok(None));
+        assert_eq!(
+            status,
+            RelayStatus {
+                healthy: true,
+                block_height: Some(100),
+                failed_checks: 0,
+                last_error: None,
+            }
+        );
+    }
+
+    #[test]
+    fn relay_health_reports() {
+        let interval = Duration::from_secs(60);
+        let mut health = RelayHealth::default();
+        assert!(health.is_check_due(interval));
+        health.record_check::<String>(Ok(Some(100)));
+        assert!(!health.is_check_due(interval));
+
+        // The first status is reported.
+        let report = health.pending_report().unwrap();
+        assert!(report.healthy);
+        health.mark_reported(report.healthy);
+        assert_eq!(health.pending_report(), None);
+
+        // The unhealthy relay is checked on every attempt.
+        health.record_check(Err("Connection refused"));
+        assert!(health.is_check_due(interval));
+        let report = health.pending_report().unwrap();
+        assert!(!report.healthy);
+        assert_eq!(report.failed_checks, 1);
+        health.mark_reported(report.healthy);
+        health.record_check(Err("Connection refused"));
+        assert_eq!(health.pending_report(), None);
+    }
+}
@@ -0,0 +1,104 @@
+// Copyright 2019 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Roles of the anchoring nodes in the anchoring pipeline.
+
+use serde_derive::{Deserialize, Serialize};
+
+/// Role of the node in the anchoring pipeline.
+///
+/// Large consortia may split the anchoring duties: the signing validators keep the
+/// Bitcoin keys, but have no access to the Bitcoin network, while the dedicated
+/// broadcast nodes have the Bitcoin RPC access, but no keys.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NodeRole {
+    /// Node signs the anchoring proposals and broadcasts the anchoring transactions.
+    Full,
+    /// Node signs the anchoring proposals, but does not connect to the Bitcoin network.
+    Signer,
+    /// Node broadcasts the anchoring transactions, but does not sign the proposals.
+    Broadcaster,
+    /// Node neither signs nor broadcasts, but tracks the statuses of the anchoring
+    /// transactions in the Bitcoin network.
+    Observer,
+}
+
+impl Default for NodeRole {
+    fn default() -> Self {
+        NodeRole::Full
+    }
+}
+
+impl NodeRole {
+    /// Checks whether the node with this role signs the anchoring proposals.
+    pub fn signs(self) -> bool {
+        match self {
+            NodeRole::Full | NodeRole::Signer => true,
+            NodeRole::Broadcaster | NodeRole::Observer => false,
+        }
+    }
+
+    /// Checks whether the node with this role broadcasts the anchoring transactions.
+    pub fn broadcasts(self) -> bool {
+        match self {
+            NodeRole::Full | NodeRole::Broadcaster => true,
+            NodeRole::Signer | NodeRole::Observer => false,
+        }
+    }
+
+    /// Checks whether the node with this role needs the connection to the Bitcoin network.
+    pub fn uses_bitcoin_relay(self) -> bool {
+        self != NodeRole::Signer
+    }
+
+    /// Checks that the node with this role can perform its duties with the given resources.
+    /// The full node performs the available duties only, as the nodes before the role split.
+    pub fn validate(self, has_keys: bool, has_bitcoin_relay: bool) -> Result<(), String> {
+        if self == NodeRole::Signer && !has_keys {
+            return Err(format!(
+                "The {:?} node requires at least one Bitcoin key in the key pool",
+                self
+            ));
+        }
+        if self != NodeRole::Full && self.uses_bitcoin_relay() && !has_bitcoin_relay {
+            return Err(format!(
+                "The {:?} node requires the Bitcoin RPC configuration",
+                self
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::NodeRole;
+
+    #[test]
+    fn node_role_validation() {
+        // The full node may work without the Bitcoin relay, as before the role split.
+        assert_eq!(NodeRole::Full.validate(true, false), Ok(()));
+        assert_eq!(NodeRole::Full.validate(false, true), Ok(()));
+        assert_eq!(NodeRole::Signer.validate(true, false), Ok(()));
+        assert!(NodeRole::Signer.validate(false, true).is_err());
+        assert_eq!(NodeRole::Broadcaster.validate(false, true), Ok(()));
+        assert!(NodeRole::Broadcaster.validate(true, false).is_err());
+        assert_eq!(NodeRole::Observer.validate(false, true), Ok(()));
+        assert!(NodeRole::Observer.validate(false, false).is_err());
+
+        let role: NodeRole = serde_json::from_str("\"broadcaster\"").unwrap();
+        assert_eq!(role, NodeRole::Broadcaster);
+    }
+}
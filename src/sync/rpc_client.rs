@@ -0,0 +1,547 @@
+// Copyright 2020 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Bitcoin RPC client, which connects to the Bitcoin node via plain HTTP, or via HTTPS
+//! with the optional client certificate and the SOCKS5 proxy.
+
+use bitcoincore_rpc::{Auth, RpcApi};
+use futures::{
+    channel::mpsc::{self, UnboundedSender},
+    future::BoxFuture,
+    StreamExt,
+};
+use jsonrpc::{Error as JsonRpcError, Response};
+use reqwest::{header::CONTENT_TYPE, StatusCode, Url};
+use serde::de::DeserializeOwned;
+use serde_derive::{Deserialize, Serialize};
+use serde_json::json;
+use thiserror::Error;
+
+use std::{
+    fmt::{self, Display},
+    fs, io,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        mpsc as std_mpsc,
+    },
+    thread,
+};
+
+use super::proxy::{ProxyConfig, ProxyError};
+
+const BEGIN_CERTIFICATE: &str = "-----BEGIN CERTIFICATE-----";
+const END_CERTIFICATE: &str = "-----END CERTIFICATE-----";
+
+/// TLS settings of the connection to the Bitcoin RPC.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TlsConfig {
+    /// PEM bundle with the certificate authorities, which are trusted in addition
+    /// to the built-in ones, for example, the private CA of the proxy.
+    #[serde(default)]
+    pub ca_bundle: Option<PathBuf>,
+    /// PEM file with the client certificate chain presented to the proxy.
+    #[serde(default)]
+    pub client_certificate: Option<PathBuf>,
+    /// PEM file with the private key of the client certificate.
+    #[serde(default)]
+    pub client_key: Option<PathBuf>,
+}
+
+/// Errors of the HTTPS transport of the Bitcoin RPC client.
+#[derive(Debug, Error)]
+pub enum TlsError {
+    /// Unable to read the certificate, key or cookie file.
+    #[error("Unable to read {}: {}", path.display(), error)]
+    ReadFile {
+        /// Path to the file.
+        path: PathBuf,
+        /// Underlying error.
+        error: io::Error,
+    },
+    /// The certificate bundle contains no certificates.
+    #[error("No certificates found in {}", .0.display())]
+    NoCertificates(PathBuf),
+    /// Only one of the client certificate and its key is specified.
+    #[error("Client certificate and its key should be specified together")]
+    IncompleteIdentity,
+    /// The Bitcoin RPC URL is invalid or does not use the `https` scheme.
+    #[error("Invalid HTTPS url of the Bitcoin RPC `{url}`: {message}")]
+    InvalidUrl {
+        /// The given URL.
+        url: String,
+        /// Error message.
+        message: String,
+    },
+    /// Unable to create the HTTPS client.
+    #[error("{0}")]
+    Http(#[from] reqwest::Error),
+    /// The SOCKS5 proxy settings are invalid.
+    #[error("{0}")]
+    Proxy(#[from] ProxyError),
+    /// Unable to start the thread of the HTTPS client.
+    #[error("{0}")]
+    Io(#[from] io::Error),
+}
+
+impl TlsConfig {
+    /// Creates the HTTPS client with these settings.
+    pub fn http_client(&self) -> Result<reqwest::Client, TlsError> {
+        Ok(self.client_builder()?.build()?)
+    }
+
+    fn client_builder(&self) -> Result<reqwest::ClientBuilder, TlsError> {
+        let mut builder = reqwest::Client::builder().use_rustls_tls();
+        if let Some(path) = &self.ca_bundle {
+            for certificate in read_certificates(path)? {
+                builder = builder.add_root_certificate(certificate);
+            }
+        }
+        match (&self.client_certificate, &self.client_key) {
+            (Some(certificate), Some(key)) => {
+                // The identity is parsed from the key followed by the certificate chain.
+                let mut pem = read_file(key)?;
+                pem.extend(read_file(certificate)?);
+                builder = builder.identity(reqwest::Identity::from_pem(&pem)?);
+            }
+            (None, None) => {}
+            _ => return Err(TlsError::IncompleteIdentity),
+        }
+        Ok(builder)
+    }
+}
+
+fn read_file(path: &Path) -> Result<Vec<u8>, TlsError> {
+    fs::read(path).map_err(|error| TlsError::ReadFile {
+        path: path.to_owned(),
+        error,
+    })
+}
+
+fn read_certificates(path: &Path) -> Result<Vec<reqwest::Certificate>, TlsError> {
+    let pem = read_file(path)?;
+    let pem = String::from_utf8_lossy(&pem);
+
+    let mut certificates = Vec::new();
+    let mut rest = pem.as_ref();
+    while let Some(begin) = rest.find(BEGIN_CERTIFICATE) {
+        let end = match rest[begin..].find(END_CERTIFICATE) {
+            Some(end) => begin + end + END_CERTIFICATE.len(),
+            None => break,
+        };
+        certificates.push(reqwest::Certificate::from_pem(rest[begin..end].as_bytes())?);
+        rest = &rest[end..];
+    }
+
+    if certificates.is_empty() {
+        return Err(TlsError::NoCertificates(path.to_owned()));
+    }
+    Ok(certificates)
+}
+
+/// Converts the failure of the HTTPS transport into the I/O error of the RPC client,
+/// so it is handled as the unreachable Bitcoin node.
+fn transport_error(error: impl Display) -> bitcoincore_rpc::Error {
+    io::Error::new(io::ErrorKind::Other, error.to_string()).into()
+}
+
+/// Bitcoin RPC client.
+///
+/// The client connects to the Bitcoin node via plain HTTP by means of `bitcoincore_rpc`,
+/// or via HTTPS if the node is placed behind a TLS-terminating proxy. The HTTPS transport
+/// trusts the custom CA bundle in addition to the built-in certificate authorities,
+/// authenticates by the client certificate, if any, and may connect via the SOCKS5 proxy.
+///
+/// The client implements [`RpcApi`], so it is used the same way as the client
+/// of `bitcoincore_rpc`. The requests are blocking, see [`BitcoinRpcRelay`] for the relay
+/// performing them on the thread pool.
+///
+/// [`RpcApi`]: https://docs.rs/bitcoincore-rpc/0.9.0/bitcoincore_rpc/trait.RpcApi.html
+/// [`BitcoinRpcRelay`]: struct.BitcoinRpcRelay.html
+pub struct BitcoinRpcClient {
+    url: String,
+    transport: Transport,
+}
+
+enum Transport {
+    Http(bitcoincore_rpc::Client),
+    Https(HttpsTransport),
+}
+
+impl fmt::Debug for BitcoinRpcClient {
+    // The credentials and the transport internals are not displayed.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BitcoinRpcClient")
+            .field("url", &self.url)
+            .finish()
+    }
+}
+
+impl BitcoinRpcClient {
+    /// Creates a client connecting to the Bitcoin node with the given url via plain HTTP.
+    pub fn new(url: String, auth: Auth) -> bitcoincore_rpc::Result<Self> {
+        let client = bitcoincore_rpc::Client::new(url.clone(), auth)?;
+        Ok(Self {
+            url,
+            transport: Transport::Http(client),
+        })
+    }
+
+    /// Creates a client connecting to the Bitcoin node with the given url via HTTPS with
+    /// the optional TLS settings, or via the SOCKS5 proxy. The `https` scheme is required
+    /// if the TLS settings are specified.
+    ///
+    /// The cookie file, if any, is read once, so the client should be recreated after
+    /// the cookie is changed by the restart of the Bitcoin node.
+    pub fn with_transport(
+        url: &str,
+        auth: Auth,
+        tls: Option<&TlsConfig>,
+        proxy: Option<&ProxyConfig>,
+    ) -> Result<Self, TlsError> {
+        let invalid_url = |message: String| TlsError::InvalidUrl {
+            url: url.to_owned(),
+            message,
+        };
+        let parsed_url = Url::parse(url).map_err(|e| invalid_url(e.to_string()))?;
+        if tls.is_some() && parsed_url.scheme() != "https" {
+            return Err(invalid_url("the `https` scheme is required".to_owned()));
+        }
+
+        let mut builder = match tls {
+            Some(config) => config.client_builder()?,
+            None => reqwest::Client::builder(),
+        };
+        if let Some(proxy) = proxy {
+            builder = builder.proxy(proxy.http_proxy()?);
+        }
+        let credentials = match auth {
+            Auth::None => None,
+            Auth::UserPass(user, password) => Some((user, password)),
+            Auth::CookieFile(path) => {
+                let cookie = read_file(&path)?;
+                let cookie = String::from_utf8_lossy(&cookie);
+                let mut parts = cookie.trim().splitn(2, ':');
+                let user = parts.next().unwrap_or_default().to_owned();
+                Some((user, parts.next().unwrap_or_default().to_owned()))
+            }
+        };
+
+        let transport = HttpsTransport {
+            client: builder.build()?,
+            url: parsed_url,
+            credentials,
+            requests: spawn_runtime()?,
+            nonce: AtomicU64::default(),
+        };
+        Ok(Self {
+            url: url.to_owned(),
+            transport: Transport::Https(transport),
+        })
+    }
+
+    /// Returns the url of the Bitcoin node.
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Performs the calls of the given method with each of the given parameters in a single
+    /// JSON-RPC batch request. Returns the results in the order of the parameters.
+    pub(super) fn batch_call(
+        &self,
+        method: &str,
+        params: &[Vec<serde_json::Value>],
+    ) -> Result<Vec<Result<serde_json::Value, JsonRpcError>>, bitcoincore_rpc::Error> {
+        // The JSON-RPC client refuses to send empty batches.
+        if params.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let responses = match &self.transport {
+            Transport::Http(client) => {
+                let client = client.get_jsonrpc_client();
+                let requests = params
+                    .iter()
+                    .map(|params| client.build_request(method, params))
+                    .collect::<Vec<_>>();
+                client.send_batch(&requests)?
+            }
+            Transport::Https(transport) => {
+                let requests = params
+                    .iter()
+                    .map(|params| transport.request(method, params))
+                    .collect::<Vec<_>>();
+                let mut responses: Vec<Response> =
+                    transport.send(&serde_json::Value::Array(requests.clone()))?;
+                // The responses of the batch may be returned in any order.
+                requests
+                    .iter()
+                    .map(|request| {
+                        let position = responses.iter().position(|r| r.id == request["id"])?;
+                        Some(responses.swap_remove(position))
+                    })
+                    .collect()
+            }
+        };
+        Ok(responses
+            .into_iter()
+            .map(|response| match response {
+                Some(response) => response.result::<serde_json::Value>(),
+                None => Err(JsonRpcError::NoErrorOrResult),
+            })
+            .collect())
+    }
+}
+
+impl RpcApi for BitcoinRpcClient {
+    fn call<T: for<'a> serde::de::Deserialize<'a>>(
+        &self,
+        cmd: &str,
+        args: &[serde_json::Value],
+    ) -> bitcoincore_rpc::Result<T> {
+        match &self.transport {
+            Transport::Http(client) => client.call(cmd, args),
+            Transport::Https(transport) => {
+                let response: Response = transport.send(&transport.request(cmd, args))?;
+                Ok(response.result::<T>()?)
+            }
+        }
+    }
+}
+
+/// Starts the thread with the runtime, which performs the requests of the HTTPS transport.
+/// The thread is stopped once the returned sender is dropped.
+fn spawn_runtime() -> io::Result<UnboundedSender<BoxFuture<'static, ()>>> {
+    let (sender, mut receiver) = mpsc::unbounded::<BoxFuture<'static, ()>>();
+    let mut runtime = tokio::runtime::Builder::new()
+        .basic_scheduler()
+        .enable_all()
+        .build()?;
+    thread::Builder::new()
+        .name("bitcoin-rpc-https".to_owned())
+        .spawn(move || {
+            runtime.block_on(async move {
+                while let Some(request) = receiver.next().await {
+                    tokio::spawn(request);
+                }
+            })
+        })?;
+    Ok(sender)
+}
+
+/// HTTPS transport of the JSON-RPC requests.
+///
+/// The requests are performed by the runtime on the dedicated thread, so the blocking calls
+/// of the client are allowed both in the synchronous code and on the thread pool of another
+/// runtime.
+struct HttpsTransport {
+    client: reqwest::Client,
+    url: Url,
+    credentials: Option<(String, String)>,
+    requests: UnboundedSender<BoxFuture<'static, ()>>,
+    nonce: AtomicU64,
+}
+
+impl HttpsTransport {
+    fn request(&self, method: &str, params: &[serde_json::Value]) -> serde_json::Value {
+        let id = self.nonce.fetch_add(1, Ordering::Relaxed);
+        json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": method,
+            "params": params,
+        })
+    }
+
+    /// Sends the given JSON-RPC request or batch and waits for the response.
+    fn send<R: DeserializeOwned>(&self, body: &serde_json::Value) -> bitcoincore_rpc::Result<R> {
+        let mut request = self
+            .client
+            .post(self.url.clone())
+            .header(CONTENT_TYPE, "application/json")
+            .body(serde_json::to_vec(body)?);
+        if let Some((user, password)) = &self.credentials {
+            request = request.basic_auth(user, Some(password));
+        }
+        let request = request.build().map_err(transport_error)?;
+
+        let (sender, receiver) = std_mpsc::channel();
+        let client = self.client.clone();
+        self.requests
+            .unbounded_send(Box::pin(async move {
+                let response = async {
+                    let response = client.execute(request).await?;
+                    let status = response.status();
+                    Ok::<_, reqwest::Error>((status, response.bytes().await?))
+                };
+                // The caller may have gone, for example, due to the expired deadline.
+                sender.send(response.await).ok();
+            }))
+            .map_err(transport_error)?;
+        let (status, body) = receiver
+            .recv()
+            .map_err(transport_error)?
+            .map_err(transport_error)?;
+
+        // The Bitcoin node returns the errors of the methods with the error status and
+        // the JSON-RPC error in the body.
+        serde_json::from_slice(&body).map_err(|e| {
+            if status == StatusCode::OK {
+                e.into()
+            } else {
+                transport_error(format!("Bitcoin RPC has responded with {}", status))
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoincore_rpc::{Auth, RpcApi};
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+    use tokio_rustls::{
+        rustls::{internal::pemfile, AllowAnyAuthenticatedClient, RootCertStore, ServerConfig},
+        TlsAcceptor,
+    };
+
+    use std::{fs, path::PathBuf, sync::Arc};
+
+    use super::*;
+
+    // The certificates of the test CA, the server (`localhost`) and the client are issued
+    // for 100 years with the P-256 keys.
+    fn fixture(name: &str) -> PathBuf {
+        [env!("CARGO_MANIFEST_DIR"), "tests", "data", "tls", name]
+            .iter()
+            .collect()
+    }
+
+    fn tls_config(with_identity: bool) -> TlsConfig {
+        TlsConfig {
+            ca_bundle: Some(fixture("ca.pem")),
+            client_certificate: Some(fixture("client.pem")).filter(|_| with_identity),
+            client_key: Some(fixture("client.key")).filter(|_| with_identity),
+        }
+    }
+
+    /// Starts the HTTPS server, which requires the client certificate issued by the test CA
+    /// and answers each request with the given JSON-RPC result. Returns the server port.
+    async fn spawn_rpc_server(result: serde_json::Value) -> u16 {
+        let mut roots = RootCertStore::empty();
+        roots
+            .add_pem_file(&mut fs::read(fixture("ca.pem")).unwrap().as_slice())
+            .unwrap();
+        let mut config = ServerConfig::new(AllowAnyAuthenticatedClient::new(roots));
+        let certificates =
+            pemfile::certs(&mut fs::read(fixture("server.pem")).unwrap().as_slice()).unwrap();
+        let mut keys =
+            pemfile::pkcs8_private_keys(&mut fs::read(fixture("server.key")).unwrap().as_slice())
+                .unwrap();
+        config
+            .set_single_cert(certificates, keys.remove(0))
+            .unwrap();
+        let acceptor = TlsAcceptor::from(Arc::new(config));
+
+        let mut listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                let mut stream = match acceptor.accept(stream).await {
+                    Ok(stream) => stream,
+                    // The clients without the certificate are rejected by the handshake.
+                    Err(_) => continue,
+                };
+                // The request fits into a single read in the tests.
+                let mut request = vec![0_u8; 64 * 1024];
+                let len = stream.read(&mut request).await.unwrap();
+                let request = String::from_utf8_lossy(&request[..len]).into_owned();
+                assert!(request
+                    .to_ascii_lowercase()
+                    .contains("authorization: basic dxnlcjpwyxnz"));
+                let id = request
+                    .split("\"id\":")
+                    .nth(1)
+                    .and_then(|rest| rest.split(|c| c == ',' || c == '}').next())
+                    .unwrap()
+                    .to_owned();
+
+                let body = format!("{{\"result\":{},\"error\":null,\"id\":{}}}", result, id);
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\n\
+                     Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                );
+                stream.write_all(response.as_bytes()).await.unwrap();
+                stream.shutdown().await.ok();
+            }
+        });
+        port
+    }
+
+    #[test]
+    fn incomplete_client_identity() {
+        let config = TlsConfig {
+            client_certificate: Some("client.pem".into()),
+            ..TlsConfig::default()
+        };
+        assert!(matches!(
+            config.http_client(),
+            Err(TlsError::IncompleteIdentity)
+        ));
+    }
+
+    #[test]
+    fn https_scheme_is_required() {
+        let result = BitcoinRpcClient::with_transport(
+            "http://127.0.0.1:8332",
+            Auth::None,
+            Some(&tls_config(true)),
+            None,
+        );
+        assert!(matches!(result, Err(TlsError::InvalidUrl { .. })));
+    }
+
+    #[tokio::test]
+    async fn https_request_with_client_certificate() {
+        let port = spawn_rpc_server(json!({ "blocks": 42 })).await;
+        let url = format!("https://localhost:{}/wallet/anchoring", port);
+        let auth = || Auth::UserPass("user".to_owned(), "pass".to_owned());
+
+        let client =
+            BitcoinRpcClient::with_transport(&url, auth(), Some(&tls_config(true)), None).unwrap();
+        let info = tokio::task::spawn_blocking(move || {
+            client.call::<serde_json::Value>("getblockchaininfo", &[])
+        })
+        .await
+        .unwrap()
+        .unwrap();
+        assert_eq!(info["blocks"], 42);
+
+        // The server rejects the clients without the certificate.
+        let client =
+            BitcoinRpcClient::with_transport(&url, auth(), Some(&tls_config(false)), None).unwrap();
+        let result = tokio::task::spawn_blocking(move || {
+            client.call::<serde_json::Value>("getblockchaininfo", &[])
+        })
+        .await
+        .unwrap();
+        assert!(matches!(result, Err(bitcoincore_rpc::Error::Io(_))));
+    }
+}
@@ -0,0 +1,69 @@
+// Copyright 2019 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Graceful shutdown of the sync utility tasks.
+
+use futures::{future, task::AtomicWaker};
+
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    task::Poll,
+};
+
+#[derive(Debug, Default)]
+struct ShutdownState {
+    requested: AtomicBool,
+    waker: AtomicWaker,
+}
+
+/// Signal which asks the sync tasks to shut down.
+///
+/// Once the shutdown is requested, the tasks do not start new operations, such as
+/// broadcasting of the anchoring transactions or submitting of the input signatures,
+/// but the operations in progress are completed and recorded. Operations which have
+/// not been started are performed after the restart, since their state is kept by
+/// the anchoring service. The signal can be cloned and requested from another task,
+/// for example, from the OS signal handler.
+#[derive(Debug, Clone, Default)]
+pub struct ShutdownSignal(Arc<ShutdownState>);
+
+impl ShutdownSignal {
+    /// Requests the shutdown.
+    pub fn request(&self) {
+        self.0.requested.store(true, Ordering::SeqCst);
+        self.0.waker.wake();
+    }
+
+    /// Checks whether the shutdown has been requested.
+    pub fn is_requested(&self) -> bool {
+        self.0.requested.load(Ordering::SeqCst)
+    }
+
+    /// Waits until the shutdown is requested. Only one task at a time may wait
+    /// for the signal.
+    pub async fn wait(&self) {
+        future::poll_fn(|cx| {
+            self.0.waker.register(cx.waker());
+            if self.is_requested() {
+                Poll::Ready(())
+            } else {
+                Poll::Pending
+            }
+        })
+        .await
+    }
+}
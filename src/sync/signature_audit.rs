@@ -0,0 +1,100 @@
+// Copyright 2020 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Audit of the signatures of the recorded anchoring transactions.
+
+use serde_derive::{Deserialize, Serialize};
+
+use crate::{
+    blockchain::{audit::MAX_AUDITED_TRANSACTIONS, replay::ReplayMismatch},
+    client::ApiClient,
+};
+
+/// Verification policy of the signatures of the recorded anchoring transactions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SignatureVerification {
+    /// Signatures are verified only by the service when they are submitted.
+    Disabled,
+    /// Every signature of every anchoring transaction is re-verified against the outputs
+    /// spent by the transaction, as the Bitcoin nodes do. Intended for the observer and
+    /// auditing nodes.
+    Full,
+}
+
+impl Default for SignatureVerification {
+    fn default() -> Self {
+        SignatureVerification::Disabled
+    }
+}
+
+/// Task, which verifies the signatures of the anchoring transactions recorded since
+/// its previous run via the public API of the anchoring service.
+///
+/// The first run verifies the whole anchoring chain.
+#[derive(Debug)]
+pub struct SignatureAuditTask {
+    client: ApiClient,
+    next_index: u64,
+}
+
+impl SignatureAuditTask {
+    /// Creates a new signature audit task instance.
+    pub fn new(client: ApiClient) -> Self {
+        Self {
+            client,
+            next_index: 0,
+        }
+    }
+
+    /// Returns the position of the first anchoring transaction, which has not been
+    /// audited yet.
+    pub fn next_index(&self) -> u64 {
+        self.next_index
+    }
+
+    /// Verifies the signatures of the anchoring transactions, which have not been audited
+    /// yet. Returns the transactions with the invalid signatures.
+    pub async fn process(&mut self) -> Result<Vec<ReplayMismatch>, reqwest::Error> {
+        let mut mismatches = Vec::new();
+        loop {
+            let report = self
+                .client
+                .get_signature_audit(Some(self.next_index))
+                .await?;
+            for mismatch in &report.mismatches {
+                log::error!(
+                    "Anchoring transaction {} with index {} has invalid signatures: {}",
+                    mismatch.txid,
+                    mismatch.index,
+                    mismatch.reason
+                );
+            }
+            log::trace!(
+                "Verified {} signatures of the anchoring transactions from {} to {}",
+                report.verified_signatures,
+                report.from,
+                report.to
+            );
+
+            mismatches.extend(report.mismatches);
+            self.next_index = report.to;
+            // The audit of a single request is limited, so the long chains are audited
+            // in several requests.
+            if report.to - report.from < MAX_AUDITED_TRANSACTIONS {
+                return Ok(mismatches);
+            }
+        }
+    }
+}
@@ -0,0 +1,92 @@
+// Copyright 2019 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Local record of the anchoring proposal inputs signed by the node.
+
+use serde_derive::{Deserialize, Serialize};
+
+use std::{
+    collections::BTreeSet,
+    fs,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use crate::btc;
+
+/// Inputs of the anchoring proposal, which signatures have been submitted by the node.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SignedInputs {
+    /// Identifier of the latest signed anchoring proposal.
+    pub txid: Option<btc::Sha256d>,
+    /// Indexes of the signed inputs of the proposal.
+    pub inputs: BTreeSet<u32>,
+}
+
+/// Marker of the latest signed anchoring proposal persisted in the local file.
+///
+/// The anchoring chain update task with the marker does not submit the signatures
+/// recorded in the marker once again, even after the restart of the node. Remove
+/// the marker file to make the node resubmit its signatures.
+#[derive(Debug)]
+pub struct SigningMarker {
+    path: PathBuf,
+    state: Mutex<SignedInputs>,
+}
+
+impl SigningMarker {
+    /// Opens the marker stored in the given file. The marker is empty if the file
+    /// does not exist.
+    pub fn open(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref().to_owned();
+        let state = if path.exists() {
+            serde_json::from_str(&fs::read_to_string(&path)?)?
+        } else {
+            SignedInputs::default()
+        };
+        Ok(Self {
+            path,
+            state: Mutex::new(state),
+        })
+    }
+
+    /// Returns the inputs of the given proposal, which signatures have been submitted.
+    pub fn signed_inputs(&self, txid: btc::Sha256d) -> BTreeSet<u32> {
+        let state = self.state.lock().unwrap();
+        if state.txid == Some(txid) {
+            state.inputs.clone()
+        } else {
+            BTreeSet::new()
+        }
+    }
+
+    /// Records the submitted signature of the given proposal input. Inputs of the previous
+    /// proposals are forgotten.
+    pub fn record(&self, txid: btc::Sha256d, input: u32) -> anyhow::Result<()> {
+        let mut state = self.state.lock().unwrap();
+        if state.txid != Some(txid) {
+            *state = SignedInputs {
+                txid: Some(txid),
+                inputs: BTreeSet::new(),
+            };
+        }
+        state.inputs.insert(input);
+
+        // Replace the file atomically to not corrupt the marker on crash.
+        let tmp_path = self.path.with_extension("tmp");
+        fs::write(&tmp_path, serde_json::to_string(&*state)?)?;
+        fs::rename(tmp_path, &self.path)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,97 @@
+// Copyright 2019 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Validator-local checks performed before signing the anchoring proposals.
+
+use anyhow::ensure;
+use async_trait::async_trait;
+use exonum::helpers::Height;
+
+use std::fmt::Debug;
+
+use crate::{btc, config::Config};
+
+/// Anchoring transaction proposal which is going to be signed by the node.
+#[derive(Debug, Clone, Copy)]
+pub struct SigningRequest<'a> {
+    /// Actual anchoring configuration.
+    pub config: &'a Config,
+    /// Anchoring transaction proposal.
+    pub proposal: &'a btc::Transaction,
+    /// Transactions spent by the proposal inputs.
+    pub inputs: &'a [btc::Transaction],
+    /// Blockchain height anchored by the proposal.
+    pub anchoring_height: Height,
+    /// Anchoring epoch of the proposal.
+    pub epoch: u64,
+}
+
+impl SigningRequest<'_> {
+    /// Returns the total amount of the outputs spent by the proposal.
+    pub fn balance(&self) -> u64 {
+        self.proposal
+            .0
+            .input
+            .iter()
+            .zip(self.inputs)
+            .map(|(input, tx)| tx.0.output[input.previous_output.vout as usize].value)
+            .sum()
+    }
+
+    /// Returns the amount which remains in the anchoring wallet after the proposal.
+    pub fn remaining(&self) -> u64 {
+        self.proposal.unspent_value().unwrap_or_default()
+    }
+
+    /// Returns the total fee paid by the proposal.
+    pub fn total_fee(&self) -> u64 {
+        self.balance().saturating_sub(self.remaining())
+    }
+}
+
+/// Custom check which the node performs before contributing its signatures
+/// to the anchoring transaction proposal.
+///
+/// Policies are local for the node and are not a part of the consensus. If a policy
+/// refuses to sign the proposal, the node does not send its signatures, and the anchoring
+/// chain update task reports the [refusal].
+///
+/// [refusal]: enum.ChainUpdateError.html#variant.SigningRefused
+#[async_trait]
+pub trait SigningPolicy: Debug + Send + Sync {
+    /// Checks the anchoring transaction proposal. Returns the reason of the refusal
+    /// if the proposal must not be signed.
+    async fn check(&self, request: &SigningRequest<'_>) -> anyhow::Result<()>;
+}
+
+/// Refuses to sign proposals which pay more than the given total fee in satoshis.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MaxFeePolicy {
+    /// Maximum total fee in satoshis.
+    pub max_fee: u64,
+}
+
+#[async_trait]
+impl SigningPolicy for MaxFeePolicy {
+    async fn check(&self, request: &SigningRequest<'_>) -> anyhow::Result<()> {
+        let total_fee = request.total_fee();
+        ensure!(
+            total_fee <= self.max_fee,
+            "Total fee {} exceeds the maximum allowed fee {}",
+            total_fee,
+            self.max_fee
+        );
+        Ok(())
+    }
+}
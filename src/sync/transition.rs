@@ -0,0 +1,199 @@
+// Copyright 2019 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Scheduling of the transition transactions to the low-fee windows.
+
+use anyhow::anyhow;
+use async_trait::async_trait;
+use serde_derive::{Deserialize, Serialize};
+
+use std::{
+    fmt::Debug,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use crate::btc;
+
+use super::{bitcoin_relay::estimate_smart_fee, BitcoinRpcClient};
+
+/// Confirmation target in blocks used to estimate the fee rate.
+const FEE_ESTIMATION_TARGET: u16 = 6;
+
+/// Source of the actual Bitcoin fee rate.
+#[async_trait]
+pub trait FeeOracle: Debug + Send + Sync {
+    /// Returns the estimated fee rate in satoshis per virtual byte.
+    async fn fee_rate(&self) -> anyhow::Result<u64>;
+}
+
+#[async_trait]
+impl FeeOracle for BitcoinRpcClient {
+    async fn fee_rate(&self) -> anyhow::Result<u64> {
+        estimate_smart_fee(self, FEE_ESTIMATION_TARGET)?
+            .ok_or_else(|| anyhow!("Fee rate is not available"))
+    }
+}
+
+/// Schedule of broadcasting the transition transactions, which transfer the anchoring
+/// funds to the address of the following configuration.
+///
+/// Transition transactions are large and not time-critical, so they may wait until the fee
+/// rate falls under the threshold, but not longer than the deadline.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TransitionSchedule {
+    /// Maximal fee rate in satoshis per virtual byte, at which the transition
+    /// transaction is broadcast.
+    pub max_fee_rate: u64,
+    /// Maximal delay of the transition transaction broadcasting in seconds.
+    pub deadline_secs: u64,
+}
+
+impl TransitionSchedule {
+    /// Returns the maximal delay of the transition transaction broadcasting.
+    pub fn deadline(&self) -> Duration {
+        Duration::from_secs(self.deadline_secs)
+    }
+}
+
+/// Decides when the transition transactions should be broadcast according to
+/// the transition schedule and the actual fee rate.
+#[derive(Debug)]
+pub(crate) struct TransitionScheduler {
+    schedule: TransitionSchedule,
+    fee_oracle: Box<dyn FeeOracle>,
+    deferred: Mutex<Option<(btc::Sha256d, Instant)>>,
+}
+
+impl TransitionScheduler {
+    pub(crate) fn new(schedule: TransitionSchedule, fee_oracle: Box<dyn FeeOracle>) -> Self {
+        Self {
+            schedule,
+            fee_oracle,
+            deferred: Mutex::default(),
+        }
+    }
+
+    /// Checks whether the transition transaction with the given identifier should be
+    /// broadcast right now. The deadline is counted from the first check of the transaction.
+    pub(crate) async fn should_broadcast(&self, txid: btc::Sha256d) -> bool {
+        let deferred_since = {
+            let mut deferred = self.deferred.lock().unwrap();
+            match *deferred {
+                Some((deferred_txid, since)) if deferred_txid == txid => since,
+                _ => {
+                    let now = Instant::now();
+                    *deferred = Some((txid, now));
+                    now
+                }
+            }
+        };
+
+        if deferred_since.elapsed() >= self.schedule.deadline() {
+            log::warn!(
+                "Transition transaction {} has reached the deadline, broadcasting it \
+                 regardless of the fee rate",
+                txid
+            );
+            return true;
+        }
+
+        match self.fee_oracle.fee_rate().await {
+            Ok(fee_rate) if fee_rate <= self.schedule.max_fee_rate => {
+                log::info!(
+                    "Fee rate {} sat/vB is under the threshold, broadcasting transition \
+                     transaction {}",
+                    fee_rate,
+                    txid
+                );
+                true
+            }
+            Ok(fee_rate) => {
+                log::info!(
+                    "Transition transaction {} is deferred, fee rate {} sat/vB exceeds \
+                     the threshold {} sat/vB",
+                    txid,
+                    fee_rate,
+                    self.schedule.max_fee_rate
+                );
+                false
+            }
+            // The deadline limits the delay even if the fee rate is never available.
+            Err(e) => {
+                log::warn!(
+                    "Unable to estimate the fee rate, transition transaction {} is deferred. {}",
+                    txid,
+                    e
+                );
+                false
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use async_trait::async_trait;
+    use bitcoin_hashes::{sha256d, Hash as BitcoinHash};
+
+    use std::sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    };
+
+    use super::{FeeOracle, TransitionSchedule, TransitionScheduler};
+    use crate::btc;
+
+    #[derive(Debug, Default)]
+    struct FixedFeeOracle(Arc<AtomicU64>);
+
+    #[async_trait]
+    impl FeeOracle for FixedFeeOracle {
+        async fn fee_rate(&self) -> anyhow::Result<u64> {
+            Ok(self.0.load(Ordering::SeqCst))
+        }
+    }
+
+    #[tokio::test]
+    async fn transition_deferred_until_low_fee() {
+        let fee_rate = Arc::new(AtomicU64::new(50));
+        let scheduler = TransitionScheduler::new(
+            TransitionSchedule {
+                max_fee_rate: 10,
+                deadline_secs: 3600,
+            },
+            Box::new(FixedFeeOracle(fee_rate.clone())),
+        );
+        let txid = btc::Sha256d(sha256d::Hash::hash(&[1]));
+
+        assert!(!scheduler.should_broadcast(txid).await);
+        fee_rate.store(10, Ordering::SeqCst);
+        assert!(scheduler.should_broadcast(txid).await);
+    }
+
+    #[tokio::test]
+    async fn transition_broadcast_after_deadline() {
+        let scheduler = TransitionScheduler::new(
+            TransitionSchedule {
+                max_fee_rate: 10,
+                deadline_secs: 0,
+            },
+            Box::new(FixedFeeOracle(Arc::new(AtomicU64::new(50)))),
+        );
+        let txid = btc::Sha256d(sha256d::Hash::hash(&[1]));
+
+        assert!(scheduler.should_broadcast(txid).await);
+    }
+}
@@ -0,0 +1,288 @@
+// Copyright 2019 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Subscription to the Bitcoin node notifications via ZMQ.
+
+use bitcoin::{consensus::encode, Block};
+use thiserror::Error;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+};
+
+use std::fmt;
+
+use crate::btc;
+
+/// Flag of the frame, which is followed by other frames of the same message.
+const FLAG_MORE: u8 = 0x01;
+/// Flag of the frame with the 8-byte size.
+const FLAG_LONG: u8 = 0x02;
+/// Flag of the command frame.
+const FLAG_COMMAND: u8 = 0x04;
+/// Maximal size of the frame, which is accepted from the Bitcoin node.
+const MAX_FRAME_SIZE: u64 = 32 * 1024 * 1024;
+
+/// Topics of the Bitcoin node notifications.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ZmqTopic {
+    /// Serialized blocks connected to the Bitcoin blockchain.
+    RawBlock,
+    /// Serialized transactions accepted to the mempool or connected in the blocks.
+    RawTx,
+}
+
+impl ZmqTopic {
+    /// Returns the name of the topic used by the Bitcoin node.
+    pub fn name(self) -> &'static str {
+        match self {
+            ZmqTopic::RawBlock => "rawblock",
+            ZmqTopic::RawTx => "rawtx",
+        }
+    }
+}
+
+impl fmt::Display for ZmqTopic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Notification published by the Bitcoin node.
+#[derive(Debug, Clone, PartialEq)]
+pub enum BitcoinNotification {
+    /// New block has been connected to the Bitcoin blockchain.
+    Block(Block),
+    /// Transaction has been accepted to the mempool or connected in the block.
+    Transaction(btc::Transaction),
+}
+
+impl BitcoinNotification {
+    /// Checks whether the notification may affect the anchoring, that is, it is a new
+    /// block, which changes the confirmations of the anchoring transactions, or
+    /// a transaction with the anchoring payload.
+    pub fn is_relevant(&self) -> bool {
+        match self {
+            BitcoinNotification::Block(_) => true,
+            BitcoinNotification::Transaction(transaction) => {
+                transaction.anchoring_payload().is_some()
+            }
+        }
+    }
+}
+
+/// Errors of the ZMQ subscription.
+#[derive(Debug, Error)]
+pub enum ZmqError {
+    /// Unable to communicate with the Bitcoin node.
+    #[error("{0}")]
+    Io(#[from] std::io::Error),
+    /// Bitcoin node has violated the ZMTP protocol.
+    #[error("ZMTP protocol violation: {0}")]
+    Protocol(String),
+    /// Unable to decode the notification body.
+    #[error("Unable to decode the {topic} notification: {error}")]
+    Decode {
+        /// Topic of the notification.
+        topic: ZmqTopic,
+        /// Decoding error.
+        error: encode::Error,
+    },
+}
+
+/// Subscriber to the ZMQ notifications of the Bitcoin node (`-zmqpubrawblock` and
+/// `-zmqpubrawtx` options), which allows the sync utility to react to the new Bitcoin
+/// blocks and anchoring transactions immediately instead of waiting for the next poll.
+///
+/// The subscriber implements the minimal subset of the ZMTP 3.0 protocol required for
+/// the `SUB` socket with the `NULL` security mechanism, which is used by the Bitcoin node.
+#[derive(Debug)]
+pub struct ZmqSubscriber {
+    stream: TcpStream,
+    topics: Vec<ZmqTopic>,
+}
+
+impl ZmqSubscriber {
+    /// Connects to the ZMQ publisher of the Bitcoin node with the given address,
+    /// for example, `127.0.0.1:28332`, and subscribes to the given topics.
+    pub async fn connect(address: &str, topics: &[ZmqTopic]) -> Result<Self, ZmqError> {
+        let mut stream = TcpStream::connect(address).await?;
+        stream.write_all(&greeting()).await?;
+        let mut peer_greeting = [0_u8; 64];
+        stream.read_exact(&mut peer_greeting).await?;
+        if peer_greeting[0] != 0xFF || peer_greeting[9] != 0x7F || peer_greeting[10] < 3 {
+            return Err(ZmqError::Protocol(
+                "Peer does not support ZMTP 3.0".to_owned(),
+            ));
+        }
+
+        stream.write_all(&ready_command()).await?;
+        let mut subscriber = Self {
+            stream,
+            topics: topics.to_vec(),
+        };
+        let (is_command, body) = subscriber.read_frame().await?;
+        if !is_command || !body.starts_with(b"\x05READY") {
+            return Err(ZmqError::Protocol(
+                "Peer has not sent the READY command".to_owned(),
+            ));
+        }
+
+        // ZMTP 3.0 subscriptions are the messages starting with the `0x01` byte.
+        for topic in topics {
+            let mut body = vec![0x01];
+            body.extend_from_slice(topic.name().as_bytes());
+            subscriber.write_frame(0, &body).await?;
+        }
+        Ok(subscriber)
+    }
+
+    /// Waits for the next notification of the subscribed topics.
+    pub async fn next(&mut self) -> Result<BitcoinNotification, ZmqError> {
+        loop {
+            let message = self.read_message().await?;
+            // Notifications consist of the topic, the body and the sequence number.
+            let (topic, body) = match message.as_slice() {
+                [topic, body, ..] => (topic, body),
+                _ => continue,
+            };
+            let topic = match self
+                .topics
+                .iter()
+                .find(|known| known.name().as_bytes() == topic.as_slice())
+            {
+                Some(topic) => *topic,
+                None => continue,
+            };
+
+            let notification = match topic {
+                ZmqTopic::RawBlock => encode::deserialize(body).map(BitcoinNotification::Block),
+                ZmqTopic::RawTx => encode::deserialize::<bitcoin::Transaction>(body)
+                    .map(|tx| BitcoinNotification::Transaction(tx.into())),
+            };
+            return notification.map_err(|error| ZmqError::Decode { topic, error });
+        }
+    }
+
+    /// Reads the next message skipping the commands.
+    async fn read_message(&mut self) -> Result<Vec<Vec<u8>>, ZmqError> {
+        let mut frames = Vec::new();
+        loop {
+            let flags = self.stream.read_u8().await?;
+            let body = self.read_frame_body(flags).await?;
+            if flags & FLAG_COMMAND != 0 {
+                continue;
+            }
+            frames.push(body);
+            if flags & FLAG_MORE == 0 {
+                return Ok(frames);
+            }
+        }
+    }
+
+    /// Reads the next frame and returns whether it is a command together with its body.
+    async fn read_frame(&mut self) -> Result<(bool, Vec<u8>), ZmqError> {
+        let flags = self.stream.read_u8().await?;
+        let body = self.read_frame_body(flags).await?;
+        Ok((flags & FLAG_COMMAND != 0, body))
+    }
+
+    async fn read_frame_body(&mut self, flags: u8) -> Result<Vec<u8>, ZmqError> {
+        let size = if flags & FLAG_LONG != 0 {
+            self.stream.read_u64().await?
+        } else {
+            u64::from(self.stream.read_u8().await?)
+        };
+        if size > MAX_FRAME_SIZE {
+            return Err(ZmqError::Protocol(format!("Frame is too large: {}", size)));
+        }
+        let mut body = vec![0_u8; size as usize];
+        self.stream.read_exact(&mut body).await?;
+        Ok(body)
+    }
+
+    async fn write_frame(&mut self, flags: u8, body: &[u8]) -> Result<(), ZmqError> {
+        let mut frame = encode_frame_header(flags, body.len());
+        frame.extend_from_slice(body);
+        self.stream.write_all(&frame).await?;
+        Ok(())
+    }
+}
+
+/// Returns the ZMTP 3.0 greeting with the `NULL` security mechanism.
+fn greeting() -> [u8; 64] {
+    let mut greeting = [0_u8; 64];
+    greeting[0] = 0xFF;
+    greeting[9] = 0x7F;
+    // Protocol version.
+    greeting[10] = 3;
+    greeting[11] = 0;
+    greeting[12..16].copy_from_slice(b"NULL");
+    greeting
+}
+
+/// Returns the `READY` command frame of the `SUB` socket.
+fn ready_command() -> Vec<u8> {
+    let mut body = vec![5];
+    body.extend_from_slice(b"READY");
+    body.push(11);
+    body.extend_from_slice(b"Socket-Type");
+    body.extend_from_slice(&3_u32.to_be_bytes());
+    body.extend_from_slice(b"SUB");
+
+    let mut frame = encode_frame_header(FLAG_COMMAND, body.len());
+    frame.extend_from_slice(&body);
+    frame
+}
+
+fn encode_frame_header(flags: u8, size: usize) -> Vec<u8> {
+    if size > usize::from(u8::max_value()) {
+        let mut header = vec![flags | FLAG_LONG];
+        header.extend_from_slice(&(size as u64).to_be_bytes());
+        header
+    } else {
+        vec![flags, size as u8]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{encode_frame_header, greeting, ready_command, FLAG_COMMAND, FLAG_LONG};
+
+    #[test]
+    fn zmtp_greeting() {
+        let greeting = greeting();
+        assert_eq!(greeting.len(), 64);
+        assert_eq!(&greeting[..2], &[0xFF, 0x00]);
+        assert_eq!(&greeting[9..12], &[0x7F, 3, 0]);
+        assert_eq!(&greeting[12..17], b"NULL\0");
+        // The subscriber is a client.
+        assert_eq!(greeting[32], 0);
+    }
+
+    #[test]
+    fn zmtp_frame_headers() {
+        assert_eq!(encode_frame_header(0, 8), vec![0, 8]);
+        assert_eq!(
+            encode_frame_header(0, 300),
+            vec![FLAG_LONG, 0, 0, 0, 0, 0, 0, 1, 44]
+        );
+
+        let ready = ready_command();
+        assert_eq!(ready[0], FLAG_COMMAND);
+        assert_eq!(usize::from(ready[1]), ready.len() - 2);
+        assert_eq!(&ready[2..8], b"\x05READY");
+        assert!(ready.ends_with(b"Socket-Type\x00\x00\x00\x03SUB"));
+    }
+}
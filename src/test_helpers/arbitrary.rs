@@ -0,0 +1,383 @@
+// Copyright 2019 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Property-test generators for the anchoring data types.
+//!
+//! The generated configurations are always valid, so they may be used to check
+//! invariants like "address derivation is stable" or "payload roundtrips".
+
+use bitcoin::network::constants::Network;
+use bitcoin_hashes::{sha256d, Hash as BitcoinHash};
+use exonum::{
+    crypto::{Hash, KeyPair, Seed},
+    helpers::Height,
+};
+use proptest::{
+    arbitrary::{any, Arbitrary},
+    collection::hash_set,
+    option, prop_oneof,
+    strategy::{BoxedStrategy, Just, Strategy},
+};
+use secp256k1::{Message, Secp256k1, SecretKey};
+
+use crate::{
+    blockchain::SignInput,
+    btc::{self, Payload},
+    config::{AnchoringKeys, Config, OutputType, Tenant},
+};
+
+/// Maximum number of tenants in the generated configurations.
+const MAX_TENANTS_COUNT: usize = 4;
+
+fn secret_key() -> impl Strategy<Value = SecretKey> {
+    any::<[u8; 32]>().prop_filter_map("Invalid secret key", |bytes| {
+        SecretKey::from_slice(&bytes).ok()
+    })
+}
+
+fn bitcoin_key(secret_key: &SecretKey) -> btc::PublicKey {
+    bitcoin::PublicKey {
+        compressed: true,
+        key: secp256k1::PublicKey::from_secret_key(&Secp256k1::new(), secret_key),
+    }
+    .into()
+}
+
+fn sha256d() -> impl Strategy<Value = btc::Sha256d> {
+    any::<[u8; 32]>().prop_map(|bytes| sha256d::Hash::from_inner(bytes).into())
+}
+
+impl Arbitrary for AnchoringKeys {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_args: Self::Parameters) -> Self::Strategy {
+        (secret_key(), any::<[u8; 32]>())
+            .prop_map(|(secret_key, seed)| Self {
+                bitcoin_key: bitcoin_key(&secret_key),
+                service_key: KeyPair::from_seed(&Seed::new(seed)).public_key(),
+            })
+            .boxed()
+    }
+}
+
+impl Arbitrary for Config {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_args: Self::Parameters) -> Self::Strategy {
+        // Distinct seeds give distinct keys, so the generated keys do not contain duplicates.
+        let anchoring_keys = hash_set(any::<[u8; 32]>(), 1..=Self::MAX_NODES_COUNT)
+            .prop_filter_map("Invalid secret key", |seeds| {
+                seeds
+                    .into_iter()
+                    .map(|seed| {
+                        let secret_key = SecretKey::from_slice(&seed).ok()?;
+                        let keys = AnchoringKeys {
+                            bitcoin_key: bitcoin_key(&secret_key),
+                            service_key: KeyPair::from_seed(&Seed::new(seed)).public_key(),
+                        };
+                        Some((keys, secret_key))
+                    })
+                    .collect::<Option<Vec<_>>>()
+            });
+        let tenants = hash_set(any::<[u8; 32]>(), 0..=MAX_TENANTS_COUNT).prop_flat_map(|seeds| {
+            let count = seeds.len();
+            proptest::collection::vec(any::<u64>(), count).prop_map(move |quotas| {
+                seeds
+                    .iter()
+                    .zip(quotas)
+                    .map(|(seed, quota)| Tenant {
+                        key: KeyPair::from_seed(&Seed::new(*seed)).public_key(),
+                        quota,
+                    })
+                    .collect::<Vec<_>>()
+            })
+        });
+        let network = prop_oneof![
+            Just(Network::Bitcoin),
+            Just(Network::Testnet),
+            Just(Network::Regtest),
+        ];
+
+        (
+            network,
+            anchoring_keys,
+            1_u64..=100_000,
+            Self::MIN_TX_FEE..=1_000,
+            any::<u64>(),
+            any::<bool>(),
+            tenants,
+            any::<u64>(),
+            any::<u64>(),
+            proptest::collection::btree_set("[a-z_]{1,16}", 0..4),
+            any::<u64>(),
+            option::of(0_u64..=1_000_000),
+        )
+            .prop_map(
+                |(
+                    network,
+                    anchoring_keys,
+                    anchoring_interval,
+                    transaction_fee,
+                    start_height,
+                    anchor_only_on_state_change,
+                    tenants,
+                    reserve_balance,
+                    standby_activation_intervals,
+                    auxiliary_services,
+                    dust_threshold,
+                    max_signature_age,
+                )| {
+                    let mut config = Self {
+                        network,
+                        anchoring_keys: anchoring_keys
+                            .iter()
+                            .map(|(keys, _)| keys.clone())
+                            .collect(),
+                        anchoring_interval,
+                        transaction_fee,
+                        start_height: Height(start_height),
+                        anchor_only_on_state_change,
+                        tenants,
+                        reserve_balance,
+                        standby_activation_intervals,
+                        auxiliary_services: auxiliary_services.into_iter().collect(),
+                        dust_threshold,
+                        max_signature_age: max_signature_age
+                            .map_or(0, |age| anchoring_interval + age),
+                        key_proofs: vec![],
+                        output_type: OutputType::P2wsh,
+                        allow_address_reuse: false,
+                        external_addresses: vec![],
+                        commit_consensus_config: false,
+                        approval_window: 0,
+                        bip69_input_ordering: false,
+                    };
+                    // Every generated key is accompanied by the proof of its possession.
+                    config.key_proofs = anchoring_keys
+                        .iter()
+                        .map(|(keys, secret_key)| {
+                            let private_key = bitcoin::PrivateKey {
+                                compressed: true,
+                                network,
+                                key: *secret_key,
+                            };
+                            config.prove_key_possession(&keys.service_key, &private_key.into())
+                        })
+                        .collect();
+                    config
+                },
+            )
+            .boxed()
+    }
+}
+
+impl Arbitrary for Payload {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_args: Self::Parameters) -> Self::Strategy {
+        (
+            any::<u64>(),
+            any::<[u8; 32]>(),
+            option::of(sha256d()),
+            option::of(any::<[u8; 32]>()),
+            option::of(any::<[u8; 32]>()),
+        )
+            .prop_map(
+                |(block_height, block_hash, prev_tx_chain, auxiliary_root, config_hash)| {
+                    // The payload contains at most one of the optional fields.
+                    let auxiliary_root = auxiliary_root
+                        .filter(|_| prev_tx_chain.is_none())
+                        .map(Hash::new);
+                    let consensus_config_hash = config_hash
+                        .filter(|_| prev_tx_chain.is_none() && auxiliary_root.is_none())
+                        .map(Hash::new);
+                    Self {
+                        block_height: Height(block_height),
+                        block_hash: Hash::new(block_hash),
+                        prev_tx_chain,
+                        auxiliary_root,
+                        consensus_config_hash,
+                    }
+                },
+            )
+            .boxed()
+    }
+}
+
+impl Arbitrary for SignInput {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_args: Self::Parameters) -> Self::Strategy {
+        (
+            sha256d(),
+            any::<u32>(),
+            secret_key(),
+            any::<[u8; 32]>(),
+            any::<u64>(),
+            any::<u64>(),
+        )
+            .prop_map(
+                |(txid, input, secret_key, sighash, anchoring_height, epoch)| {
+                    let signature = Secp256k1::new()
+                        .sign(&Message::from_slice(&sighash).unwrap(), &secret_key)
+                        .serialize_der()
+                        .to_vec();
+                    let input_signature = btc_transaction_utils::InputSignature::from_bytes(
+                        // Append `SIGHASH_ALL` flag to the signature.
+                        signature.into_iter().chain(Some(1)).collect(),
+                    )
+                    .unwrap();
+                    Self {
+                        txid,
+                        input,
+                        input_signature: input_signature.into(),
+                        anchoring_height: Height(anchoring_height),
+                        epoch,
+                        signed_transaction: Vec::new(),
+                    }
+                },
+            )
+            .boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::blockdata::{
+        opcodes::all::OP_RETURN,
+        script::{Builder, Instruction, Script},
+    };
+    use exonum::{helpers::ValidateInput, merkledb::BinaryValue};
+    use proptest::{
+        arbitrary::any, collection::vec, prop_assert, prop_assert_eq, prop_assume, proptest,
+    };
+
+    use crate::{
+        blockchain::{BtcAnchoringState, SignInput},
+        btc::{payload::PayloadBuilder, Payload},
+        config::Config,
+    };
+
+    /// Returns the data pushed by the payload script.
+    fn payload_bytes(payload: &Payload) -> Vec<u8> {
+        let script = PayloadBuilder::new()
+            .block_height(payload.block_height)
+            .block_hash(payload.block_hash)
+            .prev_tx_chain(payload.prev_tx_chain)
+            .auxiliary_root(payload.auxiliary_root)
+            .consensus_config_hash(payload.consensus_config_hash)
+            .into_script();
+        match script.iter(true).nth(1) {
+            Some(Instruction::PushBytes(bytes)) => bytes.to_vec(),
+            _ => unreachable!(),
+        }
+    }
+
+    fn op_return_script(bytes: &[u8]) -> Script {
+        Builder::new()
+            .push_opcode(OP_RETURN)
+            .push_slice(bytes)
+            .into_script()
+    }
+
+    proptest! {
+        #[test]
+        fn generated_config_is_valid(config: Config) {
+            prop_assert!(config.validate().is_ok());
+            prop_assert!(config.anchoring_keys.iter().all(|keys| config.verify_key_possession(keys)));
+        }
+
+        #[test]
+        fn anchoring_address_is_stable(config: Config) {
+            let bytes = config.to_bytes();
+            let config2 = Config::from_bytes(bytes.into()).unwrap();
+            prop_assert_eq!(config.anchoring_address(), config2.anchoring_address());
+            prop_assert_eq!(config.anchoring_out_script(), config2.anchoring_out_script());
+        }
+
+        #[test]
+        fn payload_roundtrip(payload: Payload) {
+            let script = PayloadBuilder::new()
+                .block_height(payload.block_height)
+                .block_hash(payload.block_hash)
+                .prev_tx_chain(payload.prev_tx_chain)
+                .auxiliary_root(payload.auxiliary_root)
+                .consensus_config_hash(payload.consensus_config_hash)
+                .into_script();
+            prop_assert_eq!(Payload::from_script(&script), Some(payload));
+        }
+
+        #[test]
+        fn payload_parser_is_total(bytes in vec(any::<u8>(), 0..128)) {
+            // Should not panic on arbitrary scripts.
+            Payload::from_script(&Script::from(bytes.clone()));
+            Payload::from_script(&op_return_script(&bytes));
+        }
+
+        #[test]
+        fn payload_parser_rejects_unknown_versions(
+            version: u8,
+            data in vec(any::<u8>(), 0..80),
+        ) {
+            prop_assume!(version != 1);
+            let bytes = b"EXONUM"
+                .iter()
+                .copied()
+                .chain(Some(version))
+                .chain(data)
+                .collect::<Vec<_>>();
+            prop_assert_eq!(Payload::from_script(&op_return_script(&bytes)), None);
+        }
+
+        #[test]
+        fn payload_parser_rejects_truncated_payloads(payload: Payload, cut in 1_usize..80) {
+            let bytes = payload_bytes(&payload);
+            prop_assume!(cut <= bytes.len());
+            let truncated = &bytes[..bytes.len() - cut];
+            prop_assert_eq!(Payload::from_script(&op_return_script(truncated)), None);
+        }
+
+        #[test]
+        fn payload_parser_rejects_oversized_payloads(
+            payload: Payload,
+            extra in vec(any::<u8>(), 1..64),
+        ) {
+            let mut bytes = payload_bytes(&payload);
+            bytes.extend(extra);
+            prop_assert_eq!(Payload::from_script(&op_return_script(&bytes)), None);
+        }
+
+        #[test]
+        fn anchoring_state_serde(actual_configuration: Config, following_configuration: Config) {
+            let state = BtcAnchoringState::Transition {
+                actual_configuration,
+                following_configuration,
+            };
+            let json = serde_json::to_value(&state).unwrap();
+            prop_assert_eq!(&json["type"], "transition");
+            let state2: BtcAnchoringState = serde_json::from_value(json).unwrap();
+            prop_assert_eq!(state2, state);
+        }
+
+        #[test]
+        fn sign_input_roundtrip(sign_input: SignInput) {
+            let bytes = sign_input.to_bytes();
+            prop_assert_eq!(SignInput::from_bytes(bytes.into()).unwrap(), sign_input);
+        }
+    }
+}
@@ -23,7 +23,7 @@ use exonum::{
     helpers::Height,
     keys::Keys,
     messages::{AnyTx, Verified},
-    runtime::{InstanceId, SnapshotExt, SUPERVISOR_INSTANCE_ID},
+    runtime::{ErrorMatch, InstanceId, SnapshotExt, SUPERVISOR_INSTANCE_ID},
 };
 use exonum_merkledb::{access::Access, Snapshot};
 use exonum_rust_runtime::api;
@@ -35,21 +35,37 @@ use std::collections::BTreeMap;
 
 use crate::{
     api::{
-        AnchoringChainLength, AnchoringProposalState, FindTransactionQuery, IndexQuery, PrivateApi,
-        PublicApi, TransactionProof,
+        v1, AnchoringChainLength, AnchoringProposalState, FindTransactionQuery, IndexQuery,
+        PrivateApi, PublicApi, TransactionProof,
+    },
+    blockchain::{
+        errors::Error, AddFunds, BtcAnchoringInterface, ConfirmAnchorFinality, ImportAnchor,
+        RecordAnchorBlock, ReportAnchorStatus, ReportRelayStatus, Schema, SignInput,
+        SubmitAnchoringKey, UpdateParameters,
     },
-    blockchain::{AddFunds, BtcAnchoringInterface, Schema, SignInput},
     btc,
-    config::Config,
+    client::verify_transaction_proof,
+    config::{Config, KeyPossessionProof},
     proto::AnchoringKeys,
     BtcAnchoringService,
 };
 
+pub use self::simulated_relay::{SimulatedChainRelay, SimulatedRelayError};
+
+#[cfg(any(test, feature = "testing"))]
+pub mod arbitrary;
+mod simulated_relay;
+pub mod vectors;
+
 /// Default anchoring instance ID.
 pub const ANCHORING_INSTANCE_ID: InstanceId = 14;
 /// Default anchoring instance name.
 pub const ANCHORING_INSTANCE_NAME: &str = "btc_anchoring";
 
+/// Value of the funding transaction of the testkit created by
+/// [`AnchoringTestKit::new_without_rpc`](struct.AnchoringTestKit.html#method.new_without_rpc).
+const SIMULATED_FUNDING_VALUE: u64 = 700_000;
+
 /// Generates a fake funding transaction.
 pub fn create_fake_funding_transaction(address: &btc::Address, value: u64) -> btc::Transaction {
     // Generate random transaction id.
@@ -125,6 +141,25 @@ impl AnchoringNodes {
     fn private_key(&self, pk: &btc::PublicKey) -> btc::PrivateKey {
         self.key_pool[pk].clone()
     }
+
+    /// Adds the key possession proofs for the anchoring keys of the given configuration,
+    /// which private parts are known, and replaces the outdated ones.
+    fn prove_key_possession(&self, config: &mut Config) {
+        let proofs = config
+            .anchoring_keys
+            .iter()
+            .filter_map(|keys| {
+                let private_key = self.key_pool.get(&keys.bitcoin_key)?;
+                Some(config.prove_key_possession(&keys.service_key, private_key))
+            })
+            .collect::<Vec<_>>();
+        config.key_proofs.retain(|proof| {
+            proofs
+                .iter()
+                .all(|known| known.bitcoin_key != proof.bitcoin_key)
+        });
+        config.key_proofs.extend(proofs);
+    }
 }
 
 /// Convenient wrapper around testkit with the built-in bitcoin key pool for the each
@@ -136,6 +171,41 @@ pub struct AnchoringTestKit {
     anchoring_nodes: AnchoringNodes,
 }
 
+/// Kinds of the malformed anchoring configurations, which must be rejected
+/// by the anchoring service.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MalformedConfig {
+    /// The number of anchoring keys exceeds the limit of the redeem script.
+    TooManyKeys,
+    /// The list of anchoring keys is empty.
+    NoKeys,
+    /// The list of anchoring keys contains duplicates.
+    DuplicateKeys,
+    /// The Bitcoin network differs from the actual one.
+    WrongNetwork,
+}
+
+impl MalformedConfig {
+    /// All kinds of the malformed configurations.
+    pub const ALL: [Self; 4] = [
+        MalformedConfig::TooManyKeys,
+        MalformedConfig::NoKeys,
+        MalformedConfig::DuplicateKeys,
+        MalformedConfig::WrongNetwork,
+    ];
+
+    /// Returns the error with which the service rejects the configuration of this kind.
+    pub fn expected_error(self) -> Error {
+        match self {
+            MalformedConfig::TooManyKeys | MalformedConfig::NoKeys => {
+                Error::InvalidAnchoringKeysCount
+            }
+            MalformedConfig::DuplicateKeys => Error::DuplicateAnchoringKeys,
+            MalformedConfig::WrongNetwork => Error::NetworkMismatch,
+        }
+    }
+}
+
 /// Returns an anchoring schema instance used in Testkit.
 pub fn get_anchoring_schema<'a>(snapshot: &'a dyn Snapshot) -> Schema<impl Access + 'a> {
     Schema::new(snapshot.for_service(ANCHORING_INSTANCE_NAME).unwrap())
@@ -145,19 +215,34 @@ impl AnchoringTestKit {
     /// Creates an anchoring testkit instance for the specified number of anchoring nodes,
     /// and interval between anchors.
     pub fn new(nodes_num: u16, anchoring_interval: u64) -> Self {
-        let validator_keys = (0..nodes_num)
-            .map(|_| gen_validator_keys())
-            .collect::<Vec<_>>();
+        Self::with_config(nodes_num, |config| {
+            config.anchoring_interval = anchoring_interval;
+        })
+    }
 
-        let network = Network::Testnet;
-        let anchoring_nodes = AnchoringNodes::from_keys(Network::Testnet, &validator_keys);
+    /// Creates an anchoring testkit instance for the specified number of anchoring nodes,
+    /// and interval between anchors, together with the simulated Bitcoin chain, which
+    /// replaces the Bitcoin node in the tests.
+    ///
+    /// The anchoring wallet is funded by the transaction committed both to the simulated
+    /// chain and to the testkit blockchain.
+    pub fn new_without_rpc(nodes_num: u16, anchoring_interval: u64) -> (Self, SimulatedChainRelay) {
+        let mut testkit = Self::new(nodes_num, anchoring_interval);
+        let relay = SimulatedChainRelay::default();
+        let (txs, funding_tx) = testkit.create_funding_confirmation_txs(SIMULATED_FUNDING_VALUE);
+        relay.fund(funding_tx);
+        testkit.inner.create_block_with_transactions(txs);
+        (testkit, relay)
+    }
 
-        let anchoring_config = Config {
-            network,
-            anchoring_keys: anchoring_nodes.anchoring_keys(),
-            anchoring_interval,
-            ..Config::default()
-        };
+    /// Creates an anchoring testkit instance for the specified number of anchoring nodes,
+    /// and the initial anchoring configuration adjusted by the given closure.
+    pub fn with_config<F>(nodes_num: u16, adjust_config: F) -> Self
+    where
+        F: FnOnce(&mut Config),
+    {
+        let (validator_keys, anchoring_nodes, anchoring_config) =
+            Self::prepare_nodes(nodes_num, adjust_config);
 
         let inner = TestKitBuilder::validator()
             .with_keys(validator_keys)
@@ -175,6 +260,54 @@ impl AnchoringTestKit {
         }
     }
 
+    /// Creates an anchoring testkit instance for the specified number of anchoring nodes,
+    /// in which the anchoring service is started without the configuration. Returns
+    /// the testkit and the anchoring configuration, which can be applied later via
+    /// the supervisor service.
+    pub fn unconfigured(nodes_num: u16, anchoring_interval: u64) -> (Self, Config) {
+        let (validator_keys, anchoring_nodes, anchoring_config) =
+            Self::prepare_nodes(nodes_num, |config| {
+                config.anchoring_interval = anchoring_interval;
+            });
+
+        let inner = TestKitBuilder::validator()
+            .with_keys(validator_keys)
+            .with(Supervisor::simple())
+            .with(Spec::new(BtcAnchoringService).with_instance(
+                ANCHORING_INSTANCE_ID,
+                ANCHORING_INSTANCE_NAME,
+                Vec::<u8>::new(),
+            ))
+            .build();
+
+        let testkit = Self {
+            inner,
+            anchoring_nodes,
+        };
+        (testkit, anchoring_config)
+    }
+
+    fn prepare_nodes<F>(nodes_num: u16, adjust_config: F) -> (Vec<Keys>, AnchoringNodes, Config)
+    where
+        F: FnOnce(&mut Config),
+    {
+        let validator_keys = (0..nodes_num)
+            .map(|_| gen_validator_keys())
+            .collect::<Vec<_>>();
+
+        let network = Network::Testnet;
+        let anchoring_nodes = AnchoringNodes::from_keys(Network::Testnet, &validator_keys);
+
+        let mut anchoring_config = Config {
+            network,
+            anchoring_keys: anchoring_nodes.anchoring_keys(),
+            ..Config::default()
+        };
+        adjust_config(&mut anchoring_config);
+        anchoring_nodes.prove_key_possession(&mut anchoring_config);
+        (validator_keys, anchoring_nodes, anchoring_config)
+    }
+
     /// Returns the actual anchoring configuration.
     pub fn actual_anchoring_config(&self) -> Config {
         get_anchoring_schema(&self.inner.snapshot()).actual_config()
@@ -202,43 +335,67 @@ impl AnchoringTestKit {
         &self,
         node: &TestNode,
     ) -> Result<Vec<Verified<AnyTx>>, btc::BuilderError> {
-        let service_keypair = node.service_keypair();
         let snapshot = self.inner.snapshot();
         let schema = get_anchoring_schema(&snapshot);
 
-        let mut signatures = Vec::new();
         if let Some(p) = schema.actual_proposed_anchoring_transaction(snapshot.for_core()) {
             let (proposal, proposal_inputs) = p?;
+            Ok(self.create_signature_tx_for_transaction(node, &proposal, &proposal_inputs))
+        } else {
+            Ok(Vec::new())
+        }
+    }
 
-            let actual_config = schema.actual_state().actual_config().clone();
-            let bitcoin_key = actual_config
-                .find_bitcoin_key(&service_keypair.public_key())
-                .unwrap()
-                .1;
-            let btc_private_key = self.anchoring_nodes.private_key(&bitcoin_key);
+    /// Creates signatures for each input of the given transaction spending the given inputs
+    /// signed by the specified node, as if the transaction were the anchoring proposal
+    /// of the actual anchoring epoch. The transaction may conflict with the actual proposal.
+    pub fn create_signature_tx_for_transaction(
+        &self,
+        node: &TestNode,
+        transaction: &btc::Transaction,
+        inputs: &[btc::Transaction],
+    ) -> Vec<Verified<AnyTx>> {
+        let service_keypair = node.service_keypair();
+        let snapshot = self.inner.snapshot();
+        let schema = get_anchoring_schema(&snapshot);
 
-            let redeem_script = actual_config.redeem_script();
-            let mut signer = p2wsh::InputSigner::new(redeem_script);
-            for (index, proposal_input) in proposal_inputs.iter().enumerate() {
+        let actual_config = schema.actual_state().actual_config().clone();
+        let bitcoin_key = actual_config
+            .find_bitcoin_key(&service_keypair.public_key())
+            .unwrap()
+            .1;
+        let btc_private_key = self.anchoring_nodes.private_key(&bitcoin_key);
+        let anchoring_height = transaction.anchoring_payload().unwrap().block_height;
+        let epoch = schema.anchoring_epoch();
+        let signed_transaction = bitcoin::consensus::serialize(transaction.as_ref());
+
+        let redeem_script = actual_config.redeem_script();
+        let mut signer = p2wsh::InputSigner::new(redeem_script);
+        inputs
+            .iter()
+            .enumerate()
+            .map(|(index, input)| {
                 let signature = signer
                     .sign_input(
-                        TxInRef::new(proposal.as_ref(), index),
-                        proposal_input.as_ref(),
+                        TxInRef::new(transaction.as_ref(), index),
+                        input.as_ref(),
                         &btc_private_key.0.key,
                     )
                     .unwrap();
 
-                signatures.push(service_keypair.sign_input(
+                service_keypair.sign_input(
                     ANCHORING_INSTANCE_ID,
                     SignInput {
                         input: index as u32,
                         input_signature: signature.into(),
-                        txid: proposal.id(),
+                        txid: transaction.id(),
+                        anchoring_height,
+                        epoch,
+                        signed_transaction: signed_transaction.clone(),
                     },
-                ));
-            }
-        }
-        Ok(signatures)
+                )
+            })
+            .collect()
     }
 
     /// Creates signatures for each input of the proposed anchoring transaction signed by all of
@@ -292,6 +449,122 @@ impl AnchoringTestKit {
             .collect()
     }
 
+    /// Creates the transactions importing the given external anchoring transaction
+    /// from all anchoring nodes.
+    pub fn create_import_anchor_txs(&self, transaction: btc::Transaction) -> Vec<Verified<AnyTx>> {
+        let import_anchor = ImportAnchor { transaction };
+        self.actual_anchoring_config()
+            .anchoring_keys
+            .into_iter()
+            .map(move |anchoring_keys| {
+                let node_keypair = self
+                    .find_node_by_service_key(anchoring_keys.service_key)
+                    .expect("Unable to find node by service key")
+                    .service_keypair();
+
+                node_keypair.import_anchor(ANCHORING_INSTANCE_ID, import_anchor.clone())
+            })
+            .collect()
+    }
+
+    /// Creates the transactions proposing the given anchoring parameters from all
+    /// anchoring nodes.
+    pub fn create_update_parameters_txs(
+        &self,
+        parameters: UpdateParameters,
+    ) -> Vec<Verified<AnyTx>> {
+        self.actual_anchoring_config()
+            .anchoring_keys
+            .into_iter()
+            .map(move |anchoring_keys| {
+                let node_keypair = self
+                    .find_node_by_service_key(anchoring_keys.service_key)
+                    .expect("Unable to find node by service key")
+                    .service_keypair();
+
+                node_keypair.update_parameters(ANCHORING_INSTANCE_ID, parameters.clone())
+            })
+            .collect()
+    }
+
+    /// Creates the transactions confirming the finality of the given anchoring transaction
+    /// from all anchoring nodes.
+    pub fn create_confirm_anchor_finality_txs(&self, txid: btc::Sha256d) -> Vec<Verified<AnyTx>> {
+        self.actual_anchoring_config()
+            .anchoring_keys
+            .into_iter()
+            .map(move |anchoring_keys| {
+                let node_keypair = self
+                    .find_node_by_service_key(anchoring_keys.service_key)
+                    .expect("Unable to find node by service key")
+                    .service_keypair();
+
+                node_keypair
+                    .confirm_anchor_finality(ANCHORING_INSTANCE_ID, ConfirmAnchorFinality { txid })
+            })
+            .collect()
+    }
+
+    /// Creates the transactions reporting the given status of the anchoring transaction
+    /// from all anchoring nodes.
+    pub fn create_report_anchor_status_txs(
+        &self,
+        report: ReportAnchorStatus,
+    ) -> Vec<Verified<AnyTx>> {
+        self.actual_anchoring_config()
+            .anchoring_keys
+            .into_iter()
+            .map(move |anchoring_keys| {
+                let node_keypair = self
+                    .find_node_by_service_key(anchoring_keys.service_key)
+                    .expect("Unable to find node by service key")
+                    .service_keypair();
+
+                node_keypair.report_anchor_status(ANCHORING_INSTANCE_ID, report.clone())
+            })
+            .collect()
+    }
+
+    /// Creates the transactions recording the Bitcoin block of the anchoring transaction
+    /// from all anchoring nodes.
+    pub fn create_record_anchor_block_txs(
+        &self,
+        record: RecordAnchorBlock,
+    ) -> Vec<Verified<AnyTx>> {
+        self.actual_anchoring_config()
+            .anchoring_keys
+            .into_iter()
+            .map(move |anchoring_keys| {
+                let node_keypair = self
+                    .find_node_by_service_key(anchoring_keys.service_key)
+                    .expect("Unable to find node by service key")
+                    .service_keypair();
+
+                node_keypair.record_anchor_block(ANCHORING_INSTANCE_ID, record.clone())
+            })
+            .collect()
+    }
+
+    /// Creates the transactions reporting the given health of the Bitcoin relays
+    /// from all anchoring nodes.
+    pub fn create_report_relay_status_txs(
+        &self,
+        report: ReportRelayStatus,
+    ) -> Vec<Verified<AnyTx>> {
+        self.actual_anchoring_config()
+            .anchoring_keys
+            .into_iter()
+            .map(move |anchoring_keys| {
+                let node_keypair = self
+                    .find_node_by_service_key(anchoring_keys.service_key)
+                    .expect("Unable to find node by service key")
+                    .service_keypair();
+
+                node_keypair.report_relay_status(ANCHORING_INSTANCE_ID, report.clone())
+            })
+            .collect()
+    }
+
     /// Creates configuration change transaction for simple supervisor.
     pub fn create_config_change_tx(&self, proposal: ConfigPropose) -> Verified<AnyTx> {
         let initiator_id = self.inner.network().us().validator_id().unwrap();
@@ -299,6 +572,133 @@ impl AnchoringTestKit {
         keypair.propose_config_change(SUPERVISOR_INSTANCE_ID, proposal)
     }
 
+    /// Commits the configuration change, which changes the anchoring address, finalizes
+    /// the transition anchoring transaction and confirms it in the given simulated Bitcoin
+    /// chain. Returns the transition transaction.
+    ///
+    /// Anchoring transactions which are unknown to the simulated chain are sent
+    /// to it as well.
+    ///
+    /// # Panics
+    ///
+    /// - If the anchoring transactions chain is not established.
+    /// - If the given configuration does not change the anchoring address.
+    /// - If the transition is not finished successfully.
+    pub fn commit_configuration_change_and_transition(
+        &mut self,
+        mut config: Config,
+        relay: &SimulatedChainRelay,
+    ) -> btc::Transaction {
+        assert!(
+            self.last_anchoring_tx().is_some(),
+            "Anchoring transactions chain is not established."
+        );
+        assert_ne!(
+            self.actual_anchoring_config().anchoring_address(),
+            config.anchoring_address(),
+            "Configuration change does not change the anchoring address."
+        );
+        self.prove_key_possession(&mut config);
+
+        // Commit configuration change.
+        self.inner.create_block_with_transaction(
+            self.create_config_change_tx(
+                ConfigPropose::new(0, self.inner.height().next())
+                    .service_config(ANCHORING_INSTANCE_ID, config.clone()),
+            ),
+        );
+        assert_eq!(
+            get_anchoring_schema(&self.inner.snapshot()).following_config(),
+            Some(config.clone()),
+            "Configuration change has not been applied."
+        );
+
+        // Finalize transition transaction.
+        self.inner
+            .create_block_with_transactions(self.create_signature_txs().into_iter().flatten());
+        assert_eq!(
+            self.actual_anchoring_config(),
+            config,
+            "Transition to the new anchoring address has not been finished."
+        );
+        let transition_tx = self.last_anchoring_tx().unwrap();
+
+        // Send anchoring transactions to the Bitcoin chain and wait for the confirmation.
+        let snapshot = self.inner.snapshot();
+        for tx in get_anchoring_schema(&snapshot).transactions_chain.iter() {
+            if !relay.status(tx.id()).is_known() {
+                relay
+                    .submit(tx)
+                    .expect("Unable to send anchoring transaction to the Bitcoin chain");
+            }
+        }
+        relay.mine_block();
+        assert!(
+            relay.status(transition_tx.id()).confirmations().is_some(),
+            "Transition transaction has not been confirmed."
+        );
+        transition_tx
+    }
+
+    /// Creates the malformed modification of the actual anchoring configuration
+    /// of the given kind.
+    pub fn malformed_config(&mut self, kind: MalformedConfig) -> Config {
+        let mut config = self.actual_anchoring_config();
+        match kind {
+            MalformedConfig::TooManyKeys => {
+                while config.anchoring_keys.len() <= Config::MAX_NODES_COUNT {
+                    let bitcoin_key = self.gen_bitcoin_key();
+                    config.anchoring_keys.push(AnchoringKeys {
+                        bitcoin_key,
+                        service_key: KeyPair::random().public_key(),
+                    });
+                }
+            }
+            MalformedConfig::NoKeys => config.anchoring_keys.clear(),
+            MalformedConfig::DuplicateKeys => {
+                let duplicate = config.anchoring_keys[0].clone();
+                config.anchoring_keys.push(duplicate);
+            }
+            MalformedConfig::WrongNetwork => {
+                config.network = if config.network == Network::Bitcoin {
+                    Network::Testnet
+                } else {
+                    Network::Bitcoin
+                };
+            }
+        }
+        config
+    }
+
+    /// Submits the proposal with the given anchoring configuration and asserts that
+    /// it is rejected by the anchoring service with the given error, so neither actual
+    /// nor following configuration is changed.
+    ///
+    /// # Panics
+    ///
+    /// - If the proposal is not rejected with the given error.
+    pub fn assert_config_proposal_rejected(&mut self, config: Config, error: Error) {
+        let snapshot = self.inner.snapshot();
+        let schema = get_anchoring_schema(&snapshot);
+        let (actual_config, following_config) = (schema.actual_config(), schema.following_config());
+
+        let block = self.inner.create_block_with_transaction(
+            self.create_config_change_tx(
+                ConfigPropose::new(0, self.inner.height().next())
+                    .service_config(ANCHORING_INSTANCE_ID, config),
+            ),
+        );
+        assert_eq!(
+            *block[0].status().unwrap_err(),
+            ErrorMatch::from_fail(&error).for_service(ANCHORING_INSTANCE_ID)
+        );
+
+        let snapshot = self.inner.snapshot();
+        let schema = get_anchoring_schema(&snapshot);
+        assert_eq!(schema.actual_config(), actual_config);
+        assert_eq!(schema.following_config(), following_config);
+    }
+
     /// Adds a new auditor node to the testkit network and create Bitcoin keypair for it.
     pub fn add_node(&mut self) -> AnchoringKeys {
         let service_key = self
@@ -317,6 +717,28 @@ impl AnchoringTestKit {
         }
     }
 
+    /// Adds the proofs of possession of the anchoring keys of the given configuration,
+    /// which private parts are known to the testkit.
+    pub fn prove_key_possession(&self, config: &mut Config) {
+        self.anchoring_nodes.prove_key_possession(config);
+    }
+
+    /// Returns the proof of possession of the given anchoring keys for the actual anchoring
+    /// configuration. The private part of the Bitcoin key should be known to the testkit.
+    pub fn prove_actual_key_possession(&self, keys: &AnchoringKeys) -> KeyPossessionProof {
+        self.actual_anchoring_config()
+            .prove_key_possession(&keys.service_key, &self.node_private_key(&keys.bitcoin_key))
+    }
+
+    /// Returns the proof of possession of the given anchoring keys of the joining validator
+    /// for the configuration resulting from their inclusion into the pending configuration.
+    /// The private part of the Bitcoin key should be known to the testkit.
+    pub fn prove_onboarding_key_possession(&self, keys: &AnchoringKeys) -> KeyPossessionProof {
+        get_anchoring_schema(&self.inner.snapshot())
+            .onboarding_config(keys.clone())
+            .prove_key_possession(&keys.service_key, &self.node_private_key(&keys.bitcoin_key))
+    }
+
     /// Returns a corresponding private Bitcoin key.
     pub fn node_private_key(&self, public_key: &btc::PublicKey) -> btc::PrivateKey {
         self.anchoring_nodes.private_key(public_key)
@@ -432,6 +854,13 @@ impl PrivateApi for TestKitApiClient {
             .await
     }
 
+    async fn import_anchor(&self, transaction: btc::Transaction) -> api::Result<Hash> {
+        self.private(ApiKind::Service(ANCHORING_INSTANCE_NAME))
+            .query(&transaction)
+            .post("import-anchor")
+            .await
+    }
+
     async fn anchoring_proposal(&self) -> api::Result<AnchoringProposalState> {
         self.private(ApiKind::Service(ANCHORING_INSTANCE_NAME))
             .get("anchoring-proposal")
@@ -456,6 +885,48 @@ impl PrivateApi for TestKitApiClient {
             .get("transactions-count")
             .await
     }
+
+    async fn submit_anchoring_key(&self, submit_key: SubmitAnchoringKey) -> api::Result<Hash> {
+        self.private(ApiKind::Service(ANCHORING_INSTANCE_NAME))
+            .query(&submit_key)
+            .post("submit-anchoring-key")
+            .await
+    }
+
+    async fn update_parameters(&self, parameters: UpdateParameters) -> api::Result<Hash> {
+        self.private(ApiKind::Service(ANCHORING_INSTANCE_NAME))
+            .query(&parameters)
+            .post("update-parameters")
+            .await
+    }
+
+    async fn confirm_anchor_finality(&self, txid: btc::Sha256d) -> api::Result<Hash> {
+        self.private(ApiKind::Service(ANCHORING_INSTANCE_NAME))
+            .query(&txid)
+            .post("confirm-anchor-finality")
+            .await
+    }
+
+    async fn report_anchor_status(&self, report: ReportAnchorStatus) -> api::Result<Hash> {
+        self.private(ApiKind::Service(ANCHORING_INSTANCE_NAME))
+            .query(&report)
+            .post("report-anchor-status")
+            .await
+    }
+
+    async fn record_anchor_block(&self, record: RecordAnchorBlock) -> api::Result<Hash> {
+        self.private(ApiKind::Service(ANCHORING_INSTANCE_NAME))
+            .query(&record)
+            .post("record-anchor-block")
+            .await
+    }
+
+    async fn report_relay_status(&self, report: ReportRelayStatus) -> api::Result<Hash> {
+        self.private(ApiKind::Service(ANCHORING_INSTANCE_NAME))
+            .query(&report)
+            .post("report-relay-status")
+            .await
+    }
 }
 
 /// Proof validation extension.
@@ -470,15 +941,14 @@ impl ValidateProof for TransactionProof {
     type Output = Option<(u64, btc::Transaction)>;
 
     fn validate(self, validator_keys: &[PublicKey]) -> anyhow::Result<Self::Output> {
-        self.index_proof.verify(validator_keys)?;
+        verify_transaction_proof(self.into(), validator_keys)
+    }
+}
 
-        let entry = self
-            .transaction_proof
-            .check()?
-            .entries()
-            .iter()
-            .cloned()
-            .next();
-        Ok(entry)
+impl ValidateProof for v1::TransactionProof {
+    type Output = Option<(u64, btc::Transaction)>;
+
+    fn validate(self, validator_keys: &[PublicKey]) -> anyhow::Result<Self::Output> {
+        verify_transaction_proof(self, validator_keys)
     }
 }
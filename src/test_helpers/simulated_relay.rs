@@ -0,0 +1,386 @@
+// Copyright 2019 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! In-memory model of the Bitcoin chain for testing the sync utility without
+//! the Bitcoin node.
+
+use async_trait::async_trait;
+use bitcoin::OutPoint;
+use bitcoin_hashes::{sha256d, Hash};
+use thiserror::Error;
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use crate::{
+    btc,
+    sync::{ChainQuery, MempoolEntry, RelayBackend, TransactionStatus, TxBroadcaster},
+};
+
+/// Default minimal fee rate in satoshis per byte accepted by the simulated mempool.
+const DEFAULT_MIN_FEE_RATE: u64 = 1;
+
+/// Errors returned by the simulated Bitcoin chain.
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum SimulatedRelayError {
+    /// Transaction spends outputs which are unknown to the simulated chain.
+    #[error("Transaction {0} spends unknown outputs.")]
+    MissingInputs(btc::Sha256d),
+    /// Transaction spends outputs which are already spent by another transaction.
+    #[error("Transaction {txid} conflicts with transaction {conflicting}.")]
+    Conflict {
+        /// Identifier of the rejected transaction.
+        txid: btc::Sha256d,
+        /// Identifier of the transaction which spends the same outputs.
+        conflicting: btc::Sha256d,
+    },
+    /// Transaction fee rate is below the minimal one accepted by the mempool.
+    #[error(
+        "Transaction fee rate {fee_rate} sat/byte is below the minimal {min_fee_rate} sat/byte."
+    )]
+    FeeTooLow {
+        /// Fee rate of the rejected transaction.
+        fee_rate: u64,
+        /// Minimal accepted fee rate.
+        min_fee_rate: u64,
+    },
+    /// Simulated chain is unreachable, as if the Bitcoin node is down.
+    #[error("Simulated chain is unreachable.")]
+    Unreachable,
+}
+
+#[derive(Debug)]
+struct SimulatedChain {
+    /// All known transactions.
+    transactions: HashMap<btc::Sha256d, btc::Transaction>,
+    /// Identifiers of the transactions included into the blocks.
+    blocks: Vec<Vec<btc::Sha256d>>,
+    /// Identifiers of the unconfirmed transactions in the order of their arrival.
+    mempool: Vec<btc::Sha256d>,
+    /// Identifiers of the transactions which spend the outputs.
+    spent_outputs: HashMap<bitcoin::OutPoint, btc::Sha256d>,
+    /// Minimal fee rate in satoshis per byte accepted by the mempool.
+    min_fee_rate: u64,
+    /// Whether the requests to the simulated chain fail as if the Bitcoin node is down.
+    unreachable: bool,
+}
+
+impl Default for SimulatedChain {
+    fn default() -> Self {
+        Self {
+            transactions: HashMap::new(),
+            blocks: Vec::new(),
+            mempool: Vec::new(),
+            spent_outputs: HashMap::new(),
+            min_fee_rate: DEFAULT_MIN_FEE_RATE,
+            unreachable: false,
+        }
+    }
+}
+
+/// Returns the size of the transaction, the simulated chain does not distinguish
+/// the witness data.
+fn vsize(transaction: &btc::Transaction) -> u64 {
+    bitcoin::consensus::serialize(&transaction.0).len() as u64
+}
+
+impl SimulatedChain {
+    fn fee(&self, transaction: &btc::Transaction) -> Result<u64, SimulatedRelayError> {
+        let txid = transaction.id();
+        let balance = transaction
+            .0
+            .input
+            .iter()
+            .map(|input| {
+                let prev_out = input.previous_output;
+                self.transactions
+                    .get(&btc::Sha256d::from(prev_out.txid))
+                    .and_then(|tx| tx.0.output.get(prev_out.vout as usize))
+                    .map(|out| out.value)
+                    .ok_or(SimulatedRelayError::MissingInputs(txid))
+            })
+            .sum::<Result<u64, _>>()?;
+        let output = transaction.0.output.iter().map(|out| out.value).sum();
+        Ok(balance.saturating_sub(output))
+    }
+
+    fn fee_rate(&self, transaction: &btc::Transaction) -> Result<u64, SimulatedRelayError> {
+        Ok(self.fee(transaction)? / vsize(transaction))
+    }
+
+    fn accept(&mut self, transaction: btc::Transaction) -> Result<(), SimulatedRelayError> {
+        let txid = transaction.id();
+        if self.transactions.contains_key(&txid) {
+            return Ok(());
+        }
+
+        for input in &transaction.0.input {
+            if let Some(&conflicting) = self.spent_outputs.get(&input.previous_output) {
+                return Err(SimulatedRelayError::Conflict { txid, conflicting });
+            }
+        }
+        let fee_rate = self.fee_rate(&transaction)?;
+        if fee_rate < self.min_fee_rate {
+            return Err(SimulatedRelayError::FeeTooLow {
+                fee_rate,
+                min_fee_rate: self.min_fee_rate,
+            });
+        }
+
+        for input in &transaction.0.input {
+            self.spent_outputs.insert(input.previous_output, txid);
+        }
+        self.transactions.insert(txid, transaction);
+        self.mempool.push(txid);
+        Ok(())
+    }
+
+    fn evict(&mut self, txid: btc::Sha256d) {
+        self.mempool.retain(|id| *id != txid);
+        if let Some(transaction) = self.transactions.remove(&txid) {
+            for input in &transaction.0.input {
+                self.spent_outputs.remove(&input.previous_output);
+            }
+        }
+        // Evict transactions which spend the outputs of the evicted one.
+        let descendants = self
+            .mempool
+            .iter()
+            .copied()
+            .filter(|id| {
+                self.transactions[id]
+                    .0
+                    .input
+                    .iter()
+                    .any(|input| btc::Sha256d::from(input.previous_output.txid) == txid)
+            })
+            .collect::<Vec<_>>();
+        for descendant in descendants {
+            self.evict(descendant);
+        }
+    }
+
+    /// Returns the hash of the block at the given position, which commits to the block
+    /// transactions, so the blocks replaced by a reorganization have the different hashes.
+    fn block_hash(&self, height: usize) -> btc::Sha256d {
+        let mut data = (height as u64).to_le_bytes().to_vec();
+        for txid in &self.blocks[height] {
+            data.extend_from_slice(&txid.0[..]);
+        }
+        btc::Sha256d(sha256d::Hash::hash(&data))
+    }
+
+    fn transaction_block(&self, txid: btc::Sha256d) -> Option<usize> {
+        self.blocks.iter().position(|block| block.contains(&txid))
+    }
+
+    fn transaction_status(&self, txid: btc::Sha256d) -> TransactionStatus {
+        if let Some(height) = self.transaction_block(txid) {
+            TransactionStatus::Committed((self.blocks.len() - height) as u32)
+        } else if self.mempool.contains(&txid) {
+            TransactionStatus::Mempool
+        } else {
+            TransactionStatus::Unknown
+        }
+    }
+}
+
+/// Bitcoin relay, which models a toy Bitcoin chain entirely in memory.
+///
+/// The simulated chain supports mining of blocks, mempool with the minimal fee rate
+/// and chain reorganizations, so the full anchoring lifecycle can be tested without
+/// the Bitcoin node. Clones of the relay share the same chain.
+#[derive(Debug, Clone, Default)]
+pub struct SimulatedChainRelay {
+    inner: Arc<Mutex<SimulatedChain>>,
+}
+
+impl SimulatedChainRelay {
+    /// Creates a new simulated chain with the given minimal fee rate in satoshis per byte.
+    pub fn with_min_fee_rate(min_fee_rate: u64) -> Self {
+        let relay = Self::default();
+        relay.inner.lock().unwrap().min_fee_rate = min_fee_rate;
+        relay
+    }
+
+    /// Includes the funding transaction into a new block. Inputs of the funding
+    /// transaction are not checked.
+    pub fn fund(&self, transaction: btc::Transaction) {
+        let mut chain = self.inner.lock().unwrap();
+        let txid = transaction.id();
+        chain.transactions.insert(txid, transaction);
+        chain.blocks.push(vec![txid]);
+    }
+
+    /// Mines a new block with all the mempool transactions and returns the height
+    /// of the simulated chain.
+    pub fn mine_block(&self) -> usize {
+        let mut chain = self.inner.lock().unwrap();
+        let block = chain.mempool.drain(..).collect();
+        chain.blocks.push(block);
+        chain.blocks.len()
+    }
+
+    /// Mines the given number of blocks, so the transactions from the mempool get
+    /// the same number of confirmations, and returns the height of the simulated chain.
+    pub fn mine_blocks(&self, count: usize) -> usize {
+        for _ in 0..count {
+            self.mine_block();
+        }
+        self.height()
+    }
+
+    /// Disconnects the given number of the latest blocks and returns their transactions
+    /// back to the mempool.
+    pub fn reorg(&self, depth: usize) {
+        let mut chain = self.inner.lock().unwrap();
+        let fork_height = chain.blocks.len().saturating_sub(depth);
+        let mut transactions = chain
+            .blocks
+            .drain(fork_height..)
+            .flatten()
+            .collect::<Vec<_>>();
+        transactions.append(&mut chain.mempool);
+        chain.mempool = transactions;
+    }
+
+    /// Changes the minimal fee rate accepted by the mempool and evicts mempool
+    /// transactions with the lower fee rate, as happens when the fee market rises.
+    pub fn set_min_fee_rate(&self, min_fee_rate: u64) {
+        let mut chain = self.inner.lock().unwrap();
+        chain.min_fee_rate = min_fee_rate;
+        let evicted = chain
+            .mempool
+            .iter()
+            .copied()
+            .filter(|txid| {
+                chain
+                    .fee_rate(&chain.transactions[txid])
+                    .map_or(true, |fee_rate| fee_rate < min_fee_rate)
+            })
+            .collect::<Vec<_>>();
+        for txid in evicted {
+            chain.evict(txid);
+        }
+    }
+
+    /// Submits the transaction to the mempool of the simulated chain.
+    pub fn submit(
+        &self,
+        transaction: btc::Transaction,
+    ) -> Result<btc::Sha256d, SimulatedRelayError> {
+        let txid = transaction.id();
+        self.inner.lock().unwrap().accept(transaction)?;
+        Ok(txid)
+    }
+
+    /// Returns the status of the transaction in the simulated chain.
+    pub fn status(&self, txid: btc::Sha256d) -> TransactionStatus {
+        self.inner.lock().unwrap().transaction_status(txid)
+    }
+
+    /// Returns the height of the simulated chain.
+    pub fn height(&self) -> usize {
+        self.inner.lock().unwrap().blocks.len()
+    }
+
+    /// Returns identifiers of the unconfirmed transactions.
+    pub fn mempool(&self) -> Vec<btc::Sha256d> {
+        self.inner.lock().unwrap().mempool.clone()
+    }
+
+    /// Makes the simulated chain unreachable or reachable again. The unreachable chain
+    /// rejects the broadcast transactions and fails the status requests and health checks.
+    pub fn set_reachable(&self, reachable: bool) {
+        self.inner.lock().unwrap().unreachable = !reachable;
+    }
+
+    fn check_reachable(&self) -> Result<(), SimulatedRelayError> {
+        if self.inner.lock().unwrap().unreachable {
+            Err(SimulatedRelayError::Unreachable)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl RelayBackend for SimulatedChainRelay {
+    type Error = SimulatedRelayError;
+}
+
+#[async_trait]
+impl TxBroadcaster for SimulatedChainRelay {
+    async fn send_transaction(
+        &self,
+        transaction: &btc::Transaction,
+    ) -> Result<btc::Sha256d, Self::Error> {
+        self.check_reachable()?;
+        self.submit(transaction.clone())
+    }
+}
+
+#[async_trait]
+impl ChainQuery for SimulatedChainRelay {
+    async fn transaction_status(&self, id: btc::Sha256d) -> Result<TransactionStatus, Self::Error> {
+        self.check_reachable()?;
+        Ok(self.status(id))
+    }
+
+    async fn spending_transaction(
+        &self,
+        outpoint: OutPoint,
+    ) -> Result<Option<btc::Transaction>, Self::Error> {
+        let chain = self.inner.lock().unwrap();
+        Ok(chain
+            .spent_outputs
+            .get(&outpoint)
+            .and_then(|txid| chain.transactions.get(txid))
+            .cloned())
+    }
+
+    // The simulated mempool accepts the transactions paying the minimal fee rate,
+    // and they are confirmed in the next mined block.
+    async fn fee_rate(&self, _target_blocks: u16) -> Result<Option<u64>, Self::Error> {
+        Ok(Some(self.inner.lock().unwrap().min_fee_rate))
+    }
+
+    async fn mempool_entry(&self, id: btc::Sha256d) -> Result<Option<MempoolEntry>, Self::Error> {
+        let chain = self.inner.lock().unwrap();
+        if !chain.mempool.contains(&id) {
+            return Ok(None);
+        }
+        let transaction = &chain.transactions[&id];
+        Ok(Some(MempoolEntry {
+            vsize: vsize(transaction),
+            fee: chain.fee(transaction)?,
+        }))
+    }
+
+    async fn transaction_block_hash(
+        &self,
+        id: btc::Sha256d,
+    ) -> Result<Option<btc::Sha256d>, Self::Error> {
+        let chain = self.inner.lock().unwrap();
+        Ok(chain
+            .transaction_block(id)
+            .map(|height| chain.block_hash(height)))
+    }
+
+    async fn ping(&self) -> Result<Option<u64>, Self::Error> {
+        self.check_reachable()?;
+        Ok(Some(self.height() as u64))
+    }
+}
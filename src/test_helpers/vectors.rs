@@ -0,0 +1,323 @@
+// Copyright 2019 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Deterministic test vectors for the anchoring transactions.
+//!
+//! Each vector describes the anchoring configuration and the anchored block together
+//! with the expected redeem script, anchoring address, payload script, unsigned
+//! anchoring proposal and BIP-143 signature hashes of its inputs. The vectors are
+//! serialized to JSON, so alternative implementations and auditors can verify
+//! byte-for-byte compatibility with this crate, while [`TestVector::check`] acts as
+//! the conformance checker.
+//!
+//! [`TestVector::check`]: struct.TestVector.html#method.check
+
+use bitcoin::{
+    blockdata::transaction::{OutPoint, TxIn, TxOut},
+    network::constants::Network,
+    secp256k1::{Secp256k1, SecretKey},
+    util::bip143::SighashComponents,
+};
+use bitcoin_hashes::{sha256d::Hash as Sha256dHash, Hash as BitcoinHash};
+use exonum::{
+    crypto::{Hash, PublicKey},
+    helpers::Height,
+};
+use serde_derive::{Deserialize, Serialize};
+
+use std::fmt::Display;
+
+use crate::{
+    btc::{self, BtcAnchoringTransactionBuilder},
+    config::Config,
+    proto::AnchoringKeys,
+};
+
+/// Value in satoshis of the funding output in the standard vectors.
+const FUNDING_VALUE: u64 = 100_000;
+
+/// Input data of the test vector.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VectorInput {
+    /// Bitcoin network.
+    pub network: Network,
+    /// Bitcoin public keys of the anchoring nodes in the order of the redeem script.
+    pub bitcoin_keys: Vec<btc::PublicKey>,
+    /// Fee per byte in satoshis.
+    pub transaction_fee: u64,
+    /// Funding transaction, which output is spent by the anchoring proposal.
+    pub funding_transaction: btc::Transaction,
+    /// Anchored block height.
+    pub block_height: Height,
+    /// Anchored block hash.
+    pub block_hash: Hash,
+    /// Merkle root of the auxiliary services state hashes, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auxiliary_root: Option<Hash>,
+    /// Hash of the consensus configuration, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub consensus_config_hash: Option<Hash>,
+}
+
+/// Expected output data of the test vector.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VectorOutput {
+    /// Hex-encoded redeem script.
+    pub redeem_script: String,
+    /// Anchoring address.
+    pub address: btc::Address,
+    /// Hex-encoded `OP_RETURN` script with the anchoring payload.
+    pub payload_script: String,
+    /// Unsigned anchoring proposal.
+    pub proposal: btc::Transaction,
+    /// Hex-encoded BIP-143 `SIGHASH_ALL` hashes of the proposal inputs.
+    pub sighashes: Vec<String>,
+}
+
+/// Test vector of the anchoring transaction.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TestVector {
+    /// Human-readable description of the vector.
+    pub description: String,
+    /// Input data.
+    pub input: VectorInput,
+    /// Expected output data.
+    pub output: VectorOutput,
+}
+
+impl VectorInput {
+    /// Returns the anchoring configuration described by the input. The service keys
+    /// do not affect the Bitcoin data, so the placeholder ones are used.
+    pub fn config(&self) -> Config {
+        let mut config = anchoring_config(self.network, &self.bitcoin_keys);
+        config.transaction_fee = self.transaction_fee;
+        config
+    }
+
+    /// Computes the output data for the input.
+    pub fn compute(&self) -> Result<VectorOutput, String> {
+        let config = self.config();
+        let redeem_script = config.redeem_script();
+
+        let mut builder = BtcAnchoringTransactionBuilder::new(&redeem_script);
+        builder
+            .additional_funds(self.funding_transaction.clone())
+            .map_err(|e| e.to_string())?;
+        builder.fee(self.transaction_fee);
+        builder.payload(self.block_height, self.block_hash);
+        if let Some(root) = self.auxiliary_root {
+            builder.auxiliary_root(root);
+        }
+        if let Some(config_hash) = self.consensus_config_hash {
+            builder.consensus_config_hash(config_hash);
+        }
+        let (proposal, inputs) = builder.create().map_err(|e| e.to_string())?;
+
+        if proposal.anchoring_payload().is_none() {
+            return Err("Proposal does not contain the anchoring payload".to_owned());
+        }
+        let payload_script = &proposal.0.output[1].script_pubkey;
+
+        let components = SighashComponents::new(&proposal.0);
+        let sighashes = proposal
+            .0
+            .input
+            .iter()
+            .zip(&inputs)
+            .map(|(txin, input_tx)| {
+                let value = input_tx.0.output[txin.previous_output.vout as usize].value;
+                let sighash = components.sighash_all(txin, redeem_script.as_ref(), value);
+                hex::encode(&sighash[..])
+            })
+            .collect();
+
+        Ok(VectorOutput {
+            redeem_script: hex::encode(&redeem_script.as_ref()[..]),
+            address: config.anchoring_address(),
+            payload_script: hex::encode(&payload_script[..]),
+            proposal,
+            sighashes,
+        })
+    }
+}
+
+impl TestVector {
+    /// Creates the test vector with the output computed for the given input.
+    pub fn new(description: impl Into<String>, input: VectorInput) -> Self {
+        let output = input
+            .compute()
+            .expect("Unable to compute the test vector output");
+        Self {
+            description: description.into(),
+            input,
+            output,
+        }
+    }
+
+    /// Recomputes the vector output and returns the list of mismatches between
+    /// the recomputed and the expected values.
+    pub fn check(&self) -> Result<(), Vec<String>> {
+        let actual = self.input.compute().map_err(|e| vec![e])?;
+        let expected = &self.output;
+
+        let mut mismatches = Vec::new();
+        check_field(
+            &mut mismatches,
+            "redeem_script",
+            &expected.redeem_script,
+            &actual.redeem_script,
+        );
+        check_field(
+            &mut mismatches,
+            "address",
+            &expected.address,
+            &actual.address,
+        );
+        check_field(
+            &mut mismatches,
+            "payload_script",
+            &expected.payload_script,
+            &actual.payload_script,
+        );
+        check_field(
+            &mut mismatches,
+            "proposal",
+            &hex::encode(bitcoin::consensus::serialize(&expected.proposal.0)),
+            &hex::encode(bitcoin::consensus::serialize(&actual.proposal.0)),
+        );
+        check_field(
+            &mut mismatches,
+            "sighashes",
+            &expected.sighashes.join(","),
+            &actual.sighashes.join(","),
+        );
+
+        if mismatches.is_empty() {
+            Ok(())
+        } else {
+            Err(mismatches)
+        }
+    }
+}
+
+fn check_field<T: PartialEq + Display + ?Sized>(
+    mismatches: &mut Vec<String>,
+    name: &str,
+    expected: &T,
+    actual: &T,
+) {
+    if expected != actual {
+        mismatches.push(format!("{}: expected {}, got {}", name, expected, actual));
+    }
+}
+
+fn anchoring_config(network: Network, bitcoin_keys: &[btc::PublicKey]) -> Config {
+    let keys = bitcoin_keys
+        .iter()
+        .enumerate()
+        .map(|(i, bitcoin_key)| AnchoringKeys {
+            bitcoin_key: *bitcoin_key,
+            service_key: PublicKey::new([i as u8; 32]),
+        });
+    Config::with_public_keys(network, keys)
+        .expect("Test vector should contain at least one public key")
+}
+
+/// Returns the deterministic Bitcoin public key derived from the secret key
+/// with all bytes equal to the given seed.
+fn deterministic_bitcoin_key(seed: u8, network: Network) -> btc::PublicKey {
+    let context = Secp256k1::new();
+    let key = bitcoin::PrivateKey {
+        compressed: true,
+        network,
+        key: SecretKey::from_slice(&[seed; 32]).expect("Invalid secret key"),
+    };
+    btc::PublicKey(key.public_key(&context))
+}
+
+/// Returns the deterministic funding transaction, which spends the output of the
+/// transaction with all bytes of its identifier equal to the given seed.
+fn deterministic_funding_transaction(config: &Config, seed: u8) -> btc::Transaction {
+    bitcoin::Transaction {
+        version: 2,
+        lock_time: 0,
+        input: vec![TxIn {
+            previous_output: OutPoint {
+                vout: 0,
+                txid: Sha256dHash::from_slice(&[seed; 32]).unwrap().into(),
+            },
+            script_sig: bitcoin::Script::new(),
+            sequence: 0,
+            witness: vec![],
+        }],
+        output: vec![TxOut {
+            value: FUNDING_VALUE,
+            script_pubkey: config.anchoring_out_script(),
+        }],
+    }
+    .into()
+}
+
+fn standard_input(
+    network: Network,
+    nodes: u8,
+    transaction_fee: u64,
+    block_height: u64,
+    auxiliary_root: Option<Hash>,
+) -> VectorInput {
+    let bitcoin_keys = (1..=nodes)
+        .map(|seed| deterministic_bitcoin_key(seed, network))
+        .collect::<Vec<_>>();
+    let config = anchoring_config(network, &bitcoin_keys);
+    VectorInput {
+        network,
+        bitcoin_keys,
+        transaction_fee,
+        funding_transaction: deterministic_funding_transaction(&config, nodes),
+        block_height: Height(block_height),
+        block_hash: Hash::new([nodes; 32]),
+        auxiliary_root,
+        consensus_config_hash: None,
+    }
+}
+
+/// Returns the standard set of the test vectors covering the different networks,
+/// numbers of the anchoring nodes and payload kinds.
+pub fn standard_vectors() -> Vec<TestVector> {
+    vec![
+        TestVector::new(
+            "single node, testnet, regular payload",
+            standard_input(Network::Testnet, 1, 10, 0, None),
+        ),
+        TestVector::new(
+            "three nodes, regtest, regular payload",
+            standard_input(Network::Regtest, 3, 20, 1_000, None),
+        ),
+        TestVector::new(
+            "four nodes, mainnet, regular payload",
+            standard_input(Network::Bitcoin, 4, 15, 123_456, None),
+        ),
+        TestVector::new(
+            "four nodes, testnet, auxiliary payload",
+            standard_input(Network::Testnet, 4, 10, 5_000, Some(Hash::new([0xAA; 32]))),
+        ),
+        TestVector::new(
+            "four nodes, regtest, consensus payload",
+            VectorInput {
+                consensus_config_hash: Some(Hash::new([0xCC; 32])),
+                ..standard_input(Network::Regtest, 4, 10, 10_000, None)
+            },
+        ),
+    ]
+}
@@ -12,18 +12,27 @@
 // limitations under the License.
 
 use btc_transaction_utils::{p2wsh, TxInRef};
-use exonum::helpers::Height;
+use exonum::{
+    crypto::{hash, Hash, KeyPair},
+    helpers::Height,
+};
 use exonum_btc_anchoring::{
-    api::{AnchoringProposalState, PrivateApi, PublicApi},
-    blockchain::SignInput,
+    api::{v1, AnchoringProposalState, PrivateApi, PublicApi},
+    blockchain::{
+        data_layout::TenantBlockId, export::AnchorsExport, BtcAnchoringInterface,
+        RecordAnchorBlock, ReportRelayStatus, SignInput, SubmitData,
+    },
     btc,
+    client::{verify_anchor_proofs, verify_checkpoint, verify_tenant_receipt},
+    config::Tenant,
     test_helpers::{
         create_fake_funding_transaction, get_anchoring_schema, AnchoringTestKit, ValidateProof,
-        ANCHORING_INSTANCE_ID,
+        ANCHORING_INSTANCE_ID, ANCHORING_INSTANCE_NAME,
     },
 };
+use exonum_merkledb::ObjectHash;
 use exonum_supervisor::ConfigPropose;
-use exonum_testkit::TestKitApi;
+use exonum_testkit::{ApiKind, TestKitApi};
 
 fn init_testkit() -> (AnchoringTestKit, TestKitApi) {
     let mut testkit = AnchoringTestKit::default();
@@ -107,6 +116,7 @@ async fn following_address() {
     // Add an anchoring node.
     let mut new_cfg = anchoring_testkit.actual_anchoring_config();
     new_cfg.anchoring_keys.push(anchoring_testkit.add_node());
+    anchoring_testkit.prove_key_possession(&mut new_cfg);
     let following_address = new_cfg.anchoring_address();
 
     // Commit configuration with without last anchoring node.
@@ -120,8 +130,17 @@ async fn following_address() {
 
     assert_eq!(
         anchoring_api.client().following_address().await.unwrap(),
-        Some(following_address)
+        Some(following_address.clone())
     );
+
+    let descriptor: Option<v1::AnchoringDescriptor> = anchoring_api
+        .public(ApiKind::Service(ANCHORING_INSTANCE_NAME))
+        .get("v1/address/following-descriptor")
+        .await
+        .unwrap();
+    let descriptor = descriptor.unwrap();
+    assert_eq!(descriptor.address, following_address);
+    assert!(descriptor.descriptor.starts_with("wsh(multi(4,"));
 }
 
 #[tokio::test]
@@ -212,6 +231,7 @@ async fn find_transaction_configuration_change() {
     // Add an anchoring node.
     let mut new_cfg = anchoring_testkit.actual_anchoring_config();
     new_cfg.anchoring_keys.push(anchoring_testkit.add_node());
+    anchoring_testkit.prove_key_possession(&mut new_cfg);
 
     // Commit configuration with without last anchoring node.
     anchoring_testkit.inner.create_block_with_transaction(
@@ -318,6 +338,754 @@ async fn actual_config() {
     assert_eq!(PrivateApi::config(client).await.unwrap(), cfg);
 }
 
+#[tokio::test]
+async fn actual_config_v1() {
+    let (anchoring_testkit, anchoring_api) = init_testkit();
+
+    let cfg = anchoring_testkit.actual_anchoring_config();
+
+    let cfg_v1: v1::Config = anchoring_api
+        .public(ApiKind::Service(ANCHORING_INSTANCE_NAME))
+        .get("v1/config")
+        .await
+        .unwrap();
+    assert_eq!(cfg_v1, v1::Config::from(&cfg));
+    assert_eq!(cfg_v1.anchoring_address, cfg.anchoring_address());
+
+    let address: v1::AnchoringAddress = anchoring_api
+        .public(ApiKind::Service(ANCHORING_INSTANCE_NAME))
+        .get("v1/address/following")
+        .await
+        .unwrap();
+    assert_eq!(address.address, None);
+
+    let descriptor: Option<v1::AnchoringDescriptor> = anchoring_api
+        .private(ApiKind::Service(ANCHORING_INSTANCE_NAME))
+        .get("v1/address/following-descriptor")
+        .await
+        .unwrap();
+    assert_eq!(descriptor, None);
+
+    let descriptor: v1::AnchoringDescriptor = anchoring_api
+        .private(ApiKind::Service(ANCHORING_INSTANCE_NAME))
+        .get("v1/address/actual-descriptor")
+        .await
+        .unwrap();
+    assert_eq!(descriptor, v1::AnchoringDescriptor::from(&cfg));
+}
+
+#[tokio::test]
+async fn status_and_find_anchor_v1() {
+    let (mut anchoring_testkit, anchoring_api) = init_testkit();
+
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit
+            .create_signature_txs()
+            .into_iter()
+            .flatten(),
+    );
+    let tx = anchoring_testkit.last_anchoring_tx().unwrap();
+
+    let status: v1::AnchoringStatus = anchoring_api
+        .public(ApiKind::Service(ANCHORING_INSTANCE_NAME))
+        .get("v1/status")
+        .await
+        .unwrap();
+    assert_eq!(status.transactions_count, 1);
+    assert_eq!(status.latest_anchored_height, Some(Height(0)));
+    assert_eq!(status.following_address, None);
+    assert_eq!(status.payload_version, btc::PAYLOAD_VERSION);
+    assert_eq!(status.snapshot_height, anchoring_testkit.inner.height());
+
+    let proof: v1::TransactionProof = anchoring_api
+        .public(ApiKind::Service(ANCHORING_INSTANCE_NAME))
+        .query(&v1::FindAnchorQuery { txid: tx.id() })
+        .get("v1/find-anchor")
+        .await
+        .unwrap();
+    let validator_keys = anchoring_testkit
+        .inner
+        .consensus_config()
+        .validator_keys
+        .into_iter()
+        .map(|key| key.consensus_key)
+        .collect::<Vec<_>>();
+    assert_eq!(proof.validate(&validator_keys).unwrap(), Some((0, tx)));
+}
+
+async fn checkpoint(anchoring_api: &TestKitApi) -> Option<v1::Checkpoint> {
+    anchoring_api
+        .public(ApiKind::Service(ANCHORING_INSTANCE_NAME))
+        .get("v1/checkpoint")
+        .await
+        .ok()
+}
+
+#[tokio::test]
+async fn checkpoint_v1() {
+    let (mut anchoring_testkit, anchoring_api) = init_testkit();
+    // There is no checkpoint until the anchoring chain is established.
+    assert!(checkpoint(&anchoring_api).await.is_none());
+
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit
+            .create_signature_txs()
+            .into_iter()
+            .flatten(),
+    );
+    let validator_keys = anchoring_testkit
+        .inner
+        .consensus_config()
+        .validator_keys
+        .into_iter()
+        .map(|key| key.consensus_key)
+        .collect::<Vec<_>>();
+
+    let config = anchoring_testkit.actual_anchoring_config();
+    let (address, index, tx) = verify_checkpoint(
+        checkpoint(&anchoring_api).await.unwrap(),
+        config.network,
+        &validator_keys,
+    )
+    .unwrap();
+    assert_eq!(address, config.anchoring_address());
+    assert_eq!(index, 0);
+    assert_eq!(tx, anchoring_testkit.last_anchoring_tx().unwrap());
+
+    // Checkpoint is not trusted without the signatures of the given validators.
+    let other_keys = vec![KeyPair::random().public_key()];
+    verify_checkpoint(
+        checkpoint(&anchoring_api).await.unwrap(),
+        config.network,
+        &other_keys,
+    )
+    .unwrap_err();
+}
+
+#[tokio::test]
+async fn storage_usage_v1() {
+    let (mut anchoring_testkit, anchoring_api) = init_testkit();
+
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit
+            .create_signature_txs()
+            .into_iter()
+            .flatten(),
+    );
+
+    let usage: v1::StorageUsage = anchoring_api
+        .private(ApiKind::Service(ANCHORING_INSTANCE_NAME))
+        .get("v1/storage-usage")
+        .await
+        .unwrap();
+    let entries = |name: &str| {
+        usage
+            .indexes
+            .iter()
+            .find(|index| index.name == name)
+            .unwrap()
+            .entries
+    };
+    assert_eq!(entries("transactions_chain"), 1);
+    assert_eq!(entries("spent_funding_transactions"), 1);
+    assert_eq!(entries("anchored_heights"), 1);
+    assert_eq!(entries("actual_config"), 1);
+    assert_eq!(entries("following_config"), 0);
+    assert_eq!(entries("transaction_positions"), 1);
+    assert_eq!(entries("anchor_accounting"), 1);
+    assert_eq!(entries("health_records"), 1);
+    assert_eq!(entries("used_addresses"), 1);
+    // Signatures of the finalized proposal have been removed.
+    assert_eq!(entries("transaction_signatures"), 0);
+    assert_eq!(
+        usage.total_size,
+        usage.indexes.iter().map(|index| index.size).sum::<u64>()
+    );
+}
+
+async fn balance_history(anchoring_api: &TestKitApi, count: Option<u64>) -> v1::BalanceHistory {
+    anchoring_api
+        .public(ApiKind::Service(ANCHORING_INSTANCE_NAME))
+        .query(&v1::BalanceHistoryQuery { count })
+        .get("v1/balance-history")
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn balance_history_v1() {
+    let (mut anchoring_testkit, anchoring_api) = init_testkit();
+    let anchoring_interval = anchoring_testkit
+        .actual_anchoring_config()
+        .anchoring_interval;
+
+    let history = balance_history(&anchoring_api, None).await;
+    assert!(history.records.is_empty());
+    assert_eq!(history.forecast, None);
+
+    // Establish anchoring transactions chain and anchor the next height.
+    for height in &[0, anchoring_interval] {
+        anchoring_testkit.inner.create_blocks_until(Height(*height));
+        anchoring_testkit.inner.create_block_with_transactions(
+            anchoring_testkit
+                .create_signature_txs()
+                .into_iter()
+                .flatten(),
+        );
+    }
+    let change = anchoring_testkit.last_anchoring_tx().unwrap().0.output[0].value;
+
+    let history = balance_history(&anchoring_api, None).await;
+    assert_eq!(history.records.len(), 2);
+    assert_eq!(history.records[0].fee, 1530);
+    assert_eq!(
+        history.records[1].input_value, history.records[0].change,
+        "The second anchor should spend the change of the first one"
+    );
+    assert_eq!(history.records[1].change, change);
+
+    let history = balance_history(&anchoring_api, Some(1)).await;
+    assert_eq!(history.records.len(), 1);
+    assert_eq!(history.records[0].index, 1);
+    let forecast = history.forecast.unwrap();
+    assert_eq!(forecast.average_fee, history.records[0].fee);
+    assert_eq!(forecast.remaining_anchors, change / forecast.average_fee);
+    assert_eq!(
+        forecast.remaining_blocks,
+        forecast.remaining_anchors * anchoring_interval
+    );
+}
+
+async fn health(anchoring_api: &TestKitApi) -> v1::AnchoringHealth {
+    anchoring_api
+        .public(ApiKind::Service(ANCHORING_INSTANCE_NAME))
+        .get("v1/health")
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn health_v1() {
+    let (mut anchoring_testkit, anchoring_api) = init_testkit();
+    let anchoring_interval = anchoring_testkit
+        .actual_anchoring_config()
+        .anchoring_interval;
+
+    let initial = health(&anchoring_api).await;
+    assert_eq!(initial.score, 0);
+    assert_eq!(initial.anchors_count, 0);
+    assert_eq!(initial.latest_latency, None);
+
+    for height in &[0, anchoring_interval] {
+        anchoring_testkit.inner.create_blocks_until(Height(*height));
+        anchoring_testkit.inner.create_block_with_transactions(
+            anchoring_testkit
+                .create_signature_txs()
+                .into_iter()
+                .flatten(),
+        );
+    }
+
+    let health = health(&anchoring_api).await;
+    assert_eq!(health.anchors_count, 2);
+    assert_eq!(health.on_time_anchors, 2);
+    // The quorum of the anchoring nodes is enough to finalize the proposals.
+    let quorum = anchoring_testkit
+        .actual_anchoring_config()
+        .byzantine_quorum() as u64;
+    let nodes = anchoring_testkit
+        .actual_anchoring_config()
+        .anchoring_keys
+        .len() as u64;
+    assert!(health.participation_rate >= quorum * 100 / nodes);
+    assert_eq!(health.score, (100 + health.participation_rate) / 2);
+    assert!(health.latest_latency.unwrap() <= anchoring_interval);
+}
+
+async fn next_anchor(anchoring_api: &TestKitApi) -> v1::NextAnchor {
+    anchoring_api
+        .public(ApiKind::Service(ANCHORING_INSTANCE_NAME))
+        .get("v1/next-anchor")
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn next_anchor_v1() {
+    let (mut anchoring_testkit, anchoring_api) = init_testkit();
+    let anchoring_interval = anchoring_testkit
+        .actual_anchoring_config()
+        .anchoring_interval;
+
+    // The start height is reached, but the first anchor is not recorded yet.
+    anchoring_testkit.inner.create_block();
+    let next = next_anchor(&anchoring_api).await;
+    assert_eq!(next.kind, v1::NextAnchorKind::Regular);
+    assert_eq!(next.anchoring_height, Some(Height(0)));
+    assert_eq!(next.blocks_remaining, 0);
+    assert_eq!(next.overdue_blocks, 1);
+
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit
+            .create_signature_txs()
+            .into_iter()
+            .flatten(),
+    );
+    let next = next_anchor(&anchoring_api).await;
+    assert_eq!(next.kind, v1::NextAnchorKind::Regular);
+    assert_eq!(next.anchoring_height, Some(Height(anchoring_interval)));
+    assert_eq!(next.blocks_remaining, anchoring_interval - 2);
+    assert_eq!(next.overdue_blocks, 0);
+    assert_eq!(
+        next.block_interval_ms,
+        anchoring_testkit
+            .inner
+            .consensus_config()
+            .max_propose_timeout
+    );
+    assert!(next.expected_time.is_some());
+
+    // Change the anchoring address, so that the transition transaction is expected.
+    let mut new_cfg = anchoring_testkit.actual_anchoring_config();
+    new_cfg.anchoring_keys.push(anchoring_testkit.add_node());
+    anchoring_testkit.prove_key_possession(&mut new_cfg);
+    anchoring_testkit.inner.create_block_with_transaction(
+        anchoring_testkit.create_config_change_tx(
+            ConfigPropose::new(0, anchoring_testkit.inner.height().next())
+                .service_config(ANCHORING_INSTANCE_ID, new_cfg),
+        ),
+    );
+    anchoring_testkit.inner.create_block();
+    let next = next_anchor(&anchoring_api).await;
+    assert_eq!(next.kind, v1::NextAnchorKind::Transition);
+    assert_eq!(next.anchoring_height, Some(Height(0)));
+    assert_eq!(next.blocks_remaining, 0);
+
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit
+            .create_signature_txs()
+            .into_iter()
+            .flatten(),
+    );
+    // The following configuration becomes actual along with the transition transaction.
+    let next = next_anchor(&anchoring_api).await;
+    assert_eq!(next.kind, v1::NextAnchorKind::Regular);
+    assert_eq!(next.anchoring_height, Some(Height(anchoring_interval)));
+}
+
+async fn anchor_stats(anchoring_api: &TestKitApi, count: Option<u64>) -> v1::AnchorStats {
+    anchoring_api
+        .public(ApiKind::Service(ANCHORING_INSTANCE_NAME))
+        .query(&v1::AnchorStatsQuery { count })
+        .get("v1/anchor-stats")
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn anchor_stats_v1() {
+    let (mut anchoring_testkit, anchoring_api) = init_testkit();
+    let anchoring_interval = anchoring_testkit
+        .actual_anchoring_config()
+        .anchoring_interval;
+
+    let stats = anchor_stats(&anchoring_api, None).await;
+    assert!(stats.records.is_empty());
+    assert_eq!(stats.summary, None);
+
+    for height in &[0, anchoring_interval] {
+        anchoring_testkit.inner.create_blocks_until(Height(*height));
+        anchoring_testkit.inner.create_block_with_transactions(
+            anchoring_testkit
+                .create_signature_txs()
+                .into_iter()
+                .flatten(),
+        );
+    }
+    let last_tx = anchoring_testkit.last_anchoring_tx().unwrap();
+    let history = balance_history(&anchoring_api, None).await;
+
+    let stats = anchor_stats(&anchoring_api, None).await;
+    assert_eq!(stats.records.len(), 2);
+    for (record, balance) in stats.records.iter().zip(&history.records) {
+        assert_eq!(record.fee, balance.fee);
+        assert_eq!(record.input_values.iter().sum::<u64>(), balance.input_value);
+        assert_eq!(
+            record.input_values.iter().sum::<u64>() - record.output_values.iter().sum::<u64>(),
+            record.fee
+        );
+        assert_eq!(record.output_values[0], balance.change);
+        assert_eq!(record.vsize, (record.weight + 3) / 4);
+        assert_eq!(record.fee_rate, record.fee * 1000 / record.vsize);
+    }
+    // The finalized transaction contains the witness data, so its virtual size is
+    // less than the size of the serialized transaction.
+    let record = &stats.records[1];
+    assert_eq!(record.txid, last_tx.id());
+    assert!(record.vsize < bitcoin::consensus::serialize(&last_tx.0).len() as u64);
+
+    let stats = anchor_stats(&anchoring_api, Some(1)).await;
+    assert_eq!(stats.records.len(), 1);
+    assert_eq!(stats.records[0].index, 1);
+    let summary = stats.summary.unwrap();
+    assert_eq!(summary.average_fee_rate, stats.records[0].fee_rate);
+    assert_eq!(summary.min_fee_rate, summary.max_fee_rate);
+    assert_eq!(summary.average_vsize, stats.records[0].vsize);
+}
+
+async fn anchors(anchoring_api: &TestKitApi, from: u64, count: Option<u64>) -> AnchorsExport {
+    anchoring_api
+        .public(ApiKind::Service(ANCHORING_INSTANCE_NAME))
+        .query(&v1::AnchorRangeQuery {
+            from: Some(from),
+            count,
+        })
+        .get("v1/anchors")
+        .await
+        .unwrap()
+}
+
+async fn anchor_proofs(
+    anchoring_api: &TestKitApi,
+    from: u64,
+    count: Option<u64>,
+) -> v1::AnchorProofs {
+    anchoring_api
+        .public(ApiKind::Service(ANCHORING_INSTANCE_NAME))
+        .query(&v1::AnchorRangeQuery {
+            from: Some(from),
+            count,
+        })
+        .get("v1/anchor-proofs")
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn anchors_export_v1() {
+    let (mut anchoring_testkit, anchoring_api) = init_testkit();
+    let anchoring_interval = anchoring_testkit
+        .actual_anchoring_config()
+        .anchoring_interval;
+
+    for height in &[0, anchoring_interval, anchoring_interval * 2] {
+        anchoring_testkit.inner.create_blocks_until(Height(*height));
+        anchoring_testkit.inner.create_block_with_transactions(
+            anchoring_testkit
+                .create_signature_txs()
+                .into_iter()
+                .flatten(),
+        );
+    }
+
+    // Export the anchoring chain page by page.
+    let mut records = Vec::new();
+    let mut from = Some(0);
+    while let Some(start) = from {
+        let page = anchors(&anchoring_api, start, Some(2)).await;
+        assert_eq!(page.from, start);
+        assert_eq!(page.transactions_count, 3);
+        assert!(page.records.len() <= 2);
+        records.extend(page.records.iter().cloned());
+        from = page.next_from();
+    }
+    assert_eq!(records.len(), 3);
+    for (index, record) in records.iter().enumerate() {
+        let tx = transaction_with_index(&anchoring_api, index as u64)
+            .await
+            .unwrap();
+        assert_eq!(record.index, index as u64);
+        assert_eq!(record.txid, tx.id());
+        assert_eq!(
+            record.block_height,
+            tx.anchoring_payload().unwrap().block_height
+        );
+    }
+
+    let validator_keys = anchoring_testkit
+        .inner
+        .consensus_config()
+        .validator_keys
+        .into_iter()
+        .map(|key| key.consensus_key)
+        .collect::<Vec<_>>();
+    let proofs = anchor_proofs(&anchoring_api, 1, Some(5)).await;
+    assert_eq!((proofs.from, proofs.to), (1, 3));
+    let transactions = verify_anchor_proofs(proofs, &validator_keys).unwrap();
+    assert_eq!(transactions.len(), 2);
+    for (index, tx) in transactions {
+        assert_eq!(records[index as usize].txid, tx.id());
+    }
+
+    // The page beyond the end of the anchoring chain is empty.
+    let proofs = anchor_proofs(&anchoring_api, 5, None).await;
+    assert_eq!((proofs.from, proofs.to), (3, 3));
+    assert!(verify_anchor_proofs(proofs, &validator_keys)
+        .unwrap()
+        .is_empty());
+}
+
+async fn finality_certificate(
+    anchoring_api: &TestKitApi,
+    tx_hash: Hash,
+) -> Option<v1::FinalityCertificate> {
+    anchoring_api
+        .public(ApiKind::Service(ANCHORING_INSTANCE_NAME))
+        .query(&v1::FinalityCertificateQuery { tx_hash })
+        .get("v1/finality-certificate")
+        .await
+        .ok()
+}
+
+#[tokio::test]
+async fn finality_certificate_v1() {
+    let (mut anchoring_testkit, anchoring_api) = init_testkit();
+    let anchoring_interval = anchoring_testkit
+        .actual_anchoring_config()
+        .anchoring_interval;
+
+    let signatures = anchoring_testkit
+        .create_signature_txs()
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>();
+    let tx_hash = signatures[0].object_hash();
+    anchoring_testkit
+        .inner
+        .create_block_with_transactions(signatures);
+
+    // Block with the transaction has not been anchored yet.
+    let certificate = finality_certificate(&anchoring_api, tx_hash).await.unwrap();
+    assert_eq!(certificate.tx_hash, tx_hash);
+    assert_eq!(certificate.location.block_height(), Height(2));
+    assert!(certificate.anchor.is_none());
+    assert!(certificate.bitcoin.is_none());
+
+    anchoring_testkit
+        .inner
+        .create_blocks_until(Height(anchoring_interval));
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit
+            .create_signature_txs()
+            .into_iter()
+            .flatten(),
+    );
+    let tx = anchoring_testkit.last_anchoring_tx().unwrap();
+
+    let certificate = finality_certificate(&anchoring_api, tx_hash).await.unwrap();
+    let anchor = certificate.anchor.unwrap();
+    assert_eq!(anchor.index, 1);
+    let validator_keys = anchoring_testkit
+        .inner
+        .consensus_config()
+        .validator_keys
+        .into_iter()
+        .map(|key| key.consensus_key)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        anchor.proof.validate(&validator_keys).unwrap(),
+        Some((1, tx))
+    );
+    assert_eq!(
+        certificate
+            .location_proof
+            .check_against_hash(certificate.block_proof.block.tx_hash)
+            .unwrap()
+            .entries()
+            .to_vec(),
+        vec![(u64::from(certificate.location.position_in_block()), tx_hash)]
+    );
+
+    // Unknown transaction.
+    assert!(finality_certificate(&anchoring_api, Hash::zero())
+        .await
+        .is_none());
+}
+
+async fn anchor_block(anchoring_api: &TestKitApi, txid: btc::Sha256d) -> Option<v1::AnchorBlock> {
+    anchoring_api
+        .public(ApiKind::Service(ANCHORING_INSTANCE_NAME))
+        .query(&v1::AnchorBlockQuery { txid })
+        .get("v1/anchor-block")
+        .await
+        .ok()
+}
+
+#[tokio::test]
+async fn anchor_block_v1() {
+    let (mut anchoring_testkit, anchoring_api) = init_testkit();
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit
+            .create_signature_txs()
+            .into_iter()
+            .flatten(),
+    );
+    let tx = anchoring_testkit.last_anchoring_tx().unwrap();
+
+    // Block of the transaction has not been recorded yet.
+    assert_eq!(
+        anchor_block(&anchoring_api, tx.id()).await,
+        Some(v1::AnchorBlock {
+            txid: tx.id(),
+            index: 0,
+            block_hash: None,
+        })
+    );
+
+    let funding_tx = create_fake_funding_transaction(
+        &anchoring_testkit
+            .actual_anchoring_config()
+            .anchoring_address(),
+        100,
+    );
+    let record = RecordAnchorBlock {
+        txid: tx.id(),
+        block_hash: funding_tx.id(),
+    };
+    anchoring_testkit
+        .inner
+        .create_block_with_transactions(anchoring_testkit.create_record_anchor_block_txs(record));
+    assert_eq!(
+        anchor_block(&anchoring_api, tx.id()).await,
+        Some(v1::AnchorBlock {
+            txid: tx.id(),
+            index: 0,
+            block_hash: Some(funding_tx.id()),
+        })
+    );
+
+    // Transaction is not a part of the anchoring chain.
+    assert!(anchor_block(&anchoring_api, funding_tx.id())
+        .await
+        .is_none());
+}
+
+async fn relay_status(anchoring_api: &TestKitApi) -> v1::RelayStatuses {
+    anchoring_api
+        .public(ApiKind::Service(ANCHORING_INSTANCE_NAME))
+        .get("v1/relay-status")
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn relay_status_v1() {
+    let (mut anchoring_testkit, anchoring_api) = init_testkit();
+    assert!(relay_status(&anchoring_api).await.reports.is_empty());
+
+    let report = ReportRelayStatus {
+        healthy: true,
+        block_height: 100,
+        failed_checks: 0,
+    };
+    anchoring_testkit
+        .inner
+        .create_block_with_transactions(anchoring_testkit.create_report_relay_status_txs(report));
+    let statuses = relay_status(&anchoring_api).await;
+    assert_eq!(statuses.healthy_nodes, 4);
+    assert_eq!(statuses.reports.len(), 4);
+    assert_eq!(statuses.reports[0].block_height, Some(100));
+
+    // The unhealthy relay without the known block height.
+    let report = ReportRelayStatus {
+        healthy: false,
+        block_height: 0,
+        failed_checks: 3,
+    };
+    anchoring_testkit
+        .inner
+        .create_block_with_transactions(anchoring_testkit.create_report_relay_status_txs(report));
+    let statuses = relay_status(&anchoring_api).await;
+    assert_eq!(statuses.healthy_nodes, 0);
+    assert_eq!(statuses.reports[0].block_height, None);
+    assert_eq!(statuses.reports[0].failed_checks, 3);
+}
+
+#[tokio::test]
+async fn tenant_receipt_v1() {
+    let tenant = KeyPair::random();
+    let mut anchoring_testkit = AnchoringTestKit::with_config(4, |config| {
+        config.tenants.push(Tenant {
+            key: tenant.public_key(),
+            quota: 10,
+        });
+    });
+    let anchoring_api = anchoring_testkit.inner.api();
+
+    let data_hashes = (0..3).map(|i| hash(&[i])).collect::<Vec<_>>();
+    anchoring_testkit.inner.create_block_with_transactions(
+        data_hashes
+            .iter()
+            .map(|&data_hash| tenant.submit_data(ANCHORING_INSTANCE_ID, SubmitData { data_hash })),
+    );
+
+    let usage: v1::TenantUsage = anchoring_api
+        .public(ApiKind::Service(ANCHORING_INSTANCE_NAME))
+        .query(&v1::TenantQuery {
+            tenant: tenant.public_key(),
+        })
+        .get("v1/tenant-usage")
+        .await
+        .unwrap();
+    assert_eq!(
+        usage,
+        v1::TenantUsage {
+            used: 3,
+            quota: Some(10)
+        }
+    );
+
+    let validator_keys = anchoring_testkit
+        .inner
+        .consensus_config()
+        .validator_keys
+        .into_iter()
+        .map(|key| key.consensus_key)
+        .collect::<Vec<_>>();
+    let receipt: v1::TenantReceipt = anchoring_api
+        .public(ApiKind::Service(ANCHORING_INSTANCE_NAME))
+        .query(&v1::TenantReceiptQuery {
+            tenant: tenant.public_key(),
+            height: Height(1),
+            data_hash: data_hashes[1],
+        })
+        .get("v1/tenant-receipt")
+        .await
+        .unwrap();
+    let (id, _, data_hash) = verify_tenant_receipt(receipt, &validator_keys)
+        .unwrap()
+        .unwrap();
+    assert_eq!(id, TenantBlockId::new(tenant.public_key(), Height(1)));
+    assert_eq!(data_hash, data_hashes[1]);
+
+    // Data has not been submitted at the given height.
+    anchoring_api
+        .public(ApiKind::Service(ANCHORING_INSTANCE_NAME))
+        .query(&v1::TenantReceiptQuery {
+            tenant: tenant.public_key(),
+            height: Height(2),
+            data_hash: data_hashes[1],
+        })
+        .get::<v1::TenantReceipt>("v1/tenant-receipt")
+        .await
+        .unwrap_err();
+}
+
+#[tokio::test]
+async fn anchoring_proposal_not_started() {
+    let mut anchoring_testkit = AnchoringTestKit::with_config(4, |config| {
+        config.start_height = Height(100);
+    });
+    let anchoring_api = anchoring_testkit.inner.api();
+
+    assert_eq!(
+        anchoring_api.client().anchoring_proposal().await.unwrap(),
+        AnchoringProposalState::NotStarted {
+            start_height: Height(100)
+        }
+    );
+}
+
 #[tokio::test]
 async fn anchoring_proposal_ok() {
     let (anchoring_testkit, anchoring_api) = init_testkit();
@@ -328,10 +1096,50 @@ async fn anchoring_proposal_ok() {
         AnchoringProposalState::Available {
             transaction: proposal.0,
             inputs: proposal.1,
+            epoch: 0,
         }
     );
 }
 
+#[tokio::test]
+async fn anchoring_proposal_halted() {
+    let (mut anchoring_testkit, anchoring_api) = init_testkit();
+    let (proposal, proposal_inputs) = anchoring_testkit.anchoring_transaction_proposal().unwrap();
+    let mut conflicting_tx = proposal.clone();
+    conflicting_tx.0.output[0].value -= 1;
+    // Two anchoring nodes sign the conflicting transaction.
+    let signatures = anchoring_testkit.inner.network().validators()[..2]
+        .iter()
+        .flat_map(|node| {
+            anchoring_testkit.create_signature_tx_for_transaction(
+                node,
+                &conflicting_tx,
+                &proposal_inputs,
+            )
+        })
+        .collect::<Vec<_>>();
+    anchoring_testkit
+        .inner
+        .create_block_with_transactions(signatures);
+
+    let conflict = match anchoring_api.client().anchoring_proposal().await.unwrap() {
+        AnchoringProposalState::Halted { conflict } => conflict,
+        state => panic!("Unexpected proposal state: {:?}", state),
+    };
+    assert_eq!(conflict.proposal_txid, proposal.id());
+    assert_eq!(conflict.conflicting_txid, conflicting_tx.id());
+
+    let conflicts: v1::ProposalConflicts = anchoring_api
+        .public(ApiKind::Service(ANCHORING_INSTANCE_NAME))
+        .get("v1/proposal-conflicts")
+        .await
+        .unwrap();
+    assert_eq!(conflicts.total, 2);
+    assert_eq!(conflicts.halt, Some(v1::ProposalConflict::from(conflict)));
+    assert_eq!(conflicts.conflicts.len(), 2);
+    assert_eq!(conflicts.conflicts.last(), conflicts.halt.as_ref());
+}
+
 #[tokio::test]
 async fn anchoring_proposal_none() {
     let (mut anchoring_testkit, anchoring_api) = init_testkit();
@@ -350,6 +1158,70 @@ async fn anchoring_proposal_none() {
     );
 }
 
+async fn proposal_preview(anchoring_api: &TestKitApi) -> v1::ProposalPreview {
+    anchoring_api
+        .public(ApiKind::Service(ANCHORING_INSTANCE_NAME))
+        .get("v1/preview-proposal")
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn preview_proposal_v1() {
+    let (mut anchoring_testkit, anchoring_api) = init_testkit();
+    let config = anchoring_testkit.actual_anchoring_config();
+
+    // The preview of the anchoring slot, which has been reached, matches the actual proposal.
+    let proposal = anchoring_testkit.anchoring_transaction_proposal().unwrap();
+    let preview = proposal_preview(&anchoring_api).await;
+    assert_eq!(preview.anchoring_height, Height(0));
+    assert_eq!(preview.next_anchoring_height, Height(0));
+    assert_eq!(
+        preview.proposal,
+        AnchoringProposalState::Available {
+            transaction: proposal.0.clone(),
+            inputs: proposal.1,
+            epoch: 0,
+        }
+    );
+    let summary = preview.summary.unwrap();
+    assert_eq!(summary.txid, proposal.0.id());
+    assert_eq!(summary.inputs.len(), 1);
+    assert_eq!(summary.balance, 700_000);
+    assert_eq!(summary.fee, summary.size * config.transaction_fee);
+    assert_eq!(summary.change, summary.balance - summary.fee);
+    assert_eq!(summary.payload.unwrap().block_height, Height(0));
+
+    // Establish anchoring transactions chain.
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit
+            .create_signature_txs()
+            .into_iter()
+            .flatten(),
+    );
+    anchoring_testkit.inner.create_blocks_until(Height(3));
+    let last_tx = anchoring_testkit.last_anchoring_tx().unwrap();
+
+    // Before the next anchoring slot the preview anchors the latest committed block.
+    let preview = proposal_preview(&anchoring_api).await;
+    assert_eq!(preview.anchoring_height, Height(3));
+    assert_eq!(
+        preview.next_anchoring_height,
+        Height(config.anchoring_interval)
+    );
+    let summary = preview.summary.unwrap();
+    assert_eq!(summary.inputs[0].txid, last_tx.id());
+    assert_eq!(summary.balance, last_tx.0.output[0].value);
+    assert_eq!(summary.payload.unwrap().block_height, Height(3));
+
+    // The preview does not create any service state.
+    assert_eq!(
+        anchoring_api.client().anchoring_proposal().await.unwrap(),
+        AnchoringProposalState::None
+    );
+    assert!(anchoring_testkit.anchoring_transaction_proposal().is_none());
+}
+
 #[tokio::test]
 async fn anchoring_proposal_err_without_initial_funds() {
     let mut anchoring_testkit = AnchoringTestKit::new(4, 5);
@@ -418,6 +1290,9 @@ async fn sign_input() {
             input: 0,
             input_signature: signature.into(),
             txid: proposal.id(),
+            anchoring_height: proposal.anchoring_payload().unwrap().block_height,
+            epoch: 0,
+            signed_transaction: Vec::new(),
         })
         .await
         .unwrap();
@@ -0,0 +1,226 @@
+// Copyright 2019 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Long-running soak test of the anchoring service with the chaos injection.
+//!
+//! The test is ignored by default, use the following command to run it:
+//!
+//! ```shell
+//! BTC_ANCHORING_SOAK_INTERVALS=5000 BTC_ANCHORING_SOAK_SEED=42 \
+//!     cargo test --release --test soak -- --ignored --nocapture
+//! ```
+
+use async_trait::async_trait;
+use exonum::helpers::Height;
+use exonum_btc_anchoring::{
+    btc,
+    sync::{
+        ChainQuery, RelayBackend, SyncWithBitcoinError, SyncWithBitcoinTask, TransactionStatus,
+        TxBroadcaster,
+    },
+    test_helpers::{
+        get_anchoring_schema, AnchoringTestKit, SimulatedChainRelay, SimulatedRelayError,
+        ANCHORING_INSTANCE_ID,
+    },
+};
+use exonum_supervisor::ConfigPropose;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use thiserror::Error;
+
+use std::{
+    env,
+    sync::{Arc, Mutex},
+};
+
+/// Default number of the simulated anchoring intervals.
+const DEFAULT_INTERVALS: u64 = 1_000;
+/// Probability of a Bitcoin RPC call failure.
+const RPC_FAILURE_PROBABILITY: f64 = 0.1;
+/// Probability of a Bitcoin chain reorganization within the anchoring interval.
+const REORG_PROBABILITY: f64 = 0.05;
+/// Probability of a validator crash within the anchoring interval.
+const CRASH_PROBABILITY: f64 = 0.1;
+/// Probability of an anchoring configuration change within the anchoring interval.
+const CONFIG_CHANGE_PROBABILITY: f64 = 0.02;
+
+#[derive(Debug, Error)]
+enum ChaosRelayError {
+    #[error("Injected Bitcoin RPC failure.")]
+    Injected,
+    #[error("{0}")]
+    Relay(#[from] SimulatedRelayError),
+}
+
+/// Simulated Bitcoin relay, which randomly fails the RPC calls.
+#[derive(Debug, Clone)]
+struct ChaosRelay {
+    inner: SimulatedChainRelay,
+    rng: Arc<Mutex<StdRng>>,
+}
+
+impl ChaosRelay {
+    fn inject_failure(&self) -> Result<(), ChaosRelayError> {
+        if self.rng.lock().unwrap().gen_bool(RPC_FAILURE_PROBABILITY) {
+            Err(ChaosRelayError::Injected)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl RelayBackend for ChaosRelay {
+    type Error = ChaosRelayError;
+}
+
+#[async_trait]
+impl TxBroadcaster for ChaosRelay {
+    async fn send_transaction(
+        &self,
+        transaction: &btc::Transaction,
+    ) -> Result<btc::Sha256d, Self::Error> {
+        self.inject_failure()?;
+        Ok(self.inner.send_transaction(transaction).await?)
+    }
+}
+
+#[async_trait]
+impl ChainQuery for ChaosRelay {
+    async fn transaction_status(&self, id: btc::Sha256d) -> Result<TransactionStatus, Self::Error> {
+        self.inject_failure()?;
+        Ok(self.inner.transaction_status(id).await?)
+    }
+}
+
+fn env_or_default(name: &str, default: u64) -> u64 {
+    env::var(name)
+        .ok()
+        .map(|value| value.parse().expect("Unable to parse environment variable"))
+        .unwrap_or(default)
+}
+
+/// Checks the invariants of the anchoring chain.
+fn assert_chain_invariants(testkit: &AnchoringTestKit) {
+    let snapshot = testkit.inner.snapshot();
+    let schema = get_anchoring_schema(&snapshot);
+    let interval = testkit.actual_anchoring_config().anchoring_interval;
+
+    let mut previous: Option<btc::Transaction> = None;
+    for tx in schema.transactions_chain.iter() {
+        let payload = tx.anchoring_payload().unwrap();
+        assert_eq!(
+            payload.prev_tx_chain, None,
+            "Anchoring chain has been broken"
+        );
+        assert_eq!(payload.block_height.0 % interval, 0);
+        if let Some(previous) = previous {
+            assert_eq!(tx.prev_tx_id(), previous.id());
+            assert!(payload.block_height >= previous.anchoring_payload().unwrap().block_height);
+        }
+        previous = Some(tx);
+    }
+}
+
+#[tokio::test]
+#[ignore]
+async fn soak_with_chaos() {
+    let intervals = env_or_default("BTC_ANCHORING_SOAK_INTERVALS", DEFAULT_INTERVALS);
+    let seed = env_or_default("BTC_ANCHORING_SOAK_SEED", rand::random());
+    println!("Soak test with {} intervals, seed is {}", intervals, seed);
+
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut testkit = AnchoringTestKit::new(4, 5);
+    let anchoring_interval = testkit.actual_anchoring_config().anchoring_interval;
+    let simulated_chain = SimulatedChainRelay::default();
+    let relay = ChaosRelay {
+        inner: simulated_chain.clone(),
+        rng: Arc::new(Mutex::new(StdRng::seed_from_u64(rng.gen()))),
+    };
+
+    let (txs, funding_tx) = testkit.create_funding_confirmation_txs(1_000_000_000);
+    simulated_chain.fund(funding_tx);
+    testkit.inner.create_block_with_transactions(txs);
+
+    let api = testkit.inner.api();
+    let sync = SyncWithBitcoinTask::new(relay.clone(), api.client().clone());
+    let mut latest_synced_tx_index = None;
+    let mut config_number = 0;
+
+    for interval in 0..intervals {
+        // Alive validators sign the anchoring proposal, if any.
+        let signatures = testkit
+            .create_signature_txs()
+            .into_iter()
+            .filter(|_| !rng.gen_bool(CRASH_PROBABILITY))
+            .flatten()
+            .collect::<Vec<_>>();
+        testkit.inner.create_block_with_transactions(signatures);
+
+        // Change the transaction fee without the change of the anchoring address.
+        if rng.gen_bool(CONFIG_CHANGE_PROBABILITY) {
+            let mut config = testkit.actual_anchoring_config();
+            config.transaction_fee = rng.gen_range(10, 50);
+            testkit.inner.create_block_with_transaction(
+                testkit.create_config_change_tx(
+                    ConfigPropose::new(config_number, testkit.inner.height().next())
+                        .service_config(ANCHORING_INSTANCE_ID, config.clone()),
+                ),
+            );
+            testkit.inner.create_block();
+            assert_eq!(testkit.actual_anchoring_config(), config);
+            config_number += 1;
+        }
+
+        match sync.process(latest_synced_tx_index).await {
+            Ok(index) => latest_synced_tx_index = index,
+            Err(SyncWithBitcoinError::Relay(ChaosRelayError::Injected)) => {}
+            Err(e) => panic!("Unexpected error at interval {}: {:?}", interval, e),
+        }
+
+        simulated_chain.mine_block();
+        if rng.gen_bool(REORG_PROBABILITY) {
+            simulated_chain.reorg(rng.gen_range(1, 4));
+        }
+
+        assert_chain_invariants(&testkit);
+        let next_height = Height(testkit.inner.height().0 + anchoring_interval);
+        testkit
+            .inner
+            .create_blocks_until(Height(next_height.0 - next_height.0 % anchoring_interval));
+    }
+
+    // Anchoring should make progress despite the failures.
+    let chain_len = get_anchoring_schema(&testkit.inner.snapshot())
+        .transactions_chain
+        .len();
+    assert!(
+        chain_len >= intervals / 2,
+        "Only {} anchors for {} intervals",
+        chain_len,
+        intervals
+    );
+
+    // After the chaos stops, all anchoring transactions should be committed.
+    let sync = SyncWithBitcoinTask::new(simulated_chain.clone(), api.client().clone());
+    while latest_synced_tx_index.map_or(true, |index| index + 1 < chain_len) {
+        latest_synced_tx_index = sync.process(latest_synced_tx_index).await.unwrap();
+    }
+    simulated_chain.mine_block();
+    let snapshot = testkit.inner.snapshot();
+    for tx in get_anchoring_schema(&snapshot).transactions_chain.iter() {
+        match simulated_chain.transaction_status(tx.id()).await.unwrap() {
+            TransactionStatus::Committed(_) => {}
+            status => panic!("Anchoring transaction {} is {:?}", tx.id(), status),
+        }
+    }
+}
@@ -20,22 +20,33 @@ use exonum::{
     messages::{AnyTx, Verified},
 };
 use exonum_btc_anchoring::{
-    api::{AnchoringChainLength, AnchoringProposalState, PrivateApi},
-    blockchain::{AddFunds, BtcAnchoringInterface, SignInput},
-    btc,
+    api::{v1, AnchoringChainLength, AnchoringProposalState, PrivateApi},
+    blockchain::{
+        AddFunds, BtcAnchoringInterface, ConfirmAnchorFinality, ImportAnchor, RecordAnchorBlock,
+        ReportAnchorStatus, ReportRelayStatus, SignInput, SubmitAnchoringKey, UpdateParameters,
+    },
+    btc::{self, BtcAnchoringTransactionBuilder},
     config::Config,
     sync::{
-        AnchoringChainUpdateTask, BitcoinRelay, ChainUpdateError, SyncWithBitcoinError,
-        SyncWithBitcoinTask, TransactionStatus,
+        AnchoringChainUpdateTask, BlockingRelay, CachedRelay, ChainQuery, ChainUpdateError,
+        ComposedRelay, FeePolicy, MaxFeePolicy, RehearsalMirror, RelayBackend, ShutdownSignal,
+        SigningMarker, SyncWithBitcoinError, SyncWithBitcoinTask, TransactionStatus, TxBroadcaster,
+        WatchOnlyToggle,
+    },
+    test_helpers::{
+        create_fake_funding_transaction, get_anchoring_schema, AnchoringTestKit,
+        SimulatedChainRelay, SimulatedRelayError, ANCHORING_INSTANCE_ID, ANCHORING_INSTANCE_NAME,
     },
-    test_helpers::{get_anchoring_schema, AnchoringTestKit, ANCHORING_INSTANCE_ID},
 };
 use exonum_rust_runtime::api;
-use exonum_testkit::TestKitApiClient;
+use exonum_supervisor::ConfigPropose;
+use exonum_testkit::{ApiKind, TestKitApi, TestKitApiClient};
 
 use std::{
     collections::VecDeque,
+    env, fs,
     sync::{Arc, Mutex},
+    time::Duration,
 };
 
 #[derive(Debug, Clone)]
@@ -105,10 +116,12 @@ impl Drop for FakeBitcoinRelay {
     }
 }
 
-#[async_trait]
-impl BitcoinRelay for FakeBitcoinRelay {
+impl RelayBackend for FakeBitcoinRelay {
     type Error = anyhow::Error;
+}
 
+#[async_trait]
+impl TxBroadcaster for FakeBitcoinRelay {
     async fn send_transaction(
         &self,
         transaction: &btc::Transaction,
@@ -117,7 +130,10 @@ impl BitcoinRelay for FakeBitcoinRelay {
         assert_eq!(&expected_request, transaction, "Unexpected data in request");
         Ok(response)
     }
+}
 
+#[async_trait]
+impl ChainQuery for FakeBitcoinRelay {
     async fn transaction_status(&self, id: btc::Sha256d) -> Result<TransactionStatus, Self::Error> {
         let (expected_request, response) = self.dequeue_request().into_transaction_status();
         assert_eq!(expected_request, id, "Unexpected data in request");
@@ -184,6 +200,15 @@ impl PrivateApi for FakePrivateApi {
         Ok(hash)
     }
 
+    async fn import_anchor(&self, transaction: btc::Transaction) -> Result<Hash, Self::Error> {
+        let signed_tx = self
+            .service_keypair
+            .import_anchor(ANCHORING_INSTANCE_ID, ImportAnchor { transaction });
+        let hash = signed_tx.object_hash();
+        self.send(signed_tx).await;
+        Ok(hash)
+    }
+
     async fn anchoring_proposal(&self) -> Result<AnchoringProposalState, Self::Error> {
         self.client.anchoring_proposal().await
     }
@@ -202,6 +227,63 @@ impl PrivateApi for FakePrivateApi {
     async fn transactions_count(&self) -> Result<AnchoringChainLength, Self::Error> {
         self.client.transactions_count().await
     }
+
+    async fn submit_anchoring_key(
+        &self,
+        submit_key: SubmitAnchoringKey,
+    ) -> Result<Hash, Self::Error> {
+        let signed_tx = self
+            .service_keypair
+            .submit_anchoring_key(ANCHORING_INSTANCE_ID, submit_key);
+        let hash = signed_tx.object_hash();
+        self.send(signed_tx).await;
+        Ok(hash)
+    }
+
+    async fn update_parameters(&self, parameters: UpdateParameters) -> Result<Hash, Self::Error> {
+        let signed_tx = self
+            .service_keypair
+            .update_parameters(ANCHORING_INSTANCE_ID, parameters);
+        let hash = signed_tx.object_hash();
+        self.send(signed_tx).await;
+        Ok(hash)
+    }
+
+    async fn confirm_anchor_finality(&self, txid: btc::Sha256d) -> Result<Hash, Self::Error> {
+        let signed_tx = self
+            .service_keypair
+            .confirm_anchor_finality(ANCHORING_INSTANCE_ID, ConfirmAnchorFinality { txid });
+        let hash = signed_tx.object_hash();
+        self.send(signed_tx).await;
+        Ok(hash)
+    }
+
+    async fn report_anchor_status(&self, report: ReportAnchorStatus) -> Result<Hash, Self::Error> {
+        let signed_tx = self
+            .service_keypair
+            .report_anchor_status(ANCHORING_INSTANCE_ID, report);
+        let hash = signed_tx.object_hash();
+        self.send(signed_tx).await;
+        Ok(hash)
+    }
+
+    async fn record_anchor_block(&self, record: RecordAnchorBlock) -> Result<Hash, Self::Error> {
+        let signed_tx = self
+            .service_keypair
+            .record_anchor_block(ANCHORING_INSTANCE_ID, record);
+        let hash = signed_tx.object_hash();
+        self.send(signed_tx).await;
+        Ok(hash)
+    }
+
+    async fn report_relay_status(&self, report: ReportRelayStatus) -> Result<Hash, Self::Error> {
+        let signed_tx = self
+            .service_keypair
+            .report_relay_status(ANCHORING_INSTANCE_ID, report);
+        let hash = signed_tx.object_hash();
+        self.send(signed_tx).await;
+        Ok(hash)
+    }
 }
 
 fn anchoring_transaction_payload(testkit: &AnchoringTestKit, index: u64) -> Option<btc::Payload> {
@@ -294,6 +376,201 @@ async fn chain_updater_insufficient_funds() {
     }
 }
 
+#[tokio::test]
+async fn chain_updater_signing_policy() {
+    let mut testkit = AnchoringTestKit::default();
+    let api = testkit.inner.api();
+
+    let anchoring_interval = testkit.actual_anchoring_config().anchoring_interval;
+    testkit
+        .inner
+        .create_blocks_until(Height(anchoring_interval));
+    // The total fee of the proposal is 1530 satoshis.
+    for keypair in testkit.anchoring_keypairs() {
+        let private_api =
+            FakePrivateApi::for_anchoring_node(&testkit, api.client().clone(), &keypair.0);
+
+        let e = AnchoringChainUpdateTask::new(vec![keypair], private_api)
+            .with_signing_policy(MaxFeePolicy { max_fee: 1000 })
+            .process()
+            .await
+            .unwrap_err();
+        match e {
+            ChainUpdateError::SigningRefused { reason, .. } => {
+                assert!(reason
+                    .to_string()
+                    .contains("exceeds the maximum allowed fee"))
+            }
+            e => panic!("Unexpected error occurred: {:?}", e),
+        }
+    }
+    testkit.inner.create_block();
+    // Make sure the anchoring proposal has not been signed.
+    assert!(anchoring_transaction_payload(&testkit, 0).is_none());
+
+    // Policy with the sufficient limit permits signing.
+    for keypair in testkit.anchoring_keypairs() {
+        let private_api =
+            FakePrivateApi::for_anchoring_node(&testkit, api.client().clone(), &keypair.0);
+
+        AnchoringChainUpdateTask::new(vec![keypair], private_api)
+            .with_signing_policy(MaxFeePolicy { max_fee: 2000 })
+            .process()
+            .await
+            .unwrap();
+    }
+    testkit.inner.create_block();
+    assert!(anchoring_transaction_payload(&testkit, 0).is_some());
+}
+
+#[tokio::test]
+async fn chain_updater_watch_only() {
+    let mut testkit = AnchoringTestKit::default();
+    let api = testkit.inner.api();
+
+    let anchoring_interval = testkit.actual_anchoring_config().anchoring_interval;
+    testkit
+        .inner
+        .create_blocks_until(Height(anchoring_interval));
+
+    let toggle = WatchOnlyToggle::new(true);
+    let updaters = testkit
+        .anchoring_keypairs()
+        .into_iter()
+        .map(|keypair| {
+            let private_api =
+                FakePrivateApi::for_anchoring_node(&testkit, api.client().clone(), &keypair.0);
+            AnchoringChainUpdateTask::new(vec![keypair], private_api)
+                .with_watch_only(toggle.clone())
+        })
+        .collect::<Vec<_>>();
+    // Nodes in the watch-only mode verify the proposal, but do not sign it.
+    for updater in &updaters {
+        updater.process().await.unwrap();
+    }
+    testkit.inner.create_block();
+    assert!(anchoring_transaction_payload(&testkit, 0).is_none());
+
+    // Turn the watch-only mode off.
+    assert!(toggle.set(false));
+    for updater in &updaters {
+        assert!(!updater.watch_only().is_enabled());
+        updater.process().await.unwrap();
+    }
+    testkit.inner.create_block();
+    assert!(anchoring_transaction_payload(&testkit, 0).is_some());
+}
+
+#[tokio::test]
+async fn chain_updater_shutdown() {
+    let mut testkit = AnchoringTestKit::default();
+    let api = testkit.inner.api();
+
+    let anchoring_interval = testkit.actual_anchoring_config().anchoring_interval;
+    testkit
+        .inner
+        .create_blocks_until(Height(anchoring_interval));
+
+    let shutdown = ShutdownSignal::default();
+    shutdown.request();
+    // Nodes do not submit signatures after the shutdown request.
+    for keypair in testkit.anchoring_keypairs() {
+        let private_api =
+            FakePrivateApi::for_anchoring_node(&testkit, api.client().clone(), &keypair.0);
+        AnchoringChainUpdateTask::new(vec![keypair], private_api)
+            .with_shutdown(shutdown.clone())
+            .process()
+            .await
+            .unwrap();
+    }
+    testkit.inner.create_block();
+    assert!(anchoring_transaction_payload(&testkit, 0).is_none());
+}
+
+#[tokio::test]
+async fn chain_updater_max_signatures_per_round() {
+    let mut testkit = AnchoringTestKit::default();
+    let api = testkit.inner.api();
+    // Create the first anchoring transaction and add funds, so the following proposal
+    // has two inputs.
+    testkit
+        .inner
+        .create_block_with_transactions(testkit.create_signature_txs().into_iter().flatten());
+    testkit
+        .inner
+        .create_block_with_transactions(testkit.create_funding_confirmation_txs(100_000).0);
+    let anchoring_interval = testkit.actual_anchoring_config().anchoring_interval;
+    testkit
+        .inner
+        .create_blocks_until(Height(anchoring_interval));
+    let (proposal, inputs) = testkit.anchoring_transaction_proposal().unwrap();
+    assert_eq!(inputs.len(), 2);
+
+    let updaters = testkit
+        .anchoring_keypairs()
+        .into_iter()
+        .map(|keypair| {
+            let private_api =
+                FakePrivateApi::for_anchoring_node(&testkit, api.client().clone(), &keypair.0);
+            AnchoringChainUpdateTask::new(vec![keypair], private_api)
+                .with_max_signatures_per_round(1)
+        })
+        .collect::<Vec<_>>();
+    // Only the first input is signed within the first round.
+    for updater in &updaters {
+        updater.process().await.unwrap();
+    }
+    testkit.inner.create_block();
+
+    let status: v1::AnchoringStatus = api
+        .public(ApiKind::Service(ANCHORING_INSTANCE_NAME))
+        .get("v1/status")
+        .await
+        .unwrap();
+    let progress = status.signing_progress.unwrap();
+    assert_eq!(progress.txid, proposal.id());
+    assert_eq!(progress.input_signatures, vec![3, 0]);
+    assert_eq!(progress.quorum, 3);
+
+    // The second input is signed within the following round.
+    for updater in &updaters {
+        updater.process().await.unwrap();
+    }
+    testkit.inner.create_block();
+    assert_eq!(testkit.last_anchoring_tx().unwrap().id(), proposal.id());
+}
+
+#[tokio::test]
+async fn chain_updater_signing_marker() {
+    let mut testkit = AnchoringTestKit::default();
+    let api = testkit.inner.api();
+
+    let anchoring_interval = testkit.actual_anchoring_config().anchoring_interval;
+    testkit
+        .inner
+        .create_blocks_until(Height(anchoring_interval));
+
+    let marker_path = env::temp_dir().join("btc_anchoring_signing_marker.json");
+    let _ = fs::remove_file(&marker_path);
+
+    let keypair = testkit.anchoring_keypairs()[0].clone();
+    let create_updater = |testkit: &AnchoringTestKit| {
+        let private_api =
+            FakePrivateApi::for_anchoring_node(testkit, api.client().clone(), &keypair.0);
+        AnchoringChainUpdateTask::new(vec![keypair.clone()], private_api)
+            .with_signing_marker(SigningMarker::open(&marker_path).unwrap())
+    };
+    create_updater(&testkit).process().await.unwrap();
+    assert_eq!(testkit.inner.create_block().len(), 1);
+
+    // The restarted node does not resubmit the recorded signature.
+    let updater = create_updater(&testkit);
+    updater.process().await.unwrap();
+    assert_eq!(testkit.inner.create_block().len(), 0);
+
+    fs::remove_file(&marker_path).unwrap();
+}
+
 #[tokio::test]
 async fn sync_with_bitcoin_normal() {
     let mut testkit = AnchoringTestKit::default();
@@ -421,3 +698,569 @@ async fn sync_with_bitcoin_err_unconfirmed_funding_tx() {
         e => panic!("Unexpected error occurred: {:?}", e),
     }
 }
+
+#[tokio::test]
+async fn sync_with_bitcoin_dry_run() {
+    let mut testkit = AnchoringTestKit::default();
+    // Establish anchoring transactions chain.
+    testkit
+        .inner
+        .create_block_with_transactions(testkit.create_signature_txs().into_iter().flatten());
+
+    let snapshot = testkit.inner.snapshot();
+    let anchoring_schema = get_anchoring_schema(&snapshot);
+    let tx_chain = anchoring_schema.transactions_chain;
+
+    let fake_relay = FakeBitcoinRelay::default();
+    let api = testkit.inner.api();
+    let sync =
+        SyncWithBitcoinTask::new(fake_relay.clone(), api.client().clone()).with_dry_run(true);
+    // Relay should not receive the `send_transaction` request.
+    fake_relay.enqueue_requests(vec![
+        FakeRelayRequest::TransactionStatus {
+            request: tx_chain.get(0).unwrap().id(),
+            response: TransactionStatus::Unknown,
+        },
+        FakeRelayRequest::TransactionStatus {
+            request: tx_chain.get(0).unwrap().prev_tx_id(),
+            response: TransactionStatus::Committed(10),
+        },
+    ]);
+    assert_eq!(sync.process(None).await.unwrap(), Some(0));
+    // Simulated transaction should be considered as known.
+    assert_eq!(sync.process(Some(0)).await.unwrap(), Some(0));
+}
+
+#[tokio::test]
+async fn sync_with_bitcoin_shutdown() {
+    let mut testkit = AnchoringTestKit::default();
+    // Establish anchoring transactions chain.
+    testkit
+        .inner
+        .create_block_with_transactions(testkit.create_signature_txs().into_iter().flatten());
+
+    let snapshot = testkit.inner.snapshot();
+    let anchoring_schema = get_anchoring_schema(&snapshot);
+    let tx_chain = anchoring_schema.transactions_chain;
+
+    let fake_relay = FakeBitcoinRelay::default();
+    let api = testkit.inner.api();
+    let shutdown = ShutdownSignal::default();
+    let sync = SyncWithBitcoinTask::new(fake_relay.clone(), api.client().clone())
+        .with_shutdown(shutdown.clone());
+    shutdown.request();
+    // Relay should not receive the `send_transaction` request after the shutdown request.
+    fake_relay.enqueue_requests(vec![
+        FakeRelayRequest::TransactionStatus {
+            request: tx_chain.get(0).unwrap().id(),
+            response: TransactionStatus::Unknown,
+        },
+        FakeRelayRequest::TransactionStatus {
+            request: tx_chain.get(0).unwrap().prev_tx_id(),
+            response: TransactionStatus::Committed(10),
+        },
+    ]);
+    assert_eq!(sync.process(None).await.unwrap(), None);
+}
+
+#[tokio::test]
+async fn sync_with_cached_relay() {
+    let mut testkit = AnchoringTestKit::default();
+    // Establish anchoring transactions chain.
+    testkit
+        .inner
+        .create_block_with_transactions(testkit.create_signature_txs().into_iter().flatten());
+    let txid = get_anchoring_schema(&testkit.inner.snapshot())
+        .transactions_chain
+        .get(0)
+        .unwrap()
+        .id();
+
+    let fake_relay = FakeBitcoinRelay::default();
+    let api = testkit.inner.api();
+    let sync = SyncWithBitcoinTask::new(CachedRelay::new(fake_relay.clone()), api.client().clone());
+    // The mempool status is requested again, but the committed one is taken from the cache.
+    fake_relay.enqueue_requests(vec![
+        FakeRelayRequest::TransactionStatus {
+            request: txid,
+            response: TransactionStatus::Mempool,
+        },
+        FakeRelayRequest::TransactionStatus {
+            request: txid,
+            response: TransactionStatus::Committed(1),
+        },
+    ]);
+    for _ in 0..3 {
+        assert_eq!(sync.process(Some(0)).await.unwrap(), Some(0));
+    }
+
+    // The expired statuses are requested again.
+    let relay = CachedRelay::new(fake_relay.clone()).with_status_ttl(Duration::from_secs(0));
+    fake_relay.enqueue_requests(vec![
+        FakeRelayRequest::TransactionStatus {
+            request: txid,
+            response: TransactionStatus::Committed(1),
+        },
+        FakeRelayRequest::TransactionStatus {
+            request: txid,
+            response: TransactionStatus::Committed(2),
+        },
+    ]);
+    assert_eq!(
+        relay.transaction_status(txid).await.unwrap(),
+        TransactionStatus::Committed(1)
+    );
+    assert_eq!(
+        relay.transaction_status(txid).await.unwrap(),
+        TransactionStatus::Committed(2)
+    );
+}
+
+#[tokio::test]
+async fn cached_relay_transaction_statuses() {
+    let mut testkit = AnchoringTestKit::default();
+    testkit
+        .inner
+        .create_block_with_transactions(testkit.create_signature_txs().into_iter().flatten());
+    let tx = get_anchoring_schema(&testkit.inner.snapshot())
+        .transactions_chain
+        .get(0)
+        .unwrap();
+    let (txid, prev_txid) = (tx.id(), tx.prev_tx_id());
+
+    let fake_relay = FakeBitcoinRelay::default();
+    let relay = CachedRelay::new(fake_relay.clone());
+    fake_relay.enqueue_requests(vec![
+        FakeRelayRequest::TransactionStatus {
+            request: prev_txid,
+            response: TransactionStatus::Committed(2),
+        },
+        // Only the status missing in the cache is requested.
+        FakeRelayRequest::TransactionStatus {
+            request: txid,
+            response: TransactionStatus::Mempool,
+        },
+    ]);
+    assert_eq!(
+        relay.transaction_status(prev_txid).await.unwrap(),
+        TransactionStatus::Committed(2)
+    );
+    assert_eq!(
+        relay
+            .transaction_statuses(&[prev_txid, txid])
+            .await
+            .unwrap(),
+        vec![TransactionStatus::Committed(2), TransactionStatus::Mempool]
+    );
+}
+
+#[tokio::test]
+async fn sync_with_composed_relay() {
+    let mut testkit = AnchoringTestKit::default();
+    // Establish anchoring transactions chain.
+    testkit
+        .inner
+        .create_block_with_transactions(testkit.create_signature_txs().into_iter().flatten());
+    let tx = get_anchoring_schema(&testkit.inner.snapshot())
+        .transactions_chain
+        .get(0)
+        .unwrap();
+
+    let broadcaster = FakeBitcoinRelay::default();
+    let query = FakeBitcoinRelay::default();
+    let api = testkit.inner.api();
+    let sync = SyncWithBitcoinTask::new(
+        ComposedRelay::new(broadcaster.clone(), query.clone()),
+        api.client().clone(),
+    );
+    // The statuses are requested from the query backend, and the transaction is sent
+    // via the broadcasting one.
+    query.enqueue_requests(vec![
+        FakeRelayRequest::TransactionStatus {
+            request: tx.id(),
+            response: TransactionStatus::Unknown,
+        },
+        FakeRelayRequest::TransactionStatus {
+            request: tx.prev_tx_id(),
+            response: TransactionStatus::Committed(10),
+        },
+    ]);
+    broadcaster.enqueue_requests(vec![FakeRelayRequest::SendTransaction {
+        request: tx.clone(),
+        response: tx.id(),
+    }]);
+    assert_eq!(sync.process(None).await.unwrap(), Some(0));
+}
+
+async fn simulated_transaction_status(
+    testkit: &AnchoringTestKit,
+    relay: &SimulatedChainRelay,
+    index: u64,
+) -> TransactionStatus {
+    let tx = get_anchoring_schema(&testkit.inner.snapshot())
+        .transactions_chain
+        .get(index)
+        .unwrap();
+    relay.transaction_status(tx.id()).await.unwrap()
+}
+
+#[tokio::test]
+async fn sync_with_simulated_chain() {
+    let (mut testkit, relay) = AnchoringTestKit::new_without_rpc(4, 5);
+    let api = testkit.inner.api();
+    let sync = SyncWithBitcoinTask::new(relay.clone(), api.client().clone());
+
+    // Send the first anchoring transaction and mine it.
+    testkit
+        .inner
+        .create_block_with_transactions(testkit.create_signature_txs().into_iter().flatten());
+    let index = sync.process(None).await.unwrap();
+    assert_eq!(index, Some(0));
+    assert_eq!(
+        simulated_transaction_status(&testkit, &relay, 0).await,
+        TransactionStatus::Mempool
+    );
+    relay.mine_block();
+    assert_eq!(
+        simulated_transaction_status(&testkit, &relay, 0).await,
+        TransactionStatus::Committed(1)
+    );
+
+    // Change the anchoring address and send the transition transaction.
+    let mut new_cfg = testkit.actual_anchoring_config();
+    new_cfg.anchoring_keys.push(testkit.add_node());
+    testkit.prove_key_possession(&mut new_cfg);
+    testkit.inner.create_block_with_transaction(
+        testkit.create_config_change_tx(
+            ConfigPropose::new(0, testkit.inner.height().next())
+                .service_config(ANCHORING_INSTANCE_ID, new_cfg.clone()),
+        ),
+    );
+    testkit
+        .inner
+        .create_block_with_transactions(testkit.create_signature_txs().into_iter().flatten());
+    assert_eq!(testkit.actual_anchoring_config(), new_cfg);
+
+    // The transition transaction is evicted from the mempool due to the rising fee market
+    // and cannot be sent until the fee market calms down.
+    assert_eq!(sync.process(index).await.unwrap(), Some(1));
+    relay.set_min_fee_rate(100);
+    assert_eq!(
+        simulated_transaction_status(&testkit, &relay, 1).await,
+        TransactionStatus::Unknown
+    );
+    match sync.process(Some(1)).await.unwrap_err() {
+        SyncWithBitcoinError::Relay(SimulatedRelayError::FeeTooLow { .. }) => {}
+        e => panic!("Unexpected error occurred: {:?}", e),
+    }
+    relay.set_min_fee_rate(1);
+    assert_eq!(sync.process(Some(1)).await.unwrap(), Some(1));
+
+    // The transition transaction returns to the mempool after the reorganization.
+    relay.mine_block();
+    relay.reorg(1);
+    assert_eq!(
+        simulated_transaction_status(&testkit, &relay, 1).await,
+        TransactionStatus::Mempool
+    );
+    relay.mine_block();
+    assert_eq!(
+        simulated_transaction_status(&testkit, &relay, 0).await,
+        TransactionStatus::Committed(2)
+    );
+    assert_eq!(
+        simulated_transaction_status(&testkit, &relay, 1).await,
+        TransactionStatus::Committed(1)
+    );
+    relay.mine_blocks(5);
+    assert_eq!(
+        simulated_transaction_status(&testkit, &relay, 1).await,
+        TransactionStatus::Committed(6)
+    );
+}
+
+#[tokio::test]
+async fn adjust_transaction_fee_with_simulated_chain() {
+    let (mut testkit, relay) = AnchoringTestKit::new_without_rpc(2, 5);
+    relay.set_min_fee_rate(42);
+
+    let api = testkit.inner.api();
+    let sync =
+        SyncWithBitcoinTask::new(relay.clone(), api.client().clone()).with_fee_policy(FeePolicy {
+            target_blocks: 6,
+            min_fee_rate: 5,
+            max_fee_rate: 100,
+            fee_step: 5,
+        });
+    sync.process(None).await.unwrap();
+    testkit.inner.create_block();
+    // The parameters are applied after the approval of another anchoring node.
+    let us = testkit.inner.us().service_keypair().public_key();
+    let approval = testkit
+        .create_update_parameters_txs(UpdateParameters {
+            anchoring_interval: 0,
+            transaction_fee: 45,
+        })
+        .into_iter()
+        .find(|tx| tx.author() != us)
+        .unwrap();
+    testkit.inner.create_block_with_transaction(approval);
+    assert_eq!(testkit.actual_anchoring_config().transaction_fee, 45);
+
+    // The fee is not proposed again while it matches the estimate.
+    sync.process(None).await.unwrap();
+    assert!(testkit.inner.create_block().is_empty());
+}
+
+async fn anchor_status(api: &TestKitApi) -> v1::AnchorBroadcastStatus {
+    api.public(ApiKind::Service(ANCHORING_INSTANCE_NAME))
+        .get("v1/anchor-status")
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn report_anchor_status_with_simulated_chain() {
+    let (mut testkit, relay) = AnchoringTestKit::new_without_rpc(1, 5);
+    let api = testkit.inner.api();
+    let sync =
+        SyncWithBitcoinTask::new(relay.clone(), api.client().clone()).with_anchor_status_reports();
+
+    testkit
+        .inner
+        .create_block_with_transactions(testkit.create_signature_txs().into_iter().flatten());
+    let index = sync.process(None).await.unwrap();
+    assert_eq!(
+        anchor_status(&api).await.state,
+        v1::BroadcastState::NotReported
+    );
+
+    // The transaction is in the mempool.
+    sync.process(index).await.unwrap();
+    testkit.inner.create_block();
+    let status = anchor_status(&api).await;
+    assert_eq!(status.state, v1::BroadcastState::Mempool);
+    assert_eq!(status.reports.len(), 1);
+    // The unchanged status is not reported again.
+    sync.process(index).await.unwrap();
+    assert!(testkit.inner.create_block().is_empty());
+
+    // The transaction is evicted and cannot be rebroadcast until the fee market calms down.
+    relay.set_min_fee_rate(1_000);
+    sync.process(index).await.unwrap_err();
+    testkit.inner.create_block();
+    assert_eq!(anchor_status(&api).await.state, v1::BroadcastState::Missing);
+
+    relay.set_min_fee_rate(1);
+    sync.process(index).await.unwrap();
+    relay.mine_block();
+    sync.process(index).await.unwrap();
+    testkit.inner.create_block();
+    let status = anchor_status(&api).await;
+    assert_eq!(status.state, v1::BroadcastState::Confirmed);
+    assert_eq!(status.confirmations, 1);
+}
+
+async fn relay_status(api: &TestKitApi) -> v1::RelayStatuses {
+    api.public(ApiKind::Service(ANCHORING_INSTANCE_NAME))
+        .get("v1/relay-status")
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn relay_health_checks_with_simulated_chain() {
+    let (mut testkit, relay) = AnchoringTestKit::new_without_rpc(1, 5);
+    let api = testkit.inner.api();
+    let sync = SyncWithBitcoinTask::new(relay.clone(), api.client().clone())
+        .with_health_checks(Duration::from_secs(60));
+
+    // The healthy relay is reported once.
+    sync.process(None).await.unwrap();
+    testkit.inner.create_block();
+    let status = relay_status(&api).await;
+    assert_eq!(status.healthy_nodes, 1);
+    assert_eq!(status.reports[0].block_height, Some(1));
+    sync.process(None).await.unwrap();
+    assert!(testkit.inner.create_block().is_empty());
+
+    // The unreachable relay is checked on every attempt, and nothing is sent meanwhile.
+    testkit
+        .inner
+        .create_block_with_transactions(testkit.create_signature_txs().into_iter().flatten());
+    relay.set_reachable(false);
+    for failed_checks in 1..=2 {
+        match sync.process(None).await.unwrap_err() {
+            SyncWithBitcoinError::RelayUnavailable(status) => {
+                assert_eq!(status.failed_checks, failed_checks)
+            }
+            e => panic!("Unexpected error occurred: {:?}", e),
+        }
+    }
+    assert!(!sync.relay_status().healthy);
+    testkit.inner.create_block();
+    let status = relay_status(&api).await;
+    assert_eq!(status.healthy_nodes, 0);
+    assert_eq!(status.reports[0].failed_checks, 1);
+    assert!(relay.mempool().is_empty());
+
+    // The recovery is detected by the following attempt.
+    relay.set_reachable(true);
+    assert_eq!(sync.process(None).await.unwrap(), Some(0));
+    assert_eq!(relay.mempool().len(), 1);
+    testkit.inner.create_block();
+    assert_eq!(relay_status(&api).await.healthy_nodes, 1);
+}
+
+#[tokio::test]
+async fn reconcile_with_simulated_chain() {
+    let (mut testkit, relay) = AnchoringTestKit::new_without_rpc(4, 5);
+    testkit
+        .inner
+        .create_block_with_transactions(testkit.create_signature_txs().into_iter().flatten());
+
+    let api = testkit.inner.api();
+    let sync = SyncWithBitcoinTask::new(relay.clone(), api.client().clone());
+    // The unsent anchoring transaction is not a divergence.
+    let report = sync.reconcile(10).await.unwrap();
+    assert!(report.is_consistent());
+    assert_eq!(report.anchors[0].status, TransactionStatus::Unknown);
+
+    assert_eq!(sync.process(None).await.unwrap(), Some(0));
+    relay.mine_block();
+    let report = sync.reconcile(10).await.unwrap();
+    assert!(report.is_consistent());
+    assert_eq!(report.transactions_count, 1);
+    assert_eq!(report.anchors[0].index, 0);
+    assert_eq!(report.anchors[0].status, TransactionStatus::Committed(1));
+    assert!(sync.reconcile(0).await.unwrap().anchors.is_empty());
+}
+
+#[test]
+fn blocking_relay_with_simulated_chain() {
+    let mut testkit = AnchoringTestKit::new(4, 5);
+    let relay = BlockingRelay::new(SimulatedChainRelay::default()).unwrap();
+    let (txs, funding_tx) = testkit.create_funding_confirmation_txs(700_000);
+    relay.inner().fund(funding_tx);
+    testkit.inner.create_block_with_transactions(txs);
+    testkit
+        .inner
+        .create_block_with_transactions(testkit.create_signature_txs().into_iter().flatten());
+    let anchoring_tx = testkit.last_anchoring_tx().unwrap();
+    let txid = anchoring_tx.id();
+
+    assert_eq!(
+        relay.transaction_status(txid).unwrap(),
+        TransactionStatus::Unknown
+    );
+    assert_eq!(relay.send_transaction(&anchoring_tx).unwrap(), txid);
+    relay.inner().mine_block();
+    assert_eq!(
+        relay.transaction_status(txid).unwrap(),
+        TransactionStatus::Committed(1)
+    );
+    let outpoint = anchoring_tx.0.input[0].previous_output;
+    assert_eq!(
+        relay.spending_transaction(outpoint).unwrap(),
+        Some(anchoring_tx)
+    );
+}
+
+#[tokio::test]
+async fn sync_with_bitcoin_external_anchor() {
+    let mut testkit = AnchoringTestKit::new(4, 5);
+    let relay = SimulatedChainRelay::default();
+    let (txs, funding_tx) = testkit.create_funding_confirmation_txs(700_000);
+    relay.fund(funding_tx);
+    testkit.inner.create_block_with_transactions(txs);
+    testkit
+        .inner
+        .create_block_with_transactions(testkit.create_signature_txs().into_iter().flatten());
+
+    let api = testkit.inner.api();
+    let sync = SyncWithBitcoinTask::new(relay.clone(), api.client().clone());
+    assert_eq!(sync.process(None).await.unwrap(), Some(0));
+    relay.mine_block();
+
+    // Operators spend the anchoring output by the rescue transaction crafted manually.
+    testkit.inner.create_blocks_until(Height(5));
+    let latest_tx = testkit.last_anchoring_tx().unwrap();
+    let mut builder =
+        BtcAnchoringTransactionBuilder::new(&testkit.actual_anchoring_config().redeem_script());
+    builder.prev_tx(latest_tx).unwrap();
+    builder.payload(Height(5), testkit.block_hash_on_height(Height(5)));
+    builder.fee(100);
+    let rescue_tx = builder.create().unwrap().0;
+    relay.submit(rescue_tx.clone()).unwrap();
+    relay.mine_block();
+
+    // Anchoring nodes detect the rescue transaction and import it.
+    for keypair in testkit.anchoring_keypairs() {
+        let private_api =
+            FakePrivateApi::for_anchoring_node(&testkit, api.client().clone(), &keypair.0);
+        let sync = SyncWithBitcoinTask::new(relay.clone(), private_api);
+        assert_eq!(
+            sync.import_external_anchor().await.unwrap(),
+            Some(rescue_tx.id())
+        );
+        // The transaction is submitted only once.
+        assert_eq!(sync.import_external_anchor().await.unwrap(), None);
+    }
+    testkit.inner.create_block();
+    assert_eq!(testkit.last_anchoring_tx(), Some(rescue_tx));
+    assert_eq!(sync.process(Some(0)).await.unwrap(), Some(1));
+}
+
+#[tokio::test]
+async fn rehearsal_mirror() {
+    let mut testkit = AnchoringTestKit::default();
+    let api = testkit.inner.api();
+    testkit
+        .inner
+        .create_block_with_transactions(testkit.create_signature_txs().into_iter().flatten());
+
+    // Fund the rehearsal wallet in the test network.
+    let relay = SimulatedChainRelay::default();
+    let (public_key, private_key) = btc::gen_keypair(bitcoin::Network::Testnet);
+    let address = btc::Address(bitcoin::Address::p2wpkh(
+        &public_key.0,
+        bitcoin::Network::Testnet,
+    ));
+    let funding_tx = create_fake_funding_transaction(&address, 100_000);
+    relay.fund(funding_tx.clone());
+
+    let mirror = RehearsalMirror::new(
+        api.client().clone(),
+        relay.clone(),
+        private_key,
+        10,
+        funding_tx,
+    );
+    let txid = mirror.process().await.unwrap().unwrap();
+    assert_eq!(relay.status(txid), TransactionStatus::Mempool);
+    // There is nothing to mirror until the next anchor.
+    assert_eq!(mirror.process().await.unwrap(), None);
+
+    let state = mirror.state();
+    assert_eq!(state.latest_index, Some(0));
+    assert_eq!(
+        state.unspent_transaction.anchoring_payload(),
+        anchoring_transaction_payload(&testkit, 0)
+    );
+
+    // The following anchor is mirrored as well.
+    let anchoring_interval = testkit.actual_anchoring_config().anchoring_interval;
+    testkit
+        .inner
+        .create_blocks_until(Height(anchoring_interval));
+    testkit
+        .inner
+        .create_block_with_transactions(testkit.create_signature_txs().into_iter().flatten());
+    let next_txid = mirror.process().await.unwrap().unwrap();
+    assert_eq!(mirror.state().unspent_transaction.prev_tx_id(), txid);
+    assert_eq!(
+        mirror.state().unspent_transaction.anchoring_payload(),
+        anchoring_transaction_payload(&testkit, 1)
+    );
+    assert_eq!(relay.status(next_txid), TransactionStatus::Mempool);
+}
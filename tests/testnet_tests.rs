@@ -12,21 +12,29 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use bitcoin_hashes::{sha256d, Hash};
 use exonum::helpers::Height;
 use exonum::{
+    merkledb::ObjectHash,
     messages::{AnyTx, Verified},
     runtime::{ErrorMatch, SnapshotExt},
 };
 use exonum_btc_anchoring::{
-    blockchain::{errors::Error, BtcAnchoringInterface, SignInput},
-    btc::{self, BuilderError},
-    config::Config,
+    blockchain::{
+        data_layout::{FeeRecord, TenantBlockId, TxInputId},
+        errors::{Error, Rejection},
+        AddFunds, BtcAnchoringInterface, ConfirmAnchorFinality, RecordAnchorBlock,
+        RegisterStandbyKey, ReportAnchorStatus, SignInput, SubmitAnchoringKey, SubmitData,
+        UpdateParameters,
+    },
+    btc::{self, BtcAnchoringTransactionBuilder, BuilderError},
+    config::{AnchoringKeys, Config, OutputType, Tenant},
     test_helpers::{
-        create_fake_funding_transaction, get_anchoring_schema, AnchoringTestKit,
-        ANCHORING_INSTANCE_ID,
+        create_fake_funding_transaction, get_anchoring_schema, AnchoringTestKit, MalformedConfig,
+        SimulatedChainRelay, ANCHORING_INSTANCE_ID, ANCHORING_INSTANCE_NAME,
     },
 };
-use exonum_crypto::KeyPair;
+use exonum_crypto::{hash, KeyPair};
 use exonum_explorer::CommittedTransaction;
 use exonum_supervisor::ConfigPropose;
 
@@ -77,6 +85,7 @@ where
     let mut new_cfg = anchoring_testkit.actual_anchoring_config();
     let old_cfg = new_cfg.clone();
     config_change_predicate(&mut anchoring_testkit, &mut new_cfg);
+    anchoring_testkit.prove_key_possession(&mut new_cfg);
 
     // Commit configuration with without last anchoring node.
     anchoring_testkit.inner.create_block_with_transaction(
@@ -121,6 +130,7 @@ where
     let schema = get_anchoring_schema(&snapshot);
     assert!(schema.following_config().is_none());
     assert_eq!(schema.actual_config(), new_cfg);
+    assert_eq!(schema.anchoring_epoch(), 1);
 
     assert_eq!(
         anchoring_tx_proposal.id(),
@@ -255,6 +265,611 @@ fn additional_funding() {
     assert_eq!(tx1.0.input[1].previous_output.txid, new_funding_tx.0.txid());
 }
 
+#[test]
+fn anchored_heights_index() {
+    let mut anchoring_testkit = AnchoringTestKit::default();
+    let anchoring_interval = anchoring_testkit
+        .actual_anchoring_config()
+        .anchoring_interval;
+
+    for i in 1..=3 {
+        anchoring_testkit.inner.create_block_with_transactions(
+            anchoring_testkit
+                .create_signature_txs()
+                .into_iter()
+                .flatten(),
+        );
+        anchoring_testkit
+            .inner
+            .create_blocks_until(Height(anchoring_interval * i));
+    }
+
+    let snapshot = anchoring_testkit.inner.snapshot();
+    let schema = get_anchoring_schema(&snapshot);
+    assert_eq!(schema.find_transaction_index(Height(0)), Some(0));
+    assert_eq!(schema.find_transaction_index(Height(1)), Some(1));
+    assert_eq!(
+        schema.find_transaction_index(Height(anchoring_interval)),
+        Some(1)
+    );
+    assert_eq!(
+        schema.find_transaction_index(Height(anchoring_interval * 2)),
+        Some(2)
+    );
+    assert_eq!(
+        schema.find_transaction_index(Height(anchoring_interval * 2 + 1)),
+        None
+    );
+}
+
+#[test]
+fn delayed_start() {
+    let start_height = Height(12);
+    let mut anchoring_testkit = AnchoringTestKit::with_config(4, |config| {
+        config.anchoring_interval = 5;
+        config.start_height = start_height;
+    });
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit.create_funding_confirmation_txs(700_000).0,
+    );
+
+    // There is no anchoring proposal until the start height is reached.
+    assert!(anchoring_testkit.anchoring_transaction_proposal().is_none());
+    anchoring_testkit
+        .inner
+        .create_blocks_until(start_height.previous());
+    assert!(anchoring_testkit.anchoring_transaction_proposal().is_none());
+
+    anchoring_testkit.inner.create_block();
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit
+            .create_signature_txs()
+            .into_iter()
+            .flatten(),
+    );
+
+    let tx0 = anchoring_testkit.last_anchoring_tx().unwrap();
+    assert_eq!(tx0.anchoring_payload().unwrap().block_height, start_height);
+    // Then anchoring continues in the usual way.
+    assert_eq!(
+        anchoring_testkit
+            .anchoring_transaction_proposal()
+            .map(|(tx, _)| tx.anchoring_payload().unwrap().block_height),
+        None
+    );
+    anchoring_testkit.inner.create_blocks_until(Height(15));
+    assert_eq!(
+        anchoring_testkit
+            .anchoring_transaction_proposal()
+            .map(|(tx, _)| tx.anchoring_payload().unwrap().block_height),
+        Some(Height(15))
+    );
+}
+
+#[test]
+fn anchor_only_on_state_change() {
+    let mut anchoring_testkit = AnchoringTestKit::with_config(4, |config| {
+        config.anchoring_interval = 5;
+        config.anchor_only_on_state_change = true;
+    });
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit.create_funding_confirmation_txs(700_000).0,
+    );
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit
+            .create_signature_txs()
+            .into_iter()
+            .flatten(),
+    );
+    assert!(anchoring_testkit.last_anchoring_tx().is_some());
+
+    // Blockchain state has not been changed, so the anchoring height should be skipped.
+    anchoring_testkit.inner.create_blocks_until(Height(6));
+    assert!(anchoring_testkit.anchoring_transaction_proposal().is_none());
+    let snapshot = anchoring_testkit.inner.snapshot();
+    let schema = get_anchoring_schema(&snapshot);
+    assert_eq!(schema.skipped_anchoring_heights.last(), Some(5));
+    assert_eq!(schema.latest_anchoring_slot(), Some(Height(5)));
+
+    // Change the blockchain state by adding funds.
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit.create_funding_confirmation_txs(100_000).0,
+    );
+    anchoring_testkit.inner.create_blocks_until(Height(10));
+    assert_eq!(
+        anchoring_testkit
+            .anchoring_transaction_proposal()
+            .map(|(tx, _)| tx.anchoring_payload().unwrap().block_height),
+        Some(Height(10))
+    );
+}
+
+#[test]
+fn replay_anchoring_chain() {
+    // Establish anchoring chain with the transition to the new anchoring address.
+    let mut anchoring_testkit = test_anchoring_config_change(|anchoring_testkit, cfg| {
+        cfg.anchoring_keys.push(anchoring_testkit.add_node());
+    });
+    // Add another funding transaction and anchor the next block.
+    let (txs, _) = anchoring_testkit.create_funding_confirmation_txs(150_000);
+    anchoring_testkit.inner.create_block_with_transactions(txs);
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit
+            .create_signature_txs()
+            .into_iter()
+            .flatten(),
+    );
+
+    let snapshot = anchoring_testkit.inner.snapshot();
+    let schema = get_anchoring_schema(&snapshot);
+    assert_eq!(schema.transactions_chain.len(), 3);
+    assert_eq!(schema.transactions_chain.last().unwrap().0.input.len(), 2);
+
+    let report = schema.replay_anchoring_chain(snapshot.for_core());
+    assert_eq!(report.transactions_count, 3);
+    assert!(report.is_consistent(), "{:?}", report.mismatches);
+
+    // Signatures of all transactions, including the transition one, are valid.
+    let audit = schema.audit_signatures(0);
+    assert_eq!((audit.from, audit.to), (0, 3));
+    assert!(audit.is_consistent(), "{:?}", audit.mismatches);
+    assert!(audit.verified_signatures >= 3);
+    let audit = schema.audit_signatures(2);
+    assert_eq!((audit.from, audit.to), (2, 3));
+
+    // Parallel replay should produce the same report.
+    let scan = schema.chain_scan(snapshot.for_core());
+    assert_eq!(scan.len(), 3);
+    assert_eq!(scan.replay(), report);
+
+    let anchors = scan.anchors();
+    assert_eq!(anchors.len(), 3);
+    for (index, (anchor, tx)) in anchors
+        .iter()
+        .zip(schema.transactions_chain.iter())
+        .enumerate()
+    {
+        let payload = tx.anchoring_payload().unwrap();
+        assert_eq!(anchor.index, index as u64);
+        assert_eq!(anchor.txid, tx.id());
+        assert_eq!(anchor.block_height, payload.block_height);
+        assert_eq!(anchor.block_hash, payload.block_hash);
+        assert_eq!(anchor.output_type, Some(OutputType::P2wsh));
+    }
+
+    // Lazy export should produce the same records.
+    assert_eq!(schema.anchor_records(0).collect::<Vec<_>>(), anchors);
+    let export = schema.export_anchors(1, 1);
+    assert_eq!((export.from, export.to), (1, 2));
+    assert_eq!(export.records, &anchors[1..2]);
+    assert_eq!(export.next_from(), Some(2));
+    assert_eq!(schema.export_anchors(2, 10).next_from(), None);
+}
+
+#[test]
+fn submit_tenant_data() {
+    let tenant = KeyPair::random();
+    let mut anchoring_testkit = AnchoringTestKit::with_config(4, |config| {
+        config.tenants.push(Tenant {
+            key: tenant.public_key(),
+            quota: 2,
+        });
+    });
+
+    let data_hashes = (0..3).map(|i| hash(&[i])).collect::<Vec<_>>();
+    let block = anchoring_testkit.inner.create_block_with_transactions(
+        data_hashes
+            .iter()
+            .map(|&data_hash| tenant.submit_data(ANCHORING_INSTANCE_ID, SubmitData { data_hash })),
+    );
+    // The last submission exceeds the tenant quota.
+    block[0].status().unwrap();
+    block[1].status().unwrap();
+    assert_tx_error(
+        &block[2],
+        ErrorMatch::from_fail(&Error::TenantQuotaExceeded),
+    );
+
+    // Unknown tenant is not able to submit data.
+    let block =
+        anchoring_testkit
+            .inner
+            .create_block_with_transaction(KeyPair::random().submit_data(
+                ANCHORING_INSTANCE_ID,
+                SubmitData {
+                    data_hash: data_hashes[2],
+                },
+            ));
+    assert_tx_error(&block[0], ErrorMatch::from_fail(&Error::UnknownTenant));
+
+    let snapshot = anchoring_testkit.inner.snapshot();
+    let schema = get_anchoring_schema(&snapshot);
+    assert_eq!(schema.tenant_usage(&tenant.public_key()), 2);
+    let tenant_data = schema.tenant_data(&TenantBlockId::new(tenant.public_key(), Height(1)));
+    assert_eq!(tenant_data.iter().collect::<Vec<_>>(), &data_hashes[0..2]);
+}
+
+#[test]
+fn onboard_anchoring_node() {
+    let mut joining_keys = None;
+    let mut anchoring_testkit = AnchoringTestKit::with_config(5, |config| {
+        config.anchoring_interval = 5;
+        // The last validator does not participate in the anchoring.
+        joining_keys = config.anchoring_keys.pop();
+    });
+    let joining_keys = joining_keys.unwrap();
+    let joining_keypair = anchoring_testkit
+        .find_anchoring_node(&joining_keys.bitcoin_key)
+        .unwrap()
+        .service_keypair();
+
+    // Establish anchoring transactions chain.
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit.create_funding_confirmation_txs(700_000).0,
+    );
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit
+            .create_signature_txs()
+            .into_iter()
+            .flatten(),
+    );
+    let old_cfg = anchoring_testkit.actual_anchoring_config();
+
+    // Neither the unknown validator nor the existing anchoring node are able to submit key.
+    let existing_keypair = anchoring_testkit
+        .find_anchoring_node(&old_cfg.anchoring_keys[0].bitcoin_key)
+        .unwrap()
+        .service_keypair();
+    let submit_key = |testkit: &AnchoringTestKit, keypair: &KeyPair, bitcoin_key| {
+        let key_proof = testkit.prove_onboarding_key_possession(&AnchoringKeys {
+            service_key: keypair.public_key(),
+            bitcoin_key,
+        });
+        keypair.submit_anchoring_key(
+            ANCHORING_INSTANCE_ID,
+            SubmitAnchoringKey {
+                bitcoin_key,
+                key_proof,
+            },
+        )
+    };
+    let new_bitcoin_key = anchoring_testkit.gen_bitcoin_key();
+    let block = anchoring_testkit.inner.create_block_with_transactions(vec![
+        submit_key(&anchoring_testkit, &KeyPair::random(), new_bitcoin_key),
+        submit_key(&anchoring_testkit, &existing_keypair, new_bitcoin_key),
+    ]);
+    assert_tx_error(&block[0], ErrorMatch::from_fail(&Error::UnknownValidator));
+    assert_tx_error(&block[1], ErrorMatch::from_fail(&Error::AnchoringKeyExists));
+
+    // The proof of possession is bound to the service key of the validator and to
+    // the configuration resulting from the inclusion of the key.
+    let submit_proof = |key_proof| {
+        joining_keypair.submit_anchoring_key(
+            ANCHORING_INSTANCE_ID,
+            SubmitAnchoringKey {
+                bitcoin_key: joining_keys.bitcoin_key,
+                key_proof,
+            },
+        )
+    };
+    let block = anchoring_testkit.inner.create_block_with_transactions(vec![
+        submit_proof(
+            anchoring_testkit.prove_onboarding_key_possession(&AnchoringKeys {
+                service_key: existing_keypair.public_key(),
+                bitcoin_key: joining_keys.bitcoin_key,
+            }),
+        ),
+        submit_proof(anchoring_testkit.prove_actual_key_possession(&joining_keys)),
+    ]);
+    for tx in &block {
+        assert_tx_error(tx, ErrorMatch::from_fail(&Error::MissingKeyPossessionProof));
+    }
+
+    // Submit the key of the joining validator.
+    let key_proof = anchoring_testkit.prove_onboarding_key_possession(&joining_keys);
+    let block = anchoring_testkit
+        .inner
+        .create_block_with_transaction(submit_proof(key_proof.clone()));
+    block[0].status().unwrap();
+
+    // The key is included into the pending configuration after the approval of the quorum
+    // of the anchoring nodes.
+    let approve_key = |testkit: &AnchoringTestKit, keys: &AnchoringKeys| {
+        testkit
+            .find_anchoring_node(&keys.bitcoin_key)
+            .unwrap()
+            .service_keypair()
+            .submit_anchoring_key(
+                ANCHORING_INSTANCE_ID,
+                SubmitAnchoringKey {
+                    bitcoin_key: joining_keys.bitcoin_key,
+                    key_proof: key_proof.clone(),
+                },
+            )
+    };
+    let block = anchoring_testkit.inner.create_block_with_transactions(
+        old_cfg.anchoring_keys[0..2]
+            .iter()
+            .map(|keys| approve_key(&anchoring_testkit, keys))
+            .collect::<Vec<_>>(),
+    );
+    for tx in &block {
+        tx.status().unwrap();
+    }
+    {
+        let snapshot = anchoring_testkit.inner.snapshot();
+        let schema = get_anchoring_schema(&snapshot);
+        assert_eq!(
+            schema.submitted_anchoring_key(&joining_keypair.public_key()),
+            Some(key_proof.clone())
+        );
+        assert!(schema.following_config().is_none());
+        assert_eq!(schema.pending_config(), old_cfg);
+    }
+
+    // The transition to the pending configuration is scheduled automatically.
+    let block = anchoring_testkit
+        .inner
+        .create_block_with_transaction(approve_key(&anchoring_testkit, &old_cfg.anchoring_keys[2]));
+    block[0].status().unwrap();
+    let mut new_cfg = old_cfg;
+    new_cfg.anchoring_keys.push(joining_keys.clone());
+    new_cfg.key_proofs.push(key_proof);
+    {
+        let snapshot = anchoring_testkit.inner.snapshot();
+        let schema = get_anchoring_schema(&snapshot);
+        assert_eq!(schema.following_config(), Some(new_cfg.clone()));
+        assert_eq!(schema.pending_config(), new_cfg);
+        assert_eq!(
+            schema.submitted_anchoring_key(&joining_keypair.public_key()),
+            None
+        );
+    }
+
+    // The key cannot be submitted twice.
+    let block = anchoring_testkit
+        .inner
+        .create_block_with_transaction(submit_key(
+            &anchoring_testkit,
+            &joining_keypair,
+            new_bitcoin_key,
+        ));
+    assert_tx_error(&block[0], ErrorMatch::from_fail(&Error::AnchoringKeyExists));
+
+    // Finalize transition transaction.
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit
+            .create_signature_txs()
+            .into_iter()
+            .flatten(),
+    );
+    let snapshot = anchoring_testkit.inner.snapshot();
+    let schema = get_anchoring_schema(&snapshot);
+    assert!(schema.following_config().is_none());
+    assert_eq!(schema.actual_config(), new_cfg);
+}
+
+#[test]
+fn standby_key_activation() {
+    let anchoring_interval = 5;
+    let mut anchoring_testkit = AnchoringTestKit::with_config(4, |config| {
+        config.anchoring_interval = anchoring_interval;
+        config.standby_activation_intervals = 2;
+    });
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit.create_funding_confirmation_txs(700_000).0,
+    );
+
+    let old_cfg = anchoring_testkit.actual_anchoring_config();
+    // The first anchoring node stops signing the anchoring proposals.
+    let silent_keypair = anchoring_testkit
+        .find_anchoring_node(&old_cfg.anchoring_keys[0].bitcoin_key)
+        .unwrap()
+        .service_keypair();
+    let standby_key = anchoring_testkit.gen_bitcoin_key();
+
+    let register_key = |testkit: &AnchoringTestKit, keypair: &KeyPair, bitcoin_key| {
+        let key_proof = testkit.prove_actual_key_possession(&AnchoringKeys {
+            service_key: keypair.public_key(),
+            bitcoin_key,
+        });
+        keypair.register_standby_key(
+            ANCHORING_INSTANCE_ID,
+            RegisterStandbyKey {
+                bitcoin_key,
+                key_proof,
+            },
+        )
+    };
+    // The proof of possession is bound to the actual configuration.
+    let mut other_cfg = old_cfg.clone();
+    other_cfg.transaction_fee += 1;
+    let other_proof = other_cfg.prove_key_possession(
+        &silent_keypair.public_key(),
+        &anchoring_testkit.node_private_key(&standby_key),
+    );
+
+    // Only the anchoring node is able to register the unused standby key.
+    let block = anchoring_testkit.inner.create_block_with_transactions(vec![
+        register_key(&anchoring_testkit, &KeyPair::random(), standby_key),
+        register_key(
+            &anchoring_testkit,
+            &silent_keypair,
+            old_cfg.anchoring_keys[1].bitcoin_key,
+        ),
+        silent_keypair.register_standby_key(
+            ANCHORING_INSTANCE_ID,
+            RegisterStandbyKey {
+                bitcoin_key: standby_key,
+                key_proof: other_proof,
+            },
+        ),
+        register_key(&anchoring_testkit, &silent_keypair, standby_key),
+    ]);
+    assert_tx_error(
+        &block[0],
+        ErrorMatch::from_fail(&Error::UnauthorizedAnchoringKey),
+    );
+    assert_tx_error(&block[1], ErrorMatch::from_fail(&Error::AnchoringKeyExists));
+    assert_tx_error(
+        &block[2],
+        ErrorMatch::from_fail(&Error::MissingKeyPossessionProof),
+    );
+    block[3].status().unwrap();
+    {
+        let snapshot = anchoring_testkit.inner.snapshot();
+        let schema = get_anchoring_schema(&snapshot);
+        assert_eq!(schema.standby_key(&silent_keypair.public_key()), None);
+        assert_eq!(
+            schema.unconfirmed_standby_key(&silent_keypair.public_key()),
+            Some(standby_key)
+        );
+    }
+
+    // The standby key is registered after the approval of another anchoring node.
+    let approving_keypair = anchoring_testkit
+        .find_anchoring_node(&old_cfg.anchoring_keys[1].bitcoin_key)
+        .unwrap()
+        .service_keypair();
+    let key_proof = anchoring_testkit.prove_actual_key_possession(&AnchoringKeys {
+        service_key: silent_keypair.public_key(),
+        bitcoin_key: standby_key,
+    });
+    anchoring_testkit
+        .inner
+        .create_block_with_transaction(approving_keypair.register_standby_key(
+            ANCHORING_INSTANCE_ID,
+            RegisterStandbyKey {
+                bitcoin_key: standby_key,
+                key_proof,
+            },
+        ))[0]
+        .status()
+        .unwrap();
+    {
+        let snapshot = anchoring_testkit.inner.snapshot();
+        let schema = get_anchoring_schema(&snapshot);
+        assert_eq!(
+            schema.standby_key(&silent_keypair.public_key()),
+            Some(standby_key)
+        );
+        assert_eq!(
+            schema.unconfirmed_standby_key(&silent_keypair.public_key()),
+            None
+        );
+    }
+
+    // Anchor several heights without the signatures of the silent node.
+    for i in 0..3 {
+        let snapshot = anchoring_testkit.inner.snapshot();
+        assert!(get_anchoring_schema(&snapshot).following_config().is_none());
+
+        anchoring_testkit
+            .inner
+            .create_blocks_until(Height(anchoring_interval * i));
+        anchoring_testkit.inner.create_block_with_transactions(
+            anchoring_testkit
+                .create_signature_txs()
+                .into_iter()
+                .skip(1)
+                .flatten(),
+        );
+    }
+
+    // The transition to the standby key is scheduled automatically.
+    let mut new_cfg = old_cfg;
+    new_cfg.anchoring_keys[0].bitcoin_key = standby_key;
+    {
+        let snapshot = anchoring_testkit.inner.snapshot();
+        let schema = get_anchoring_schema(&snapshot);
+        assert_eq!(schema.following_config(), Some(new_cfg.clone()));
+        assert_eq!(schema.standby_key(&silent_keypair.public_key()), None);
+    }
+
+    // Finalize transition transaction.
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit
+            .create_signature_txs()
+            .into_iter()
+            .skip(1)
+            .flatten(),
+    );
+    assert_eq!(anchoring_testkit.actual_anchoring_config(), new_cfg);
+}
+
+#[test]
+fn standby_key_approval_expired() {
+    let mut anchoring_testkit = AnchoringTestKit::with_config(4, |config| {
+        config.anchoring_interval = 5;
+        config.standby_activation_intervals = 2;
+        config.approval_window = 5;
+    });
+    let config = anchoring_testkit.actual_anchoring_config();
+    let keypairs = config.anchoring_keys[..2]
+        .iter()
+        .map(|keys| {
+            anchoring_testkit
+                .find_anchoring_node(&keys.bitcoin_key)
+                .unwrap()
+                .service_keypair()
+        })
+        .collect::<Vec<_>>();
+    let standby_key = anchoring_testkit.gen_bitcoin_key();
+    let register_key = RegisterStandbyKey {
+        bitcoin_key: standby_key,
+        key_proof: anchoring_testkit.prove_actual_key_possession(&AnchoringKeys {
+            service_key: keypairs[0].public_key(),
+            bitcoin_key: standby_key,
+        }),
+    };
+
+    anchoring_testkit.inner.create_block_with_transaction(
+        keypairs[0].register_standby_key(ANCHORING_INSTANCE_ID, register_key.clone()),
+    );
+    let window_end = Height(anchoring_testkit.inner.height().0 + 5);
+    anchoring_testkit.inner.create_blocks_until(window_end);
+
+    // The approval is rejected after the approval window has expired.
+    let block = anchoring_testkit.inner.create_block_with_transaction(
+        keypairs[1].register_standby_key(ANCHORING_INSTANCE_ID, register_key.clone()),
+    );
+    assert_tx_error(&block[0], ErrorMatch::from_fail(&Error::ApprovalExpired));
+    let snapshot = anchoring_testkit.inner.snapshot();
+    assert_eq!(
+        get_anchoring_schema(&snapshot).standby_key(&keypairs[0].public_key()),
+        None
+    );
+}
+
+#[test]
+fn fee_accounting() {
+    let mut anchoring_testkit = AnchoringTestKit::default();
+    let anchoring_keys = anchoring_testkit.actual_anchoring_config().anchoring_keys;
+
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit
+            .create_signature_txs()
+            .into_iter()
+            .flatten(),
+    );
+
+    // The anchoring transaction is finalized by the node which has submitted
+    // the last required signature.
+    let snapshot = anchoring_testkit.inner.snapshot();
+    let schema = get_anchoring_schema(&snapshot);
+    assert_eq!(schema.anchoring_epoch(), 0);
+    assert_eq!(
+        schema.fee_records(0).iter().collect::<Vec<_>>(),
+        vec![(
+            anchoring_keys[2].service_key,
+            FeeRecord {
+                transactions_count: 1,
+                total_fee: 1530,
+            }
+        )]
+    );
+}
+
 #[test]
 fn err_spent_funding() {
     let anchoring_interval = 5;
@@ -340,11 +955,212 @@ fn insufficient_funds() {
 }
 
 #[test]
-fn no_anchoring_proposal() {
-    let mut anchoring_testkit = AnchoringTestKit::default();
-    let anchoring_interval = anchoring_testkit
-        .actual_anchoring_config()
-        .anchoring_interval;
+fn reserve_balance() {
+    let mut anchoring_testkit = AnchoringTestKit::with_config(4, |config| {
+        config.anchoring_interval = 5;
+        config.reserve_balance = 10_000;
+    });
+
+    // Replenish the anchoring wallet by the amount which is less than the reserve
+    // plus the transaction fee.
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit.create_funding_confirmation_txs(10_000).0,
+    );
+    {
+        let snapshot = anchoring_testkit.inner.snapshot();
+        let schema = get_anchoring_schema(&snapshot);
+        let proposal = schema
+            .actual_proposed_anchoring_transaction(snapshot.for_core())
+            .unwrap();
+        assert_eq!(
+            proposal,
+            Err(BuilderError::ReserveReached {
+                remaining: 8_470,
+                reserve: 10_000
+            })
+        );
+    }
+
+    // Replenish the anchoring wallet once more, so the reserve remains after anchoring.
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit.create_funding_confirmation_txs(20_000).0,
+    );
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit
+            .create_signature_txs()
+            .into_iter()
+            .flatten(),
+    );
+    let tx = anchoring_testkit.last_anchoring_tx().unwrap();
+    assert!(tx.0.output[0].value >= 10_000);
+}
+
+#[test]
+fn dust_threshold() {
+    let mut anchoring_testkit = AnchoringTestKit::with_config(4, |config| {
+        config.anchoring_interval = 5;
+        config.dust_threshold = 546;
+    });
+
+    // Replenish the anchoring wallet by the amount which leaves the dust change output.
+    anchoring_testkit
+        .inner
+        .create_block_with_transactions(anchoring_testkit.create_funding_confirmation_txs(2_000).0);
+    let snapshot = anchoring_testkit.inner.snapshot();
+    let schema = get_anchoring_schema(&snapshot);
+    let proposal = schema
+        .actual_proposed_anchoring_transaction(snapshot.for_core())
+        .unwrap();
+    assert_eq!(
+        proposal,
+        Err(BuilderError::DustChange {
+            remaining: 470,
+            dust_threshold: 546
+        })
+    );
+}
+
+/// Creates the rescue transaction, which spends the output of the latest anchoring transaction
+/// to the given script and anchors the block at the given height.
+fn create_rescue_transaction(
+    anchoring_testkit: &AnchoringTestKit,
+    script_pubkey: bitcoin::Script,
+    height: Height,
+) -> btc::Transaction {
+    let latest_tx = anchoring_testkit.last_anchoring_tx().unwrap();
+    let config = anchoring_testkit.actual_anchoring_config();
+    let mut builder = BtcAnchoringTransactionBuilder::new(&config.redeem_script());
+    builder.prev_tx(latest_tx).unwrap();
+    builder.transit_to(script_pubkey);
+    builder.payload(height, anchoring_testkit.block_hash_on_height(height));
+    // Operators pay a higher fee to speed up the rescue.
+    builder.fee(100);
+    builder.create().unwrap().0
+}
+
+#[test]
+fn import_external_anchor() {
+    let mut anchoring_testkit = AnchoringTestKit::new(4, 5);
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit.create_funding_confirmation_txs(100_000).0,
+    );
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit
+            .create_signature_txs()
+            .into_iter()
+            .flatten(),
+    );
+    anchoring_testkit.inner.create_blocks_until(Height(5));
+
+    let script_pubkey = anchoring_testkit
+        .actual_anchoring_config()
+        .anchoring_out_script();
+    let rescue_tx = create_rescue_transaction(&anchoring_testkit, script_pubkey, Height(5));
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit.create_import_anchor_txs(rescue_tx.clone()),
+    );
+
+    // The rescue transaction with the valid payload is adopted into the anchoring chain.
+    assert_eq!(
+        anchoring_testkit.last_anchoring_tx(),
+        Some(rescue_tx.clone())
+    );
+    let snapshot = anchoring_testkit.inner.snapshot();
+    let schema = get_anchoring_schema(&snapshot);
+    assert_eq!(schema.transaction_index(&rescue_tx.id()), Some(1));
+    assert_eq!(schema.latest_anchored_height(), Some(Height(5)));
+
+    // Anchoring continues from the rescue transaction.
+    anchoring_testkit.inner.create_blocks_until(Height(10));
+    let (proposal, _) = anchoring_testkit.anchoring_transaction_proposal().unwrap();
+    assert_eq!(proposal.prev_tx_id(), rescue_tx.id());
+}
+
+#[test]
+fn import_external_anchor_breaks_chain() {
+    let mut anchoring_testkit = AnchoringTestKit::new(4, 5);
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit.create_funding_confirmation_txs(100_000).0,
+    );
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit
+            .create_signature_txs()
+            .into_iter()
+            .flatten(),
+    );
+    anchoring_testkit.inner.create_blocks_until(Height(5));
+
+    // The rescue transaction spends the anchoring output to the foreign address.
+    let latest_tx = anchoring_testkit.last_anchoring_tx().unwrap();
+    // `OP_TRUE` script.
+    let foreign_script = bitcoin::Script::from(vec![0x51]);
+    let rescue_tx = create_rescue_transaction(&anchoring_testkit, foreign_script, Height(5));
+    // Only one node imports the transaction at first.
+    let mut import_txs = anchoring_testkit.create_import_anchor_txs(rescue_tx.clone());
+    anchoring_testkit
+        .inner
+        .create_block_with_transaction(import_txs.remove(0));
+    assert!(get_anchoring_schema(&anchoring_testkit.inner.snapshot())
+        .external_spend(&latest_tx.id())
+        .is_none());
+
+    anchoring_testkit
+        .inner
+        .create_block_with_transactions(import_txs);
+    {
+        let snapshot = anchoring_testkit.inner.snapshot();
+        let schema = get_anchoring_schema(&snapshot);
+        assert_eq!(schema.external_spend(&latest_tx.id()), Some(rescue_tx));
+        assert_eq!(schema.transactions_chain.len(), 1);
+        // The anchoring chain can be recovered only with the new funds.
+        assert_eq!(
+            schema.actual_proposed_anchoring_transaction(snapshot.for_core()),
+            Some(Err(BuilderError::NoInputs))
+        );
+    }
+
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit.create_funding_confirmation_txs(100_000).0,
+    );
+    let (proposal, _) = anchoring_testkit.anchoring_transaction_proposal().unwrap();
+    assert_eq!(
+        proposal.anchoring_payload().unwrap().prev_tx_chain,
+        Some(latest_tx.id())
+    );
+}
+
+#[test]
+fn import_external_anchor_err_unsuitable() {
+    let mut anchoring_testkit = AnchoringTestKit::default();
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit
+            .create_signature_txs()
+            .into_iter()
+            .flatten(),
+    );
+
+    let unrelated_tx = create_fake_funding_transaction(
+        &anchoring_testkit
+            .actual_anchoring_config()
+            .anchoring_address(),
+        10_000,
+    );
+    let tx = anchoring_testkit
+        .create_import_anchor_txs(unrelated_tx)
+        .remove(0);
+    let block = anchoring_testkit.inner.create_block_with_transaction(tx);
+    assert_tx_error(
+        &block[0],
+        ErrorMatch::from_fail(&Error::UnsuitableExternalAnchor),
+    );
+}
+
+#[test]
+fn no_anchoring_proposal() {
+    let mut anchoring_testkit = AnchoringTestKit::default();
+    let anchoring_interval = anchoring_testkit
+        .actual_anchoring_config()
+        .anchoring_interval;
 
     assert!(anchoring_testkit.last_anchoring_tx().is_none());
 
@@ -434,6 +1250,15 @@ fn remove_anchoring_node() {
     });
 }
 
+#[test]
+fn reorder_anchoring_nodes() {
+    // Anchoring nodes are identified by their service keys, so the signatures remain
+    // valid after the change of the nodes order.
+    test_anchoring_config_change(|_, cfg| {
+        cfg.anchoring_keys.swap(0, 3);
+    });
+}
+
 #[test]
 fn change_anchoring_node_without_funds() {
     test_anchoring_config_change(|anchoring_testkit, cfg| {
@@ -441,6 +1266,194 @@ fn change_anchoring_node_without_funds() {
     });
 }
 
+#[test]
+fn commit_configuration_change_and_transition() {
+    let mut anchoring_testkit = AnchoringTestKit::default();
+    let relay = SimulatedChainRelay::default();
+    relay.fund(unspent_funding_transaction(&anchoring_testkit).unwrap());
+
+    // Establish anchoring transactions chain.
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit
+            .create_signature_txs()
+            .into_iter()
+            .flatten(),
+    );
+    let first_anchoring_tx = anchoring_testkit.last_anchoring_tx().unwrap();
+
+    let mut new_cfg = anchoring_testkit.actual_anchoring_config();
+    new_cfg.anchoring_keys.push(anchoring_testkit.add_node());
+    let transition_tx =
+        anchoring_testkit.commit_configuration_change_and_transition(new_cfg.clone(), &relay);
+
+    // Both anchoring transactions have been mined in the simulated chain.
+    assert_eq!(
+        relay.status(first_anchoring_tx.id()).confirmations(),
+        Some(1)
+    );
+    assert_eq!(relay.status(transition_tx.id()).confirmations(), Some(1));
+    assert!(relay.mempool().is_empty());
+    assert_eq!(
+        transition_tx.anchoring_metadata().unwrap().0,
+        &new_cfg.anchoring_out_script()
+    );
+}
+
+#[test]
+fn reject_malformed_config_proposals() {
+    let mut anchoring_testkit = AnchoringTestKit::default();
+    for &kind in &MalformedConfig::ALL {
+        let config = anchoring_testkit.malformed_config(kind);
+        anchoring_testkit.assert_config_proposal_rejected(config, kind.expected_error());
+    }
+}
+
+#[test]
+fn reject_config_without_key_possession_proof() {
+    let mut anchoring_testkit = AnchoringTestKit::default();
+    let mut config = anchoring_testkit.actual_anchoring_config();
+    config.anchoring_keys.push(anchoring_testkit.add_node());
+    anchoring_testkit
+        .assert_config_proposal_rejected(config.clone(), Error::MissingKeyPossessionProof);
+
+    // The proof of possession is bound to the configuration.
+    let mut other_config = config.clone();
+    other_config.transaction_fee += 1;
+    anchoring_testkit.prove_key_possession(&mut other_config);
+    config.key_proofs = other_config.key_proofs;
+    anchoring_testkit
+        .assert_config_proposal_rejected(config.clone(), Error::MissingKeyPossessionProof);
+
+    anchoring_testkit.prove_key_possession(&mut config);
+    let block = anchoring_testkit.inner.create_block_with_transaction(
+        anchoring_testkit.create_config_change_tx(
+            ConfigPropose::new(0, anchoring_testkit.inner.height().next())
+                .service_config(ANCHORING_INSTANCE_ID, config),
+        ),
+    );
+    block[0].status().unwrap();
+}
+
+#[test]
+fn reject_config_with_reused_address() {
+    let mut anchoring_testkit = AnchoringTestKit::default();
+    let relay = SimulatedChainRelay::default();
+    relay.fund(unspent_funding_transaction(&anchoring_testkit).unwrap());
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit
+            .create_signature_txs()
+            .into_iter()
+            .flatten(),
+    );
+
+    let old_config = anchoring_testkit.actual_anchoring_config();
+    let mut new_config = old_config.clone();
+    new_config.anchoring_keys.push(anchoring_testkit.add_node());
+    anchoring_testkit.commit_configuration_change_and_transition(new_config.clone(), &relay);
+    {
+        let snapshot = anchoring_testkit.inner.snapshot();
+        let schema = get_anchoring_schema(&snapshot);
+        assert_eq!(
+            schema.address_epoch(&old_config.anchoring_address()),
+            Some(0)
+        );
+        assert_eq!(
+            schema.address_epoch(&new_config.anchoring_address()),
+            Some(1)
+        );
+    }
+
+    let mut propose_config = |config: Config| {
+        let block = anchoring_testkit.inner.create_block_with_transaction(
+            anchoring_testkit.create_config_change_tx(
+                ConfigPropose::new(1, anchoring_testkit.inner.height().next())
+                    .service_config(ANCHORING_INSTANCE_ID, config),
+            ),
+        );
+        block[0].status().map_err(|e| e.clone())
+    };
+    let address_reuse =
+        ErrorMatch::from_fail(&Error::AddressReuse).for_service(ANCHORING_INSTANCE_ID);
+
+    // The address of the previous anchoring epoch is rejected.
+    assert_eq!(
+        propose_config(old_config.clone()).unwrap_err(),
+        address_reuse
+    );
+    // The known external address is rejected.
+    let mut external_config = new_config.clone();
+    external_config.transaction_fee += 1;
+    external_config.external_addresses = vec![new_config.anchoring_address().to_string()];
+    assert_eq!(propose_config(external_config).unwrap_err(), address_reuse);
+    // The reuse may be allowed explicitly.
+    let mut reused_config = old_config;
+    reused_config.allow_address_reuse = true;
+    propose_config(reused_config).unwrap();
+}
+
+#[test]
+fn late_anchoring_configuration() {
+    let (mut anchoring_testkit, config) = AnchoringTestKit::unconfigured(4, 5);
+    {
+        let snapshot = anchoring_testkit.inner.snapshot();
+        assert_eq!(get_anchoring_schema(&snapshot).try_actual_config(), None);
+    }
+
+    // Anchoring transactions are rejected until the service is configured.
+    let funding_tx = create_fake_funding_transaction(&config.anchoring_address(), 700_000);
+    let block = anchoring_testkit.inner.create_block_with_transaction(
+        anchoring_testkit.inner.us().service_keypair().add_funds(
+            ANCHORING_INSTANCE_ID,
+            AddFunds {
+                transaction: funding_tx,
+            },
+        ),
+    );
+    assert_tx_error(&block[0], ErrorMatch::from_fail(&Error::NotConfigured));
+
+    // Apply the first anchoring configuration via the supervisor.
+    anchoring_testkit.inner.create_block_with_transaction(
+        anchoring_testkit.create_config_change_tx(
+            ConfigPropose::new(0, anchoring_testkit.inner.height().next())
+                .service_config(ANCHORING_INSTANCE_ID, config.clone()),
+        ),
+    );
+    assert_eq!(anchoring_testkit.actual_anchoring_config(), config);
+
+    // Anchoring works as usual.
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit.create_funding_confirmation_txs(700_000).0,
+    );
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit
+            .create_signature_txs()
+            .into_iter()
+            .flatten(),
+    );
+    assert!(anchoring_testkit.last_anchoring_tx().is_some());
+}
+
+#[test]
+fn resume_with_new_configuration() {
+    let mut anchoring_testkit = AnchoringTestKit::default();
+    let mut config = anchoring_testkit.actual_anchoring_config();
+    config.transaction_fee += 10;
+
+    anchoring_testkit.inner.create_block_with_transaction(
+        anchoring_testkit.create_config_change_tx(
+            ConfigPropose::new(0, anchoring_testkit.inner.height().next())
+                .stop_service(ANCHORING_INSTANCE_ID),
+        ),
+    );
+    anchoring_testkit.inner.create_block_with_transaction(
+        anchoring_testkit.create_config_change_tx(
+            ConfigPropose::new(1, anchoring_testkit.inner.height().next())
+                .resume_service(ANCHORING_INSTANCE_ID, config.clone()),
+        ),
+    );
+    assert_eq!(anchoring_testkit.actual_anchoring_config(), config);
+}
+
 #[test]
 fn add_anchoring_node_insufficient_funds() {
     let mut anchoring_testkit = AnchoringTestKit::new(4, 5);
@@ -470,6 +1483,7 @@ fn add_anchoring_node_insufficient_funds() {
     // Add an anchoring node.
     let mut new_cfg = anchoring_testkit.actual_anchoring_config();
     new_cfg.anchoring_keys.push(anchoring_testkit.add_node());
+    anchoring_testkit.prove_key_possession(&mut new_cfg);
 
     // Commit configuration with without last anchoring node.
     anchoring_testkit.inner.create_block_with_transaction(
@@ -551,6 +1565,287 @@ fn funding_tx_override() {
     );
 }
 
+#[test]
+fn update_parameters() {
+    let mut testkit = AnchoringTestKit::default();
+    let initial_config = testkit.actual_anchoring_config();
+    let parameters = UpdateParameters {
+        anchoring_interval: 10,
+        transaction_fee: 0,
+    };
+    let mut txs = testkit.create_update_parameters_txs(parameters);
+
+    // The parameters are not applied until the quorum of the anchoring nodes sent them.
+    let quorum = initial_config.byzantine_quorum();
+    let remaining_txs = txs.split_off(quorum - 1);
+    testkit.inner.create_block_with_transactions(txs);
+    assert_eq!(testkit.actual_anchoring_config(), initial_config);
+
+    testkit.inner.create_block_with_transactions(remaining_txs);
+    let actual_config = testkit.actual_anchoring_config();
+    assert_eq!(actual_config.anchoring_interval, 10);
+    // Zero fee leaves the fee unchanged.
+    assert_eq!(
+        actual_config.transaction_fee,
+        initial_config.transaction_fee
+    );
+    assert_eq!(
+        actual_config.anchoring_address(),
+        initial_config.anchoring_address()
+    );
+}
+
+#[test]
+fn update_parameters_approval_window() {
+    let mut testkit = AnchoringTestKit::with_config(4, |config| {
+        config.anchoring_interval = 5;
+        config.approval_window = 5;
+    });
+    let initial_config = testkit.actual_anchoring_config();
+    let mut txs = testkit.create_update_parameters_txs(UpdateParameters {
+        anchoring_interval: 10,
+        transaction_fee: 0,
+    });
+
+    // The confirmations given within the expired approval window are discarded.
+    testkit.inner.create_block_with_transaction(txs.remove(0));
+    let window_end = Height(testkit.inner.height().0 + 5);
+    testkit.inner.create_blocks_until(window_end);
+    testkit
+        .inner
+        .create_block_with_transactions(txs.drain(0..2));
+    assert_eq!(testkit.actual_anchoring_config(), initial_config);
+
+    // The quorum is reached within the new window.
+    testkit.inner.create_block_with_transactions(txs);
+    assert_eq!(testkit.actual_anchoring_config().anchoring_interval, 10);
+}
+
+#[test]
+fn update_parameters_two_person_rule() {
+    // A single anchoring node cannot update the parameters on its own.
+    let mut testkit = AnchoringTestKit::new(1, 5);
+    let initial_config = testkit.actual_anchoring_config();
+    let block = testkit
+        .inner
+        .create_block_with_transactions(testkit.create_update_parameters_txs(UpdateParameters {
+            anchoring_interval: 10,
+            transaction_fee: 0,
+        }));
+    block[0].status().unwrap();
+    assert_eq!(testkit.actual_anchoring_config(), initial_config);
+}
+
+#[test]
+fn update_parameters_err_invalid() {
+    let mut testkit = AnchoringTestKit::default();
+    let tx = testkit.create_update_parameters_txs(UpdateParameters {
+        anchoring_interval: 0,
+        transaction_fee: 1,
+    })[0]
+        .clone();
+    let block = testkit.inner.create_block_with_transaction(tx);
+    assert_tx_error(&block[0], ErrorMatch::from_fail(&Error::InvalidParameters));
+}
+
+#[test]
+fn update_parameters_err_unauthorized() {
+    let mut testkit = AnchoringTestKit::default();
+    let tx = testkit.create_update_parameters_txs(UpdateParameters {
+        anchoring_interval: 10,
+        transaction_fee: 0,
+    })[0]
+        .clone();
+    let malformed_tx = change_tx_signature(tx, &KeyPair::random());
+    let block = testkit.inner.create_block_with_transaction(malformed_tx);
+    assert_tx_error(
+        &block[0],
+        ErrorMatch::from_fail(&Error::UnauthorizedAnchoringKey),
+    );
+}
+
+#[test]
+fn confirm_anchor_finality() {
+    let mut testkit = AnchoringTestKit::default();
+    // Establish anchoring transactions chain.
+    testkit
+        .inner
+        .create_block_with_transactions(testkit.create_signature_txs().into_iter().flatten());
+    let anchored_height = testkit.inner.height();
+    let anchoring_tx = testkit.last_anchoring_tx().unwrap();
+    {
+        let snapshot = testkit.inner.snapshot();
+        let schema = get_anchoring_schema(&snapshot);
+        assert_eq!(schema.final_anchor_index(), None);
+        assert!(!schema.is_anchored_and_final(Height(0)));
+    }
+
+    // The anchor is not final until the quorum of the anchoring nodes confirmed it.
+    let mut txs = testkit.create_confirm_anchor_finality_txs(anchoring_tx.id());
+    let quorum = testkit.actual_anchoring_config().byzantine_quorum();
+    let remaining_txs = txs.split_off(quorum - 1);
+    testkit.inner.create_block_with_transactions(txs);
+    assert_eq!(
+        get_anchoring_schema(&testkit.inner.snapshot()).final_anchor_index(),
+        None
+    );
+
+    testkit.inner.create_block_with_transactions(remaining_txs);
+    let snapshot = testkit.inner.snapshot();
+    let schema = get_anchoring_schema(&snapshot);
+    assert_eq!(schema.final_anchor_index(), Some(0));
+    assert!(schema.is_anchored_and_final(Height(0)));
+    // The following blocks are not anchored yet.
+    assert!(!schema.is_anchored_and_final(anchored_height.next()));
+}
+
+#[test]
+fn report_anchor_status() {
+    let mut testkit = AnchoringTestKit::default();
+    let anchoring_interval = testkit.actual_anchoring_config().anchoring_interval;
+    testkit
+        .inner
+        .create_block_with_transactions(testkit.create_signature_txs().into_iter().flatten());
+    let first_tx = testkit.last_anchoring_tx().unwrap();
+
+    let report = ReportAnchorStatus {
+        txid: first_tx.id(),
+        known: true,
+        confirmations: 0,
+    };
+    let reported_height = testkit.inner.height();
+    testkit
+        .inner
+        .create_block_with_transactions(testkit.create_report_anchor_status_txs(report));
+    {
+        let snapshot = testkit.inner.snapshot();
+        let schema = get_anchoring_schema(&snapshot);
+        let reports = schema.anchor_status_reports().values().collect::<Vec<_>>();
+        assert_eq!(
+            reports.len(),
+            testkit.actual_anchoring_config().anchoring_keys.len()
+        );
+        assert!(reports.iter().all(|report| report.txid == first_tx.id()
+            && report.is_in_mempool()
+            && report.reported_height == reported_height.0));
+    }
+
+    // The reports for the previous anchoring transactions are ignored.
+    testkit
+        .inner
+        .create_blocks_until(Height(anchoring_interval));
+    testkit
+        .inner
+        .create_block_with_transactions(testkit.create_signature_txs().into_iter().flatten());
+    let report = ReportAnchorStatus {
+        txid: first_tx.id(),
+        known: false,
+        confirmations: 0,
+    };
+    let block = testkit
+        .inner
+        .create_block_with_transactions(testkit.create_report_anchor_status_txs(report));
+    assert!(block.iter().all(|tx| tx.status().is_ok()));
+    let snapshot = testkit.inner.snapshot();
+    let schema = get_anchoring_schema(&snapshot);
+    assert!(schema
+        .anchor_status_reports()
+        .values()
+        .all(|report| !report.is_missing()));
+}
+
+#[test]
+fn report_anchor_status_err_unknown_transaction() {
+    let mut testkit = AnchoringTestKit::default();
+    let config = testkit.actual_anchoring_config();
+    let funding_tx = create_fake_funding_transaction(&config.anchoring_address(), 700_000);
+    let tx = testkit.inner.us().service_keypair().report_anchor_status(
+        ANCHORING_INSTANCE_ID,
+        ReportAnchorStatus {
+            txid: funding_tx.id(),
+            known: true,
+            confirmations: 1,
+        },
+    );
+    let block = testkit.inner.create_block_with_transaction(tx);
+    assert_tx_error(
+        &block[0],
+        ErrorMatch::from_fail(&Error::UnknownAnchoringTransaction),
+    );
+}
+
+#[test]
+fn record_anchor_block() {
+    let mut testkit = AnchoringTestKit::default();
+    testkit
+        .inner
+        .create_block_with_transactions(testkit.create_signature_txs().into_iter().flatten());
+    let anchoring_tx = testkit.last_anchoring_tx().unwrap();
+    let record = RecordAnchorBlock {
+        txid: anchoring_tx.id(),
+        block_hash: btc::Sha256d(sha256d::Hash::hash(b"block")),
+    };
+
+    // The block hash is not recorded until the quorum of the anchoring nodes confirmed it.
+    let mut txs = testkit.create_record_anchor_block_txs(record.clone());
+    let quorum = testkit.actual_anchoring_config().byzantine_quorum();
+    let remaining_txs = txs.split_off(quorum - 1);
+    testkit.inner.create_block_with_transactions(txs);
+    assert_eq!(
+        get_anchoring_schema(&testkit.inner.snapshot()).anchor_block_hash(&record.txid),
+        None
+    );
+
+    let block = testkit.inner.create_block_with_transactions(remaining_txs);
+    assert!(block.iter().all(|tx| tx.status().is_ok()));
+    assert_eq!(
+        get_anchoring_schema(&testkit.inner.snapshot()).anchor_block_hash(&record.txid),
+        Some(record.block_hash)
+    );
+}
+
+#[test]
+fn record_anchor_block_err_unknown_transaction() {
+    let mut testkit = AnchoringTestKit::default();
+    let config = testkit.actual_anchoring_config();
+    let funding_tx = create_fake_funding_transaction(&config.anchoring_address(), 700_000);
+    let tx = testkit.inner.us().service_keypair().record_anchor_block(
+        ANCHORING_INSTANCE_ID,
+        RecordAnchorBlock {
+            txid: funding_tx.id(),
+            block_hash: btc::Sha256d(sha256d::Hash::hash(b"block")),
+        },
+    );
+    let block = testkit.inner.create_block_with_transaction(tx);
+    assert_tx_error(
+        &block[0],
+        ErrorMatch::from_fail(&Error::UnknownAnchoringTransaction),
+    );
+}
+
+#[test]
+fn confirm_anchor_finality_err_unknown_transaction() {
+    let mut testkit = AnchoringTestKit::default();
+    // Funding transaction is not a part of the anchoring chain.
+    let config = testkit.actual_anchoring_config();
+    let funding_tx = create_fake_funding_transaction(&config.anchoring_address(), 700_000);
+    let tx = testkit
+        .inner
+        .us()
+        .service_keypair()
+        .confirm_anchor_finality(
+            ANCHORING_INSTANCE_ID,
+            ConfirmAnchorFinality {
+                txid: funding_tx.id(),
+            },
+        );
+    let block = testkit.inner.create_block_with_transaction(tx);
+    assert_tx_error(
+        &block[0],
+        ErrorMatch::from_fail(&Error::UnknownAnchoringTransaction),
+    );
+}
+
 #[test]
 fn sign_input_err_unauthorized() {
     let mut testkit = AnchoringTestKit::default();
@@ -602,6 +1897,360 @@ fn sing_input_err_no_such_input() {
     assert_tx_error(&block[0], ErrorMatch::from_fail(&Error::NoSuchInput));
 }
 
+#[test]
+fn sign_input_conflicting_proposal() {
+    let mut testkit = AnchoringTestKit::default();
+    let anchoring_interval = testkit.actual_anchoring_config().anchoring_interval;
+    let validators = testkit.inner.network().validators().to_vec();
+    // Establish the anchoring chain and add funds, so the next proposal has two inputs.
+    testkit
+        .inner
+        .create_block_with_transactions(testkit.create_signature_txs().into_iter().flatten());
+    testkit
+        .inner
+        .create_block_with_transactions(testkit.create_funding_confirmation_txs(150_000).0);
+    testkit
+        .inner
+        .create_blocks_until(Height(anchoring_interval));
+
+    let (proposal, proposal_inputs) = testkit.anchoring_transaction_proposal().unwrap();
+    assert_eq!(proposal_inputs.len(), 2);
+    // The conflicting transaction spends the same outputs as the proposal.
+    let mut conflicting_tx = proposal.clone();
+    conflicting_tx.0.output[0].value -= 1;
+    let conflicting_signature = |testkit: &AnchoringTestKit, index: usize| -> SignInput {
+        testkit.create_signature_tx_for_transaction(
+            &validators[index],
+            &conflicting_tx,
+            &proposal_inputs,
+        )[0]
+        .payload()
+        .parse()
+        .unwrap()
+    };
+
+    // Evidence from a single node does not halt the anchoring.
+    let block =
+        testkit
+            .inner
+            .create_block_with_transactions(testkit.create_signature_tx_for_transaction(
+                &validators[0],
+                &conflicting_tx,
+                &proposal_inputs,
+            ));
+    assert!(block.iter().all(|tx| tx.status().is_ok()));
+    {
+        let snapshot = testkit.inner.snapshot();
+        let schema = get_anchoring_schema(&snapshot);
+        // Repeated evidence is ignored.
+        assert_eq!(schema.proposal_conflicts().len(), 1);
+        assert!(schema.anchoring_halt().is_none());
+    }
+
+    // The signature is verified against the conflicting transaction.
+    let tx = conflicting_signature(&testkit, 1);
+    let block = testkit.inner.create_block_with_transactions(
+        vec![
+            SignInput {
+                signed_transaction: Vec::new(),
+                ..tx.clone()
+            },
+            SignInput {
+                signed_transaction: bitcoin::consensus::serialize(proposal.as_ref()),
+                ..tx.clone()
+            },
+            SignInput {
+                input_signature: conflicting_signature(&testkit, 0).input_signature,
+                ..tx
+            },
+        ]
+        .into_iter()
+        .map(|tx| {
+            validators[1]
+                .service_keypair()
+                .sign_input(ANCHORING_INSTANCE_ID, tx)
+        }),
+    );
+    assert_tx_error(
+        &block[0],
+        ErrorMatch::from_fail(&Error::UnexpectedProposalTxId),
+    );
+    assert_tx_error(
+        &block[1],
+        ErrorMatch::from_fail(&Error::UnexpectedProposalTxId),
+    );
+    assert_tx_error(
+        &block[2],
+        ErrorMatch::from_fail(&Error::InputVerificationFailed),
+    );
+    assert_eq!(
+        get_anchoring_schema(&testkit.inner.snapshot())
+            .proposal_conflicts()
+            .len(),
+        1
+    );
+
+    // Evidence from another node halts the anchoring.
+    testkit
+        .inner
+        .create_block_with_transactions(testkit.create_signature_tx_for_transaction(
+            &validators[1],
+            &conflicting_tx,
+            &proposal_inputs,
+        ));
+    {
+        let snapshot = testkit.inner.snapshot();
+        let schema = get_anchoring_schema(&snapshot);
+        assert_eq!(schema.proposal_conflicts().len(), 2);
+        let conflict = schema.anchoring_halt().unwrap();
+        assert_eq!(conflict.proposal_txid, proposal.id());
+        assert_eq!(conflict.conflicting_txid, conflicting_tx.id());
+        assert_eq!(conflict.conflicting_transaction, conflicting_tx);
+        assert_eq!(
+            conflict.author,
+            validators[1].service_keypair().public_key()
+        );
+        assert!(schema.is_anchoring_halted(Height(anchoring_interval), 0));
+    }
+
+    // The proposal is not finalized even if a quorum of other nodes signed it.
+    let block = testkit.inner.create_block_with_transactions(
+        validators[2..]
+            .iter()
+            .flat_map(|node| testkit.create_signature_tx_for_node(node).unwrap()),
+    );
+    assert_tx_error(&block[0], ErrorMatch::from_fail(&Error::AnchoringHalted));
+    let anchored_height = |testkit: &AnchoringTestKit| {
+        testkit
+            .last_anchoring_tx()
+            .unwrap()
+            .anchoring_payload()
+            .unwrap()
+            .block_height
+    };
+    assert_eq!(anchored_height(&testkit), Height(0));
+
+    // The configuration change approved by the validators resumes the anchoring.
+    let mut new_config = testkit.actual_anchoring_config();
+    new_config.transaction_fee += 1;
+    testkit.prove_key_possession(&mut new_config);
+    testkit.inner.create_block_with_transaction(
+        testkit.create_config_change_tx(
+            ConfigPropose::new(0, testkit.inner.height().next())
+                .service_config(ANCHORING_INSTANCE_ID, new_config),
+        ),
+    );
+    testkit.inner.create_block();
+    assert!(get_anchoring_schema(&testkit.inner.snapshot())
+        .anchoring_halt()
+        .is_none());
+    testkit
+        .inner
+        .create_block_with_transactions(testkit.create_signature_txs().into_iter().flatten());
+    assert_eq!(anchored_height(&testkit), Height(anchoring_interval));
+    // The evidence is kept.
+    assert_eq!(
+        get_anchoring_schema(&testkit.inner.snapshot())
+            .proposal_conflicts()
+            .len(),
+        2
+    );
+}
+
+#[test]
+fn sign_input_funding_race() {
+    let mut testkit = AnchoringTestKit::default();
+    let anchoring_interval = testkit.actual_anchoring_config().anchoring_interval;
+    testkit
+        .inner
+        .create_block_with_transactions(testkit.create_signature_txs().into_iter().flatten());
+    testkit
+        .inner
+        .create_blocks_until(Height(anchoring_interval));
+
+    // The anchoring nodes sign the proposal while the funding transaction is being confirmed.
+    let stale_proposal = testkit.anchoring_transaction_proposal().unwrap().0;
+    let stale_signatures = testkit.create_signature_txs();
+    testkit
+        .inner
+        .create_block_with_transactions(testkit.create_funding_confirmation_txs(150_000).0);
+    let proposal = testkit.anchoring_transaction_proposal().unwrap().0;
+    assert_ne!(proposal.id(), stale_proposal.id());
+    assert_eq!(
+        proposal.0.input[0].previous_output,
+        stale_proposal.0.input[0].previous_output
+    );
+
+    // The signatures for the superseded proposal are not taken as evidence.
+    let block = testkit
+        .inner
+        .create_block_with_transactions(stale_signatures.into_iter().flatten());
+    for tx in block.iter() {
+        assert_tx_error(tx, ErrorMatch::from_fail(&Error::UnexpectedProposalTxId));
+    }
+    {
+        let snapshot = testkit.inner.snapshot();
+        let schema = get_anchoring_schema(&snapshot);
+        assert!(schema.is_proposal_superseded(&stale_proposal.id()));
+        assert_eq!(schema.proposal_conflicts().len(), 0);
+        assert!(schema.anchoring_halt().is_none());
+    }
+
+    // The actual proposal is finalized.
+    testkit
+        .inner
+        .create_block_with_transactions(testkit.create_signature_txs().into_iter().flatten());
+    assert_eq!(testkit.last_anchoring_tx().unwrap().id(), proposal.id());
+    assert!(!get_anchoring_schema(&testkit.inner.snapshot())
+        .is_proposal_superseded(&stale_proposal.id()));
+}
+
+#[test]
+fn sign_input_err_replayed_signature() {
+    let mut testkit = AnchoringTestKit::default();
+    let us = testkit.inner.us();
+    let tx = testkit.create_signature_tx_for_node(&us).unwrap()[0]
+        .payload()
+        .parse::<SignInput>()
+        .unwrap();
+    // Replay the signature for another anchoring height and epoch.
+    let replayed_txs = vec![
+        SignInput {
+            anchoring_height: Height(10),
+            ..tx.clone()
+        },
+        SignInput { epoch: 1, ..tx },
+    ]
+    .into_iter()
+    .map(|tx| us.service_keypair().sign_input(ANCHORING_INSTANCE_ID, tx));
+    // Commit these transactions and check status.
+    let block = testkit.inner.create_block_with_transactions(replayed_txs);
+    assert_tx_error(&block[0], ErrorMatch::from_fail(&Error::ReplayedSignature));
+    assert_tx_error(&block[1], ErrorMatch::from_fail(&Error::ReplayedSignature));
+}
+
+#[test]
+fn sign_input_err_stale_signature() {
+    let mut testkit = AnchoringTestKit::with_config(4, |config| {
+        config.anchoring_interval = 5;
+        config.max_signature_age = 10;
+    });
+    let proposal_height = |testkit: &AnchoringTestKit| {
+        testkit
+            .anchoring_transaction_proposal()
+            .unwrap()
+            .0
+            .anchoring_payload()
+            .unwrap()
+            .block_height
+    };
+    testkit
+        .inner
+        .create_block_with_transactions(testkit.create_funding_confirmation_txs(700_000).0);
+    assert_eq!(proposal_height(&testkit), Height(0));
+
+    // Submit the signatures after the proposal becomes too old.
+    let signatures = testkit
+        .create_signature_txs()
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>();
+    testkit.inner.create_blocks_until(Height(11));
+    let block = testkit.inner.create_block_with_transactions(signatures);
+    assert_tx_error(&block[0], ErrorMatch::from_fail(&Error::StaleSignature));
+    // The rejection points to the offending field of the transaction.
+    let rejection = Rejection::from_execution_error(block[0].status().unwrap_err()).unwrap();
+    assert_eq!(rejection.error(), Some(Error::StaleSignature));
+    assert_eq!(rejection.field.as_deref(), Some("anchoring_height"));
+    // The stale anchoring height should be skipped.
+    assert_eq!(proposal_height(&testkit), Height(5));
+
+    testkit
+        .inner
+        .create_block_with_transactions(testkit.create_signature_txs().into_iter().flatten());
+    let payload = testkit
+        .last_anchoring_tx()
+        .unwrap()
+        .anchoring_payload()
+        .unwrap();
+    assert_eq!(payload.block_height, Height(5));
+}
+
+#[test]
+fn sign_input_transition_with_old_anchored_height() {
+    let mut testkit = AnchoringTestKit::with_config(4, |config| {
+        config.anchoring_interval = 5;
+        config.max_signature_age = 10;
+    });
+    testkit
+        .inner
+        .create_block_with_transactions(testkit.create_funding_confirmation_txs(700_000).0);
+    testkit
+        .inner
+        .create_block_with_transactions(testkit.create_signature_txs().into_iter().flatten());
+    // The latest anchored height becomes older than the maximal signature age.
+    testkit.inner.create_blocks_until(Height(20));
+
+    let mut new_cfg = testkit.actual_anchoring_config();
+    new_cfg.anchoring_keys[0].bitcoin_key = testkit.gen_bitcoin_key();
+    testkit.prove_key_possession(&mut new_cfg);
+    testkit.inner.create_block_with_transaction(
+        testkit.create_config_change_tx(
+            ConfigPropose::new(0, testkit.inner.height().next())
+                .service_config(ANCHORING_INSTANCE_ID, new_cfg.clone()),
+        ),
+    );
+    // The transition transaction re-anchors the latest anchored height.
+    let transition_tx = testkit.anchoring_transaction_proposal().unwrap().0;
+    assert_eq!(
+        transition_tx.anchoring_payload().unwrap().block_height,
+        Height(0)
+    );
+
+    // Signatures of the transition transaction are not stale.
+    let block = testkit
+        .inner
+        .create_block_with_transactions(testkit.create_signature_txs().into_iter().flatten());
+    assert!(block.iter().all(|tx| tx.status().is_ok()));
+    assert_eq!(testkit.last_anchoring_tx().unwrap(), transition_tx);
+    let snapshot = testkit.inner.snapshot();
+    assert_eq!(get_anchoring_schema(&snapshot).actual_config(), new_cfg);
+}
+
+#[test]
+fn expired_proposal_signatures_compaction() {
+    let mut testkit = AnchoringTestKit::default();
+    let us = testkit.inner.us();
+    let input_signatures = |testkit: &AnchoringTestKit, txid| {
+        get_anchoring_schema(&testkit.inner.snapshot())
+            .input_signatures(&TxInputId::new(txid, 0))
+            .0
+            .len()
+    };
+
+    // Sign the proposal by one of the anchoring nodes.
+    let proposal = testkit.anchoring_transaction_proposal().unwrap().0;
+    testkit
+        .inner
+        .create_block_with_transactions(testkit.create_signature_tx_for_node(&us).unwrap());
+    assert_eq!(input_signatures(&testkit, proposal.id()), 1);
+
+    // Supersede the proposal by another funding transaction.
+    testkit
+        .inner
+        .create_block_with_transactions(testkit.create_funding_confirmation_txs(2000).0);
+    let new_proposal = testkit.anchoring_transaction_proposal().unwrap().0;
+    assert_ne!(proposal.id(), new_proposal.id());
+    assert_eq!(input_signatures(&testkit, proposal.id()), 0);
+
+    // Finalize the new proposal.
+    testkit
+        .inner
+        .create_block_with_transactions(testkit.create_signature_txs().into_iter().flatten());
+    assert_eq!(testkit.last_anchoring_tx().unwrap().id(), new_proposal.id());
+    assert_eq!(input_signatures(&testkit, new_proposal.id()), 0);
+}
+
 #[test]
 fn sign_input_err_input_verification_failed() {
     let mut testkit = AnchoringTestKit::default();
@@ -623,3 +2272,74 @@ fn sign_input_err_input_verification_failed() {
 }
 
 // TODO Implement tests for anchoring recovery [ECR-3581]
+
+#[test]
+fn auxiliary_state_root() {
+    let mut anchoring_testkit = AnchoringTestKit::with_config(4, |config| {
+        config.anchoring_interval = 5;
+        config.auxiliary_services = vec![ANCHORING_INSTANCE_NAME.to_owned()];
+    });
+    // The root of the genesis block state is recorded in the following block.
+    assert!(anchoring_testkit.anchoring_transaction_proposal().is_none());
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit.create_funding_confirmation_txs(700_000).0,
+    );
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit
+            .create_signature_txs()
+            .into_iter()
+            .flatten(),
+    );
+
+    let snapshot = anchoring_testkit.inner.snapshot();
+    let root = get_anchoring_schema(&snapshot).auxiliary_root(Height(0));
+    assert!(root.is_some());
+
+    let payload = anchoring_testkit
+        .last_anchoring_tx()
+        .unwrap()
+        .anchoring_payload()
+        .unwrap();
+    assert_eq!(payload.block_height, Height(0));
+    assert_eq!(payload.auxiliary_root, root);
+}
+
+#[test]
+fn consensus_config_commitment() {
+    let mut anchoring_testkit = AnchoringTestKit::with_config(4, |config| {
+        config.anchoring_interval = 5;
+        config.commit_consensus_config = true;
+    });
+    // The hash of the consensus configuration is recorded in the following block.
+    assert!(anchoring_testkit.anchoring_transaction_proposal().is_none());
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit.create_funding_confirmation_txs(700_000).0,
+    );
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit
+            .create_signature_txs()
+            .into_iter()
+            .flatten(),
+    );
+
+    let snapshot = anchoring_testkit.inner.snapshot();
+    let schema = get_anchoring_schema(&snapshot);
+    let config_hash = schema.consensus_config_hash(Height(0));
+    assert_eq!(
+        config_hash,
+        Some(snapshot.for_core().consensus_config().object_hash())
+    );
+
+    let payload = anchoring_testkit
+        .last_anchoring_tx()
+        .unwrap()
+        .anchoring_payload()
+        .unwrap();
+    assert_eq!(payload.block_height, Height(0));
+    assert_eq!(payload.auxiliary_root, None);
+    assert_eq!(payload.consensus_config_hash, config_hash);
+    // The committed hash does not prevent the anchoring chain from being replayed.
+    assert!(schema
+        .replay_anchoring_chain(snapshot.for_core())
+        .is_consistent());
+}
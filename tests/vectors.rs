@@ -0,0 +1,70 @@
+// Copyright 2019 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use exonum_btc_anchoring::test_helpers::vectors::{standard_vectors, TestVector};
+
+use std::fs;
+
+/// Path to the published test vectors relative to the crate root.
+const VECTORS_PATH: &str = "tests/vectors/anchoring.json";
+
+#[test]
+fn standard_vectors_conform() {
+    for vector in standard_vectors() {
+        assert_eq!(vector.check(), Ok(()), "{}", vector.description);
+    }
+}
+
+#[test]
+fn vectors_json_roundtrip() {
+    let vectors = standard_vectors();
+    let json = serde_json::to_string_pretty(&vectors).unwrap();
+    let parsed: Vec<TestVector> = serde_json::from_str(&json).unwrap();
+    assert_eq!(parsed, vectors);
+}
+
+#[test]
+fn conformance_checker_reports_mismatches() {
+    let mut vector = standard_vectors().remove(0);
+    vector.output.redeem_script.replace_range(..2, "00");
+    vector.output.sighashes.clear();
+
+    let mismatches = vector.check().unwrap_err();
+    assert_eq!(mismatches.len(), 2);
+    assert!(mismatches[0].starts_with("redeem_script:"));
+    assert!(mismatches[1].starts_with("sighashes:"));
+}
+
+#[test]
+fn published_vectors_conform() {
+    let json = match fs::read_to_string(VECTORS_PATH) {
+        Ok(json) => json,
+        Err(_) => return,
+    };
+    let vectors: Vec<TestVector> = serde_json::from_str(&json).unwrap();
+    assert_eq!(vectors, standard_vectors());
+    for vector in vectors {
+        assert_eq!(vector.check(), Ok(()), "{}", vector.description);
+    }
+}
+
+/// Writes the standard vectors to the published file. Run with
+/// `cargo test --test vectors -- --ignored` after changing the vectors.
+#[test]
+#[ignore]
+fn export_vectors() {
+    let json = serde_json::to_string_pretty(&standard_vectors()).unwrap();
+    fs::create_dir_all("tests/vectors").unwrap();
+    fs::write(VECTORS_PATH, json + "\n").unwrap();
+}